@@ -0,0 +1,228 @@
+use std::net::SocketAddr;
+use std::sync::{Arc, OnceLock};
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+
+use crate::{
+    config::{AccessLogConfig, AccessLogFormat},
+    enhanced_error::enhanced_logging::{forwarded_client_ip, RequestLogger},
+    error::Result,
+};
+
+/// NCSA Common / Combined access log, written next to (not through) the tracing output
+///
+/// Lines are handed to a background writer so a slow disk never holds up a response.
+pub struct AccessLog {
+    format: AccessLogFormat,
+    include_duration: bool,
+    lines: mpsc::UnboundedSender<String>,
+}
+
+/// Slot the authentication extractor fills with the caller's name for the `authuser` field
+#[derive(Debug, Clone, Default)]
+pub struct AccessLogUser(Arc<OnceLock<String>>);
+
+impl AccessLogUser {
+    pub fn set(&self, name: &str) {
+        let _ = self.0.set(name.to_string());
+    }
+}
+
+/// One request as it appears in the log
+#[derive(Debug)]
+struct AccessLogEntry<'a> {
+    client_ip: &'a str,
+    user: Option<&'a str>,
+    time: DateTime<Utc>,
+    request_line: String,
+    status: u16,
+    bytes: Option<u64>,
+    referer: Option<&'a str>,
+    user_agent: Option<&'a str>,
+    duration_micros: Option<u128>,
+}
+
+impl AccessLog {
+    /// Open the configured destination, if access logging is enabled
+    pub async fn from_config(config: &AccessLogConfig) -> Result<Option<Arc<Self>>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let mut writer: Box<dyn tokio::io::AsyncWrite + Send + Unpin> = match &config.path {
+            Some(path) => {
+                if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                    tokio::fs::create_dir_all(parent).await?;
+                }
+                let file = tokio::fs::OpenOptions::new().create(true).append(true).open(path).await?;
+                tracing::info!("Writing access log to {}", path.display());
+                Box::new(file)
+            }
+            None => Box::new(tokio::io::stdout()),
+        };
+
+        let (lines, mut receiver) = mpsc::unbounded_channel::<String>();
+        tokio::spawn(async move {
+            while let Some(line) = receiver.recv().await {
+                if let Err(e) = writer.write_all(line.as_bytes()).await {
+                    tracing::warn!("Failed to write access log line: {}", e);
+                    continue;
+                }
+                // Keep the file tail current for log shippers without flushing every line under load
+                if receiver.is_empty() {
+                    let _ = writer.flush().await;
+                }
+            }
+        });
+
+        Ok(Some(Arc::new(Self {
+            format: config.format,
+            include_duration: config.include_duration,
+            lines,
+        })))
+    }
+
+    fn write(&self, entry: &AccessLogEntry<'_>) {
+        let _ = self.lines.send(format_line(entry, self.format, self.include_duration));
+    }
+}
+
+/// Log every request that passes through, once the response status is known
+pub async fn middleware(State(log): State<Arc<AccessLog>>, mut request: Request, next: Next) -> Response {
+    let client_ip = forwarded_client_ip(request.headers())
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        })
+        .unwrap_or_else(|| "-".to_string());
+
+    let mut logger = RequestLogger::new(request.method().to_string(), request.uri().path().to_string(), client_ip);
+    logger.user_agent = header_str(request.headers(), header::USER_AGENT).map(str::to_string);
+    let referer = header_str(request.headers(), header::REFERER).map(str::to_string);
+    let request_line = format!(
+        "{} {} {:?}",
+        request.method(),
+        request.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/"),
+        request.version()
+    );
+    let time = Utc::now();
+
+    let user = AccessLogUser::default();
+    request.extensions_mut().insert(user.clone());
+
+    let response = next.run(request).await;
+
+    log.write(&AccessLogEntry {
+        client_ip: &logger.client_ip,
+        user: user.0.get().map(String::as_str),
+        time,
+        request_line,
+        status: response.status().as_u16(),
+        bytes: header_str(response.headers(), header::CONTENT_LENGTH).and_then(|v| v.parse().ok()),
+        referer: referer.as_deref(),
+        user_agent: logger.user_agent.as_deref(),
+        duration_micros: Some(logger.start_time.elapsed().as_micros()),
+    });
+
+    response
+}
+
+fn header_str(headers: &HeaderMap, name: header::HeaderName) -> Option<&str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+/// Render an entry in Common or Combined Log Format, optionally followed by the duration in microseconds
+fn format_line(entry: &AccessLogEntry<'_>, format: AccessLogFormat, include_duration: bool) -> String {
+    let mut line = format!(
+        "{} - {} [{}] \"{}\" {} {}",
+        field(Some(entry.client_ip)),
+        field(entry.user),
+        entry.time.format("%d/%b/%Y:%H:%M:%S %z"),
+        escape(&entry.request_line),
+        entry.status,
+        entry.bytes.map_or_else(|| "-".to_string(), |b| b.to_string()),
+    );
+
+    if format == AccessLogFormat::Combined {
+        line.push_str(&format!(
+            " \"{}\" \"{}\"",
+            escape(entry.referer.unwrap_or("-")),
+            escape(entry.user_agent.unwrap_or("-"))
+        ));
+    }
+    if let Some(micros) = entry.duration_micros.filter(|_| include_duration) {
+        line.push_str(&format!(" {}", micros));
+    }
+
+    line.push('\n');
+    line
+}
+
+/// Unquoted fields may not contain spaces or be empty
+fn field(value: Option<&str>) -> String {
+    match value.filter(|v| !v.is_empty()) {
+        Some(v) => escape(v).replace(' ', "%20"),
+        None => "-".to_string(),
+    }
+}
+
+/// Escape quotes, backslashes, and control characters so every request stays on one parseable line
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn entry() -> AccessLogEntry<'static> {
+        AccessLogEntry {
+            client_ip: "10.0.0.7",
+            user: Some("alice"),
+            time: Utc.with_ymd_and_hms(2024, 3, 9, 14, 5, 1).unwrap(),
+            request_line: "GET /v2/team/app/manifests/latest HTTP/1.1".to_string(),
+            status: 200,
+            bytes: Some(1234),
+            referer: None,
+            user_agent: Some("docker/24.0.7 \"quoted\""),
+            duration_micros: Some(850),
+        }
+    }
+
+    #[test]
+    fn test_common_and_combined_lines() {
+        assert_eq!(
+            format_line(&entry(), AccessLogFormat::Common, false),
+            "10.0.0.7 - alice [09/Mar/2024:14:05:01 +0000] \"GET /v2/team/app/manifests/latest HTTP/1.1\" 200 1234\n"
+        );
+        assert_eq!(
+            format_line(&entry(), AccessLogFormat::Combined, true),
+            "10.0.0.7 - alice [09/Mar/2024:14:05:01 +0000] \"GET /v2/team/app/manifests/latest HTTP/1.1\" 200 1234 \
+             \"-\" \"docker/24.0.7 \\\"quoted\\\"\" 850\n"
+        );
+
+        let anonymous = AccessLogEntry { user: None, bytes: None, ..entry() };
+        assert!(format_line(&anonymous, AccessLogFormat::Common, false).starts_with("10.0.0.7 - - ["));
+        assert!(format_line(&anonymous, AccessLogFormat::Common, false).ends_with("\" 200 -\n"));
+    }
+}
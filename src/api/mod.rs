@@ -1,2 +0,0 @@
-// API module - placeholder for now
-pub mod v2;
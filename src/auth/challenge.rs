@@ -0,0 +1,100 @@
+use axum::http::{header, HeaderMap, Method};
+
+use crate::config::AuthChallengeConfig;
+
+/// `WWW-Authenticate` challenges for registry 401s
+///
+/// Docker clients read the Bearer challenge to find the token endpoint and the scope to
+/// ask for; tools that only speak Basic retry with credentials when Basic is offered.
+
+/// Token endpoint for the Bearer challenge: the configured realm, else `/token` on the
+/// host the client called, over the scheme a TLS-terminating proxy reports
+pub fn realm(config: &AuthChallengeConfig, headers: &HeaderMap) -> Option<String> {
+    if let Some(realm) = &config.realm {
+        return Some(realm.clone());
+    }
+
+    let first = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(str::trim)
+            .filter(|v| !v.is_empty())
+    };
+    let host = first("x-forwarded-host").or_else(|| first(header::HOST.as_str()))?;
+    let scheme = first("x-forwarded-proto").filter(|p| p.eq_ignore_ascii_case("https")).map_or("http", |_| "https");
+    Some(format!("{}://{}/token", scheme, host))
+}
+
+/// Token scope a request to `path` needs, e.g. `repository:team/app:pull`
+pub fn scope(method: &Method, path: &str) -> Option<String> {
+    let rest = path.strip_prefix("/v2/")?;
+    if rest.trim_end_matches('/') == "_catalog" {
+        return Some("registry:catalog:*".to_string());
+    }
+
+    // Repository names may contain these words too, so the last marker ends the name
+    let end = ["/blobs/", "/manifests/", "/tags/", "/referrers/"]
+        .iter()
+        .filter_map(|marker| rest.rfind(marker))
+        .max()?;
+    let actions = match *method {
+        Method::GET | Method::HEAD => "pull",
+        Method::DELETE => "delete",
+        _ => "pull,push",
+    };
+    Some(format!("repository:{}:{}", &rest[..end], actions))
+}
+
+/// Challenge header values, one per enabled scheme
+pub fn challenges(config: &AuthChallengeConfig, realm: Option<&str>, scope: Option<&str>) -> Vec<String> {
+    let mut challenges = Vec::new();
+    if config.bearer {
+        if let Some(realm) = realm {
+            let mut bearer = format!("Bearer realm=\"{}\",service=\"{}\"", realm, config.service);
+            if let Some(scope) = scope {
+                bearer.push_str(&format!(",scope=\"{}\"", scope));
+            }
+            challenges.push(bearer);
+        }
+    }
+    if config.basic {
+        challenges.push(format!("Basic realm=\"{}\"", config.service));
+    }
+    challenges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry_challenges() {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, "registry.example.com:5000".parse().unwrap());
+        let mut config = AuthChallengeConfig::default();
+
+        assert_eq!(realm(&config, &headers).as_deref(), Some("http://registry.example.com:5000/token"));
+        headers.insert("x-forwarded-proto", "https".parse().unwrap());
+        headers.insert("x-forwarded-host", "registry.example.com".parse().unwrap());
+        let token_url = realm(&config, &headers).unwrap();
+        assert_eq!(token_url, "https://registry.example.com/token");
+
+        let push = scope(&Method::PUT, "/v2/team/blobs/app/manifests/latest");
+        assert_eq!(push.as_deref(), Some("repository:team/blobs/app:pull,push"));
+        assert_eq!(scope(&Method::HEAD, "/v2/app/blobs/sha256:abc").as_deref(), Some("repository:app:pull"));
+        assert_eq!(scope(&Method::GET, "/v2/_catalog").as_deref(), Some("registry:catalog:*"));
+        assert_eq!(scope(&Method::GET, "/v2/"), None);
+
+        assert_eq!(
+            challenges(&config, Some(&token_url), push.as_deref()),
+            vec![r#"Bearer realm="https://registry.example.com/token",service="ghostdock",scope="repository:team/blobs/app:pull,push""#]
+        );
+
+        config.basic = true;
+        assert_eq!(challenges(&config, Some(&token_url), None).len(), 2);
+        config.bearer = false;
+        assert_eq!(challenges(&config, Some(&token_url), None), vec![r#"Basic realm="ghostdock""#]);
+    }
+}
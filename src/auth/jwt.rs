@@ -1,17 +1,63 @@
-use crate::error::Result;
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
-use serde::{Deserialize, Serialize};
-use std::time::{SystemTime, UNIX_EPOCH};
+use crate::{
+    config::{AuthConfig, JwtAlgorithm, TrustedIssuerConfig},
+    error::{Error, Result},
+};
+use jsonwebtoken::{
+    decode, decode_header, encode,
+    jwk::{AlgorithmParameters, JwkSet, KeyAlgorithm},
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
+};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+
+/// `iss` of every token GhostDock issues
+pub const ISSUER: &str = "ghostdock";
+
+/// Unknown `kid`s refetch an issuer's JWKS at most this often
+const MIN_JWKS_REFRESH: Duration = Duration::from_secs(60);
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,        // User ID
+    #[serde(default)]
     pub name: String,       // User name
+    #[serde(default)]
     pub email: String,      // User email
     pub exp: usize,         // Expiration time
+    #[serde(default)]
     pub iat: usize,         // Issued at
     pub iss: String,        // Issuer
+    #[serde(default, deserialize_with = "scope_list")]
     pub scope: Vec<String>, // Permissions/scopes
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub access: Vec<AccessEntry>, // Docker token resource grants
+}
+
+/// One entry of the Docker token `access` claim
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AccessEntry {
+    #[serde(rename = "type")]
+    pub resource_type: String,
+    pub name: String,
+    pub actions: Vec<String>,
+}
+
+/// Accept scopes as a list or as the space-separated string OAuth providers issue
+fn scope_list<'de, D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Scopes {
+        List(Vec<String>),
+        Spaced(String),
+    }
+
+    Ok(match Scopes::deserialize(deserializer)? {
+        Scopes::List(scopes) => scopes,
+        Scopes::Spaced(scopes) => scopes.split_whitespace().map(str::to_string).collect(),
+    })
 }
 
 #[derive(Clone)]
@@ -19,18 +65,177 @@ pub struct JwtConfig {
     pub secret: String,
     pub issuer: String,
     pub expiration_hours: u64,
+    /// Algorithm of the tokens we issue; our own tokens must arrive with it
+    pub algorithm: Algorithm,
+    pub key_id: Option<String>,
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    trusted_issuers: Arc<Vec<TrustedIssuer>>,
 }
 
 impl JwtConfig {
     pub fn new(secret: String) -> Self {
         Self {
+            encoding_key: EncodingKey::from_secret(secret.as_ref()),
+            decoding_key: DecodingKey::from_secret(secret.as_ref()),
             secret,
-            issuer: "ghostdock".to_string(),
+            issuer: ISSUER.to_string(),
             expiration_hours: 24,
+            algorithm: Algorithm::HS256,
+            key_id: None,
+            trusted_issuers: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Keys and trusted issuers from `[auth]`, reading the RS256 key pair if configured
+    pub fn from_config(auth: &AuthConfig) -> Result<Self> {
+        let mut config = Self::new(auth.jwt_secret.clone());
+        config.expiration_hours = (auth.jwt_expiration / 3600).max(1);
+        config.key_id = auth.jwt.key_id.clone();
+
+        if auth.jwt.algorithm == JwtAlgorithm::Rs256 {
+            let read = |path: &Option<std::path::PathBuf>, name: &str| {
+                let path = path
+                    .as_ref()
+                    .ok_or_else(|| Error::validation(format!("auth.jwt.{} is required for rs256", name)))?;
+                std::fs::read(path).map_err(|e| Error::validation(format!("Failed to read {}: {}", path.display(), e)))
+            };
+            config.algorithm = Algorithm::RS256;
+            config.encoding_key = EncodingKey::from_rsa_pem(&read(&auth.jwt.private_key_path, "private_key_path")?)
+                .map_err(|e| Error::validation(format!("Invalid RSA private key: {}", e)))?;
+            config.decoding_key = DecodingKey::from_rsa_pem(&read(&auth.jwt.public_key_path, "public_key_path")?)
+                .map_err(|e| Error::validation(format!("Invalid RSA public key: {}", e)))?;
+        }
+
+        config.trusted_issuers = Arc::new(auth.jwt.trusted_issuers.iter().map(TrustedIssuer::new).collect());
+        Ok(config)
+    }
+
+    /// Sign claims with the configured algorithm and key
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let mut header = Header::new(self.algorithm);
+        header.kid = self.key_id.clone();
+
+        encode(&header, claims, &self.encoding_key)
+            .map_err(|e| Error::from(anyhow::anyhow!("JWT encoding failed: {}", e)))
+    }
+}
+
+/// External token issuer and its cached signing keys
+struct TrustedIssuer {
+    issuer: String,
+    audience: String,
+    scope_mapping: HashMap<String, Vec<String>>,
+    jwks: JwksCache,
+}
+
+impl TrustedIssuer {
+    fn new(config: &TrustedIssuerConfig) -> Self {
+        Self {
+            issuer: config.issuer.clone(),
+            audience: config.audience.clone(),
+            scope_mapping: config.scope_mapping.clone(),
+            jwks: JwksCache::new(config.jwks_url.clone(), Duration::from_secs(config.jwks_cache_secs)),
+        }
+    }
+
+    /// GhostDock scopes for an external token's `scope` claim; unmapped values grant nothing
+    fn map_scopes(&self, external: &[String]) -> Vec<String> {
+        let mut scopes: Vec<String> = Vec::new();
+        for granted in external.iter().filter_map(|scope| self.scope_mapping.get(scope)).flatten() {
+            if !scopes.contains(granted) {
+                scopes.push(granted.clone());
+            }
+        }
+        scopes
+    }
+}
+
+/// Signing keys published by an identity provider, refetched when stale or when a token names an unknown `kid`
+pub struct JwksCache {
+    url: String,
+    ttl: Duration,
+    client: reqwest::Client,
+    state: RwLock<JwksState>,
+}
+
+#[derive(Default)]
+struct JwksState {
+    keys: HashMap<String, (DecodingKey, Algorithm)>,
+    fetched_at: Option<Instant>,
+}
+
+impl JwksCache {
+    pub fn new(url: String, ttl: Duration) -> Self {
+        Self {
+            url,
+            ttl,
+            client: reqwest::Client::new(),
+            state: RwLock::new(JwksState::default()),
+        }
+    }
+
+    /// Key and algorithm for a `kid`
+    async fn key(&self, kid: &str) -> Result<(DecodingKey, Algorithm)> {
+        {
+            let state = self.state.read().await;
+            let fresh = matches!(state.fetched_at, Some(at) if at.elapsed() < self.ttl);
+            if let (true, Some(key)) = (fresh, state.keys.get(kid)) {
+                return Ok(key.clone());
+            }
         }
+
+        let mut state = self.state.write().await;
+        // Bound refetches so tokens with made-up kids cannot hammer the provider
+        let may_refresh = !matches!(state.fetched_at, Some(at) if at.elapsed() < MIN_JWKS_REFRESH);
+        if may_refresh {
+            match self.fetch().await {
+                Ok(keys) => {
+                    state.keys = keys;
+                    state.fetched_at = Some(Instant::now());
+                }
+                // Keep serving the keys we have; the provider may be briefly unreachable
+                Err(e) => tracing::warn!("Failed to fetch JWKS from {}: {}", self.url, e),
+            }
+        }
+
+        state
+            .keys
+            .get(kid)
+            .cloned()
+            .ok_or_else(|| Error::authentication(format!("Unknown signing key '{}'", kid)))
+    }
+
+    async fn fetch(&self) -> Result<HashMap<String, (DecodingKey, Algorithm)>> {
+        let response = self.client.get(&self.url).send().await?;
+        if !response.status().is_success() {
+            return Err(Error::authentication(format!("JWKS endpoint returned {}", response.status())));
+        }
+        Ok(jwks_keys(&response.json::<JwkSet>().await?))
     }
 }
 
+/// RSA keys of a JWKS by `kid`; keys of other types or without a `kid` are ignored
+fn jwks_keys(set: &JwkSet) -> HashMap<String, (DecodingKey, Algorithm)> {
+    set.keys
+        .iter()
+        .filter_map(|jwk| {
+            let kid = jwk.common.key_id.clone()?;
+            if !matches!(jwk.algorithm, AlgorithmParameters::RSA(_)) {
+                return None;
+            }
+            let algorithm = match jwk.common.key_algorithm {
+                None | Some(KeyAlgorithm::RS256) => Algorithm::RS256,
+                Some(KeyAlgorithm::RS384) => Algorithm::RS384,
+                Some(KeyAlgorithm::RS512) => Algorithm::RS512,
+                Some(_) => return None,
+            };
+            let key = DecodingKey::from_jwk(jwk).ok()?;
+            Some((kid, (key, algorithm)))
+        })
+        .collect()
+}
+
 /// Generate a new JWT token for a user
 pub fn generate_token(
     user_id: &str,
@@ -38,6 +243,18 @@ pub fn generate_token(
     email: &str,
     scopes: Vec<String>,
     config: &JwtConfig,
+) -> Result<String> {
+    generate_token_with_access(user_id, name, email, scopes, vec![], config)
+}
+
+/// Generate a JWT carrying Docker-style resource grants in the `access` claim
+pub fn generate_token_with_access(
+    user_id: &str,
+    name: &str,
+    email: &str,
+    scopes: Vec<String>,
+    access: Vec<AccessEntry>,
+    config: &JwtConfig,
 ) -> Result<String> {
     let now = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -54,27 +271,74 @@ pub fn generate_token(
         iat: now,
         iss: config.issuer.clone(),
         scope: scopes,
+        access,
     };
 
-    let header = Header::new(Algorithm::HS256);
-    let encoding_key = EncodingKey::from_secret(config.secret.as_ref());
-
-    encode(&header, &claims, &encoding_key)
-        .map_err(|e| crate::error::Error::from(anyhow::anyhow!("JWT encoding failed: {}", e)))
+    config.sign(&claims)
 }
 
 /// Validate a JWT token and extract claims
-pub fn validate_token(token: &str, config: &JwtConfig) -> Result<Claims> {
-    let decoding_key = DecodingKey::from_secret(config.secret.as_ref());
-    let mut validation = Validation::new(Algorithm::HS256);
-    validation.set_issuer(&[&config.issuer]);
+///
+/// Our own tokens must carry our issuer and the configured algorithm. Tokens from a
+/// trusted issuer are checked against the JWKS key named by their `kid`, with the
+/// algorithm that key is published for; the header's `alg` must agree. They must name
+/// the configured audience, and their scopes are replaced by the issuer's mapping.
+pub async fn validate_token(token: &str, config: &JwtConfig) -> Result<Claims> {
+    let header = decode_header(token).map_err(invalid_token)?;
+
+    let (key, algorithm, issuer, trusted) = match unverified_issuer(token) {
+        Some(iss) if iss != config.issuer => {
+            let trusted = config
+                .trusted_issuers
+                .iter()
+                .find(|t| t.issuer == iss)
+                .ok_or_else(|| Error::authentication(format!("Tokens from '{}' are not trusted", iss)))?;
+            let kid = header
+                .kid
+                .as_deref()
+                .ok_or_else(|| Error::authentication("External token has no key id"))?;
+            let (key, algorithm) = trusted.jwks.key(kid).await?;
+            (key, algorithm, trusted.issuer.clone(), Some(trusted))
+        }
+        _ => (config.decoding_key.clone(), config.algorithm, config.issuer.clone(), None),
+    };
+
+    if header.alg != algorithm {
+        return Err(Error::authentication(format!("Token algorithm {:?} is not accepted", header.alg)));
+    }
+
+    let mut validation = Validation::new(algorithm);
+    validation.set_issuer(&[&issuer]);
+    match trusted {
+        Some(trusted) => validation.set_audience(&[&trusted.audience]),
+        None => validation.validate_aud = false,
+    }
 
-    let token_data = decode::<Claims>(token, &decoding_key, &validation)
-        .map_err(|e| {
-            crate::error::Error::from(anyhow::anyhow!("JWT validation failed: {}", e))
-        })?;
+    let mut claims = decode::<Claims>(token, &key, &validation).map_err(invalid_token)?.claims;
+    if let Some(trusted) = trusted {
+        claims.scope = trusted.map_scopes(&claims.scope);
+        // Docker access grants only come from our own token endpoint
+        claims.access.clear();
+    }
+    if claims.name.is_empty() {
+        claims.name = claims.sub.clone();
+    }
 
-    Ok(token_data.claims)
+    Ok(claims)
+}
+
+fn invalid_token(e: jsonwebtoken::errors::Error) -> Error {
+    Error::from(anyhow::anyhow!("JWT validation failed: {}", e))
+}
+
+/// `iss` claim read without verifying the signature, only to pick the verification key
+fn unverified_issuer(token: &str) -> Option<String> {
+    use base64::Engine;
+
+    let payload = token.split('.').nth(1)?;
+    let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(payload).ok()?;
+    let claims: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    claims.get("iss")?.as_str().map(str::to_string)
 }
 
 /// Extract token from Authorization header
@@ -92,6 +356,53 @@ pub fn has_scope(claims: &Claims, required_scope: &str) -> bool {
     claims.scope.contains(&"admin".to_string()) // Admin has all permissions
 }
 
+/// Parse a single `type:name:action1,action2` scope.
+/// Names may contain colons (registry host ports), so the actions follow the last one.
+pub fn parse_scope(scope: &str) -> Option<AccessEntry> {
+    let (resource_type, rest) = scope.split_once(':')?;
+    let (name, actions) = rest.rsplit_once(':')?;
+
+    let actions: Vec<String> = actions
+        .split(',')
+        .filter(|a| !a.is_empty())
+        .map(str::to_string)
+        .collect();
+
+    if resource_type.is_empty() || name.is_empty() || actions.is_empty() {
+        return None;
+    }
+
+    Some(AccessEntry {
+        resource_type: resource_type.to_string(),
+        name: name.to_string(),
+        actions,
+    })
+}
+
+/// Parse repeated `scope` parameters (each may hold several space-separated scopes),
+/// merging actions requested for the same resource
+pub fn parse_scopes<'a>(params: impl IntoIterator<Item = &'a str>) -> Vec<AccessEntry> {
+    let mut entries: Vec<AccessEntry> = Vec::new();
+
+    for entry in params.into_iter().flat_map(str::split_whitespace).filter_map(parse_scope) {
+        match entries
+            .iter_mut()
+            .find(|e| e.resource_type == entry.resource_type && e.name == entry.name)
+        {
+            Some(existing) => {
+                for action in entry.actions {
+                    if !existing.actions.contains(&action) {
+                        existing.actions.push(action);
+                    }
+                }
+            }
+            None => entries.push(entry),
+        }
+    }
+
+    entries
+}
+
 /// Generate scopes based on user role
 pub fn generate_scopes_for_role(role: &str) -> Vec<String> {
     match role {
@@ -111,6 +422,10 @@ pub fn generate_scopes_for_role(role: &str) -> Vec<String> {
         "reader" => vec![
             "registry:read".to_string(),
         ],
+        // Local accounts: repository access comes from ownership, grants and visibility
+        "user" => vec![
+            "stack:manage".to_string(),
+        ],
         _ => vec!["registry:read".to_string()],
     }
 }
@@ -119,8 +434,8 @@ pub fn generate_scopes_for_role(role: &str) -> Vec<String> {
 mod tests {
     use super::*;
 
-    #[test]
-    fn test_jwt_flow() {
+    #[tokio::test]
+    async fn test_jwt_flow() {
         let config = JwtConfig::new("test-secret".to_string());
         let scopes = generate_scopes_for_role("developer");
         
@@ -130,6 +445,7 @@ mod tests {
         
         // Validate token
         let claims = validate_token(&token, &config)
+            .await
             .expect("Failed to validate token");
         
         assert_eq!(claims.sub, "user123");
@@ -138,4 +454,92 @@ mod tests {
         assert!(has_scope(&claims, "registry:write"));
         assert!(!has_scope(&claims, "admin"));
     }
+
+    #[test]
+    fn test_parse_scope_grammar() {
+        let entry = parse_scope("repository:localhost:5000/team/app:pull,push").unwrap();
+        assert_eq!(entry.resource_type, "repository");
+        assert_eq!(entry.name, "localhost:5000/team/app");
+        assert_eq!(entry.actions, vec!["pull", "push"]);
+
+        assert!(parse_scope("repository:app").is_none());
+        assert!(parse_scope("repository:app:").is_none());
+    }
+
+    #[test]
+    fn test_parse_multiple_scopes() {
+        let entries = parse_scopes([
+            "repository:a:pull",
+            "repository:b:pull repository:a:push",
+        ]);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].name, "a");
+        assert_eq!(entries[0].actions, vec!["pull", "push"]);
+        assert_eq!(entries[1].name, "b");
+    }
+
+    #[tokio::test]
+    async fn test_untrusted_issuer_rejected_even_with_our_secret() {
+        let config = JwtConfig::new("test-secret".to_string());
+        let claims = serde_json::json!({
+            "sub": "intruder",
+            "exp": 4_102_444_800u64,
+            "iss": "https://idp.example.com/",
+            "scope": "admin",
+        });
+        let token = config.sign(&claims).unwrap();
+
+        assert_eq!(unverified_issuer(&token).as_deref(), Some("https://idp.example.com/"));
+        assert!(validate_token(&token, &config).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_own_tokens_need_the_configured_algorithm() {
+        let config = JwtConfig::new("test-secret".to_string());
+        let claims = serde_json::json!({ "sub": "u", "exp": 4_102_444_800u64, "iss": ISSUER });
+        let token = encode(
+            &Header::new(Algorithm::HS512),
+            &claims,
+            &EncodingKey::from_secret(b"test-secret"),
+        )
+        .unwrap();
+
+        assert!(validate_token(&token, &config).await.is_err());
+    }
+
+    #[test]
+    fn test_external_scopes_are_mapped() {
+        let issuer = TrustedIssuer::new(&TrustedIssuerConfig {
+            issuer: "https://idp.example.com/".to_string(),
+            jwks_url: "https://idp.example.com/jwks.json".to_string(),
+            audience: "ghostdock".to_string(),
+            scope_mapping: HashMap::from([
+                ("ghostdock-admins".to_string(), vec!["admin".to_string()]),
+                ("devs".to_string(), vec!["registry:read".to_string(), "registry:write".to_string()]),
+                ("readers".to_string(), vec!["registry:read".to_string()]),
+            ]),
+            jwks_cache_secs: 3600,
+        });
+
+        let external = |scopes: &[&str]| scopes.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(issuer.map_scopes(&external(&["devs", "readers"])), vec!["registry:read", "registry:write"]);
+        // A provider-side "admin" scope is not GhostDock's admin
+        assert!(issuer.map_scopes(&external(&["admin", "openid"])).is_empty());
+    }
+
+    #[test]
+    fn test_scope_claim_as_string_or_list() {
+        let claims: Claims = serde_json::from_value(serde_json::json!({
+            "sub": "u", "exp": 1, "iss": "idp", "scope": "registry:read registry:write",
+        }))
+        .unwrap();
+        assert_eq!(claims.scope, vec!["registry:read", "registry:write"]);
+        assert!(claims.name.is_empty());
+
+        let claims: Claims = serde_json::from_value(serde_json::json!({
+            "sub": "u", "exp": 1, "iss": "idp", "scope": ["admin"],
+        }))
+        .unwrap();
+        assert_eq!(claims.scope, vec!["admin"]);
+    }
 }
@@ -0,0 +1,129 @@
+use crate::{config::LoginLockoutConfig, performance::RateLimiter};
+use dashmap::DashMap;
+use std::time::{Duration, Instant};
+
+/// Temporarily locks usernames and client IPs after repeated failed logins.
+/// Failures are counted in sliding windows by a dedicated rate limiter.
+pub struct LoginLockout {
+    config: LoginLockoutConfig,
+    failures: RateLimiter,
+    locked_until: DashMap<String, Instant>,
+}
+
+impl LoginLockout {
+    pub fn new(config: LoginLockoutConfig) -> Self {
+        Self {
+            config,
+            failures: RateLimiter::default(),
+            locked_until: DashMap::new(),
+        }
+    }
+
+    pub fn user_key(username: &str) -> String {
+        format!("user:{}", username.to_lowercase())
+    }
+
+    pub fn ip_key(client_ip: &str) -> String {
+        format!("ip:{}", client_ip)
+    }
+
+    /// How long a locked lockout lasts, for `Retry-After`
+    pub fn lockout_secs(&self) -> u64 {
+        self.config.lockout_secs
+    }
+
+    /// Seconds left on the longest active lock among `keys`, if any
+    pub fn locked_for(&self, keys: &[String]) -> Option<u64> {
+        let now = Instant::now();
+        keys.iter()
+            .filter_map(|key| {
+                let until = *self.locked_until.get(key)?;
+                if until > now {
+                    // Round up so clients never retry a moment too early
+                    Some((until - now).as_millis().div_ceil(1000) as u64)
+                } else {
+                    self.locked_until.remove(key);
+                    None
+                }
+            })
+            .max()
+    }
+
+    /// Count a failed login against each key; returns the keys that just became locked
+    pub fn record_failure(&self, keys: &[String]) -> Vec<String> {
+        if self.config.max_failures == 0 {
+            return Vec::new();
+        }
+
+        let window = Duration::from_secs(self.config.window_secs);
+        let lockout = Duration::from_secs(self.config.lockout_secs);
+        let mut locked = Vec::new();
+        for key in keys {
+            if self.failures.record(key, window) >= self.config.max_failures {
+                self.failures.reset(key);
+                self.locked_until.insert(key.clone(), Instant::now() + lockout);
+                locked.push(key.clone());
+            }
+        }
+        locked
+    }
+
+    /// A successful login clears the failure count
+    pub fn record_success(&self, keys: &[String]) {
+        for key in keys {
+            self.failures.reset(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lockout(max_failures: usize) -> LoginLockout {
+        LoginLockout::new(LoginLockoutConfig {
+            max_failures,
+            window_secs: 60,
+            lockout_secs: 30,
+        })
+    }
+
+    #[test]
+    fn test_locks_after_threshold() {
+        let lockout = lockout(3);
+        let keys = vec![LoginLockout::user_key("Alice"), LoginLockout::ip_key("10.0.0.1")];
+
+        assert!(lockout.record_failure(&keys).is_empty());
+        assert!(lockout.record_failure(&keys).is_empty());
+        assert!(lockout.locked_for(&keys).is_none());
+
+        assert_eq!(lockout.record_failure(&keys), keys);
+        let retry_after = lockout.locked_for(&keys).unwrap();
+        assert!(retry_after > 0 && retry_after <= 30);
+
+        // Usernames are matched case-insensitively
+        assert!(lockout.locked_for(&[LoginLockout::user_key("alice")]).is_some());
+        assert!(lockout.locked_for(&[LoginLockout::ip_key("10.0.0.2")]).is_none());
+    }
+
+    #[test]
+    fn test_success_resets_failures() {
+        let lockout = lockout(2);
+        let keys = vec![LoginLockout::user_key("bob")];
+
+        assert!(lockout.record_failure(&keys).is_empty());
+        lockout.record_success(&keys);
+        assert!(lockout.record_failure(&keys).is_empty());
+        assert_eq!(lockout.record_failure(&keys), keys);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let lockout = lockout(0);
+        let keys = vec![LoginLockout::user_key("carol")];
+        for _ in 0..10 {
+            assert!(lockout.record_failure(&keys).is_empty());
+        }
+        assert!(lockout.locked_for(&keys).is_none());
+    }
+}
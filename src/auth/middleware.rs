@@ -1,13 +1,35 @@
 use axum::{
-    extract::{Request, State, FromRequestParts},
-    http::{HeaderMap, StatusCode, request::Parts},
+    extract::{ConnectInfo, FromRef, Request, State, FromRequestParts},
+    http::{header, HeaderMap, StatusCode, request::Parts},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
+use std::net::SocketAddr;
 use std::sync::Arc;
 use async_trait::async_trait;
 
-use crate::auth::jwt::{validate_token, extract_token_from_header, has_scope, Claims, JwtConfig};
+use crate::auth::jwt::{validate_token, extract_token_from_header, has_scope, AccessEntry, Claims, JwtConfig};
+use crate::{
+    access_log::AccessLogUser,
+    database::queries::in_personal_namespace,
+    error::{Error, Result as AppResult},
+    server::AppState,
+};
+
+/// Username prefix identifying robot account credentials in Basic auth
+pub const ROBOT_USERNAME_PREFIX: &str = "robot$";
+
+/// User id prefix for authenticated robot accounts
+pub const ROBOT_ID_PREFIX: &str = "robot:";
+
+/// Username prefix identifying share credentials in Basic auth
+pub const SHARE_USERNAME_PREFIX: &str = "share$";
+
+/// User id prefix for principals authenticated with a share token
+pub const SHARE_ID_PREFIX: &str = "share:";
+
+/// User id of the principal behind tokens issued without credentials
+pub const ANONYMOUS_ID: &str = "anonymous";
 
 /// Authentication state passed to middleware
 #[derive(Clone)]
@@ -25,37 +47,250 @@ pub struct AuthenticatedUser {
     pub scopes: Vec<String>,
 }
 
+impl AuthenticatedUser {
+    /// Whether this principal is a robot account
+    pub fn is_robot(&self) -> bool {
+        self.id.starts_with(ROBOT_ID_PREFIX)
+    }
+
+    /// Whether this principal holds a share token rather than an account
+    pub fn is_share(&self) -> bool {
+        self.id.starts_with(SHARE_ID_PREFIX)
+    }
+
+    /// Whether this principal is an anonymous token holder
+    pub fn is_anonymous(&self) -> bool {
+        self.id == ANONYMOUS_ID
+    }
+
+    /// Whether this principal may perform `action` (pull, push, delete) on `repository`
+    pub fn can_access(&self, repository: &str, action: &str) -> bool {
+        if self.scopes.iter().any(|s| s == "admin") {
+            return true;
+        }
+
+        if self.scopes.contains(&format!("repository:{}:{}", repository, action))
+            || self.scopes.contains(&format!("repository:{}:*", repository))
+        {
+            return true;
+        }
+
+        // Robots and shares only ever get repository-scoped permissions
+        if self.is_robot() || self.is_share() {
+            return false;
+        }
+
+        let registry_scope = match action {
+            "pull" => "registry:read",
+            "push" => "registry:write",
+            "delete" => "registry:delete",
+            _ => return false,
+        };
+        has_scope_list(&self.scopes, registry_scope)
+    }
+}
+
+/// Grant the intersection of requested and permitted actions for a token request
+pub async fn grant_access(state: &AppState, requested: &[AccessEntry], user: &AuthenticatedUser) -> AppResult<Vec<AccessEntry>> {
+    let mut granted = Vec::new();
+    for entry in requested.iter().filter(|entry| entry.resource_type == "repository") {
+        let mut actions = Vec::new();
+        for action in &entry.actions {
+            if may_access(state, Some(user), &entry.name, action).await? {
+                actions.push(action.clone());
+            }
+        }
+
+        if !actions.is_empty() {
+            granted.push(AccessEntry {
+                resource_type: entry.resource_type.clone(),
+                name: entry.name.clone(),
+                actions,
+            });
+        }
+    }
+    Ok(granted)
+}
+
+/// Whether the caller may perform `action` (pull, push, delete) on `repository`.
+///
+/// Scopes carry admin access, ownership and grants. Beyond those, accounts may pull public
+/// repositories and pull from or push to a repository that doesn't exist yet in their
+/// personal namespace, which the push then creates. Anonymous callers may pull public
+/// repositories when `auth.enable_anonymous_read` is set. Robots and shares get nothing more.
+pub async fn may_access(
+    state: &AppState,
+    user: Option<&AuthenticatedUser>,
+    repository: &str,
+    action: &str,
+) -> AppResult<bool> {
+    let user = user.filter(|u| !u.is_anonymous());
+    if user.is_some_and(|u| u.can_access(repository, action)) {
+        return Ok(true);
+    }
+    if user.is_some_and(|u| u.is_robot() || u.is_share()) {
+        return Ok(false);
+    }
+
+    let is_public: Option<bool> = sqlx::query_scalar("SELECT is_public FROM repositories WHERE name = $1")
+        .bind(repository)
+        .fetch_optional(&state.database.pool)
+        .await?;
+    Ok(match (user, is_public) {
+        (Some(user), None) => {
+            matches!(action, "pull" | "push") && in_personal_namespace(&state.config.registry, repository, user)
+        }
+        (Some(_), Some(is_public)) => action == "pull" && is_public,
+        (None, is_public) => action == "pull" && state.config.auth.enable_anonymous_read && is_public.unwrap_or(false),
+    })
+}
+
+fn has_scope_list(scopes: &[String], scope: &str) -> bool {
+    scopes.iter().any(|s| s == scope)
+}
+
+/// Reject the request unless the caller may perform `action` (pull, push, delete) on
+/// `repository`. Anonymous callers, with no credentials or an anonymous token, may only pull
+/// public repositories and only when `auth.enable_anonymous_read` is set; anything else
+/// answers 401 so the client authenticates.
+pub async fn authorize_repository(
+    state: &AppState,
+    user: &Option<AuthenticatedUser>,
+    repository: &str,
+    action: &str,
+) -> AppResult<()> {
+    if may_access(state, user.as_ref(), repository, action).await? {
+        return Ok(());
+    }
+
+    match user.as_ref().filter(|u| !u.is_anonymous()) {
+        Some(_) => Err(Error::authorization(format!(
+            "Not permitted to {} repository '{}'",
+            action, repository
+        ))),
+        None => Err(Error::authentication(format!(
+            "Authentication required to {} repository '{}'",
+            action, repository
+        ))),
+    }
+}
+
+/// Authenticate registry requests that carry an Authorization header before any handler
+/// runs, so bad credentials answer 401 instead of being handled as an anonymous request.
+/// The principal is kept in the request extensions for the `AuthenticatedUser` extractor.
+pub async fn reject_invalid_credentials(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !request.headers().contains_key(header::AUTHORIZATION) {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    match authenticate_parts(&mut parts, &state).await {
+        Ok(user) => {
+            parts.extensions.insert(user);
+            next.run(Request::from_parts(parts, body)).await
+        }
+        Err(e) => e.into_response(),
+    }
+}
+
 #[async_trait]
 impl<S> FromRequestParts<S> for AuthenticatedUser
 where
     S: Send + Sync,
+    AppState: FromRef<S>,
 {
     type Rejection = StatusCode;
 
-    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
-        // Extract Authorization header
-        let authorization = parts
-            .headers
-            .get("authorization")
-            .and_then(|value| value.to_str().ok())
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-
-        // Extract token from "Bearer <token>" format
-        let token = extract_token_from_header(authorization)
-            .ok_or(StatusCode::UNAUTHORIZED)?;
-
-        // For now, create a default JWT config
-        // TODO: This should come from app state or configuration
-        let jwt_config = JwtConfig::new(
-            std::env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string())
-        );
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let user = match parts.extensions.get::<AuthenticatedUser>() {
+            Some(user) => user.clone(),
+            None => authenticate_parts(parts, state).await.map_err(|e| e.status_code())?,
+        };
+        if let Some(slot) = parts.extensions.get::<AccessLogUser>() {
+            slot.set(&user.name);
+        }
+        Ok(user)
+    }
+}
+
+/// Resolve the caller from the Authorization header: robot or share Basic credentials, account
+/// Basic credentials on registry routes when `auth.challenge.basic` is set, or a bearer token
+async fn authenticate_parts<S>(parts: &mut Parts, state: &S) -> AppResult<AuthenticatedUser>
+where
+    S: Send + Sync,
+    AppState: FromRef<S>,
+{
+    let invalid = || Error::authentication("Invalid credentials");
+
+    // Extract Authorization header
+    let authorization = parts
+        .headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .ok_or_else(|| Error::authentication("Authentication required"))?;
+
+    let app_state = AppState::from_ref(state);
+
+    // Robot accounts authenticate with Basic credentials
+    if let Some((username, secret)) = decode_basic_auth(authorization) {
+        if username.starts_with(ROBOT_USERNAME_PREFIX) {
+            return crate::handlers::robots::authenticate_robot(&app_state, &username, &secret)
+                .await
+                .map_err(|_| invalid());
+        }
+        if username.starts_with(SHARE_USERNAME_PREFIX) {
+            return crate::handlers::shares::authenticate_share(&app_state, &username, &secret)
+                .await
+                .map_err(|_| invalid());
+        }
+
+        // Account passwords are accepted on registry requests when the Basic challenge is offered
+        if app_state.config.auth.challenge.basic && parts.uri.path().starts_with("/v2/") {
+            let peer = parts.extensions.get::<ConnectInfo<SocketAddr>>().map(|ConnectInfo(addr)| *addr);
+            let client_ip = crate::handlers::auth::client_ip(&app_state.config.server, &parts.headers, peer);
+            return crate::handlers::auth::authenticate_basic_user(&app_state, &username, &secret, client_ip.as_deref())
+                .await
+                .map_err(|e| match e {
+                    Error::TooManyRequests { .. } => e,
+                    _ => invalid(),
+                });
+        }
+    }
+
+    // Extract token from "Bearer <token>" format
+    let token = extract_token_from_header(authorization).ok_or_else(invalid)?;
+
+    // Validate token and extract claims
+    let claims = validate_token(token, &app_state.jwt)
+        .await
+        .map_err(|_| invalid())?;
 
-        // Validate token and extract claims
-        let claims = validate_token(&token, &jwt_config)
-            .map_err(|_| StatusCode::UNAUTHORIZED)?;
+    let mut user = AuthenticatedUser::from(claims);
 
-        Ok(AuthenticatedUser::from(claims))
+    // Tokens derived from a share stop working as soon as it's revoked
+    if user.is_share() {
+        crate::handlers::shares::ensure_share_active(&app_state, &user.id)
+            .await
+            .map_err(|_| invalid())?;
+        return Ok(user);
     }
+
+    // Tokens outlive their account; deleted or deactivated users are refused straight away
+    if !user.is_robot() && !user.is_anonymous() {
+        let active: Option<bool> = sqlx::query_scalar("SELECT is_active FROM users WHERE id = $1")
+            .bind(&user.id)
+            .fetch_optional(&app_state.database.pool)
+            .await?;
+        if active != Some(true) {
+            return Err(invalid());
+        }
+    }
+
+    // Repository grants, direct or through a team, apply without reissuing the token
+    let granted = crate::handlers::teams::granted_scopes(&app_state.database.pool, &user.id).await?;
+    user.scopes.extend(granted);
+
+    Ok(user)
 }
 
 impl From<Claims> for AuthenticatedUser {
@@ -64,11 +299,42 @@ impl From<Claims> for AuthenticatedUser {
             id: claims.sub,
             name: claims.name,
             email: claims.email,
-            scopes: claims.scope,
+            // Docker token grants become repository-scoped permissions
+            scopes: claims
+                .scope
+                .into_iter()
+                .chain(claims.access.iter().flat_map(|entry| {
+                    entry
+                        .actions
+                        .iter()
+                        .map(move |action| format!("{}:{}:{}", entry.resource_type, entry.name, action))
+                }))
+                .collect(),
         }
     }
 }
 
+/// User id behind a valid bearer token, without loading grants; robot and share
+/// principals have no user account and yield None
+pub async fn bearer_subject(headers: &HeaderMap, jwt: &JwtConfig) -> Option<String> {
+    let authorization = headers.get("authorization")?.to_str().ok()?;
+    let token = extract_token_from_header(authorization)?;
+    let claims = validate_token(token, jwt).await.ok()?;
+    let user = AuthenticatedUser::from(claims);
+    (!user.is_robot() && !user.is_share()).then_some(user.id)
+}
+
+/// Decode a `Basic` Authorization header into (username, password)
+pub fn decode_basic_auth(auth_header: &str) -> Option<(String, String)> {
+    use base64::Engine;
+
+    let encoded = auth_header.strip_prefix("Basic ")?;
+    let decoded = base64::engine::general_purpose::STANDARD.decode(encoded.trim()).ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
 /// Authentication middleware that validates JWT tokens
 pub async fn auth_middleware(
     State(auth_state): State<AuthState>,
@@ -89,7 +355,7 @@ pub async fn auth_middleware(
 
     if let Some(auth_header) = auth_header {
         if let Some(token) = extract_token_from_header(auth_header) {
-            match validate_token(token, &auth_state.jwt_config) {
+            match validate_token(token, &auth_state.jwt_config).await {
                 Ok(claims) => {
                     // Add user info to request extensions
                     let user = AuthenticatedUser::from(claims);
@@ -135,6 +401,7 @@ fn is_public_endpoint(path: &str) -> bool {
     matches!(
         path,
         "/health" | 
+        // Scrapers don't carry user JWTs; `[metrics]` gates this in the handler
         "/metrics" | 
         "/" | 
         "/auth/login" | 
@@ -186,6 +453,49 @@ mod tests {
         assert!(!is_public_endpoint("/repositories"));
     }
 
+    #[test]
+    fn test_robot_scopes_are_exact() {
+        let robot = AuthenticatedUser {
+            id: format!("{}1234", ROBOT_ID_PREFIX),
+            name: "robot$team/app+ci".to_string(),
+            email: String::new(),
+            scopes: vec!["repository:team/app:pull".to_string()],
+        };
+
+        assert!(robot.can_access("team/app", "pull"));
+        assert!(!robot.can_access("team/app", "push"));
+        assert!(!robot.can_access("team/other", "pull"));
+    }
+
+    #[test]
+    fn test_registry_scopes_are_global() {
+        let user = AuthenticatedUser {
+            id: "user-1".to_string(),
+            name: "dev".to_string(),
+            email: "dev@example.com".to_string(),
+            scopes: vec!["repository:a:pull".to_string()],
+        };
+        assert!(user.can_access("a", "pull"));
+        assert!(!user.can_access("a", "push"));
+        assert!(!user.can_access("b", "pull"));
+
+        // Only admins, or callers an external issuer maps to them, hold registry-wide scopes
+        let reader = AuthenticatedUser { scopes: vec!["registry:read".to_string()], ..user };
+        assert!(reader.can_access("b", "pull"));
+        assert!(!reader.can_access("b", "push"));
+    }
+
+    #[test]
+    fn test_decode_basic_auth() {
+        // "robot$app+ci:secret"
+        let header = "Basic cm9ib3QkYXBwK2NpOnNlY3JldA==";
+        assert_eq!(
+            decode_basic_auth(header),
+            Some(("robot$app+ci".to_string(), "secret".to_string()))
+        );
+        assert_eq!(decode_basic_auth("Bearer abc"), None);
+    }
+
     #[test]
     fn test_auth_required_endpoints() {
         assert!(requires_auth("/v2/myrepo/manifests/latest"));
@@ -1,3 +1,5 @@
 // Auth module - middleware and utilities
 pub mod middleware;
+pub mod challenge;
 pub mod jwt;
+pub mod lockout;
@@ -0,0 +1,367 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{
+    fs,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use crate::{
+    config::{Config, StorageBackend, StorageConfig},
+    database::Database,
+    error::{Error, Result},
+};
+
+/// Self-contained backups: a point-in-time copy of the SQLite database plus, on the
+/// filesystem backend, the blob store. Object-storage buckets are left where they are;
+/// the bucket stays the source of truth for blobs and only the database is bundled.
+
+/// Bundle layout version; restore refuses bundles written by a newer layout
+pub const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_FILE: &str = "backup.json";
+const DATABASE_FILE: &str = "ghostdock.db";
+const STORAGE_DIR: &str = "storage";
+
+/// Upload staging under the storage root; interrupted pushes restart rather than resume
+const SKIPPED_STORAGE_DIRS: &[&str] = &["uploads"];
+
+/// Describes a bundle; written as `backup.json` at its root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub ghostdock_version: String,
+    pub created_at: DateTime<Utc>,
+    pub storage_backend: StorageBackend,
+    /// Whether `storage/` holds the blob store; false for object-storage backends
+    pub includes_storage: bool,
+    pub database_bytes: u64,
+    pub storage_files: u64,
+    pub storage_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BackupReport {
+    pub output: PathBuf,
+    pub manifest: BackupManifest,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreReport {
+    pub manifest: BackupManifest,
+    pub database_path: PathBuf,
+    /// Blob files copied into the storage directory
+    pub storage_files: u64,
+    pub storage_bytes: u64,
+    pub warnings: Vec<String>,
+    pub duration_ms: u64,
+}
+
+/// Whether `path` names a gzipped tarball rather than a bundle directory
+pub fn is_archive(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_ascii_lowercase())
+        .unwrap_or_default();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+/// Write a bundle to `output`: a `.tar.gz`/`.tgz` archive, or otherwise a directory that
+/// must not exist yet or be empty.
+///
+/// The database is snapshotted before the blob store is copied, so every blob the
+/// snapshot references is in the bundle as long as nothing deletes blobs meanwhile;
+/// a live server pauses writes around this.
+pub async fn create(database: &Database, storage: &StorageConfig, output: &Path) -> Result<BackupReport> {
+    let started = Instant::now();
+    let archive = is_archive(output);
+    if (archive && output.exists()) || (!archive && !is_empty_or_missing(output)?) {
+        return Err(Error::conflict(format!("{} already exists", output.display())));
+    }
+
+    // Archives are assembled beside their destination, then packed and renamed into place
+    let scratch = if archive {
+        let parent = parent_dir(output);
+        fs::create_dir_all(parent)?;
+        Some(tempfile::Builder::new().prefix(".ghostdock-backup-").tempdir_in(parent)?)
+    } else {
+        fs::create_dir_all(output)?;
+        None
+    };
+    let bundle = scratch.as_ref().map_or(output, |dir| dir.path()).to_path_buf();
+
+    let database_file = bundle.join(DATABASE_FILE);
+    database.snapshot_to(&database_file).await?;
+    let database_bytes = fs::metadata(&database_file)?.len();
+
+    let includes_storage = matches!(storage.backend, StorageBackend::Filesystem);
+    let (storage_files, storage_bytes) = if includes_storage {
+        let (source, target) = (storage.path.clone(), bundle.join(STORAGE_DIR));
+        blocking(move || copy_tree(&source, &target, SKIPPED_STORAGE_DIRS)).await?
+    } else {
+        tracing::info!("{:?} storage: backing up the database only; the bucket holds the blobs", storage.backend);
+        (0, 0)
+    };
+
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        ghostdock_version: crate::VERSION.to_string(),
+        created_at: Utc::now(),
+        storage_backend: storage.backend.clone(),
+        includes_storage,
+        database_bytes,
+        storage_files,
+        storage_bytes,
+    };
+    fs::write(bundle.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?)?;
+
+    if archive {
+        let target = output.to_path_buf();
+        blocking(move || pack(&bundle, &target)).await?;
+    }
+
+    Ok(BackupReport {
+        output: output.to_path_buf(),
+        manifest,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Rebuild the configured database and, on the filesystem backend, the blob store from
+/// a bundle. Run with the server stopped.
+///
+/// An existing database or non-empty storage directory is only replaced with `force`;
+/// restored blobs are copied over what is there, and `fsck --repair` removes leftovers.
+pub async fn restore(config: &Config, input: &Path, force: bool) -> Result<RestoreReport> {
+    let started = Instant::now();
+    if !input.exists() {
+        return Err(Error::not_found(format!("Backup {}", input.display())));
+    }
+
+    // Archives are unpacked next to the database so the final rename stays on one filesystem
+    let database_path = config.database.path.clone();
+    let scratch = if is_archive(input) {
+        let parent = parent_dir(&database_path);
+        fs::create_dir_all(parent)?;
+        let dir = tempfile::Builder::new().prefix(".ghostdock-restore-").tempdir_in(parent)?;
+        let (source, target) = (input.to_path_buf(), dir.path().to_path_buf());
+        blocking(move || unpack(&source, &target)).await?;
+        Some(dir)
+    } else {
+        None
+    };
+    let bundle = scratch.as_ref().map_or(input, |dir| dir.path());
+
+    let manifest: BackupManifest = match fs::read(bundle.join(MANIFEST_FILE)) {
+        Ok(content) => serde_json::from_slice(&content)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::bad_request(format!("{} is not a GhostDock backup: no {}", input.display(), MANIFEST_FILE)));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    if manifest.format_version > FORMAT_VERSION {
+        return Err(Error::bad_request(format!(
+            "Backup format {} was written by GhostDock {} and is newer than this version understands ({})",
+            manifest.format_version, manifest.ghostdock_version, FORMAT_VERSION
+        )));
+    }
+
+    let mut warnings = Vec::new();
+    let restore_storage = match (manifest.includes_storage, &config.storage.backend) {
+        (true, StorageBackend::Filesystem) => true,
+        (true, backend) => {
+            warnings.push(format!(
+                "The backup holds a filesystem blob store but storage.backend is {:?}; blobs were not restored",
+                backend
+            ));
+            false
+        }
+        (false, backend) => {
+            warnings.push(format!(
+                "The backup holds no blobs ({:?} storage); the configured {:?} storage must already contain them",
+                manifest.storage_backend, backend
+            ));
+            false
+        }
+    };
+
+    // Refuse before touching anything, so a refused restore changes nothing
+    if !force {
+        if database_path.exists() {
+            return Err(Error::conflict(format!("{} already exists; pass --force to replace it", database_path.display())));
+        }
+        if restore_storage && !is_empty_or_missing(&config.storage.path)? {
+            return Err(Error::conflict(format!(
+                "{} is not empty; pass --force to restore into it",
+                config.storage.path.display()
+            )));
+        }
+    }
+
+    // Copy beside the live file, then swap it in; a stale WAL would be replayed over it
+    let staged = sibling(&database_path, ".restore");
+    fs::copy(bundle.join(DATABASE_FILE), &staged)?;
+    for suffix in ["-wal", "-shm"] {
+        if let Err(e) = fs::remove_file(sibling(&database_path, suffix)) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e.into());
+            }
+        }
+    }
+    fs::rename(&staged, &database_path)?;
+
+    let (storage_files, storage_bytes) = if restore_storage {
+        let (source, target) = (bundle.join(STORAGE_DIR), config.storage.path.clone());
+        blocking(move || copy_tree(&source, &target, &[])).await?
+    } else {
+        (0, 0)
+    };
+
+    Ok(RestoreReport {
+        manifest,
+        database_path,
+        storage_files,
+        storage_bytes,
+        warnings,
+        duration_ms: started.elapsed().as_millis() as u64,
+    })
+}
+
+/// Run filesystem work off the async runtime
+async fn blocking<T, F>(work: F) -> Result<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|e| Error::internal(format!("Backup task failed: {}", e)))?
+        .map_err(Error::from)
+}
+
+/// Copy every file under `source` into `target`, skipping the named top-level directories.
+/// Returns the number of files and bytes copied; a missing source copies nothing.
+fn copy_tree(source: &Path, target: &Path, skip: &[&str]) -> io::Result<(u64, u64)> {
+    fs::create_dir_all(target)?;
+    if !source.exists() {
+        return Ok((0, 0));
+    }
+
+    let (mut files, mut bytes) = (0, 0);
+    let walker = walkdir::WalkDir::new(source).min_depth(1).into_iter().filter_entry(|entry| {
+        entry.depth() != 1 || !skip.iter().any(|name| entry.file_name() == *name)
+    });
+    for entry in walker {
+        let entry = entry.map_err(io::Error::from)?;
+        let Ok(relative) = entry.path().strip_prefix(source) else { continue };
+        let destination = target.join(relative);
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&destination)?;
+        } else if entry.file_type().is_file() {
+            bytes += fs::copy(entry.path(), &destination)?;
+            files += 1;
+        }
+    }
+    Ok((files, bytes))
+}
+
+/// Pack a bundle directory into a gzipped tarball, renamed into place once complete
+fn pack(bundle: &Path, archive: &Path) -> io::Result<()> {
+    let staged = tempfile::NamedTempFile::new_in(parent_dir(archive))?;
+    let encoder = flate2::write::GzEncoder::new(staged, flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.append_dir_all(".", bundle)?;
+    let mut staged = builder.into_inner()?.finish()?;
+    staged.flush()?;
+    staged.as_file().sync_all()?;
+    staged.persist(archive).map_err(|e| e.error)?;
+    Ok(())
+}
+
+fn unpack(archive: &Path, target: &Path) -> io::Result<()> {
+    let decoder = flate2::read::GzDecoder::new(fs::File::open(archive)?);
+    tar::Archive::new(decoder).unpack(target)
+}
+
+fn is_empty_or_missing(dir: &Path) -> io::Result<bool> {
+    match fs::read_dir(dir) {
+        Ok(mut entries) => Ok(entries.next().is_none()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+fn parent_dir(path: &Path) -> &Path {
+    path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or_else(|| Path::new("."))
+}
+
+/// `ghostdock.db` with `-wal` appended is `ghostdock.db-wal`
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DatabaseConfig;
+
+    #[test]
+    fn test_is_archive() {
+        assert!(is_archive(Path::new("/backups/ghostdock-20240101.tar.gz")));
+        assert!(is_archive(Path::new("nightly.TGZ")));
+        assert!(!is_archive(Path::new("/backups/nightly")));
+        assert!(!is_archive(Path::new("nightly.tar")));
+    }
+
+    #[tokio::test]
+    async fn test_backup_restore_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.database.path = dir.path().join("source/ghostdock.db");
+        config.storage.path = dir.path().join("source/storage");
+
+        fs::create_dir_all(config.storage.path.join("blobs")).unwrap();
+        fs::create_dir_all(config.storage.path.join("uploads")).unwrap();
+        fs::write(config.storage.path.join("blobs/sha256:aa"), b"layer").unwrap();
+        fs::write(config.storage.path.join("uploads/session"), b"partial").unwrap();
+        fs::File::create(&config.database.path).unwrap();
+
+        let database = Database::new(&config.database).await.unwrap();
+        sqlx::query("CREATE TABLE probe (value TEXT)").execute(&database.pool).await.unwrap();
+        sqlx::query("INSERT INTO probe (value) VALUES ('kept')").execute(&database.pool).await.unwrap();
+
+        let archive = dir.path().join("backups/nightly.tar.gz");
+        let report = create(&database, &config.storage, &archive).await.unwrap();
+        assert!(report.manifest.includes_storage);
+        assert_eq!((report.manifest.storage_files, report.manifest.storage_bytes), (1, 5));
+        assert!(create(&database, &config.storage, &archive).await.is_err());
+
+        let mut target = config.clone();
+        target.database = DatabaseConfig { path: dir.path().join("target/ghostdock.db"), ..config.database.clone() };
+        target.storage.path = dir.path().join("target/storage");
+        let restored = restore(&target, &archive, false).await.unwrap();
+        assert_eq!(restored.storage_files, 1);
+        assert!(restored.warnings.is_empty());
+        assert_eq!(fs::read(target.storage.path.join("blobs/sha256:aa")).unwrap(), b"layer");
+        assert!(!target.storage.path.join("uploads").exists());
+
+        let pool = sqlx::SqlitePool::connect(&format!("sqlite:{}", target.database.path.display())).await.unwrap();
+        let value: String = sqlx::query_scalar("SELECT value FROM probe").fetch_one(&pool).await.unwrap();
+        assert_eq!(value, "kept");
+        pool.close().await;
+
+        // The restored database is only replaced on request
+        assert!(matches!(restore(&target, &archive, false).await, Err(Error::Conflict { .. })));
+        assert!(restore(&target, &archive, true).await.is_ok());
+
+        // Directory bundles hold the same layout
+        let bundle = dir.path().join("backups/nightly");
+        create(&database, &config.storage, &bundle).await.unwrap();
+        assert!(bundle.join(MANIFEST_FILE).exists());
+        assert!(bundle.join(DATABASE_FILE).exists());
+    }
+}
@@ -1,4 +1,4 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
 #[derive(Parser)]
@@ -7,7 +7,7 @@ use std::path::PathBuf;
 #[command(version = env!("CARGO_PKG_VERSION"))]
 pub struct Cli {
     /// Path to configuration file
-    #[arg(short, long, default_value = "config.toml")]
+    #[arg(short, long, default_value = "config.toml", global = true)]
     pub config: PathBuf,
     
     /// Verbosity level (can be used multiple times)
@@ -37,4 +37,58 @@ pub struct Cli {
     /// Enable development mode (with additional logging and debug features)
     #[arg(long)]
     pub dev: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Run garbage collection once and exit
+    Gc {
+        /// Report what would be deleted without deleting anything
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Minimum age (hours) before an untagged manifest is deleted
+        #[arg(long)]
+        min_age_hours: Option<u64>,
+    },
+    /// Check the blobs table against storage and report drift
+    Fsck {
+        /// Remove rows for missing objects, re-register referenced orphans and delete the rest
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Load and lint a configuration file without starting the server
+    ValidateConfig,
+    /// Snapshot the database and, on the filesystem backend, the blob store.
+    /// With the server running, pause writes first or use `POST /api/admin/database/backup`.
+    Backup {
+        /// Bundle directory, or an archive path ending in .tar.gz or .tgz
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// Rebuild the database and blob store from a backup; run while the server is stopped
+    Restore {
+        /// Bundle directory or .tar.gz/.tgz archive written by `backup`
+        #[arg(long)]
+        input: PathBuf,
+
+        /// Replace an existing database and restore into non-empty storage
+        #[arg(long)]
+        force: bool,
+    },
+    /// Database maintenance
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum DbCommand {
+    /// Checkpoint the WAL, VACUUM and ANALYZE; run while the server is stopped
+    /// (a running server should use `POST /api/admin/database/optimize`, which pauses writes)
+    Optimize,
 }
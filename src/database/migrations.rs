@@ -1,75 +1,296 @@
-// Database migration utilities will go here
-// For now, we'll implement basic table creation
+use crate::{
+    error::Result,
+    types::{ArtifactType, ManifestType},
+    utils::manifest_subject_digest,
+};
+use sqlx::{Connection, Row, SqliteConnection, SqlitePool};
 
-use crate::error::Result;
-use sqlx::SqlitePool;
+/// Number of upgrade steps in `upgrade_step`, recorded in `PRAGMA user_version`
+const SCHEMA_VERSION: i64 = 3;
 
-pub async fn create_tables(pool: &SqlitePool) -> Result<()> {
-    // Users table
-    sqlx::query(
+/// Repositories filled by the pull-through cache have no owner, so `owner_id` is nullable
+fn repositories_table(name: &str) -> String {
+    format!(
         r#"
-        CREATE TABLE IF NOT EXISTS users (
-            id TEXT PRIMARY KEY,
-            username TEXT UNIQUE NOT NULL,
-            email TEXT UNIQUE NOT NULL,
-            password_hash TEXT,
-            full_name TEXT,
-            avatar_url TEXT,
-            provider TEXT,
-            provider_id TEXT,
-            is_admin BOOLEAN NOT NULL DEFAULT FALSE,
-            is_active BOOLEAN NOT NULL DEFAULT TRUE,
-            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            last_login DATETIME
-        );
-        "#,
-    )
-    .execute(pool)
-    .await?;
-
-    // Repositories table
-    sqlx::query(
-        r#"
-        CREATE TABLE IF NOT EXISTS repositories (
+        CREATE TABLE IF NOT EXISTS {} (
             id TEXT PRIMARY KEY,
             name TEXT NOT NULL,
             namespace TEXT,
             description TEXT,
             is_public BOOLEAN NOT NULL DEFAULT FALSE,
-            owner_id TEXT NOT NULL,
+            owner_id TEXT,
             star_count INTEGER NOT NULL DEFAULT 0,
             pull_count INTEGER NOT NULL DEFAULT 0,
             push_count INTEGER NOT NULL DEFAULT 0,
+            allowed_artifact_types TEXT,
+            deletion_protected BOOLEAN NOT NULL DEFAULT FALSE,
+            default_platform TEXT,
             created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
             updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
             FOREIGN KEY (owner_id) REFERENCES users (id),
             UNIQUE(namespace, name)
         );
         "#,
+        name
     )
-    .execute(pool)
-    .await?;
+}
 
-    // Manifests table
-    sqlx::query(
+/// Manifest digests are unique per repository, so the same image can live in several
+fn manifests_table(name: &str) -> String {
+    format!(
         r#"
-        CREATE TABLE IF NOT EXISTS manifests (
+        CREATE TABLE IF NOT EXISTS {} (
             id TEXT PRIMARY KEY,
             repository_id TEXT NOT NULL,
-            digest TEXT UNIQUE NOT NULL,
+            digest TEXT NOT NULL,
             media_type TEXT NOT NULL,
-            schema_version INTEGER NOT NULL,
+            schema_version INTEGER NOT NULL DEFAULT 2,
             content BLOB NOT NULL,
             size INTEGER NOT NULL,
+            image_size INTEGER,
+            artifact_type TEXT NOT NULL DEFAULT 'image',
+            subject_digest TEXT,
             created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
-            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+            FOREIGN KEY (repository_id) REFERENCES repositories (id),
+            UNIQUE(repository_id, digest)
+        );
+        "#,
+        name
+    )
+}
+
+/// Bring a database created by an earlier release up to the current schema.
+///
+/// `create_tables` only adds missing tables, so changes to existing ones are made here,
+/// before it runs. `PRAGMA user_version` records the last step applied; each step also
+/// checks the live schema, so it is a no-op on tables that never had the old shape.
+pub async fn upgrade(pool: &SqlitePool) -> Result<()> {
+    let mut conn = pool.acquire().await?;
+    let version: i64 = sqlx::query_scalar("PRAGMA user_version")
+        .fetch_one(&mut *conn)
+        .await?;
+
+    for step in (version + 1)..=SCHEMA_VERSION {
+        upgrade_step(&mut conn, step).await?;
+        sqlx::query(&format!("PRAGMA user_version = {}", step))
+            .execute(&mut *conn)
+            .await?;
+    }
+
+    Ok(())
+}
+
+async fn upgrade_step(conn: &mut SqliteConnection, step: i64) -> Result<()> {
+    match step {
+        1 => {
+            if column_not_null(conn, "repositories", "owner_id").await? {
+                rebuild_table(conn, "repositories", repositories_table).await?;
+            }
+        }
+        2 => {
+            // Columns added to existing tables; each is nullable or has a default
+            let columns = [
+                ("users", "max_repositories", "INTEGER"),
+                ("users", "rate_limit_override", "INTEGER"),
+                ("repositories", "allowed_artifact_types", "TEXT"),
+                ("repositories", "deletion_protected", "BOOLEAN NOT NULL DEFAULT FALSE"),
+                ("repositories", "default_platform", "TEXT"),
+                ("webhooks", "tag_patterns", "TEXT"),
+                ("deployments", "compose_content", "TEXT NOT NULL DEFAULT ''"),
+                ("deployments", "rolled_back_from", "TEXT"),
+            ];
+            for (table, column, definition) in columns {
+                add_column(conn, table, column, definition).await?;
+            }
+
+            // Digests used to be unique across the whole registry
+            if !column_names(conn, "manifests").await?.is_empty()
+                && !has_unique_index(conn, "manifests", &["repository_id", "digest"]).await?
+            {
+                rebuild_table(conn, "manifests", manifests_table).await?;
+                backfill_manifests(conn).await?;
+            }
+        }
+        3 => {
+            // Re-pushed manifests used to gain a duplicate link to each of their blobs
+            if !column_names(conn, "manifest_blobs").await?.is_empty()
+                && !has_unique_index(conn, "manifest_blobs", &["manifest_id", "blob_id"]).await?
+            {
+                sqlx::query(
+                    "DELETE FROM manifest_blobs WHERE rowid NOT IN \
+                     (SELECT MIN(rowid) FROM manifest_blobs GROUP BY manifest_id, blob_id)",
+                )
+                .execute(&mut *conn)
+                .await?;
+                sqlx::query("CREATE UNIQUE INDEX idx_manifest_blobs_link ON manifest_blobs (manifest_id, blob_id)")
+                    .execute(&mut *conn)
+                    .await?;
+            }
+        }
+        _ => unreachable!("no upgrade step {}", step),
+    }
+    Ok(())
+}
+
+/// Column names of `table`, empty when the table does not exist
+async fn column_names(conn: &mut SqliteConnection, table: &str) -> Result<Vec<String>> {
+    let rows = sqlx::query("SELECT name FROM pragma_table_info($1)")
+        .bind(table)
+        .fetch_all(&mut *conn)
+        .await?;
+    Ok(rows.iter().map(|row| row.get("name")).collect())
+}
+
+/// Add `column` to `table` unless the table is missing or already has it
+async fn add_column(conn: &mut SqliteConnection, table: &str, column: &str, definition: &str) -> Result<()> {
+    let columns = column_names(conn, table).await?;
+    if !columns.is_empty() && !columns.iter().any(|c| c == column) {
+        sqlx::query(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))
+            .execute(&mut *conn)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Whether `table` has a unique index or constraint on exactly `columns`, in order
+async fn has_unique_index(conn: &mut SqliteConnection, table: &str, columns: &[&str]) -> Result<bool> {
+    let indexes: Vec<String> =
+        sqlx::query_scalar(r#"SELECT name FROM pragma_index_list($1) WHERE "unique" = 1"#)
+            .bind(table)
+            .fetch_all(&mut *conn)
+            .await?;
+
+    for index in indexes {
+        let indexed: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_index_info($1) ORDER BY seqno")
+            .bind(&index)
+            .fetch_all(&mut *conn)
+            .await?;
+        if indexed.iter().map(String::as_str).eq(columns.iter().copied()) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Fill the manifest columns derived from content for rows stored before they existed.
+/// `image_size` stays NULL and is computed on first read.
+async fn backfill_manifests(conn: &mut SqliteConnection) -> Result<()> {
+    let mut tx = conn.begin().await?;
+    let rows = sqlx::query("SELECT id, media_type, content FROM manifests")
+        .fetch_all(&mut *tx)
+        .await?;
+
+    for row in rows {
+        let id: String = row.get("id");
+        let media_type: String = row.get("media_type");
+        let content: Vec<u8> = row.get("content");
+        let artifact_type = ManifestType::parse(&content, Some(&media_type))
+            .map(|manifest| manifest.artifact_type())
+            .unwrap_or(ArtifactType::Image);
+
+        sqlx::query("UPDATE manifests SET artifact_type = $1, subject_digest = $2 WHERE id = $3")
+            .bind(artifact_type.as_str())
+            .bind(manifest_subject_digest(&content))
+            .bind(&id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+async fn column_not_null(conn: &mut SqliteConnection, table: &str, column: &str) -> Result<bool> {
+    let not_null: Option<bool> =
+        sqlx::query_scalar(r#"SELECT "notnull" FROM pragma_table_info($1) WHERE name = $2"#)
+            .bind(table)
+            .bind(column)
+            .fetch_optional(&mut *conn)
+            .await?;
+    Ok(not_null.unwrap_or(false))
+}
+
+/// Recreate `table` from `create` (which takes the table name), copying the columns the
+/// old and new shapes share. SQLite cannot alter a column or constraint in place, so this
+/// follows its documented procedure: foreign keys off, build a replacement, copy, drop,
+/// rename. Indexes on the table are dropped with it; `create_tables` recreates them.
+async fn rebuild_table(conn: &mut SqliteConnection, table: &str, create: fn(&str) -> String) -> Result<()> {
+    let staging = format!("{}_rebuild", table);
+
+    // Has no effect inside a transaction, so it is switched per connection around one
+    sqlx::query("PRAGMA foreign_keys = OFF").execute(&mut *conn).await?;
+    let rebuilt = async {
+        let mut tx = conn.begin().await?;
+        sqlx::query(&create(&staging)).execute(&mut *tx).await?;
+
+        let new_columns = column_names(&mut tx, &staging).await?;
+        let shared = column_names(&mut tx, table)
+            .await?
+            .into_iter()
+            .filter(|column| new_columns.contains(column))
+            .collect::<Vec<_>>()
+            .join(", ");
+        sqlx::query(&format!(
+            "INSERT INTO {} ({}) SELECT {} FROM {}",
+            staging, shared, shared, table
+        ))
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query(&format!("DROP TABLE {}", table)).execute(&mut *tx).await?;
+        sqlx::query(&format!("ALTER TABLE {} RENAME TO {}", staging, table))
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok::<_, crate::error::Error>(())
+    }
+    .await;
+    sqlx::query("PRAGMA foreign_keys = ON").execute(&mut *conn).await?;
+
+    rebuilt
+}
+
+pub async fn create_tables(pool: &SqlitePool) -> Result<()> {
+    // Users table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS users (
+            id TEXT PRIMARY KEY,
+            username TEXT UNIQUE NOT NULL,
+            email TEXT UNIQUE NOT NULL,
+            password_hash TEXT,
+            full_name TEXT,
+            avatar_url TEXT,
+            provider TEXT,
+            provider_id TEXT,
+            is_admin BOOLEAN NOT NULL DEFAULT FALSE,
+            is_active BOOLEAN NOT NULL DEFAULT TRUE,
+            max_repositories INTEGER,
+            rate_limit_override INTEGER,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            last_login DATETIME
         );
         "#,
     )
     .execute(pool)
     .await?;
 
+    // Repositories table
+    sqlx::query(&repositories_table("repositories"))
+        .execute(pool)
+        .await?;
+
+    // Manifests table
+    sqlx::query(&manifests_table("manifests"))
+        .execute(pool)
+        .await?;
+
+    // Referrers of a manifest are the manifests whose `subject` names it
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_manifests_subject ON manifests (repository_id, subject_digest)")
+        .execute(pool)
+        .await?;
+
     // Tags table
     sqlx::query(
         r#"
@@ -125,6 +346,23 @@ pub async fn create_tables(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await?;
 
+    // Manifest-blob relationship table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS manifest_blobs (
+            id TEXT PRIMARY KEY,
+            manifest_id TEXT NOT NULL,
+            blob_id TEXT NOT NULL,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE (manifest_id, blob_id),
+            FOREIGN KEY (manifest_id) REFERENCES manifests (id),
+            FOREIGN KEY (blob_id) REFERENCES blobs (id)
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
     // Upload sessions table
     sqlx::query(
         r#"
@@ -146,5 +384,541 @@ pub async fn create_tables(pool: &SqlitePool) -> Result<()> {
     .execute(pool)
     .await?;
 
+    // Notification preferences table
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS notification_preferences (
+            id TEXT PRIMARY KEY,
+            user_id TEXT NOT NULL,
+            channel TEXT NOT NULL,
+            destination TEXT NOT NULL,
+            min_severity TEXT NOT NULL DEFAULT 'warning',
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (user_id) REFERENCES users (id),
+            UNIQUE(user_id, channel, destination)
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Daily usage statistics per repository
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS usage_stats (
+            repository_id TEXT NOT NULL,
+            day DATE NOT NULL,
+            pulls INTEGER NOT NULL DEFAULT 0,
+            pushes INTEGER NOT NULL DEFAULT 0,
+            bytes_pulled INTEGER NOT NULL DEFAULT 0,
+            bytes_pushed INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (repository_id, day),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id)
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Repository-scoped robot accounts for CI/CD
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS robot_accounts (
+            id TEXT PRIMARY KEY,
+            repository_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            token_hash TEXT NOT NULL,
+            permission TEXT NOT NULL,
+            expires_at DATETIME,
+            created_by TEXT NOT NULL,
+            created_at DATETIME NOT NULL,
+            last_used DATETIME,
+            UNIQUE (repository_id, name),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Teams that repository permissions can be granted to as a whole
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS teams (
+            id TEXT PRIMARY KEY,
+            name TEXT UNIQUE NOT NULL,
+            description TEXT NOT NULL DEFAULT '',
+            created_by TEXT NOT NULL,
+            created_at DATETIME NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS team_members (
+            team_id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            added_at DATETIME NOT NULL,
+            PRIMARY KEY (team_id, user_id),
+            FOREIGN KEY (team_id) REFERENCES teams (id) ON DELETE CASCADE,
+            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_team_members_user ON team_members (user_id)")
+        .execute(pool)
+        .await?;
+
+    // Per-repository grants to a single user or a whole team
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS repository_permissions (
+            id TEXT PRIMARY KEY,
+            repository_id TEXT NOT NULL,
+            user_id TEXT,
+            team_id TEXT,
+            permission TEXT NOT NULL,
+            created_by TEXT NOT NULL,
+            created_at DATETIME NOT NULL,
+            CHECK ((user_id IS NULL) != (team_id IS NULL)),
+            FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE,
+            FOREIGN KEY (team_id) REFERENCES teams (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Time-limited share credentials; the signed token is never stored, only its id
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS share_tokens (
+            id TEXT PRIMARY KEY,
+            repository_id TEXT NOT NULL,
+            permission TEXT NOT NULL,
+            expires_at DATETIME NOT NULL,
+            created_by TEXT NOT NULL,
+            created_at DATETIME NOT NULL,
+            revoked_at DATETIME,
+            FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Audit log of administrative actions
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS audit_log (
+            id TEXT PRIMARY KEY,
+            actor_id TEXT NOT NULL,
+            action TEXT NOT NULL,
+            target TEXT NOT NULL,
+            details TEXT,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Outgoing webhooks; the previous secret stays valid for verification after a rotation
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhooks (
+            id TEXT PRIMARY KEY,
+            repository_id TEXT,
+            url TEXT NOT NULL,
+            secret TEXT,
+            previous_secret TEXT,
+            secret_rotated_at DATETIME,
+            events TEXT NOT NULL,
+            tag_patterns TEXT,
+            is_active BOOLEAN NOT NULL DEFAULT TRUE,
+            created_by TEXT NOT NULL,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Every webhook delivery attempt with its request and response, for debugging failures
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS webhook_deliveries (
+            id TEXT PRIMARY KEY,
+            webhook_id TEXT NOT NULL,
+            event_type TEXT NOT NULL,
+            url TEXT NOT NULL,
+            attempt INTEGER NOT NULL DEFAULT 1,
+            payload TEXT NOT NULL,
+            success BOOLEAN NOT NULL,
+            response_status INTEGER,
+            response_body TEXT,
+            latency_ms INTEGER NOT NULL,
+            error_kind TEXT,
+            error_message TEXT,
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY (webhook_id) REFERENCES webhooks (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_webhook ON webhook_deliveries (webhook_id, created_at)")
+        .execute(pool)
+        .await?;
+
+    // Docker Compose stacks with their discovery ranking
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS stacks (
+            id TEXT PRIMARY KEY,
+            name TEXT NOT NULL,
+            description TEXT,
+            compose_content TEXT NOT NULL,
+            version TEXT NOT NULL,
+            author TEXT NOT NULL,
+            author_email TEXT NOT NULL,
+            tags TEXT NOT NULL DEFAULT '[]',
+            is_public BOOLEAN NOT NULL DEFAULT FALSE,
+            featured BOOLEAN NOT NULL DEFAULT FALSE,
+            download_count INTEGER NOT NULL DEFAULT 0,
+            star_count INTEGER NOT NULL DEFAULT 0,
+            popularity_score REAL NOT NULL DEFAULT 0,
+            created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+            updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // One star per user per stack
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS stack_stars (
+            stack_id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            created_at DATETIME NOT NULL,
+            PRIMARY KEY (stack_id, user_id),
+            FOREIGN KEY (stack_id) REFERENCES stacks (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Repositories a user pinned; one favorite per user per repository
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS user_favorites (
+            user_id TEXT NOT NULL,
+            repository_id TEXT NOT NULL,
+            created_at DATETIME NOT NULL,
+            PRIMARY KEY (user_id, repository_id),
+            FOREIGN KEY (user_id) REFERENCES users (id) ON DELETE CASCADE,
+            FOREIGN KEY (repository_id) REFERENCES repositories (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Recent downloads and stars, the inputs to the decayed popularity score
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS stack_activity (
+            stack_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            created_at DATETIME NOT NULL,
+            FOREIGN KEY (stack_id) REFERENCES stacks (id) ON DELETE CASCADE
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    // Stack deployments, one compose project each
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS deployments (
+            deployment_id TEXT PRIMARY KEY,
+            stack_id TEXT NOT NULL,
+            status TEXT NOT NULL,
+            started_by TEXT NOT NULL,
+            started_at DATETIME NOT NULL,
+            updated_at DATETIME NOT NULL,
+            error TEXT,
+            compose_content TEXT NOT NULL DEFAULT '',
+            rolled_back_from TEXT,
+            FOREIGN KEY (stack_id) REFERENCES stacks (id)
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_deployments_stack ON deployments (stack_id, started_at)")
+        .execute(pool)
+        .await?;
+
+    // Image promotions awaiting or past review
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS promotions (
+            id TEXT PRIMARY KEY,
+            source_repository TEXT NOT NULL,
+            source_reference TEXT NOT NULL,
+            digest TEXT NOT NULL,
+            destination_repository TEXT NOT NULL,
+            destination_tag TEXT NOT NULL,
+            status TEXT NOT NULL,
+            requested_by TEXT NOT NULL,
+            requested_at DATETIME NOT NULL,
+            comment TEXT,
+            reviewed_by TEXT,
+            reviewed_at DATETIME,
+            review_comment TEXT,
+            error TEXT,
+            completed_at DATETIME
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query("CREATE INDEX IF NOT EXISTS idx_promotions_status ON promotions (status, requested_at)")
+        .execute(pool)
+        .await?;
+
+    // Remote registry import jobs and their latest progress
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS import_jobs (
+            id TEXT PRIMARY KEY,
+            source TEXT NOT NULL,
+            repositories TEXT NOT NULL,
+            status TEXT NOT NULL,
+            progress TEXT NOT NULL,
+            created_by TEXT NOT NULL,
+            created_at DATETIME NOT NULL,
+            updated_at DATETIME NOT NULL
+        );
+        "#,
+    )
+    .execute(pool)
+    .await?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_upgrade_makes_repository_owner_optional() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        // Shape written by earlier releases, before default_platform existed
+        sqlx::query("CREATE TABLE users (id TEXT PRIMARY KEY, username TEXT UNIQUE NOT NULL, email TEXT UNIQUE NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(
+            r#"
+            CREATE TABLE repositories (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                namespace TEXT,
+                description TEXT,
+                is_public BOOLEAN NOT NULL DEFAULT FALSE,
+                owner_id TEXT NOT NULL,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (owner_id) REFERENCES users (id),
+                UNIQUE(namespace, name)
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO users (id, username, email) VALUES ('u', 'alice', 'alice@example.com')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO repositories (id, name, is_public, owner_id) VALUES ('r', 'app', TRUE, 'u')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        upgrade(&pool).await.unwrap();
+        create_tables(&pool).await.unwrap();
+
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(&pool).await.unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+        let (owner, is_public): (Option<String>, bool) =
+            sqlx::query_as("SELECT owner_id, is_public FROM repositories WHERE name = 'app'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(owner.as_deref(), Some("u"));
+        assert!(is_public);
+
+        // Pull-through cache repositories have no owner
+        sqlx::query("INSERT INTO repositories (id, name) VALUES ('c', 'library/alpine')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        // Foreign keys are enforced again once the rebuild is done
+        assert!(sqlx::query("INSERT INTO repositories (id, name, owner_id) VALUES ('d', 'app2', 'nobody')")
+            .execute(&pool)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_scopes_manifest_digests_to_repository() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+
+        // Shape written by earlier releases: digests unique registry-wide, no derived columns
+        sqlx::query("CREATE TABLE users (id TEXT PRIMARY KEY, username TEXT UNIQUE NOT NULL, email TEXT UNIQUE NOT NULL)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query(&repositories_table("repositories")).execute(&pool).await.unwrap();
+        sqlx::query(
+            r#"
+            CREATE TABLE manifests (
+                id TEXT PRIMARY KEY,
+                repository_id TEXT NOT NULL,
+                digest TEXT UNIQUE NOT NULL,
+                media_type TEXT NOT NULL,
+                schema_version INTEGER NOT NULL,
+                content BLOB NOT NULL,
+                size INTEGER NOT NULL,
+                created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP,
+                FOREIGN KEY (repository_id) REFERENCES repositories (id)
+            )
+            "#,
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO repositories (id, name) VALUES ('a', 'charts'), ('b', 'mirror')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let content = format!(
+            r#"{{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json","config":{{"mediaType":"application/vnd.cncf.helm.config.v1+json","digest":"sha256:{}","size":2}},"layers":[],"subject":{{"mediaType":"application/vnd.oci.image.manifest.v1+json","digest":"sha256:{}","size":2}}}}"#,
+            "a".repeat(64),
+            "b".repeat(64)
+        );
+        sqlx::query(
+            "INSERT INTO manifests (id, repository_id, digest, media_type, schema_version, content, size) \
+             VALUES ('m', 'a', 'sha256:feed', 'application/vnd.oci.image.manifest.v1+json', 2, $1, 1)",
+        )
+        .bind(content.as_bytes())
+        .execute(&pool)
+        .await
+        .unwrap();
+
+        upgrade(&pool).await.unwrap();
+        create_tables(&pool).await.unwrap();
+
+        let (artifact_type, subject): (String, Option<String>) =
+            sqlx::query_as("SELECT artifact_type, subject_digest FROM manifests WHERE id = 'm'")
+                .fetch_one(&pool)
+                .await
+                .unwrap();
+        assert_eq!(artifact_type, "helm_chart");
+        assert_eq!(subject, Some(format!("sha256:{}", "b".repeat(64))));
+
+        // The same digest may now be stored in a second repository, but not twice in one
+        let pool_ref = &pool;
+        let insert = move |id: &'static str, repository: &'static str| {
+            sqlx::query(
+                "INSERT INTO manifests (id, repository_id, digest, media_type, content, size) \
+                 VALUES ($1, $2, 'sha256:feed', 'application/vnd.oci.image.manifest.v1+json', x'00', 1)",
+            )
+            .bind(id)
+            .bind(repository)
+            .execute(pool_ref)
+        };
+        insert("m2", "b").await.unwrap();
+        assert!(insert("m3", "b").await.is_err());
+
+        // Columns added to tables that already existed
+        sqlx::query("SELECT max_repositories, rate_limit_override FROM users").fetch_all(&pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_removes_duplicate_manifest_blob_links() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::query(
+            "CREATE TABLE manifest_blobs (id TEXT PRIMARY KEY, manifest_id TEXT NOT NULL, blob_id TEXT NOT NULL, \
+             created_at DATETIME NOT NULL DEFAULT CURRENT_TIMESTAMP)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query("INSERT INTO manifest_blobs (id, manifest_id, blob_id) VALUES ('1', 'm', 'b'), ('2', 'm', 'b'), ('3', 'm', 'c')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("PRAGMA user_version = 2").execute(&pool).await.unwrap();
+
+        upgrade(&pool).await.unwrap();
+
+        let links: Vec<String> = sqlx::query_scalar("SELECT id FROM manifest_blobs ORDER BY id")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(links, ["1", "3"]);
+        assert!(sqlx::query("INSERT INTO manifest_blobs (id, manifest_id, blob_id) VALUES ('4', 'm', 'c')")
+            .execute(&pool)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upgrade_on_fresh_database() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        upgrade(&pool).await.unwrap();
+        create_tables(&pool).await.unwrap();
+        upgrade(&pool).await.unwrap();
+
+        let version: i64 = sqlx::query_scalar("PRAGMA user_version").fetch_one(&pool).await.unwrap();
+        assert_eq!(version, SCHEMA_VERSION);
+    }
+}
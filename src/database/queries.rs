@@ -1,15 +1,20 @@
 use crate::{
+    auth::middleware::AuthenticatedUser,
+    config::RegistryConfig,
     error::{Error, Result},
     server::AppState,
     types::*,
+    utils::{manifest_list_children, manifest_subject_digest},
 };
+use async_trait::async_trait;
 use uuid::Uuid;
-use sqlx::Row;
+use sqlx::{Row, SqlitePool};
+use std::{future::Future, pin::Pin};
 
 /// Get repository by name
 pub async fn get_repository_by_name(state: &AppState, name: &str) -> Result<Repository> {
     let row = sqlx::query(
-        "SELECT id, name, description, is_public, created_at, updated_at FROM repositories WHERE name = $1"
+        "SELECT id, name, namespace, description, is_public, created_at, updated_at FROM repositories WHERE name = $1"
     )
     .bind(name)
     .fetch_one(&state.database.pool)
@@ -19,6 +24,7 @@ pub async fn get_repository_by_name(state: &AppState, name: &str) -> Result<Repo
     Ok(Repository {
         id: row.get("id"),
         name: row.get("name"),
+        namespace: row.get("namespace"),
         description: row.get("description"),
         is_public: row.get("is_public"),
         created_at: row.get("created_at"),
@@ -26,38 +32,300 @@ pub async fn get_repository_by_name(state: &AppState, name: &str) -> Result<Repo
     })
 }
 
-/// Get or create repository
-pub async fn get_or_create_repository(state: &AppState, name: &str) -> Result<Repository> {
+/// Get or create repository on push, honouring `registry.allow_push_creates_repository`
+/// and applying the configured namespace and visibility defaults for the pusher
+pub async fn get_or_create_repository(state: &AppState, name: &str, user: &Option<AuthenticatedUser>) -> Result<Repository> {
     // Try to get existing repository first
     match get_repository_by_name(state, name).await {
         Ok(repo) => Ok(repo),
-        Err(_) => {
-            // Create new repository
-            let repo_id = Uuid::new_v4();
-            let now = chrono::Utc::now();
-            
-            sqlx::query(
-                "INSERT INTO repositories (id, name, description, is_public, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6)"
-            )
-            .bind(repo_id)
-            .bind(name)
-            .bind("")
-            .bind(false)
-            .bind(now)
-            .bind(now)
-            .execute(&state.database.pool)
+        Err(Error::NotFound { .. }) => {
+            ensure_push_may_create(&state.config.registry, name)?;
+            let defaults = repository_defaults(&state.config.registry, name, user.as_ref());
+            ensure_repository_quota(state, user.as_ref(), defaults.namespace.as_deref()).await?;
+            let repo = create_repository(state, name, "", defaults.is_public, defaults.namespace.as_deref(), defaults.owner_id.as_deref()).await?;
+            state.performance.catalog_cache.invalidate();
+            Ok(repo)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Reject implicit repository creation when the registry is locked down
+pub fn ensure_push_may_create(config: &RegistryConfig, name: &str) -> Result<()> {
+    if config.allow_push_creates_repository {
+        Ok(())
+    } else {
+        Err(Error::name_unknown(name))
+    }
+}
+
+/// Reject creating another repository once its owner or namespace is at the configured
+/// limit. Admins are exempt, and an admin-set per-user limit replaces the default.
+pub async fn ensure_repository_quota(state: &AppState, user: Option<&AuthenticatedUser>, namespace: Option<&str>) -> Result<()> {
+    let config = &state.config.registry;
+    let user = user.filter(|u| !u.is_robot());
+    if user.is_some_and(|u| u.scopes.iter().any(|s| s == "admin")) {
+        return Ok(());
+    }
+
+    if let Some(user) = user {
+        let user_limit: Option<i64> = sqlx::query_scalar("SELECT max_repositories FROM users WHERE id = $1")
+            .bind(&user.id)
+            .fetch_optional(&state.database.pool)
+            .await?
+            .flatten();
+
+        if let Some(limit) = repository_limit(user_limit, config.max_repositories_per_user) {
+            let owned: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM repositories WHERE owner_id = $1")
+                .bind(&user.id)
+                .fetch_one(&state.database.pool)
+                .await?;
+            if owned as usize >= limit {
+                return Err(Error::authorization(format!(
+                    "You already own {} repositories, the most allowed; delete one or ask an admin to raise your limit",
+                    owned
+                )));
+            }
+        }
+    }
+
+    if let (Some(namespace), Some(limit)) = (namespace, repository_limit(None, config.max_repositories_per_namespace)) {
+        let contained: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM repositories WHERE namespace = $1")
+            .bind(namespace)
+            .fetch_one(&state.database.pool)
             .await?;
-            
-            Ok(Repository {
-                id: repo_id,
-                name: name.to_string(),
-                description: String::new(),
-                is_public: false,
-                created_at: now,
-                updated_at: now,
-            })
+        if contained as usize >= limit {
+            return Err(Error::authorization(format!(
+                "Namespace '{}' already holds {} repositories, the most allowed",
+                namespace, contained
+            )));
         }
     }
+
+    Ok(())
+}
+
+/// Effective repository limit: a per-user override wins over the default, and 0 means unlimited
+pub fn repository_limit(user_override: Option<i64>, default: usize) -> Option<usize> {
+    let limit = user_override.map_or(default, |l| l.max(0) as usize);
+    (limit > 0).then_some(limit)
+}
+
+/// Namespace, visibility, and owner for a repository created by a push
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepositoryDefaults {
+    pub namespace: Option<String>,
+    pub is_public: bool,
+    pub owner_id: Option<String>,
+}
+
+/// Personal namespace for a user, or None for robots (which never own repositories)
+pub fn user_namespace(config: &RegistryConfig, user: &AuthenticatedUser) -> Option<String> {
+    if user.is_robot() {
+        None
+    } else {
+        Some(config.user_namespace.replace("{username}", &user.name))
+    }
+}
+
+/// Whether a repository named `name` would be filed under `user`'s personal namespace,
+/// which accounts may create repositories in without a grant
+pub fn in_personal_namespace(config: &RegistryConfig, name: &str, user: &AuthenticatedUser) -> bool {
+    user_namespace(config, user).is_some_and(|personal| {
+        repository_defaults(config, name, Some(user)).namespace.as_deref() == Some(personal.as_str())
+    })
+}
+
+/// Work out the defaults for a repository created implicitly by `user` pushing to `name`.
+/// Namespaced names (`team/app`) keep their namespace; bare names are filed under the
+/// pusher's personal namespace, which may carry its own default visibility.
+pub fn repository_defaults(config: &RegistryConfig, name: &str, user: Option<&AuthenticatedUser>) -> RepositoryDefaults {
+    let personal = user.and_then(|u| user_namespace(config, u));
+    let namespace = match name.rsplit_once('/') {
+        Some((namespace, _)) => Some(namespace.to_string()),
+        None => personal.clone(),
+    };
+
+    let in_personal_namespace = personal.is_some() && namespace == personal;
+    let visibility = match config.user_namespace_visibility {
+        Some(visibility) if in_personal_namespace => visibility,
+        _ => config.default_visibility,
+    };
+
+    RepositoryDefaults {
+        namespace,
+        is_public: visibility.is_public(),
+        owner_id: user.filter(|u| !u.is_robot()).map(|u| u.id.clone()),
+    }
+}
+
+/// Every user with an admin-set request rate, as (user id, requests per second)
+pub async fn list_rate_limit_overrides(pool: &SqlitePool) -> Result<Vec<(String, u32)>> {
+    let rows: Vec<(String, i64)> =
+        sqlx::query_as("SELECT id, rate_limit_override FROM users WHERE rate_limit_override IS NOT NULL AND is_active = TRUE")
+            .fetch_all(pool)
+            .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(user_id, limit)| (user_id, limit.clamp(0, u32::MAX as i64) as u32))
+        .collect())
+}
+
+/// Create a repository
+pub async fn create_repository(
+    state: &AppState,
+    name: &str,
+    description: &str,
+    is_public: bool,
+    namespace: Option<&str>,
+    owner_id: Option<&str>,
+) -> Result<Repository> {
+    let repo_id = Uuid::new_v4();
+    let now = chrono::Utc::now();
+    
+    sqlx::query(
+        "INSERT INTO repositories (id, name, namespace, description, is_public, owner_id, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+    )
+    .bind(repo_id)
+    .bind(name)
+    .bind(namespace)
+    .bind(description)
+    .bind(is_public)
+    .bind(owner_id)
+    .bind(now)
+    .bind(now)
+    .execute(&state.database.pool)
+    .await?;
+    
+    Ok(Repository {
+        id: repo_id,
+        name: name.to_string(),
+        namespace: namespace.map(str::to_string),
+        description: description.to_string(),
+        is_public,
+        created_at: now,
+        updated_at: now,
+    })
+}
+
+/// Artifact kinds a repository accepts, or `None` when it accepts any
+pub async fn get_allowed_artifact_types(state: &AppState, repository_id: &Uuid) -> Result<Option<Vec<ArtifactType>>> {
+    let stored: Option<String> = sqlx::query_scalar("SELECT allowed_artifact_types FROM repositories WHERE id = $1")
+        .bind(repository_id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .flatten();
+
+    Ok(stored.map(|list| list.split(',').filter_map(ArtifactType::parse).collect()))
+}
+
+/// Restrict a repository to the given artifact kinds; `None` accepts any
+pub async fn set_allowed_artifact_types(
+    state: &AppState,
+    repository_id: &Uuid,
+    allowed: Option<&[ArtifactType]>,
+) -> Result<()> {
+    let stored = allowed.map(|types| types.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(","));
+    sqlx::query("UPDATE repositories SET allowed_artifact_types = $1, updated_at = $2 WHERE id = $3")
+        .bind(stored)
+        .bind(chrono::Utc::now())
+        .bind(repository_id)
+        .execute(&state.database.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Platform a repository serves from manifest lists to clients that can't take them, if overridden
+pub async fn get_default_platform(state: &AppState, repository_id: &Uuid) -> Result<Option<String>> {
+    let platform: Option<String> = sqlx::query_scalar("SELECT default_platform FROM repositories WHERE id = $1")
+        .bind(repository_id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .flatten();
+
+    Ok(platform)
+}
+
+/// Override the registry's default platform for a repository; `None` restores the global default
+pub async fn set_default_platform(state: &AppState, repository_id: &Uuid, platform: Option<&str>) -> Result<()> {
+    sqlx::query("UPDATE repositories SET default_platform = $1, updated_at = $2 WHERE id = $3")
+        .bind(platform)
+        .bind(chrono::Utc::now())
+        .bind(repository_id)
+        .execute(&state.database.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Whether deletes in a repository are currently refused
+pub async fn is_deletion_protected(state: &AppState, repository_id: &Uuid) -> Result<bool> {
+    let protected: Option<bool> = sqlx::query_scalar("SELECT deletion_protected FROM repositories WHERE id = $1")
+        .bind(repository_id)
+        .fetch_optional(&state.database.pool)
+        .await?;
+
+    Ok(protected.unwrap_or(false))
+}
+
+/// Turn deletion protection on or off for a repository
+pub async fn set_deletion_protected(state: &AppState, repository_id: &Uuid, protected: bool) -> Result<()> {
+    sqlx::query("UPDATE repositories SET deletion_protected = $1, updated_at = $2 WHERE id = $3")
+        .bind(protected)
+        .bind(chrono::Utc::now())
+        .bind(repository_id)
+        .execute(&state.database.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Refuse to delete anything from a deletion-protected repository
+pub async fn ensure_not_deletion_protected(state: &AppState, repository_id: &Uuid, name: &str) -> Result<()> {
+    if is_deletion_protected(state, repository_id).await? {
+        return Err(Error::authorization(format!(
+            "Repository '{}' is deletion protected; an admin must remove the protection first",
+            name
+        )));
+    }
+    Ok(())
+}
+
+/// Favorite a repository for a user; returns false if it already was
+pub async fn add_favorite(state: &AppState, user_id: &str, repository_id: &Uuid) -> Result<bool> {
+    let added = sqlx::query("INSERT OR IGNORE INTO user_favorites (user_id, repository_id, created_at) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(repository_id)
+        .bind(chrono::Utc::now())
+        .execute(&state.database.pool)
+        .await?
+        .rows_affected() > 0;
+
+    Ok(added)
+}
+
+/// Remove a favorite; returns false if there was none
+pub async fn remove_favorite(state: &AppState, user_id: &str, repository_id: &Uuid) -> Result<bool> {
+    let removed = sqlx::query("DELETE FROM user_favorites WHERE user_id = $1 AND repository_id = $2")
+        .bind(user_id)
+        .bind(repository_id)
+        .execute(&state.database.pool)
+        .await?
+        .rows_affected() > 0;
+
+    Ok(removed)
+}
+
+/// Ids of the repositories a user has favorited
+pub async fn favorite_repository_ids(state: &AppState, user_id: &str) -> Result<std::collections::HashSet<Uuid>> {
+    let ids: Vec<Uuid> = sqlx::query_scalar("SELECT repository_id FROM user_favorites WHERE user_id = $1")
+        .bind(user_id)
+        .fetch_all(&state.database.pool)
+        .await?;
+
+    Ok(ids.into_iter().collect())
 }
 
 /// Get blob by digest
@@ -87,6 +355,46 @@ pub async fn get_blob_by_digest(state: &AppState, repository_id: &Uuid, digest:
     })
 }
 
+/// Record a stored blob and link it to a repository; safe to repeat
+pub async fn record_blob(state: &AppState, repository: &Repository, digest: &str, size: i64) -> Result<Uuid> {
+    sqlx::query(
+        r#"
+        INSERT INTO blobs (id, digest, media_type, size, storage_path, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (digest) DO NOTHING
+        "#
+    )
+    .bind(Uuid::new_v4())
+    .bind(digest)
+    .bind("application/octet-stream")
+    .bind(size)
+    .bind(format!("blobs/{}", digest))
+    .bind(chrono::Utc::now())
+    .execute(&state.database.pool)
+    .await?;
+
+    let blob_id: Uuid = sqlx::query_scalar("SELECT id FROM blobs WHERE digest = $1")
+        .bind(digest)
+        .fetch_one(&state.database.pool)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO repository_blobs (id, repository_id, blob_id, created_at)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (repository_id, blob_id) DO NOTHING
+        "#
+    )
+    .bind(Uuid::new_v4())
+    .bind(repository.id)
+    .bind(blob_id)
+    .bind(chrono::Utc::now())
+    .execute(&state.database.pool)
+    .await?;
+
+    Ok(blob_id)
+}
+
 /// Update blob access time
 pub async fn update_blob_access_time(state: &AppState, blob_id: &Uuid) -> Result<()> {
     sqlx::query("UPDATE blobs SET last_accessed = $1 WHERE id = $2")
@@ -98,6 +406,102 @@ pub async fn update_blob_access_time(state: &AppState, blob_id: &Uuid) -> Result
     Ok(())
 }
 
+/// Insert or replace a manifest, returning the id of the stored row
+pub async fn store_manifest(
+    pool: &SqlitePool,
+    repository_id: &Uuid,
+    digest: &str,
+    media_type: &str,
+    content: &[u8],
+    artifact_type: ArtifactType,
+) -> Result<Uuid> {
+    let manifest_id: Uuid = sqlx::query_scalar(
+        r#"
+        INSERT INTO manifests (id, repository_id, digest, media_type, content, size, artifact_type, subject_digest, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        ON CONFLICT (repository_id, digest) DO UPDATE SET
+            media_type = EXCLUDED.media_type,
+            artifact_type = EXCLUDED.artifact_type,
+            subject_digest = EXCLUDED.subject_digest,
+            content = EXCLUDED.content,
+            size = EXCLUDED.size,
+            image_size = NULL
+        RETURNING id
+        "#
+    )
+    .bind(Uuid::new_v4())
+    .bind(repository_id)
+    .bind(digest)
+    .bind(media_type)
+    .bind(content)
+    .bind(content.len() as i64)
+    .bind(artifact_type.as_str())
+    .bind(manifest_subject_digest(content))
+    .bind(chrono::Utc::now())
+    .fetch_one(pool)
+    .await?;
+
+    Ok(manifest_id)
+}
+
+/// Create or move a tag to point at a manifest.
+/// Callers hold the tag's `TagLocks` entry so concurrent pushes apply in arrival order.
+pub async fn tag_manifest(pool: &SqlitePool, repository_id: &Uuid, tag: &str, manifest_id: Uuid) -> Result<()> {
+    let now = chrono::Utc::now();
+    sqlx::query(
+        r#"
+        INSERT INTO tags (id, repository_id, name, manifest_id, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (repository_id, name) DO UPDATE SET
+            manifest_id = EXCLUDED.manifest_id,
+            updated_at = EXCLUDED.updated_at
+        "#
+    )
+    .bind(Uuid::new_v4())
+    .bind(repository_id)
+    .bind(tag)
+    .bind(manifest_id)
+    .bind(now)
+    .bind(now)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Link manifest to blob, returning false when no blob with that digest is stored
+pub async fn link_manifest_to_blob(state: &AppState, manifest_id: Uuid, blob_digest: &str) -> Result<bool> {
+    // Find the blob by digest
+    let blob_id: Option<Uuid> = sqlx::query_scalar("SELECT id FROM blobs WHERE digest = $1")
+        .bind(blob_digest)
+        .fetch_optional(&state.database.pool)
+        .await?;
+
+    match blob_id {
+        Some(blob_id) => {
+            // Re-pushing a manifest keeps its id, so the link may already exist
+            sqlx::query(
+                r#"
+                INSERT INTO manifest_blobs (id, manifest_id, blob_id, created_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (manifest_id, blob_id) DO NOTHING
+                "#
+            )
+            .bind(Uuid::new_v4())
+            .bind(manifest_id)
+            .bind(blob_id)
+            .bind(chrono::Utc::now())
+            .execute(&state.database.pool)
+            .await?;
+            Ok(true)
+        }
+        None => {
+            tracing::warn!("Referenced blob {} not found when linking to manifest", blob_digest);
+            Ok(false)
+        }
+    }
+}
+
 /// Get manifest by digest
 pub async fn get_manifest_by_digest(state: &AppState, repository_id: &Uuid, digest: &str) -> Result<Manifest> {
     let row = sqlx::query(
@@ -120,6 +524,48 @@ pub async fn get_manifest_by_digest(state: &AppState, repository_id: &Uuid, dige
     })
 }
 
+/// Manifests in a repository whose `subject` is `subject_digest`, oldest first
+pub async fn list_referrers(pool: &SqlitePool, repository_id: &Uuid, subject_digest: &str) -> Result<Vec<Manifest>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, repository_id, digest, media_type, content, size, created_at
+        FROM manifests
+        WHERE repository_id = $1 AND subject_digest = $2
+        ORDER BY created_at, digest
+        "#
+    )
+    .bind(repository_id)
+    .bind(subject_digest)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| Manifest {
+            id: row.get("id"),
+            repository_id: row.get("repository_id"),
+            digest: row.get("digest"),
+            media_type: row.get("media_type"),
+            content: row.get("content"),
+            size: row.get("size"),
+            created_at: row.get("created_at"),
+        })
+        .collect())
+}
+
+/// The `subject` a stored manifest is attached to, if any
+pub async fn get_manifest_subject(pool: &SqlitePool, repository_id: &Uuid, digest: &str) -> Result<Option<String>> {
+    let subject: Option<Option<String>> = sqlx::query_scalar(
+        "SELECT subject_digest FROM manifests WHERE repository_id = $1 AND digest = $2"
+    )
+    .bind(repository_id)
+    .bind(digest)
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(subject.flatten())
+}
+
 /// Get manifest by tag
 pub async fn get_manifest_by_tag(state: &AppState, repository_id: &Uuid, tag: &str) -> Result<Manifest> {
     let row = sqlx::query(
@@ -147,34 +593,197 @@ pub async fn get_manifest_by_tag(state: &AppState, repository_id: &Uuid, tag: &s
     })
 }
 
+/// Total image size of a manifest: config plus layer blobs, summed across every
+/// platform for manifest lists. Cached on the manifest row once computed.
+pub async fn get_manifest_image_size(state: &AppState, repository_id: &Uuid, digest: &str) -> Result<i64> {
+    let mut path = Vec::new();
+    manifest_image_size(state, repository_id, digest, &mut path).await
+}
+
+fn manifest_image_size<'a>(
+    state: &'a AppState,
+    repository_id: &'a Uuid,
+    digest: &'a str,
+    path: &'a mut Vec<String>,
+) -> Pin<Box<dyn Future<Output = Result<i64>> + Send + 'a>> {
+    Box::pin(async move {
+        let row = sqlx::query(
+            "SELECT id, media_type, content, image_size FROM manifests WHERE repository_id = $1 AND digest = $2"
+        )
+        .bind(repository_id)
+        .bind(digest)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Manifest '{}' not found", digest)))?;
+
+        if let Some(size) = row.get::<Option<i64>, _>("image_size") {
+            return Ok(size);
+        }
+
+        let manifest_id: Uuid = row.get("id");
+        let media_type: String = row.get("media_type");
+        let content: Vec<u8> = row.get("content");
+
+        let children = manifest_list_children(&media_type, &content);
+        let size = if children.is_empty() {
+            sqlx::query_scalar(
+                r#"
+                SELECT COALESCE(SUM(b.size), 0)
+                FROM manifest_blobs mb
+                JOIN blobs b ON b.id = mb.blob_id
+                WHERE mb.manifest_id = $1
+                "#
+            )
+            .bind(manifest_id)
+            .fetch_one(&state.database.pool)
+            .await?
+        } else {
+            path.push(digest.to_string());
+            check_manifest_list_nesting(path, &children, state.config.registry.max_manifest_list_depth)?;
+
+            let mut total = 0i64;
+            for child in &children {
+                match manifest_image_size(state, repository_id, child, path).await {
+                    Ok(size) => total += size,
+                    // Platforms that were never pushed here contribute nothing
+                    Err(Error::NotFound { .. }) => {}
+                    Err(e) => return Err(e),
+                }
+            }
+            path.pop();
+            total
+        };
+
+        sqlx::query("UPDATE manifests SET image_size = $1 WHERE id = $2")
+            .bind(size)
+            .bind(manifest_id)
+            .execute(&state.database.pool)
+            .await?;
+
+        Ok(size)
+    })
+}
+
+/// Looks up manifests by digest while walking manifest lists
+#[async_trait]
+pub trait ManifestLookup: Send + Sync {
+    /// Media type and content of a manifest, or None if it doesn't exist
+    async fn lookup(&self, digest: &str) -> Result<Option<(String, Vec<u8>)>>;
+}
+
+/// Manifests stored in one repository
+pub struct RepositoryManifests<'a> {
+    pub pool: &'a SqlitePool,
+    pub repository_id: &'a Uuid,
+}
+
+#[async_trait]
+impl ManifestLookup for RepositoryManifests<'_> {
+    async fn lookup(&self, digest: &str) -> Result<Option<(String, Vec<u8>)>> {
+        let row = sqlx::query("SELECT media_type, content FROM manifests WHERE repository_id = $1 AND digest = $2")
+            .bind(self.repository_id)
+            .bind(digest)
+            .fetch_optional(self.pool)
+            .await?;
+
+        Ok(row.map(|row| (row.get("media_type"), row.get("content"))))
+    }
+}
+
+/// Reject a manifest list whose children would close a cycle or nest too deeply.
+/// `path` holds the digests of the lists from the root down to the current one.
+fn check_manifest_list_nesting(path: &[String], children: &[String], max_depth: usize) -> Result<()> {
+    if path.len() > max_depth {
+        return Err(Error::manifest_invalid(format!(
+            "Manifest lists are nested deeper than the maximum of {}",
+            max_depth
+        )));
+    }
+    if let Some(child) = children.iter().find(|child| path.contains(child)) {
+        return Err(Error::manifest_invalid(format!("Manifest list cycle through {}", child)));
+    }
+    Ok(())
+}
+
+/// Walk the manifests referenced by a manifest list or OCI index, rejecting cycles and
+/// nesting deeper than `max_depth`. With `require_children`, every referenced manifest
+/// must already exist.
+pub async fn check_manifest_list_graph(
+    lookup: &dyn ManifestLookup,
+    digest: &str,
+    media_type: &str,
+    content: &[u8],
+    max_depth: usize,
+    require_children: bool,
+) -> Result<()> {
+    let mut path = vec![digest.to_string()];
+    walk_manifest_list(lookup, media_type, content, max_depth, require_children, &mut path).await
+}
+
+fn walk_manifest_list<'a>(
+    lookup: &'a dyn ManifestLookup,
+    media_type: &'a str,
+    content: &'a [u8],
+    max_depth: usize,
+    require_children: bool,
+    path: &'a mut Vec<String>,
+) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>> {
+    Box::pin(async move {
+        let children = manifest_list_children(media_type, content);
+        if children.is_empty() {
+            return Ok(());
+        }
+        check_manifest_list_nesting(path, &children, max_depth)?;
+
+        for child in children {
+            match lookup.lookup(&child).await? {
+                Some((child_type, child_content)) => {
+                    path.push(child);
+                    walk_manifest_list(lookup, &child_type, &child_content, max_depth, require_children, path).await?;
+                    path.pop();
+                }
+                None if require_children => {
+                    return Err(Error::manifest_invalid(format!(
+                        "Manifest list references unknown manifest {}",
+                        child
+                    )));
+                }
+                None => {}
+            }
+        }
+
+        Ok(())
+    })
+}
+
 /// Delete manifest by digest
 pub async fn delete_manifest_by_digest(state: &AppState, repository_id: &Uuid, digest: &str) -> Result<()> {
-    // First delete associated tags
-    sqlx::query(
-        r#"
-        DELETE FROM tags 
-        WHERE repository_id = $1 AND manifest_id IN (
-            SELECT id FROM manifests WHERE repository_id = $1 AND digest = $2
-        )
-        "#
-    )
-    .bind(repository_id)
-    .bind(digest)
-    .execute(&state.database.pool)
-    .await?;
-    
-    // Then delete manifest
+    let mut tx = state.database.pool.begin().await?;
+
+    // Tags and blob links reference the manifest; its blobs are left for garbage collection
+    for statement in [
+        "DELETE FROM tags WHERE manifest_id IN (SELECT id FROM manifests WHERE repository_id = $1 AND digest = $2)",
+        "DELETE FROM manifest_blobs WHERE manifest_id IN (SELECT id FROM manifests WHERE repository_id = $1 AND digest = $2)",
+    ] {
+        sqlx::query(statement)
+            .bind(repository_id)
+            .bind(digest)
+            .execute(&mut *tx)
+            .await?;
+    }
+
     let result = sqlx::query(
         "DELETE FROM manifests WHERE repository_id = $1 AND digest = $2"
     )
     .bind(repository_id)
     .bind(digest)
-    .execute(&state.database.pool)
+    .execute(&mut *tx)
     .await?;
     
     if result.rows_affected() == 0 {
         return Err(Error::not_found(format!("Manifest '{}' not found", digest)));
     }
+    tx.commit().await?;
     
     Ok(())
 }
@@ -200,7 +809,7 @@ pub async fn delete_tag(state: &AppState, repository_id: &Uuid, tag_name: &str)
 pub async fn get_upload_session(state: &AppState, uuid: Uuid) -> Result<UploadSession> {
     let row = sqlx::query(
         r#"
-        SELECT id, uuid, repository_id, uploaded_size, storage_path, created_at, updated_at, expires_at
+        SELECT id, uuid, repository_id, uploaded_size, total_size, storage_path, created_at, updated_at, expires_at
         FROM upload_sessions 
         WHERE uuid = $1 AND expires_at > $2
         "#
@@ -216,6 +825,7 @@ pub async fn get_upload_session(state: &AppState, uuid: Uuid) -> Result<UploadSe
         uuid: row.get("uuid"),
         repository_id: row.get("repository_id"),
         uploaded_size: row.get("uploaded_size"),
+        total_size: row.get("total_size"),
         storage_path: row.get("storage_path"),
         created_at: row.get("created_at"),
         updated_at: row.get("updated_at"),
@@ -233,3 +843,368 @@ pub async fn cleanup_upload_session(state: &AppState, uuid: Uuid) -> Result<()>
     
     Ok(())
 }
+
+/// Record the durably persisted size of an upload session
+pub async fn update_upload_progress(state: &AppState, uuid: Uuid, uploaded_size: i64) -> Result<()> {
+    sqlx::query("UPDATE upload_sessions SET uploaded_size = $1, updated_at = $2 WHERE uuid = $3")
+        .bind(uploaded_size)
+        .bind(chrono::Utc::now())
+        .bind(uuid)
+        .execute(&state.database.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Record the total length a client declared for an upload session
+pub async fn set_upload_total_size(state: &AppState, uuid: Uuid, total_size: i64) -> Result<()> {
+    sqlx::query("UPDATE upload_sessions SET total_size = $1, updated_at = $2 WHERE uuid = $3")
+        .bind(total_size)
+        .bind(chrono::Utc::now())
+        .bind(uuid)
+        .execute(&state.database.pool)
+        .await?;
+
+    Ok(())
+}
+
+/// Append an entry to the audit log
+pub async fn record_audit(
+    state: &AppState,
+    actor_id: &str,
+    action: &str,
+    target: &str,
+    details: Option<serde_json::Value>,
+) -> Result<()> {
+    insert_audit(&state.database.pool, actor_id, action, target, details).await
+}
+
+/// Record an audit entry as part of a transaction, so it commits or rolls back with the change
+pub async fn record_audit_in(
+    tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    actor_id: &str,
+    action: &str,
+    target: &str,
+    details: Option<serde_json::Value>,
+) -> Result<()> {
+    insert_audit(&mut **tx, actor_id, action, target, details).await
+}
+
+async fn insert_audit<'e, E: sqlx::SqliteExecutor<'e>>(
+    executor: E,
+    actor_id: &str,
+    action: &str,
+    target: &str,
+    details: Option<serde_json::Value>,
+) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO audit_log (id, actor_id, action, target, details, created_at) VALUES ($1, $2, $3, $4, $5, $6)"
+    )
+    .bind(Uuid::new_v4())
+    .bind(actor_id)
+    .bind(action)
+    .bind(target)
+    .bind(details.map(|d| d.to_string()))
+    .bind(chrono::Utc::now())
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete audit entries recorded before `cutoff`, returning how many were removed
+pub async fn purge_audit_log(pool: &SqlitePool, cutoff: chrono::DateTime<chrono::Utc>) -> Result<u64> {
+    let result = sqlx::query("DELETE FROM audit_log WHERE created_at < $1")
+        .bind(cutoff)
+        .execute(pool)
+        .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Kind of registry usage recorded in the daily stats buckets
+#[derive(Debug, Clone, Copy)]
+pub enum UsageEvent {
+    Pull { bytes: i64 },
+    Push { bytes: i64 },
+    BytesPulled(i64),
+    BytesPushed(i64),
+}
+
+/// Increment today's usage bucket for a repository
+pub async fn record_usage(state: &AppState, repository_id: &Uuid, event: UsageEvent) -> Result<()> {
+    let (pulls, pushes, bytes_pulled, bytes_pushed) = match event {
+        UsageEvent::Pull { bytes } => (1i64, 0i64, bytes, 0i64),
+        UsageEvent::Push { bytes } => (0, 1, 0, bytes),
+        UsageEvent::BytesPulled(bytes) => (0, 0, bytes, 0),
+        UsageEvent::BytesPushed(bytes) => (0, 0, 0, bytes),
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO usage_stats (repository_id, day, pulls, pushes, bytes_pulled, bytes_pushed)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (repository_id, day) DO UPDATE SET
+            pulls = pulls + EXCLUDED.pulls,
+            pushes = pushes + EXCLUDED.pushes,
+            bytes_pulled = bytes_pulled + EXCLUDED.bytes_pulled,
+            bytes_pushed = bytes_pushed + EXCLUDED.bytes_pushed
+        "#
+    )
+    .bind(repository_id)
+    .bind(chrono::Utc::now().date_naive())
+    .bind(pulls)
+    .bind(pushes)
+    .bind(bytes_pulled)
+    .bind(bytes_pushed)
+    .execute(&state.database.pool)
+    .await?;
+
+    if pulls > 0 || pushes > 0 {
+        sqlx::query("UPDATE repositories SET pull_count = pull_count + $1, push_count = push_count + $2 WHERE id = $3")
+            .bind(pulls)
+            .bind(pushes)
+            .bind(repository_id)
+            .execute(&state.database.pool)
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, RepositoryVisibility};
+    use crate::utils::sha256_digest;
+
+    #[test]
+    fn test_push_creates_repository_when_allowed() {
+        let config = Config::default();
+        assert!(ensure_push_may_create(&config.registry, "team/app").is_ok());
+    }
+
+    #[test]
+    fn test_push_to_unknown_repository_rejected_when_disabled() {
+        let mut config = Config::default();
+        config.registry.allow_push_creates_repository = false;
+
+        let err = ensure_push_may_create(&config.registry, "team/typo").unwrap_err();
+        assert_eq!(err.status_code(), axum::http::StatusCode::NOT_FOUND);
+        assert_eq!(err.error_code(), "NAME_UNKNOWN");
+    }
+
+    fn pusher(name: &str) -> AuthenticatedUser {
+        AuthenticatedUser {
+            id: format!("{}-id", name),
+            name: name.to_string(),
+            email: String::new(),
+            scopes: vec!["registry:write".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_repository_limit_override_and_unlimited() {
+        assert_eq!(repository_limit(None, 0), None);
+        assert_eq!(repository_limit(None, 10), Some(10));
+        assert_eq!(repository_limit(Some(25), 10), Some(25));
+        // An override of 0 lifts the default for that user
+        assert_eq!(repository_limit(Some(0), 10), None);
+    }
+
+    #[test]
+    fn test_repository_defaults_personal_namespace() {
+        let mut config = Config::default();
+        config.registry.user_namespace_visibility = Some(RepositoryVisibility::Public);
+        let alice = pusher("alice");
+
+        // Bare names land in the pusher's namespace with its visibility
+        let defaults = repository_defaults(&config.registry, "app", Some(&alice));
+        assert_eq!(defaults.namespace.as_deref(), Some("alice"));
+        assert!(defaults.is_public);
+        assert_eq!(defaults.owner_id.as_deref(), Some("alice-id"));
+
+        assert_eq!(
+            repository_defaults(&config.registry, "alice/tools/cli", Some(&alice)).namespace.as_deref(),
+            Some("alice/tools")
+        );
+        assert!(repository_defaults(&config.registry, "alice/app", Some(&alice)).is_public);
+
+        // Someone else's namespace gets the registry-wide default
+        let defaults = repository_defaults(&config.registry, "team/app", Some(&alice));
+        assert_eq!(defaults.namespace.as_deref(), Some("team"));
+        assert!(!defaults.is_public);
+    }
+
+    #[test]
+    fn test_repository_defaults_without_user() {
+        let mut config = Config::default();
+        config.registry.default_visibility = RepositoryVisibility::Public;
+
+        let defaults = repository_defaults(&config.registry, "app", None);
+        assert_eq!(defaults.namespace, None);
+        assert!(defaults.is_public);
+        assert_eq!(defaults.owner_id, None);
+    }
+
+    /// In-memory manifests keyed by digest
+    struct FakeManifests(std::collections::HashMap<String, (String, Vec<u8>)>);
+
+    #[async_trait]
+    impl ManifestLookup for FakeManifests {
+        async fn lookup(&self, digest: &str) -> Result<Option<(String, Vec<u8>)>> {
+            Ok(self.0.get(digest).cloned())
+        }
+    }
+
+    fn index(children: &[&str]) -> (String, Vec<u8>) {
+        let manifests: Vec<_> = children
+            .iter()
+            .map(|digest| serde_json::json!({"mediaType": media_types::OCI_INDEX, "digest": digest}))
+            .collect();
+        let content = serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": media_types::OCI_INDEX,
+            "manifests": manifests,
+        });
+        (media_types::OCI_INDEX.to_string(), content.to_string().into_bytes())
+    }
+
+    #[tokio::test]
+    async fn test_manifest_round_trips_byte_for_byte() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::database::migrations::create_tables(&pool).await.unwrap();
+
+        let owner_id = Uuid::new_v4();
+        let repository_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users (id, username, email) VALUES ($1, 'alice', 'alice@example.com')")
+            .bind(owner_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO repositories (id, name, owner_id) VALUES ($1, 'app', $2)")
+            .bind(repository_id)
+            .bind(owner_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Key order, indentation and trailing newline must all survive the round trip
+        let pushed = b"{\n   \"schemaVersion\": 2,\n\t\"mediaType\": \"application/vnd.oci.image.manifest.v1+json\",\n   \"layers\": [ ]\n}\n";
+        let digest = sha256_digest(pushed);
+        store_manifest(&pool, &repository_id, &digest, media_types::OCI_MANIFEST, pushed, ArtifactType::Image)
+            .await
+            .unwrap();
+
+        let manifests = RepositoryManifests { pool: &pool, repository_id: &repository_id };
+        let (media_type, pulled) = manifests.lookup(&digest).await.unwrap().unwrap();
+        assert_eq!(media_type, media_types::OCI_MANIFEST);
+        assert_eq!(pulled, pushed);
+        assert_eq!(sha256_digest(&pulled), digest);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_pushes_to_one_tag_end_on_last_applied() {
+        use crate::{config::ConcurrencyConfig, performance::TagLocks};
+        use std::sync::{Arc, Mutex};
+
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::database::migrations::create_tables(&pool).await.unwrap();
+        let owner_id = Uuid::new_v4();
+        let repository_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users (id, username, email) VALUES ($1, 'alice', 'alice@example.com')")
+            .bind(owner_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO repositories (id, name, owner_id) VALUES ($1, 'team/app', $2)")
+            .bind(repository_id)
+            .bind(owner_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let locks = Arc::new(TagLocks::new(&ConcurrencyConfig::default()));
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        let pushes: Vec<_> = (0..24)
+            .map(|i| {
+                let (pool, locks, applied) = (pool.clone(), Arc::clone(&locks), Arc::clone(&applied));
+                tokio::spawn(async move {
+                    let content = format!(r#"{{"schemaVersion":2,"layers":[],"annotations":{{"build":"{}"}}}}"#, i);
+                    let digest = sha256_digest(content.as_bytes());
+
+                    let _guard = locks.lock("team/app", "latest").await.unwrap();
+                    let manifest_id = store_manifest(
+                        &pool,
+                        &repository_id,
+                        &digest,
+                        media_types::OCI_MANIFEST,
+                        content.as_bytes(),
+                        ArtifactType::Image,
+                    )
+                    .await
+                    .unwrap();
+                    tokio::task::yield_now().await;
+                    tag_manifest(&pool, &repository_id, "latest", manifest_id).await.unwrap();
+                    applied.lock().unwrap().push(digest);
+                })
+            })
+            .collect();
+        for push in pushes {
+            push.await.unwrap();
+        }
+
+        let tagged: String = sqlx::query_scalar(
+            "SELECT m.digest FROM tags t JOIN manifests m ON m.id = t.manifest_id WHERE t.repository_id = $1 AND t.name = 'latest'",
+        )
+        .bind(repository_id)
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+        assert_eq!(Some(&tagged), applied.lock().unwrap().last());
+        assert_eq!(locks.tracked(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_self_referential_index_rejected() {
+        let (media_type, content) = index(&["sha256:self"]);
+        let lookup = FakeManifests([("sha256:self".to_string(), (media_type.clone(), content.clone()))].into());
+
+        let err = check_manifest_list_graph(&lookup, "sha256:self", &media_type, &content, 4, false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error_code(), "MANIFEST_INVALID");
+    }
+
+    #[tokio::test]
+    async fn test_over_deep_index_chain_rejected() {
+        // sha256:0 -> sha256:1 -> ... -> sha256:5, each an index
+        let mut manifests = std::collections::HashMap::new();
+        for level in 0..5 {
+            manifests.insert(format!("sha256:{}", level), index(&[format!("sha256:{}", level + 1).as_str()]));
+        }
+        manifests.insert("sha256:5".to_string(), index(&[]));
+        let lookup = FakeManifests(manifests);
+        let (media_type, content) = lookup.0["sha256:0"].clone();
+
+        let err = check_manifest_list_graph(&lookup, "sha256:0", &media_type, &content, 4, false)
+            .await
+            .unwrap_err();
+        assert_eq!(err.error_code(), "MANIFEST_INVALID");
+
+        assert!(check_manifest_list_graph(&lookup, "sha256:0", &media_type, &content, 5, false).await.is_ok());
+
+        // Missing children only matter when they are required
+        let (media_type, content) = index(&["sha256:missing"]);
+        assert!(check_manifest_list_graph(&lookup, "sha256:x", &media_type, &content, 4, false).await.is_ok());
+        assert!(check_manifest_list_graph(&lookup, "sha256:x", &media_type, &content, 4, true).await.is_err());
+    }
+}
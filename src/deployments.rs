@@ -0,0 +1,738 @@
+use axum::{
+    extract::{Query, State},
+    routing::get,
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use std::{process::Stdio, sync::Arc};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
+    process::Command,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    error::{Error, Result},
+    performance::OperationClass,
+    server::AppState,
+    types::{Page, PageQuery},
+    websocket::{self, WebSocketState},
+};
+
+/// Deployment registry for Docker Compose stacks
+/// Each deployment is a `docker compose` project named after its deployment id
+
+/// Lifecycle of a stack deployment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeploymentStatus {
+    Deploying,
+    Running,
+    Undeploying,
+    Stopped,
+    Failed,
+}
+
+impl DeploymentStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeploymentStatus::Deploying => "deploying",
+            DeploymentStatus::Running => "running",
+            DeploymentStatus::Undeploying => "undeploying",
+            DeploymentStatus::Stopped => "stopped",
+            DeploymentStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "deploying" => Some(DeploymentStatus::Deploying),
+            "running" => Some(DeploymentStatus::Running),
+            "undeploying" => Some(DeploymentStatus::Undeploying),
+            "stopped" => Some(DeploymentStatus::Stopped),
+            "failed" => Some(DeploymentStatus::Failed),
+            _ => None,
+        }
+    }
+
+    /// Whether the deployment may still own containers
+    pub fn is_active(&self) -> bool {
+        matches!(self, DeploymentStatus::Deploying | DeploymentStatus::Running | DeploymentStatus::Undeploying)
+    }
+
+    /// Status as announced on the `stack_deployments` WebSocket topic
+    fn broadcast_status(&self) -> websocket::DeploymentStatus {
+        match self {
+            DeploymentStatus::Deploying => websocket::DeploymentStatus::Deploying,
+            DeploymentStatus::Running => websocket::DeploymentStatus::Running,
+            DeploymentStatus::Undeploying => websocket::DeploymentStatus::Stopping,
+            DeploymentStatus::Stopped => websocket::DeploymentStatus::Stopped,
+            DeploymentStatus::Failed => websocket::DeploymentStatus::Failed,
+        }
+    }
+}
+
+/// A recorded deployment of a stack
+#[derive(Debug, Clone, Serialize)]
+pub struct Deployment {
+    pub deployment_id: Uuid,
+    pub stack_id: String,
+    pub status: DeploymentStatus,
+    pub started_by: String,
+    pub started_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Output of the failed compose command, when status is `failed`
+    pub error: Option<String>,
+    /// Deployment whose compose snapshot this one rolled back to
+    pub rolled_back_from: Option<Uuid>,
+}
+
+impl Deployment {
+    /// Compose project owning this deployment's containers, networks and volumes
+    pub fn project(&self) -> String {
+        compose_project(&self.deployment_id)
+    }
+}
+
+/// State of one service container as reported by `docker compose ps`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub state: String,
+    pub status: String,
+}
+
+/// What to bring up for a new deployment
+pub struct DeployRequest<'a> {
+    pub stack_id: &'a str,
+    /// Compose file to run; stored with the deployment so it can be rolled back to
+    pub compose_content: String,
+    pub started_by: &'a str,
+    pub rolled_back_from: Option<Uuid>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeploymentQuery {
+    pub stack_id: Option<String>,
+    pub status: Option<DeploymentStatus>,
+}
+
+pub fn compose_project(deployment_id: &Uuid) -> String {
+    format!("ghostdock-{}", deployment_id.simple())
+}
+
+/// Deployment routes
+pub fn deployment_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/deployments", get(list_deployments))
+}
+
+/// List deployments, newest first; admins see every deployment, others only their own
+async fn list_deployments(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Query(query): Query<DeploymentQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Deployment>>> {
+    let is_admin = !user.is_robot() && user.scopes.iter().any(|s| s == "admin");
+    let (limit, offset) = page.bounds()?;
+
+    let total: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*) FROM deployments
+        WHERE ($1 OR started_by = $2)
+          AND ($3 IS NULL OR stack_id = $3)
+          AND ($4 IS NULL OR status = $4)
+        "#
+    )
+    .bind(is_admin)
+    .bind(&user.id)
+    .bind(&query.stack_id)
+    .bind(query.status.map(|s| s.as_str()))
+    .fetch_one(&state.database.pool)
+    .await?;
+
+    let deployments = sqlx::query(
+        r#"
+        SELECT * FROM deployments
+        WHERE ($1 OR started_by = $2)
+          AND ($3 IS NULL OR stack_id = $3)
+          AND ($4 IS NULL OR status = $4)
+        ORDER BY started_at DESC
+        LIMIT $5 OFFSET $6
+        "#
+    )
+    .bind(is_admin)
+    .bind(&user.id)
+    .bind(&query.stack_id)
+    .bind(query.status.map(|s| s.as_str()))
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(&state.database.pool)
+    .await?
+    .iter()
+    .map(deployment_from_row)
+    .collect();
+
+    Ok(Json(Page::new(deployments, total as u64, limit, offset)))
+}
+
+/// Most recent deployment of a stack
+pub async fn latest_for_stack(pool: &SqlitePool, stack_id: &str) -> Result<Option<Deployment>> {
+    let row = sqlx::query("SELECT * FROM deployments WHERE stack_id = $1 ORDER BY started_at DESC LIMIT 1")
+        .bind(stack_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.as_ref().map(deployment_from_row))
+}
+
+/// A deployment together with the compose file it ran
+pub async fn snapshot(pool: &SqlitePool, deployment_id: Uuid) -> Result<Option<(Deployment, String)>> {
+    let row = sqlx::query("SELECT * FROM deployments WHERE deployment_id = $1")
+        .bind(deployment_id)
+        .fetch_optional(pool)
+        .await?;
+
+    Ok(row.map(|row| (deployment_from_row(&row), row.get("compose_content"))))
+}
+
+/// Record a new deployment and bring its compose project up in the background
+pub async fn start(state: &AppState, request: DeployRequest<'_>) -> Result<Deployment> {
+    let _starting = state.performance.deployment_starts.lock().await;
+    if let Some(existing) = latest_for_stack(&state.database.pool, request.stack_id).await? {
+        if existing.status.is_active() {
+            return Err(Error::conflict(format!(
+                "Stack {} is already deployed as {}",
+                request.stack_id, existing.deployment_id
+            )));
+        }
+    }
+    check_user_limit(state, request.started_by, None).await?;
+
+    launch(state, request, None).await
+}
+
+/// Replace whatever is running for a stack with a new deployment of `request`
+///
+/// The current deployment, if any, is torn down first in the same background
+/// task, so the two never run side by side and contend for ports.
+pub async fn replace(state: &AppState, request: DeployRequest<'_>, current: Option<Deployment>) -> Result<Deployment> {
+    let _starting = state.performance.deployment_starts.lock().await;
+
+    // The caller authorized against `current`; refuse if another deploy got in since
+    let latest = latest_for_stack(&state.database.pool, request.stack_id).await?;
+    if latest.as_ref().map(|d| d.deployment_id) != current.as_ref().map(|d| d.deployment_id) {
+        return Err(Error::conflict(format!("Stack {} was redeployed meanwhile; try again", request.stack_id)));
+    }
+    let current = latest.filter(|d| d.status.is_active());
+    if let Some(current) = &current {
+        if current.status == DeploymentStatus::Undeploying {
+            return Err(Error::conflict(format!("Deployment {} is still being torn down", current.deployment_id)));
+        }
+    }
+    check_user_limit(state, request.started_by, current.as_ref()).await?;
+
+    if let Some(current) = &current {
+        set_status(&state.database.pool, current.deployment_id, DeploymentStatus::Undeploying, None).await?;
+    }
+
+    launch(state, request, current).await
+}
+
+/// Refuse callers outside the deployment policy: registry admins, plus the users and team
+/// members listed under `[deployments]`. Robots and shares never deploy.
+pub async fn ensure_may_deploy(state: &AppState, user: &AuthenticatedUser) -> Result<()> {
+    let denied = || Err(Error::authorization("Deploying stacks is limited to admins and [deployments] allowed users"));
+    if user.is_robot() || user.is_share() {
+        return denied();
+    }
+    if user.scopes.iter().any(|s| s == "admin") {
+        return Ok(());
+    }
+
+    let policy = &state.config.deployments;
+    if policy.allowed_users.contains(&user.name) {
+        return Ok(());
+    }
+    if !policy.allowed_teams.is_empty() {
+        let teams: Vec<String> = sqlx::query_scalar(
+            "SELECT t.name FROM teams t JOIN team_members m ON m.team_id = t.id WHERE m.user_id = $1"
+        )
+        .bind(&user.id)
+        .fetch_all(&state.database.pool)
+        .await?;
+        if teams.iter().any(|team| policy.allowed_teams.contains(team)) {
+            return Ok(());
+        }
+    }
+
+    denied()
+}
+
+/// Settings that would hand a deployed container control of the host: privileged mode,
+/// extra capabilities or devices, host namespaces, and host paths mounted or read in.
+/// Each entry names the service (or top-level key) and the setting.
+pub fn host_access_violations(compose: &serde_yaml::Value) -> Vec<String> {
+    let mut violations = vec![];
+
+    if let Some(services) = compose.get("services").and_then(|s| s.as_mapping()) {
+        for (name, service) in services {
+            let name = name.as_str().unwrap_or_default();
+            let mut violation = |setting: &str| violations.push(format!("service '{}': {}", name, setting));
+
+            if service.get("privileged").and_then(|p| p.as_bool()).unwrap_or(false) {
+                violation("privileged mode");
+            }
+            for key in ["cap_add", "devices", "device_cgroup_rules", "env_file"] {
+                if service.get(key).is_some() {
+                    violation(key);
+                }
+            }
+            for key in ["network_mode", "pid", "ipc", "uts", "userns_mode"] {
+                if service.get(key).and_then(|v| v.as_str()) == Some("host") {
+                    violation(&format!("{}: host", key));
+                }
+            }
+            let security_opts = service.get("security_opt").and_then(|o| o.as_sequence());
+            if security_opts.into_iter().flatten().filter_map(|o| o.as_str()).any(|o| o.contains("unconfined")) {
+                violation("unconfined security_opt");
+            }
+            let volumes = service.get("volumes").and_then(|v| v.as_sequence());
+            if volumes.into_iter().flatten().any(is_host_mount) {
+                violation("host bind mount");
+            }
+        }
+    }
+
+    // Named volumes can bind a host path through the local driver, and file-backed
+    // secrets and configs copy host files into the container
+    for (section, key) in [("volumes", "driver_opts"), ("secrets", "file"), ("configs", "file")] {
+        if let Some(entries) = compose.get(section).and_then(|s| s.as_mapping()) {
+            for (name, entry) in entries {
+                if entry.get(key).is_some() {
+                    violations.push(format!("{} '{}': {}", section, name.as_str().unwrap_or_default(), key));
+                }
+            }
+        }
+    }
+
+    violations
+}
+
+/// Whether a service volume entry mounts a path from the host. Named volumes and
+/// anonymous container paths are fine; paths, `~` and variables in the source are not.
+fn is_host_mount(volume: &serde_yaml::Value) -> bool {
+    let is_host_path = |source: &str| {
+        source.starts_with(['/', '.', '~', '$', '\\']) || source.as_bytes().get(1) == Some(&b':')
+    };
+    match volume {
+        serde_yaml::Value::String(spec) => match spec.split_once(':') {
+            Some((source, _)) => is_host_path(source),
+            None => false,
+        },
+        serde_yaml::Value::Mapping(_) => {
+            volume.get("type").and_then(|t| t.as_str()) == Some("bind")
+                || volume.get("source").and_then(|s| s.as_str()).is_some_and(is_host_path)
+        }
+        _ => false,
+    }
+}
+
+/// Reject compose content that fails to parse or asks for host access
+fn check_host_access(compose_content: &str) -> Result<()> {
+    let compose: serde_yaml::Value = serde_yaml::from_str(compose_content)
+        .map_err(|e| Error::validation(format!("YAML parsing error: {}", e)))?;
+    let violations = host_access_violations(&compose);
+    if violations.is_empty() {
+        return Ok(());
+    }
+    Err(Error::validation(format!("Stack requests host access: {}", violations.join(", "))))
+}
+
+/// Deployments counted against a user's concurrency limit
+pub async fn active_for_user(pool: &SqlitePool, user_id: &str) -> Result<Vec<Uuid>> {
+    Ok(sqlx::query_scalar(
+        "SELECT deployment_id FROM deployments WHERE started_by = $1 AND status IN ('deploying', 'running')"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?)
+}
+
+/// Refuse a new deployment once a user has `max_deployments_per_user` deploying or
+/// running; a deployment about to be replaced doesn't count. Callers hold
+/// `deployment_starts` until the new row is inserted, so the count can't go stale.
+async fn check_user_limit(state: &AppState, user_id: &str, replacing: Option<&Deployment>) -> Result<()> {
+    let concurrency = &state.config.concurrency;
+    if concurrency.max_deployments_per_user == 0 {
+        return Ok(());
+    }
+
+    let active = active_for_user(&state.database.pool, user_id).await?;
+    let counted = counted_deployments(&active, replacing.map(|d| d.deployment_id));
+
+    if counted >= concurrency.max_deployments_per_user {
+        return Err(Error::too_many_requests(
+            format!(
+                "You already have {} active deployments; undeploy one before starting another",
+                counted
+            ),
+            concurrency.retry_after_secs,
+        ));
+    }
+
+    Ok(())
+}
+
+fn counted_deployments(active: &[Uuid], replacing: Option<Uuid>) -> usize {
+    active.iter().filter(|id| Some(**id) != replacing).count()
+}
+
+async fn launch(state: &AppState, request: DeployRequest<'_>, replacing: Option<Deployment>) -> Result<Deployment> {
+    check_host_access(&request.compose_content)?;
+
+    let now = Utc::now();
+    let deployment = Deployment {
+        deployment_id: Uuid::new_v4(),
+        stack_id: request.stack_id.to_string(),
+        status: DeploymentStatus::Deploying,
+        started_by: request.started_by.to_string(),
+        started_at: now,
+        updated_at: now,
+        error: None,
+        rolled_back_from: request.rolled_back_from,
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO deployments (deployment_id, stack_id, status, started_by, started_at, updated_at, compose_content, rolled_back_from)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#
+    )
+    .bind(deployment.deployment_id)
+    .bind(&deployment.stack_id)
+    .bind(deployment.status.as_str())
+    .bind(&deployment.started_by)
+    .bind(deployment.started_at)
+    .bind(deployment.updated_at)
+    .bind(&request.compose_content)
+    .bind(deployment.rolled_back_from)
+    .execute(&state.database.pool)
+    .await?;
+
+    let pool = state.database.pool.clone();
+    let performance = state.performance.clone();
+    let logs = LogStream::new(&state.websocket, &deployment);
+    let compose_content = request.compose_content;
+    tokio::spawn(async move {
+        let _permit = match performance.acquire_operation(OperationClass::Deployment).await {
+            Ok(permit) => permit,
+            Err(e) => return finish(&pool, &logs, DeploymentStatus::Running, Err(e)).await,
+        };
+
+        if let Some(previous) = replacing {
+            let previous_logs = LogStream::new(&logs.websocket, &previous);
+            let result = compose(&previous.project(), &["down", "--volumes", "--remove-orphans"], None, Some(&previous_logs)).await;
+            let failed = result.is_err();
+            finish(&pool, &previous_logs, DeploymentStatus::Stopped, result).await;
+            if failed {
+                let error = Err(Error::conflict(format!("Deployment {} could not be torn down", previous.deployment_id)));
+                finish(&pool, &logs, DeploymentStatus::Running, error).await;
+                return;
+            }
+        }
+
+        logs.status(DeploymentStatus::Deploying, "Bringing up compose project").await;
+        let result = compose(
+            &compose_project(&logs.deployment_id),
+            &["-f", "-", "up", "--detach", "--remove-orphans"],
+            Some(&compose_content),
+            Some(&logs),
+        )
+        .await;
+        finish(&pool, &logs, DeploymentStatus::Running, result).await;
+    });
+
+    Ok(deployment)
+}
+
+/// Tear down a deployment's containers, networks and volumes in the background
+pub async fn stop(state: &AppState, deployment: &Deployment) -> Result<()> {
+    if matches!(deployment.status, DeploymentStatus::Stopped | DeploymentStatus::Undeploying) {
+        return Err(Error::conflict(format!(
+            "Deployment {} is already {}",
+            deployment.deployment_id,
+            deployment.status.as_str()
+        )));
+    }
+
+    set_status(&state.database.pool, deployment.deployment_id, DeploymentStatus::Undeploying, None).await?;
+
+    let pool = state.database.pool.clone();
+    let performance = state.performance.clone();
+    let logs = LogStream::new(&state.websocket, deployment);
+    let project = deployment.project();
+    tokio::spawn(async move {
+        let _permit = match performance.acquire_operation(OperationClass::Deployment).await {
+            Ok(permit) => permit,
+            Err(e) => return finish(&pool, &logs, DeploymentStatus::Stopped, Err(e)).await,
+        };
+
+        logs.status(DeploymentStatus::Undeploying, "Tearing down compose project").await;
+        let result = compose(&project, &["down", "--volumes", "--remove-orphans"], None, Some(&logs)).await;
+        finish(&pool, &logs, DeploymentStatus::Stopped, result).await;
+    });
+
+    Ok(())
+}
+
+/// Where a deployment's compose output and status changes are broadcast
+struct LogStream {
+    websocket: Arc<WebSocketState>,
+    stack_id: String,
+    deployment_id: Uuid,
+}
+
+impl LogStream {
+    fn new(websocket: &Arc<WebSocketState>, deployment: &Deployment) -> Self {
+        Self {
+            websocket: Arc::clone(websocket),
+            stack_id: deployment.stack_id.clone(),
+            deployment_id: deployment.deployment_id,
+        }
+    }
+
+    async fn line(&self, line: String) {
+        self.websocket
+            .broadcast_deployment_logs(self.stack_id.clone(), self.deployment_id.to_string(), line)
+            .await;
+    }
+
+    async fn status(&self, status: DeploymentStatus, message: impl Into<String>) {
+        self.websocket
+            .broadcast_stack_deployment(self.stack_id.clone(), status.broadcast_status(), message.into())
+            .await;
+    }
+}
+
+/// Live state of a deployment's service containers
+pub async fn services(deployment: &Deployment) -> Result<Vec<ServiceStatus>> {
+    let output = compose(&deployment.project(), &["ps", "--all", "--format", "json"], None, None).await?;
+    Ok(parse_compose_ps(&output))
+}
+
+async fn finish(pool: &SqlitePool, logs: &LogStream, success: DeploymentStatus, result: Result<String>) {
+    let deployment_id = logs.deployment_id;
+    let (status, error) = match result {
+        Ok(_) => (success, None),
+        Err(e) => {
+            tracing::warn!("Deployment {} failed: {}", deployment_id, e);
+            (DeploymentStatus::Failed, Some(e.to_string()))
+        }
+    };
+
+    logs.status(status, error.clone().unwrap_or_else(|| format!("Deployment {}", status.as_str()))).await;
+    if let Err(e) = set_status(pool, deployment_id, status, error).await {
+        tracing::error!("Failed to record status of deployment {}: {}", deployment_id, e);
+    }
+}
+
+async fn set_status(pool: &SqlitePool, deployment_id: Uuid, status: DeploymentStatus, error: Option<String>) -> Result<()> {
+    sqlx::query("UPDATE deployments SET status = $1, error = $2, updated_at = $3 WHERE deployment_id = $4")
+        .bind(status.as_str())
+        .bind(error)
+        .bind(Utc::now())
+        .bind(deployment_id)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// Run `docker compose` for a project, feeding `stdin` as the compose file when given.
+/// Compose reports progress on stderr, which is streamed line by line to `logs`.
+async fn compose(project: &str, args: &[&str], stdin: Option<&str>, logs: Option<&LogStream>) -> Result<String> {
+    let mut child = Command::new("docker")
+        .arg("compose")
+        .args(["--project-name", project])
+        .args(args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| Error::internal(format!("Failed to run docker compose: {}", e)))?;
+
+    if let (Some(content), Some(mut pipe)) = (stdin, child.stdin.take()) {
+        pipe.write_all(content.as_bytes()).await?;
+    }
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let read_stdout = async {
+        let mut output = String::new();
+        if let Some(mut stdout) = stdout {
+            stdout.read_to_string(&mut output).await?;
+        }
+        Ok::<_, std::io::Error>(output)
+    };
+    let read_stderr = async {
+        let mut output = String::new();
+        if let Some(stderr) = stderr {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Some(line) = lines.next_line().await? {
+                output.push_str(&line);
+                output.push('\n');
+                if let Some(logs) = logs {
+                    logs.line(line).await;
+                }
+            }
+        }
+        Ok::<_, std::io::Error>(output)
+    };
+    let (stdout, stderr) = tokio::try_join!(read_stdout, read_stderr)?;
+
+    if !child.wait().await?.success() {
+        return Err(Error::internal(format!(
+            "docker compose {} failed: {}",
+            args.iter().rev().find(|a| !a.starts_with('-')).unwrap_or(&""),
+            stderr.trim()
+        )));
+    }
+
+    Ok(stdout)
+}
+
+/// Parse `docker compose ps --format json`, which older Compose releases print as one
+/// JSON array and newer ones as one object per line
+fn parse_compose_ps(output: &str) -> Vec<ServiceStatus> {
+    let entries: Vec<serde_json::Value> = match serde_json::from_str::<Vec<serde_json::Value>>(output.trim()) {
+        Ok(entries) => entries,
+        Err(_) => output
+            .lines()
+            .filter_map(|line| serde_json::from_str(line.trim()).ok())
+            .collect(),
+    };
+
+    entries
+        .iter()
+        .map(|entry| {
+            let field = |key: &str| entry.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string();
+            ServiceStatus {
+                name: field("Service"),
+                state: field("State"),
+                status: field("Status"),
+            }
+        })
+        .collect()
+}
+
+fn deployment_from_row(row: &SqliteRow) -> Deployment {
+    let status: String = row.get("status");
+    Deployment {
+        deployment_id: row.get("deployment_id"),
+        stack_id: row.get("stack_id"),
+        status: DeploymentStatus::parse(&status).unwrap_or(DeploymentStatus::Failed),
+        started_by: row.get("started_by"),
+        started_at: row.get("started_at"),
+        updated_at: row.get("updated_at"),
+        error: row.get("error"),
+        rolled_back_from: row.get("rolled_back_from"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_compose_ps() {
+        let ndjson = concat!(
+            r#"{"Name":"ghostdock-1-web-1","Service":"web","State":"running","Status":"Up 2 minutes"}"#,
+            "\n",
+            r#"{"Name":"ghostdock-1-db-1","Service":"db","State":"exited","Status":"Exited (1) 5 seconds ago"}"#,
+            "\n",
+        );
+        let array = format!("[{}]", ndjson.trim().replace('\n', ","));
+
+        for output in [ndjson.to_string(), array] {
+            let services = parse_compose_ps(&output);
+            assert_eq!(services.len(), 2);
+            assert_eq!(services[0].name, "web");
+            assert_eq!(services[0].state, "running");
+            assert_eq!(services[1].state, "exited");
+        }
+
+        assert!(parse_compose_ps("").is_empty());
+    }
+
+    #[test]
+    fn test_replaced_deployment_not_counted_against_limit() {
+        let active = [Uuid::new_v4(), Uuid::new_v4()];
+        assert_eq!(counted_deployments(&active, None), 2);
+        assert_eq!(counted_deployments(&active, Some(active[1])), 1);
+        // Replacing someone else's deployment frees none of the caller's slots
+        assert_eq!(counted_deployments(&active, Some(Uuid::new_v4())), 2);
+    }
+
+    #[test]
+    fn test_host_access_violations() {
+        let compose: serde_yaml::Value = serde_yaml::from_str(
+            r#"
+            services:
+              web:
+                image: nginx:1.25
+                volumes: ["data:/var/lib/data", "/cache", "./site:/usr/share/nginx/html:ro"]
+              agent:
+                image: agent:1
+                privileged: true
+                pid: host
+                cap_add: [SYS_ADMIN]
+                volumes:
+                  - type: bind
+                    source: /
+                    target: /host
+              db:
+                image: postgres:16
+                network_mode: bridge
+                volumes:
+                  - type: volume
+                    source: pgdata
+                    target: /var/lib/postgresql/data
+            volumes:
+              data: {}
+              root:
+                driver_opts: { type: none, o: bind, device: / }
+            secrets:
+              shadow:
+                file: /etc/shadow
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            host_access_violations(&compose),
+            vec![
+                "service 'web': host bind mount",
+                "service 'agent': privileged mode",
+                "service 'agent': cap_add",
+                "service 'agent': pid: host",
+                "service 'agent': host bind mount",
+                "volumes 'root': driver_opts",
+                "secrets 'shadow': file",
+            ]
+        );
+        assert!(check_host_access("services:\n  web:\n    image: nginx:1.25\n").is_ok());
+        assert!(check_host_access("services:\n  web:\n    volumes: [\"/var/run/docker.sock:/var/run/docker.sock\"]\n").is_err());
+    }
+
+    #[test]
+    fn test_compose_project_name() {
+        let id = Uuid::parse_str("6f1c1b2e-8d3a-4b6e-9a57-2c4d5e6f7a8b").unwrap();
+        // Compose project names must be lowercase alphanumerics, dashes and underscores
+        assert_eq!(compose_project(&id), "ghostdock-6f1c1b2e8d3a4b6e9a572c4d5e6f7a8b");
+    }
+}
@@ -97,7 +97,8 @@ impl IntoResponse for GhostDockError {
                 "code": error_code,
                 "message": message,
                 "timestamp": chrono::Utc::now().to_rfc3339(),
-                "request_id": uuid::Uuid::new_v4().to_string()
+                "request_id": enhanced_logging::current_request_id()
+                    .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
             }
         }));
 
@@ -134,8 +135,85 @@ pub type Result<T> = std::result::Result<T, GhostDockError>;
 
 /// Enhanced logging with structured data
 pub mod enhanced_logging {
-    use tracing::{info, error, warn, debug};
+    use axum::{
+        extract::Request,
+        http::{HeaderMap, HeaderValue},
+        middleware::Next,
+        response::Response,
+    };
+    use tracing::{info, error, warn, debug, Instrument};
     use std::time::Instant;
+
+    /// Header carrying the correlation ID in both directions
+    pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+    /// Correlation ID for the current request, stored in request extensions
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct RequestId(pub String);
+
+    tokio::task_local! {
+        static CURRENT_REQUEST_ID: String;
+    }
+
+    /// Correlation ID of the request being handled on this task, if any
+    pub fn current_request_id() -> Option<String> {
+        CURRENT_REQUEST_ID.try_with(|id| id.clone()).ok()
+    }
+
+    /// Accept client-supplied IDs only if they are short, printable, and header-safe
+    fn is_valid_request_id(id: &str) -> bool {
+        !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic())
+    }
+
+    /// Originating client address from `X-Forwarded-For`, if a proxy set it
+    pub fn forwarded_client_ip(headers: &HeaderMap) -> Option<String> {
+        headers
+            .get("x-forwarded-for")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.split(',').next())
+            .map(|ip| ip.trim().to_string())
+            .filter(|ip| !ip.is_empty())
+    }
+
+    /// Assign each request a correlation ID (honouring an incoming `X-Request-Id`),
+    /// run it inside a tracing span carrying that ID, and echo it on the response
+    pub async fn request_id(mut request: Request, next: Next) -> Response {
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .filter(|id| is_valid_request_id(id))
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        request.extensions_mut().insert(RequestId(request_id.clone()));
+
+        let client_ip = forwarded_client_ip(request.headers()).unwrap_or_else(|| "unknown".to_string());
+        let mut logger = RequestLogger::with_request_id(
+            request.method().to_string(),
+            request.uri().path().to_string(),
+            client_ip,
+            request_id.clone(),
+        );
+        logger.user_agent = request
+            .headers()
+            .get("user-agent")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let span = tracing::info_span!("request", request_id = %request_id);
+        async move {
+            logger.log_request_start();
+            let mut response = CURRENT_REQUEST_ID.scope(request_id.clone(), next.run(request)).await;
+            logger.log_request_end(response.status().as_u16(), None);
+
+            if let Ok(value) = HeaderValue::from_str(&request_id) {
+                response.headers_mut().insert(REQUEST_ID_HEADER, value);
+            }
+            response
+        }
+        .instrument(span)
+        .await
+    }
     
     pub struct RequestLogger {
         pub start_time: Instant,
@@ -148,9 +226,14 @@ pub mod enhanced_logging {
 
     impl RequestLogger {
         pub fn new(method: String, path: String, client_ip: String) -> Self {
+            Self::with_request_id(method, path, client_ip, uuid::Uuid::new_v4().to_string())
+        }
+
+        /// Logger for a request whose correlation ID is already known
+        pub fn with_request_id(method: String, path: String, client_ip: String, request_id: String) -> Self {
             Self {
                 start_time: Instant::now(),
-                request_id: uuid::Uuid::new_v4().to_string(),
+                request_id,
                 method,
                 path,
                 user_agent: None,
@@ -175,6 +258,7 @@ pub mod enhanced_logging {
             // Log based on status code
             match status_code {
                 200..=299 => tracing::info!(
+                    request_id = %self.request_id,
                     method = ?self.method,
                     path = %self.path,
                     status = status_code,
@@ -183,6 +267,7 @@ pub mod enhanced_logging {
                     "Request completed"
                 ),
                 400..=499 => tracing::warn!(
+                    request_id = %self.request_id,
                     method = ?self.method,
                     path = %self.path,
                     status = status_code,
@@ -191,6 +276,7 @@ pub mod enhanced_logging {
                     "Request completed with warning"
                 ),
                 500..=599 => tracing::error!(
+                    request_id = %self.request_id,
                     method = ?self.method,
                     path = %self.path,
                     status = status_code,
@@ -199,6 +285,7 @@ pub mod enhanced_logging {
                     "Request completed with error"
                 ),
                 _ => tracing::debug!(
+                    request_id = %self.request_id,
                     method = ?self.method,
                     path = %self.path,
                     status = status_code,
@@ -286,10 +373,16 @@ pub mod enhanced_logging {
 
 /// Metrics collection
 pub mod metrics {
-    use std::sync::Arc;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex};
     use std::sync::atomic::{AtomicU64, Ordering};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use axum::{extract::{Request, State}, middleware::Next, response::Response};
     use dashmap::DashMap;
 
+    /// Seconds covered by the rolling operation count
+    const WINDOW_SECS: u64 = 60;
+
     #[derive(Default)]
     pub struct Metrics {
         pub requests_total: AtomicU64,
@@ -298,6 +391,8 @@ pub mod metrics {
         pub registry_pushes: AtomicU64,
         pub bytes_transferred: AtomicU64,
         pub active_connections: AtomicU64,
+        /// Registry API requests per second over the last minute, oldest first
+        recent_registry_operations: Mutex<VecDeque<(u64, u64)>>,
     }
 
     impl Metrics {
@@ -305,6 +400,30 @@ pub mod metrics {
             Arc::new(Self::default())
         }
 
+        pub fn record_registry_operation(&self) {
+            self.record_registry_operation_at(unix_secs());
+        }
+
+        /// Registry API requests answered in the last minute
+        pub fn registry_operations_per_minute(&self) -> u64 {
+            self.registry_operations_at(unix_secs())
+        }
+
+        fn record_registry_operation_at(&self, now: u64) {
+            let mut recent = self.recent_registry_operations.lock().unwrap();
+            match recent.back_mut() {
+                Some((second, count)) if *second == now => *count += 1,
+                _ => recent.push_back((now, 1)),
+            }
+            expire(&mut recent, now);
+        }
+
+        fn registry_operations_at(&self, now: u64) -> u64 {
+            let mut recent = self.recent_registry_operations.lock().unwrap();
+            expire(&mut recent, now);
+            recent.iter().map(|(_, count)| count).sum()
+        }
+
         pub fn record_request(&self, status_code: u16) {
             self.requests_total.fetch_add(1, Ordering::Relaxed);
             self.requests_by_status
@@ -370,4 +489,75 @@ pub mod metrics {
             output
         }
     }
+
+    fn expire(recent: &mut VecDeque<(u64, u64)>, now: u64) {
+        while recent.front().is_some_and(|(second, _)| second + WINDOW_SECS <= now) {
+            recent.pop_front();
+        }
+    }
+
+    fn unix_secs() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+
+    /// Count registry API requests and their statuses
+    pub async fn track_registry_operations(State(metrics): State<Arc<Metrics>>, request: Request, next: Next) -> Response {
+        let response = next.run(request).await;
+        metrics.record_request(response.status().as_u16());
+        metrics.record_registry_operation();
+        response
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_registry_operations_roll_off_after_a_minute() {
+            let metrics = Metrics::default();
+            for _ in 0..3 {
+                metrics.record_registry_operation_at(1_000);
+            }
+            metrics.record_registry_operation_at(1_030);
+            assert_eq!(metrics.registry_operations_at(1_030), 4);
+            assert_eq!(metrics.registry_operations_at(1_059), 4);
+            assert_eq!(metrics.registry_operations_at(1_060), 1);
+            assert_eq!(metrics.registry_operations_at(1_090), 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::enhanced_logging::{request_id, REQUEST_ID_HEADER};
+    use axum::{body::Body, http::Request, routing::get, Router};
+    use tower::ServiceExt;
+
+    async fn failing() -> crate::error::Result<()> {
+        Err(crate::error::Error::not_found("thing"))
+    }
+
+    #[tokio::test]
+    async fn test_request_id_echoed_in_header_and_error_body() {
+        let app = Router::new()
+            .route("/fail", get(failing))
+            .layer(axum::middleware::from_fn(request_id));
+
+        let response = app
+            .clone()
+            .oneshot(Request::get("/fail").header(REQUEST_ID_HEADER, "support-1234").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[REQUEST_ID_HEADER], "support-1234");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["request_id"], "support-1234");
+
+        // Without an incoming ID one is generated and still matches the body
+        let response = app.oneshot(Request::get("/fail").body(Body::empty()).unwrap()).await.unwrap();
+        let generated = response.headers()[REQUEST_ID_HEADER].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["request_id"], generated);
+    }
 }
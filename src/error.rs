@@ -43,12 +43,24 @@ pub enum Error {
     #[error("Manifest error: {message}")]
     Manifest { message: String },
 
+    #[error("Manifest invalid: {message}")]
+    ManifestInvalid { message: String },
+
+    #[error("Manifest references a blob unknown to the repository: {digest}")]
+    ManifestBlobUnknown { digest: String },
+
+    #[error("Range invalid: {message}")]
+    RangeInvalid { message: String },
+
     #[error("Blob error: {message}")]
     Blob { message: String },
 
     #[error("Not found: {resource}")]
     NotFound { resource: String },
 
+    #[error("Repository name not known to registry: {name}")]
+    NameUnknown { name: String },
+
     #[error("Conflict: {message}")]
     Conflict { message: String },
 
@@ -61,6 +73,18 @@ pub enum Error {
     #[error("Service unavailable: {message}")]
     ServiceUnavailable { message: String },
 
+    #[error("Too busy: {message}")]
+    TooBusy { message: String, retry_after: u64 },
+
+    #[error("Read-only: {message}")]
+    ReadOnly { message: String, retry_after: u64 },
+
+    #[error("Too many requests: {message}")]
+    TooManyRequests { message: String, retry_after: u64 },
+
+    #[error("Insufficient storage: {message}")]
+    InsufficientStorage { message: String },
+
     #[error("JWT error: {0}")]
     Jwt(#[from] jsonwebtoken::errors::Error),
 
@@ -82,12 +106,20 @@ impl Error {
             Error::Validation { .. } => StatusCode::BAD_REQUEST,
             Error::BadRequest { .. } => StatusCode::BAD_REQUEST,
             Error::NotFound { .. } => StatusCode::NOT_FOUND,
+            Error::NameUnknown { .. } => StatusCode::NOT_FOUND,
             Error::Conflict { .. } => StatusCode::CONFLICT,
             Error::ServiceUnavailable { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Error::TooBusy { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Error::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Error::ReadOnly { .. } => StatusCode::SERVICE_UNAVAILABLE,
+            Error::InsufficientStorage { .. } => StatusCode::INSUFFICIENT_STORAGE,
             Error::Registry { .. } => StatusCode::BAD_REQUEST,
             Error::Storage { .. } => StatusCode::INTERNAL_SERVER_ERROR,
             Error::Manifest { .. } => StatusCode::BAD_REQUEST,
+            Error::ManifestInvalid { .. } => StatusCode::BAD_REQUEST,
+            Error::ManifestBlobUnknown { .. } => StatusCode::BAD_REQUEST,
             Error::Blob { .. } => StatusCode::BAD_REQUEST,
+            Error::RangeInvalid { .. } => StatusCode::RANGE_NOT_SATISFIABLE,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -104,12 +136,20 @@ impl Error {
             Error::Registry { .. } => "REGISTRY_ERROR",
             Error::Storage { .. } => "STORAGE_ERROR",
             Error::Manifest { .. } => "MANIFEST_ERROR",
+            Error::ManifestInvalid { .. } => "MANIFEST_INVALID",
+            Error::ManifestBlobUnknown { .. } => "MANIFEST_BLOB_UNKNOWN",
             Error::Blob { .. } => "BLOB_ERROR",
+            Error::RangeInvalid { .. } => "RANGE_INVALID",
             Error::NotFound { .. } => "NOT_FOUND",
+            Error::NameUnknown { .. } => "NAME_UNKNOWN",
             Error::Conflict { .. } => "CONFLICT",
             Error::Internal { .. } => "INTERNAL_ERROR",
             Error::BadRequest { .. } => "BAD_REQUEST",
             Error::ServiceUnavailable { .. } => "SERVICE_UNAVAILABLE",
+            Error::TooBusy { .. } => "TOO_BUSY",
+            Error::TooManyRequests { .. } => "TOO_MANY_REQUESTS",
+            Error::ReadOnly { .. } => "READ_ONLY",
+            Error::InsufficientStorage { .. } => "INSUFFICIENT_STORAGE",
             Error::Jwt(_) => "JWT_ERROR",
             Error::HttpClient(_) => "HTTP_CLIENT_ERROR",
             Error::Toml(_) => "TOML_ERROR",
@@ -122,14 +162,26 @@ impl Error {
 impl IntoResponse for Error {
     fn into_response(self) -> Response {
         let status = self.status_code();
-        let error_response = json!({
+        let mut error_response = json!({
             "error": {
                 "code": self.error_code(),
                 "message": self.to_string()
             }
         });
+        if let Some(request_id) = crate::enhanced_error::enhanced_logging::current_request_id() {
+            error_response["error"]["request_id"] = json!(request_id);
+        }
 
-        (status, Json(error_response)).into_response()
+        let mut response = (status, Json(error_response)).into_response();
+        if let Error::TooBusy { retry_after, .. }
+        | Error::TooManyRequests { retry_after, .. }
+        | Error::ReadOnly { retry_after, .. } = &self
+        {
+            if let Ok(value) = retry_after.to_string().parse() {
+                response.headers_mut().insert("Retry-After", value);
+            }
+        }
+        response
     }
 }
 
@@ -165,12 +217,36 @@ impl Error {
         }
     }
 
+    pub fn manifest_invalid<S: Into<String>>(message: S) -> Self {
+        Self::ManifestInvalid {
+            message: message.into(),
+        }
+    }
+
+    pub fn manifest_blob_unknown<S: Into<String>>(digest: S) -> Self {
+        Self::ManifestBlobUnknown {
+            digest: digest.into(),
+        }
+    }
+
+    pub fn range_invalid<S: Into<String>>(message: S) -> Self {
+        Self::RangeInvalid {
+            message: message.into(),
+        }
+    }
+
     pub fn not_found<S: Into<String>>(resource: S) -> Self {
         Self::NotFound {
             resource: resource.into(),
         }
     }
 
+    pub fn name_unknown<S: Into<String>>(name: S) -> Self {
+        Self::NameUnknown {
+            name: name.into(),
+        }
+    }
+
     pub fn conflict<S: Into<String>>(message: S) -> Self {
         Self::Conflict {
             message: message.into(),
@@ -188,4 +264,24 @@ impl Error {
             message: message.into(),
         }
     }
+
+    pub fn too_many_requests<S: Into<String>>(message: S, retry_after: u64) -> Self {
+        Self::TooManyRequests {
+            message: message.into(),
+            retry_after,
+        }
+    }
+
+    pub fn read_only<S: Into<String>>(message: S, retry_after: u64) -> Self {
+        Self::ReadOnly {
+            message: message.into(),
+            retry_after,
+        }
+    }
+
+    pub fn insufficient_storage<S: Into<String>>(message: S) -> Self {
+        Self::InsufficientStorage {
+            message: message.into(),
+        }
+    }
 }
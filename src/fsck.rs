@@ -0,0 +1,542 @@
+use serde::Serialize;
+use sqlx::SqlitePool;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+use crate::{
+    config::{StorageBackend, StorageConfig},
+    database::Database,
+    error::Result,
+    gc::{self, ManifestRecord},
+    storage::Storage,
+    storage_cache::BlobReadCache,
+    storage_fallback::{self, StorageFallback},
+    utils::manifest_blob_digests,
+};
+
+/// Consistency check between the blobs table and storage
+/// Finds rows without objects, objects without rows, and manifests whose layers are gone
+
+/// Objects written this recently may belong to a push that hasn't recorded its row yet
+const ORPHAN_MIN_AGE: Duration = Duration::from_secs(60 * 60);
+
+/// A blob object found in storage
+#[derive(Debug, Clone)]
+pub struct StoredObject {
+    pub digest: String,
+    pub path: String,
+    pub size: u64,
+}
+
+/// What `--repair` does with an object that has no row
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanAction {
+    /// A manifest references it, so the row is recreated
+    Register,
+    /// Nothing references it, so the object is deleted
+    Delete,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct OrphanedObject {
+    pub digest: String,
+    pub path: String,
+    pub size: u64,
+    pub action: OrphanAction,
+}
+
+/// A manifest whose referenced blobs are not all available
+#[derive(Debug, Clone, Serialize)]
+pub struct BrokenManifest {
+    pub repository_id: Uuid,
+    pub digest: String,
+    pub missing_blobs: Vec<String>,
+}
+
+/// Link rows whose blob, manifest or repository no longer exists, left behind by databases
+/// written without foreign key enforcement or edited by hand. They make blobs look referenced
+/// to GC, so their storage is never reclaimed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct DanglingLinks {
+    pub repository_blobs: u64,
+    pub manifest_blobs: u64,
+}
+
+impl DanglingLinks {
+    pub fn is_empty(&self) -> bool {
+        self.repository_blobs == 0 && self.manifest_blobs == 0
+    }
+}
+
+const DANGLING_REPOSITORY_BLOBS: &str = "repository_blobs WHERE \
+    NOT EXISTS (SELECT 1 FROM blobs WHERE blobs.id = repository_blobs.blob_id) \
+    OR NOT EXISTS (SELECT 1 FROM repositories WHERE repositories.id = repository_blobs.repository_id)";
+
+const DANGLING_MANIFEST_BLOBS: &str = "manifest_blobs WHERE \
+    NOT EXISTS (SELECT 1 FROM blobs WHERE blobs.id = manifest_blobs.blob_id) \
+    OR NOT EXISTS (SELECT 1 FROM manifests WHERE manifests.id = manifest_blobs.manifest_id)";
+
+/// Count dangling link rows without removing them
+pub async fn count_dangling_links(pool: &SqlitePool) -> Result<DanglingLinks> {
+    let count = |rows: &str| format!("SELECT COUNT(*) FROM {}", rows);
+    let repository_blobs: i64 = sqlx::query_scalar(&count(DANGLING_REPOSITORY_BLOBS)).fetch_one(pool).await?;
+    let manifest_blobs: i64 = sqlx::query_scalar(&count(DANGLING_MANIFEST_BLOBS)).fetch_one(pool).await?;
+
+    Ok(DanglingLinks {
+        repository_blobs: repository_blobs as u64,
+        manifest_blobs: manifest_blobs as u64,
+    })
+}
+
+/// Delete dangling link rows in one transaction, returning how many went
+pub async fn prune_dangling_links(pool: &SqlitePool) -> Result<DanglingLinks> {
+    let mut tx = pool.begin().await?;
+    let repository_blobs = sqlx::query(&format!("DELETE FROM {}", DANGLING_REPOSITORY_BLOBS))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    let manifest_blobs = sqlx::query(&format!("DELETE FROM {}", DANGLING_MANIFEST_BLOBS))
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    tx.commit().await?;
+
+    let pruned = DanglingLinks { repository_blobs, manifest_blobs };
+    if !pruned.is_empty() {
+        tracing::info!(
+            "Removed {} dangling repository_blobs and {} dangling manifest_blobs rows",
+            pruned.repository_blobs,
+            pruned.manifest_blobs
+        );
+    }
+    Ok(pruned)
+}
+
+/// Outcome of an fsck run
+#[derive(Debug, Default, Serialize)]
+pub struct FsckReport {
+    pub repaired: bool,
+    pub blobs_checked: usize,
+    /// None when the storage backend can't be listed, so orphans weren't looked for
+    pub objects_scanned: Option<usize>,
+    /// Blob rows whose backing object is missing; removed on repair
+    pub missing_objects: Vec<String>,
+    pub orphaned_objects: Vec<OrphanedObject>,
+    /// Reported only: the missing content has to be pushed again
+    pub broken_manifests: Vec<BrokenManifest>,
+    /// Link rows pointing at deleted blobs, manifests or repositories; pruned on repair
+    pub dangling_links: DanglingLinks,
+}
+
+impl FsckReport {
+    pub fn is_clean(&self) -> bool {
+        self.missing_objects.is_empty() && self.orphaned_objects.is_empty() && self.broken_manifests.is_empty()
+            && self.dangling_links.is_empty()
+    }
+}
+
+/// Check (and with `repair`, fix) drift between the database and storage.
+/// A blob still readable from the migration fallback isn't missing.
+pub async fn run(
+    database: &Database,
+    storage: &Storage,
+    fallback: Option<&StorageFallback>,
+    blob_cache: Option<&BlobReadCache>,
+    config: &StorageConfig,
+    repair: bool,
+) -> Result<FsckReport> {
+    let blobs: Vec<String> = sqlx::query_scalar("SELECT digest FROM blobs")
+        .fetch_all(&database.pool)
+        .await?;
+
+    let mut missing = HashSet::new();
+    for digest in &blobs {
+        if !storage_fallback::blob_exists(storage, fallback, digest).await? {
+            missing.insert(digest.clone());
+        }
+    }
+
+    let objects = if matches!(config.backend, StorageBackend::Filesystem) {
+        Some(scan_objects(&config.path)?)
+    } else {
+        tracing::warn!("Storage backend {:?} can't be listed; skipping the orphaned object scan", config.backend);
+        None
+    };
+
+    let manifests = gc::load_manifests(database).await?;
+    let mut report = reconcile(&blobs, &missing, objects.as_deref().unwrap_or_default(), &manifests);
+    report.objects_scanned = objects.as_ref().map(Vec::len);
+
+    if repair {
+        apply_repairs(database, storage, blob_cache, &report, &manifests).await?;
+        // Runs after the missing-blob deletes, which take their own links with them
+        report.dangling_links = prune_dangling_links(&database.pool).await?;
+        report.repaired = true;
+    } else {
+        report.dangling_links = count_dangling_links(&database.pool).await?;
+    }
+
+    Ok(report)
+}
+
+/// Work out what is inconsistent without touching anything
+pub fn reconcile(
+    blobs: &[String],
+    missing: &HashSet<String>,
+    objects: &[StoredObject],
+    manifests: &[ManifestRecord],
+) -> FsckReport {
+    let recorded: HashSet<&str> = blobs.iter().map(String::as_str).collect();
+    let manifest_digests: HashSet<&str> = manifests.iter().map(|m| m.digest.as_str()).collect();
+    let referenced: HashSet<String> = manifests.iter().flat_map(|m| manifest_blob_digests(&m.content)).collect();
+
+    let mut missing_objects: Vec<String> = blobs.iter().filter(|d| missing.contains(*d)).cloned().collect();
+    missing_objects.sort();
+
+    let orphaned_objects: Vec<OrphanedObject> = objects
+        .iter()
+        .filter(|o| !recorded.contains(o.digest.as_str()) && !manifest_digests.contains(o.digest.as_str()))
+        .map(|o| OrphanedObject {
+            digest: o.digest.clone(),
+            path: o.path.clone(),
+            size: o.size,
+            action: if referenced.contains(&o.digest) { OrphanAction::Register } else { OrphanAction::Delete },
+        })
+        .collect();
+
+    // After repair, a blob is available if it has a row and an object, or is re-registered
+    let available: HashSet<&str> = recorded
+        .iter()
+        .copied()
+        .filter(|d| !missing.contains(*d))
+        .chain(
+            orphaned_objects
+                .iter()
+                .filter(|o| o.action == OrphanAction::Register)
+                .map(|o| o.digest.as_str()),
+        )
+        .collect();
+
+    let broken_manifests = manifests
+        .iter()
+        .filter_map(|m| {
+            let missing_blobs: Vec<String> = manifest_blob_digests(&m.content)
+                .into_iter()
+                .filter(|d| !available.contains(d.as_str()))
+                .collect();
+            (!missing_blobs.is_empty()).then(|| BrokenManifest {
+                repository_id: m.repository_id,
+                digest: m.digest.clone(),
+                missing_blobs,
+            })
+        })
+        .collect();
+
+    FsckReport {
+        repaired: false,
+        blobs_checked: blobs.len(),
+        objects_scanned: Some(objects.len()),
+        missing_objects,
+        orphaned_objects,
+        broken_manifests,
+        dangling_links: DanglingLinks::default(),
+    }
+}
+
+async fn apply_repairs(
+    database: &Database,
+    storage: &Storage,
+    blob_cache: Option<&BlobReadCache>,
+    report: &FsckReport,
+    manifests: &[ManifestRecord],
+) -> Result<()> {
+    for digest in &report.missing_objects {
+        let mut tx = database.pool.begin().await?;
+        for statement in [
+            "DELETE FROM manifest_blobs WHERE blob_id IN (SELECT id FROM blobs WHERE digest = $1)",
+            "DELETE FROM repository_blobs WHERE blob_id IN (SELECT id FROM blobs WHERE digest = $1)",
+            "DELETE FROM blobs WHERE digest = $1",
+        ] {
+            sqlx::query(statement).bind(digest).execute(&mut *tx).await?;
+        }
+        tx.commit().await?;
+        tracing::info!("fsck removed row for missing blob {}", digest);
+    }
+
+    // Manifests that reference each digest, to re-link registered blobs
+    let mut referencing: HashMap<String, Vec<&ManifestRecord>> = HashMap::new();
+    for manifest in manifests {
+        for digest in manifest_blob_digests(&manifest.content) {
+            referencing.entry(digest).or_default().push(manifest);
+        }
+    }
+
+    for orphan in &report.orphaned_objects {
+        match orphan.action {
+            OrphanAction::Delete => {
+                if let Err(e) = storage.delete_blob(&orphan.digest).await {
+                    tracing::warn!("fsck failed to delete orphaned blob {}: {}", orphan.digest, e);
+                    continue;
+                }
+                if let Some(cache) = blob_cache {
+                    cache.invalidate(&orphan.digest).await;
+                }
+                tracing::info!("fsck deleted orphaned blob {}", orphan.digest);
+            }
+            OrphanAction::Register => {
+                register_blob(database, orphan, referencing.get(&orphan.digest).map(Vec::as_slice).unwrap_or_default())
+                    .await?;
+                tracing::info!("fsck registered orphaned blob {}", orphan.digest);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn register_blob(database: &Database, orphan: &OrphanedObject, manifests: &[&ManifestRecord]) -> Result<()> {
+    let now = chrono::Utc::now();
+    let blob_id = Uuid::new_v4();
+    let mut tx = database.pool.begin().await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO blobs (id, digest, media_type, size, storage_path, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(blob_id)
+    .bind(&orphan.digest)
+    .bind("application/octet-stream")
+    .bind(orphan.size as i64)
+    .bind(&orphan.path)
+    .bind(now)
+    .execute(&mut *tx)
+    .await?;
+
+    for manifest in manifests {
+        sqlx::query(
+            r#"
+            INSERT INTO repository_blobs (id, repository_id, blob_id, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (repository_id, blob_id) DO NOTHING
+            "#
+        )
+        .bind(Uuid::new_v4())
+        .bind(manifest.repository_id)
+        .bind(blob_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query("INSERT INTO manifest_blobs (id, manifest_id, blob_id, created_at) VALUES ($1, $2, $3, $4)")
+            .bind(Uuid::new_v4())
+            .bind(manifest.id)
+            .bind(blob_id)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
+/// Digest-named objects under the storage root, skipping ones too new to judge
+fn scan_objects(root: &Path) -> Result<Vec<StoredObject>> {
+    let cutoff = SystemTime::now() - ORPHAN_MIN_AGE;
+    let mut objects = Vec::new();
+
+    for entry in walkdir::WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let Ok(relative) = entry.path().strip_prefix(root) else {
+            continue;
+        };
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let Some(digest) = digest_from_object_path(&relative) else {
+            continue;
+        };
+
+        let metadata = entry.metadata().map_err(std::io::Error::from)?;
+        if metadata.modified().map(|m| m > cutoff).unwrap_or(true) {
+            continue;
+        }
+        objects.push(StoredObject { digest, path: relative, size: metadata.len() });
+    }
+
+    Ok(objects)
+}
+
+/// Recover a blob digest from an object key: `.../sha256:<hex>`, `.../sha256/<hex>`,
+/// or the distribution layout `.../sha256/<xx>/<hex>/data`
+pub fn digest_from_object_path(path: &str) -> Option<String> {
+    let is_hex = |s: &str| s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase());
+    let parts: Vec<&str> = path.split('/').filter(|p| !p.is_empty()).collect();
+
+    let (last, parent) = match parts.as_slice() {
+        [.., parent, last] => (*last, Some(*parent)),
+        [last] => (*last, None),
+        [] => return None,
+    };
+
+    if let Some(hex) = last.strip_prefix("sha256:") {
+        return is_hex(hex).then(|| last.to_string());
+    }
+    if is_hex(last) && parent == Some("sha256") {
+        return Some(format!("sha256:{}", last));
+    }
+    if last == "data" {
+        if let [.., algorithm, _, hex, _] = parts.as_slice() {
+            if *algorithm == "sha256" && is_hex(hex) {
+                return Some(format!("sha256:{}", hex));
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::media_types;
+    use chrono::Utc;
+
+    const HEX_A: &str = "aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa";
+    const HEX_B: &str = "bbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbbb";
+    const HEX_C: &str = "cccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccccc";
+
+    fn object(hex: &str) -> StoredObject {
+        StoredObject { digest: format!("sha256:{}", hex), path: format!("blobs/sha256:{}", hex), size: 10 }
+    }
+
+    fn manifest(layers: &[&str]) -> ManifestRecord {
+        let layers: Vec<_> = layers
+            .iter()
+            .map(|hex| serde_json::json!({ "mediaType": media_types::OCI_LAYERS[0], "digest": format!("sha256:{}", hex), "size": 10 }))
+            .collect();
+        let content = serde_json::json!({ "schemaVersion": 2, "mediaType": media_types::OCI_MANIFEST, "layers": layers });
+        ManifestRecord {
+            id: Uuid::new_v4(),
+            repository_id: Uuid::new_v4(),
+            digest: "sha256:manifest".to_string(),
+            media_type: media_types::OCI_MANIFEST.to_string(),
+            content: content.to_string().into_bytes(),
+            created_at: Utc::now(),
+            tagged: true,
+        }
+    }
+
+    #[test]
+    fn test_digest_from_object_path() {
+        assert_eq!(digest_from_object_path(&format!("blobs/sha256:{}", HEX_A)), Some(format!("sha256:{}", HEX_A)));
+        assert_eq!(digest_from_object_path(&format!("acme/blobs/sha256/{}", HEX_A)), Some(format!("sha256:{}", HEX_A)));
+        assert_eq!(
+            digest_from_object_path(&format!("docker/registry/v2/blobs/sha256/aa/{}/data", HEX_A)),
+            Some(format!("sha256:{}", HEX_A))
+        );
+        assert_eq!(digest_from_object_path("uploads/0b7e5c3a/data"), None);
+        assert_eq!(digest_from_object_path("blobs/sha256:short"), None);
+    }
+
+    #[test]
+    fn test_reconcile() {
+        let blobs = vec![format!("sha256:{}", HEX_A), format!("sha256:{}", HEX_B)];
+        let missing: HashSet<String> = [format!("sha256:{}", HEX_B)].into();
+        // C exists in storage without a row; the manifest references A, B and C
+        let objects = vec![object(HEX_A), object(HEX_C)];
+        let manifests = vec![manifest(&[HEX_A, HEX_B, HEX_C])];
+
+        let report = reconcile(&blobs, &missing, &objects, &manifests);
+        assert_eq!(report.missing_objects, vec![format!("sha256:{}", HEX_B)]);
+        assert_eq!(report.orphaned_objects.len(), 1);
+        assert_eq!(report.orphaned_objects[0].action, OrphanAction::Register);
+
+        // C will be registered, but B is gone for good
+        assert_eq!(report.broken_manifests.len(), 1);
+        assert_eq!(report.broken_manifests[0].missing_blobs, vec![format!("sha256:{}", HEX_B)]);
+
+        // Nothing references an orphan, so repair deletes it
+        let report = reconcile(&blobs[..1], &HashSet::new(), &objects, &[manifest(&[HEX_A])]);
+        assert_eq!(report.orphaned_objects[0].action, OrphanAction::Delete);
+        assert!(report.broken_manifests.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prune_dangling_links() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::database::migrations::create_tables(&pool).await.unwrap();
+        // Dangling rows can only be written with enforcement off, as older databases were
+        sqlx::query("PRAGMA foreign_keys = OFF").execute(&pool).await.unwrap();
+
+        let (owner_id, repository_id, manifest_id, blob_id) = (Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4(), Uuid::new_v4());
+        sqlx::query("INSERT INTO users (id, username, email) VALUES ($1, 'alice', 'alice@example.com')")
+            .bind(owner_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO repositories (id, name, owner_id) VALUES ($1, 'app', $2)")
+            .bind(repository_id)
+            .bind(owner_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO manifests (id, repository_id, digest, media_type, content, size) VALUES ($1, $2, 'sha256:m', $3, x'7b7d', 2)")
+            .bind(manifest_id)
+            .bind(repository_id)
+            .bind(media_types::OCI_MANIFEST)
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO blobs (id, digest, media_type, size, storage_path) VALUES ($1, $2, 'application/octet-stream', 10, 'blobs/a')")
+            .bind(blob_id)
+            .bind(format!("sha256:{}", HEX_A))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // One valid link of each kind, plus links to a deleted blob, repository and manifest
+        let (gone, now) = (Uuid::new_v4(), Utc::now());
+        for (repository, blob) in [(repository_id, blob_id), (repository_id, gone), (gone, blob_id)] {
+            sqlx::query("INSERT INTO repository_blobs (id, repository_id, blob_id, created_at) VALUES ($1, $2, $3, $4)")
+                .bind(Uuid::new_v4())
+                .bind(repository)
+                .bind(blob)
+                .bind(now)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        for (manifest, blob) in [(manifest_id, blob_id), (manifest_id, gone), (gone, blob_id)] {
+            sqlx::query("INSERT INTO manifest_blobs (id, manifest_id, blob_id, created_at) VALUES ($1, $2, $3, $4)")
+                .bind(Uuid::new_v4())
+                .bind(manifest)
+                .bind(blob)
+                .bind(now)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+
+        let expected = DanglingLinks { repository_blobs: 2, manifest_blobs: 2 };
+        assert_eq!(count_dangling_links(&pool).await.unwrap(), expected);
+        assert_eq!(prune_dangling_links(&pool).await.unwrap(), expected);
+        assert!(count_dangling_links(&pool).await.unwrap().is_empty());
+
+        let remaining: i64 = sqlx::query_scalar("SELECT (SELECT COUNT(*) FROM repository_blobs) + (SELECT COUNT(*) FROM manifest_blobs)")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, 2);
+    }
+}
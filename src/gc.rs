@@ -0,0 +1,562 @@
+use chrono::{DateTime, Duration, Utc};
+use cron::Schedule;
+use serde::Serialize;
+use sqlx::Row;
+use std::{collections::HashSet, str::FromStr, sync::Arc};
+use uuid::Uuid;
+
+use crate::{
+    config::GcConfig,
+    database::Database,
+    error::{Error, Result},
+    fsck::{self, DanglingLinks},
+    performance::{OperationClass, PerformanceLayer},
+    storage::Storage,
+    storage_cache::BlobReadCache,
+    utils::manifest_list_children,
+};
+
+/// Garbage collection for untagged manifests and blobs no manifest references
+
+/// A manifest row as seen by the collector
+#[derive(Debug, Clone)]
+pub struct ManifestRecord {
+    pub id: Uuid,
+    pub repository_id: Uuid,
+    pub digest: String,
+    pub media_type: String,
+    pub content: Vec<u8>,
+    pub created_at: DateTime<Utc>,
+    pub tagged: bool,
+}
+
+/// Outcome of a GC run
+#[derive(Debug, Default, Serialize)]
+pub struct GcReport {
+    pub dry_run: bool,
+    pub manifests_deleted: Vec<String>,
+    pub blobs_deleted: Vec<String>,
+    pub bytes_freed: i64,
+    /// Unreferenced blobs kept because they were pushed inside the safety window
+    pub blobs_deferred: Vec<String>,
+    /// Dangling link rows removed before collecting; counted only on a dry run
+    pub dangling_links: DanglingLinks,
+    /// `repository@digest` links dropped because no manifest in that repository uses the
+    /// blob any more; the blob itself lives on for the repositories that still do
+    pub repository_links_removed: Vec<String>,
+}
+
+/// An unreferenced blob considered for deletion
+#[derive(Debug, Clone)]
+pub struct BlobCandidate {
+    pub digest: String,
+    pub size: i64,
+    /// When the blob was last uploaded into any repository
+    pub last_pushed: DateTime<Utc>,
+}
+
+/// A repository's link to a blob, which lets the blob be pulled through that repository
+#[derive(Debug, Clone)]
+pub struct RepositoryLink {
+    pub id: Uuid,
+    pub repository_id: Uuid,
+    pub blob_id: Uuid,
+    /// `repository@digest`, for the report
+    pub label: String,
+}
+
+/// A blob used by a manifest: (repository, blob, manifest)
+pub type BlobReference = (Uuid, Uuid, Uuid);
+
+/// Options for a single GC run
+#[derive(Debug, Clone)]
+pub struct GcOptions {
+    pub dry_run: bool,
+    pub delete_untagged_manifests: bool,
+    pub untagged_min_age: Duration,
+    pub blob_safety_window: Duration,
+    pub prune_dangling_links: bool,
+}
+
+impl From<&GcConfig> for GcOptions {
+    fn from(config: &GcConfig) -> Self {
+        Self {
+            dry_run: config.dry_run,
+            delete_untagged_manifests: config.delete_untagged_manifests,
+            untagged_min_age: Duration::hours(config.untagged_manifest_min_age_hours as i64),
+            blob_safety_window: Duration::minutes(config.blob_safety_window_minutes as i64),
+            prune_dangling_links: config.prune_dangling_links,
+        }
+    }
+}
+
+/// Handle for running GC from background tasks, detached from the server
+#[derive(Clone)]
+pub struct GcRunner {
+    database: Arc<Database>,
+    storage: Arc<Storage>,
+    blob_cache: Option<Arc<BlobReadCache>>,
+    performance: PerformanceLayer,
+}
+
+impl GcRunner {
+    pub fn new(
+        database: Arc<Database>,
+        storage: Arc<Storage>,
+        blob_cache: Option<Arc<BlobReadCache>>,
+        performance: PerformanceLayer,
+    ) -> Self {
+        Self {
+            database,
+            storage,
+            blob_cache,
+            performance,
+        }
+    }
+
+    /// Run a pass while holding a maintenance permit so serving is not disrupted
+    pub async fn run_scheduled(&self, options: &GcOptions) -> Result<GcReport> {
+        let _permit = self.performance.acquire_operation(OperationClass::Maintenance).await?;
+        let report = run(&self.database, &self.storage, self.blob_cache.as_deref(), options).await?;
+        if !report.dry_run && !report.manifests_deleted.is_empty() {
+            self.performance.catalog_cache.invalidate();
+            self.performance.manifest_cache.clear();
+        }
+        Ok(report)
+    }
+
+    /// Users to notify about maintenance results
+    pub async fn admin_user_ids(&self) -> Result<Vec<String>> {
+        Ok(sqlx::query_scalar("SELECT id FROM users WHERE is_admin = TRUE AND is_active = TRUE")
+            .fetch_all(&self.database.pool)
+            .await?)
+    }
+}
+
+/// Parse a GC cron expression
+pub fn parse_schedule(expression: &str) -> Result<Schedule> {
+    Schedule::from_str(expression)
+        .map_err(|e| Error::validation(format!("Invalid GC schedule '{}': {}", expression, e)))
+}
+
+/// Time to wait before the next scheduled run
+pub fn next_run_delay(schedule: Option<&Schedule>, interval_secs: u64, now: DateTime<Utc>) -> std::time::Duration {
+    let fallback = std::time::Duration::from_secs(interval_secs.max(60));
+
+    match schedule.and_then(|s| s.after(&now).next()) {
+        Some(next) => (next - now).to_std().unwrap_or(fallback),
+        None => fallback,
+    }
+}
+
+/// Run a garbage collection pass
+pub async fn run(
+    database: &Database,
+    storage: &Storage,
+    blob_cache: Option<&BlobReadCache>,
+    options: &GcOptions,
+) -> Result<GcReport> {
+    let mut report = GcReport {
+        dry_run: options.dry_run,
+        ..GcReport::default()
+    };
+    let now = Utc::now();
+
+    if options.prune_dangling_links {
+        report.dangling_links = if options.dry_run {
+            fsck::count_dangling_links(&database.pool).await?
+        } else {
+            fsck::prune_dangling_links(&database.pool).await?
+        };
+    }
+
+    let doomed = if options.delete_untagged_manifests {
+        find_untagged_manifests(&load_manifests(database).await?, options.untagged_min_age, now)
+    } else {
+        Vec::new()
+    };
+
+    // Blobs referenced by the manifests we are about to delete, plus blobs no manifest references.
+    // Manifest pushes refuse blobs the repository doesn't hold, so every referenced blob is linked.
+    let mut candidate_blobs: HashSet<Uuid> = sqlx::query_scalar(
+        "SELECT id FROM blobs b WHERE NOT EXISTS (SELECT 1 FROM manifest_blobs mb WHERE mb.blob_id = b.id)"
+    )
+    .fetch_all(&database.pool)
+    .await?
+    .into_iter()
+    .collect();
+    for manifest in &doomed {
+        let blob_ids: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT blob_id FROM manifest_blobs WHERE manifest_id = $1"
+        )
+        .bind(manifest.id)
+        .fetch_all(&database.pool)
+        .await?;
+        candidate_blobs.extend(blob_ids);
+    }
+
+    let doomed_ids: HashSet<Uuid> = doomed.iter().map(|m| m.id).collect();
+    report.manifests_deleted = doomed.iter().map(|m| m.digest.clone()).collect();
+
+    // Keep only blobs that no surviving manifest references
+    let mut unreferenced = Vec::new();
+    for blob_id in candidate_blobs {
+        let referencing: Vec<Uuid> = sqlx::query_scalar(
+            "SELECT manifest_id FROM manifest_blobs WHERE blob_id = $1"
+        )
+        .bind(blob_id)
+        .fetch_all(&database.pool)
+        .await?;
+
+        if referencing.iter().all(|id| doomed_ids.contains(id)) {
+            if let Some(candidate) = load_blob_candidate(database, blob_id).await? {
+                unreferenced.push(candidate);
+            }
+        }
+    }
+
+    let (expired, young) = partition_by_safety_window(unreferenced, options.blob_safety_window, now);
+    report.blobs_deferred = young.into_iter().map(|b| b.digest).collect();
+    report.bytes_freed = expired.iter().map(|b| b.size).sum();
+    report.blobs_deleted = expired.into_iter().map(|b| b.digest).collect();
+
+    // Links made inside the safety window may be waiting for their manifest, like new blobs
+    let links = load_repository_links(database, now - options.blob_safety_window).await?;
+    let stale_links = find_stale_links(&links, &load_blob_references(database).await?, &doomed_ids);
+    report.repository_links_removed = stale_links.iter().map(|l| l.label.clone()).collect();
+
+    if options.dry_run
+        || (report.manifests_deleted.is_empty() && report.blobs_deleted.is_empty() && stale_links.is_empty())
+    {
+        return Ok(report);
+    }
+
+    for manifest in &doomed {
+        sqlx::query("DELETE FROM manifest_blobs WHERE manifest_id = $1")
+            .bind(manifest.id)
+            .execute(&database.pool)
+            .await?;
+        sqlx::query("DELETE FROM manifests WHERE id = $1")
+            .bind(manifest.id)
+            .execute(&database.pool)
+            .await?;
+    }
+
+    for link in &stale_links {
+        sqlx::query("DELETE FROM repository_blobs WHERE id = $1")
+            .bind(link.id)
+            .execute(&database.pool)
+            .await?;
+    }
+
+    for digest in &report.blobs_deleted {
+        if let Err(e) = storage.delete_blob(digest).await {
+            tracing::warn!("GC failed to remove blob {} from storage: {}", digest, e);
+            continue;
+        }
+        if let Some(cache) = blob_cache {
+            cache.invalidate(digest).await;
+        }
+        sqlx::query("DELETE FROM repository_blobs WHERE blob_id IN (SELECT id FROM blobs WHERE digest = $1)")
+            .bind(digest)
+            .execute(&database.pool)
+            .await?;
+        sqlx::query("DELETE FROM blobs WHERE digest = $1")
+            .bind(digest)
+            .execute(&database.pool)
+            .await?;
+    }
+
+    tracing::info!(
+        "GC removed {} manifests, {} blobs ({} bytes) and {} stale repository links",
+        report.manifests_deleted.len(),
+        report.blobs_deleted.len(),
+        report.bytes_freed,
+        report.repository_links_removed.len()
+    );
+
+    Ok(report)
+}
+
+/// A blob's size and latest push, taking re-uploads into other repositories into account
+async fn load_blob_candidate(database: &Database, blob_id: Uuid) -> Result<Option<BlobCandidate>> {
+    let row = sqlx::query(
+        r#"
+        SELECT b.digest, b.size, b.created_at,
+               (SELECT MAX(rb.created_at) FROM repository_blobs rb WHERE rb.blob_id = b.id) AS linked_at
+        FROM blobs b
+        WHERE b.id = $1
+        "#
+    )
+    .bind(blob_id)
+    .fetch_optional(&database.pool)
+    .await?;
+
+    Ok(row.map(|row| {
+        let created_at: DateTime<Utc> = row.get("created_at");
+        let linked_at: Option<DateTime<Utc>> = row.get("linked_at");
+        BlobCandidate {
+            digest: row.get("digest"),
+            size: row.get("size"),
+            last_pushed: linked_at.map_or(created_at, |linked| linked.max(created_at)),
+        }
+    }))
+}
+
+/// Split unreferenced blobs into those old enough to delete and those pushed
+/// within `window`, which may belong to a push whose manifest hasn't arrived yet
+pub fn partition_by_safety_window(
+    blobs: Vec<BlobCandidate>,
+    window: Duration,
+    now: DateTime<Utc>,
+) -> (Vec<BlobCandidate>, Vec<BlobCandidate>) {
+    let cutoff = now - window;
+    blobs.into_iter().partition(|b| b.last_pushed <= cutoff)
+}
+
+/// Repository links created before `cutoff`
+async fn load_repository_links(database: &Database, cutoff: DateTime<Utc>) -> Result<Vec<RepositoryLink>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT rb.id, rb.repository_id, rb.blob_id, r.name, b.digest
+        FROM repository_blobs rb
+        JOIN repositories r ON r.id = rb.repository_id
+        JOIN blobs b ON b.id = rb.blob_id
+        WHERE rb.created_at <= $1
+        "#
+    )
+    .bind(cutoff)
+    .fetch_all(&database.pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| RepositoryLink {
+            id: row.get("id"),
+            repository_id: row.get("repository_id"),
+            blob_id: row.get("blob_id"),
+            label: format!("{}@{}", row.get::<String, _>("name"), row.get::<String, _>("digest")),
+        })
+        .collect())
+}
+
+async fn load_blob_references(database: &Database) -> Result<Vec<BlobReference>> {
+    let rows = sqlx::query(
+        "SELECT m.repository_id, mb.blob_id, mb.manifest_id FROM manifest_blobs mb JOIN manifests m ON m.id = mb.manifest_id"
+    )
+    .fetch_all(&database.pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| (row.get("repository_id"), row.get("blob_id"), row.get("manifest_id")))
+        .collect())
+}
+
+/// Links whose blob a surviving manifest elsewhere still uses, but none in the link's own
+/// repository. Blobs no surviving manifest uses at all are left to blob collection, which
+/// removes their links with them.
+pub fn find_stale_links<'a>(
+    links: &'a [RepositoryLink],
+    references: &[BlobReference],
+    doomed: &HashSet<Uuid>,
+) -> Vec<&'a RepositoryLink> {
+    let surviving: Vec<&BlobReference> = references.iter().filter(|(_, _, manifest)| !doomed.contains(manifest)).collect();
+    let used_in_repository: HashSet<(Uuid, Uuid)> = surviving.iter().map(|(repository, blob, _)| (*repository, *blob)).collect();
+    let used_anywhere: HashSet<Uuid> = surviving.iter().map(|(_, blob, _)| *blob).collect();
+
+    links
+        .iter()
+        .filter(|link| used_anywhere.contains(&link.blob_id))
+        .filter(|link| !used_in_repository.contains(&(link.repository_id, link.blob_id)))
+        .collect()
+}
+
+pub(crate) async fn load_manifests(database: &Database) -> Result<Vec<ManifestRecord>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT m.id, m.repository_id, m.digest, m.media_type, m.content, m.created_at,
+               EXISTS(SELECT 1 FROM tags t WHERE t.manifest_id = m.id) AS tagged
+        FROM manifests m
+        "#
+    )
+    .fetch_all(&database.pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .map(|row| ManifestRecord {
+            id: row.get("id"),
+            repository_id: row.get("repository_id"),
+            digest: row.get("digest"),
+            media_type: row.get("media_type"),
+            content: row.get("content"),
+            created_at: row.get("created_at"),
+            tagged: row.get("tagged"),
+        })
+        .collect())
+}
+
+/// Child manifest digests referenced by a manifest list or OCI index
+fn child_digests(manifest: &ManifestRecord) -> Vec<String> {
+    manifest_list_children(&manifest.media_type, &manifest.content)
+}
+
+/// Select untagged manifests older than `min_age` that no surviving manifest list references.
+/// Deleting an untagged list releases its children, so selection repeats until stable.
+pub fn find_untagged_manifests(
+    manifests: &[ManifestRecord],
+    min_age: Duration,
+    now: DateTime<Utc>,
+) -> Vec<&ManifestRecord> {
+    let cutoff = now - min_age;
+    let mut doomed: HashSet<Uuid> = HashSet::new();
+
+    loop {
+        let children: HashSet<(Uuid, String)> = manifests
+            .iter()
+            .filter(|m| !doomed.contains(&m.id))
+            .flat_map(|m| child_digests(m).into_iter().map(move |d| (m.repository_id, d)))
+            .collect();
+
+        let newly_doomed: Vec<Uuid> = manifests
+            .iter()
+            .filter(|m| !doomed.contains(&m.id))
+            .filter(|m| !m.tagged && m.created_at <= cutoff)
+            .filter(|m| !children.contains(&(m.repository_id, m.digest.clone())))
+            .map(|m| m.id)
+            .collect();
+
+        if newly_doomed.is_empty() {
+            break;
+        }
+        doomed.extend(newly_doomed);
+    }
+
+    manifests.iter().filter(|m| doomed.contains(&m.id)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::media_types;
+
+    fn record(digest: &str, media_type: &str, content: &str, tagged: bool, age_hours: i64) -> ManifestRecord {
+        ManifestRecord {
+            id: Uuid::new_v4(),
+            repository_id: Uuid::nil(),
+            digest: digest.to_string(),
+            media_type: media_type.to_string(),
+            content: content.as_bytes().to_vec(),
+            created_at: Utc::now() - Duration::hours(age_hours),
+            tagged,
+        }
+    }
+
+    fn list_of(children: &[&str]) -> String {
+        let entries: Vec<_> = children
+            .iter()
+            .map(|d| serde_json::json!({ "digest": d, "mediaType": media_types::DOCKER_MANIFEST_V2, "size": 1 }))
+            .collect();
+        serde_json::json!({ "manifests": entries }).to_string()
+    }
+
+    fn blob(digest: &str, pushed_minutes_ago: i64) -> BlobCandidate {
+        BlobCandidate {
+            digest: digest.to_string(),
+            size: 10,
+            last_pushed: Utc::now() - Duration::minutes(pushed_minutes_ago),
+        }
+    }
+
+    #[test]
+    fn test_just_uploaded_unreferenced_blob_survives() {
+        let blobs = vec![blob("sha256:fresh", 1), blob("sha256:stale", 6 * 60)];
+
+        let (expired, young) = partition_by_safety_window(blobs, Duration::hours(2), Utc::now());
+        assert_eq!(expired.iter().map(|b| b.digest.as_str()).collect::<Vec<_>>(), ["sha256:stale"]);
+        assert_eq!(young.iter().map(|b| b.digest.as_str()).collect::<Vec<_>>(), ["sha256:fresh"]);
+
+        // Without a window every unreferenced blob is fair game
+        let (expired, young) = partition_by_safety_window(vec![blob("sha256:fresh", 1)], Duration::zero(), Utc::now());
+        assert_eq!(expired.len(), 1);
+        assert!(young.is_empty());
+    }
+
+    #[test]
+    fn test_next_run_delay_follows_schedule() {
+        let schedule = parse_schedule("0 0 3 * * *").unwrap();
+        let now = DateTime::parse_from_rfc3339("2024-05-01T02:00:00Z").unwrap().with_timezone(&Utc);
+
+        let delay = next_run_delay(Some(&schedule), 86400, now);
+        assert_eq!(delay, std::time::Duration::from_secs(3600));
+
+        assert_eq!(next_run_delay(None, 86400, now), std::time::Duration::from_secs(86400));
+        assert!(parse_schedule("every night").is_err());
+    }
+
+    #[test]
+    fn test_tagged_manifest_is_kept() {
+        let manifests = vec![record("sha256:a", media_types::DOCKER_MANIFEST_V2, "{}", true, 48)];
+        assert!(find_untagged_manifests(&manifests, Duration::hours(24), Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn test_untagged_manifest_is_collected_after_min_age() {
+        let manifests = vec![
+            record("sha256:old", media_types::DOCKER_MANIFEST_V2, "{}", false, 48),
+            record("sha256:new", media_types::DOCKER_MANIFEST_V2, "{}", false, 1),
+        ];
+        let doomed = find_untagged_manifests(&manifests, Duration::hours(24), Utc::now());
+        assert_eq!(doomed.len(), 1);
+        assert_eq!(doomed[0].digest, "sha256:old");
+    }
+
+    #[test]
+    fn test_children_of_tagged_list_are_kept() {
+        let manifests = vec![
+            record("sha256:list", media_types::DOCKER_MANIFEST_LIST, &list_of(&["sha256:amd64"]), true, 48),
+            record("sha256:amd64", media_types::DOCKER_MANIFEST_V2, "{}", false, 48),
+        ];
+        assert!(find_untagged_manifests(&manifests, Duration::hours(24), Utc::now()).is_empty());
+    }
+
+    #[test]
+    fn test_stale_repository_links() {
+        let (team, other) = (Uuid::new_v4(), Uuid::new_v4());
+        let (shared, unused) = (Uuid::new_v4(), Uuid::new_v4());
+        let (kept, doomed) = (Uuid::new_v4(), Uuid::new_v4());
+        let link = |repository_id: Uuid, blob_id: Uuid, label: &str| RepositoryLink {
+            id: Uuid::new_v4(),
+            repository_id,
+            blob_id,
+            label: label.to_string(),
+        };
+        let links = vec![
+            link(team, shared, "team@shared"),
+            link(other, shared, "other@shared"),
+            link(other, unused, "other@unused"),
+        ];
+
+        // `other` dropped the manifest that used the shared layer; `team` still uses it
+        let references = vec![(team, shared, kept)];
+        let stale = find_stale_links(&links, &references, &HashSet::new());
+        // The unreferenced blob is collected whole rather than unlinked
+        assert_eq!(stale.iter().map(|l| l.label.as_str()).collect::<Vec<_>>(), ["other@shared"]);
+
+        // A manifest this pass deletes no longer holds its repository's link
+        let references = vec![(team, shared, kept), (other, shared, doomed)];
+        assert_eq!(find_stale_links(&links, &references, &HashSet::new()).len(), 0);
+        assert_eq!(find_stale_links(&links, &references, &HashSet::from([doomed])).len(), 1);
+    }
+
+    #[test]
+    fn test_children_of_untagged_list_are_collected_with_it() {
+        let manifests = vec![
+            record("sha256:list", media_types::OCI_INDEX, &list_of(&["sha256:arm64"]), false, 48),
+            record("sha256:arm64", media_types::OCI_MANIFEST, "{}", false, 48),
+        ];
+        let doomed = find_untagged_manifests(&manifests, Duration::hours(24), Utc::now());
+        assert_eq!(doomed.len(), 2);
+    }
+}
@@ -0,0 +1,242 @@
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    database::queries::record_audit,
+    error::{Error, Result},
+    server::AppState,
+};
+use axum::{
+    body::Body,
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use futures::stream;
+use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+
+/// Audit log export for archival in external systems before retention purges it
+/// Entries are read in time-ordered batches, so exports of any size stream in bounded memory.
+
+/// Entries fetched per database round trip
+const EXPORT_BATCH_SIZE: i64 = 1000;
+
+const CSV_HEADER: &str = "id,created_at,actor_id,action,target,details\n";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Ndjson,
+    Csv,
+}
+
+impl ExportFormat {
+    fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Csv => "csv",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditExportQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+    /// Only entries recorded at or after this time
+    pub from: Option<DateTime<Utc>>,
+    /// Only entries recorded before this time
+    pub to: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditEntry {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub actor_id: String,
+    pub action: String,
+    pub target: String,
+    pub details: Option<serde_json::Value>,
+}
+
+impl AuditEntry {
+    fn from_row(row: &SqliteRow) -> Self {
+        let details: Option<String> = row.get("details");
+        Self {
+            id: row.get("id"),
+            created_at: row.get("created_at"),
+            actor_id: row.get("actor_id"),
+            action: row.get("action"),
+            target: row.get("target"),
+            // Details are always written as JSON; anything else is kept as text
+            details: details.map(|d| serde_json::from_str(&d).unwrap_or(serde_json::Value::String(d))),
+        }
+    }
+
+    /// One line of the export, newline included
+    fn render(&self, format: ExportFormat) -> String {
+        match format {
+            ExportFormat::Ndjson => {
+                let mut line = serde_json::to_string(self).unwrap_or_default();
+                line.push('\n');
+                line
+            }
+            ExportFormat::Csv => {
+                let created_at = self.created_at.to_rfc3339();
+                let details = self.details.as_ref().map(|d| d.to_string()).unwrap_or_default();
+                let fields = [
+                    self.id.as_str(),
+                    created_at.as_str(),
+                    self.actor_id.as_str(),
+                    self.action.as_str(),
+                    self.target.as_str(),
+                    details.as_str(),
+                ];
+                let mut line = fields.map(csv_field).join(",");
+                line.push('\n');
+                line
+            }
+        }
+    }
+}
+
+/// Quote a CSV field when it contains a delimiter, quote, or line break
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Stream the audit log as NDJSON or CSV, oldest first (admin only)
+pub async fn export_audit_log(
+    State(state): State<AppState>,
+    Query(query): Query<AuditExportQuery>,
+    user: AuthenticatedUser,
+) -> Result<Response> {
+    if user.is_robot() || !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Exporting the audit log requires admin access"));
+    }
+    if let (Some(from), Some(to)) = (query.from, query.to) {
+        if from >= to {
+            return Err(Error::validation("from must be earlier than to"));
+        }
+    }
+
+    // Recorded first so the export shows up in later exports
+    record_audit(
+        &state,
+        &user.id,
+        "audit.export",
+        "audit_log",
+        Some(serde_json::json!({ "format": query.format, "from": query.from, "to": query.to })),
+    )
+    .await?;
+
+    let format = query.format;
+    let header_row = (format == ExportFormat::Csv).then(|| Ok(Bytes::from_static(CSV_HEADER.as_bytes())));
+    let batches = export_batches(state.database.pool.clone(), format, query.from, query.to);
+    let body = Body::from_stream(futures::StreamExt::chain(stream::iter(header_row), batches));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"audit-log.{}\"", format.extension()),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+/// Where the next batch starts: after this `(created_at, id)`
+type ExportCursor = Option<(DateTime<Utc>, String)>;
+
+fn export_batches(
+    pool: SqlitePool,
+    format: ExportFormat,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+) -> impl futures::Stream<Item = std::result::Result<Bytes, std::io::Error>> {
+    stream::unfold(Some(ExportCursor::None), move |cursor: Option<ExportCursor>| {
+        let pool = pool.clone();
+        async move {
+            let after = cursor?;
+            let rows = sqlx::query(
+                r#"
+                SELECT id, actor_id, action, target, details, created_at
+                FROM audit_log
+                WHERE ($1 IS NULL OR created_at >= $1)
+                  AND ($2 IS NULL OR created_at < $2)
+                  AND ($3 IS NULL OR created_at > $3 OR (created_at = $3 AND id > $4))
+                ORDER BY created_at ASC, id ASC
+                LIMIT $5
+                "#
+            )
+            .bind(from)
+            .bind(to)
+            .bind(after.as_ref().map(|(created_at, _)| *created_at))
+            .bind(after.as_ref().map(|(_, id)| id.clone()))
+            .bind(EXPORT_BATCH_SIZE)
+            .fetch_all(&pool)
+            .await;
+
+            let rows = match rows {
+                Ok(rows) if rows.is_empty() => return None,
+                Ok(rows) => rows,
+                Err(e) => {
+                    tracing::error!("Audit export failed: {}", e);
+                    return Some((Err(std::io::Error::other(e.to_string())), None));
+                }
+            };
+
+            let entries: Vec<AuditEntry> = rows.iter().map(AuditEntry::from_row).collect();
+            let chunk: String = entries.iter().map(|entry| entry.render(format)).collect();
+            let next = (rows.len() as i64 == EXPORT_BATCH_SIZE)
+                .then(|| entries.last().map(|last| (last.created_at, last.id.clone())));
+
+            Some((Ok(Bytes::from(chunk)), next))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_export_rendering() {
+        let entry = AuditEntry {
+            id: "0b6c".to_string(),
+            created_at: DateTime::parse_from_rfc3339("2024-01-15T12:00:00Z").unwrap().with_timezone(&Utc),
+            actor_id: "u1".to_string(),
+            action: "repository.share".to_string(),
+            target: "team/app".to_string(),
+            details: Some(serde_json::json!({ "permission": "pull", "note": "a \"quoted\", value" })),
+        };
+
+        let ndjson: serde_json::Value = serde_json::from_str(entry.render(ExportFormat::Ndjson).trim_end()).unwrap();
+        assert_eq!(ndjson["action"], "repository.share");
+        assert_eq!(ndjson["details"]["permission"], "pull");
+
+        let csv = entry.render(ExportFormat::Csv);
+        assert!(csv.starts_with("0b6c,2024-01-15T12:00:00+00:00,u1,repository.share,team/app,\"{"));
+        assert!(csv.contains(r#"\""quoted\"""#));
+        assert!(csv.ends_with("}\"\n"));
+
+        assert_eq!(csv_field("plain"), "plain");
+        assert_eq!(csv_field("line\nbreak"), "\"line\nbreak\"");
+    }
+}
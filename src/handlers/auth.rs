@@ -1,35 +1,161 @@
 use crate::{
-    config::OAuthProvider,
+    auth::{
+        lockout::LoginLockout,
+        jwt::{generate_scopes_for_role, generate_token, generate_token_with_access, parse_scopes},
+        middleware::{decode_basic_auth, grant_access, AuthenticatedUser, ANONYMOUS_ID, ROBOT_USERNAME_PREFIX, SHARE_USERNAME_PREFIX},
+    },
+    config::{OAuthProvider, ServerConfig},
+    database::queries::record_audit,
     error::{Error, Result},
     models::{LoginRequest, LoginResponse, UserModel},
     server::AppState,
-    types::Claims,
+    handlers::{robots, shares},
     utils::verify_password,
 };
 use axum::{
-    extract::{Path, Query, State},
+    extract::{ConnectInfo, Form, Path, Query, State},
+    http::{header, HeaderMap},
     response::{IntoResponse, Redirect},
     Json,
 };
 use chrono::{Duration, Utc};
-use jsonwebtoken::{encode, EncodingKey, Header};
+use hmac::{Hmac, Mac};
 use oauth2::{
     basic::BasicClient, AuthUrl, AuthorizationCode, ClientId, ClientSecret, CsrfToken,
     RedirectUrl, Scope, TokenResponse, TokenUrl,
 };
 use serde::Deserialize;
+use sha2::Sha256;
 use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Cookie binding an OAuth flow's `state` to the browser that started it
+const OAUTH_STATE_COOKIE: &str = "ghostdock_oauth_state";
+/// Time allowed to sign in at the provider before the flow has to be restarted
+const OAUTH_STATE_TTL_SECS: i64 = 600;
 
 /// Handle user login with username/password
 pub async fn login(
     State(state): State<AppState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
     Json(request): Json<LoginRequest>,
 ) -> Result<impl IntoResponse> {
+    let client_ip = client_ip(&state.config.server, &headers, peer.map(|ConnectInfo(addr)| addr));
+    let user = authenticate_password_guarded(&state, &request.username, &request.password, client_ip.as_deref()).await?;
+
+    // Update last login
+    sqlx::query("UPDATE users SET last_login = $1 WHERE id = $2")
+        .bind(Utc::now())
+        .bind(&user.id)
+        .execute(&state.database.pool)
+        .await?;
+
+    let (token, expires_at) = session_token(&state, &user)?;
+
+    Ok(Json(LoginResponse {
+        token,
+        user,
+        expires_at,
+    }))
+}
+
+/// Role whose scopes an account's tokens carry
+fn account_role(user: &UserModel) -> &'static str {
+    if user.is_admin { "admin" } else { "user" }
+}
+
+/// Token for an interactive session, carrying the account's role scopes, and when it expires
+fn session_token(state: &AppState, user: &UserModel) -> Result<(String, chrono::DateTime<Utc>)> {
+    let scopes = generate_scopes_for_role(account_role(user));
+    let token = generate_token(&user.id.to_string(), &user.username, &user.email, scopes, &state.jwt)?;
+    let expires_at = Utc::now() + Duration::hours(state.jwt.expiration_hours as i64);
+    Ok((token, expires_at))
+}
+
+/// Client address for lockout bookkeeping: the TCP peer, or the forwarded origin when the
+/// peer is one of `[server] trusted_proxies` (anyone else could forge `X-Forwarded-For`).
+/// The forwarded chain is read from the nearest hop back, stopping at the first address
+/// that isn't a trusted proxy; entries before it are whatever the client sent.
+pub fn client_ip(config: &ServerConfig, headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<String> {
+    let peer = peer?.ip();
+    if !config.is_trusted_proxy(peer) {
+        return Some(peer.to_string());
+    }
+
+    let chain: Vec<IpAddr> = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|hop| hop.trim().parse().ok())
+        .collect();
+    let client = chain
+        .iter()
+        .rev()
+        .find(|hop| !config.is_trusted_proxy(**hop))
+        .or(chain.first())
+        .copied()
+        .unwrap_or(peer);
+    Some(client.to_string())
+}
+
+/// Password authentication with brute-force protection.
+/// Repeated failures lock the username and client IP, answering 429 until the lock expires.
+async fn authenticate_password_guarded(
+    state: &AppState,
+    username: &str,
+    password: &str,
+    client_ip: Option<&str>,
+) -> Result<UserModel> {
+    let mut keys = vec![LoginLockout::user_key(username)];
+    keys.extend(client_ip.map(LoginLockout::ip_key));
+
+    let lockout = &state.login_lockout;
+    if let Some(retry_after) = lockout.locked_for(&keys) {
+        return Err(Error::too_many_requests("Too many failed login attempts, try again later", retry_after));
+    }
+
+    match authenticate_password(state, username, password).await {
+        Ok(user) => {
+            lockout.record_success(&keys);
+            Ok(user)
+        }
+        Err(e @ Error::Authentication { .. }) => {
+            let locked = lockout.record_failure(&keys);
+            if locked.is_empty() {
+                return Err(e);
+            }
+
+            tracing::warn!("Locking login for {:?} after repeated failures", locked);
+            record_audit(
+                state,
+                "anonymous",
+                "auth.lockout",
+                username,
+                Some(serde_json::json!({
+                    "client_ip": client_ip,
+                    "locked": locked,
+                    "lockout_secs": lockout.lockout_secs(),
+                })),
+            )
+            .await?;
+
+            Err(Error::too_many_requests("Too many failed login attempts, try again later", lockout.lockout_secs()))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Verify username/email and password against the users table
+async fn authenticate_password(state: &AppState, username: &str, password: &str) -> Result<UserModel> {
     // Find user by username or email
     let user = sqlx::query_as::<_, UserModel>(
         "SELECT * FROM users WHERE username = $1 OR email = $1"
     )
-    .bind(&request.username)
+    .bind(username)
     .fetch_optional(&state.database.pool)
     .await?
     .ok_or_else(|| Error::authentication("Invalid username or password"))?;
@@ -44,39 +170,126 @@ pub async fn login(
         .as_ref()
         .ok_or_else(|| Error::authentication("Password authentication not available"))?;
 
-    if !verify_password(&request.password, password_hash).await? {
+    if !verify_password(password, password_hash).await? {
         return Err(Error::authentication("Invalid username or password"));
     }
 
-    // Update last login
-    sqlx::query("UPDATE users SET last_login = $1 WHERE id = $2")
-        .bind(Utc::now())
-        .bind(&user.id)
-        .execute(&state.database.pool)
-        .await?;
+    Ok(user)
+}
+
+/// Resolve account Basic credentials to a principal with its role and granted scopes,
+/// under the same lockout as interactive logins
+pub(crate) async fn authenticate_basic_user(
+    state: &AppState,
+    username: &str,
+    password: &str,
+    client_ip: Option<&str>,
+) -> Result<AuthenticatedUser> {
+    let user = authenticate_password_guarded(state, username, password, client_ip).await?;
+    let mut scopes = generate_scopes_for_role(account_role(&user));
+    scopes.extend(crate::handlers::teams::granted_scopes(&state.database.pool, &user.id.to_string()).await?);
+
+    Ok(AuthenticatedUser {
+        id: user.id.to_string(),
+        name: user.username,
+        email: user.email,
+        scopes,
+    })
+}
+
+/// Docker registry token endpoint
+/// Handles repeated `scope` parameters and grants the permitted subset of each
+pub async fn registry_token(
+    State(state): State<AppState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    Query(params): Query<Vec<(String, String)>>,
+    headers: HeaderMap,
+) -> Result<impl IntoResponse> {
+    let credentials = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(decode_basic_auth);
+    let client_ip = client_ip(&state.config.server, &headers, peer.map(|ConnectInfo(addr)| addr));
+    let user = token_principal(&state, credentials, client_ip.as_deref()).await?;
+
+    let requested = parse_scopes(
+        params
+            .iter()
+            .filter(|(key, _)| key == "scope")
+            .map(|(_, value)| value.as_str()),
+    );
+    let access = grant_access(&state, &requested, &user).await?;
+
+    let jwt_config = &state.jwt;
+    let token = generate_token_with_access(&user.id, &user.name, &user.email, vec![], access, jwt_config)?;
+
+    Ok(Json(serde_json::json!({
+        "token": token,
+        "access_token": token,
+        "expires_in": jwt_config.expiration_hours * 3600,
+        "issued_at": Utc::now().to_rfc3339(),
+    })))
+}
 
-    // Generate JWT token
-    let expires_at = Utc::now() + Duration::seconds(state.config.auth.jwt_expiration as i64);
-    let claims = Claims {
-        sub: user.id.to_string(),
-        username: user.username.clone(),
-        email: user.email.clone(),
-        is_admin: user.is_admin,
-        exp: expires_at.timestamp() as usize,
-        iat: Utc::now().timestamp() as usize,
+/// OAuth2 form of the token endpoint (`POST /token`), which containerd and BuildKit try
+/// before falling back to `GET`. Only the `password` grant is supported; no refresh tokens
+/// are issued, so clients sign in again when the access token expires.
+pub async fn registry_oauth_token(
+    State(state): State<AppState>,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    Form(params): Form<Vec<(String, String)>>,
+) -> Result<impl IntoResponse> {
+    let param = |name: &str| params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str());
+    match param("grant_type") {
+        Some("password") => {}
+        Some(grant_type) => return Err(Error::bad_request(format!("Unsupported grant_type: {}", grant_type))),
+        None => return Err(Error::bad_request("grant_type is required")),
+    }
+    let (Some(username), Some(password)) = (param("username"), param("password")) else {
+        return Err(Error::bad_request("username and password are required"));
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.config.auth.jwt_secret.as_ref()),
-    )?;
+    let client_ip = client_ip(&state.config.server, &headers, peer.map(|ConnectInfo(addr)| addr));
+    let user = token_principal(&state, Some((username.to_string(), password.to_string())), client_ip.as_deref()).await?;
 
-    Ok(Json(LoginResponse {
-        token,
-        user,
-        expires_at,
-    }))
+    // Scopes are space-separated here rather than repeated
+    let scope = param("scope").unwrap_or_default();
+    let access = grant_access(&state, &parse_scopes([scope]), &user).await?;
+
+    let jwt_config = &state.jwt;
+    let token = generate_token_with_access(&user.id, &user.name, &user.email, vec![], access, jwt_config)?;
+
+    Ok(Json(serde_json::json!({
+        "access_token": token,
+        "scope": scope,
+        "expires_in": jwt_config.expiration_hours * 3600,
+        "issued_at": Utc::now().to_rfc3339(),
+    })))
+}
+
+/// Who a token request is for: robot, share or account credentials, or anonymous without any.
+/// Anonymous callers hold no scopes; public pulls are granted by `grant_access`.
+async fn token_principal(
+    state: &AppState,
+    credentials: Option<(String, String)>,
+    client_ip: Option<&str>,
+) -> Result<AuthenticatedUser> {
+    match credentials {
+        Some((username, password)) if username.starts_with(ROBOT_USERNAME_PREFIX) => {
+            robots::authenticate_robot(state, &username, &password).await
+        }
+        Some((username, password)) if username.starts_with(SHARE_USERNAME_PREFIX) => {
+            shares::authenticate_share(state, &username, &password).await
+        }
+        Some((username, password)) => authenticate_basic_user(state, &username, &password, client_ip).await,
+        None => Ok(AuthenticatedUser {
+            id: ANONYMOUS_ID.to_string(),
+            name: ANONYMOUS_ID.to_string(),
+            email: String::new(),
+            scopes: vec![],
+        }),
+    }
 }
 
 /// Handle user logout
@@ -109,22 +322,42 @@ pub async fn oauth_redirect(
 
     let client = create_oauth_client(&provider, oauth_config)?;
 
-    let (auth_url, _csrf_token) = client
+    let (auth_url, csrf_token) = client
         .authorize_url(CsrfToken::new_random)
         .add_scope(Scope::new("openid".to_string()))
         .add_scope(Scope::new("profile".to_string()))
         .add_scope(Scope::new("email".to_string()))
         .url();
 
-    Ok(Redirect::to(auth_url.as_ref()))
+    // The provider echoes `state` back to the callback; only this browser holds a cookie signed for it
+    let expires = Utc::now().timestamp() + OAUTH_STATE_TTL_SECS;
+    let cookie = format!(
+        "{}={}; Path=/auth/oauth/{}; Max-Age={}; HttpOnly; SameSite=Lax{}",
+        OAUTH_STATE_COOKIE,
+        oauth_state_cookie(&state.config.auth.jwt_secret, &provider, csrf_token.secret(), expires),
+        provider,
+        OAUTH_STATE_TTL_SECS,
+        if oauth_config.redirect_url.starts_with("https://") { "; Secure" } else { "" },
+    );
+
+    Ok(([(header::SET_COOKIE, cookie)], Redirect::to(auth_url.as_ref())))
 }
 
 /// OAuth callback endpoint
 pub async fn oauth_callback(
     State(state): State<AppState>,
     Path(provider): Path<String>,
+    headers: HeaderMap,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<impl IntoResponse> {
+    verify_oauth_state(
+        &state.config.auth.jwt_secret,
+        &provider,
+        &headers,
+        params.get("state").map(String::as_str),
+        Utc::now().timestamp(),
+    )?;
+
     let code = params
         .get("code")
         .ok_or_else(|| Error::authentication("Authorization code not provided"))?;
@@ -161,25 +394,59 @@ pub async fn oauth_callback(
         .execute(&state.database.pool)
         .await?;
 
-    // Generate JWT token
-    let expires_at = Utc::now() + Duration::seconds(state.config.auth.jwt_expiration as i64);
-    let claims = Claims {
-        sub: user.id.to_string(),
-        username: user.username.clone(),
-        email: user.email.clone(),
-        is_admin: user.is_admin,
-        exp: expires_at.timestamp() as usize,
-        iat: Utc::now().timestamp() as usize,
-    };
+    let (token, _) = session_token(&state, &user)?;
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.config.auth.jwt_secret.as_ref()),
-    )?;
+    // The state is single-use; drop the cookie now that the flow is complete
+    let clear_cookie = format!("{}=; Path=/auth/oauth/{}; Max-Age=0; HttpOnly; SameSite=Lax", OAUTH_STATE_COOKIE, provider);
 
     // Redirect to frontend with token (you might want to use a different approach)
-    Ok(Redirect::to(&format!("/auth/callback?token={}", token)))
+    Ok((
+        [(header::SET_COOKIE, clear_cookie)],
+        Redirect::to(&format!("/auth/callback?token={}", token)),
+    ))
+}
+
+fn oauth_state_mac(secret: &str, provider: &str, csrf_state: &str, expires: i64) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}\n{}\n{}", provider, expires, csrf_state).as_bytes());
+    mac
+}
+
+/// Cookie value `<expires>.<mac>`; the state itself stays out of the cookie and is
+/// proven by the MAC when the provider sends it back
+fn oauth_state_cookie(secret: &str, provider: &str, csrf_state: &str, expires: i64) -> String {
+    let mac = oauth_state_mac(secret, provider, csrf_state, expires).finalize().into_bytes();
+    format!("{}.{}", expires, hex::encode(mac))
+}
+
+fn cookie_value<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find_map(|(key, value)| (key == name).then_some(value))
+}
+
+/// Reject a callback whose `state` wasn't issued to this browser for this provider, or has expired
+fn verify_oauth_state(secret: &str, provider: &str, headers: &HeaderMap, returned: Option<&str>, now: i64) -> Result<()> {
+    let returned = returned.ok_or_else(|| Error::authentication("OAuth state not provided"))?;
+    let cookie = cookie_value(headers, OAUTH_STATE_COOKIE)
+        .ok_or_else(|| Error::authentication("OAuth sign-in was not started from this browser"))?;
+
+    let invalid = || Error::authentication("OAuth state does not match; restart sign-in");
+    let (expires, mac) = cookie.split_once('.').ok_or_else(invalid)?;
+    let expires: i64 = expires.parse().map_err(|_| invalid())?;
+    let mac = hex::decode(mac).map_err(|_| invalid())?;
+    oauth_state_mac(secret, provider, returned, expires)
+        .verify_slice(&mac)
+        .map_err(|_| invalid())?;
+
+    if expires < now {
+        return Err(Error::authentication("OAuth sign-in took too long; restart sign-in"));
+    }
+    Ok(())
 }
 
 fn create_oauth_client(provider: &str, config: &OAuthProvider) -> Result<BasicClient> {
@@ -315,3 +582,57 @@ async fn create_or_update_oauth_user(
 
     Ok(new_user)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "test-secret-that-is-long-enough";
+
+    fn with_cookie(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::COOKIE, format!("theme=dark; {}={}", OAUTH_STATE_COOKIE, value).parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_oauth_state_validation() {
+        let now = 1_700_000_000;
+        let cookie = oauth_state_cookie(SECRET, "github", "state-abc", now + OAUTH_STATE_TTL_SECS);
+        let headers = with_cookie(&cookie);
+
+        assert!(verify_oauth_state(SECRET, "github", &headers, Some("state-abc"), now).is_ok());
+
+        // Missing or wrong state
+        assert!(verify_oauth_state(SECRET, "github", &headers, None, now).is_err());
+        assert!(verify_oauth_state(SECRET, "github", &headers, Some("state-xyz"), now).is_err());
+        // No cookie: the flow was started in another browser, e.g. by an attacker
+        assert!(verify_oauth_state(SECRET, "github", &HeaderMap::new(), Some("state-abc"), now).is_err());
+        // Issued for a different provider, signed with another key, or expired
+        assert!(verify_oauth_state(SECRET, "google", &headers, Some("state-abc"), now).is_err());
+        assert!(verify_oauth_state("another-secret", "github", &headers, Some("state-abc"), now).is_err());
+        assert!(verify_oauth_state(SECRET, "github", &headers, Some("state-abc"), now + OAUTH_STATE_TTL_SECS + 1).is_err());
+        // A tampered expiry breaks the signature
+        let extended = format!("{}.{}", now + 86_400, cookie.split_once('.').unwrap().1);
+        assert!(verify_oauth_state(SECRET, "github", &with_cookie(&extended), Some("state-abc"), now).is_err());
+    }
+
+    #[test]
+    fn test_client_ip_trusts_only_configured_proxies() {
+        let mut config = crate::config::Config::default().server;
+        let mut headers = HeaderMap::new();
+        headers.insert("x-forwarded-for", "1.2.3.4, 203.0.113.9".parse().unwrap());
+        let proxy: SocketAddr = "10.0.0.5:40000".parse().unwrap();
+        let direct: SocketAddr = "198.51.100.7:40000".parse().unwrap();
+
+        // Without trusted proxies the header is ignored
+        assert_eq!(client_ip(&config, &headers, Some(proxy)).as_deref(), Some("10.0.0.5"));
+
+        // Behind a trusted proxy the nearest untrusted hop is the client; the forged
+        // leftmost entry is not
+        config.trusted_proxies = vec!["10.0.0.0/8".to_string()];
+        assert_eq!(client_ip(&config, &headers, Some(proxy)).as_deref(), Some("203.0.113.9"));
+        assert_eq!(client_ip(&config, &headers, Some(direct)).as_deref(), Some("198.51.100.7"));
+        assert_eq!(client_ip(&config, &HeaderMap::new(), Some(proxy)).as_deref(), Some("10.0.0.5"));
+    }
+}
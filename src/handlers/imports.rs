@@ -0,0 +1,157 @@
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    error::{Error, Result},
+    import::{self, ImportRequest, RemoteRegistry},
+    server::AppState,
+    types::{Page, PageQuery},
+    utils::validate_repository_name,
+    database::queries::record_audit,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Source credentials for resuming a job; they are never persisted
+#[derive(Debug, Default, Deserialize)]
+pub struct ResumeImportRequest {
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+/// Start a background import from another registry (admin only).
+/// Progress is persisted with the job and streamed on the `import_progress` WebSocket topic.
+pub async fn start_import(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<ImportRequest>,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+
+    if request.repositories.is_empty() {
+        return Err(Error::validation("At least one repository is required"));
+    }
+    for name in &request.repositories {
+        validate_repository_name(name)?;
+    }
+    // Reject a malformed source before creating the job
+    RemoteRegistry::new(&request.source, None)?;
+
+    let job = import::create_job(&state, &request, &user).await?;
+    record_audit(
+        &state,
+        &user.id,
+        "import.start",
+        &job.id.to_string(),
+        Some(serde_json::json!({
+            "source": request.source,
+            "repositories": request.repositories,
+        })),
+    )
+    .await?;
+
+    import::spawn(state.clone(), &job, request, user)?;
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// List import jobs, newest first
+pub async fn list_imports(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+    let (limit, offset) = page.bounds()?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM import_jobs")
+        .fetch_one(&state.database.pool)
+        .await?;
+    let ids: Vec<Uuid> = sqlx::query_scalar("SELECT id FROM import_jobs ORDER BY created_at DESC LIMIT $1 OFFSET $2")
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(&state.database.pool)
+        .await?;
+
+    let mut jobs = Vec::with_capacity(ids.len());
+    for id in ids {
+        jobs.push(import::load_job(&state, id).await?);
+    }
+
+    Ok(Json(Page::new(jobs, total as u64, limit, offset)))
+}
+
+/// Current state of an import job
+pub async fn get_import(
+    State(state): State<AppState>,
+    Path(job_id): Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+    Ok(Json(import::load_job(&state, job_id).await?))
+}
+
+/// Cancel a running import; staged data is kept so the job can be resumed
+pub async fn cancel_import(
+    State(state): State<AppState>,
+    Path(job_id): Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+
+    let job = import::load_job(&state, job_id).await?;
+    if !state.imports.cancel(job_id) {
+        return Err(Error::conflict(format!(
+            "Import {} is not running (status: {})",
+            job_id,
+            job.progress.status.as_str()
+        )));
+    }
+
+    record_audit(&state, &user.id, "import.cancel", &job_id.to_string(), None).await?;
+
+    Ok(StatusCode::ACCEPTED)
+}
+
+/// Restart a failed, cancelled, or interrupted import from where it stopped
+pub async fn resume_import(
+    State(state): State<AppState>,
+    Path(job_id): Path<Uuid>,
+    user: AuthenticatedUser,
+    body: Option<Json<ResumeImportRequest>>,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+
+    let job = import::load_job(&state, job_id).await?;
+    if !job.progress.status.is_resumable() {
+        return Err(Error::conflict(format!(
+            "Import {} cannot be resumed (status: {})",
+            job_id,
+            job.progress.status.as_str()
+        )));
+    }
+
+    let credentials = body.map(|Json(b)| b).unwrap_or_default();
+    let request = ImportRequest {
+        source: job.source.clone(),
+        repositories: job.repositories.clone(),
+        username: credentials.username,
+        password: credentials.password,
+    };
+
+    record_audit(&state, &user.id, "import.resume", &job_id.to_string(), None).await?;
+    import::spawn(state.clone(), &job, request, user)?;
+
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+fn ensure_admin(user: &AuthenticatedUser) -> Result<()> {
+    if user.is_robot() || !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Imports require admin access"));
+    }
+    Ok(())
+}
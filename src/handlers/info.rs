@@ -0,0 +1,119 @@
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    config::{Config, StorageBackend},
+    error::Result,
+    server::AppState,
+    types::media_types,
+};
+use axum::{extract::State, response::IntoResponse, Json};
+use serde::Serialize;
+
+/// Registry capabilities for clients and the UI, assembled from the live configuration
+/// Anonymous callers get everything except storage details.
+
+#[derive(Debug, Serialize)]
+pub struct RegistryInfo {
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    pub version: &'static str,
+    pub manifest_media_types: Vec<&'static str>,
+    pub features: RegistryFeatures,
+    pub limits: RegistryLimits,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageInfo>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegistryFeatures {
+    /// Pushes are accepted (false while in read-only maintenance mode)
+    pub push: bool,
+    /// Manifests, tags, and blobs can be deleted (false while read-only)
+    pub deletion: bool,
+    pub anonymous_pull: bool,
+    /// At least one pull-through proxy rule has an upstream
+    pub proxy: bool,
+    pub content_trust: bool,
+    /// No vulnerability scanner is integrated
+    pub scanning: bool,
+    /// `/v2/<name>/referrers/<digest>` is served and fallback tags are maintained
+    pub referrers: bool,
+    pub read_only: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RegistryLimits {
+    pub max_manifest_size: u64,
+    pub max_blob_size: u64,
+    pub max_upload_size: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct StorageInfo {
+    pub backend: StorageBackend,
+    pub deduplication: bool,
+}
+
+/// Describe the registry (`GET /api/info`)
+pub async fn get_info(
+    State(state): State<AppState>,
+    user: Option<AuthenticatedUser>,
+) -> Result<impl IntoResponse> {
+    let proxying = state.proxy.rules().iter().any(|rule| rule.upstream.is_some());
+    let read_only = state.maintenance.is_read_only();
+
+    Ok(Json(registry_info(&state.config, read_only, proxying, user.is_some())))
+}
+
+fn registry_info(config: &Config, read_only: bool, proxying: bool, authenticated: bool) -> RegistryInfo {
+    RegistryInfo {
+        name: config.registry.name.clone(),
+        title: config.registry.title.clone(),
+        description: config.registry.description.clone(),
+        version: crate::VERSION,
+        manifest_media_types: vec![
+            media_types::DOCKER_MANIFEST_V2,
+            media_types::DOCKER_MANIFEST_LIST,
+            media_types::OCI_MANIFEST,
+            media_types::OCI_INDEX,
+        ],
+        features: RegistryFeatures {
+            push: !read_only,
+            deletion: !read_only,
+            anonymous_pull: config.auth.enable_anonymous_read,
+            proxy: proxying,
+            content_trust: config.registry.enable_content_trust,
+            scanning: false,
+            referrers: true,
+            read_only,
+        },
+        limits: RegistryLimits {
+            max_manifest_size: config.registry.max_manifest_size,
+            max_blob_size: config.registry.max_layer_size,
+            max_upload_size: config.storage.max_upload_size,
+        },
+        storage: authenticated.then(|| StorageInfo {
+            backend: config.storage.backend.clone(),
+            deduplication: config.storage.enable_deduplication,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_info_hides_storage_from_anonymous_callers() {
+        let config = Config::default();
+
+        let anonymous = serde_json::to_value(registry_info(&config, false, false, false)).unwrap();
+        assert!(anonymous.get("storage").is_none());
+        assert_eq!(anonymous["features"]["push"], true);
+
+        let authenticated = serde_json::to_value(registry_info(&config, true, true, true)).unwrap();
+        assert_eq!(authenticated["storage"]["backend"], "filesystem");
+        assert_eq!(authenticated["features"]["deletion"], false);
+        assert_eq!(authenticated["features"]["proxy"], true);
+    }
+}
@@ -1,76 +1,251 @@
 use crate::{
+    auth::middleware::{authorize_repository, AuthenticatedUser},
+    config::EmptyTagList,
     error::{Error, Result},
+    handlers::registry::{cache_headers, etag_matches},
+    performance::{CachedManifest, ExistenceCache, ExistenceInfo, ManifestCache, OperationClass},
+    referrers,
     server::AppState,
+    storage_fallback,
     types::*,
-    utils::{validate_repository_name, validate_tag_name, validate_digest, sha256_digest},
+    utils::{validate_platform, validate_repository_name, validate_tag_name, validate_digest, sha256_digest},
+    webhooks::{self, RepositoryEvent},
     database::queries::*,
 };
 use axum::{
-    extract::{Path, State, Request},
+    extract::{Path, Query, State, Request},
     response::{IntoResponse, Response},
     body::Body,
     http::{StatusCode, HeaderMap, header},
     Json,
 };
+use serde::Deserialize;
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use sqlx::Row;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
 use uuid::Uuid;
 
+#[derive(Debug, Deserialize)]
+pub struct ManifestQuery {
+    /// `os/architecture[/variant]` to resolve a manifest list to, even for clients that accept lists
+    pub platform: Option<String>,
+}
+
 /// Get manifest by tag or digest
+/// A manifest list pulled by tag is resolved to one platform's manifest unless the client accepts lists.
 pub async fn get_manifest(
     State(state): State<AppState>,
     Path((name, reference)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
+    Query(query): Query<ManifestQuery>,
+    request_headers: HeaderMap,
+    user: Option<AuthenticatedUser>,
+) -> Result<Response> {
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
+
+    let _permit = state.performance.acquire_operation(OperationClass::Pull).await?;
     
-    let repo = get_repository_by_name(&state, &name).await?;
-    
-    let manifest = if reference.starts_with("sha256:") {
-        // It's a digest
+    let by_digest = reference.starts_with("sha256:");
+    let manifest = if by_digest {
+        // Content behind a digest never changes, so repeat pulls are served from memory
         validate_digest(&reference)?;
-        get_manifest_by_digest(&state, &repo.id, &reference).await?
+        state.performance.manifest_cache
+            .get_or_load(&ManifestCache::key(&name, &reference), || load_manifest(&state, &name, &reference))
+            .await?
     } else {
-        // It's a tag
+        // Tags move, so they are always resolved from the database
         validate_tag_name(&reference)?;
-        get_manifest_by_tag(&state, &repo.id, &reference).await?
+        let manifest = Arc::new(load_manifest(&state, &name, &reference).await?);
+        negotiate_manifest(&state, &name, manifest, &request_headers, query.platform.as_deref()).await?
     };
     
-    // Parse the manifest content
-    let manifest_json: Value = serde_json::from_str(&manifest.content)
-        .map_err(|_| Error::internal("Invalid manifest JSON"))?;
-    
+    // Serve the stored bytes untouched so clients recompute the same digest
     let mut headers = HeaderMap::new();
     headers.insert(
         header::CONTENT_TYPE,
         manifest.media_type.parse().unwrap_or("application/vnd.docker.distribution.manifest.v2+json".parse().unwrap())
     );
     headers.insert(
-        "Docker-Content-Digest",
+        registry_headers::CONTENT_DIGEST,
         manifest.digest.parse().unwrap()
     );
+    manifest_cache_headers(&state, &mut headers, &manifest.digest, by_digest, user.is_some());
+    if etag_matches(&request_headers, &manifest.digest) {
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
     headers.insert(
         header::CONTENT_LENGTH,
         manifest.content.len().to_string().parse().unwrap()
     );
 
-    Ok((StatusCode::OK, headers, manifest.content))
+    if let Err(e) = record_usage(&state, &manifest.repository_id, UsageEvent::Pull { bytes: manifest.content.len() as i64 }).await {
+        tracing::warn!("Failed to record pull for {}: {}", name, e);
+    }
+
+    Ok((StatusCode::OK, headers, manifest.content.clone()).into_response())
+}
+
+/// Caching headers for a manifest response. A tag may resolve to a different platform
+/// manifest depending on `Accept`, so caches have to key tag responses on it.
+fn manifest_cache_headers(state: &AppState, headers: &mut HeaderMap, digest: &str, by_digest: bool, authenticated: bool) {
+    cache_headers(&state.config.cache, headers, digest, by_digest, authenticated);
+    if !by_digest {
+        headers.insert(header::VARY, header::ACCEPT.as_str().parse().unwrap());
+    }
+}
+
+/// Follow a manifest list down to one platform's manifest while the client can't take the list.
+/// Digest pulls never come through here: a digest names exactly the bytes to return.
+async fn negotiate_manifest(
+    state: &AppState,
+    name: &str,
+    mut manifest: Arc<CachedManifest>,
+    headers: &HeaderMap,
+    platform: Option<&str>,
+) -> Result<Arc<CachedManifest>> {
+    if let Some(platform) = platform {
+        validate_platform(platform)?;
+    }
+
+    let mut preferred = None;
+    let mut depth = 0;
+    while needs_platform_resolution(&manifest.media_type, headers, platform) {
+        depth += 1;
+        if depth > state.config.registry.max_manifest_list_depth {
+            return Err(Error::manifest_invalid(format!("Manifest list '{}' is nested too deeply", manifest.digest)));
+        }
+
+        let index: Value = serde_json::from_slice(&manifest.content)
+            .map_err(|e| Error::manifest_invalid(format!("Stored manifest list is not valid JSON: {}", e)))?;
+        if preferred.is_none() {
+            preferred = Some(preferred_platform(state, &manifest.repository_id).await?);
+        }
+        let (digest, _) = select_platform(&index, platform, preferred.as_deref().unwrap_or(DEFAULT_PLATFORM))?;
+        manifest = state.performance.manifest_cache
+            .get_or_load(&ManifestCache::key(name, &digest), || load_manifest(state, name, &digest))
+            .await?;
+    }
+
+    Ok(manifest)
+}
+
+/// Platform for clients that don't name one: the repository's override, else the registry default
+async fn preferred_platform(state: &AppState, repository_id: &Uuid) -> Result<String> {
+    Ok(get_default_platform(state, repository_id)
+        .await?
+        .unwrap_or_else(|| state.config.registry.default_platform.clone()))
+}
+
+/// A manifest list must be resolved when a platform was asked for or the client doesn't accept its type
+fn needs_platform_resolution(media_type: &str, headers: &HeaderMap, platform: Option<&str>) -> bool {
+    let is_list = media_type == media_types::DOCKER_MANIFEST_LIST || media_type == media_types::OCI_INDEX;
+    is_list && (platform.is_some() || !accepts_media_type(headers, media_type))
+}
+
+/// Whether the `Accept` headers admit a media type; no `Accept` at all admits anything
+fn accepts_media_type(headers: &HeaderMap, media_type: &str) -> bool {
+    let accepted: Vec<&str> = headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|entry| entry.split(';').next().unwrap_or(entry).trim())
+        .filter(|entry| !entry.is_empty())
+        .collect();
+
+    accepted.is_empty() || accepted.iter().any(|entry| *entry == media_type || *entry == "*/*")
+}
+
+/// Resolve a tag or digest, filling misses from the pull-through cache when a rule proxies the repository
+async fn load_manifest(state: &AppState, name: &str, reference: &str) -> Result<CachedManifest> {
+    match load_local_manifest(state, name, reference).await {
+        Err(e @ (Error::NotFound { .. } | Error::NameUnknown { .. })) => {
+            state.proxy.fetch_manifest(state, name, reference).await?.ok_or(e)
+        }
+        result => result,
+    }
+}
+
+/// Resolve a tag or digest and check the manifest is safe to serve
+async fn load_local_manifest(state: &AppState, name: &str, reference: &str) -> Result<CachedManifest> {
+    let repo = get_repository_by_name(state, name).await?;
+
+    let manifest = if reference.starts_with("sha256:") {
+        get_manifest_by_digest(state, &repo.id, reference).await?
+    } else {
+        get_manifest_by_tag(state, &repo.id, reference).await?
+    };
+
+    // Refuse to serve a manifest list whose references loop or nest too deeply
+    check_manifest_list_graph(
+        &RepositoryManifests { pool: &state.database.pool, repository_id: &repo.id },
+        &manifest.digest,
+        &manifest.media_type,
+        &manifest.content,
+        state.config.registry.max_manifest_list_depth,
+        false,
+    )
+    .await?;
+
+    if state.config.registry.verify_manifest_digest {
+        verify_stored_digest(&manifest.digest, &manifest.content)?;
+    }
+
+    Ok(CachedManifest {
+        repository_id: repo.id,
+        digest: manifest.digest,
+        media_type: manifest.media_type,
+        content: manifest.content.into(),
+    })
 }
 
-/// Head manifest by tag or digest
+/// Head manifest by tag or digest, negotiating manifest lists the same way as `get_manifest`
 pub async fn head_manifest(
     State(state): State<AppState>,
     Path((name, reference)): Path<(String, String)>,
+    Query(query): Query<ManifestQuery>,
+    request_headers: HeaderMap,
+    user: Option<AuthenticatedUser>,
 ) -> Result<impl IntoResponse> {
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
     
-    let repo = get_repository_by_name(&state, &name).await?;
-    
-    let manifest = if reference.starts_with("sha256:") {
+    if reference.starts_with("sha256:") {
         validate_digest(&reference)?;
-        get_manifest_by_digest(&state, &repo.id, &reference).await?
     } else {
         validate_tag_name(&reference)?;
-        get_manifest_by_tag(&state, &repo.id, &reference).await?
+    }
+
+    // Check existence through the cache, which also remembers misses
+    let key = ExistenceCache::manifest_key(&name, &reference);
+    let manifest = state.performance.existence_cache
+        .get_or_lookup(&key, || async {
+            // Goes through the pull-through cache, since clients often HEAD before they GET
+            match load_manifest(&state, &name, &reference).await {
+                Ok(manifest) => Ok(Some(ExistenceInfo {
+                    size: manifest.content.len() as i64,
+                    digest: manifest.digest,
+                    media_type: manifest.media_type,
+                })),
+                Err(Error::NotFound { .. }) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Manifest '{}' not found", reference)))?;
+
+    let platform = query.platform.as_deref();
+    let manifest = if !reference.starts_with("sha256:") && needs_platform_resolution(&manifest.media_type, &request_headers, platform) {
+        let list = Arc::new(load_manifest(&state, &name, &reference).await?);
+        let resolved = negotiate_manifest(&state, &name, list, &request_headers, platform).await?;
+        ExistenceInfo {
+            digest: resolved.digest.clone(),
+            media_type: resolved.media_type.clone(),
+            size: resolved.content.len() as i64,
+        }
+    } else {
+        manifest
     };
     
     let mut headers = HeaderMap::new();
@@ -79,13 +254,14 @@ pub async fn head_manifest(
         manifest.media_type.parse().unwrap_or("application/vnd.docker.distribution.manifest.v2+json".parse().unwrap())
     );
     headers.insert(
-        "Docker-Content-Digest",
+        registry_headers::CONTENT_DIGEST,
         manifest.digest.parse().unwrap()
     );
     headers.insert(
         header::CONTENT_LENGTH,
-        manifest.content.len().to_string().parse().unwrap()
+        manifest.size.to_string().parse().unwrap()
     );
+    manifest_cache_headers(&state, &mut headers, &manifest.digest, reference.starts_with("sha256:"), user.is_some());
 
     Ok((StatusCode::OK, headers))
 }
@@ -94,98 +270,126 @@ pub async fn head_manifest(
 pub async fn put_manifest(
     State(state): State<AppState>,
     Path((name, reference)): Path<(String, String)>,
+    user: Option<AuthenticatedUser>,
     request: Request<Body>,
 ) -> Result<impl IntoResponse> {
     validate_repository_name(&name)?;
-    
-    // Get or create repository
-    let repo = get_or_create_repository(&state, &name).await?;
+    authorize_repository(&state, &user, &name, "push").await?;
+
+    let _permit = state.performance.acquire_operation(OperationClass::ManifestWrite).await?;
+
+    // A repository is only created once the manifest is known to be valid; until then one
+    // that doesn't exist holds no manifests or blobs, which the nil id matches
+    let existing = match get_repository_by_name(&state, &name).await {
+        Ok(repo) => Some(repo),
+        Err(Error::NotFound { .. }) => None,
+        Err(e) => return Err(e),
+    };
+    let repository_id = existing.as_ref().map_or_else(Uuid::nil, |repo| repo.id);
     
     // Read manifest content
-    let body_bytes = axum::body::to_bytes(request.into_body(), usize::MAX).await
-        .map_err(|_| Error::bad_request("Failed to read manifest body"))?;
-    
-    let manifest_content = String::from_utf8(body_bytes.to_vec())
-        .map_err(|_| Error::bad_request("Invalid UTF-8 in manifest"))?;
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let max_size = usize::try_from(state.config.registry.max_manifest_size).unwrap_or(usize::MAX);
+    let body_bytes = axum::body::to_bytes(request.into_body(), max_size).await
+        .map_err(|_| Error::manifest_invalid(format!("Manifest body is unreadable or larger than {} bytes", max_size)))?;
     
-    // Calculate digest  
-    let calculated_digest = sha256_digest(manifest_content.as_bytes());
+    // The digest covers the exact received bytes, which are stored and served as-is
+    let manifest_content = body_bytes.as_ref();
+    let calculated_digest = pushed_manifest_digest(&reference, manifest_content)?;
     
-    // Parse manifest to determine media type
-    let manifest_json: Value = serde_json::from_str(&manifest_content)
-        .map_err(|_| Error::bad_request("Invalid JSON manifest"))?;
-    
-    let media_type = manifest_json.get("mediaType")
-        .and_then(|v| v.as_str())
-        .unwrap_or("application/vnd.docker.distribution.manifest.v2+json")
-        .to_string();
+    // The typed view drives validation and blob linking
+    let manifest = ManifestType::parse(manifest_content, content_type.as_deref())?;
+    let media_type = manifest.media_type();
     
     // Validate manifest structure
-    validate_manifest_structure(&manifest_json)?;
+    validate_manifest_structure(&manifest)?;
+    check_manifest_list_graph(
+        &RepositoryManifests { pool: &state.database.pool, repository_id: &repository_id },
+        &calculated_digest,
+        media_type,
+        manifest_content,
+        state.config.registry.max_manifest_list_depth,
+        true,
+    )
+    .await?;
+    let artifact_type = manifest.artifact_type();
+    let allowed_types = get_allowed_artifact_types(&state, &repository_id).await?;
+    check_artifact_type_allowed(&name, allowed_types.as_deref(), artifact_type)?;
+
+    // Garbage collection trusts `manifest_blobs`, so every blob must be in the repository first
+    for digest in manifest.blob_digests() {
+        ensure_blob_pushed(&state, &repository_id, digest).await?;
+    }
     
+    // Concurrent pushes to one tag are applied one at a time, so the last to arrive wins
+    let tag_lock = if reference.starts_with("sha256:") {
+        None
+    } else {
+        validate_tag_name(&reference)?;
+        Some(state.performance.tag_locks.lock(&name, &reference).await?)
+    };
+
+    let repo = match existing {
+        Some(repo) => repo,
+        None => get_or_create_repository(&state, &name, &user).await?,
+    };
+
     // Store manifest
-    let manifest_id = Uuid::new_v4();
-    sqlx::query(
-        r#"
-        INSERT INTO manifests (id, repository_id, digest, media_type, content, size, created_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $7)
-        ON CONFLICT (repository_id, digest) DO UPDATE SET
-            media_type = EXCLUDED.media_type,
-            content = EXCLUDED.content,
-            size = EXCLUDED.size
-        "#
+    let manifest_id = store_manifest(
+        &state.database.pool,
+        &repo.id,
+        &calculated_digest,
+        media_type,
+        manifest_content,
+        artifact_type,
     )
-    .bind(manifest_id)
-    .bind(&repo.id)
-    .bind(&calculated_digest)
-    .bind(&media_type)
-    .bind(&manifest_content)
-    .bind(manifest_content.len() as i64)
-    .bind(chrono::Utc::now())
-    .execute(&state.database.pool)
     .await?;
     
     // If reference is a tag (not a digest), create/update the tag
-    if !reference.starts_with("sha256:") {
-        validate_tag_name(&reference)?;
-        
-        sqlx::query(
-            r#"
-            INSERT INTO tags (id, repository_id, name, manifest_id, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5, $6)
-            ON CONFLICT (repository_id, name) DO UPDATE SET
-                manifest_id = EXCLUDED.manifest_id,
-                updated_at = EXCLUDED.updated_at
-            "#
-        )
-        .bind(Uuid::new_v4())
-        .bind(&repo.id)
-        .bind(&reference)
-        .bind(manifest_id)
-        .bind(chrono::Utc::now())
-        .bind(chrono::Utc::now())
-        .execute(&state.database.pool)
-        .await?;
+    if tag_lock.is_some() {
+        tag_manifest(&state.database.pool, &repo.id, &reference, manifest_id).await?;
     }
+    drop(tag_lock);
     
-    // Create blob relationships if this is an image manifest
-    if let Some(config) = manifest_json.get("config") {
-        if let Some(digest) = config.get("digest").and_then(|d| d.as_str()) {
-            link_manifest_to_blob(&state, manifest_id, digest).await?;
-        }
-    }
+    state.performance.existence_cache.invalidate_manifests(&name);
+    // The listing shows which artifact types each repository holds
+    state.performance.catalog_cache.invalidate();
+    state.performance.manifest_cache.insert(
+        &ManifestCache::key(&name, &calculated_digest),
+        CachedManifest {
+            repository_id: repo.id,
+            digest: calculated_digest.clone(),
+            media_type: media_type.to_string(),
+            content: body_bytes.clone(),
+        },
+    );
     
-    if let Some(layers) = manifest_json.get("layers").and_then(|l| l.as_array()) {
-        for layer in layers {
-            if let Some(digest) = layer.get("digest").and_then(|d| d.as_str()) {
-                link_manifest_to_blob(&state, manifest_id, digest).await?;
-            }
+    // Create blob relationships if this is an image manifest; blobs still being verified
+    // are linked by the verification once they are stored
+    for digest in manifest.blob_digests() {
+        if !state.pending_blobs.link_when_verified(repo.id, digest, manifest_id)
+            && !link_manifest_to_blob(&state, manifest_id, digest).await?
+        {
+            tracing::warn!("Blob {} of manifest {} in {} failed verification", digest, calculated_digest, name);
         }
     }
 
+    // Keep the subject's fallback tag in step for clients that discover referrers by tag
+    let subject = manifest.subject().map(|subject| subject.digest.clone());
+    if let Some(subject) = &subject {
+        referrers::refresh_fallback_tag(&state, &repo.id, &name, subject).await?;
+    }
+
     let mut headers = HeaderMap::new();
+    if let Some(subject) = &subject {
+        headers.insert(registry_headers::OCI_SUBJECT, subject.parse().unwrap());
+    }
     headers.insert(
-        "Docker-Content-Digest",
+        registry_headers::CONTENT_DIGEST,
         calculated_digest.parse().unwrap()
     );
     headers.insert(
@@ -193,39 +397,188 @@ pub async fn put_manifest(
         format!("/v2/{}/manifests/{}", name, calculated_digest).parse().unwrap()
     );
 
+    if let Err(e) = record_usage(&state, &repo.id, UsageEvent::Push { bytes: manifest_content.len() as i64 }).await {
+        tracing::warn!("Failed to record push for {}: {}", name, e);
+    }
+
+    let event = RepositoryEvent {
+        event: "push",
+        repository: name.clone(),
+        tag: (!reference.starts_with("sha256:")).then(|| reference.clone()),
+        digest: calculated_digest.clone(),
+        media_type: Some(media_type.to_string()),
+        actor: user.as_ref().map(|u| u.name.clone()),
+        timestamp: chrono::Utc::now(),
+    };
+    if let Err(e) = webhooks::dispatch(&state, &repo.id, event).await {
+        tracing::warn!("Failed to dispatch push webhooks for {}: {}", name, e);
+    }
+
     Ok((StatusCode::CREATED, headers))
 }
 
+/// Fail with MANIFEST_BLOB_UNKNOWN unless the repository holds `digest` or is still verifying
+/// an upload of it under deferred verification
+async fn ensure_blob_pushed(state: &AppState, repository_id: &Uuid, digest: &str) -> Result<()> {
+    match get_blob_by_digest(state, repository_id, digest).await {
+        Ok(_) => Ok(()),
+        Err(Error::NotFound { .. }) if state.pending_blobs.is_pending(*repository_id, digest) => Ok(()),
+        Err(Error::NotFound { .. }) => Err(Error::manifest_blob_unknown(digest)),
+        Err(e) => Err(e),
+    }
+}
+
 /// Delete manifest
 pub async fn delete_manifest(
     State(state): State<AppState>,
     Path((name, reference)): Path<(String, String)>,
+    user: Option<AuthenticatedUser>,
 ) -> Result<impl IntoResponse> {
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "delete").await?;
     
     let repo = get_repository_by_name(&state, &name).await?;
+    ensure_not_deletion_protected(&state, &repo.id, &name).await?;
     
+    let mut tag_digest = None;
     if reference.starts_with("sha256:") {
         // Delete by digest
         validate_digest(&reference)?;
+        let subject = get_manifest_subject(&state.database.pool, &repo.id, &reference).await?;
         delete_manifest_by_digest(&state, &repo.id, &reference).await?;
+        state.performance.manifest_cache.invalidate(&ManifestCache::key(&name, &reference));
+        // A deleted referrer drops out of its subject's fallback index
+        if let Some(subject) = subject {
+            referrers::refresh_fallback_tag(&state, &repo.id, &name, &subject).await?;
+        }
     } else {
-        // Delete by tag
+        // Delete by tag, remembering what it pointed at for webhooks
         validate_tag_name(&reference)?;
+        tag_digest = get_manifest_by_tag(&state, &repo.id, &reference).await.ok().map(|m| m.digest);
         delete_tag(&state, &repo.id, &reference).await?;
     }
 
+    state.performance.existence_cache.invalidate_manifests(&name);
+    state.performance.catalog_cache.invalidate();
+
+    let event = RepositoryEvent {
+        event: "delete",
+        repository: name.clone(),
+        tag: tag_digest.as_ref().map(|_| reference.clone()),
+        digest: tag_digest.unwrap_or_else(|| reference.clone()),
+        media_type: None,
+        actor: user.as_ref().map(|u| u.name.clone()),
+        timestamp: chrono::Utc::now(),
+    };
+    if let Err(e) = webhooks::dispatch(&state, &repo.id, event).await {
+        tracing::warn!("Failed to dispatch delete webhooks for {}: {}", name, e);
+    }
+
     Ok(StatusCode::ACCEPTED)
 }
 
+/// Most references accepted by one drift check
+const MAX_DRIFT_REFERENCES: usize = 500;
+
+/// Resolve `repository:tag` references and report which moved off their baseline digest.
+/// Each reference is answered on its own, so one unknown or forbidden tag doesn't fail the batch.
+pub async fn check_tag_drift(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<TagDriftRequest>,
+) -> Result<Json<TagDriftReport>> {
+    if request.references.len() > MAX_DRIFT_REFERENCES {
+        return Err(Error::validation(format!(
+            "At most {} references can be checked at once",
+            MAX_DRIFT_REFERENCES
+        )));
+    }
+
+    let user = Some(user);
+    let mut results = Vec::with_capacity(request.references.len());
+    for reference in request.references {
+        let mut result = TagDriftResult {
+            reference: reference.reference.clone(),
+            baseline_digest: reference.baseline_digest.clone(),
+            current_digest: None,
+            drifted: false,
+            tag_updated_at: None,
+            error: None,
+        };
+
+        match resolve_drift_reference(&state, &user, &reference.reference).await {
+            Ok((digest, updated_at)) => {
+                result.drifted = reference.baseline_digest.as_deref().is_some_and(|baseline| baseline != digest);
+                result.current_digest = Some(digest);
+                result.tag_updated_at = Some(updated_at);
+            }
+            // Storage trouble is the registry's problem, not the reference's
+            Err(e @ Error::Database(_)) => return Err(e),
+            Err(e) => result.error = Some(e.to_string()),
+        }
+        results.push(result);
+    }
+
+    Ok(Json(TagDriftReport {
+        drifted: results.iter().filter(|r| r.drifted).count(),
+        results,
+    }))
+}
+
+/// Current digest of a tag reference and when the tag last moved
+async fn resolve_drift_reference(
+    state: &AppState,
+    user: &Option<AuthenticatedUser>,
+    reference: &str,
+) -> Result<(String, chrono::DateTime<chrono::Utc>)> {
+    let (name, tag) = split_tag_reference(reference);
+    validate_repository_name(name)?;
+    validate_tag_name(tag)?;
+    authorize_repository(state, user, name, "pull").await?;
+
+    let repo = get_repository_by_name(state, name).await?;
+    let row = sqlx::query(
+        r#"
+        SELECT m.digest, t.updated_at
+        FROM tags t
+        JOIN manifests m ON m.id = t.manifest_id
+        WHERE t.repository_id = $1 AND t.name = $2
+        "#
+    )
+    .bind(&repo.id)
+    .bind(tag)
+    .fetch_optional(&state.database.pool)
+    .await?
+    .ok_or_else(|| Error::not_found(format!("Tag '{}' not found in '{}'", tag, name)))?;
+
+    Ok((row.get("digest"), row.get("updated_at")))
+}
+
+/// Split `repository:tag`, defaulting to `latest`; a `:` before the last `/` is not a tag
+fn split_tag_reference(reference: &str) -> (&str, &str) {
+    let name_end = reference.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match reference[name_end..].rfind(':') {
+        Some(i) => (&reference[..name_end + i], &reference[name_end + i + 1..]),
+        None => (reference, "latest"),
+    }
+}
+
 /// Get repository tags
 pub async fn get_tags(
     State(state): State<AppState>,
     Path(name): Path<String>,
+    user: Option<AuthenticatedUser>,
 ) -> Result<impl IntoResponse> {
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
     
-    let repo = get_repository_by_name(&state, &name).await?;
+    // An existing repository without tags is still a 200; only unknown names are errors
+    let repo = get_repository_by_name(&state, &name)
+        .await
+        .map_err(|e| match e {
+            Error::NotFound { .. } => Error::name_unknown(name.clone()),
+            other => other,
+        })?;
     
     let tags: Vec<String> = sqlx::query_scalar(
         "SELECT name FROM tags WHERE repository_id = $1 ORDER BY created_at DESC"
@@ -234,72 +587,793 @@ pub async fn get_tags(
     .fetch_all(&state.database.pool)
     .await?;
 
-    Ok(Json(json!({
+    Ok(Json(tag_list(&name, tags, state.config.registry.empty_tag_list)))
+}
+
+/// Body of a `tags/list` response
+fn tag_list(name: &str, tags: Vec<String>, empty: EmptyTagList) -> Value {
+    let tags = if tags.is_empty() && empty == EmptyTagList::Null {
+        Value::Null
+    } else {
+        json!(tags)
+    };
+
+    json!({
         "name": name,
         "tags": tags
-    })))
+    })
 }
 
-/// Validate manifest structure
-fn validate_manifest_structure(manifest: &Value) -> Result<()> {
-    // Check for required fields based on manifest type
-    let media_type = manifest.get("mediaType")
-        .and_then(|v| v.as_str())
-        .unwrap_or("application/vnd.docker.distribution.manifest.v2+json");
-    
-    match media_type {
-        "application/vnd.docker.distribution.manifest.v2+json" => {
+#[derive(Debug, Deserialize)]
+pub struct ReferrersQuery {
+    #[serde(rename = "artifactType")]
+    pub artifact_type: Option<String>,
+}
+
+/// List manifests attached to `digest` through their `subject`, as an OCI image index.
+/// A subject with no referrers, or one that was never pushed, answers an empty index.
+pub async fn get_referrers(
+    State(state): State<AppState>,
+    Path((name, digest)): Path<(String, String)>,
+    Query(query): Query<ReferrersQuery>,
+    user: Option<AuthenticatedUser>,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    validate_digest(&digest)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
+
+    let repo = get_repository_by_name(&state, &name)
+        .await
+        .map_err(|e| match e {
+            Error::NotFound { .. } => Error::name_unknown(name.clone()),
+            other => other,
+        })?;
+
+    let filter = query.artifact_type.as_deref().filter(|t| !t.is_empty());
+    let descriptors = list_referrers(&state.database.pool, &repo.id, &digest)
+        .await?
+        .iter()
+        .map(referrers::referrer_descriptor)
+        .filter(|descriptor| referrers::matches_artifact_type(descriptor, filter))
+        .collect();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(header::CONTENT_TYPE, media_types::OCI_INDEX.parse().unwrap());
+    if filter.is_some() {
+        headers.insert(registry_headers::OCI_FILTERS_APPLIED, "artifactType".parse().unwrap());
+    }
+
+    Ok((StatusCode::OK, headers, Json(referrers::referrers_index(descriptors))))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TagDetailsQuery {
+    pub artifact_type: Option<String>,
+}
+
+/// List repository tags with digest, media type, artifact type, and total image size
+pub async fn get_tag_details(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<TagDetailsQuery>,
+    Query(page): Query<PageQuery>,
+    user: Option<AuthenticatedUser>,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
+    let (limit, offset) = page.bounds()?;
+
+    let artifact_filter = query.artifact_type
+        .as_deref()
+        .map(|t| ArtifactType::parse(t).ok_or_else(|| Error::bad_request(format!("Unknown artifact type '{}'", t))))
+        .transpose()?;
+
+    let repo = get_repository_by_name(&state, &name).await?;
+
+    let total: i64 = sqlx::query_scalar(
+        r#"
+        SELECT COUNT(*)
+        FROM tags t
+        JOIN manifests m ON m.id = t.manifest_id
+        WHERE t.repository_id = $1 AND ($2 IS NULL OR m.artifact_type = $2)
+        "#
+    )
+    .bind(&repo.id)
+    .bind(artifact_filter.map(|t| t.as_str()))
+    .fetch_one(&state.database.pool)
+    .await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT t.name, t.updated_at, m.digest, m.media_type, m.artifact_type
+        FROM tags t
+        JOIN manifests m ON m.id = t.manifest_id
+        WHERE t.repository_id = $1 AND ($2 IS NULL OR m.artifact_type = $2)
+        ORDER BY t.updated_at DESC, t.name ASC
+        LIMIT $3 OFFSET $4
+        "#
+    )
+    .bind(&repo.id)
+    .bind(artifact_filter.map(|t| t.as_str()))
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(&state.database.pool)
+    .await?;
+
+    let mut tags = Vec::with_capacity(rows.len());
+    for row in rows {
+        let digest: String = row.get("digest");
+        tags.push(TagDetail {
+            image_size: get_manifest_image_size(&state, &repo.id, &digest).await?,
+            name: row.get("name"),
+            digest,
+            media_type: row.get("media_type"),
+            artifact_type: ArtifactType::parse(row.get::<String, _>("artifact_type").as_str())
+                .unwrap_or(ArtifactType::Image),
+            updated_at: row.get("updated_at"),
+        });
+    }
+
+    Ok(Json(Page::new(tags, total as u64, limit, offset)))
+}
+
+/// Fallback when the configured default platform isn't in a manifest list
+const DEFAULT_PLATFORM: &str = "linux/amd64";
+
+#[derive(Debug, Deserialize)]
+pub struct ImageConfigQuery {
+    /// `os/architecture[/variant]` to resolve manifest lists to
+    pub platform: Option<String>,
+}
+
+/// The parts of a Docker / OCI image config blob surfaced by `get_image_config`
+#[derive(Debug, Default, Deserialize)]
+struct ImageConfigBlob {
+    architecture: Option<String>,
+    os: Option<String>,
+    variant: Option<String>,
+    created: Option<String>,
+    author: Option<String>,
+    config: Option<ImageRuntimeConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ImageRuntimeConfig {
+    user: Option<String>,
+    env: Option<Vec<String>>,
+    entrypoint: Option<Vec<String>>,
+    cmd: Option<Vec<String>>,
+    working_dir: Option<String>,
+    exposed_ports: Option<HashMap<String, Value>>,
+    volumes: Option<HashMap<String, Value>>,
+    labels: Option<HashMap<String, String>>,
+    stop_signal: Option<String>,
+}
+
+/// Entrypoint, env, ports, and labels of a tagged image, read from its config blob.
+/// Manifest lists resolve to the requested platform, or `linux/amd64` by default.
+pub async fn get_image_config(
+    State(state): State<AppState>,
+    Path((name, tag)): Path<(String, String)>,
+    Query(query): Query<ImageConfigQuery>,
+    user: Option<AuthenticatedUser>,
+) -> Result<Json<ImageConfigDetails>> {
+    validate_repository_name(&name)?;
+    validate_tag_name(&tag)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
+
+    let repo = get_repository_by_name(&state, &name).await?;
+    if let Some(platform) = query.platform.as_deref() {
+        validate_platform(platform)?;
+    }
+    let mut manifest = get_manifest_by_tag(&state, &repo.id, &tag).await?;
+    let preferred = preferred_platform(&state, &repo.id).await?;
+    let mut platform = None;
+    let mut depth = 0;
+
+    while manifest.media_type == media_types::DOCKER_MANIFEST_LIST || manifest.media_type == media_types::OCI_INDEX {
+        depth += 1;
+        if depth > state.config.registry.max_manifest_list_depth {
+            return Err(Error::manifest_invalid(format!("Manifest list '{}' is nested too deeply", tag)));
+        }
+
+        let index: Value = serde_json::from_slice(&manifest.content)
+            .map_err(|e| Error::manifest_invalid(format!("Stored manifest list is not valid JSON: {}", e)))?;
+        let (digest, selected) = select_platform(&index, query.platform.as_deref(), &preferred)?;
+        manifest = get_manifest_by_digest(&state, &repo.id, &digest).await?;
+        platform = Some(selected);
+    }
+
+    let image = ManifestType::parse(&manifest.content, Some(&manifest.media_type))?;
+    let config_digest = match image.config() {
+        Some(config) if config.media_type == media_types::DOCKER_CONFIG || config.media_type == media_types::OCI_CONFIG => {
+            config.digest.clone()
+        }
+        other => {
+            return Err(Error::bad_request(format!(
+                "'{}:{}' is not a container image (config type {})",
+                name,
+                tag,
+                other.map(|c| c.media_type.as_str()).unwrap_or("missing")
+            )));
+        }
+    };
+
+    let data = storage_fallback::get_blob(&state.storage, state.storage_fallback.as_deref(), &config_digest)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Config blob '{}' not found", config_digest)))?;
+    let blob: ImageConfigBlob = serde_json::from_slice(&data)
+        .map_err(|e| Error::bad_request(format!("Config blob '{}' is not a valid image config: {}", config_digest, e)))?;
+
+    Ok(Json(image_config_details(manifest.digest, config_digest, platform, blob)))
+}
+
+/// Pick the child manifest for a platform out of a manifest list or OCI index.
+/// A wanted platform without a variant matches any variant. Without a wanted platform,
+/// `preferred` is tried, then `DEFAULT_PLATFORM`, then the first real platform
+/// (attestations are `unknown/unknown`).
+fn select_platform(index: &Value, wanted: Option<&str>, preferred: &str) -> Result<(String, String)> {
+    let entries: Vec<(String, String)> = index
+        .get("manifests")
+        .and_then(|m| m.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let digest = entry.get("digest")?.as_str()?;
+            let platform = entry.get("platform")?;
+            let label = platform_label(
+                platform.get("os")?.as_str()?,
+                platform.get("architecture")?.as_str()?,
+                platform.get("variant").and_then(|v| v.as_str()),
+            );
+            Some((digest.to_string(), label))
+        })
+        .collect();
+
+    let matches = |wanted: &str, label: &str| {
+        label == wanted || label.strip_prefix(wanted).is_some_and(|rest| rest.starts_with('/'))
+    };
+
+    if let Some(wanted) = wanted {
+        return entries
+            .into_iter()
+            .find(|(_, label)| matches(wanted, label))
+            .ok_or_else(|| Error::not_found(format!("No manifest for platform '{}'", wanted)));
+    }
+
+    entries.iter()
+        .find(|(_, label)| matches(preferred, label))
+        .or_else(|| entries.iter().find(|(_, label)| matches(DEFAULT_PLATFORM, label)))
+        .or_else(|| entries.iter().find(|(_, label)| !label.starts_with("unknown/")))
+        .cloned()
+        .ok_or_else(|| Error::not_found("Manifest list has no platform-specific images"))
+}
+
+fn platform_label(os: &str, architecture: &str, variant: Option<&str>) -> String {
+    match variant {
+        Some(variant) => format!("{}/{}/{}", os, architecture, variant),
+        None => format!("{}/{}", os, architecture),
+    }
+}
+
+fn image_config_details(
+    manifest_digest: String,
+    config_digest: String,
+    platform: Option<String>,
+    blob: ImageConfigBlob,
+) -> ImageConfigDetails {
+    let platform = platform.or_else(|| {
+        Some(platform_label(blob.os.as_deref()?, blob.architecture.as_deref()?, blob.variant.as_deref()))
+    });
+    let config = blob.config.unwrap_or_default();
+    let sorted_keys = |map: Option<HashMap<String, Value>>| {
+        let mut keys: Vec<String> = map.unwrap_or_default().into_keys().collect();
+        keys.sort();
+        keys
+    };
+
+    ImageConfigDetails {
+        manifest_digest,
+        config_digest,
+        platform,
+        created: blob.created,
+        author: blob.author,
+        user: config.user.filter(|u| !u.is_empty()),
+        entrypoint: config.entrypoint.unwrap_or_default(),
+        cmd: config.cmd.unwrap_or_default(),
+        working_dir: config.working_dir.filter(|d| !d.is_empty()),
+        env: config.env.unwrap_or_default(),
+        exposed_ports: sorted_keys(config.exposed_ports),
+        volumes: sorted_keys(config.volumes),
+        labels: config.labels.unwrap_or_default().into_iter().collect::<BTreeMap<_, _>>(),
+        stop_signal: config.stop_signal,
+    }
+}
+
+/// Digest of a pushed manifest; a push to a digest reference must match the bytes it carries
+fn pushed_manifest_digest(reference: &str, content: &[u8]) -> Result<String> {
+    let digest = sha256_digest(content);
+    if reference.starts_with("sha256:") {
+        validate_digest(reference)?;
+        if reference != digest {
+            return Err(Error::manifest_invalid(format!(
+                "Manifest digest {} does not match reference {}",
+                digest, reference
+            )));
+        }
+    }
+    Ok(digest)
+}
+
+/// Fail rather than serve a manifest whose bytes no longer hash to its recorded digest
+fn verify_stored_digest(digest: &str, content: &[u8]) -> Result<()> {
+    let actual = sha256_digest(content);
+    if actual != digest {
+        tracing::error!("Stored manifest {} hashes to {}; refusing to serve it", digest, actual);
+        return Err(Error::internal(format!("Stored manifest {} failed digest verification", digest)));
+    }
+    Ok(())
+}
+
+/// Validate manifest structure beyond what parsing into `ManifestType` already guarantees
+fn validate_manifest_structure(manifest: &ManifestType) -> Result<()> {
+    if let Some(subject) = manifest.subject() {
+        validate_digest(&subject.digest)
+            .map_err(|e| Error::manifest_invalid(format!("Invalid subject digest '{}': {}", subject.digest, e)))?;
+    }
+
+    match manifest {
+        ManifestType::V2(image) => {
             // Docker Image Manifest v2
-            if !manifest.get("config").is_some() {
-                return Err(Error::bad_request("Missing config in image manifest"));
-            }
-            if !manifest.get("layers").and_then(|l| l.as_array()).is_some() {
-                return Err(Error::bad_request("Missing or invalid layers in image manifest"));
-            }
+            validate_image_manifest(
+                manifest.media_type(),
+                image.schema_version,
+                &image.config,
+                &image.layers,
+                &[media_types::DOCKER_CONFIG],
+                &[media_types::DOCKER_LAYERS, media_types::OCI_LAYERS],
+            )?;
         }
-        "application/vnd.docker.distribution.manifest.list.v2+json" => {
-            // Manifest List (multi-arch)
-            if !manifest.get("manifests").and_then(|m| m.as_array()).is_some() {
-                return Err(Error::bad_request("Missing or invalid manifests in manifest list"));
-            }
+        ManifestType::Oci(image) if image.config.media_type == media_types::HELM_CONFIG => {
+            // Helm chart pushed as an OCI artifact
+            validate_image_manifest(
+                manifest.media_type(),
+                image.schema_version,
+                &image.config,
+                &image.layers,
+                &[media_types::HELM_CONFIG],
+                &[media_types::HELM_LAYERS],
+            )?;
+        }
+        ManifestType::Oci(image) if image.artifact_type.is_some() => {
+            // OCI 1.1 artifact (signature, SBOM, ...): config and layers carry arbitrary media types
+            check_schema_version(manifest.media_type(), image.schema_version, 2)?;
+        }
+        ManifestType::Oci(image) => {
+            // OCI Image Manifest
+            validate_image_manifest(
+                manifest.media_type(),
+                image.schema_version,
+                &image.config,
+                &image.layers,
+                &[media_types::OCI_CONFIG, media_types::OCI_EMPTY],
+                &[media_types::DOCKER_LAYERS, media_types::OCI_LAYERS],
+            )?;
         }
-        _ => {
-            // Allow other types but log a warning
-            tracing::warn!("Unknown manifest media type: {}", media_type);
+        // Manifest List / OCI Index (multi-arch): entries are checked by the list graph walk
+        ManifestType::List(ManifestList { schema_version, .. })
+        | ManifestType::OciIndex(OciIndex { schema_version, .. }) => {
+            check_schema_version(manifest.media_type(), *schema_version, 2)?;
+        }
+        ManifestType::V1(legacy) => {
+            check_schema_version(manifest.media_type(), legacy.schema_version, 1)?;
         }
     }
     
     Ok(())
 }
 
-/// Link manifest to blob
-async fn link_manifest_to_blob(
-    state: &AppState,
-    manifest_id: Uuid,
-    blob_digest: &str,
+fn check_schema_version(media_type: &str, actual: u32, expected: u32) -> Result<()> {
+    if actual != expected {
+        return Err(Error::manifest_invalid(format!(
+            "{} manifests have schemaVersion {}, not {}",
+            media_type, expected, actual
+        )));
+    }
+    Ok(())
+}
+
+/// Reject manifests whose artifact type the repository doesn't accept
+///
+/// An index is accepted wherever images are, since it only groups platform images.
+pub(crate) fn check_artifact_type_allowed(name: &str, allowed: Option<&[ArtifactType]>, detected: ArtifactType) -> Result<()> {
+    let Some(allowed) = allowed else {
+        return Ok(());
+    };
+
+    let accepted = allowed.contains(&detected)
+        || (detected == ArtifactType::Index && allowed.contains(&ArtifactType::Image));
+    if accepted {
+        return Ok(());
+    }
+
+    Err(Error::manifest_invalid(format!(
+        "Repository '{}' does not accept {} artifacts (allowed: {})",
+        name,
+        detected.as_str(),
+        allowed.iter().map(|t| t.as_str()).collect::<Vec<_>>().join(", ")
+    )))
+}
+
+/// Validate an image manifest's config and layer descriptors
+fn validate_image_manifest(
+    media_type: &str,
+    schema_version: u32,
+    config: &Descriptor,
+    layers: &[Descriptor],
+    allowed_config_types: &[&str],
+    allowed_layer_types: &[&[&str]],
 ) -> Result<()> {
-    // Find the blob by digest
-    let blob_result: std::result::Result<Uuid, sqlx::Error> = sqlx::query_scalar(
-        "SELECT id FROM blobs WHERE digest = $1"
-    )
-    .bind(blob_digest)
-    .fetch_one(&state.database.pool)
-    .await;
-    
-    if let Ok(blob_id) = blob_result {
-        // Create the link
-        sqlx::query(
-            "INSERT INTO manifest_blobs (id, manifest_id, blob_id, created_at) VALUES ($1, $2, $3, $4)"
-        )
-        .bind(Uuid::new_v4())
-        .bind(manifest_id)
-        .bind(blob_id)
-        .bind(chrono::Utc::now())
-        .execute(&state.database.pool)
-        .await?;
-    } else {
-        tracing::warn!("Referenced blob {} not found when linking to manifest", blob_digest);
+    check_schema_version(media_type, schema_version, 2)?;
+
+    if !allowed_config_types.contains(&config.media_type.as_str()) {
+        return Err(Error::manifest_invalid(format!(
+            "Config media type '{}' does not match manifest media type '{}'",
+            config.media_type, media_type
+        )));
     }
-    
+
+    for layer in layers {
+        if !allowed_layer_types.iter().any(|types| types.contains(&layer.media_type.as_str())) {
+            return Err(Error::manifest_invalid(format!(
+                "Unrecognized layer media type '{}'",
+                layer.media_type
+            )));
+        }
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(manifest: &Value) -> Result<ManifestType> {
+        ManifestType::parse(&serde_json::to_vec(manifest).unwrap(), None)
+    }
+
+    fn validate(manifest: &Value) -> Result<()> {
+        validate_manifest_structure(&parse(manifest)?)
+    }
+
+    fn docker_manifest() -> Value {
+        json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.docker.distribution.manifest.v2+json",
+            "config": {
+                "mediaType": "application/vnd.docker.container.image.v1+json",
+                "size": 1469,
+                "digest": "sha256:feb5d9fea6a5e9606aa995e879d862b825965ba48de054caab5ef356dc6b3412"
+            },
+            "layers": [{
+                "mediaType": "application/vnd.docker.image.rootfs.diff.tar.gzip",
+                "size": 2479,
+                "digest": "sha256:2db29710123e3e53a794f2694094b9b4338aa9ee5c40b930cb8063a1be392c54"
+            }]
+        })
+    }
+
+    fn oci_manifest() -> Value {
+        json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "config": {
+                "mediaType": "application/vnd.oci.image.config.v1+json",
+                "size": 1469,
+                "digest": "sha256:feb5d9fea6a5e9606aa995e879d862b825965ba48de054caab5ef356dc6b3412"
+            },
+            "layers": [{
+                "mediaType": "application/vnd.oci.image.layer.v1.tar+gzip",
+                "size": 2479,
+                "digest": "sha256:2db29710123e3e53a794f2694094b9b4338aa9ee5c40b930cb8063a1be392c54"
+            }]
+        })
+    }
+
+    #[test]
+    fn test_valid_docker_manifest() {
+        assert!(validate(&docker_manifest()).is_ok());
+    }
+
+    #[test]
+    fn test_valid_oci_manifest() {
+        assert!(validate(&oci_manifest()).is_ok());
+    }
+
+    #[test]
+    fn test_mismatched_config_media_type() {
+        let mut manifest = docker_manifest();
+        manifest["config"]["mediaType"] = json!("application/vnd.oci.image.config.v1+json");
+
+        let err = validate(&manifest).unwrap_err();
+        assert_eq!(err.error_code(), "MANIFEST_INVALID");
+    }
+
+    #[test]
+    fn test_unrecognized_layer_media_type() {
+        let mut manifest = oci_manifest();
+        manifest["layers"][0]["mediaType"] = json!("application/x-unknown");
+
+        let err = validate(&manifest).unwrap_err();
+        assert_eq!(err.error_code(), "MANIFEST_INVALID");
+    }
+
+    #[test]
+    fn test_typed_manifest_parsing() {
+        let manifest = parse(&docker_manifest()).unwrap();
+        assert_eq!(manifest.media_type(), media_types::DOCKER_MANIFEST_V2);
+        assert_eq!(
+            manifest.blob_digests(),
+            vec![
+                "sha256:feb5d9fea6a5e9606aa995e879d862b825965ba48de054caab5ef356dc6b3412",
+                "sha256:2db29710123e3e53a794f2694094b9b4338aa9ee5c40b930cb8063a1be392c54",
+            ]
+        );
+
+        // Without mediaType a manifest is read as Docker v2, as before
+        let mut untyped = docker_manifest();
+        untyped.as_object_mut().unwrap().remove("mediaType");
+        assert_eq!(parse(&untyped).unwrap().media_type(), media_types::DOCKER_MANIFEST_V2);
+
+        let mut missing_config = oci_manifest();
+        missing_config.as_object_mut().unwrap().remove("config");
+        assert_eq!(parse(&missing_config).unwrap_err().error_code(), "MANIFEST_INVALID");
+
+        let mut missing_digest = oci_manifest();
+        missing_digest["layers"][0].as_object_mut().unwrap().remove("digest");
+        assert_eq!(parse(&missing_digest).unwrap_err().error_code(), "MANIFEST_INVALID");
+
+        let unknown = json!({ "schemaVersion": 2, "mediaType": "application/x-unknown" });
+        assert_eq!(parse(&unknown).unwrap_err().error_code(), "MANIFEST_INVALID");
+
+        let mut wrong_version = oci_manifest();
+        wrong_version["schemaVersion"] = json!(1);
+        assert_eq!(validate(&wrong_version).unwrap_err().error_code(), "MANIFEST_INVALID");
+
+        let index = parse(&json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.index.v1+json",
+            "manifests": []
+        }))
+        .unwrap();
+        assert!(index.blob_digests().is_empty());
+        assert_eq!(index.artifact_type(), ArtifactType::Index);
+    }
+
+    #[test]
+    fn test_manifest_type_from_content_type() {
+        let bytes = |manifest: &Value| serde_json::to_vec(manifest).unwrap();
+
+        // An untyped body takes the pushed Content-Type
+        let mut untyped = oci_manifest();
+        untyped.as_object_mut().unwrap().remove("mediaType");
+        let parsed = ManifestType::parse(&bytes(&untyped), Some("application/vnd.oci.image.manifest.v1+json; charset=utf-8")).unwrap();
+        assert_eq!(parsed.media_type(), media_types::OCI_MANIFEST);
+
+        // A body contradicting the Content-Type is refused
+        let err = ManifestType::parse(&bytes(&docker_manifest()), Some(media_types::OCI_MANIFEST)).unwrap_err();
+        assert_eq!(err.error_code(), "MANIFEST_INVALID");
+
+        // Generic content types carry no manifest type
+        let parsed = ManifestType::parse(&bytes(&oci_manifest()), Some("application/json")).unwrap();
+        assert_eq!(parsed.media_type(), media_types::OCI_MANIFEST);
+    }
+
+    fn helm_manifest() -> Value {
+        json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "config": {
+                "mediaType": "application/vnd.cncf.helm.config.v1+json",
+                "size": 117,
+                "digest": "sha256:8ec7c0f2f6860037c19b54c3cfbab48d9b4b21b485a93d87b64690fdb68c2111"
+            },
+            "layers": [{
+                "mediaType": "application/vnd.cncf.helm.chart.content.v1.tar+gzip",
+                "size": 3584,
+                "digest": "sha256:4c8a5ff3d5ffd0e1c8d4e8a2c9c5b1d1e3f6a7b8c9d0e1f2a3b4c5d6e7f8a9b0"
+            }]
+        })
+    }
+
+    #[test]
+    fn test_helm_chart_manifest() {
+        let manifest = helm_manifest();
+        assert!(validate(&manifest).is_ok());
+        assert_eq!(parse(&manifest).unwrap().artifact_type(), ArtifactType::HelmChart);
+        assert_eq!(parse(&oci_manifest()).unwrap().artifact_type(), ArtifactType::Image);
+    }
+
+    #[test]
+    fn test_chart_rejected_by_image_only_repository() {
+        let manifest = helm_manifest();
+        let detected = parse(&manifest).unwrap().artifact_type();
+
+        let err = check_artifact_type_allowed("team/app", Some(&[ArtifactType::Image]), detected).unwrap_err();
+        assert_eq!(err.error_code(), "MANIFEST_INVALID");
+
+        assert!(check_artifact_type_allowed("team/app", None, detected).is_ok());
+        assert!(check_artifact_type_allowed("team/charts", Some(&[ArtifactType::HelmChart]), detected).is_ok());
+        assert!(check_artifact_type_allowed("team/app", Some(&[ArtifactType::Image]), ArtifactType::Index).is_ok());
+    }
+
+    #[test]
+    fn test_helm_config_requires_chart_layers() {
+        let mut manifest = helm_manifest();
+        manifest["layers"][0]["mediaType"] = json!("application/vnd.oci.image.layer.v1.tar+gzip");
+
+        let err = validate(&manifest).unwrap_err();
+        assert_eq!(err.error_code(), "MANIFEST_INVALID");
+    }
+
+    #[test]
+    fn test_artifact_manifest_with_subject() {
+        let subject = "sha256:feb5d9fea6a5e9606aa995e879d862b825965ba48de054caab5ef356dc6b3412";
+        let mut manifest = json!({
+            "schemaVersion": 2,
+            "mediaType": media_types::OCI_MANIFEST,
+            "artifactType": "application/vnd.example.sbom+json",
+            "config": { "mediaType": media_types::OCI_EMPTY, "size": 2, "digest": subject },
+            "layers": [{ "mediaType": "application/spdx+json", "size": 10, "digest": subject }],
+            "subject": { "mediaType": media_types::OCI_MANIFEST, "size": 100, "digest": subject },
+        });
+
+        // Artifact layers aren't image layers, so their media types aren't checked
+        assert!(validate(&manifest).is_ok());
+        assert_eq!(parse(&manifest).unwrap().subject().unwrap().digest, subject);
+
+        manifest["subject"]["digest"] = json!("sha256:nothex");
+        assert_eq!(validate(&manifest).unwrap_err().error_code(), "MANIFEST_INVALID");
+
+        // Without artifactType the layers must still be image layers
+        manifest.as_object_mut().unwrap().remove("artifactType");
+        manifest["subject"]["digest"] = json!(subject);
+        assert_eq!(validate(&manifest).unwrap_err().error_code(), "MANIFEST_INVALID");
+    }
+
+    #[test]
+    fn test_push_digest_must_match_reference() {
+        // Whitespace is significant: the digest covers the bytes as sent, not re-serialized JSON
+        let pushed = b"{\"schemaVersion\": 2,  \"mediaType\": \"application/vnd.oci.image.index.v1+json\", \"manifests\": []}\n";
+        let digest = sha256_digest(pushed);
+        assert_ne!(digest, sha256_digest(serde_json::from_slice::<Value>(pushed).unwrap().to_string().as_bytes()));
+
+        assert_eq!(pushed_manifest_digest("latest", pushed).unwrap(), digest);
+        assert_eq!(pushed_manifest_digest(&digest, pushed).unwrap(), digest);
+
+        let other = sha256_digest(b"something else");
+        let err = pushed_manifest_digest(&other, pushed).unwrap_err();
+        assert_eq!(err.error_code(), "MANIFEST_INVALID");
+    }
+
+    #[test]
+    fn test_stored_digest_verification() {
+        let content = br#"{"schemaVersion":2}"#;
+        assert!(verify_stored_digest(&sha256_digest(content), content).is_ok());
+        assert!(verify_stored_digest(&sha256_digest(content), br#"{"schemaVersion": 2}"#).is_err());
+    }
+
+    #[test]
+    fn test_empty_tag_list_representation() {
+        assert_eq!(tag_list("app", vec![], EmptyTagList::Empty), json!({ "name": "app", "tags": [] }));
+        assert_eq!(tag_list("app", vec![], EmptyTagList::Null), json!({ "name": "app", "tags": null }));
+        // Repositories with tags list them either way
+        assert_eq!(
+            tag_list("app", vec!["v1".to_string()], EmptyTagList::Null),
+            json!({ "name": "app", "tags": ["v1"] })
+        );
+        assert_eq!(Error::name_unknown("app").error_code(), "NAME_UNKNOWN");
+    }
+
+    #[test]
+    fn test_manifest_list_negotiation() {
+        let accept = |types: &[&str]| {
+            let mut headers = HeaderMap::new();
+            for media_type in types {
+                headers.append(header::ACCEPT, media_type.parse().unwrap());
+            }
+            headers
+        };
+        let modern = accept(&[media_types::DOCKER_MANIFEST_V2, media_types::DOCKER_MANIFEST_LIST, media_types::OCI_INDEX]);
+        let legacy = accept(&[media_types::DOCKER_MANIFEST_V2]);
+
+        assert!(!needs_platform_resolution(media_types::OCI_INDEX, &modern, None));
+        assert!(needs_platform_resolution(media_types::OCI_INDEX, &legacy, None));
+        // An explicit platform resolves even for clients that take lists
+        assert!(needs_platform_resolution(media_types::DOCKER_MANIFEST_LIST, &modern, Some("linux/arm64")));
+        // Single-platform manifests are served as they are
+        assert!(!needs_platform_resolution(media_types::DOCKER_MANIFEST_V2, &legacy, Some("linux/arm64")));
+        // No Accept header, a wildcard, or a comma-separated list with parameters
+        assert!(!needs_platform_resolution(media_types::OCI_INDEX, &HeaderMap::new(), None));
+        assert!(accepts_media_type(&accept(&["*/*"]), media_types::OCI_INDEX));
+        assert!(accepts_media_type(
+            &accept(&[&format!("{}; q=0.9, {}", media_types::DOCKER_MANIFEST_V2, media_types::OCI_INDEX)]),
+            media_types::OCI_INDEX
+        ));
+    }
+
+    #[test]
+    fn test_split_tag_reference() {
+        assert_eq!(split_tag_reference("team/app:prod"), ("team/app", "prod"));
+        assert_eq!(split_tag_reference("team/app"), ("team/app", "latest"));
+        assert_eq!(split_tag_reference("app:1.2.3"), ("app", "1.2.3"));
+        // A colon in an earlier component belongs to the name
+        assert_eq!(split_tag_reference("host:5000/app"), ("host:5000/app", "latest"));
+    }
+
+    #[test]
+    fn test_select_platform_from_manifest_list() {
+        let index = json!({
+            "manifests": [
+                { "digest": "sha256:arm", "platform": { "os": "linux", "architecture": "arm64", "variant": "v8" } },
+                { "digest": "sha256:amd", "platform": { "os": "linux", "architecture": "amd64" } },
+                { "digest": "sha256:att", "platform": { "os": "unknown", "architecture": "unknown" } },
+            ]
+        });
+
+        assert_eq!(
+            select_platform(&index, None, DEFAULT_PLATFORM).unwrap(),
+            ("sha256:amd".to_string(), "linux/amd64".to_string())
+        );
+        // No variant requested matches any variant
+        assert_eq!(select_platform(&index, Some("linux/arm64"), DEFAULT_PLATFORM).unwrap().0, "sha256:arm");
+        assert_eq!(select_platform(&index, Some("linux/arm64/v8"), DEFAULT_PLATFORM).unwrap().0, "sha256:arm");
+        assert!(select_platform(&index, Some("linux/arm"), DEFAULT_PLATFORM).is_err());
+        // A configured default picks the arm64 child for clients that don't ask
+        assert_eq!(select_platform(&index, None, "linux/arm64").unwrap().0, "sha256:arm");
+        // A default the list doesn't carry falls back to amd64
+        assert_eq!(select_platform(&index, None, "linux/riscv64").unwrap().0, "sha256:amd");
+
+        let arm_only = json!({
+            "manifests": [
+                { "digest": "sha256:att", "platform": { "os": "unknown", "architecture": "unknown" } },
+                { "digest": "sha256:arm", "platform": { "os": "linux", "architecture": "arm64" } },
+            ]
+        });
+        assert_eq!(select_platform(&arm_only, None, DEFAULT_PLATFORM).unwrap().0, "sha256:arm");
+    }
+
+    #[test]
+    fn test_image_config_details_from_blob() {
+        let blob: ImageConfigBlob = serde_json::from_value(json!({
+            "architecture": "amd64",
+            "os": "linux",
+            "created": "2024-01-01T00:00:00Z",
+            "config": {
+                "Env": ["PATH=/usr/bin"],
+                "Entrypoint": ["/docker-entrypoint.sh"],
+                "Cmd": ["nginx", "-g", "daemon off;"],
+                "WorkingDir": "",
+                "ExposedPorts": { "443/tcp": {}, "80/tcp": {} },
+                "Labels": { "maintainer": "ops" },
+                "StopSignal": "SIGQUIT"
+            },
+            "rootfs": { "type": "layers", "diff_ids": [] }
+        }))
+        .unwrap();
+
+        let details = image_config_details("sha256:m".into(), "sha256:c".into(), None, blob);
+        assert_eq!(details.platform.as_deref(), Some("linux/amd64"));
+        assert_eq!(details.exposed_ports, vec!["443/tcp", "80/tcp"]);
+        assert_eq!(details.entrypoint, vec!["/docker-entrypoint.sh"]);
+        assert_eq!(details.working_dir, None);
+        assert_eq!(details.labels.get("maintainer").map(String::as_str), Some("ops"));
+
+        // Configs with null sections still parse
+        let sparse: ImageConfigBlob = serde_json::from_value(json!({ "config": { "Labels": null } })).unwrap();
+        let details = image_config_details("sha256:m".into(), "sha256:c".into(), None, sparse);
+        assert!(details.labels.is_empty() && details.platform.is_none());
+    }
+}
@@ -1,5 +1,15 @@
+pub mod audit;
 pub mod auth;
 pub mod health;
+pub mod imports;
+pub mod info;
 pub mod registry;
+pub mod repositories;
+pub mod robots;
+pub mod shares;
 pub mod manifest;
+pub mod openapi;
+pub mod stats;
+pub mod teams;
+pub mod users;
 pub mod web;
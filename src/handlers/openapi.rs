@@ -0,0 +1,1843 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Map, Value};
+
+use crate::server::AppState;
+
+/// Serve the OpenAPI document for the management API
+pub async fn openapi_json() -> impl IntoResponse {
+    Json(openapi_spec())
+}
+
+/// Swagger UI for exploring the management API. Its assets are served from
+/// `web.docs_assets_path` under `/api/docs/assets`, so the page loads nothing from
+/// third-party hosts.
+pub async fn swagger_ui(State(state): State<AppState>) -> Response {
+    let assets = &state.config.web.docs_assets_path;
+    if !SWAGGER_UI_ASSETS.iter().all(|file| assets.join(file).is_file()) {
+        return (
+            StatusCode::NOT_FOUND,
+            format!(
+                "Swagger UI assets are not installed in {}; the OpenAPI document is at /api/openapi.json",
+                assets.display()
+            ),
+        )
+            .into_response();
+    }
+    Html(SWAGGER_UI_HTML).into_response()
+}
+
+/// Files from the `swagger-ui-dist` package that the page loads
+const SWAGGER_UI_ASSETS: [&str; 2] = ["swagger-ui.css", "swagger-ui-bundle.js"];
+
+const SWAGGER_UI_HTML: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <meta charset="utf-8" />
+  <title>GhostDock API</title>
+  <link rel="stylesheet" href="/api/docs/assets/swagger-ui.css" />
+</head>
+<body>
+  <div id="swagger-ui"></div>
+  <script src="/api/docs/assets/swagger-ui-bundle.js"></script>
+  <script>
+    window.onload = () => {
+      window.ui = SwaggerUIBundle({ url: "/api/openapi.json", dom_id: "#swagger-ui" });
+    };
+  </script>
+</body>
+</html>
+"#;
+
+/// Hand-maintained OpenAPI 3.0 document for the `/api/*` management endpoints.
+/// The Docker Registry v2 endpoints follow the distribution spec and are not described here.
+/// Keep it in step with the routes in `server.rs` and the request/response types they use.
+pub fn openapi_spec() -> Value {
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "GhostDock Management API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Repository, user, robot account, stack, import, and statistics management for GhostDock.",
+        },
+        "servers": [{ "url": "/" }],
+        "security": [{ "bearerAuth": [] }],
+        "tags": [
+            { "name": "auth" },
+            { "name": "info" },
+            { "name": "repositories" },
+            { "name": "robots" },
+            { "name": "shares" },
+            { "name": "teams" },
+            { "name": "stats" },
+            { "name": "users" },
+            { "name": "audit" },
+            { "name": "imports" },
+            { "name": "notifications" },
+            { "name": "webhooks" },
+            { "name": "maintenance" },
+            { "name": "proxy" },
+            { "name": "stacks" },
+            { "name": "deployments" },
+            { "name": "promotions" },
+        ],
+        "paths": paths(),
+        "components": {
+            "securitySchemes": {
+                "bearerAuth": { "type": "http", "scheme": "bearer", "bearerFormat": "JWT" },
+            },
+            "schemas": schemas(),
+        },
+    })
+}
+
+fn paths() -> Value {
+    let entries = [
+        ("/auth/login", json!({
+            "post": {
+                "tags": ["auth"],
+                "summary": "Log in with username or email and password",
+                "security": [],
+                "requestBody": json_body("LoginRequest"),
+                "responses": responses(&[
+                    ("200", "Session token", Some("LoginResponse")),
+                    ("401", "Invalid credentials", Some("Error")),
+                    ("429", "Too many failed attempts; see Retry-After", Some("Error")),
+                ]),
+            },
+        })),
+        ("/auth/logout", json!({
+            "post": {
+                "tags": ["auth"],
+                "summary": "End the current session",
+                "responses": responses(&[("200", "Logged out", None)]),
+            },
+        })),
+        ("/auth/oauth/{provider}", json!({
+            "get": {
+                "tags": ["auth"],
+                "summary": "Start an OAuth sign-in; sets a short-lived cookie that ties the callback to this browser",
+                "security": [],
+                "parameters": [oauth_provider_param()],
+                "responses": responses(&[
+                    ("303", "Redirect to the provider's consent page", None),
+                    ("400", "Unknown, unconfigured, or disabled provider", Some("Error")),
+                ]),
+            },
+        })),
+        ("/auth/oauth/{provider}/callback", json!({
+            "get": {
+                "tags": ["auth"],
+                "summary": "Provider redirect target; exchanges the code and signs the user in",
+                "security": [],
+                "parameters": [
+                    oauth_provider_param(),
+                    query_param("code", "Authorization code from the provider", json!({ "type": "string" })),
+                    query_param("state", "State echoed by the provider; must match this browser's cookie", json!({ "type": "string" })),
+                ],
+                "responses": responses(&[
+                    ("303", "Redirect to `/auth/callback?token=...` with a session token", None),
+                    ("400", "Unknown, unconfigured, or disabled provider", Some("Error")),
+                    ("401", "Missing code, or state that doesn't match the cookie", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/info", json!({
+            "get": {
+                "tags": ["info"],
+                "summary": "Registry version, capabilities, and limits; storage details need authentication",
+                "security": [{}, { "bearerAuth": [] }],
+                "responses": responses(&[("200", "Registry information", Some("RegistryInfo"))]),
+            },
+        })),
+        ("/api/repositories", json!({
+            "get": {
+                "tags": ["repositories"],
+                "summary": "List repositories visible to the caller",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": paged(vec![
+                    query_param("artifact_type", "Only repositories holding this artifact type", schema_ref("ArtifactType")),
+                ]),
+                "responses": responses(&[
+                    ("200", "Repositories", Some("RepositorySummaryPage")),
+                    ("400", "Unknown artifact type", Some("Error")),
+                ]),
+            },
+            "post": {
+                "tags": ["repositories"],
+                "summary": "Create a repository",
+                "requestBody": json_body("CreateRepositoryRequest"),
+                "responses": responses(&[
+                    ("201", "Created repository", Some("Repository")),
+                    ("400", "Invalid repository name", Some("Error")),
+                    ("409", "Repository already exists", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/artifact-types", json!({
+            "put": {
+                "tags": ["repositories"],
+                "summary": "Restrict which artifact types may be pushed (owner or admin)",
+                "parameters": [path_param("name", "Repository name")],
+                "requestBody": json_body("ArtifactTypeRestriction"),
+                "responses": responses(&[
+                    ("200", "Restriction updated", Some("ArtifactTypeRestriction")),
+                    ("400", "Empty type list", Some("Error")),
+                    ("403", "Not the repository owner or an admin", Some("Error")),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/default-platform", json!({
+            "put": {
+                "tags": ["repositories"],
+                "summary": "Set the platform served from manifest lists to clients that can't take them (owner or admin)",
+                "parameters": [path_param("name", "Repository name")],
+                "requestBody": json_body("DefaultPlatform"),
+                "responses": responses(&[
+                    ("200", "Default platform updated", Some("DefaultPlatform")),
+                    ("400", "Invalid platform", Some("Error")),
+                    ("403", "Not the repository owner or an admin", Some("Error")),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/deletion-protection", json!({
+            "put": {
+                "tags": ["repositories"],
+                "summary": "Protect a repository's manifests and tags from deletion (owner or admin; only admins can remove it)",
+                "parameters": [path_param("name", "Repository name")],
+                "requestBody": json_body("DeletionProtection"),
+                "responses": responses(&[
+                    ("200", "Protection updated", Some("DeletionProtection")),
+                    ("403", "Not permitted to change protection", Some("Error")),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/favorite", json!({
+            "post": {
+                "tags": ["repositories"],
+                "summary": "Add a repository to the caller's favorites (needs read access)",
+                "parameters": [path_param("name", "Repository name")],
+                "responses": responses(&[
+                    ("204", "Favorited", None),
+                    ("403", "No read access, or not a user account", Some("Error")),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+            "delete": {
+                "tags": ["repositories"],
+                "summary": "Remove a repository from the caller's favorites",
+                "parameters": [path_param("name", "Repository name")],
+                "responses": responses(&[
+                    ("204", "No longer a favorite", None),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/tags", json!({
+            "get": {
+                "tags": ["repositories"],
+                "summary": "List tags with digest, media type, artifact type, and image size",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": paged(vec![
+                    path_param("name", "Repository name"),
+                    query_param("artifact_type", "Only tags of this artifact type", schema_ref("ArtifactType")),
+                ]),
+                "responses": responses(&[
+                    ("200", "Tag details", Some("TagDetailPage")),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/tags/drift", json!({
+            "post": {
+                "tags": ["repositories"],
+                "summary": "Report where tags point now and which moved off a baseline digest",
+                "requestBody": json_body("TagDriftRequest"),
+                "responses": responses(&[
+                    ("200", "Per-reference drift; unresolvable references carry an error", Some("TagDriftReport")),
+                    ("400", "Too many references", Some("Error")),
+                    ("401", "Authentication required", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/tags/{tag}/config", json!({
+            "get": {
+                "tags": ["repositories"],
+                "summary": "Entrypoint, env, exposed ports, and labels from a tagged image's config",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": [
+                    path_param("name", "Repository name"),
+                    path_param("tag", "Tag name"),
+                    query_param(
+                        "platform",
+                        "os/architecture[/variant] to pick from a manifest list (default linux/amd64)",
+                        json!({ "type": "string" }),
+                    ),
+                ],
+                "responses": responses(&[
+                    ("200", "Image config", Some("ImageConfigDetails")),
+                    ("400", "Tag is not a container image", Some("Error")),
+                    ("404", "Repository, tag, platform, or config blob not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/blobs:check", json!({
+            "post": {
+                "tags": ["repositories"],
+                "summary": "Check which of a batch of blobs the repository already has",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": [path_param("name", "Repository name")],
+                "requestBody": json_body("BlobCheckRequest"),
+                "responses": responses(&[
+                    ("200", "Present and missing digests", Some("BlobCheckResponse")),
+                    ("400", "Invalid digest or too many digests", Some("Error")),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/stats", json!({
+            "get": {
+                "tags": ["stats"],
+                "summary": "Daily pull and push statistics for a repository",
+                "parameters": [path_param("name", "Repository name"), range_param()],
+                "responses": responses(&[
+                    ("200", "Usage series", Some("UsageSeries")),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/stats/usage", json!({
+            "get": {
+                "tags": ["stats"],
+                "summary": "Daily pull and push statistics across all repositories",
+                "parameters": [range_param()],
+                "responses": responses(&[("200", "Usage series", Some("UsageSeries"))]),
+            },
+        })),
+        ("/api/repositories/{name}/robots", json!({
+            "get": {
+                "tags": ["robots"],
+                "summary": "List robot accounts for a repository",
+                "parameters": paged(vec![path_param("name", "Repository name")]),
+                "responses": responses(&[
+                    ("200", "Robot accounts", Some("RobotAccountPage")),
+                    ("403", "Not permitted to manage this repository", Some("Error")),
+                ]),
+            },
+            "post": {
+                "tags": ["robots"],
+                "summary": "Create a robot account; the token is returned only once",
+                "parameters": [path_param("name", "Repository name")],
+                "requestBody": json_body("CreateRobotRequest"),
+                "responses": responses(&[
+                    ("201", "Created robot account", Some("CreateRobotResponse")),
+                    ("409", "A robot with this name already exists", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/robots/{robot_id}", json!({
+            "delete": {
+                "tags": ["robots"],
+                "summary": "Revoke a robot account",
+                "parameters": [path_param("name", "Repository name"), path_param("robot_id", "Robot account id")],
+                "responses": responses(&[
+                    ("204", "Revoked", None),
+                    ("404", "Robot account not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/share", json!({
+            "post": {
+                "tags": ["shares"],
+                "summary": "Mint a time-limited pull or push credential for this repository; the token is returned only once",
+                "parameters": [path_param("name", "Repository name")],
+                "requestBody": json_body("CreateShareRequest"),
+                "responses": responses(&[
+                    ("201", "Share credential", Some("CreateShareResponse")),
+                    ("400", "ttl_secs out of range", Some("Error")),
+                    ("403", "Not the repository owner, or share links are disabled", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/shares", json!({
+            "get": {
+                "tags": ["shares"],
+                "summary": "List shares of a repository, including expired and revoked ones",
+                "parameters": paged(vec![path_param("name", "Repository name")]),
+                "responses": responses(&[
+                    ("200", "Shares", Some("SharePage")),
+                    ("403", "Not permitted to manage this repository", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/shares/{share_id}", json!({
+            "delete": {
+                "tags": ["shares"],
+                "summary": "Revoke a share; tokens issued from it stop working immediately",
+                "parameters": [path_param("name", "Repository name"), path_param("share_id", "Share id")],
+                "responses": responses(&[
+                    ("204", "Revoked", None),
+                    ("404", "Share not found or already revoked", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/teams", json!({
+            "get": {
+                "tags": ["teams"],
+                "summary": "List teams (admin)",
+                "parameters": paged(vec![]),
+                "responses": responses(&[
+                    ("200", "Teams", Some("TeamPage")),
+                    ("403", "Admin access required", Some("Error")),
+                ]),
+            },
+            "post": {
+                "tags": ["teams"],
+                "summary": "Create a team (admin)",
+                "requestBody": json_body("CreateTeamRequest"),
+                "responses": responses(&[
+                    ("201", "Created team", Some("Team")),
+                    ("409", "A team with this name already exists", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/teams/{id}", json!({
+            "delete": {
+                "tags": ["teams"],
+                "summary": "Delete a team with its memberships and repository grants (admin)",
+                "parameters": [path_param("id", "Team id")],
+                "responses": responses(&[
+                    ("204", "Deleted", None),
+                    ("404", "Team not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/teams/{id}/members", json!({
+            "get": {
+                "tags": ["teams"],
+                "summary": "List team members (admin)",
+                "parameters": paged(vec![path_param("id", "Team id")]),
+                "responses": responses(&[
+                    ("200", "Team members", Some("TeamMemberPage")),
+                    ("404", "Team not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/teams/{id}/members/{user_id}", json!({
+            "put": {
+                "tags": ["teams"],
+                "summary": "Add a user to a team (admin)",
+                "parameters": [path_param("id", "Team id"), path_param("user_id", "User id")],
+                "responses": responses(&[
+                    ("204", "Added", None),
+                    ("404", "Team or user not found", Some("Error")),
+                ]),
+            },
+            "delete": {
+                "tags": ["teams"],
+                "summary": "Remove a user from a team (admin)",
+                "parameters": [path_param("id", "Team id"), path_param("user_id", "User id")],
+                "responses": responses(&[
+                    ("204", "Removed", None),
+                    ("404", "User is not a member", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/permissions", json!({
+            "get": {
+                "tags": ["teams"],
+                "summary": "List user and team grants on a repository",
+                "parameters": paged(vec![path_param("name", "Repository name")]),
+                "responses": responses(&[
+                    ("200", "Repository grants", Some("RepositoryGrantPage")),
+                    ("403", "Not permitted to manage this repository", Some("Error")),
+                ]),
+            },
+            "post": {
+                "tags": ["teams"],
+                "summary": "Grant a user or team a permission, replacing any existing grant for them",
+                "parameters": [path_param("name", "Repository name")],
+                "requestBody": json_body("GrantPermissionRequest"),
+                "responses": responses(&[
+                    ("201", "Created grant", Some("RepositoryGrant")),
+                    ("400", "Exactly one of user_id or team_id is required", Some("Error")),
+                    ("404", "Repository, user, or team not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/repositories/{name}/permissions/{grant_id}", json!({
+            "delete": {
+                "tags": ["teams"],
+                "summary": "Revoke a repository grant",
+                "parameters": [path_param("name", "Repository name"), path_param("grant_id", "Grant id")],
+                "responses": responses(&[
+                    ("204", "Revoked", None),
+                    ("404", "Grant not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/audit/export", json!({
+            "get": {
+                "tags": ["audit"],
+                "summary": "Stream the audit log, oldest first, for off-box archival (admin)",
+                "parameters": [
+                    query_param("format", "Output format", json!({ "type": "string", "enum": ["ndjson", "csv"], "default": "ndjson" })),
+                    query_param("from", "Only entries at or after this time", json!({ "type": "string", "format": "date-time" })),
+                    query_param("to", "Only entries before this time", json!({ "type": "string", "format": "date-time" })),
+                ],
+                "responses": responses(&[
+                    ("200", "NDJSON lines or CSV rows of id, created_at, actor_id, action, target, details", None),
+                    ("400", "Empty date range", Some("Error")),
+                    ("403", "Admin access required", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/users/{id}", json!({
+            "delete": {
+                "tags": ["users"],
+                "summary": "Delete a user, reassigning or deleting their repositories and stacks (admin)",
+                "parameters": [
+                    path_param("id", "User id"),
+                    query_param("reassign_to", "Username or id of the new owner", json!({ "type": "string" })),
+                    query_param("delete_resources", "Delete owned repositories and stacks instead", json!({ "type": "boolean" })),
+                ],
+                "responses": responses(&[
+                    ("200", "Purge summary", Some("DeleteUserResponse")),
+                    ("403", "Admin access required, or an owned repository is deletion protected", Some("Error")),
+                    ("409", "User still owns repositories or stacks, has a deployed stack, or is the last admin", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/users/{id}/repository-limit", json!({
+            "put": {
+                "tags": ["users"],
+                "summary": "Set or clear a user's repository limit override (admin)",
+                "parameters": [path_param("id", "User id")],
+                "requestBody": json_body("RepositoryLimit"),
+                "responses": responses(&[
+                    ("200", "Limit updated", Some("RepositoryLimit")),
+                    ("403", "Admin access required", Some("Error")),
+                    ("404", "User not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/users/{id}/rate-limit", json!({
+            "put": {
+                "tags": ["users"],
+                "summary": "Set or clear a user's request rate limit override (admin)",
+                "parameters": [path_param("id", "User id")],
+                "requestBody": json_body("RateLimitOverride"),
+                "responses": responses(&[
+                    ("200", "Limit updated", Some("RateLimitOverride")),
+                    ("403", "Admin access required", Some("Error")),
+                    ("404", "User not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/me", json!({
+            "get": {
+                "tags": ["users"],
+                "summary": "Profile of the authenticated user",
+                "responses": responses(&[
+                    ("200", "Profile", Some("UserProfile")),
+                    ("404", "No user account behind the token", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/me/favorites", json!({
+            "get": {
+                "tags": ["users"],
+                "summary": "Repositories the caller favorited and can still read, most recent first",
+                "parameters": paged(vec![]),
+                "responses": responses(&[("200", "Favorite repositories", Some("FavoriteRepositoryPage"))]),
+            },
+        })),
+        ("/api/me/password", json!({
+            "post": {
+                "tags": ["users"],
+                "summary": "Change the caller's password",
+                "requestBody": json_body("ChangePasswordRequest"),
+                "responses": responses(&[
+                    ("204", "Password changed", None),
+                    ("400", "Account has no password or the new one is too weak", Some("Error")),
+                    ("401", "Current password is incorrect", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/users/me/notifications", json!({
+            "get": {
+                "tags": ["notifications"],
+                "summary": "List the caller's notification preferences",
+                "responses": responses(&[("200", "Preferences", Some("NotificationPreferenceList"))]),
+            },
+            "put": {
+                "tags": ["notifications"],
+                "summary": "Replace the caller's notification preferences",
+                "requestBody": json_body("NotificationPreferenceList"),
+                "responses": responses(&[
+                    ("200", "Saved preferences", Some("NotificationPreferenceList")),
+                    ("400", "Invalid destination", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/webhooks", json!({
+            "post": {
+                "tags": ["webhooks"],
+                "summary": "Create a signed webhook; the generated secret is returned only once",
+                "description": "Repository webhooks need the repository owner or an admin; omit `repository` for a \
+                    registry-wide webhook (admins only). With `tag_patterns`, tag events only fire for matching tags.",
+                "requestBody": json_body("CreateWebhookRequest"),
+                "responses": responses(&[
+                    ("201", "Created webhook, including its secret", Some("Webhook")),
+                    ("400", "Invalid URL, event, or tag pattern", Some("Error")),
+                    ("403", "Not the repository owner or an admin", Some("Error")),
+                    ("404", "Repository not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/webhooks/{id}/filter", json!({
+            "put": {
+                "tags": ["webhooks"],
+                "summary": "Replace the events and tag patterns a webhook fires for",
+                "parameters": [path_param("id", "Webhook id")],
+                "requestBody": json_body("WebhookFilter"),
+                "responses": responses(&[
+                    ("200", "Saved filter", Some("WebhookFilter")),
+                    ("400", "Invalid event or tag pattern", Some("Error")),
+                    ("403", "Only the creator or an admin may change the filter", Some("Error")),
+                    ("404", "Webhook not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/webhooks/{id}/rotate-secret", json!({
+            "post": {
+                "tags": ["webhooks"],
+                "summary": "Rotate a webhook's signing secret; the new secret is returned only once",
+                "description": "Deliveries are signed with `X-GhostDock-Signature: sha256=<hex HMAC-SHA256 of the body>`. \
+                    The replaced secret stays valid as the previous secret until the next rotation.",
+                "parameters": [path_param("id", "Webhook id")],
+                "responses": responses(&[
+                    ("200", "New secret", Some("RotateSecretResponse")),
+                    ("403", "Only the creator or an admin may rotate", Some("Error")),
+                    ("404", "Webhook not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/webhooks/{id}/deliveries", json!({
+            "get": {
+                "tags": ["webhooks"],
+                "summary": "Delivery log for a webhook, newest first, with its failure rate over the range",
+                "parameters": paged(vec![
+                    path_param("id", "Webhook id"),
+                    query_param("success", "Only successful (`true`) or failed (`false`) attempts", json!({ "type": "boolean" })),
+                    query_param("since", "Only attempts at or after this time", json!({ "type": "string", "format": "date-time" })),
+                    query_param("until", "Only attempts before this time", json!({ "type": "string", "format": "date-time" })),
+                ]),
+                "responses": responses(&[
+                    ("200", "Delivery log", Some("DeliveryLog")),
+                    ("403", "Only the creator or an admin may view deliveries", Some("Error")),
+                    ("404", "Webhook not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/admin/maintenance", json!({
+            "get": {
+                "tags": ["maintenance"],
+                "summary": "Current read-only maintenance state",
+                "responses": responses(&[("200", "Maintenance state", Some("MaintenanceStatus"))]),
+            },
+            "put": {
+                "tags": ["maintenance"],
+                "summary": "Enter or leave read-only mode (admin); writes then answer 503 with Retry-After",
+                "requestBody": json_body("SetMaintenanceRequest"),
+                "responses": responses(&[
+                    ("200", "New maintenance state", Some("MaintenanceStatus")),
+                    ("403", "Admin access required", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/admin/proxy/rules", json!({
+            "get": {
+                "tags": ["proxy"],
+                "summary": "Pull-through cache rules in match order (admin)",
+                "parameters": paged(vec![]),
+                "responses": responses(&[
+                    ("200", "Rules", Some("ProxyRulePage")),
+                    ("403", "Admin access required", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/admin/proxy/reload", json!({
+            "post": {
+                "tags": ["proxy"],
+                "summary": "Re-read pull-through cache rules from the config file (admin)",
+                "responses": responses(&[
+                    ("200", "Rules now in effect", Some("ProxyRuleList")),
+                    ("400", "No config file, or the new rules are invalid", Some("Error")),
+                    ("403", "Admin access required", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/admin/database/optimize", json!({
+            "post": {
+                "tags": ["maintenance"],
+                "summary": "Checkpoint, VACUUM, and ANALYZE the database (admin); writes pause while it runs",
+                "responses": responses(&[
+                    ("200", "Sizes before and after", Some("OptimizeReport")),
+                    ("403", "Admin access required", Some("Error")),
+                    ("409", "Optimization already running", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/admin/database/backup", json!({
+            "post": {
+                "tags": ["maintenance"],
+                "summary": "Write a backup archive of the database and, on filesystem storage, the blob store to maintenance.backup_dir (admin); writes pause while it runs",
+                "responses": responses(&[
+                    ("200", "Archive written", Some("BackupReport")),
+                    ("400", "maintenance.backup_dir is not configured", Some("Error")),
+                    ("403", "Admin access required", Some("Error")),
+                    ("409", "An optimization or backup is already running", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/stacks", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "List the caller's stacks and public stacks",
+                "parameters": paged(stack_filter_params(vec![
+                    query_param("public_only", "Leave out the caller's private stacks", json!({ "type": "boolean" })),
+                ])),
+                "responses": responses(&[("200", "Stacks", Some("StackPage"))]),
+            },
+            "post": {
+                "tags": ["stacks"],
+                "summary": "Save a Docker Compose stack",
+                "requestBody": json_body("CreateStackRequest"),
+                "responses": responses(&[
+                    ("201", "Created stack", Some("Stack")),
+                    ("400", "Invalid Docker Compose content", Some("InvalidStack")),
+                ]),
+            },
+        })),
+        ("/api/stacks/{id}", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "One stack; private stacks are visible only to their author",
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[
+                    ("200", "Stack", Some("Stack")),
+                    ("404", "Stack not found", Some("Error")),
+                ]),
+            },
+            "put": {
+                "tags": ["stacks"],
+                "summary": "Update a stack (author only); omitted fields are left unchanged",
+                "parameters": [path_param("id", "Stack id")],
+                "requestBody": json_body("UpdateStackRequest"),
+                "responses": responses(&[
+                    ("200", "Updated stack", Some("Stack")),
+                    ("400", "Invalid Docker Compose content", Some("InvalidStack")),
+                    ("403", "Not the stack's author", Some("Error")),
+                    ("404", "Stack not found", Some("Error")),
+                ]),
+            },
+            "delete": {
+                "tags": ["stacks"],
+                "summary": "Delete a stack and its deployment history (author only)",
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[
+                    ("204", "Deleted", None),
+                    ("403", "Not the stack's author", Some("Error")),
+                    ("404", "Stack not found", Some("Error")),
+                    ("409", "Stack is deployed; undeploy it first", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/stacks/{id}/star", json!({
+            "post": {
+                "tags": ["stacks"],
+                "summary": "Star a stack; starring twice counts once",
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[("200", "Starred", None)]),
+            },
+        })),
+        ("/api/stacks/{id}/unstar", json!({
+            "post": {
+                "tags": ["stacks"],
+                "summary": "Remove the caller's star",
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[("200", "Unstarred", None)]),
+            },
+        })),
+        ("/api/stacks/{id}/download", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "Fetch a public stack and count the download towards its popularity",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[
+                    ("200", "Stack", Some("Stack")),
+                    ("404", "Stack not found or not public", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/stacks/{id}/raw", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "Compose file of a public stack as a YAML attachment",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": [path_param("id", "Stack id")],
+                "responses": {
+                    "200": {
+                        "description": "Compose file",
+                        "content": { "text/yaml": { "schema": { "type": "string" } } },
+                    },
+                    "404": {
+                        "description": "Stack not found or not public",
+                        "content": { "application/json": { "schema": schema_ref("Error") } },
+                    },
+                },
+            },
+        })),
+        ("/api/stacks/{id}/export", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "Stack as a `ghostdock-stack-v1` JSON attachment for importing elsewhere",
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[
+                    ("200", "Exported stack", Some("StackExport")),
+                    ("404", "Stack not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/stacks/import", json!({
+            "post": {
+                "tags": ["stacks"],
+                "summary": "Save a stack from a compose file at a URL",
+                "requestBody": json_body("ImportStackRequest"),
+                "responses": responses(&[
+                    ("201", "Created stack", Some("Stack")),
+                    ("400", "Unsupported URL, failed fetch, or invalid Docker Compose content", Some("InvalidStack")),
+                ]),
+            },
+        })),
+        ("/api/stacks/validate", json!({
+            "post": {
+                "tags": ["stacks"],
+                "summary": "Preflight a compose file without saving it: image availability and security lint",
+                "requestBody": json_body("ValidateStackRequest"),
+                "responses": responses(&[("200", "Validation report", Some("StackValidationReport"))]),
+            },
+        })),
+        ("/api/stacks/{id}/deploy", json!({
+            "post": {
+                "tags": ["stacks"],
+                "summary": "Deploy a stack with `docker compose` (admins and `[deployments]` allowed users)",
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[
+                    ("202", "Deployment started", Some("Deployment")),
+                    ("400", "Stack asks for host access", Some("Error")),
+                    ("403", "Not allowed to deploy", Some("Error")),
+                    ("404", "Stack not found", Some("Error")),
+                    ("409", "Stack is already deployed", Some("Error")),
+                    ("429", "Caller is at `max_deployments_per_user`", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/stacks/{id}/undeploy", json!({
+            "post": {
+                "tags": ["stacks"],
+                "summary": "Tear down a stack's current deployment (whoever deployed it, or an admin)",
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[
+                    ("202", "Undeploying", None),
+                    ("403", "Not the deploying user or an admin", Some("Error")),
+                    ("404", "Stack is not deployed", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/stacks/{id}/status", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "Latest deployment of a stack with live container state",
+                "parameters": [path_param("id", "Stack id")],
+                "responses": responses(&[
+                    ("200", "Deployment status", Some("StackDeploymentStatus")),
+                    ("404", "Stack has never been deployed", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/stacks/{id}/rollback", json!({
+            "post": {
+                "tags": ["stacks"],
+                "summary": "Redeploy the compose file of an earlier deployment, replacing the current one",
+                "parameters": [
+                    path_param("id", "Stack id"),
+                    {
+                        "name": "to",
+                        "in": "query",
+                        "required": true,
+                        "description": "Earlier deployment to roll back to",
+                        "schema": { "type": "string", "format": "uuid" },
+                    },
+                ],
+                "responses": responses(&[
+                    ("202", "Rollback deployment started", Some("Deployment")),
+                    ("400", "Deployment has no snapshot or failed", Some("Error")),
+                    ("403", "Not allowed to deploy, or the current deployment is someone else's", Some("Error")),
+                    ("404", "Stack or deployment not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/registry/stacks", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "Browse public stacks",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": paged(stack_filter_params(vec![])),
+                "responses": responses(&[("200", "Public stacks", Some("StackPage"))]),
+            },
+        })),
+        ("/api/registry/stacks/featured", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "Public stacks an admin has featured, most popular first",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": paged(vec![]),
+                "responses": responses(&[("200", "Featured stacks", Some("RankedStackPage"))]),
+            },
+        })),
+        ("/api/registry/stacks/popular", json!({
+            "get": {
+                "tags": ["stacks"],
+                "summary": "Public stacks by time-decayed popularity",
+                "security": [{}, { "bearerAuth": [] }],
+                "parameters": paged(vec![]),
+                "responses": responses(&[("200", "Popular stacks", Some("RankedStackPage"))]),
+            },
+        })),
+        ("/api/registry/stacks/{id}/featured", json!({
+            "put": {
+                "tags": ["stacks"],
+                "summary": "Feature or unfeature a stack (admin)",
+                "parameters": [path_param("id", "Stack id")],
+                "requestBody": json_body("FeatureStackRequest"),
+                "responses": responses(&[
+                    ("204", "Updated", None),
+                    ("403", "Admin access required", Some("Error")),
+                    ("404", "Stack not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/deployments", json!({
+            "get": {
+                "tags": ["deployments"],
+                "summary": "List stack deployments, newest first; admins see all, others their own",
+                "parameters": paged(vec![
+                    query_param("stack_id", "Only deployments of this stack", json!({ "type": "string" })),
+                    query_param("status", "Only deployments in this state", schema_ref("DeploymentStatus")),
+                ]),
+                "responses": responses(&[("200", "Deployments", Some("DeploymentPage"))]),
+            },
+        })),
+        ("/api/repositories/{name}/promote", json!({
+            "post": {
+                "tags": ["promotions"],
+                "summary": "Request that an image be copied to another repository or tag once approved",
+                "parameters": [path_param("name", "Source repository")],
+                "requestBody": json_body("PromoteRequest"),
+                "responses": responses(&[
+                    ("201", "Pending promotion; approvers are notified", Some("Promotion")),
+                    ("400", "Invalid reference or destination", Some("Error")),
+                    ("403", "No pull access to the source", Some("Error")),
+                    ("404", "Source image or destination repository not found", Some("Error")),
+                    ("409", "The same promotion is already pending", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/promotions", json!({
+            "get": {
+                "tags": ["promotions"],
+                "summary": "List promotions, newest first; approvers see all, others their own and those into repositories they administer",
+                "parameters": paged(vec![
+                    query_param("status", "Only promotions in this state", schema_ref("PromotionStatus")),
+                    query_param("repository", "Only promotions into this repository", json!({ "type": "string" })),
+                ]),
+                "responses": responses(&[("200", "Promotions", Some("PromotionPage"))]),
+            },
+        })),
+        ("/api/promotions/{id}", json!({
+            "get": {
+                "tags": ["promotions"],
+                "summary": "One promotion, visible to its requester and its approvers",
+                "parameters": [path_param("id", "Promotion id")],
+                "responses": responses(&[
+                    ("200", "Promotion", Some("Promotion")),
+                    ("404", "Promotion not found", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/promotions/{id}/approve", json!({
+            "post": {
+                "tags": ["promotions"],
+                "summary": "Approve a pending promotion and copy the image; the result is completed or failed",
+                "parameters": [path_param("id", "Promotion id")],
+                "requestBody": {
+                    "required": false,
+                    "content": { "application/json": { "schema": schema_ref("ReviewRequest") } },
+                },
+                "responses": responses(&[
+                    ("200", "Promotion after the copy", Some("Promotion")),
+                    ("403", "Not an approver for the destination, or approving your own request", Some("Error")),
+                    ("404", "Promotion not found", Some("Error")),
+                    ("409", "Promotion is no longer pending", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/promotions/{id}/reject", json!({
+            "post": {
+                "tags": ["promotions"],
+                "summary": "Reject a pending promotion",
+                "parameters": [path_param("id", "Promotion id")],
+                "requestBody": {
+                    "required": false,
+                    "content": { "application/json": { "schema": schema_ref("ReviewRequest") } },
+                },
+                "responses": responses(&[
+                    ("200", "Rejected promotion", Some("Promotion")),
+                    ("403", "Not an approver for the destination, or rejecting your own request", Some("Error")),
+                    ("404", "Promotion not found", Some("Error")),
+                    ("409", "Promotion is no longer pending", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/admin/import", json!({
+            "get": {
+                "tags": ["imports"],
+                "summary": "List import jobs, newest first (admin)",
+                "parameters": paged(vec![]),
+                "responses": responses(&[("200", "Import jobs", Some("ImportJobPage"))]),
+            },
+            "post": {
+                "tags": ["imports"],
+                "summary": "Start a background import from another registry (admin)",
+                "requestBody": json_body("ImportRequest"),
+                "responses": responses(&[
+                    ("202", "Import started", Some("ImportJob")),
+                    ("400", "Invalid source or repository name", Some("Error")),
+                    ("403", "Admin access required", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/admin/import/{job_id}", json!({
+            "get": {
+                "tags": ["imports"],
+                "summary": "Current state of an import job (admin)",
+                "parameters": [path_param("job_id", "Import job id")],
+                "responses": responses(&[
+                    ("200", "Import job", Some("ImportJob")),
+                    ("404", "Import job not found", Some("Error")),
+                ]),
+            },
+            "delete": {
+                "tags": ["imports"],
+                "summary": "Cancel a running import (admin)",
+                "parameters": [path_param("job_id", "Import job id")],
+                "responses": responses(&[
+                    ("202", "Cancellation requested", None),
+                    ("409", "Import is not running", Some("Error")),
+                ]),
+            },
+        })),
+        ("/api/admin/import/{job_id}/resume", json!({
+            "post": {
+                "tags": ["imports"],
+                "summary": "Resume a failed, cancelled, or interrupted import (admin)",
+                "parameters": [path_param("job_id", "Import job id")],
+                "requestBody": {
+                    "required": false,
+                    "content": { "application/json": { "schema": schema_ref("ResumeImportRequest") } },
+                },
+                "responses": responses(&[
+                    ("202", "Import resumed", Some("ImportJob")),
+                    ("409", "Import cannot be resumed", Some("Error")),
+                ]),
+            },
+        })),
+    ];
+    Value::Object(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+fn schemas() -> Value {
+    let timestamp = json!({ "type": "string", "format": "date-time" });
+    let nullable_timestamp = json!({ "type": "string", "format": "date-time", "nullable": true });
+    let nullable_string = json!({ "type": "string", "nullable": true });
+
+    let entries = [
+        ("Error", object(&[
+            ("error", object(&[
+                ("code", json!({ "type": "string", "example": "NOT_FOUND" })),
+                ("message", json!({ "type": "string" })),
+                ("request_id", json!({ "type": "string" })),
+            ], &["code", "message"])),
+        ], &["error"])),
+        ("LoginRequest", object(&[
+            ("username", json!({ "type": "string", "description": "Username or email" })),
+            ("password", json!({ "type": "string", "format": "password" })),
+        ], &["username", "password"])),
+        ("LoginResponse", object(&[
+            ("token", json!({ "type": "string" })),
+            ("user", schema_ref("User")),
+            ("expires_at", timestamp.clone()),
+        ], &["token", "user", "expires_at"])),
+        ("User", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("username", json!({ "type": "string" })),
+            ("email", json!({ "type": "string" })),
+            ("full_name", nullable_string.clone()),
+            ("avatar_url", nullable_string.clone()),
+            ("provider", nullable_string.clone()),
+            ("provider_id", nullable_string.clone()),
+            ("is_admin", json!({ "type": "boolean" })),
+            ("is_active", json!({ "type": "boolean" })),
+            ("created_at", timestamp.clone()),
+            ("updated_at", timestamp.clone()),
+            ("last_login", nullable_timestamp.clone()),
+        ], &["id", "username", "email", "is_admin", "is_active", "created_at", "updated_at"])),
+        ("ArtifactType", json!({ "type": "string", "enum": ["image", "helm_chart", "index", "artifact"] })),
+        ("Repository", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("name", json!({ "type": "string" })),
+            ("namespace", nullable_string.clone()),
+            ("description", json!({ "type": "string" })),
+            ("is_public", json!({ "type": "boolean" })),
+            ("created_at", timestamp.clone()),
+            ("updated_at", timestamp.clone()),
+        ], &["id", "name", "description", "is_public", "created_at", "updated_at"])),
+        ("RepositorySummary", json!({
+            "allOf": [
+                schema_ref("Repository"),
+                object(&[
+                    ("artifact_types", array_of(schema_ref("ArtifactType"))),
+                    ("is_favorited", json!({ "type": "boolean" })),
+                ], &["artifact_types", "is_favorited"]),
+            ],
+        })),
+        ("RepositorySummaryPage", page_of(schema_ref("RepositorySummary"), &[])),
+        ("FavoriteRepository", json!({
+            "allOf": [
+                schema_ref("Repository"),
+                object(&[("favorited_at", timestamp.clone())], &["favorited_at"]),
+            ],
+        })),
+        ("FavoriteRepositoryPage", page_of(schema_ref("FavoriteRepository"), &[])),
+        ("CreateRepositoryRequest", object(&[
+            ("name", json!({ "type": "string" })),
+            ("description", nullable_string.clone()),
+            ("is_public", json!({
+                "type": "boolean",
+                "nullable": true,
+                "description": "Defaults to the configured visibility for the caller's namespace",
+            })),
+            ("allowed_artifact_types", json!({
+                "type": "array",
+                "items": { "$ref": "#/components/schemas/ArtifactType" },
+                "nullable": true,
+                "description": "Artifact types pushes may contain; omitted accepts any",
+            })),
+        ], &["name"])),
+        ("ArtifactTypeRestriction", object(&[
+            ("allowed_artifact_types", json!({
+                "type": "array",
+                "items": { "$ref": "#/components/schemas/ArtifactType" },
+                "nullable": true,
+                "description": "null accepts any type; an index is accepted wherever images are",
+            })),
+        ], &["allowed_artifact_types"])),
+        ("DefaultPlatform", object(&[
+            ("default_platform", json!({
+                "type": "string",
+                "nullable": true,
+                "description": "os/architecture[/variant]; null uses registry.default_platform",
+            })),
+        ], &["default_platform"])),
+        ("DeletionProtection", object(&[
+            ("deletion_protected", json!({ "type": "boolean" })),
+        ], &["deletion_protected"])),
+        ("TagDriftRequest", object(&[
+            ("references", array_of(object(&[
+                ("reference", json!({ "type": "string", "description": "repository:tag; a bare name means latest" })),
+                ("baseline_digest", nullable_string.clone()),
+            ], &["reference"]))),
+        ], &["references"])),
+        ("TagDriftReport", object(&[
+            ("drifted", json!({ "type": "integer" })),
+            ("results", array_of(object(&[
+                ("reference", json!({ "type": "string" })),
+                ("baseline_digest", nullable_string.clone()),
+                ("current_digest", nullable_string.clone()),
+                ("drifted", json!({ "type": "boolean" })),
+                ("tag_updated_at", nullable_timestamp.clone()),
+                ("error", nullable_string.clone()),
+            ], &["reference", "drifted"]))),
+        ], &["drifted", "results"])),
+        ("TagDetail", object(&[
+            ("name", json!({ "type": "string" })),
+            ("digest", json!({ "type": "string" })),
+            ("media_type", json!({ "type": "string" })),
+            ("artifact_type", schema_ref("ArtifactType")),
+            ("image_size", json!({ "type": "integer", "format": "int64" })),
+            ("updated_at", timestamp.clone()),
+        ], &["name", "digest", "media_type", "artifact_type", "image_size", "updated_at"])),
+        ("TagDetailPage", page_of(schema_ref("TagDetail"), &[])),
+        ("BlobCheckRequest", object(&[
+            ("digests", json!({ "type": "array", "items": { "type": "string" }, "maxItems": 1000 })),
+        ], &["digests"])),
+        ("BlobCheckResponse", object(&[
+            ("present", array_of(object(&[
+                ("digest", json!({ "type": "string" })),
+                ("size", json!({ "type": "integer", "format": "int64" })),
+            ], &["digest", "size"]))),
+            ("missing", array_of(json!({ "type": "string" }))),
+        ], &["present", "missing"])),
+        ("ImageConfigDetails", object(&[
+            ("manifest_digest", json!({ "type": "string" })),
+            ("config_digest", json!({ "type": "string" })),
+            ("platform", nullable_string.clone()),
+            ("created", nullable_string.clone()),
+            ("author", nullable_string.clone()),
+            ("user", nullable_string.clone()),
+            ("entrypoint", array_of(json!({ "type": "string" }))),
+            ("cmd", array_of(json!({ "type": "string" }))),
+            ("working_dir", nullable_string.clone()),
+            ("env", array_of(json!({ "type": "string" }))),
+            ("exposed_ports", array_of(json!({ "type": "string" }))),
+            ("volumes", array_of(json!({ "type": "string" }))),
+            ("labels", json!({ "type": "object", "additionalProperties": { "type": "string" } })),
+            ("stop_signal", nullable_string.clone()),
+        ], &["manifest_digest", "config_digest", "entrypoint", "cmd", "env", "exposed_ports", "volumes", "labels"])),
+        ("UsagePoint", object(&[
+            ("day", json!({ "type": "string", "format": "date" })),
+            ("pulls", json!({ "type": "integer", "format": "int64" })),
+            ("pushes", json!({ "type": "integer", "format": "int64" })),
+            ("bytes_pulled", json!({ "type": "integer", "format": "int64" })),
+            ("bytes_pushed", json!({ "type": "integer", "format": "int64" })),
+        ], &["day", "pulls", "pushes", "bytes_pulled", "bytes_pushed"])),
+        ("UsageSeries", object(&[
+            ("repository", nullable_string.clone()),
+            ("range_days", json!({ "type": "integer", "format": "int64" })),
+            ("total_image_size", json!({ "type": "integer", "format": "int64" })),
+            ("points", array_of(schema_ref("UsagePoint"))),
+        ], &["range_days", "points"])),
+        ("RobotPermission", json!({ "type": "string", "enum": ["read", "read_write"] })),
+        ("RobotAccount", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("name", json!({ "type": "string" })),
+            ("username", json!({ "type": "string", "example": "robot$myapp+ci" })),
+            ("permission", schema_ref("RobotPermission")),
+            ("expires_at", nullable_timestamp.clone()),
+            ("created_at", timestamp.clone()),
+            ("last_used", nullable_timestamp.clone()),
+        ], &["id", "name", "username", "permission", "created_at"])),
+        ("RobotAccountPage", page_of(schema_ref("RobotAccount"), &[])),
+        ("CreateRobotRequest", object(&[
+            ("name", json!({ "type": "string" })),
+            ("permission", schema_ref("RobotPermission")),
+            ("expires_at", nullable_timestamp.clone()),
+        ], &["name", "permission"])),
+        ("CreateRobotResponse", json!({
+            "allOf": [
+                schema_ref("RobotAccount"),
+                object(&[("token", json!({ "type": "string", "description": "Shown only once" }))], &["token"]),
+            ],
+        })),
+        ("RegistryInfo", object(&[
+            ("name", json!({ "type": "string" })),
+            ("title", json!({ "type": "string" })),
+            ("description", json!({ "type": "string" })),
+            ("version", json!({ "type": "string" })),
+            ("manifest_media_types", array_of(json!({ "type": "string" }))),
+            ("features", object(&[
+                ("push", json!({ "type": "boolean" })),
+                ("deletion", json!({ "type": "boolean" })),
+                ("anonymous_pull", json!({ "type": "boolean" })),
+                ("proxy", json!({ "type": "boolean" })),
+                ("content_trust", json!({ "type": "boolean" })),
+                ("scanning", json!({ "type": "boolean" })),
+                ("referrers", json!({ "type": "boolean" })),
+                ("read_only", json!({ "type": "boolean" })),
+            ], &["push", "deletion", "anonymous_pull", "proxy", "content_trust", "scanning", "referrers", "read_only"])),
+            ("limits", object(&[
+                ("max_manifest_size", json!({ "type": "integer" })),
+                ("max_blob_size", json!({ "type": "integer" })),
+                ("max_upload_size", json!({ "type": "integer" })),
+            ], &["max_manifest_size", "max_blob_size", "max_upload_size"])),
+            ("storage", json!({
+                "description": "Only returned to authenticated callers",
+                "allOf": [object(&[
+                    ("backend", json!({ "type": "string", "enum": ["filesystem", "s3", "gcs", "azure"] })),
+                    ("deduplication", json!({ "type": "boolean" })),
+                ], &["backend", "deduplication"])],
+            })),
+        ], &["name", "title", "description", "version", "manifest_media_types", "features", "limits"])),
+        ("SharePermission", json!({ "type": "string", "enum": ["pull", "push"] })),
+        ("Share", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("username", json!({ "type": "string", "example": "share$6f1c2a9e-..." })),
+            ("permission", schema_ref("SharePermission")),
+            ("expires_at", timestamp.clone()),
+            ("created_by", json!({ "type": "string" })),
+            ("created_at", timestamp.clone()),
+            ("revoked_at", nullable_timestamp.clone()),
+        ], &["id", "username", "permission", "expires_at", "created_by", "created_at"])),
+        ("SharePage", page_of(schema_ref("Share"), &[])),
+        ("CreateShareRequest", object(&[
+            ("permission", schema_ref("SharePermission")),
+            ("ttl_secs", json!({ "type": "integer", "description": "Defaults to auth.share_links.default_ttl_secs" })),
+        ], &[])),
+        ("CreateShareResponse", json!({
+            "allOf": [
+                schema_ref("Share"),
+                object(&[
+                    ("token", json!({ "type": "string", "description": "Shown only once; the password for docker login" })),
+                    ("docker_login", json!({ "type": "string" })),
+                ], &["token", "docker_login"]),
+            ],
+        })),
+        ("Team", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("name", json!({ "type": "string" })),
+            ("description", json!({ "type": "string" })),
+            ("member_count", json!({ "type": "integer" })),
+            ("created_at", timestamp.clone()),
+        ], &["id", "name", "description", "member_count", "created_at"])),
+        ("TeamPage", page_of(schema_ref("Team"), &[])),
+        ("CreateTeamRequest", object(&[
+            ("name", json!({ "type": "string" })),
+            ("description", json!({ "type": "string" })),
+        ], &["name"])),
+        ("TeamMember", object(&[
+            ("user_id", json!({ "type": "string" })),
+            ("username", json!({ "type": "string" })),
+            ("added_at", timestamp.clone()),
+        ], &["user_id", "username", "added_at"])),
+        ("TeamMemberPage", page_of(schema_ref("TeamMember"), &[])),
+        ("RepositoryPermission", json!({ "type": "string", "enum": ["read", "write", "admin"] })),
+        ("RepositoryGrant", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("user_id", nullable_string.clone()),
+            ("team_id", json!({ "type": "string", "format": "uuid", "nullable": true })),
+            ("permission", schema_ref("RepositoryPermission")),
+            ("created_by", json!({ "type": "string" })),
+            ("created_at", timestamp.clone()),
+        ], &["id", "permission", "created_by", "created_at"])),
+        ("RepositoryGrantPage", page_of(schema_ref("RepositoryGrant"), &[])),
+        ("GrantPermissionRequest", object(&[
+            ("user_id", json!({ "type": "string" })),
+            ("team_id", json!({ "type": "string", "format": "uuid" })),
+            ("permission", schema_ref("RepositoryPermission")),
+        ], &["permission"])),
+        ("DeleteUserResponse", object(&[
+            ("user_id", json!({ "type": "string" })),
+            ("repositories_reassigned", array_of(json!({ "type": "string" }))),
+            ("repositories_deleted", array_of(json!({ "type": "string" }))),
+            ("stacks_reassigned", array_of(json!({ "type": "string" }))),
+            ("stacks_deleted", array_of(json!({ "type": "string" }))),
+            ("reassigned_to", nullable_string.clone()),
+        ], &["user_id", "repositories_reassigned", "repositories_deleted", "stacks_reassigned", "stacks_deleted"])),
+        ("UserProfile", object(&[
+            ("id", json!({ "type": "string" })),
+            ("username", json!({ "type": "string" })),
+            ("email", json!({ "type": "string" })),
+            ("full_name", nullable_string.clone()),
+            ("avatar_url", nullable_string.clone()),
+            ("provider", nullable_string.clone()),
+            ("is_admin", json!({ "type": "boolean" })),
+            ("has_password", json!({ "type": "boolean" })),
+            ("created_at", timestamp.clone()),
+            ("last_login", nullable_timestamp.clone()),
+        ], &["id", "username", "email", "is_admin", "has_password", "created_at"])),
+        ("ChangePasswordRequest", object(&[
+            ("current_password", json!({ "type": "string" })),
+            ("new_password", json!({ "type": "string", "minLength": 8 })),
+        ], &["current_password", "new_password"])),
+        ("RepositoryLimit", object(&[
+            ("max_repositories", json!({
+                "type": "integer",
+                "nullable": true,
+                "description": "null uses the configured default; 0 is unlimited",
+            })),
+        ], &["max_repositories"])),
+        ("RateLimitOverride", object(&[
+            ("requests_per_sec", json!({
+                "type": "integer",
+                "nullable": true,
+                "description": "null uses rate_limit.requests_per_sec; 0 is unlimited",
+            })),
+        ], &["requests_per_sec"])),
+        ("NotificationPreference", object(&[
+            ("channel", json!({ "type": "string", "enum": ["email", "slack", "discord"] })),
+            ("destination", json!({ "type": "string", "description": "Email address or webhook URL" })),
+            ("min_severity", json!({ "type": "string", "enum": ["info", "warning", "error", "success"] })),
+        ], &["channel", "destination", "min_severity"])),
+        ("NotificationPreferenceList", array_of(schema_ref("NotificationPreference"))),
+        ("WebhookFilter", object(&[
+            ("events", array_of(json!({ "type": "string", "enum": ["push", "delete"] }))),
+            ("tag_patterns", json!({
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Tag globs such as `prod-*`; omit to fire for every tag",
+            })),
+        ], &["events"])),
+        ("CreateWebhookRequest", object(&[
+            ("url", json!({ "type": "string", "format": "uri" })),
+            ("repository", nullable_string.clone()),
+            ("events", array_of(json!({ "type": "string", "enum": ["push", "delete"] }))),
+            ("tag_patterns", array_of(json!({ "type": "string" }))),
+        ], &["url", "events"])),
+        ("Webhook", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("url", json!({ "type": "string" })),
+            ("repository", nullable_string.clone()),
+            ("events", array_of(json!({ "type": "string" }))),
+            ("tag_patterns", array_of(json!({ "type": "string" }))),
+            ("secret", json!({ "type": "string", "description": "Only present in the create response" })),
+            ("created_at", timestamp.clone()),
+        ], &["id", "url", "repository", "events", "created_at"])),
+        ("RotateSecretResponse", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("secret", json!({ "type": "string" })),
+            ("previous_secret_valid", json!({ "type": "boolean" })),
+            ("rotated_at", timestamp.clone()),
+        ], &["id", "secret", "previous_secret_valid", "rotated_at"])),
+        ("WebhookDelivery", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("webhook_id", json!({ "type": "string", "format": "uuid" })),
+            ("event_type", json!({ "type": "string" })),
+            ("url", json!({ "type": "string" })),
+            ("attempt", json!({ "type": "integer" })),
+            ("success", json!({ "type": "boolean" })),
+            ("response_status", json!({ "type": "integer", "nullable": true })),
+            ("response_body", nullable_string.clone()),
+            ("latency_ms", json!({ "type": "integer" })),
+            ("error_kind", json!({
+                "type": "string",
+                "nullable": true,
+                "enum": ["timeout", "connect", "redirect", "request", "http_status"],
+            })),
+            ("error_message", nullable_string.clone()),
+            ("created_at", timestamp.clone()),
+        ], &["id", "webhook_id", "event_type", "url", "attempt", "success", "latency_ms", "created_at"])),
+        ("DeliveryLog", page_of(schema_ref("WebhookDelivery"), &[
+            ("webhook_id", json!({ "type": "string", "format": "uuid" })),
+            ("attempts", json!({ "type": "integer", "description": "Attempts in the time range, whatever their outcome" })),
+            ("failed", json!({ "type": "integer" })),
+            ("failure_rate", json!({ "type": "number", "minimum": 0, "maximum": 1 })),
+        ])),
+        ("MaintenanceStatus", object(&[
+            ("read_only", json!({ "type": "boolean" })),
+            ("message", nullable_string.clone()),
+            ("retry_after_secs", json!({ "type": "integer" })),
+        ], &["read_only", "retry_after_secs"])),
+        ("ProxyRule", object(&[
+            ("pattern", json!({ "type": "string", "description": "Repository glob; * matches any characters" })),
+            ("upstream", json!({ "type": "string", "nullable": true, "description": "null means never proxied" })),
+            ("authenticated", json!({ "type": "boolean" })),
+        ], &["pattern", "authenticated"])),
+        ("ProxyRuleList", array_of(schema_ref("ProxyRule"))),
+        ("ProxyRulePage", page_of(schema_ref("ProxyRule"), &[])),
+        ("OptimizeReport", object(&[
+            ("size_before_bytes", json!({ "type": "integer", "format": "int64" })),
+            ("wal_size_before_bytes", json!({ "type": "integer", "format": "int64" })),
+            ("size_after_bytes", json!({ "type": "integer", "format": "int64" })),
+            ("wal_size_after_bytes", json!({ "type": "integer", "format": "int64" })),
+            ("duration_ms", json!({ "type": "integer", "format": "int64" })),
+        ], &["size_before_bytes", "wal_size_before_bytes", "size_after_bytes", "wal_size_after_bytes", "duration_ms"])),
+        ("BackupManifest", object(&[
+            ("format_version", json!({ "type": "integer" })),
+            ("ghostdock_version", json!({ "type": "string" })),
+            ("created_at", json!({ "type": "string", "format": "date-time" })),
+            ("storage_backend", json!({ "type": "string", "enum": ["filesystem", "s3", "gcs", "azure"] })),
+            ("includes_storage", json!({ "type": "boolean", "description": "false for object storage, whose bucket stays the source of truth" })),
+            ("database_bytes", json!({ "type": "integer", "format": "int64" })),
+            ("storage_files", json!({ "type": "integer", "format": "int64" })),
+            ("storage_bytes", json!({ "type": "integer", "format": "int64" })),
+        ], &["format_version", "ghostdock_version", "created_at", "storage_backend", "includes_storage", "database_bytes", "storage_files", "storage_bytes"])),
+        ("BackupReport", object(&[
+            ("output", json!({ "type": "string", "description": "Archive path on the server" })),
+            ("manifest", schema_ref("BackupManifest")),
+            ("duration_ms", json!({ "type": "integer", "format": "int64" })),
+        ], &["output", "manifest", "duration_ms"])),
+        ("SetMaintenanceRequest", object(&[
+            ("read_only", json!({ "type": "boolean" })),
+            ("message", nullable_string.clone()),
+        ], &["read_only"])),
+        ("ImportRequest", object(&[
+            ("source", json!({ "type": "string", "example": "https://registry.example.com" })),
+            ("repositories", array_of(json!({ "type": "string" }))),
+            ("username", nullable_string.clone()),
+            ("password", json!({ "type": "string", "format": "password", "nullable": true })),
+        ], &["source", "repositories"])),
+        ("ResumeImportRequest", object(&[
+            ("username", nullable_string.clone()),
+            ("password", json!({ "type": "string", "format": "password", "nullable": true })),
+        ], &[])),
+        ("ImportStatus", json!({
+            "type": "string",
+            "enum": ["pending", "running", "completed", "failed", "cancelled", "interrupted"],
+        })),
+        ("ImportProgress", object(&[
+            ("job_id", json!({ "type": "string", "format": "uuid" })),
+            ("status", schema_ref("ImportStatus")),
+            ("repository", nullable_string.clone()),
+            ("tag", nullable_string.clone()),
+            ("blob", nullable_string.clone()),
+            ("repositories_done", json!({ "type": "integer" })),
+            ("repositories_total", json!({ "type": "integer" })),
+            ("tags_done", json!({ "type": "integer" })),
+            ("tags_total", json!({ "type": "integer" })),
+            ("blobs_done", json!({ "type": "integer" })),
+            ("blobs_total", json!({ "type": "integer" })),
+            ("bytes_done", json!({ "type": "integer", "format": "int64" })),
+            ("error", nullable_string.clone()),
+        ], &["job_id", "status"])),
+        ("ImportJob", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("source", json!({ "type": "string" })),
+            ("repositories", array_of(json!({ "type": "string" }))),
+            ("created_by", json!({ "type": "string" })),
+            ("created_at", timestamp.clone()),
+            ("updated_at", timestamp.clone()),
+            ("progress", schema_ref("ImportProgress")),
+        ], &["id", "source", "repositories", "created_by", "created_at", "updated_at", "progress"])),
+        ("ImportJobPage", page_of(schema_ref("ImportJob"), &[])),
+        ("Stack", object(&[
+            ("id", json!({ "type": "string" })),
+            ("name", json!({ "type": "string" })),
+            ("description", nullable_string.clone()),
+            ("compose_content", json!({ "type": "string" })),
+            ("version", json!({ "type": "string" })),
+            ("author", json!({ "type": "string", "description": "Id of the authoring user" })),
+            ("author_email", json!({ "type": "string" })),
+            ("tags", array_of(json!({ "type": "string" }))),
+            ("is_public", json!({ "type": "boolean" })),
+            ("created_at", timestamp.clone()),
+            ("updated_at", timestamp.clone()),
+            ("download_count", json!({ "type": "integer", "format": "int64" })),
+            ("star_count", json!({ "type": "integer", "format": "int64" })),
+        ], &[
+            "id", "name", "compose_content", "version", "author", "author_email", "tags", "is_public",
+            "created_at", "updated_at", "download_count", "star_count",
+        ])),
+        ("StackPage", page_of(schema_ref("Stack"), &[])),
+        ("RankedStack", json!({
+            "allOf": [
+                schema_ref("Stack"),
+                object(&[
+                    ("featured", json!({ "type": "boolean" })),
+                    ("popularity_score", json!({ "type": "number" })),
+                ], &["featured", "popularity_score"]),
+            ],
+        })),
+        ("RankedStackPage", page_of(schema_ref("RankedStack"), &[])),
+        ("CreateStackRequest", object(&[
+            ("name", json!({ "type": "string" })),
+            ("description", json!({ "type": "string" })),
+            ("compose_content", json!({ "type": "string" })),
+            ("tags", array_of(json!({ "type": "string" }))),
+            ("is_public", json!({ "type": "boolean" })),
+        ], &["name", "compose_content", "tags", "is_public"])),
+        ("UpdateStackRequest", object(&[
+            ("name", json!({ "type": "string" })),
+            ("description", json!({ "type": "string" })),
+            ("compose_content", json!({ "type": "string" })),
+            ("tags", array_of(json!({ "type": "string" }))),
+            ("is_public", json!({ "type": "boolean" })),
+        ], &[])),
+        ("ImportStackRequest", object(&[
+            ("url", json!({ "type": "string", "format": "uri" })),
+            ("name", json!({ "type": "string", "description": "Defaults to the file name in the URL" })),
+            ("description", json!({ "type": "string" })),
+            ("tags", array_of(json!({ "type": "string" }))),
+            ("is_public", json!({ "type": "boolean" })),
+        ], &["url"])),
+        ("InvalidStack", object(&[
+            ("error", json!({ "type": "string" })),
+            ("details", json!({ "type": "string" })),
+        ], &["error"])),
+        ("StackExport", object(&[
+            ("format", json!({ "type": "string", "enum": ["ghostdock-stack-v1"] })),
+            ("exported_at", timestamp.clone()),
+            ("exported_by", json!({ "type": "string" })),
+            ("stack", object(&[
+                ("name", json!({ "type": "string" })),
+                ("description", nullable_string.clone()),
+                ("version", json!({ "type": "string" })),
+                ("tags", array_of(json!({ "type": "string" }))),
+                ("compose_content", json!({ "type": "string" })),
+            ], &["name", "version", "tags", "compose_content"])),
+        ], &["format", "exported_at", "exported_by", "stack"])),
+        ("ValidateStackRequest", object(&[
+            ("compose_content", json!({ "type": "string" })),
+        ], &["compose_content"])),
+        ("ImageCheck", object(&[
+            ("service", json!({ "type": "string" })),
+            ("image", json!({ "type": "string" })),
+            ("registry", json!({ "type": "string" })),
+            ("repository", json!({ "type": "string" })),
+            ("reference", json!({ "type": "string" })),
+            ("resolvable", json!({ "type": "boolean" })),
+            ("source", nullable_string.clone()),
+            ("reason", nullable_string.clone()),
+        ], &["service", "image", "registry", "repository", "reference", "resolvable"])),
+        ("StackValidationReport", object(&[
+            ("valid", json!({ "type": "boolean" })),
+            ("errors", array_of(json!({ "type": "string" }))),
+            ("resolvable", array_of(schema_ref("ImageCheck"))),
+            ("unresolvable", array_of(schema_ref("ImageCheck"))),
+            ("build_only_services", array_of(json!({ "type": "string" }))),
+            ("lint_findings", array_of(object(&[
+                ("service", json!({ "type": "string" })),
+                ("severity", json!({ "type": "string" })),
+                ("message", json!({ "type": "string" })),
+            ], &["service", "severity", "message"]))),
+        ], &["valid", "errors", "resolvable", "unresolvable", "build_only_services", "lint_findings"])),
+        ("FeatureStackRequest", object(&[("featured", json!({ "type": "boolean" }))], &["featured"])),
+        ("StackDeploymentStatus", object(&[
+            ("stack_id", json!({ "type": "string" })),
+            ("deployment_id", json!({ "type": "string", "format": "uuid" })),
+            ("status", schema_ref("DeploymentStatus")),
+            ("error", nullable_string.clone()),
+            ("services", array_of(object(&[
+                ("name", json!({ "type": "string" })),
+                ("state", json!({ "type": "string", "example": "running" })),
+                ("status", json!({ "type": "string", "example": "Up 5 minutes" })),
+            ], &["name", "state", "status"]))),
+            ("started_by", json!({ "type": "string" })),
+            ("started_at", timestamp.clone()),
+            ("last_updated", timestamp.clone()),
+        ], &["stack_id", "deployment_id", "status", "services", "started_by", "started_at", "last_updated"])),
+        ("DeploymentStatus", json!({
+            "type": "string",
+            "enum": ["deploying", "running", "undeploying", "stopped", "failed"],
+        })),
+        ("Deployment", object(&[
+            ("deployment_id", json!({ "type": "string", "format": "uuid" })),
+            ("stack_id", json!({ "type": "string" })),
+            ("status", schema_ref("DeploymentStatus")),
+            ("started_by", json!({ "type": "string" })),
+            ("started_at", timestamp.clone()),
+            ("updated_at", timestamp.clone()),
+            ("error", nullable_string.clone()),
+            ("rolled_back_from", json!({ "type": "string", "format": "uuid", "nullable": true })),
+        ], &["deployment_id", "stack_id", "status", "started_by", "started_at", "updated_at"])),
+        ("DeploymentPage", page_of(schema_ref("Deployment"), &[])),
+        ("PromotionStatus", json!({
+            "type": "string",
+            "enum": ["pending", "approved", "rejected", "completed", "failed"],
+        })),
+        ("PromoteRequest", object(&[
+            ("reference", json!({ "type": "string", "description": "Tag or digest in the source repository" })),
+            ("destination_repository", json!({ "type": "string" })),
+            ("destination_tag", json!({ "type": "string", "description": "Defaults to the source tag; required when promoting by digest" })),
+            ("comment", json!({ "type": "string" })),
+        ], &["reference", "destination_repository"])),
+        ("ReviewRequest", object(&[("comment", json!({ "type": "string" }))], &[])),
+        ("Promotion", object(&[
+            ("id", json!({ "type": "string", "format": "uuid" })),
+            ("source_repository", json!({ "type": "string" })),
+            ("source_reference", json!({ "type": "string" })),
+            ("digest", json!({ "type": "string", "description": "Source digest pinned when requested" })),
+            ("destination_repository", json!({ "type": "string" })),
+            ("destination_tag", json!({ "type": "string" })),
+            ("status", schema_ref("PromotionStatus")),
+            ("requested_by", json!({ "type": "string" })),
+            ("requested_at", timestamp),
+            ("comment", nullable_string.clone()),
+            ("reviewed_by", nullable_string.clone()),
+            ("reviewed_at", nullable_timestamp.clone()),
+            ("review_comment", nullable_string.clone()),
+            ("error", nullable_string),
+            ("completed_at", nullable_timestamp),
+        ], &["id", "source_repository", "source_reference", "digest", "destination_repository", "destination_tag", "status", "requested_by", "requested_at"])),
+        ("PromotionPage", page_of(schema_ref("Promotion"), &[])),
+    ];
+    Value::Object(entries.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+fn schema_ref(name: &str) -> Value {
+    json!({ "$ref": format!("#/components/schemas/{}", name) })
+}
+
+fn array_of(items: Value) -> Value {
+    json!({ "type": "array", "items": items })
+}
+
+/// The shared `Page<T>` list envelope, plus any endpoint-specific fields (all required)
+fn page_of(items: Value, extra: &[(&str, Value)]) -> Value {
+    let mut properties = vec![
+        ("items", array_of(items)),
+        ("total", json!({ "type": "integer", "format": "int64", "description": "Matching items across all pages" })),
+        ("limit", json!({ "type": "integer" })),
+        ("offset", json!({ "type": "integer" })),
+        ("next_cursor", json!({
+            "type": "string",
+            "nullable": true,
+            "description": "Pass as `cursor` for the next page; null on the last page",
+        })),
+    ];
+    properties.extend(extra.iter().cloned());
+
+    let mut required = vec!["items", "total", "limit", "offset"];
+    required.extend(extra.iter().map(|(name, _)| *name));
+    object(&properties, &required)
+}
+
+fn object(properties: &[(&str, Value)], required: &[&str]) -> Value {
+    let properties: Map<String, Value> = properties
+        .iter()
+        .map(|(name, schema)| (name.to_string(), schema.clone()))
+        .collect();
+
+    let mut schema = json!({ "type": "object", "properties": properties });
+    if !required.is_empty() {
+        schema["required"] = json!(required);
+    }
+    schema
+}
+
+fn json_body(schema: &str) -> Value {
+    json!({
+        "required": true,
+        "content": { "application/json": { "schema": schema_ref(schema) } },
+    })
+}
+
+fn responses(entries: &[(&str, &str, Option<&str>)]) -> Value {
+    let mut responses = Map::new();
+    for (status, description, schema) in entries {
+        let mut response = json!({ "description": description });
+        if let Some(schema) = schema {
+            response["content"] = json!({ "application/json": { "schema": schema_ref(schema) } });
+        }
+        responses.insert(status.to_string(), response);
+    }
+    Value::Object(responses)
+}
+
+fn path_param(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "required": true,
+        "description": description,
+        "schema": { "type": "string" },
+    })
+}
+
+fn query_param(name: &str, description: &str, schema: Value) -> Value {
+    json!({
+        "name": name,
+        "in": "query",
+        "required": false,
+        "description": description,
+        "schema": schema,
+    })
+}
+
+/// Endpoint parameters followed by the shared `limit` / `offset` / `cursor` ones
+fn paged(mut params: Vec<Value>) -> Vec<Value> {
+    params.extend([
+        query_param("limit", "Page size (default 50, max 500)", json!({ "type": "integer", "minimum": 1 })),
+        query_param("offset", "Items to skip", json!({ "type": "integer", "minimum": 0 })),
+        query_param("cursor", "`next_cursor` from the previous page; overrides `offset`", json!({ "type": "string" })),
+    ]);
+    params
+}
+
+/// Search filters shared by the stack listings
+fn stack_filter_params(mut params: Vec<Value>) -> Vec<Value> {
+    params.splice(0..0, [
+        query_param("search", "Substring of the name or description", json!({ "type": "string" })),
+        query_param("tags", "Comma-separated tags that must all be present", json!({ "type": "string" })),
+        query_param("author", "Only stacks by this user id", json!({ "type": "string" })),
+    ]);
+    params
+}
+
+fn oauth_provider_param() -> Value {
+    json!({
+        "name": "provider",
+        "in": "path",
+        "required": true,
+        "schema": { "type": "string", "enum": ["google", "github", "microsoft"] },
+    })
+}
+
+fn range_param() -> Value {
+    query_param(
+        "range",
+        "Number of days to cover, e.g. `30d` (max 365)",
+        json!({ "type": "string", "example": "30d" }),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collect_refs(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                if let Some(Value::String(r)) = map.get("$ref") {
+                    refs.push(r.clone());
+                }
+                map.values().for_each(|v| collect_refs(v, refs));
+            }
+            Value::Array(items) => items.iter().for_each(|v| collect_refs(v, refs)),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_all_schema_refs_resolve() {
+        let spec = openapi_spec();
+        let schemas = spec["components"]["schemas"].as_object().unwrap();
+
+        let mut refs = Vec::new();
+        collect_refs(&spec, &mut refs);
+        assert!(!refs.is_empty());
+
+        for r in refs {
+            let name = r.strip_prefix("#/components/schemas/").unwrap();
+            assert!(schemas.contains_key(name), "unresolved schema reference {}", r);
+        }
+    }
+
+    #[test]
+    fn test_swagger_ui_loads_only_local_assets() {
+        assert!(!SWAGGER_UI_HTML.contains("://"));
+        for file in SWAGGER_UI_ASSETS {
+            assert!(SWAGGER_UI_HTML.contains(&format!("/api/docs/assets/{}", file)));
+        }
+    }
+
+    #[test]
+    fn test_only_management_paths() {
+        let spec = openapi_spec();
+        let paths = spec["paths"].as_object().unwrap();
+
+        assert!(paths.contains_key("/api/repositories"));
+        assert!(paths.keys().all(|p| p.starts_with("/api/") || p.starts_with("/auth/")));
+        // Path templates use OpenAPI braces, not axum's `:param`
+        assert!(paths.keys().all(|p| !p.contains(':')));
+    }
+}
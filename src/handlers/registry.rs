@@ -1,16 +1,26 @@
 use crate::{
+    auth::{
+        challenge,
+        middleware::{authorize_repository, AuthenticatedUser},
+    },
+    config::{BlobVerification, CacheConfig},
     error::{Error, Result},
+    performance::{streaming, ExistenceCache, ExistenceInfo, OperationClass},
     server::AppState,
     storage::Storage,
+    storage_fallback,
+    storage_pressure,
     types::*,
-    utils::{validate_repository_name, validate_tag_name, validate_digest, sha256_digest},
+    uploads::{check_chunk_range, declared_length, upload_headers, UploadProgress, UploadStore},
+    utils::{validate_repository_name, validate_tag_name, validate_digest, parse_content_range},
     database::queries::*,
+    websocket::{Notification, NotificationSeverity},
 };
 use axum::{
     extract::{Path, State, Query, Request},
     response::{IntoResponse, Response},
     body::Body,
-    http::{StatusCode, HeaderMap, header},
+    http::{StatusCode, HeaderMap, HeaderValue, Method, Uri, header},
     Json,
 };
 use serde_json::json;
@@ -19,10 +29,20 @@ use uuid::Uuid;
 use tokio::io::AsyncReadExt;
 
 /// Docker Registry v2 API root endpoint
-/// Returns API version information
-pub async fn root() -> Result<impl IntoResponse> {
+/// Returns API version information (GET and HEAD). Unauthenticated callers get a 401
+/// challenge, which is how `docker login` finds out how to authenticate.
+pub async fn root(user: Option<AuthenticatedUser>) -> Result<Response> {
+    if user.is_none() {
+        return Ok((
+            StatusCode::UNAUTHORIZED,
+            registry_error_body("UNAUTHORIZED", "authentication required", json!(null)),
+        )
+            .into_response());
+    }
+
     Ok((
         StatusCode::OK,
+        [(registry_headers::API_VERSION, registry_headers::API_VERSION_VALUE)],
         Json(json!({
             "registry": {
                 "version": "2.0",
@@ -31,32 +51,201 @@ pub async fn root() -> Result<impl IntoResponse> {
                 "build": env!("CARGO_PKG_VERSION")
             }
         }))
-    ))
+    )
+        .into_response())
+}
+
+/// Add the `Docker-Distribution-Api-Version` header to every registry response
+pub async fn api_version_header(mut response: Response) -> Response {
+    response.headers_mut().insert(
+        registry_headers::API_VERSION,
+        HeaderValue::from_static(registry_headers::API_VERSION_VALUE),
+    );
+    response
+}
+
+/// Attach `WWW-Authenticate` challenges to registry 401s, per `auth.challenge`
+pub async fn auth_challenge(
+    State(state): State<AppState>,
+    method: Method,
+    uri: Uri,
+    headers: HeaderMap,
+    mut response: Response,
+) -> Response {
+    if response.status() != StatusCode::UNAUTHORIZED || response.headers().contains_key(header::WWW_AUTHENTICATE) {
+        return response;
+    }
+
+    let config = &state.config.auth.challenge;
+    let realm = challenge::realm(config, &headers);
+    let scope = challenge::scope(&method, uri.path());
+    for value in challenge::challenges(config, realm.as_deref(), scope.as_deref()) {
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            response.headers_mut().append(header::WWW_AUTHENTICATE, value);
+        }
+    }
+    response
+}
+
+/// Registry-format error body: `{"errors":[{code, message, detail}]}`
+pub(crate) fn registry_error_body(code: &str, message: &str, detail: serde_json::Value) -> Json<serde_json::Value> {
+    Json(json!({
+        "errors": [{
+            "code": code,
+            "message": message,
+            "detail": detail
+        }]
+    }))
+}
+
+/// Add `Cache-Control` and `ETag` for content addressed by digest or by tag. Digest content
+/// never changes, so caches may keep it; tags move, so caches must revalidate them. The ETag is
+/// the content digest either way, which lets a revalidated tag answer `304` while it hasn't moved.
+pub(crate) fn cache_headers(config: &CacheConfig, headers: &mut HeaderMap, digest: &str, by_digest: bool, authenticated: bool) {
+    if let Ok(etag) = HeaderValue::from_str(&format!("\"{}\"", digest)) {
+        headers.insert(header::ETAG, etag);
+    }
+
+    let policy = if by_digest { &config.digest_cache_control } else { &config.tag_cache_control };
+    // A response to credentials may be private content, which shared caches mustn't store
+    let policy = if authenticated {
+        policy
+            .split(',')
+            .map(|d| if d.trim().eq_ignore_ascii_case("public") { "private" } else { d.trim() })
+            .collect::<Vec<_>>()
+            .join(", ")
+    } else {
+        policy.clone()
+    };
+    if policy.is_empty() {
+        return;
+    }
+    if let Ok(value) = HeaderValue::from_str(&policy) {
+        headers.insert(header::CACHE_CONTROL, value);
+    }
+}
+
+/// Whether the request's `If-None-Match` already names the content with this digest
+pub(crate) fn etag_matches(request: &HeaderMap, digest: &str) -> bool {
+    request
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/").trim_matches('"') == digest)
+}
+
+/// Fallback for unmatched routes: registry error envelope under `/v2/`, plain 404 elsewhere
+pub async fn fallback(uri: Uri) -> Response {
+    if uri.path() == "/v2" || uri.path().starts_with("/v2/") {
+        (
+            StatusCode::NOT_FOUND,
+            [(registry_headers::API_VERSION, registry_headers::API_VERSION_VALUE)],
+            registry_error_body(
+                "UNSUPPORTED",
+                "The operation is unsupported",
+                json!({ "path": uri.path() }),
+            ),
+        )
+            .into_response()
+    } else {
+        StatusCode::NOT_FOUND.into_response()
+    }
+}
+
+/// Give 405 responses on registry routes the registry error envelope, keeping the `Allow` header
+pub async fn method_not_allowed_envelope(method: Method, uri: Uri, response: Response) -> Response {
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return response;
+    }
+
+    let allow = response.headers().get(header::ALLOW).cloned();
+    let mut rewritten = (
+        StatusCode::METHOD_NOT_ALLOWED,
+        registry_error_body(
+            "UNSUPPORTED",
+            "The operation is unsupported",
+            json!({ "method": method.as_str(), "path": uri.path() }),
+        ),
+    )
+        .into_response();
+    if let Some(allow) = allow {
+        rewritten.headers_mut().insert(header::ALLOW, allow);
+    }
+    rewritten
 }
 
 /// Get blob by digest
 pub async fn get_blob(
     State(state): State<AppState>,
     Path((name, digest)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
+    user: Option<AuthenticatedUser>,
+    request_headers: HeaderMap,
+) -> Result<Response> {
     // Validate inputs
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
     validate_digest(&digest)?;
 
-    // Get blob data from storage
-    let blob_data = state.storage.get_blob(&digest).await
-        .map_err(|e| Error::Storage { message: e.to_string() })?;
+    // A client revalidating a blob it holds only needs to know the repository still has it;
+    // anything else, including blobs only the pull-through cache has, takes the full path
+    let recorded = etag_matches(&request_headers, &digest)
+        && match get_repository_by_name(&state, &name).await {
+            Ok(repo) => get_blob_by_digest(&state, &repo.id, &digest).await.is_ok(),
+            Err(_) => false,
+        };
+    if recorded {
+        let mut headers = HeaderMap::new();
+        headers.insert(registry_headers::CONTENT_DIGEST, digest.parse().unwrap());
+        cache_headers(&state.config.cache, &mut headers, &digest, true, user.is_some());
+        return Ok((StatusCode::NOT_MODIFIED, headers).into_response());
+    }
+
+    let _permit = state.performance.acquire_operation(OperationClass::Pull).await?;
+
+    // Get blob data from storage; a rollout pulling one layer from many nodes reads it once
+    let mut blob_data = state.performance.blob_reads
+        .get_or_read(&digest, || async {
+            let read = || async {
+                storage_fallback::get_blob(&state.storage, state.storage_fallback.as_deref(), &digest)
+                    .await
+                    .map(|data| data.map(bytes::Bytes::from))
+                    .map_err(|e| Error::Storage { message: e.to_string() })
+            };
+            match &state.blob_cache {
+                Some(cache) => cache.get_or_fetch(&digest, read).await,
+                None => read().await,
+            }
+        })
+        .await?;
+    if blob_data.is_none() {
+        blob_data = state.proxy.fetch_blob(&state, &name, &digest).await?.map(bytes::Bytes::from);
+    }
     
     // Create response headers
     let mut headers = HeaderMap::new();
     headers.insert("content-type", "application/octet-stream".parse().unwrap());
-    headers.insert("docker-content-digest", digest.parse().unwrap());
+    headers.insert(registry_headers::CONTENT_DIGEST, digest.parse().unwrap());
+    cache_headers(&state.config.cache, &mut headers, &digest, true, user.is_some());
     
     // Return the blob data if found
     match blob_data {
         Some(data) => {
             headers.insert("content-length", data.len().to_string().parse().unwrap());
-            Ok((StatusCode::OK, headers, data))
+            if let Ok(repo) = get_repository_by_name(&state, &name).await {
+                if let Err(e) = record_usage(&state, &repo.id, UsageEvent::BytesPulled(data.len() as i64)).await {
+                    tracing::warn!("Failed to record blob pull for {}: {}", name, e);
+                }
+            }
+            let throttles = state.performance.download_limits.throttles(user.is_some());
+            let body = if throttles.is_empty() {
+                Body::from(data)
+            } else {
+                let chunks = futures::stream::once(async move { Ok(data) });
+                Body::from_stream(streaming::throttle(chunks, throttles))
+            };
+            Ok((StatusCode::OK, headers, body).into_response())
         }
         None => Err(Error::NotFound {
             resource: format!("blob {}", digest),
@@ -64,20 +253,107 @@ pub async fn get_blob(
     }
 }
 
+/// Most digests accepted by one batch existence check
+const MAX_BLOB_CHECK_DIGESTS: usize = 1000;
+
+/// Report which of a list of blobs a repository already has (`POST .../blobs:check`).
+/// One query answers the whole batch; rows whose object is gone from storage count as missing.
+pub async fn check_blobs(
+    State(state): State<AppState>,
+    Path((name, action)): Path<(String, String)>,
+    user: Option<AuthenticatedUser>,
+    Json(request): Json<BlobCheckRequest>,
+) -> Result<Json<BlobCheckResponse>> {
+    // The router captures everything after `blobs` in the segment
+    if action != ":check" {
+        return Err(Error::not_found(format!("No endpoint 'blobs{}'", action)));
+    }
+    validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
+
+    let mut digests: Vec<String> = Vec::with_capacity(request.digests.len());
+    for digest in request.digests {
+        validate_digest(&digest)?;
+        if !digests.contains(&digest) {
+            digests.push(digest);
+        }
+    }
+    if digests.len() > MAX_BLOB_CHECK_DIGESTS {
+        return Err(Error::bad_request(format!(
+            "At most {} digests can be checked at once",
+            MAX_BLOB_CHECK_DIGESTS
+        )));
+    }
+
+    let repo = get_repository_by_name(&state, &name).await?;
+    let known = if digests.is_empty() {
+        HashMap::new()
+    } else {
+        let placeholders: Vec<String> = (2..digests.len() + 2).map(|i| format!("${}", i)).collect();
+        let sql = format!(
+            r#"
+            SELECT b.digest, b.size
+            FROM blobs b
+            JOIN repository_blobs rb ON b.id = rb.blob_id
+            WHERE rb.repository_id = $1 AND b.digest IN ({})
+            "#,
+            placeholders.join(", ")
+        );
+        let mut query = sqlx::query_as::<_, (String, i64)>(&sql).bind(repo.id);
+        for digest in &digests {
+            query = query.bind(digest);
+        }
+        query.fetch_all(&state.database.pool).await?.into_iter().collect::<HashMap<_, _>>()
+    };
+
+    let mut response = BlobCheckResponse { present: Vec::new(), missing: Vec::new() };
+    for digest in digests {
+        let size = match known.get(&digest) {
+            Some(size) => *size,
+            None => {
+                response.missing.push(digest);
+                continue;
+            }
+        };
+        if storage_fallback::blob_exists(&state.storage, state.storage_fallback.as_deref(), &digest).await? {
+            response.present.push(PresentBlob { digest, size });
+        } else {
+            tracing::warn!("Blob {} is recorded for {} but missing from storage", digest, name);
+            response.missing.push(digest);
+        }
+    }
+
+    Ok(Json(response))
+}
+
 /// Head blob by digest (same as GET but without body)
 pub async fn head_blob(
     State(state): State<AppState>,
     Path((name, digest)): Path<(String, String)>,
+    user: Option<AuthenticatedUser>,
 ) -> Result<impl IntoResponse> {
     // Validate inputs
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "pull").await?;
     validate_digest(&digest)?;
 
-    // Check if repository exists
-    let repo = get_repository_by_name(&state, &name).await?;
-    
-    // Check if blob exists for this repository
-    let blob = get_blob_by_digest(&state, &repo.id, &digest).await?;
+    // Check if blob exists for this repository, caching misses as well as hits
+    let key = ExistenceCache::blob_key(&name, &digest);
+    let blob = state.performance.existence_cache
+        .get_or_lookup(&key, || async {
+            let repo = get_repository_by_name(&state, &name).await?;
+            match get_blob_by_digest(&state, &repo.id, &digest).await {
+                Ok(blob) => Ok(Some(ExistenceInfo {
+                    digest: blob.digest,
+                    media_type: blob.media_type,
+                    size: blob.size,
+                })),
+                Err(Error::NotFound { .. }) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Blob '{}' not found", digest)))?;
     
     let mut headers = HeaderMap::new();
     headers.insert(
@@ -89,9 +365,10 @@ pub async fn head_blob(
         blob.size.to_string().parse().unwrap()
     );
     headers.insert(
-        "Docker-Content-Digest",
+        registry_headers::CONTENT_DIGEST,
         digest.parse().unwrap()
     );
+    cache_headers(&state.config.cache, &mut headers, &digest, true, user.is_some());
 
     Ok((StatusCode::OK, headers))
 }
@@ -100,60 +377,84 @@ pub async fn head_blob(
 pub async fn delete_blob(
     State(state): State<AppState>,
     Path((name, digest)): Path<(String, String)>,
+    user: Option<AuthenticatedUser>,
 ) -> Result<impl IntoResponse> {
     // Validate inputs
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "delete").await?;
     validate_digest(&digest)?;
 
     // Check if repository exists
     let repo = get_repository_by_name(&state, &name).await?;
+    ensure_not_deletion_protected(&state, &repo.id, &name).await?;
     
     // Check if blob exists for this repository
     let blob = get_blob_by_digest(&state, &repo.id, &digest).await?;
     
-    // Remove blob from storage
-    state.storage.delete_blob(&digest).await?;
-    
-    // Remove blob from database
-    sqlx::query("DELETE FROM blobs WHERE id = $1")
-        .bind(&blob.id)
-        .execute(&state.database.pool)
-        .await?;
-    
-    // Remove repository-blob relationship
+    // Only this repository's link goes; other repositories may share the content, so the
+    // stored object and its row are left to garbage collection
     sqlx::query("DELETE FROM repository_blobs WHERE repository_id = $1 AND blob_id = $2")
         .bind(&repo.id)
         .bind(&blob.id)
         .execute(&state.database.pool)
         .await?;
 
+    state.performance.existence_cache.invalidate(&ExistenceCache::blob_key(&name, &digest));
+
     Ok(StatusCode::ACCEPTED)
 }
 
-/// Initiate blob upload
+/// Initiate blob upload, or mount a blob from another repository when `mount` and `from` are given
 pub async fn initiate_blob_upload(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<impl IntoResponse> {
+    user: Option<AuthenticatedUser>,
+    Query(params): Query<HashMap<String, String>>,
+    headers: HeaderMap,
+) -> Result<Response> {
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "push").await?;
+    let total_size = declared_length(&headers)?;
+    check_blob_size(&state, total_size.unwrap_or(0))?;
+
+    // A mounted blob is linked, not copied, so it needs no free space
+    let mount = match (params.get("mount"), params.get("from")) {
+        (Some(digest), Some(from)) => mountable_blob(&state, &user, from, digest).await.map(|blob| (blob, from)),
+        _ => None,
+    };
+    if mount.is_none() {
+        storage_pressure::ensure_capacity(&state, 0).await?;
+    }
+
+    // Created only once the mount or upload is known to go ahead
+    let repo = get_or_create_repository(&state, &name, &user).await?;
+
+    if let Some((blob, from)) = mount {
+        record_blob(&state, &repo, &blob.digest, blob.size).await?;
+        state.performance.existence_cache.invalidate(&ExistenceCache::blob_key(&name, &blob.digest));
+        tracing::debug!("Mounted {} from {} into {}", blob.digest, from, name);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(registry_headers::CONTENT_DIGEST, blob.digest.parse().unwrap());
+        headers.insert(header::LOCATION, format!("/v2/{}/blobs/{}", name, blob.digest).parse().unwrap());
+        return Ok((StatusCode::CREATED, headers).into_response());
+    }
 
-    // Get or create repository
-    let repo = get_or_create_repository(&state, &name).await?;
-    
     // Create upload session
     let upload_uuid = Uuid::new_v4();
     let storage_path = format!("uploads/{}", upload_uuid);
     
     sqlx::query(
         r#"
-        INSERT INTO upload_sessions (id, uuid, repository_id, uploaded_size, storage_path, created_at, updated_at, expires_at)
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        INSERT INTO upload_sessions (id, uuid, repository_id, uploaded_size, total_size, storage_path, created_at, updated_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
         "#
     )
     .bind(Uuid::new_v4())
     .bind(upload_uuid)
     .bind(&repo.id)
     .bind(0i64)
+    .bind(total_size.map(|size| size as i64))
     .bind(&storage_path)
     .bind(chrono::Utc::now())
     .bind(chrono::Utc::now())
@@ -161,31 +462,34 @@ pub async fn initiate_blob_upload(
     .execute(&state.database.pool)
     .await?;
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Docker-Upload-UUID",
-        upload_uuid.to_string().parse().unwrap()
-    );
-    headers.insert(
-        header::LOCATION,
-        format!("/v2/{}/blobs/uploads/{}", name, upload_uuid).parse().unwrap()
-    );
-    headers.insert(
-        "Range",
-        "0-0".parse().unwrap()
-    );
+    Ok((StatusCode::ACCEPTED, upload_headers(&name, &upload_uuid.to_string(), 0)).into_response())
+}
 
-    Ok((StatusCode::ACCEPTED, headers))
+/// The blob a `mount`/`from` pair names, if `from` holds it and the caller may pull from there.
+/// Anything else, including malformed parameters, falls back to a regular upload as the spec asks.
+async fn mountable_blob(state: &AppState, user: &Option<AuthenticatedUser>, from: &str, digest: &str) -> Option<Blob> {
+    if validate_repository_name(from).is_err()
+        || validate_digest(digest).is_err()
+    {
+        return None;
+    }
+    if authorize_repository(state, user, from, "pull").await.is_err() {
+        return None;
+    }
+    let source = get_repository_by_name(state, from).await.ok()?;
+    get_blob_by_digest(state, &source.id, digest).await.ok()
 }
 
 /// Complete blob upload
 pub async fn complete_blob_upload(
     State(state): State<AppState>,
     Path((name, uuid)): Path<(String, String)>,
+    user: Option<AuthenticatedUser>,
     Query(params): Query<HashMap<String, String>>,
     request: Request<Body>,
 ) -> Result<impl IntoResponse> {
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "push").await?;
     
     let upload_uuid = Uuid::parse_str(&uuid)
         .map_err(|_| Error::bad_request("Invalid upload UUID"))?;
@@ -195,169 +499,489 @@ pub async fn complete_blob_upload(
     validate_digest(expected_digest)?;
 
     // Get upload session
-    let upload_session = get_upload_session(&state, upload_uuid).await?;
-    
-    // Read request body
-    let body_bytes = axum::body::to_bytes(request.into_body(), usize::MAX).await
-        .map_err(|_| Error::bad_request("Failed to read request body"))?;
-    
-    // Calculate digest
-    let calculated_digest = sha256_digest(&body_bytes);
+    let upload_session = repository_upload_session(&state, &name, upload_uuid).await?;
+
+    let _permit = state.performance.acquire_operation(OperationClass::BlobUpload).await?;
     
-    if &calculated_digest != expected_digest {
+    // Append the final chunk (possibly empty) to whatever was already PATCHed
+    let uploads = upload_store(&state);
+    let persisted = uploads.persisted_size(&upload_session.storage_path).await?;
+    let final_chunk = read_chunk(&state, request.into_body(), persisted).await?;
+    // The final chunk lands in the staging file, then the whole blob is copied into storage
+    let blob_size = persisted + final_chunk.len() as u64;
+    storage_pressure::ensure_capacity(&state, final_chunk.len() as u64 + blob_size).await?;
+    uploads.append_chunk(&upload_session.storage_path, persisted, &final_chunk).await?;
+
+    // Deferred verification only covers content the registry doesn't hold yet. It stays in the
+    // upload area until it hashes to its digest, so unverified bytes never replace a stored blob.
+    let stored = stored_blob_id(&state, expected_digest).await?.is_some();
+    if state.config.storage.blob_verification == BlobVerification::Deferred && !stored {
+        // Dropping the session stops further chunks landing in the staged file
+        cleanup_upload_session(&state, upload_uuid).await?;
+        state.websocket.upload_finished(&upload_uuid);
+        // Manifests pushed before the check completes may reference it
+        state.pending_blobs.begin(upload_session.repository_id, expected_digest);
+        tokio::spawn(verify_blob_deferred(
+            state.clone(),
+            DeferredBlob {
+                repository: name.clone(),
+                repository_id: upload_session.repository_id,
+                digest: expected_digest.clone(),
+                staging_path: upload_session.storage_path,
+                size: blob_size,
+                pushed_by: user.map(|u| u.id),
+            },
+        ));
+        return Ok((StatusCode::CREATED, blob_created_headers(&name, expected_digest)));
+    }
+
+    // Hashed from disk; only content the registry doesn't hold yet is read into memory,
+    // since storage backends take a blob whole
+    let calculated = uploads.digest(&upload_session.storage_path).await?;
+    if calculated != *expected_digest {
         return Err(Error::bad_request(format!(
-            "Digest mismatch: expected {}, got {}", 
-            expected_digest, calculated_digest
+            "Digest mismatch: expected {}, got {}",
+            expected_digest, calculated
         )));
     }
-    
-    // Store blob
-    state.storage.put_blob(expected_digest, &body_bytes).await?;
-    
+    let data = if stored { None } else { Some(uploads.read(&upload_session.storage_path).await?) };
+    store_blob(&state, &name, &upload_session.repository_id, expected_digest, blob_size, data.as_deref()).await?;
+
+    // Clean up upload session
+    uploads.remove(&upload_session.storage_path).await?;
+    cleanup_upload_session(&state, upload_uuid).await?;
+    state.websocket.upload_finished(&upload_uuid);
+
+    Ok((StatusCode::CREATED, blob_created_headers(&name, expected_digest)))
+}
+
+fn blob_created_headers(name: &str, digest: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        registry_headers::CONTENT_DIGEST,
+        digest.parse().unwrap()
+    );
+    headers.insert(
+        header::LOCATION,
+        format!("/v2/{}/blobs/{}", name, digest).parse().unwrap()
+    );
+    headers
+}
+
+/// Id of the stored blob with this digest, in any repository
+async fn stored_blob_id(state: &AppState, digest: &str) -> Result<Option<Uuid>> {
+    Ok(sqlx::query_scalar("SELECT id FROM blobs WHERE digest = $1")
+        .bind(digest)
+        .fetch_optional(&state.database.pool)
+        .await?)
+}
+
+/// Record verified blob content and link it to the repository. Bytes are only written when
+/// given, for a new digest; a digest already stored holds the same content.
+async fn store_blob(
+    state: &AppState,
+    name: &str,
+    repository_id: &Uuid,
+    digest: &str,
+    size: u64,
+    data: Option<&[u8]>,
+) -> Result<()> {
+    if let Some(data) = data {
+        state.storage.put_blob(digest, data).await?;
+    }
+
     // Create blob record
-    let blob_id = Uuid::new_v4();
     sqlx::query(
         r#"
         INSERT INTO blobs (id, digest, media_type, size, storage_path, created_at)
         VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT(digest) DO NOTHING
         "#
     )
-    .bind(blob_id)
-    .bind(expected_digest)
+    .bind(Uuid::new_v4())
+    .bind(digest)
     .bind("application/octet-stream") // Default media type
-    .bind(body_bytes.len() as i64)
-    .bind(format!("blobs/{}", expected_digest))
+    .bind(size as i64)
+    .bind(format!("blobs/{}", digest))
     .bind(chrono::Utc::now())
     .execute(&state.database.pool)
     .await?;
-    
+    let blob_id = stored_blob_id(state, digest)
+        .await?
+        .ok_or_else(|| Error::internal(format!("Blob {} vanished while being linked", digest)))?;
+
     // Link blob to repository
     sqlx::query(
-        "INSERT INTO repository_blobs (id, repository_id, blob_id, created_at) VALUES ($1, $2, $3, $4)"
+        r#"
+        INSERT INTO repository_blobs (id, repository_id, blob_id, created_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT(repository_id, blob_id) DO NOTHING
+        "#
     )
     .bind(Uuid::new_v4())
-    .bind(&upload_session.repository_id)
+    .bind(repository_id)
     .bind(blob_id)
     .bind(chrono::Utc::now())
     .execute(&state.database.pool)
     .await?;
-    
-    // Clean up upload session
-    cleanup_upload_session(&state, upload_uuid).await?;
 
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Docker-Content-Digest",
-        expected_digest.parse().unwrap()
-    );
-    headers.insert(
-        header::LOCATION,
-        format!("/v2/{}/blobs/{}", name, expected_digest).parse().unwrap()
+    // Clear any cached "not found" so the client's follow-up HEAD sees the blob
+    state.performance.existence_cache.invalidate(&ExistenceCache::blob_key(name, digest));
+
+    if let Err(e) = record_usage(state, repository_id, UsageEvent::BytesPushed(size as i64)).await {
+        tracing::warn!("Failed to record blob push for {}: {}", name, e);
+    }
+    Ok(())
+}
+
+/// An upload acknowledged before its digest was checked
+struct DeferredBlob {
+    repository: String,
+    repository_id: Uuid,
+    digest: String,
+    /// Upload-area file holding the bytes until they are verified
+    staging_path: String,
+    size: u64,
+    pushed_by: Option<String>,
+}
+
+/// Background digest check for `blob_verification = "deferred"`.
+/// Content that matches its digest is moved into storage and linked to the repository, along
+/// with any manifests pushed meanwhile; anything else is discarded from the upload area, and
+/// admins (plus the pusher) are notified.
+async fn verify_blob_deferred(state: AppState, blob: DeferredBlob) {
+    let DeferredBlob { repository, repository_id, digest, staging_path, size, pushed_by } = blob;
+    let uploads = upload_store(&state);
+    let calculated = match uploads.digest(&staging_path).await {
+        Ok(calculated) => calculated,
+        Err(e) => {
+            tracing::error!("Deferred verification of {} did not run: {}", digest, e);
+            state.pending_blobs.finish(repository_id, &digest, false);
+            return;
+        }
+    };
+
+    let verified = calculated == digest;
+    let mut stored = false;
+    if verified {
+        let written = match uploads.read(&staging_path).await {
+            Ok(data) => store_blob(&state, &repository, &repository_id, &digest, size, Some(&data)).await,
+            Err(e) => Err(e),
+        };
+        match written {
+            Ok(()) => stored = true,
+            Err(e) => tracing::error!("Failed to store verified blob {} in {}: {}", digest, repository, e),
+        }
+    }
+    for manifest_id in state.pending_blobs.finish(repository_id, &digest, stored) {
+        let linked = if stored {
+            link_manifest_to_blob(&state, manifest_id, &digest).await
+        } else {
+            Ok(false)
+        };
+        match linked {
+            Ok(true) => {}
+            Ok(false) => tracing::warn!("Manifest {} in {} references discarded blob {}", manifest_id, repository, digest),
+            Err(e) => tracing::error!("Failed to link manifest {} to blob {}: {}", manifest_id, digest, e),
+        }
+    }
+    if let Err(e) = uploads.remove(&staging_path).await {
+        tracing::warn!("Failed to remove staged upload {}: {}", staging_path, e);
+    }
+    if verified {
+        return;
+    }
+
+    tracing::warn!(
+        "Deferred verification failed for {} in {}: content hashes to {}; discarding it",
+        digest, repository, calculated
     );
+    if let Err(e) = record_audit(
+        &state,
+        "system",
+        "blob.quarantine",
+        &digest,
+        Some(json!({ "repository": repository, "calculated_digest": calculated })),
+    )
+    .await
+    {
+        tracing::warn!("Failed to record quarantine of {}: {}", digest, e);
+    }
+
+    let mut recipients: Vec<String> = sqlx::query_scalar("SELECT id FROM users WHERE is_admin = TRUE AND is_active = TRUE")
+        .fetch_all(&state.database.pool)
+        .await
+        .unwrap_or_default();
+    if let Some(user_id) = pushed_by {
+        if !recipients.contains(&user_id) {
+            recipients.push(user_id);
+        }
+    }
 
-    Ok((StatusCode::CREATED, headers))
+    for user_id in recipients {
+        let notification = Notification {
+            id: Uuid::new_v4().to_string(),
+            title: "Blob failed verification".to_string(),
+            message: format!(
+                "Blob {} pushed to {} did not match its digest and was discarded",
+                digest, repository
+            ),
+            severity: NotificationSeverity::Error,
+            timestamp: chrono::Utc::now(),
+            read: false,
+        };
+        state.websocket.broadcast_notification(user_id, notification).await;
+    }
+}
+
+/// Staging area for in-progress uploads under the storage root
+fn upload_store(state: &AppState) -> UploadStore {
+    UploadStore::new(&state.config.storage.path)
+}
+
+/// Largest blob that may be pushed: the tighter of `registry.max_layer_size` and
+/// `storage.max_upload_size`
+fn max_blob_size(state: &AppState) -> u64 {
+    state.config.registry.max_layer_size.min(state.config.storage.max_upload_size)
+}
+
+fn check_blob_size(state: &AppState, size: u64) -> Result<()> {
+    let max_size = max_blob_size(state);
+    if size > max_size {
+        return Err(Error::bad_request(format!("Blob of {} bytes exceeds the {} byte upload limit", size, max_size)));
+    }
+    Ok(())
+}
+
+/// Read a chunk that continues an upload holding `offset` bytes, refusing bodies that would
+/// take the blob past its size limit
+async fn read_chunk(state: &AppState, body: Body, offset: u64) -> Result<axum::body::Bytes> {
+    let max_size = max_blob_size(state);
+    check_blob_size(state, offset)?;
+    let remaining = usize::try_from(max_size - offset).unwrap_or(usize::MAX);
+    axum::body::to_bytes(body, remaining).await.map_err(|_| {
+        Error::bad_request(format!("Chunk is unreadable or takes the blob past the {} byte upload limit", max_size))
+    })
+}
+
+/// Upload session `upload_uuid`, which must have been started in repository `name`;
+/// sessions of other repositories are reported as unknown
+async fn repository_upload_session(state: &AppState, name: &str, upload_uuid: Uuid) -> Result<UploadSession> {
+    let upload_session = get_upload_session(state, upload_uuid).await?;
+    let repo = get_repository_by_name(state, name).await?;
+    if upload_session.repository_id != repo.id {
+        return Err(Error::not_found("Upload session not found or expired"));
+    }
+    Ok(upload_session)
 }
 
 /// Upload blob chunk (PATCH)
+/// Chunks are fsynced before responding so the reported Range survives restarts
 pub async fn upload_blob_chunk(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Path((name, uuid)): Path<(String, String)>,
-) -> Result<StatusCode> {
-    // TODO: Implement chunked upload
-    tracing::warn!("Chunked upload not yet implemented for {} upload {}", name, uuid);
-    Err(Error::registry("Chunked upload not yet implemented".to_string()))
+    user: Option<AuthenticatedUser>,
+    request: Request<Body>,
+) -> Result<Response> {
+    validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "push").await?;
+
+    let upload_uuid = Uuid::parse_str(&uuid)
+        .map_err(|_| Error::bad_request("Invalid upload UUID"))?;
+
+    let upload_session = repository_upload_session(&state, &name, upload_uuid).await?;
+    let uploads = upload_store(&state);
+
+    // The length may be declared on any chunk, but can't change once set
+    let total_size = match (upload_session.total_size, declared_length(request.headers())?) {
+        (Some(known), Some(declared)) if known as u64 != declared => {
+            return Err(Error::bad_request(format!(
+                "Upload-Length {} conflicts with the declared length {}",
+                declared, known
+            )));
+        }
+        (None, Some(declared)) => {
+            check_blob_size(&state, declared)?;
+            set_upload_total_size(&state, upload_uuid, declared as i64).await?;
+            Some(declared)
+        }
+        (known, _) => known.map(|size| size as u64),
+    };
+
+    // Clients may omit Content-Range for streamed uploads; the chunk then follows the persisted bytes
+    let range = request.headers().get(header::CONTENT_RANGE)
+        .and_then(|v| v.to_str().ok())
+        .map(parse_content_range)
+        .transpose()?;
+    let offset = match range {
+        Some((start, _)) => start,
+        None => uploads.persisted_size(&upload_session.storage_path).await?,
+    };
+
+    let _permit = state.performance.acquire_operation(OperationClass::BlobUpload).await?;
+
+    let chunk = read_chunk(&state, request.into_body(), offset).await?;
+
+    // A start other than the persisted size is rejected by append_chunk with the same 416
+    if let Some((start, end)) = range {
+        if let Err(e) = check_chunk_range(start, end, chunk.len()) {
+            return range_rejection(&uploads, &upload_session.storage_path, &name, &upload_uuid.to_string(), e).await;
+        }
+    }
+
+    if let Some(total_size) = total_size {
+        if offset + chunk.len() as u64 > total_size {
+            return Err(Error::range_invalid(format!(
+                "Chunk ends at {} but the declared length is {}",
+                offset + chunk.len() as u64,
+                total_size
+            )));
+        }
+    }
+
+    storage_pressure::ensure_capacity(&state, chunk.len() as u64).await?;
+    let uploaded_size = match uploads.append_chunk(&upload_session.storage_path, offset, &chunk).await {
+        Ok(size) => size,
+        Err(e @ Error::RangeInvalid { .. }) => {
+            return range_rejection(&uploads, &upload_session.storage_path, &name, &upload_uuid.to_string(), e).await;
+        }
+        Err(e) => return Err(e),
+    };
+    update_upload_progress(&state, upload_uuid, uploaded_size as i64).await?;
+
+    if let Some(total_size) = total_size {
+        state.websocket.broadcast_upload_progress(UploadProgress {
+            upload_id: upload_uuid,
+            repository: name.clone(),
+            uploaded_size,
+            total_size,
+        }).await;
+    }
+
+    Ok((StatusCode::ACCEPTED, upload_headers(&name, &upload_uuid.to_string(), uploaded_size)).into_response())
+}
+
+/// 416 for a chunk that doesn't continue the upload, carrying the Location and Range the
+/// client should resume from
+async fn range_rejection(uploads: &UploadStore, storage_path: &str, name: &str, uuid: &str, error: Error) -> Result<Response> {
+    let persisted = uploads.persisted_size(storage_path).await?;
+    let mut response = error.into_response();
+    response.headers_mut().extend(upload_headers(name, uuid, persisted));
+    Ok(response)
 }
 
 /// Get upload status
 pub async fn get_upload_status(
     State(state): State<AppState>,
     Path((name, uuid)): Path<(String, String)>,
+    user: Option<AuthenticatedUser>,
 ) -> Result<impl IntoResponse> {
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "push").await?;
     
     let upload_uuid = Uuid::parse_str(&uuid)
         .map_err(|_| Error::bad_request("Invalid upload UUID"))?;
     
-    let upload_session = get_upload_session(&state, upload_uuid).await?;
-    
-    let mut headers = HeaderMap::new();
-    headers.insert(
-        "Docker-Upload-UUID",
-        upload_uuid.to_string().parse().unwrap()
-    );
-    headers.insert(
-        "Range",
-        format!("0-{}", upload_session.uploaded_size).parse().unwrap()
-    );
+    let upload_session = repository_upload_session(&state, &name, upload_uuid).await?;
 
-    Ok((StatusCode::NO_CONTENT, headers))
+    // The staged file is the source of truth; the DB counter may lag a crash
+    let persisted = upload_store(&state).persisted_size(&upload_session.storage_path).await?;
+    if persisted as i64 != upload_session.uploaded_size {
+        update_upload_progress(&state, upload_uuid, persisted as i64).await?;
+    }
+    
+    Ok((StatusCode::NO_CONTENT, upload_headers(&name, &upload_uuid.to_string(), persisted)))
 }
 
 /// Cancel upload
 pub async fn cancel_upload(
     State(state): State<AppState>,
     Path((name, uuid)): Path<(String, String)>,
+    user: Option<AuthenticatedUser>,
 ) -> Result<impl IntoResponse> {
     validate_repository_name(&name)?;
+    authorize_repository(&state, &user, &name, "push").await?;
     
     let upload_uuid = Uuid::parse_str(&uuid)
         .map_err(|_| Error::bad_request("Invalid upload UUID"))?;
     
-    cleanup_upload_session(&state, upload_uuid).await?;
+    // Unknown sessions, and sessions started in another repository, are left alone
+    if let Ok(upload_session) = repository_upload_session(&state, &name, upload_uuid).await {
+        upload_store(&state).remove(&upload_session.storage_path).await?;
+        cleanup_upload_session(&state, upload_uuid).await?;
+        state.websocket.upload_finished(&upload_uuid);
+    }
 
     Ok(StatusCode::NO_CONTENT)
 }
 
-/// Get manifest by reference
-pub async fn get_manifest(
-    State(_state): State<AppState>,
-    Path((name, reference)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
-    // TODO: Implement manifest retrieval
-    tracing::info!("Getting manifest {} for repository {}", reference, name);
-    Ok("Manifest get endpoint - not yet implemented")
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-/// Put manifest by reference
-pub async fn put_manifest(
-    State(_state): State<AppState>,
-    Path((name, reference)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
-    // TODO: Implement manifest storage
-    tracing::info!("Putting manifest {} for repository {}", reference, name);
-    Ok("Manifest put endpoint - not yet implemented")
-}
+    #[tokio::test]
+    async fn test_fallback_uses_registry_envelope_under_v2() {
+        let response = fallback(Uri::from_static("/v2/app/unknown")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
 
-/// Head manifest by reference
-pub async fn head_manifest(
-    State(_state): State<AppState>,
-    Path((name, reference)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
-    // TODO: Implement manifest head
-    tracing::info!("Head manifest {} for repository {}", reference, name);
-    Ok("Manifest head endpoint - not yet implemented")
-}
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["errors"][0]["code"], "UNSUPPORTED");
 
-/// Delete manifest by reference
-pub async fn delete_manifest(
-    State(_state): State<AppState>,
-    Path((name, reference)): Path<(String, String)>,
-) -> Result<impl IntoResponse> {
-    // TODO: Implement manifest deletion
-    tracing::info!("Deleting manifest {} for repository {}", reference, name);
-    Ok("Manifest delete endpoint - not yet implemented")
-}
+        let response = fallback(Uri::from_static("/nope")).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap().is_empty());
+    }
 
-/// List tags for repository
-pub async fn list_tags(
-    State(_state): State<AppState>,
-    Path(name): Path<String>,
-    Query(_params): Query<HashMap<String, String>>,
-) -> Result<impl IntoResponse> {
-    // TODO: Implement tag listing
-    tracing::info!("Listing tags for repository {}", name);
-    Ok(Json(json!({
-        "name": name,
-        "tags": []
-    })))
+    #[tokio::test]
+    async fn test_method_not_allowed_keeps_allow_header() {
+        let mut response = StatusCode::METHOD_NOT_ALLOWED.into_response();
+        response.headers_mut().insert(header::ALLOW, HeaderValue::from_static("GET,HEAD"));
+
+        let response = method_not_allowed_envelope(Method::POST, Uri::from_static("/v2/app/tags/list"), response).await;
+        assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+        assert_eq!(response.headers()[header::ALLOW], "GET,HEAD");
+    }
+
+    #[test]
+    fn test_cache_headers_by_reference_type() {
+        let config = CacheConfig::default();
+        let digest = format!("sha256:{}", "a".repeat(64));
+        let served = |by_digest, authenticated| {
+            let mut headers = HeaderMap::new();
+            cache_headers(&config, &mut headers, &digest, by_digest, authenticated);
+            headers
+        };
+
+        let immutable = served(true, false);
+        assert_eq!(immutable[header::CACHE_CONTROL], "public, max-age=31536000, immutable");
+        assert_eq!(immutable[header::ETAG], format!("\"{}\"", digest).as_str());
+        // Shared caches mustn't keep what was served to credentials
+        assert_eq!(served(true, true)[header::CACHE_CONTROL], "private, max-age=31536000, immutable");
+
+        let tag = served(false, true);
+        assert_eq!(tag[header::CACHE_CONTROL], "no-cache");
+        assert_eq!(tag[header::ETAG], immutable[header::ETAG]);
+
+        let config = CacheConfig { tag_cache_control: String::new(), ..CacheConfig::default() };
+        let mut headers = HeaderMap::new();
+        cache_headers(&config, &mut headers, &digest, false, false);
+        assert!(headers.get(header::CACHE_CONTROL).is_none());
+        assert!(headers.contains_key(header::ETAG));
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        let digest = format!("sha256:{}", "b".repeat(64));
+        let request = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(header::IF_NONE_MATCH, value.parse().unwrap());
+            headers
+        };
+
+        assert!(etag_matches(&request(&format!("\"{}\"", digest)), &digest));
+        assert!(etag_matches(&request(&format!("\"sha256:other\", W/\"{}\"", digest)), &digest));
+        assert!(etag_matches(&request("*"), &digest));
+        assert!(!etag_matches(&request("\"sha256:other\""), &digest));
+        assert!(!etag_matches(&HeaderMap::new(), &digest));
+    }
 }
@@ -0,0 +1,525 @@
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    error::{Error, Result},
+    server::AppState,
+    types::*,
+    utils::{validate_platform, validate_repository_name},
+    database::queries,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct ListRepositoriesQuery {
+    pub artifact_type: Option<String>,
+}
+
+/// Repository with the kinds of artifacts it holds
+#[derive(Debug, Serialize)]
+pub struct RepositorySummary {
+    #[serde(flatten)]
+    pub repository: Repository,
+    pub artifact_types: Vec<ArtifactType>,
+    /// The caller has favorited this repository
+    pub is_favorited: bool,
+}
+
+/// A favorited repository, as listed by `GET /api/me/favorites`
+#[derive(Debug, Serialize)]
+pub struct FavoriteRepository {
+    #[serde(flatten)]
+    pub repository: Repository,
+    pub favorited_at: DateTime<Utc>,
+}
+
+/// List repositories visible to the caller, optionally only those holding a given artifact type.
+/// Listing pages are cached per visibility scope until a repository or manifest changes.
+pub async fn list_repositories(
+    State(state): State<AppState>,
+    Query(query): Query<ListRepositoriesQuery>,
+    Query(page): Query<PageQuery>,
+    user: Option<AuthenticatedUser>,
+) -> Result<impl IntoResponse> {
+    let (limit, offset) = page.bounds()?;
+    let artifact_filter = query.artifact_type
+        .as_deref()
+        .map(|t| ArtifactType::parse(t).ok_or_else(|| Error::bad_request(format!("Unknown artifact type '{}'", t))))
+        .transpose()?;
+
+    let scope = format!("{}|{}+{}", catalog_scope(user.as_ref(), artifact_filter), offset, limit);
+    let body = state
+        .performance
+        .catalog_cache
+        .get_or_render(&scope, || async {
+            let repositories = visible_repositories(&state, user.as_ref(), artifact_filter).await?;
+            Ok(Bytes::from(serde_json::to_vec(&Page::from_vec(repositories, limit, offset))?))
+        })
+        .await?;
+
+    Ok(([(header::CONTENT_TYPE, "application/json")], body))
+}
+
+/// Page size for `/v2/_catalog` when the client doesn't pass `n`
+const DEFAULT_CATALOG_PAGE: usize = 100;
+const MAX_CATALOG_PAGE: usize = 1000;
+
+#[derive(Debug, Deserialize)]
+pub struct CatalogQuery {
+    pub n: Option<usize>,
+    /// Return names sorting after this one
+    pub last: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CatalogPage {
+    repositories: Vec<String>,
+}
+
+/// Registry catalog (`GET /v2/_catalog`): names of the repositories visible to the caller.
+/// Pages are cached alongside `GET /api/repositories`, so repeated crawls stay cheap.
+pub async fn catalog(
+    State(state): State<AppState>,
+    Query(query): Query<CatalogQuery>,
+    user: Option<AuthenticatedUser>,
+) -> Result<Response> {
+    let n = query.n.unwrap_or(DEFAULT_CATALOG_PAGE).clamp(1, MAX_CATALOG_PAGE);
+    let last = query.last.unwrap_or_default();
+
+    let scope = format!("v2|{}|{}+{}", catalog_scope(user.as_ref(), None), last, n);
+    let body = state
+        .performance
+        .catalog_cache
+        .get_or_render(&scope, || async {
+            let repositories = visible_repositories(&state, user.as_ref(), None)
+                .await?
+                .into_iter()
+                .map(|summary| summary.repository.name)
+                .filter(|name| *name > last)
+                .take(n)
+                .collect();
+            Ok(Bytes::from(serde_json::to_vec(&CatalogPage { repositories })?))
+        })
+        .await?;
+
+    let mut response = ([(header::CONTENT_TYPE, "application/json")], body.clone()).into_response();
+    // A full page may have more after it; point the client at the next one
+    let page: CatalogPage = serde_json::from_slice(&body)?;
+    if let Some(tail) = page.repositories.last().filter(|_| page.repositories.len() == n) {
+        let link = format!("</v2/_catalog?n={}&last={}>; rel=\"next\"", n, tail);
+        if let Ok(value) = HeaderValue::from_str(&link) {
+            response.headers_mut().insert(header::LINK, value);
+        }
+    }
+
+    Ok(response)
+}
+
+/// Cache key covering everything that decides what a caller may see: anonymous callers
+/// share one listing, authenticated ones are keyed by identity and granted scopes
+fn catalog_scope(user: Option<&AuthenticatedUser>, artifact_filter: Option<ArtifactType>) -> String {
+    let filter = artifact_filter.map(|t| t.as_str()).unwrap_or("*");
+    match user {
+        None => format!("anonymous|{}", filter),
+        Some(user) => {
+            let mut scopes = user.scopes.clone();
+            scopes.sort();
+            format!("user:{}|{}|{}", user.id, scopes.join(" "), filter)
+        }
+    }
+}
+
+async fn visible_repositories(
+    state: &AppState,
+    user: Option<&AuthenticatedUser>,
+    artifact_filter: Option<ArtifactType>,
+) -> Result<Vec<RepositorySummary>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT r.id, r.name, r.namespace, r.description, r.is_public, r.owner_id, r.created_at, r.updated_at,
+               GROUP_CONCAT(DISTINCT m.artifact_type) AS artifact_types
+        FROM repositories r
+        LEFT JOIN manifests m ON m.repository_id = r.id
+        GROUP BY r.id
+        ORDER BY r.name ASC
+        "#
+    )
+    .fetch_all(&state.database.pool)
+    .await?;
+    let favorites = match user {
+        Some(user) => queries::favorite_repository_ids(state, &user.id).await?,
+        None => Default::default(),
+    };
+
+    let repositories: Vec<RepositorySummary> = rows
+        .iter()
+        .filter(|row| {
+            can_view_repository(
+                user,
+                row.get::<String, _>("name").as_str(),
+                row.get("is_public"),
+                row.get::<Option<String>, _>("owner_id").as_deref(),
+            )
+        })
+        .map(|row| RepositorySummary {
+            is_favorited: favorites.contains(&row.get::<Uuid, _>("id")),
+            repository: Repository {
+                id: row.get("id"),
+                name: row.get("name"),
+                namespace: row.get("namespace"),
+                description: row.get("description"),
+                is_public: row.get("is_public"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+            },
+            artifact_types: row
+                .get::<Option<String>, _>("artifact_types")
+                .unwrap_or_default()
+                .split(',')
+                .filter_map(ArtifactType::parse)
+                .collect(),
+        })
+        .filter(|summary| match artifact_filter {
+            Some(artifact_type) => summary.artifact_types.contains(&artifact_type),
+            None => true,
+        })
+        .collect();
+
+    Ok(repositories)
+}
+
+/// Explicitly create a repository
+/// Required when `registry.allow_push_creates_repository` is disabled. Accounts may create
+/// repositories in their personal namespace; anywhere else takes registry-wide write access.
+pub async fn create_repository(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<CreateRepositoryRequest>,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&request.name)?;
+
+    let may_create = user.scopes.iter().any(|s| s == "admin" || s == "registry:write")
+        || queries::in_personal_namespace(&state.config.registry, &request.name, &user);
+    if user.is_robot() || user.is_share() || !may_create {
+        return Err(Error::authorization("Repositories outside your namespace can only be created by an admin"));
+    }
+
+    match queries::get_repository_by_name(&state, &request.name).await {
+        Ok(_) => {
+            return Err(Error::conflict(format!("Repository '{}' already exists", request.name)));
+        }
+        Err(Error::NotFound { .. }) => {}
+        Err(e) => return Err(e),
+    }
+
+    let defaults = queries::repository_defaults(&state.config.registry, &request.name, Some(&user));
+    queries::ensure_repository_quota(&state, Some(&user), defaults.namespace.as_deref()).await?;
+    let repo = queries::create_repository(
+        &state,
+        &request.name,
+        request.description.as_deref().unwrap_or(""),
+        request.is_public.unwrap_or(defaults.is_public),
+        defaults.namespace.as_deref(),
+        defaults.owner_id.as_deref(),
+    )
+    .await?;
+    if let Some(allowed) = request.allowed_artifact_types.as_deref() {
+        queries::set_allowed_artifact_types(&state, &repo.id, Some(allowed)).await?;
+    }
+    state.performance.catalog_cache.invalidate();
+
+    Ok((StatusCode::CREATED, Json(repo)))
+}
+
+/// Restrict which artifact types may be pushed to a repository
+/// Only the repository owner or an admin may change it; `null` accepts any type again
+pub async fn set_artifact_types(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+    Json(request): Json<ArtifactTypeRestriction>,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = queries::get_repository_by_name(&state, &name).await?;
+
+    if user.is_robot() {
+        return Err(Error::authorization("Robot accounts cannot change repository settings"));
+    }
+    if !user.scopes.iter().any(|s| s == "admin") {
+        let owner_id: Option<String> = sqlx::query_scalar("SELECT owner_id FROM repositories WHERE id = $1")
+            .bind(&repo.id)
+            .fetch_optional(&state.database.pool)
+            .await?
+            .flatten();
+        if owner_id.as_deref() != Some(user.id.as_str()) {
+            return Err(Error::authorization("Only the repository owner or an admin can restrict artifact types"));
+        }
+    }
+
+    if request.allowed_artifact_types.as_ref().is_some_and(|types| types.is_empty()) {
+        return Err(Error::validation("allowed_artifact_types must list at least one type, or be null"));
+    }
+
+    queries::set_allowed_artifact_types(&state, &repo.id, request.allowed_artifact_types.as_deref()).await?;
+    queries::record_audit(
+        &state,
+        &user.id,
+        "repository.artifact_types",
+        &name,
+        Some(serde_json::json!({ "allowed_artifact_types": request.allowed_artifact_types })),
+    )
+    .await?;
+
+    Ok(Json(request))
+}
+
+/// Choose the platform old single-arch clients get from this repository's manifest lists
+/// Only the repository owner or an admin may change it; `null` restores the registry default
+pub async fn set_default_platform(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+    Json(request): Json<DefaultPlatform>,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = queries::get_repository_by_name(&state, &name).await?;
+
+    if user.is_robot() {
+        return Err(Error::authorization("Robot accounts cannot change repository settings"));
+    }
+    if !user.scopes.iter().any(|s| s == "admin") {
+        let owner_id: Option<String> = sqlx::query_scalar("SELECT owner_id FROM repositories WHERE id = $1")
+            .bind(&repo.id)
+            .fetch_optional(&state.database.pool)
+            .await?
+            .flatten();
+        if owner_id.as_deref() != Some(user.id.as_str()) {
+            return Err(Error::authorization("Only the repository owner or an admin can change the default platform"));
+        }
+    }
+
+    if let Some(platform) = request.default_platform.as_deref() {
+        validate_platform(platform)?;
+    }
+
+    queries::set_default_platform(&state, &repo.id, request.default_platform.as_deref()).await?;
+    queries::record_audit(
+        &state,
+        &user.id,
+        "repository.default_platform",
+        &name,
+        Some(serde_json::json!({ "default_platform": request.default_platform })),
+    )
+    .await?;
+
+    Ok(Json(request))
+}
+
+/// Turn deletion protection on or off for a repository
+/// The owner or an admin may turn it on; only an admin may turn it off
+pub async fn set_deletion_protection(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+    Json(request): Json<DeletionProtection>,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = queries::get_repository_by_name(&state, &name).await?;
+
+    if user.is_robot() {
+        return Err(Error::authorization("Robot accounts cannot change repository settings"));
+    }
+    let is_admin = user.scopes.iter().any(|s| s == "admin");
+    if !request.deletion_protected && !is_admin {
+        return Err(Error::authorization("Only an admin can remove deletion protection"));
+    }
+    if !is_admin {
+        let owner_id: Option<String> = sqlx::query_scalar("SELECT owner_id FROM repositories WHERE id = $1")
+            .bind(&repo.id)
+            .fetch_optional(&state.database.pool)
+            .await?
+            .flatten();
+        if owner_id.as_deref() != Some(user.id.as_str()) {
+            return Err(Error::authorization("Only the repository owner or an admin can protect a repository"));
+        }
+    }
+
+    let was_protected = queries::is_deletion_protected(&state, &repo.id).await?;
+    if was_protected != request.deletion_protected {
+        queries::set_deletion_protected(&state, &repo.id, request.deletion_protected).await?;
+        queries::record_audit(
+            &state,
+            &user.id,
+            "repository.deletion_protection",
+            &name,
+            Some(serde_json::json!({ "deletion_protected": request.deletion_protected })),
+        )
+        .await?;
+    }
+
+    Ok(Json(request))
+}
+
+/// Favorite a repository for the caller; favoriting twice is a no-op
+pub async fn favorite_repository(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    let repo = favoritable_repository(&state, &name, &user).await?;
+
+    if queries::add_favorite(&state, &user.id, &repo.id).await? {
+        // Listings carry the caller's `is_favorited` flags
+        state.performance.catalog_cache.invalidate();
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a repository from the caller's favorites
+pub async fn unfavorite_repository(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = queries::get_repository_by_name(&state, &name).await?;
+
+    // No read check: losing access to a repository shouldn't pin it to your favorites
+    if queries::remove_favorite(&state, &user.id, &repo.id).await? {
+        state.performance.catalog_cache.invalidate();
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The caller's favorited repositories they can still see, most recently favorited first
+pub async fn list_favorites(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    let (limit, offset) = page.bounds()?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT r.id, r.name, r.namespace, r.description, r.is_public, r.owner_id, r.created_at, r.updated_at,
+               f.created_at AS favorited_at
+        FROM user_favorites f
+        JOIN repositories r ON r.id = f.repository_id
+        WHERE f.user_id = $1
+        ORDER BY f.created_at DESC, r.name ASC
+        "#
+    )
+    .bind(&user.id)
+    .fetch_all(&state.database.pool)
+    .await?;
+
+    let favorites: Vec<FavoriteRepository> = rows
+        .iter()
+        .filter(|row| {
+            can_view_repository(
+                Some(&user),
+                row.get::<String, _>("name").as_str(),
+                row.get("is_public"),
+                row.get::<Option<String>, _>("owner_id").as_deref(),
+            )
+        })
+        .map(|row| FavoriteRepository {
+            repository: Repository {
+                id: row.get("id"),
+                name: row.get("name"),
+                namespace: row.get("namespace"),
+                description: row.get("description"),
+                is_public: row.get("is_public"),
+                created_at: row.get("created_at"),
+                updated_at: row.get("updated_at"),
+            },
+            favorited_at: row.get("favorited_at"),
+        })
+        .collect();
+
+    Ok(Json(Page::from_vec(favorites, limit, offset)))
+}
+
+/// Favorites belong to user accounts that can read the repository
+async fn favoritable_repository(state: &AppState, name: &str, user: &AuthenticatedUser) -> Result<Repository> {
+    validate_repository_name(name)?;
+    if user.is_robot() || user.is_share() {
+        return Err(Error::authorization("Only user accounts can favorite repositories"));
+    }
+
+    let repo = queries::get_repository_by_name(state, name).await?;
+    let row = sqlx::query("SELECT is_public, owner_id FROM repositories WHERE id = $1")
+        .bind(&repo.id)
+        .fetch_one(&state.database.pool)
+        .await?;
+    if !can_view_repository(Some(user), name, row.get("is_public"), row.get::<Option<String>, _>("owner_id").as_deref()) {
+        return Err(Error::authorization("Favoriting a repository requires read access to it"));
+    }
+
+    Ok(repo)
+}
+
+/// Public repositories are visible to everyone; private ones to their owner and
+/// to callers allowed to pull from them
+fn can_view_repository(user: Option<&AuthenticatedUser>, name: &str, is_public: bool, owner_id: Option<&str>) -> bool {
+    if is_public {
+        return true;
+    }
+    match user {
+        Some(user) => owner_id == Some(user.id.as_str()) || user.can_access(name, "pull"),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_repositories_hidden_from_anonymous() {
+        assert!(can_view_repository(None, "team/app", true, None));
+        assert!(!can_view_repository(None, "team/app", false, Some("owner")));
+
+        let owner = AuthenticatedUser {
+            id: "owner".to_string(),
+            name: "alice".to_string(),
+            email: String::new(),
+            scopes: vec![],
+        };
+        assert!(can_view_repository(Some(&owner), "alice/app", false, Some("owner")));
+        assert!(!can_view_repository(Some(&owner), "team/app", false, Some("someone-else")));
+    }
+
+    #[test]
+    fn test_catalog_scope_separates_callers() {
+        let user = |scopes: &[&str]| AuthenticatedUser {
+            id: "u1".to_string(),
+            name: "alice".to_string(),
+            email: String::new(),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        };
+
+        assert_eq!(catalog_scope(None, None), "anonymous|*");
+        assert_ne!(catalog_scope(None, None), catalog_scope(None, Some(ArtifactType::HelmChart)));
+        assert_ne!(catalog_scope(Some(&user(&[])), None), catalog_scope(None, None));
+        // Grant order doesn't matter, the grants themselves do
+        assert_eq!(
+            catalog_scope(Some(&user(&["repository:a:pull", "registry:read"])), None),
+            catalog_scope(Some(&user(&["registry:read", "repository:a:pull"])), None)
+        );
+        assert_ne!(
+            catalog_scope(Some(&user(&["repository:a:pull"])), None),
+            catalog_scope(Some(&user(&["repository:b:pull"])), None)
+        );
+    }
+}
@@ -0,0 +1,306 @@
+use crate::{
+    auth::middleware::{AuthenticatedUser, ROBOT_ID_PREFIX, ROBOT_USERNAME_PREFIX},
+    error::{Error, Result},
+    server::AppState,
+    types::{Page, PageQuery},
+    utils::{constant_time_eq, sha256_digest, validate_repository_name},
+    database::queries::*,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+/// Robot account permission levels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RobotPermission {
+    Read,
+    ReadWrite,
+}
+
+impl RobotPermission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RobotPermission::Read => "read",
+            RobotPermission::ReadWrite => "read_write",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(RobotPermission::Read),
+            "read_write" => Some(RobotPermission::ReadWrite),
+            _ => None,
+        }
+    }
+
+    /// Repository-scoped grants for this permission
+    pub fn scopes(&self, repository: &str) -> Vec<String> {
+        let mut scopes = vec![format!("repository:{}:pull", repository)];
+        if *self == RobotPermission::ReadWrite {
+            scopes.push(format!("repository:{}:push", repository));
+        }
+        scopes
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateRobotRequest {
+    pub name: String,
+    pub permission: RobotPermission,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/// Robot account as returned by the API (never includes the token)
+#[derive(Debug, Serialize)]
+pub struct RobotAccount {
+    pub id: Uuid,
+    pub name: String,
+    pub username: String,
+    pub permission: RobotPermission,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub last_used: Option<DateTime<Utc>>,
+}
+
+/// Response to robot creation; the token is only shown once
+#[derive(Debug, Serialize)]
+pub struct CreateRobotResponse {
+    #[serde(flatten)]
+    pub robot: RobotAccount,
+    pub token: String,
+}
+
+/// Login username for a robot: `robot$<repository>+<name>`
+pub fn robot_username(repository: &str, name: &str) -> String {
+    format!("{}{}+{}", ROBOT_USERNAME_PREFIX, repository, name)
+}
+
+/// List robot accounts for a repository
+pub async fn list_robots(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(page): Query<PageQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let (limit, offset) = page.bounds()?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_manage(&state, &user, &repo.id).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT id, name, permission, expires_at, created_at, last_used
+        FROM robot_accounts
+        WHERE repository_id = $1
+        ORDER BY created_at ASC
+        "#
+    )
+    .bind(&repo.id)
+    .fetch_all(&state.database.pool)
+    .await?;
+
+    let robots: Vec<RobotAccount> = rows
+        .iter()
+        .map(|row| {
+            let robot_name: String = row.get("name");
+            RobotAccount {
+                id: row.get("id"),
+                username: robot_username(&name, &robot_name),
+                name: robot_name,
+                permission: RobotPermission::parse(row.get::<String, _>("permission").as_str())
+                    .unwrap_or(RobotPermission::Read),
+                expires_at: row.get("expires_at"),
+                created_at: row.get("created_at"),
+                last_used: row.get("last_used"),
+            }
+        })
+        .collect();
+
+    Ok(Json(Page::from_vec(robots, limit, offset)))
+}
+
+/// Create a robot account scoped to a single repository
+pub async fn create_robot(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+    Json(request): Json<CreateRobotRequest>,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_manage(&state, &user, &repo.id).await?;
+
+    if request.name.is_empty()
+        || !request.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::validation(
+            "Robot name must contain only letters, numbers, dashes, and underscores",
+        ));
+    }
+
+    let token = generate_robot_token();
+    let id = Uuid::new_v4();
+    let now = Utc::now();
+
+    sqlx::query(
+        r#"
+        INSERT INTO robot_accounts (id, repository_id, name, token_hash, permission, expires_at, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#
+    )
+    .bind(id)
+    .bind(&repo.id)
+    .bind(&request.name)
+    .bind(sha256_digest(token.as_bytes()))
+    .bind(request.permission.as_str())
+    .bind(request.expires_at)
+    .bind(&user.id)
+    .bind(now)
+    .execute(&state.database.pool)
+    .await
+    .map_err(|_| Error::conflict(format!("Robot '{}' already exists", request.name)))?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateRobotResponse {
+            robot: RobotAccount {
+                id,
+                username: robot_username(&name, &request.name),
+                name: request.name,
+                permission: request.permission,
+                expires_at: request.expires_at,
+                created_at: now,
+                last_used: None,
+            },
+            token,
+        }),
+    ))
+}
+
+/// Revoke (delete) a robot account
+pub async fn delete_robot(
+    State(state): State<AppState>,
+    Path((name, robot_id)): Path<(String, Uuid)>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_manage(&state, &user, &repo.id).await?;
+
+    let result = sqlx::query("DELETE FROM robot_accounts WHERE id = $1 AND repository_id = $2")
+        .bind(robot_id)
+        .bind(&repo.id)
+        .execute(&state.database.pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::not_found(format!("Robot '{}' not found", robot_id)));
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Authenticate `robot$<repository>+<name>` credentials
+pub async fn authenticate_robot(state: &AppState, username: &str, token: &str) -> Result<AuthenticatedUser> {
+    let (repository, robot_name) = username
+        .strip_prefix(ROBOT_USERNAME_PREFIX)
+        .and_then(|rest| rest.rsplit_once('+'))
+        .ok_or_else(|| Error::authentication("Malformed robot username"))?;
+
+    let row = sqlx::query(
+        r#"
+        SELECT ra.id, ra.token_hash, ra.permission, ra.expires_at
+        FROM robot_accounts ra
+        JOIN repositories r ON r.id = ra.repository_id
+        WHERE r.name = $1 AND ra.name = $2
+        "#
+    )
+    .bind(repository)
+    .bind(robot_name)
+    .fetch_optional(&state.database.pool)
+    .await?
+    .ok_or_else(|| Error::authentication("Invalid robot credentials"))?;
+
+    let token_hash: String = row.get("token_hash");
+    if !constant_time_eq(token_hash.as_bytes(), sha256_digest(token.as_bytes()).as_bytes()) {
+        return Err(Error::authentication("Invalid robot credentials"));
+    }
+
+    let expires_at: Option<DateTime<Utc>> = row.get("expires_at");
+    if expires_at.is_some_and(|exp| exp <= Utc::now()) {
+        return Err(Error::authentication("Robot credentials expired"));
+    }
+
+    let robot_id: Uuid = row.get("id");
+    let permission = RobotPermission::parse(row.get::<String, _>("permission").as_str())
+        .ok_or_else(|| Error::internal("Unknown robot permission"))?;
+
+    sqlx::query("UPDATE robot_accounts SET last_used = $1 WHERE id = $2")
+        .bind(Utc::now())
+        .bind(robot_id)
+        .execute(&state.database.pool)
+        .await?;
+
+    Ok(AuthenticatedUser {
+        id: format!("{}{}", ROBOT_ID_PREFIX, robot_id),
+        name: username.to_string(),
+        email: String::new(),
+        scopes: permission.scopes(repository),
+    })
+}
+
+/// Only admins and the repository owner may manage robots; robots never can
+async fn ensure_can_manage(state: &AppState, user: &AuthenticatedUser, repository_id: &Uuid) -> Result<()> {
+    if user.is_robot() {
+        return Err(Error::authorization("Robot accounts cannot manage robots"));
+    }
+    if user.scopes.iter().any(|s| s == "admin") {
+        return Ok(());
+    }
+
+    let owner_id: Option<String> = sqlx::query_scalar("SELECT owner_id FROM repositories WHERE id = $1")
+        .bind(repository_id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .flatten();
+
+    if owner_id.as_deref() == Some(user.id.as_str()) {
+        Ok(())
+    } else {
+        Err(Error::authorization("Only the repository owner or an admin can manage robot accounts"))
+    }
+}
+
+fn generate_robot_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("gdr_{}", hex::encode(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_scopes() {
+        assert_eq!(
+            RobotPermission::Read.scopes("team/app"),
+            vec!["repository:team/app:pull".to_string()]
+        );
+        assert_eq!(RobotPermission::ReadWrite.scopes("team/app").len(), 2);
+    }
+
+    #[test]
+    fn test_robot_username() {
+        assert_eq!(robot_username("team/app", "ci"), "robot$team/app+ci");
+    }
+}
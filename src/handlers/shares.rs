@@ -0,0 +1,355 @@
+use crate::{
+    auth::{
+        jwt::{validate_token, Claims},
+        middleware::{AuthenticatedUser, SHARE_ID_PREFIX, SHARE_USERNAME_PREFIX},
+    },
+    error::{Error, Result},
+    server::AppState,
+    types::{Page, PageQuery},
+    utils::validate_repository_name,
+    database::queries::*,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use uuid::Uuid;
+
+/// Share links: signed, time-limited pull or push credentials for one repository
+/// The token is a JWT carrying the repository scopes; only its id is stored, so it can be revoked.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SharePermission {
+    Pull,
+    Push,
+}
+
+impl SharePermission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SharePermission::Pull => "pull",
+            SharePermission::Push => "push",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pull" => Some(SharePermission::Pull),
+            "push" => Some(SharePermission::Push),
+            _ => None,
+        }
+    }
+
+    /// Repository-scoped grants for this permission; push implies pull
+    pub fn scopes(&self, repository: &str) -> Vec<String> {
+        let mut scopes = vec![format!("repository:{}:pull", repository)];
+        if *self == SharePermission::Push {
+            scopes.push(format!("repository:{}:push", repository));
+        }
+        scopes
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateShareRequest {
+    #[serde(default = "default_share_permission")]
+    pub permission: SharePermission,
+    /// Lifetime in seconds; defaults to `auth.share_links.default_ttl_secs`
+    pub ttl_secs: Option<u64>,
+}
+
+fn default_share_permission() -> SharePermission {
+    SharePermission::Pull
+}
+
+/// Share as returned by the API (never includes the token)
+#[derive(Debug, Serialize)]
+pub struct Share {
+    pub id: Uuid,
+    pub username: String,
+    pub permission: SharePermission,
+    pub expires_at: DateTime<Utc>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Response to share creation; the token is only shown once
+#[derive(Debug, Serialize)]
+pub struct CreateShareResponse {
+    #[serde(flatten)]
+    pub share: Share,
+    pub token: String,
+    /// Ready-to-run login for the recipient
+    pub docker_login: String,
+}
+
+/// Login username for a share: `share$<id>`
+pub fn share_username(id: &Uuid) -> String {
+    format!("{}{}", SHARE_USERNAME_PREFIX, id)
+}
+
+/// Mint a share token for a repository (owner or admin)
+pub async fn create_share(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    user: AuthenticatedUser,
+    Json(request): Json<CreateShareRequest>,
+) -> Result<impl IntoResponse> {
+    let config = &state.config.auth.share_links;
+    if !config.enabled {
+        return Err(Error::authorization("Share links are disabled"));
+    }
+
+    validate_repository_name(&name)?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_manage(&state, &user, &repo.id).await?;
+
+    let ttl_secs = request.ttl_secs.unwrap_or(config.default_ttl_secs);
+    if ttl_secs == 0 || ttl_secs > config.max_ttl_secs {
+        return Err(Error::validation(format!(
+            "ttl_secs must be between 1 and {}",
+            config.max_ttl_secs
+        )));
+    }
+
+    let id = Uuid::new_v4();
+    let now = Utc::now();
+    let expires_at = now + Duration::seconds(ttl_secs as i64);
+
+    let claims = Claims {
+        sub: format!("{}{}", SHARE_ID_PREFIX, id),
+        name: share_username(&id),
+        email: String::new(),
+        exp: expires_at.timestamp() as usize,
+        iat: now.timestamp() as usize,
+        iss: state.jwt.issuer.clone(),
+        scope: request.permission.scopes(&name),
+        access: vec![],
+    };
+    let token = state.jwt.sign(&claims)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO share_tokens (id, repository_id, permission, expires_at, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#
+    )
+    .bind(id)
+    .bind(&repo.id)
+    .bind(request.permission.as_str())
+    .bind(expires_at)
+    .bind(&user.id)
+    .bind(now)
+    .execute(&state.database.pool)
+    .await?;
+
+    record_audit(
+        &state,
+        &user.id,
+        "repository.share",
+        &name,
+        Some(serde_json::json!({
+            "share_id": id,
+            "permission": request.permission,
+            "expires_at": expires_at,
+        })),
+    )
+    .await?;
+
+    let registry = headers
+        .get(header::HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("localhost:{}", state.config.server.port));
+    let username = share_username(&id);
+
+    Ok((
+        StatusCode::CREATED,
+        Json(CreateShareResponse {
+            docker_login: format!("docker login {} -u '{}' -p '{}'", registry, username, token),
+            share: Share {
+                id,
+                username,
+                permission: request.permission,
+                expires_at,
+                created_by: user.id.clone(),
+                created_at: now,
+                revoked_at: None,
+            },
+            token,
+        }),
+    ))
+}
+
+/// List a repository's shares, including expired and revoked ones
+pub async fn list_shares(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(page): Query<PageQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let (limit, offset) = page.bounds()?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_manage(&state, &user, &repo.id).await?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM share_tokens WHERE repository_id = $1")
+        .bind(&repo.id)
+        .fetch_one(&state.database.pool)
+        .await?;
+    let shares: Vec<Share> = sqlx::query(
+        r#"
+        SELECT id, permission, expires_at, created_by, created_at, revoked_at
+        FROM share_tokens
+        WHERE repository_id = $1
+        ORDER BY created_at DESC
+        LIMIT $2 OFFSET $3
+        "#
+    )
+    .bind(&repo.id)
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(&state.database.pool)
+    .await?
+    .iter()
+    .map(|row| {
+        let id: Uuid = row.get("id");
+        Share {
+            username: share_username(&id),
+            id,
+            permission: SharePermission::parse(row.get::<String, _>("permission").as_str())
+                .unwrap_or(SharePermission::Pull),
+            expires_at: row.get("expires_at"),
+            created_by: row.get("created_by"),
+            created_at: row.get("created_at"),
+            revoked_at: row.get("revoked_at"),
+        }
+    })
+    .collect();
+
+    Ok(Json(Page::new(shares, total as u64, limit, offset)))
+}
+
+/// Revoke a share; tokens already issued from it stop working immediately
+pub async fn revoke_share(
+    State(state): State<AppState>,
+    Path((name, share_id)): Path<(String, Uuid)>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_manage(&state, &user, &repo.id).await?;
+
+    let result = sqlx::query(
+        "UPDATE share_tokens SET revoked_at = $1 WHERE id = $2 AND repository_id = $3 AND revoked_at IS NULL"
+    )
+    .bind(Utc::now())
+    .bind(share_id)
+    .bind(&repo.id)
+    .execute(&state.database.pool)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::not_found(format!("Share '{}' not found", share_id)));
+    }
+
+    record_audit(&state, &user.id, "repository.share_revoke", &name, Some(serde_json::json!({ "share_id": share_id }))).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Authenticate `share$<id>` credentials whose password is the share token
+pub async fn authenticate_share(state: &AppState, username: &str, token: &str) -> Result<AuthenticatedUser> {
+    let id = username
+        .strip_prefix(SHARE_USERNAME_PREFIX)
+        .ok_or_else(|| Error::authentication("Malformed share username"))?;
+
+    let claims = validate_token(token, &state.jwt)
+        .await
+        .map_err(|_| Error::authentication("Invalid share credentials"))?;
+    if claims.iss != state.jwt.issuer || claims.sub != format!("{}{}", SHARE_ID_PREFIX, id) {
+        return Err(Error::authentication("Invalid share credentials"));
+    }
+
+    let user = AuthenticatedUser::from(claims);
+    ensure_share_active(state, &user.id).await?;
+    Ok(user)
+}
+
+/// Fail unless the share behind a `share:<id>` principal exists, is unexpired, and isn't revoked
+pub async fn ensure_share_active(state: &AppState, principal_id: &str) -> Result<()> {
+    let id = principal_id
+        .strip_prefix(SHARE_ID_PREFIX)
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or_else(|| Error::authentication("Malformed share id"))?;
+
+    let row = sqlx::query("SELECT expires_at, revoked_at FROM share_tokens WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .ok_or_else(|| Error::authentication("Share not found"))?;
+
+    let revoked_at: Option<DateTime<Utc>> = row.get("revoked_at");
+    if revoked_at.is_some() {
+        return Err(Error::authentication("Share has been revoked"));
+    }
+    let expires_at: DateTime<Utc> = row.get("expires_at");
+    if expires_at <= Utc::now() {
+        return Err(Error::authentication("Share has expired"));
+    }
+
+    Ok(())
+}
+
+/// Only admins and the repository owner may manage shares; robots and shares never can
+async fn ensure_can_manage(state: &AppState, user: &AuthenticatedUser, repository_id: &Uuid) -> Result<()> {
+    if user.is_robot() || user.is_share() {
+        return Err(Error::authorization("Only user accounts can manage share links"));
+    }
+    if user.scopes.iter().any(|s| s == "admin") {
+        return Ok(());
+    }
+
+    let owner_id: Option<String> = sqlx::query_scalar("SELECT owner_id FROM repositories WHERE id = $1")
+        .bind(repository_id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .flatten();
+
+    if owner_id.as_deref() == Some(user.id.as_str()) {
+        Ok(())
+    } else {
+        Err(Error::authorization("Only the repository owner or an admin can manage share links"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_share_scopes_stay_on_one_repository() {
+        let id = Uuid::new_v4();
+        let share = AuthenticatedUser {
+            id: format!("{}{}", SHARE_ID_PREFIX, id),
+            name: share_username(&id),
+            email: String::new(),
+            scopes: SharePermission::Pull.scopes("team/app"),
+        };
+
+        assert!(share.is_share());
+        assert!(share.can_access("team/app", "pull"));
+        assert!(!share.can_access("team/app", "push"));
+        assert!(!share.can_access("team/other", "pull"));
+        assert_eq!(SharePermission::Push.scopes("team/app").len(), 2);
+        assert_eq!(share_username(&id), format!("share${}", id));
+    }
+}
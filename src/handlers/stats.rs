@@ -0,0 +1,220 @@
+use crate::{
+    auth::middleware::{authorize_repository, AuthenticatedUser},
+    error::{Error, Result},
+    server::AppState,
+    utils::validate_repository_name,
+    database::queries::*,
+};
+use axum::{
+    extract::{Path, Query, State},
+    response::IntoResponse,
+    Json,
+};
+use chrono::{Duration, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+/// Maximum number of days a stats query may cover
+const MAX_RANGE_DAYS: i64 = 365;
+
+#[derive(Debug, Deserialize)]
+pub struct StatsQuery {
+    pub range: Option<String>,
+}
+
+/// One day of usage
+#[derive(Debug, Serialize)]
+pub struct UsagePoint {
+    pub day: NaiveDate,
+    pub pulls: i64,
+    pub pushes: i64,
+    pub bytes_pulled: i64,
+    pub bytes_pushed: i64,
+}
+
+/// Usage time series response
+#[derive(Debug, Serialize)]
+pub struct UsageSeries {
+    pub repository: Option<String>,
+    pub range_days: i64,
+    /// Sum of image sizes across the repository's tagged manifests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub total_image_size: Option<i64>,
+    pub points: Vec<UsagePoint>,
+}
+
+/// Get daily pull/push statistics for a repository
+pub async fn repository_stats(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(query): Query<StatsQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    authorize_repository(&state, &Some(user), &name, "pull").await?;
+    let days = parse_range(query.range.as_deref())?;
+
+    let repo = get_repository_by_name(&state, &name).await?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT day, pulls, pushes, bytes_pulled, bytes_pushed
+        FROM usage_stats
+        WHERE repository_id = $1 AND day >= $2
+        ORDER BY day ASC
+        "#
+    )
+    .bind(&repo.id)
+    .bind(range_start(days))
+    .fetch_all(&state.database.pool)
+    .await?;
+
+    let tagged_digests: Vec<String> = sqlx::query_scalar(
+        r#"
+        SELECT DISTINCT m.digest
+        FROM tags t
+        JOIN manifests m ON m.id = t.manifest_id
+        WHERE t.repository_id = $1
+        "#
+    )
+    .bind(&repo.id)
+    .fetch_all(&state.database.pool)
+    .await?;
+
+    let mut total_image_size = 0i64;
+    for digest in &tagged_digests {
+        total_image_size += get_manifest_image_size(&state, &repo.id, digest).await?;
+    }
+
+    Ok(Json(UsageSeries {
+        repository: Some(name),
+        range_days: days,
+        total_image_size: Some(total_image_size),
+        points: fill_series(days, rows.iter().map(row_to_point).collect()),
+    }))
+}
+
+/// Get daily pull/push statistics across all repositories (admin only)
+pub async fn global_stats(
+    State(state): State<AppState>,
+    Query(query): Query<StatsQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    if !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Global statistics require admin access"));
+    }
+
+    let days = parse_range(query.range.as_deref())?;
+
+    let rows = sqlx::query(
+        r#"
+        SELECT day,
+               SUM(pulls) AS pulls,
+               SUM(pushes) AS pushes,
+               SUM(bytes_pulled) AS bytes_pulled,
+               SUM(bytes_pushed) AS bytes_pushed
+        FROM usage_stats
+        WHERE day >= $1
+        GROUP BY day
+        ORDER BY day ASC
+        "#
+    )
+    .bind(range_start(days))
+    .fetch_all(&state.database.pool)
+    .await?;
+
+    Ok(Json(UsageSeries {
+        repository: None,
+        range_days: days,
+        total_image_size: None,
+        points: fill_series(days, rows.iter().map(row_to_point).collect()),
+    }))
+}
+
+/// Parse a range like "30d" into a number of days
+fn parse_range(range: Option<&str>) -> Result<i64> {
+    let Some(range) = range else {
+        return Ok(30);
+    };
+
+    let days = range
+        .strip_suffix('d')
+        .and_then(|n| n.parse::<i64>().ok())
+        .ok_or_else(|| Error::bad_request(format!("Invalid range '{}': expected e.g. '30d'", range)))?;
+
+    if days < 1 || days > MAX_RANGE_DAYS {
+        return Err(Error::bad_request(format!(
+            "Range must be between 1d and {}d",
+            MAX_RANGE_DAYS
+        )));
+    }
+
+    Ok(days)
+}
+
+fn range_start(days: i64) -> NaiveDate {
+    Utc::now().date_naive() - Duration::days(days - 1)
+}
+
+fn row_to_point(row: &sqlx::sqlite::SqliteRow) -> UsagePoint {
+    UsagePoint {
+        day: row.get("day"),
+        pulls: row.get("pulls"),
+        pushes: row.get("pushes"),
+        bytes_pulled: row.get("bytes_pulled"),
+        bytes_pushed: row.get("bytes_pushed"),
+    }
+}
+
+/// Fill days without activity with zeroes so charts get a contiguous series
+fn fill_series(days: i64, points: Vec<UsagePoint>) -> Vec<UsagePoint> {
+    let start = range_start(days);
+    let mut points = points.into_iter().peekable();
+
+    (0..days)
+        .map(|offset| {
+            let day = start + Duration::days(offset);
+            match points.peek() {
+                Some(point) if point.day == day => points.next().unwrap(),
+                _ => UsagePoint {
+                    day,
+                    pulls: 0,
+                    pushes: 0,
+                    bytes_pulled: 0,
+                    bytes_pushed: 0,
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_range() {
+        assert_eq!(parse_range(None).unwrap(), 30);
+        assert_eq!(parse_range(Some("7d")).unwrap(), 7);
+        assert!(parse_range(Some("0d")).is_err());
+        assert!(parse_range(Some("30")).is_err());
+        assert!(parse_range(Some("1000d")).is_err());
+    }
+
+    #[test]
+    fn test_fill_series() {
+        let today = Utc::now().date_naive();
+        let points = vec![UsagePoint {
+            day: today,
+            pulls: 3,
+            pushes: 1,
+            bytes_pulled: 10,
+            bytes_pushed: 5,
+        }];
+
+        let series = fill_series(7, points);
+        assert_eq!(series.len(), 7);
+        assert_eq!(series[6].pulls, 3);
+        assert_eq!(series[0].pulls, 0);
+    }
+}
@@ -0,0 +1,535 @@
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    error::{Error, Result},
+    server::AppState,
+    types::{Page, PageQuery},
+    utils::validate_repository_name,
+    database::queries::*,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, SqlitePool};
+use uuid::Uuid;
+
+/// Teams and per-repository permission grants
+/// A user's effective access is the union of their token scopes, their own grants,
+/// and the grants of every team they belong to.
+
+/// Permission levels grantable on a repository
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RepositoryPermission {
+    Read,
+    Write,
+    Admin,
+}
+
+impl RepositoryPermission {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RepositoryPermission::Read => "read",
+            RepositoryPermission::Write => "write",
+            RepositoryPermission::Admin => "admin",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "read" => Some(RepositoryPermission::Read),
+            "write" => Some(RepositoryPermission::Write),
+            "admin" => Some(RepositoryPermission::Admin),
+            _ => None,
+        }
+    }
+
+    /// Repository-scoped grants for this permission; admin covers every action
+    pub fn scopes(&self, repository: &str) -> Vec<String> {
+        match self {
+            RepositoryPermission::Read => vec![format!("repository:{}:pull", repository)],
+            RepositoryPermission::Write => vec![
+                format!("repository:{}:pull", repository),
+                format!("repository:{}:push", repository),
+            ],
+            RepositoryPermission::Admin => vec![format!("repository:{}:*", repository)],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Team {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub member_count: i64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTeamRequest {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TeamMember {
+    pub user_id: String,
+    pub username: String,
+    pub added_at: DateTime<Utc>,
+}
+
+/// A grant to exactly one of a user or a team
+#[derive(Debug, Serialize)]
+pub struct RepositoryGrant {
+    pub id: Uuid,
+    pub user_id: Option<String>,
+    pub team_id: Option<Uuid>,
+    pub permission: RepositoryPermission,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GrantPermissionRequest {
+    pub user_id: Option<String>,
+    pub team_id: Option<Uuid>,
+    pub permission: RepositoryPermission,
+}
+
+/// Repository scopes a user holds: full access to the repositories they own, plus
+/// grants made to them directly or through their teams
+pub async fn granted_scopes(pool: &SqlitePool, user_id: &str) -> Result<Vec<String>> {
+    let rows = sqlx::query(
+        r#"
+        SELECT r.name, p.permission
+        FROM repository_permissions p
+        JOIN repositories r ON r.id = p.repository_id
+        WHERE p.user_id = $1
+           OR p.team_id IN (SELECT team_id FROM team_members WHERE user_id = $1)
+        UNION ALL
+        SELECT name, 'admin' FROM repositories WHERE owner_id = $1
+        "#
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .iter()
+        .filter_map(|row| {
+            let permission = RepositoryPermission::parse(row.get::<String, _>("permission").as_str())?;
+            Some(permission.scopes(row.get::<String, _>("name").as_str()))
+        })
+        .flatten()
+        .collect())
+}
+
+/// List teams with their member counts (admin only)
+pub async fn list_teams(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+    let (limit, offset) = page.bounds()?;
+
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM teams")
+        .fetch_one(&state.database.pool)
+        .await?;
+    let teams: Vec<Team> = sqlx::query(
+        r#"
+        SELECT t.id, t.name, t.description, t.created_at,
+               (SELECT COUNT(*) FROM team_members m WHERE m.team_id = t.id) AS member_count
+        FROM teams t
+        ORDER BY t.name ASC
+        LIMIT $1 OFFSET $2
+        "#
+    )
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(&state.database.pool)
+    .await?
+    .iter()
+    .map(|row| Team {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        member_count: row.get("member_count"),
+        created_at: row.get("created_at"),
+    })
+    .collect();
+
+    Ok(Json(Page::new(teams, total as u64, limit, offset)))
+}
+
+/// Create a team (admin only)
+pub async fn create_team(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<CreateTeamRequest>,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+
+    if request.name.is_empty()
+        || !request.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(Error::validation(
+            "Team name must contain only letters, numbers, dashes, and underscores",
+        ));
+    }
+
+    let id = Uuid::new_v4();
+    let now = Utc::now();
+    sqlx::query("INSERT INTO teams (id, name, description, created_by, created_at) VALUES ($1, $2, $3, $4, $5)")
+        .bind(id)
+        .bind(&request.name)
+        .bind(&request.description)
+        .bind(&user.id)
+        .bind(now)
+        .execute(&state.database.pool)
+        .await
+        .map_err(|_| Error::conflict(format!("Team '{}' already exists", request.name)))?;
+
+    record_audit(&state, &user.id, "team.create", &request.name, None).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(Team {
+            id,
+            name: request.name,
+            description: request.description,
+            member_count: 0,
+            created_at: now,
+        }),
+    ))
+}
+
+/// Delete a team, its memberships, and its repository grants (admin only)
+pub async fn delete_team(
+    State(state): State<AppState>,
+    Path(team_id): Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+    let name = team_name(&state, team_id).await?;
+
+    let mut tx = state.database.pool.begin().await?;
+    for statement in [
+        "DELETE FROM repository_permissions WHERE team_id = $1",
+        "DELETE FROM team_members WHERE team_id = $1",
+        "DELETE FROM teams WHERE id = $1",
+    ] {
+        sqlx::query(statement).bind(team_id).execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+
+    record_audit(&state, &user.id, "team.delete", &name, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List a team's members (admin only)
+pub async fn list_members(
+    State(state): State<AppState>,
+    Path(team_id): Path<Uuid>,
+    Query(page): Query<PageQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+    team_name(&state, team_id).await?;
+    let (limit, offset) = page.bounds()?;
+
+    let members: Vec<TeamMember> = sqlx::query(
+        r#"
+        SELECT m.user_id, u.username, m.added_at
+        FROM team_members m
+        JOIN users u ON u.id = m.user_id
+        WHERE m.team_id = $1
+        ORDER BY u.username ASC
+        "#
+    )
+    .bind(team_id)
+    .fetch_all(&state.database.pool)
+    .await?
+    .iter()
+    .map(|row| TeamMember {
+        user_id: row.get("user_id"),
+        username: row.get("username"),
+        added_at: row.get("added_at"),
+    })
+    .collect();
+
+    Ok(Json(Page::from_vec(members, limit, offset)))
+}
+
+/// Add a user to a team; adding an existing member is a no-op (admin only)
+pub async fn add_member(
+    State(state): State<AppState>,
+    Path((team_id, user_id)): Path<(Uuid, String)>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+    let name = team_name(&state, team_id).await?;
+
+    let exists: Option<String> = sqlx::query_scalar("SELECT id FROM users WHERE id = $1")
+        .bind(&user_id)
+        .fetch_optional(&state.database.pool)
+        .await?;
+    if exists.is_none() {
+        return Err(Error::not_found(format!("User '{}' not found", user_id)));
+    }
+
+    let result = sqlx::query("INSERT OR IGNORE INTO team_members (team_id, user_id, added_at) VALUES ($1, $2, $3)")
+        .bind(team_id)
+        .bind(&user_id)
+        .bind(Utc::now())
+        .execute(&state.database.pool)
+        .await?;
+
+    if result.rows_affected() > 0 {
+        record_audit(&state, &user.id, "team.member_add", &name, Some(serde_json::json!({ "user_id": user_id }))).await?;
+    }
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Remove a user from a team (admin only)
+pub async fn remove_member(
+    State(state): State<AppState>,
+    Path((team_id, user_id)): Path<(Uuid, String)>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    ensure_admin(&user)?;
+    let name = team_name(&state, team_id).await?;
+
+    let result = sqlx::query("DELETE FROM team_members WHERE team_id = $1 AND user_id = $2")
+        .bind(team_id)
+        .bind(&user_id)
+        .execute(&state.database.pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::not_found(format!("User '{}' is not a member of team '{}'", user_id, name)));
+    }
+
+    record_audit(&state, &user.id, "team.member_remove", &name, Some(serde_json::json!({ "user_id": user_id }))).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// List the user and team grants on a repository
+pub async fn list_permissions(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Query(page): Query<PageQuery>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_grant(&state, &user, &name, &repo.id).await?;
+    let (limit, offset) = page.bounds()?;
+
+    let grants: Vec<RepositoryGrant> = sqlx::query(
+        r#"
+        SELECT id, user_id, team_id, permission, created_by, created_at
+        FROM repository_permissions
+        WHERE repository_id = $1
+        ORDER BY created_at ASC
+        "#
+    )
+    .bind(&repo.id)
+    .fetch_all(&state.database.pool)
+    .await?
+    .iter()
+    .filter_map(|row| {
+        Some(RepositoryGrant {
+            id: row.get("id"),
+            user_id: row.get("user_id"),
+            team_id: row.get("team_id"),
+            permission: RepositoryPermission::parse(row.get::<String, _>("permission").as_str())?,
+            created_by: row.get("created_by"),
+            created_at: row.get("created_at"),
+        })
+    })
+    .collect();
+
+    Ok(Json(Page::from_vec(grants, limit, offset)))
+}
+
+/// Grant a user or a team a permission on a repository, replacing any grant they already had
+pub async fn grant_permission(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+    Json(request): Json<GrantPermissionRequest>,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_grant(&state, &user, &name, &repo.id).await?;
+
+    match (&request.user_id, request.team_id) {
+        (Some(user_id), None) => {
+            let exists: Option<String> = sqlx::query_scalar("SELECT id FROM users WHERE id = $1")
+                .bind(user_id)
+                .fetch_optional(&state.database.pool)
+                .await?;
+            if exists.is_none() {
+                return Err(Error::not_found(format!("User '{}' not found", user_id)));
+            }
+        }
+        (None, Some(team_id)) => {
+            team_name(&state, team_id).await?;
+        }
+        _ => return Err(Error::validation("Grant to exactly one of user_id or team_id")),
+    }
+
+    let grant = RepositoryGrant {
+        id: Uuid::new_v4(),
+        user_id: request.user_id,
+        team_id: request.team_id,
+        permission: request.permission,
+        created_by: user.id.clone(),
+        created_at: Utc::now(),
+    };
+
+    let mut tx = state.database.pool.begin().await?;
+    sqlx::query(
+        r#"
+        DELETE FROM repository_permissions
+        WHERE repository_id = $1
+          AND (($2 IS NOT NULL AND user_id = $2) OR ($3 IS NOT NULL AND team_id = $3))
+        "#
+    )
+    .bind(&repo.id)
+    .bind(&grant.user_id)
+    .bind(grant.team_id)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO repository_permissions (id, repository_id, user_id, team_id, permission, created_by, created_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        "#
+    )
+    .bind(grant.id)
+    .bind(&repo.id)
+    .bind(&grant.user_id)
+    .bind(grant.team_id)
+    .bind(grant.permission.as_str())
+    .bind(&grant.created_by)
+    .bind(grant.created_at)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    record_audit(
+        &state,
+        &user.id,
+        "repository.grant",
+        &name,
+        Some(serde_json::json!({
+            "user_id": grant.user_id,
+            "team_id": grant.team_id,
+            "permission": grant.permission,
+        })),
+    )
+    .await?;
+
+    Ok((StatusCode::CREATED, Json(grant)))
+}
+
+/// Revoke a repository grant
+pub async fn revoke_permission(
+    State(state): State<AppState>,
+    Path((name, grant_id)): Path<(String, Uuid)>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    validate_repository_name(&name)?;
+    let repo = get_repository_by_name(&state, &name).await?;
+    ensure_can_grant(&state, &user, &name, &repo.id).await?;
+
+    let result = sqlx::query("DELETE FROM repository_permissions WHERE id = $1 AND repository_id = $2")
+        .bind(grant_id)
+        .bind(&repo.id)
+        .execute(&state.database.pool)
+        .await?;
+
+    if result.rows_affected() == 0 {
+        return Err(Error::not_found(format!("Grant '{}' not found", grant_id)));
+    }
+
+    record_audit(&state, &user.id, "repository.revoke", &name, Some(serde_json::json!({ "grant_id": grant_id }))).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn team_name(state: &AppState, team_id: Uuid) -> Result<String> {
+    sqlx::query_scalar("SELECT name FROM teams WHERE id = $1")
+        .bind(team_id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Team '{}' not found", team_id)))
+}
+
+fn ensure_admin(user: &AuthenticatedUser) -> Result<()> {
+    if user.is_robot() || !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Managing teams requires admin access"));
+    }
+    Ok(())
+}
+
+/// Admins, the repository owner, and holders of an admin grant may manage grants; robots never can
+async fn ensure_can_grant(state: &AppState, user: &AuthenticatedUser, name: &str, repository_id: &Uuid) -> Result<()> {
+    if user.is_robot() {
+        return Err(Error::authorization("Robot accounts cannot manage repository permissions"));
+    }
+    if user.scopes.iter().any(|s| s == "admin") || user.scopes.contains(&format!("repository:{}:*", name)) {
+        return Ok(());
+    }
+
+    let owner_id: Option<String> = sqlx::query_scalar("SELECT owner_id FROM repositories WHERE id = $1")
+        .bind(repository_id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .flatten();
+
+    if owner_id.as_deref() == Some(user.id.as_str()) {
+        Ok(())
+    } else {
+        Err(Error::authorization("Only the repository owner or an admin can manage permissions"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permission_scopes_grant_access() {
+        let mut member = AuthenticatedUser {
+            id: "user-1".to_string(),
+            name: "dev".to_string(),
+            email: String::new(),
+            scopes: vec![],
+        };
+        assert!(!member.can_access("team/app", "pull"));
+
+        // Direct and team grants are simply unioned into the caller's scopes
+        member.scopes.extend(RepositoryPermission::Read.scopes("team/app"));
+        member.scopes.extend(RepositoryPermission::Write.scopes("team/web"));
+        assert!(member.can_access("team/app", "pull"));
+        assert!(!member.can_access("team/app", "push"));
+        assert!(member.can_access("team/web", "push"));
+        assert!(!member.can_access("team/web", "delete"));
+
+        member.scopes.extend(RepositoryPermission::Admin.scopes("team/app"));
+        assert!(member.can_access("team/app", "delete"));
+        assert!(!member.can_access("team/other", "pull"));
+    }
+}
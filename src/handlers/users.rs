@@ -0,0 +1,474 @@
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    deployments::{self, DeploymentStatus},
+    error::{Error, Result},
+    server::AppState,
+    database::queries::*,
+    models::UserModel,
+    utils::{hash_password, verify_password},
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::IntoResponse,
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{Row, Sqlite, Transaction};
+
+const MIN_PASSWORD_LENGTH: usize = 8;
+
+#[derive(Debug, Deserialize)]
+pub struct DeleteUserQuery {
+    /// Username or id of the user who takes over owned repositories and stacks
+    pub reassign_to: Option<String>,
+    /// Delete owned repositories and stacks instead of reassigning them
+    #[serde(default)]
+    pub delete_resources: bool,
+}
+
+/// Summary of a user purge
+#[derive(Debug, Serialize)]
+pub struct DeleteUserResponse {
+    pub user_id: String,
+    pub repositories_reassigned: Vec<String>,
+    pub repositories_deleted: Vec<String>,
+    /// Ids of stacks the user authored
+    pub stacks_reassigned: Vec<String>,
+    pub stacks_deleted: Vec<String>,
+    pub reassigned_to: Option<String>,
+}
+
+/// The caller's own account, without credentials
+#[derive(Debug, Serialize)]
+pub struct UserProfile {
+    pub id: String,
+    pub username: String,
+    pub email: String,
+    pub full_name: Option<String>,
+    pub avatar_url: Option<String>,
+    /// OAuth provider the account signs in with, if any
+    pub provider: Option<String>,
+    pub is_admin: bool,
+    /// Whether the account can sign in with a password
+    pub has_password: bool,
+    pub created_at: DateTime<Utc>,
+    pub last_login: Option<DateTime<Utc>>,
+}
+
+impl From<UserModel> for UserProfile {
+    fn from(user: UserModel) -> Self {
+        Self {
+            id: user.id.to_string(),
+            username: user.username,
+            email: user.email,
+            full_name: user.full_name,
+            avatar_url: user.avatar_url,
+            provider: user.provider,
+            is_admin: user.is_admin,
+            has_password: user.password_hash.is_some(),
+            created_at: user.created_at,
+            last_login: user.last_login,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    pub new_password: String,
+}
+
+/// Per-user repository limit; `null` falls back to `registry.max_repositories_per_user`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepositoryLimit {
+    /// 0 lifts the limit for this user
+    pub max_repositories: Option<u32>,
+}
+
+/// Per-user request rate; `null` falls back to `rate_limit.requests_per_sec`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RateLimitOverride {
+    /// Requests per second for this user's tokens; 0 exempts them from the request rate limit
+    pub requests_per_sec: Option<u32>,
+}
+
+/// What to do with a departing user's repositories and stacks
+#[derive(Debug, PartialEq, Eq)]
+enum Disposition {
+    Reassign(String),
+    Delete,
+    Nothing,
+}
+
+/// Delete a user, reassigning or deleting the repositories and stacks they own (admin only).
+/// Their robot credentials, notification settings, favorites, team memberships, and grants are removed with them.
+pub async fn delete_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    Query(query): Query<DeleteUserQuery>,
+    admin: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    if admin.is_robot() || !admin.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Deleting users requires admin access"));
+    }
+
+    let target = sqlx::query("SELECT id, is_admin FROM users WHERE id = $1")
+        .bind(&user_id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("User '{}' not found", user_id)))?;
+
+    if target.get::<bool, _>("is_admin") {
+        let admins: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users WHERE is_admin = TRUE AND is_active = TRUE")
+            .fetch_one(&state.database.pool)
+            .await?;
+        if admins <= 1 {
+            return Err(Error::conflict("Cannot delete the last admin"));
+        }
+    }
+
+    let owned: Vec<(String, String)> = sqlx::query("SELECT id, name FROM repositories WHERE owner_id = $1")
+        .bind(&user_id)
+        .fetch_all(&state.database.pool)
+        .await?
+        .iter()
+        .map(|row| (row.get("id"), row.get("name")))
+        .collect();
+
+    let stacks: Vec<String> = sqlx::query_scalar("SELECT id FROM stacks WHERE author = $1")
+        .bind(&user_id)
+        .fetch_all(&state.database.pool)
+        .await?;
+
+    let disposition = match (&query.reassign_to, query.delete_resources) {
+        (Some(_), true) => {
+            return Err(Error::bad_request("Use either reassign_to or delete_resources, not both"));
+        }
+        (Some(new_owner), false) => {
+            let new_owner_id: String = sqlx::query_scalar(
+                "SELECT id FROM users WHERE (id = $1 OR username = $1) AND is_active = TRUE"
+            )
+            .bind(new_owner)
+            .fetch_optional(&state.database.pool)
+            .await?
+            .ok_or_else(|| Error::not_found(format!("User '{}' not found", new_owner)))?;
+
+            if new_owner_id == user_id {
+                return Err(Error::bad_request("Cannot reassign resources to the user being deleted"));
+            }
+            Disposition::Reassign(new_owner_id)
+        }
+        (None, true) => Disposition::Delete,
+        (None, false) if owned.is_empty() && stacks.is_empty() => Disposition::Nothing,
+        (None, false) => {
+            return Err(Error::conflict(format!(
+                "User owns {} repositories and {} stacks; pass reassign_to or delete_resources=true",
+                owned.len(),
+                stacks.len()
+            )));
+        }
+    };
+
+    if disposition == Disposition::Delete {
+        let protected: Vec<String> = sqlx::query_scalar(
+            "SELECT name FROM repositories WHERE owner_id = $1 AND deletion_protected = TRUE ORDER BY name"
+        )
+        .bind(&user_id)
+        .fetch_all(&state.database.pool)
+        .await?;
+        if !protected.is_empty() {
+            return Err(Error::authorization(format!(
+                "Repositories are deletion protected: {}; remove the protection or pass reassign_to",
+                protected.join(", ")
+            )));
+        }
+
+        // Running containers have to be torn down first, as when deleting a single stack
+        for stack_id in &stacks {
+            if let Some(deployment) = deployments::latest_for_stack(&state.database.pool, stack_id).await? {
+                if deployment.status != DeploymentStatus::Stopped {
+                    return Err(Error::conflict(format!("Stack {} is deployed; undeploy it before deleting", stack_id)));
+                }
+            }
+        }
+    }
+
+    let mut tx = state.database.pool.begin().await?;
+
+    let mut response = DeleteUserResponse {
+        user_id: user_id.clone(),
+        repositories_reassigned: vec![],
+        repositories_deleted: vec![],
+        stacks_reassigned: vec![],
+        stacks_deleted: vec![],
+        reassigned_to: None,
+    };
+
+    match &disposition {
+        Disposition::Reassign(new_owner_id) => {
+            sqlx::query("UPDATE repositories SET owner_id = $1, updated_at = $2 WHERE owner_id = $3")
+                .bind(new_owner_id)
+                .bind(chrono::Utc::now())
+                .bind(&user_id)
+                .execute(&mut *tx)
+                .await?;
+            sqlx::query(
+                "UPDATE stacks SET author = $1, author_email = (SELECT email FROM users WHERE id = $1), updated_at = $2 WHERE author = $3"
+            )
+            .bind(new_owner_id)
+            .bind(chrono::Utc::now())
+            .bind(&user_id)
+            .execute(&mut *tx)
+            .await?;
+            response.repositories_reassigned = owned.iter().map(|(_, name)| name.clone()).collect();
+            response.stacks_reassigned = stacks.clone();
+            response.reassigned_to = Some(new_owner_id.clone());
+        }
+        Disposition::Delete => {
+            for (repository_id, _) in &owned {
+                delete_repository_records(&mut tx, repository_id).await?;
+            }
+            response.repositories_deleted = owned.iter().map(|(_, name)| name.clone()).collect();
+
+            // Stars and activity cascade
+            for statement in [
+                "DELETE FROM deployments WHERE stack_id IN (SELECT id FROM stacks WHERE author = $1)",
+                "DELETE FROM stacks WHERE author = $1",
+            ] {
+                sqlx::query(statement).bind(&user_id).execute(&mut *tx).await?;
+            }
+            response.stacks_deleted = stacks.clone();
+        }
+        Disposition::Nothing => {}
+    }
+
+    // Credentials and settings that belong to the user
+    sqlx::query("DELETE FROM robot_accounts WHERE created_by = $1")
+        .bind(&user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM share_tokens WHERE created_by = $1")
+        .bind(&user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM notification_preferences WHERE user_id = $1")
+        .bind(&user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM user_favorites WHERE user_id = $1")
+        .bind(&user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM team_members WHERE user_id = $1")
+        .bind(&user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM repository_permissions WHERE user_id = $1")
+        .bind(&user_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("DELETE FROM users WHERE id = $1")
+        .bind(&user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    record_audit_in(
+        &mut tx,
+        &admin.id,
+        "user.delete",
+        &user_id,
+        Some(serde_json::json!({
+            "reassigned_to": response.reassigned_to,
+            "repositories_reassigned": response.repositories_reassigned,
+            "repositories_deleted": response.repositories_deleted,
+            "stacks_reassigned": response.stacks_reassigned,
+            "stacks_deleted": response.stacks_deleted,
+        })),
+    )
+    .await?;
+
+    tx.commit().await?;
+    if !owned.is_empty() {
+        state.performance.catalog_cache.invalidate();
+        state.performance.manifest_cache.clear();
+    }
+
+    Ok(Json(response))
+}
+
+/// Profile of the authenticated user
+pub async fn get_profile(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<UserProfile>> {
+    Ok(Json(load_account(&state, &user).await?.into()))
+}
+
+/// Change the caller's password after verifying the current one
+pub async fn change_password(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<ChangePasswordRequest>,
+) -> Result<impl IntoResponse> {
+    let account = load_account(&state, &user).await?;
+
+    let password_hash = account.password_hash.as_deref().ok_or_else(|| {
+        Error::bad_request(format!(
+            "This account signs in through {} and has no password to change",
+            account.provider.as_deref().unwrap_or("an external provider")
+        ))
+    })?;
+
+    if !verify_password(&request.current_password, password_hash).await? {
+        return Err(Error::authentication("Current password is incorrect"));
+    }
+    validate_new_password(&request.current_password, &request.new_password)?;
+
+    sqlx::query("UPDATE users SET password_hash = $1, updated_at = $2 WHERE id = $3")
+        .bind(hash_password(&request.new_password).await?)
+        .bind(Utc::now())
+        .bind(&user.id)
+        .execute(&state.database.pool)
+        .await?;
+
+    record_audit(&state, &user.id, "user.password_change", &user.id, None).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Set or clear a user's repository limit override (admin only)
+pub async fn set_repository_limit(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin: AuthenticatedUser,
+    Json(request): Json<RepositoryLimit>,
+) -> Result<Json<RepositoryLimit>> {
+    if admin.is_robot() || !admin.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Setting repository limits requires admin access"));
+    }
+
+    let updated = sqlx::query("UPDATE users SET max_repositories = $1, updated_at = $2 WHERE id = $3")
+        .bind(request.max_repositories.map(i64::from))
+        .bind(Utc::now())
+        .bind(&user_id)
+        .execute(&state.database.pool)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(Error::not_found(format!("User '{}' not found", user_id)));
+    }
+
+    record_audit(
+        &state,
+        &admin.id,
+        "user.repository_limit",
+        &user_id,
+        Some(serde_json::json!({ "max_repositories": request.max_repositories })),
+    )
+    .await?;
+
+    Ok(Json(request))
+}
+
+/// Set or clear a user's request rate limit override (admin only)
+pub async fn set_rate_limit(
+    State(state): State<AppState>,
+    Path(user_id): Path<String>,
+    admin: AuthenticatedUser,
+    Json(request): Json<RateLimitOverride>,
+) -> Result<Json<RateLimitOverride>> {
+    if admin.is_robot() || !admin.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Setting rate limits requires admin access"));
+    }
+
+    let updated = sqlx::query("UPDATE users SET rate_limit_override = $1, updated_at = $2 WHERE id = $3")
+        .bind(request.requests_per_sec.map(i64::from))
+        .bind(Utc::now())
+        .bind(&user_id)
+        .execute(&state.database.pool)
+        .await?;
+    if updated.rows_affected() == 0 {
+        return Err(Error::not_found(format!("User '{}' not found", user_id)));
+    }
+    state.performance.rate_limit_overrides.set(&user_id, request.requests_per_sec);
+
+    record_audit(
+        &state,
+        &admin.id,
+        "user.rate_limit",
+        &user_id,
+        Some(serde_json::json!({ "requests_per_sec": request.requests_per_sec })),
+    )
+    .await?;
+
+    Ok(Json(request))
+}
+
+/// Look up the row behind the caller's token; robots and anonymous callers have none
+async fn load_account(state: &AppState, user: &AuthenticatedUser) -> Result<UserModel> {
+    if user.is_robot() {
+        return Err(Error::bad_request("Robot accounts have no user profile"));
+    }
+
+    sqlx::query_as::<_, UserModel>("SELECT * FROM users WHERE id = $1 AND is_active = TRUE")
+        .bind(&user.id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .ok_or_else(|| Error::not_found("User account not found"))
+}
+
+fn validate_new_password(current: &str, new: &str) -> Result<()> {
+    if new.chars().count() < MIN_PASSWORD_LENGTH {
+        return Err(Error::validation(format!(
+            "New password must be at least {} characters",
+            MIN_PASSWORD_LENGTH
+        )));
+    }
+    if new == current {
+        return Err(Error::validation("New password must differ from the current one"));
+    }
+    Ok(())
+}
+
+/// Remove a repository and every row that hangs off it.
+/// Blobs stay in place for garbage collection since other repositories may share them.
+async fn delete_repository_records(tx: &mut Transaction<'_, Sqlite>, repository_id: &str) -> Result<()> {
+    for statement in [
+        "DELETE FROM tags WHERE repository_id = $1",
+        "DELETE FROM manifest_blobs WHERE manifest_id IN (SELECT id FROM manifests WHERE repository_id = $1)",
+        "DELETE FROM manifests WHERE repository_id = $1",
+        "DELETE FROM repository_blobs WHERE repository_id = $1",
+        "DELETE FROM upload_sessions WHERE repository_id = $1",
+        "DELETE FROM robot_accounts WHERE repository_id = $1",
+        "DELETE FROM repository_permissions WHERE repository_id = $1",
+        "DELETE FROM share_tokens WHERE repository_id = $1",
+        "DELETE FROM usage_stats WHERE repository_id = $1",
+        "DELETE FROM user_favorites WHERE repository_id = $1",
+        "DELETE FROM webhook_deliveries WHERE webhook_id IN (SELECT id FROM webhooks WHERE repository_id = $1)",
+        "DELETE FROM webhooks WHERE repository_id = $1",
+        // Promotions name their repositories rather than referencing them
+        "DELETE FROM promotions WHERE source_repository = (SELECT name FROM repositories WHERE id = $1) \
+         OR destination_repository = (SELECT name FROM repositories WHERE id = $1)",
+        "DELETE FROM repositories WHERE id = $1",
+    ] {
+        sqlx::query(statement)
+            .bind(repository_id)
+            .execute(&mut **tx)
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_new_password() {
+        assert!(validate_new_password("old-secret", "correct horse").is_ok());
+        assert!(matches!(validate_new_password("old-secret", "short"), Err(Error::Validation { .. })));
+        assert!(matches!(validate_new_password("old-secret", "old-secret"), Err(Error::Validation { .. })));
+    }
+}
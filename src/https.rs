@@ -0,0 +1,128 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use crate::config::WebHttpsConfig;
+
+/// HTTPS redirect and HSTS for the web UI
+///
+/// GhostDock serves plain HTTP and expects TLS to be terminated in front of it, so the
+/// original scheme comes from `X-Forwarded-Proto`; a request without it arrived in the clear.
+
+/// Paths served as-is over plain HTTP: registry clients and probes don't follow redirects
+const EXEMPT_PREFIXES: &[&str] = &["/v2", "/api", "/auth", "/health", "/readyz", "/metrics"];
+
+pub struct HttpsPolicy {
+    hsts: HeaderValue,
+}
+
+impl HttpsPolicy {
+    /// The policy for the web UI, if enabled
+    pub fn from_config(config: &WebHttpsConfig) -> Option<Arc<Self>> {
+        if !config.enabled {
+            return None;
+        }
+
+        let mut hsts = format!("max-age={}", config.hsts_max_age_secs);
+        if config.hsts_include_subdomains {
+            hsts.push_str("; includeSubDomains");
+        }
+
+        Some(Arc::new(Self {
+            hsts: HeaderValue::from_str(&hsts).expect("HSTS value is ASCII"),
+        }))
+    }
+}
+
+/// Redirect plain-HTTP page loads to HTTPS and mark HTTPS responses with HSTS
+pub async fn middleware(State(policy): State<Arc<HttpsPolicy>>, request: Request, next: Next) -> Response {
+    if is_https(request.headers()) {
+        let mut response = next.run(request).await;
+        response.headers_mut().insert(header::STRICT_TRANSPORT_SECURITY, policy.hsts.clone());
+        return response;
+    }
+
+    let path = request.uri().path();
+    if is_exempt(path) {
+        return next.run(request).await;
+    }
+
+    match https_location(&request) {
+        Some(location) => (StatusCode::MOVED_PERMANENTLY, [(header::LOCATION, location)]).into_response(),
+        // Without a Host there is nowhere to redirect to
+        None => next.run(request).await,
+    }
+}
+
+fn is_https(headers: &HeaderMap) -> bool {
+    headers
+        .get("x-forwarded-proto")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .is_some_and(|proto| proto.trim().eq_ignore_ascii_case("https"))
+}
+
+fn is_exempt(path: &str) -> bool {
+    EXEMPT_PREFIXES
+        .iter()
+        .any(|prefix| path.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('/')))
+}
+
+/// `https://` equivalent of the request URL, keeping host (minus any port), path, and query
+fn https_location(request: &Request) -> Option<String> {
+    let host = request
+        .headers()
+        .get("x-forwarded-host")
+        .or_else(|| request.headers().get(header::HOST))
+        .and_then(|v| v.to_str().ok())?;
+    let host = host.split(',').next()?.trim();
+    // The plain-HTTP port is never the HTTPS one
+    let host = match host.rfind(']') {
+        Some(end) => &host[..=end],
+        None => host.split(':').next().unwrap_or(host),
+    };
+    if host.is_empty() {
+        return None;
+    }
+
+    let path_and_query = request.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    Some(format!("https://{}{}", host, path_and_query))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+
+    fn request(uri: &str, host: &str) -> Request {
+        Request::builder().uri(uri).header(header::HOST, host).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn test_https_location_and_exemptions() {
+        assert_eq!(
+            https_location(&request("/repositories?page=2", "registry.example.com:8080")).as_deref(),
+            Some("https://registry.example.com/repositories?page=2")
+        );
+        assert_eq!(
+            https_location(&request("/", "registry.example.com")).as_deref(),
+            Some("https://registry.example.com/")
+        );
+        assert_eq!(https_location(&request("/", "[::1]:8080")).as_deref(), Some("https://[::1]/"));
+
+        assert!(is_exempt("/v2/team/app/manifests/latest"));
+        assert!(is_exempt("/health"));
+        assert!(!is_exempt("/healthz-page"));
+        assert!(!is_exempt("/dashboard"));
+
+        let mut headers = HeaderMap::new();
+        assert!(!is_https(&headers));
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https"));
+        assert!(is_https(&headers));
+    }
+}
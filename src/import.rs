@@ -0,0 +1,807 @@
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use futures::StreamExt;
+use reqwest::{header, StatusCode};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    database::queries,
+    error::{Error, Result},
+    performance::ExistenceCache,
+    server::AppState,
+    types::{ArtifactType, Repository},
+    uploads::UploadStore,
+    utils::{manifest_blob_digests, manifest_list_children, sha256_digest, validate_digest},
+};
+
+/// Bytes buffered from the source registry before each durable append
+const STAGING_FLUSH_BYTES: usize = 4 * 1024 * 1024;
+
+const MANIFEST_ACCEPT: &str = "application/vnd.docker.distribution.manifest.v2+json, application/vnd.docker.distribution.manifest.list.v2+json, application/vnd.oci.image.manifest.v1+json, application/vnd.oci.image.index.v1+json";
+
+/// Request to copy repositories from another registry
+#[derive(Debug, Clone, Deserialize)]
+pub struct ImportRequest {
+    /// Base URL of the source registry, e.g. `https://registry.example.com`
+    pub source: String,
+    pub repositories: Vec<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl ImportRequest {
+    fn credentials(&self) -> Option<(String, String)> {
+        self.username.clone().map(|u| (u, self.password.clone().unwrap_or_default()))
+    }
+}
+
+/// Lifecycle of an import job
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+    /// Was running when the server stopped; can be resumed
+    Interrupted,
+}
+
+impl ImportStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ImportStatus::Pending => "pending",
+            ImportStatus::Running => "running",
+            ImportStatus::Completed => "completed",
+            ImportStatus::Failed => "failed",
+            ImportStatus::Cancelled => "cancelled",
+            ImportStatus::Interrupted => "interrupted",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(ImportStatus::Pending),
+            "running" => Some(ImportStatus::Running),
+            "completed" => Some(ImportStatus::Completed),
+            "failed" => Some(ImportStatus::Failed),
+            "cancelled" => Some(ImportStatus::Cancelled),
+            "interrupted" => Some(ImportStatus::Interrupted),
+            _ => None,
+        }
+    }
+
+    /// Whether the job can be started again, picking up where it stopped
+    pub fn is_resumable(&self) -> bool {
+        matches!(self, ImportStatus::Failed | ImportStatus::Cancelled | ImportStatus::Interrupted)
+    }
+}
+
+/// Progress snapshot, persisted with the job and broadcast on the `import_progress` topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportProgress {
+    pub job_id: Uuid,
+    pub status: ImportStatus,
+    /// Item currently being copied
+    pub repository: Option<String>,
+    pub tag: Option<String>,
+    pub blob: Option<String>,
+    pub repositories_done: u64,
+    pub repositories_total: u64,
+    pub tags_done: u64,
+    pub tags_total: u64,
+    pub blobs_done: u64,
+    pub blobs_total: u64,
+    pub bytes_done: u64,
+    pub error: Option<String>,
+}
+
+impl ImportProgress {
+    pub fn new(job_id: Uuid, repositories_total: usize) -> Self {
+        Self {
+            job_id,
+            status: ImportStatus::Pending,
+            repository: None,
+            tag: None,
+            blob: None,
+            repositories_done: 0,
+            repositories_total: repositories_total as u64,
+            tags_done: 0,
+            tags_total: 0,
+            blobs_done: 0,
+            blobs_total: 0,
+            bytes_done: 0,
+            error: None,
+        }
+    }
+}
+
+/// Persisted import job as returned by the API
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportJob {
+    pub id: Uuid,
+    pub source: String,
+    pub repositories: Vec<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub progress: ImportProgress,
+}
+
+/// Cancellation flags for jobs running in this process
+#[derive(Debug, Default)]
+pub struct ImportJobs {
+    running: DashMap<Uuid, Arc<AtomicBool>>,
+}
+
+impl ImportJobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a job as running; fails if it already is
+    fn register(&self, job_id: Uuid) -> Result<Arc<AtomicBool>> {
+        let cancel = Arc::new(AtomicBool::new(false));
+        match self.running.entry(job_id) {
+            dashmap::mapref::entry::Entry::Occupied(_) => {
+                Err(Error::conflict(format!("Import {} is already running", job_id)))
+            }
+            dashmap::mapref::entry::Entry::Vacant(entry) => {
+                entry.insert(Arc::clone(&cancel));
+                Ok(cancel)
+            }
+        }
+    }
+
+    /// Ask a running job to stop; returns false if it isn't running here
+    pub fn cancel(&self, job_id: Uuid) -> bool {
+        match self.running.get(&job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn is_running(&self, job_id: Uuid) -> bool {
+        self.running.contains_key(&job_id)
+    }
+
+    fn finish(&self, job_id: Uuid) {
+        self.running.remove(&job_id);
+    }
+}
+
+/// Create a job record for `request`
+pub async fn create_job(state: &AppState, request: &ImportRequest, actor: &AuthenticatedUser) -> Result<ImportJob> {
+    let id = Uuid::new_v4();
+    let now = Utc::now();
+    let progress = ImportProgress::new(id, request.repositories.len());
+
+    sqlx::query(
+        r#"
+        INSERT INTO import_jobs (id, source, repositories, status, progress, created_by, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#
+    )
+    .bind(id)
+    .bind(&request.source)
+    .bind(serde_json::to_string(&request.repositories)?)
+    .bind(progress.status.as_str())
+    .bind(serde_json::to_string(&progress)?)
+    .bind(&actor.id)
+    .bind(now)
+    .bind(now)
+    .execute(&state.database.pool)
+    .await?;
+
+    Ok(ImportJob {
+        id,
+        source: request.source.clone(),
+        repositories: request.repositories.clone(),
+        created_by: actor.id.clone(),
+        created_at: now,
+        updated_at: now,
+        progress,
+    })
+}
+
+/// Load a job; jobs left running by a previous process are reported as interrupted
+pub async fn load_job(state: &AppState, job_id: Uuid) -> Result<ImportJob> {
+    let row = sqlx::query(
+        "SELECT id, source, repositories, progress, created_by, created_at, updated_at FROM import_jobs WHERE id = $1"
+    )
+    .bind(job_id)
+    .fetch_optional(&state.database.pool)
+    .await?
+    .ok_or_else(|| Error::not_found(format!("Import job '{}' not found", job_id)))?;
+
+    let mut progress: ImportProgress = serde_json::from_str(row.get::<String, _>("progress").as_str())?;
+    if matches!(progress.status, ImportStatus::Pending | ImportStatus::Running) && !state.imports.is_running(job_id) {
+        progress.status = ImportStatus::Interrupted;
+    }
+
+    Ok(ImportJob {
+        id: row.get("id"),
+        source: row.get("source"),
+        repositories: serde_json::from_str(row.get::<String, _>("repositories").as_str())?,
+        created_by: row.get("created_by"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        progress,
+    })
+}
+
+/// Start (or resume) a job in the background
+pub fn spawn(state: AppState, job: &ImportJob, request: ImportRequest, actor: AuthenticatedUser) -> Result<()> {
+    let cancel = state.imports.register(job.id)?;
+    // Counters restart on resume; blobs already copied are skipped quickly
+    let mut progress = ImportProgress::new(job.id, request.repositories.len());
+
+    tokio::spawn(async move {
+        run(&state, request, &actor, &cancel, &mut progress).await;
+        state.imports.finish(progress.job_id);
+    });
+
+    Ok(())
+}
+
+async fn run(
+    state: &AppState,
+    request: ImportRequest,
+    actor: &AuthenticatedUser,
+    cancel: &AtomicBool,
+    progress: &mut ImportProgress,
+) {
+    progress.status = ImportStatus::Running;
+    progress.error = None;
+    report(state, progress).await;
+
+    let result = match RemoteRegistry::new(&request.source, request.credentials()) {
+        Ok(remote) => import_all(state, &remote, &request, actor, cancel, progress).await,
+        Err(e) => Err(e),
+    };
+
+    progress.status = match result {
+        Ok(()) => ImportStatus::Completed,
+        Err(_) if cancel.load(Ordering::SeqCst) => ImportStatus::Cancelled,
+        Err(e) => {
+            tracing::warn!("Import {} from {} failed: {}", progress.job_id, request.source, e);
+            progress.error = Some(e.to_string());
+            ImportStatus::Failed
+        }
+    };
+    progress.repository = None;
+    progress.tag = None;
+    progress.blob = None;
+
+    // Partially staged blobs are kept so a resumed job continues from their offsets
+    if progress.status == ImportStatus::Completed {
+        if let Err(e) = tokio::fs::remove_dir_all(state.config.storage.path.join(staging_dir(progress.job_id))).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!("Failed to remove import staging for {}: {}", progress.job_id, e);
+            }
+        }
+    }
+
+    report(state, progress).await;
+}
+
+async fn import_all(
+    state: &AppState,
+    remote: &RemoteRegistry,
+    request: &ImportRequest,
+    actor: &AuthenticatedUser,
+    cancel: &AtomicBool,
+    progress: &mut ImportProgress,
+) -> Result<()> {
+    let store = UploadStore::new(&state.config.storage.path);
+
+    for name in &request.repositories {
+        check_cancelled(cancel)?;
+        progress.repository = Some(name.clone());
+        report(state, progress).await;
+
+        let repo = local_repository(state, name, actor).await?;
+        let tags = remote.list_tags(name).await?;
+        progress.tags_total += tags.len() as u64;
+
+        for tag in tags {
+            check_cancelled(cancel)?;
+            progress.tag = Some(tag.clone());
+
+            let top = remote.get_manifest(name, &tag).await?;
+            let mut manifests = Vec::new();
+            for child in manifest_list_children(&top.media_type, &top.content) {
+                validate_digest(&child)?;
+                manifests.push(remote.get_manifest(name, &child).await?);
+            }
+            manifests.push(top);
+
+            let blobs: Vec<String> = manifests.iter().flat_map(|m| manifest_blob_digests(&m.content)).collect();
+            progress.blobs_total += blobs.len() as u64;
+            for digest in &blobs {
+                check_cancelled(cancel)?;
+                progress.blob = Some(digest.clone());
+                import_blob(state, remote, &store, &repo, digest, cancel, progress).await?;
+                progress.blobs_done += 1;
+                report(state, progress).await;
+            }
+            progress.blob = None;
+
+            // Children are stored before the index that references them
+            let mut manifest_id = None;
+            for manifest in &manifests {
+                let id = queries::store_manifest(
+                    &state.database.pool,
+                    &repo.id,
+                    &manifest.digest,
+                    &manifest.media_type,
+                    &manifest.content,
+                    manifest.artifact_type(),
+                )
+                .await?;
+                for digest in manifest_blob_digests(&manifest.content) {
+                    queries::link_manifest_to_blob(state, id, &digest).await?;
+                }
+                manifest_id = Some(id);
+            }
+            if let Some(manifest_id) = manifest_id {
+                let _tag_lock = state.performance.tag_locks.lock(&repo.name, &tag).await?;
+                queries::tag_manifest(&state.database.pool, &repo.id, &tag, manifest_id).await?;
+            }
+
+            progress.tags_done += 1;
+            report(state, progress).await;
+        }
+
+        state.performance.existence_cache.invalidate_manifests(name);
+        state.performance.catalog_cache.invalidate();
+        progress.repositories_done += 1;
+    }
+
+    Ok(())
+}
+
+/// Find or create the destination repository, owned by whoever started the import
+async fn local_repository(state: &AppState, name: &str, actor: &AuthenticatedUser) -> Result<Repository> {
+    match queries::get_repository_by_name(state, name).await {
+        Ok(repo) => Ok(repo),
+        Err(Error::NotFound { .. }) => {
+            let defaults = queries::repository_defaults(&state.config.registry, name, Some(actor));
+            queries::create_repository(
+                state,
+                name,
+                "",
+                defaults.is_public,
+                defaults.namespace.as_deref(),
+                defaults.owner_id.as_deref(),
+            )
+            .await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Copy one blob, streaming it through a durable staging file so an interrupted
+/// transfer resumes from the bytes already on disk
+async fn import_blob(
+    state: &AppState,
+    remote: &RemoteRegistry,
+    store: &UploadStore,
+    repo: &Repository,
+    digest: &str,
+    cancel: &AtomicBool,
+    progress: &mut ImportProgress,
+) -> Result<()> {
+    let existing: Option<i64> = sqlx::query_scalar("SELECT size FROM blobs WHERE digest = $1")
+        .bind(digest)
+        .fetch_optional(&state.database.pool)
+        .await?;
+    if let Some(size) = existing {
+        queries::record_blob(state, repo, digest, size).await?;
+        return Ok(());
+    }
+
+    let max_size = state.config.storage.max_upload_size;
+    let staging = format!("{}/{}", staging_dir(progress.job_id), digest.replace(':', "_"));
+    let mut offset = store.persisted_size(&staging).await?;
+    let response = remote.open_blob(&repo.name, digest, offset).await?;
+
+    match response.status() {
+        // Everything was staged before the interruption
+        StatusCode::RANGE_NOT_SATISFIABLE if offset > 0 => {}
+        StatusCode::PARTIAL_CONTENT | StatusCode::OK => {
+            if offset > 0 && response.status() == StatusCode::OK {
+                // Source ignored the Range header; start over
+                store.remove(&staging).await?;
+                offset = 0;
+            }
+
+            let mut stream = response.bytes_stream();
+            let mut buffer = Vec::with_capacity(STAGING_FLUSH_BYTES);
+            while let Some(chunk) = stream.next().await {
+                buffer.extend_from_slice(&chunk?);
+                if offset + buffer.len() as u64 > max_size {
+                    store.remove(&staging).await?;
+                    return Err(blob_too_large(digest, max_size));
+                }
+                if buffer.len() >= STAGING_FLUSH_BYTES {
+                    offset = store.append_chunk(&staging, offset, &buffer).await?;
+                    progress.bytes_done += buffer.len() as u64;
+                    buffer.clear();
+                    check_cancelled(cancel)?;
+                }
+            }
+            if !buffer.is_empty() {
+                store.append_chunk(&staging, offset, &buffer).await?;
+                progress.bytes_done += buffer.len() as u64;
+            }
+        }
+        status => {
+            return Err(Error::bad_request(format!(
+                "Source registry returned {} for blob {}",
+                status, digest
+            )));
+        }
+    }
+
+    let calculated = store.digest(&staging).await?;
+    if calculated != digest {
+        tracing::warn!("Rejected blob {} imported for {}: content hashes to {}", digest, repo.name, calculated);
+        store.remove(&staging).await?;
+        return Err(Error::bad_request(format!(
+            "Digest mismatch: expected {}, got {}",
+            digest, calculated
+        )));
+    }
+
+    // Storage backends take a blob whole, so the verified blob is held in memory once
+    // while it's written; `max_upload_size` bounds how much that can be
+    let data = store.read(&staging).await?;
+    state.storage.put_blob(digest, &data).await?;
+    queries::record_blob(state, repo, digest, data.len() as i64).await?;
+    state.performance.existence_cache.invalidate(&ExistenceCache::blob_key(&repo.name, digest));
+    store.remove(&staging).await?;
+
+    Ok(())
+}
+
+fn check_cancelled(cancel: &AtomicBool) -> Result<()> {
+    if cancel.load(Ordering::SeqCst) {
+        Err(Error::conflict("Import cancelled"))
+    } else {
+        Ok(())
+    }
+}
+
+fn blob_too_large(digest: &str, max_size: u64) -> Error {
+    Error::bad_request(format!(
+        "Blob {} exceeds the {} byte max_upload_size",
+        digest, max_size
+    ))
+}
+
+fn staging_dir(job_id: Uuid) -> String {
+    format!("imports/{}", job_id)
+}
+
+/// Persist the latest progress and push it to WebSocket subscribers
+async fn report(state: &AppState, progress: &ImportProgress) {
+    let saved = match serde_json::to_string(progress) {
+        Ok(json) => sqlx::query("UPDATE import_jobs SET status = $1, progress = $2, updated_at = $3 WHERE id = $4")
+            .bind(progress.status.as_str())
+            .bind(json)
+            .bind(Utc::now())
+            .bind(progress.job_id)
+            .execute(&state.database.pool)
+            .await
+            .map(|_| ())
+            .map_err(Error::from),
+        Err(e) => Err(Error::from(e)),
+    };
+    if let Err(e) = saved {
+        tracing::warn!("Failed to persist progress for import {}: {}", progress.job_id, e);
+    }
+
+    state.websocket.broadcast_import_progress(progress.clone()).await;
+}
+
+/// Manifest fetched from the source registry
+#[derive(Debug, Clone)]
+pub struct RemoteManifest {
+    pub digest: String,
+    pub media_type: String,
+    /// Raw bytes as served by the source, so the digest is preserved
+    pub content: Vec<u8>,
+}
+
+impl RemoteManifest {
+    pub fn artifact_type(&self) -> ArtifactType {
+        let config_media_type = serde_json::from_slice::<serde_json::Value>(&self.content)
+            .ok()
+            .and_then(|v| v.pointer("/config/mediaType").and_then(|m| m.as_str()).map(str::to_string));
+        ArtifactType::detect(&self.media_type, config_media_type.as_deref())
+    }
+}
+
+/// Parameters from a `WWW-Authenticate: Bearer ...` challenge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BearerChallenge {
+    pub realm: String,
+    pub service: Option<String>,
+    pub scope: Option<String>,
+}
+
+/// Parse a Bearer challenge; quoted values may contain commas (`scope="repository:a:pull,push"`)
+pub fn parse_bearer_challenge(value: &str) -> Option<BearerChallenge> {
+    let (scheme, params) = value.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("bearer") {
+        return None;
+    }
+
+    let mut realm = None;
+    let mut service = None;
+    let mut scope = None;
+    let mut rest = params.trim();
+    while !rest.is_empty() {
+        let (key, after) = rest.split_once('=')?;
+        let (val, remainder) = match after.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => after.split_once(',').unwrap_or((after, "")),
+        };
+        match key.trim() {
+            "realm" => realm = Some(val.to_string()),
+            "service" => service = Some(val.to_string()),
+            "scope" => scope = Some(val.to_string()),
+            _ => {}
+        }
+        rest = remainder.trim_start_matches(|c: char| c == ',' || c.is_whitespace());
+    }
+
+    Some(BearerChallenge {
+        realm: realm?,
+        service,
+        scope,
+    })
+}
+
+/// Minimal Registry v2 client for pulling from another registry
+pub struct RemoteRegistry {
+    client: reqwest::Client,
+    base: String,
+    credentials: Option<(String, String)>,
+    token: Mutex<Option<String>>,
+}
+
+impl RemoteRegistry {
+    pub fn new(source: &str, credentials: Option<(String, String)>) -> Result<Self> {
+        let url = url::Url::parse(source).map_err(|_| Error::validation(format!("Invalid source URL '{}'", source)))?;
+        if !matches!(url.scheme(), "http" | "https") {
+            return Err(Error::validation("Source URL must use http or https"));
+        }
+
+        Ok(Self {
+            client: reqwest::Client::new(),
+            base: source.trim_end_matches('/').to_string(),
+            credentials,
+            token: Mutex::new(None),
+        })
+    }
+
+    /// GET a registry path, answering a Bearer challenge once if the source asks for one
+    async fn get(&self, path: &str, headers: &[(header::HeaderName, String)]) -> Result<reqwest::Response> {
+        let url = format!("{}{}", self.base, path);
+        let mut challenged = false;
+
+        loop {
+            let mut request = self.client.get(&url);
+            for (name, value) in headers {
+                request = request.header(name, value);
+            }
+            let token = self.token.lock().await.clone();
+            request = match (token, &self.credentials) {
+                (Some(token), _) => request.bearer_auth(token),
+                (None, Some((username, password))) => request.basic_auth(username, Some(password)),
+                (None, None) => request,
+            };
+
+            let response = request.send().await?;
+            if response.status() != StatusCode::UNAUTHORIZED || challenged {
+                return Ok(response);
+            }
+
+            let challenge = response
+                .headers()
+                .get(header::WWW_AUTHENTICATE)
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_bearer_challenge);
+            match challenge {
+                Some(challenge) => {
+                    let token = self.fetch_token(&challenge).await?;
+                    *self.token.lock().await = Some(token);
+                    challenged = true;
+                }
+                None => return Ok(response),
+            }
+        }
+    }
+
+    async fn fetch_token(&self, challenge: &BearerChallenge) -> Result<String> {
+        let mut query = Vec::new();
+        if let Some(service) = &challenge.service {
+            query.push(("service", service.as_str()));
+        }
+        if let Some(scope) = &challenge.scope {
+            query.push(("scope", scope.as_str()));
+        }
+
+        let mut request = self.client.get(&challenge.realm).query(&query);
+        if let Some((username, password)) = &self.credentials {
+            request = request.basic_auth(username, Some(password));
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(Error::authentication(format!(
+                "Source registry token endpoint returned {}",
+                response.status()
+            )));
+        }
+
+        let body: serde_json::Value = response.json().await?;
+        body.get("token")
+            .or_else(|| body.get("access_token"))
+            .and_then(|t| t.as_str())
+            .map(str::to_string)
+            .ok_or_else(|| Error::authentication("Source registry token response had no token"))
+    }
+
+    pub async fn list_tags(&self, repository: &str) -> Result<Vec<String>> {
+        let path = format!("/v2/{}/tags/list", repository);
+        let response = self.get(&path, &[]).await?;
+        if !response.status().is_success() {
+            return Err(remote_error(response.status(), &path));
+        }
+
+        let body: serde_json::Value = response.json().await?;
+        Ok(body
+            .get("tags")
+            .and_then(|t| t.as_array())
+            .map(|tags| tags.iter().filter_map(|t| t.as_str().map(str::to_string)).collect())
+            .unwrap_or_default())
+    }
+
+    pub async fn get_manifest(&self, repository: &str, reference: &str) -> Result<RemoteManifest> {
+        let path = format!("/v2/{}/manifests/{}", repository, reference);
+        let response = self.get(&path, &[(header::ACCEPT, MANIFEST_ACCEPT.to_string())]).await?;
+        if !response.status().is_success() {
+            return Err(remote_error(response.status(), &path));
+        }
+
+        let content_type = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.split(';').next().unwrap_or(v).trim().to_string());
+        let advertised = response
+            .headers()
+            .get("docker-content-digest")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let content = response.bytes().await?.to_vec();
+        let digest = remote_manifest_digest(reference, advertised.as_deref(), &content).map_err(|e| {
+            tracing::warn!("Rejected manifest {}:{} from {}: {}", repository, reference, self.base, e);
+            e
+        })?;
+        let media_type = serde_json::from_slice::<serde_json::Value>(&content)
+            .ok()
+            .and_then(|v| v.get("mediaType").and_then(|m| m.as_str()).map(str::to_string))
+            .or(content_type)
+            .unwrap_or_else(|| "application/vnd.docker.distribution.manifest.v2+json".to_string());
+
+        Ok(RemoteManifest {
+            digest,
+            media_type,
+            content,
+        })
+    }
+
+    /// Open a blob for streaming, starting at `offset`
+    pub async fn open_blob(&self, repository: &str, digest: &str, offset: u64) -> Result<reqwest::Response> {
+        let path = format!("/v2/{}/blobs/{}", repository, digest);
+        let headers = if offset > 0 {
+            vec![(header::RANGE, format!("bytes={}-", offset))]
+        } else {
+            vec![]
+        };
+        self.get(&path, &headers).await
+    }
+}
+
+/// Digest of manifest bytes from a source registry, which must match both the digest
+/// it was requested by and the `Docker-Content-Digest` the source advertised
+fn remote_manifest_digest(reference: &str, advertised: Option<&str>, content: &[u8]) -> Result<String> {
+    let digest = sha256_digest(content);
+    let requested = Some(reference).filter(|r| r.starts_with("sha256:"));
+    // Only sha256 can be recomputed here; other advertised algorithms are ignored
+    let advertised = advertised.filter(|d| d.starts_with("sha256:"));
+
+    for expected in [requested, advertised].into_iter().flatten() {
+        if expected != digest {
+            return Err(Error::manifest_invalid(format!(
+                "Digest mismatch: expected {}, got {}",
+                expected, digest
+            )));
+        }
+    }
+    Ok(digest)
+}
+
+fn remote_error(status: StatusCode, path: &str) -> Error {
+    if status == StatusCode::NOT_FOUND {
+        return Error::not_found(format!("{} on source registry", path));
+    }
+    Error::bad_request(format!("Source registry returned {} for {}", status, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        let challenge = parse_bearer_challenge(
+            r#"Bearer realm="https://auth.example.com/token",service="registry.example.com",scope="repository:team/app:pull,push""#,
+        )
+        .unwrap();
+        assert_eq!(challenge.realm, "https://auth.example.com/token");
+        assert_eq!(challenge.service.as_deref(), Some("registry.example.com"));
+        assert_eq!(challenge.scope.as_deref(), Some("repository:team/app:pull,push"));
+
+        assert!(parse_bearer_challenge(r#"Basic realm="registry""#).is_none());
+        assert!(parse_bearer_challenge(r#"Bearer service="no-realm""#).is_none());
+    }
+
+    #[test]
+    fn test_remote_manifest_digest_mismatch_rejected() {
+        let content = br#"{"schemaVersion":2,"mediaType":"application/vnd.oci.image.manifest.v1+json"}"#;
+        let digest = sha256_digest(content);
+        let tampered = br#"{"schemaVersion":2,"mediaType":"application/vnd.oci.image.index.v1+json"}"#;
+
+        assert_eq!(remote_manifest_digest("latest", Some(&digest), content).unwrap(), digest);
+        assert_eq!(remote_manifest_digest(&digest, None, content).unwrap(), digest);
+        // Upstream advertises one digest but serves different bytes
+        assert!(remote_manifest_digest("latest", Some(&digest), tampered).is_err());
+        // Requested by digest, served something else
+        assert!(remote_manifest_digest(&digest, None, tampered).is_err());
+    }
+
+    #[test]
+    fn test_cancel_only_running_jobs() {
+        let jobs = ImportJobs::new();
+        let job_id = Uuid::new_v4();
+        assert!(!jobs.cancel(job_id));
+
+        let flag = jobs.register(job_id).unwrap();
+        assert!(jobs.register(job_id).is_err());
+        assert!(jobs.cancel(job_id));
+        assert!(flag.load(Ordering::SeqCst));
+
+        jobs.finish(job_id);
+        assert!(!jobs.is_running(job_id));
+    }
+}
@@ -12,23 +12,40 @@
 //! - Blob storage with configurable backends
 //! - Production-ready with monitoring and metrics
 
-pub mod api;
+pub mod access_log;
 pub mod auth;
+pub mod backup;
 pub mod cli;
 pub mod config;
 pub mod database;
+pub mod deployments;
 pub mod enhanced_error;
 pub mod error;
+pub mod fsck;
+pub mod gc;
 pub mod handlers;
+pub mod https;
+pub mod import;
+pub mod maintenance;
 pub mod models;
+pub mod notifications;
 pub mod performance;
+pub mod promotions;
+pub mod proxy;
+pub mod referrers;
 pub mod server;
 pub mod stack_management;
 pub mod storage;
+pub mod storage_cache;
+pub mod storage_fallback;
+pub mod storage_pressure;
+pub mod system_metrics;
 pub mod types;
+pub mod uploads;
 pub mod utils;
 pub mod web;
 pub mod web_enhanced;
+pub mod webhooks;
 pub mod websocket;
 
 pub use config::Config;
@@ -1,9 +1,16 @@
 use anyhow::Result;
 use clap::Parser;
 use ghostdock::{
-    cli::Cli, 
+    backup,
+    cli::{Cli, Command, DbCommand},
+    config::{AuditConfig, Config, GcConfig, IssueLevel, DEFAULT_JWT_SECRET},
+    database::{queries, Database},
+    enhanced_error::metrics::Metrics,
+    gc::{self, GcOptions, GcRunner},
     server::Server,
-    websocket::WebSocketState,
+    stack_management,
+    system_metrics::SystemSampler,
+    websocket::{Notification, NotificationSeverity, WebSocketState},
 };
 use std::sync::Arc;
 use tokio::time::{interval, Duration};
@@ -28,17 +35,97 @@ async fn main() -> Result<()> {
     info!("🚀 Starting GhostDock Registry v{}", env!("CARGO_PKG_VERSION"));
     info!("📁 Config file: {:?}", cli.config);
 
-    // Create shared state for WebSocket connections
-    let websocket_state = Arc::new(WebSocketState::new());
+    match cli.command {
+        Some(Command::Gc { dry_run, min_age_hours }) => {
+            let server = Server::new(cli.config).await?;
+            let mut gc_config = server.config().gc.clone();
+            gc_config.delete_untagged_manifests = true;
+            gc_config.dry_run |= dry_run;
+            if let Some(hours) = min_age_hours {
+                gc_config.untagged_manifest_min_age_hours = hours;
+            }
+
+            let report = server.run_gc(&GcOptions::from(&gc_config)).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        Some(Command::Fsck { repair }) => {
+            let server = Server::new(cli.config).await?;
+            let report = server.run_fsck(repair).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            if !report.is_clean() && !repair {
+                std::process::exit(1);
+            }
+            return Ok(());
+        }
+        Some(Command::Db { command: DbCommand::Optimize }) => {
+            let server = Server::new(cli.config).await?;
+            let report = server.database().optimize().await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        Some(Command::Backup { output }) => {
+            let server = Server::new(cli.config).await?;
+            let report = server.run_backup(&output).await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        Some(Command::Restore { input, force }) => {
+            // Loaded without Server::new, which would open and migrate the database being replaced
+            let config = if cli.config.exists() { Config::load(&cli.config)? } else { Config::default() };
+            let report = backup::restore(&config, &input, force).await?;
+            for warning in &report.warnings {
+                warn!("{}", warning);
+            }
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        Some(Command::ValidateConfig) => {
+            std::process::exit(validate_config(&cli.config, cli.dev));
+        }
+        None => {}
+    }
+
+    // Create and start server with enhanced features
+    let server = Server::new(cli.config).await?;
+
+    // Shared state for WebSocket connections, also used by registry handlers
+    let websocket_state = server.websocket_state();
     
     // Start background tasks
     let ws_state_metrics = Arc::clone(&websocket_state);
+    let registry_metrics = server.metrics();
+    let sampler = SystemSampler::new(&server.config().storage.path).await;
+    let simulate = cli.dev;
     tokio::spawn(async move {
-        start_metrics_broadcaster(ws_state_metrics).await;
+        start_metrics_broadcaster(ws_state_metrics, registry_metrics, sampler, simulate).await;
     });
 
-    // Create and start server with enhanced features
-    let server = Server::new(cli.config).await?;
+    if let Some(dispatcher) = server.notification_dispatcher() {
+        websocket_state.set_dispatcher(dispatcher);
+    }
+
+    if server.config().gc.enabled {
+        let gc_runner = server.gc_runner();
+        let gc_config = server.config().gc.clone();
+        let ws_state_gc = Arc::clone(&websocket_state);
+        tokio::spawn(async move {
+            start_gc_scheduler(gc_runner, gc_config, ws_state_gc).await;
+        });
+    }
+    
+    let database = server.database();
+    tokio::spawn(async move {
+        start_popularity_refresher(database).await;
+    });
+
+    if server.config().audit.retention_days > 0 {
+        let database = server.database();
+        let audit_config = server.config().audit.clone();
+        tokio::spawn(async move {
+            start_audit_purger(database, audit_config).await;
+        });
+    }
     
     info!("🌐 Registry server starting...");
     info!("📊 Real-time WebSocket updates enabled");
@@ -64,45 +151,168 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
+/// Validate a configuration file and print a report; returns the process exit code
+fn validate_config(path: &std::path::Path, dev_mode: bool) -> i32 {
+    let config = match Config::load(path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("❌ Failed to load {}: {}", path.display(), e);
+            return 1;
+        }
+    };
+
+    let issues = config.validate(dev_mode);
+    let errors = issues.iter().filter(|i| i.level == IssueLevel::Error).count();
+
+    for issue in &issues {
+        match issue.level {
+            IssueLevel::Error => println!("❌ {}: {}", issue.field, issue.message),
+            IssueLevel::Warning => println!("⚠️  {}: {}", issue.field, issue.message),
+        }
+    }
+
+    if config.auth.jwt_secret == DEFAULT_JWT_SECRET {
+        eprintln!("!!! WARNING: jwt_secret is the insecure default \"{}\" — change it before deploying !!!", DEFAULT_JWT_SECRET);
+    }
+
+    if errors > 0 {
+        println!("Configuration {} is INVALID ({} errors, {} warnings)", path.display(), errors, issues.len() - errors);
+        1
+    } else {
+        println!("Configuration {} is valid ({} warnings)", path.display(), issues.len());
+        0
+    }
+}
+
 /// Background task to broadcast system metrics
-async fn start_metrics_broadcaster(websocket_state: Arc<WebSocketState>) {
+async fn start_metrics_broadcaster(
+    websocket_state: Arc<WebSocketState>,
+    registry_metrics: Arc<Metrics>,
+    mut sampler: SystemSampler,
+    simulate: bool,
+) {
     let mut interval = interval(Duration::from_secs(5));
     
     loop {
         interval.tick().await;
         
         // Collect system metrics
-        let metrics = collect_system_metrics(&websocket_state).await;
+        let metrics = collect_system_metrics(&websocket_state, &registry_metrics, &mut sampler, simulate).await;
         
         // Broadcast to all connected WebSocket clients
         websocket_state.broadcast_system_metrics(metrics).await;
     }
 }
 
-/// Collect current system metrics
-async fn collect_system_metrics(websocket_state: &WebSocketState) -> ghostdock::websocket::SystemMetrics {
+/// Background task to recompute decayed stack popularity scores
+async fn start_popularity_refresher(database: Arc<Database>) {
+    let mut interval = interval(stack_management::POPULARITY_REFRESH_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(e) = stack_management::refresh_popularity(&database.pool).await {
+            warn!("Failed to refresh stack popularity: {}", e);
+        }
+    }
+}
+
+/// Background task to drop audit entries older than the retention period
+async fn start_audit_purger(database: Arc<Database>, audit_config: AuditConfig) {
+    let retention = chrono::Duration::days(audit_config.retention_days as i64);
+    let mut interval = interval(Duration::from_secs(audit_config.purge_interval_secs.max(1)));
+
+    loop {
+        interval.tick().await;
+
+        match queries::purge_audit_log(&database.pool, chrono::Utc::now() - retention).await {
+            Ok(0) => {}
+            Ok(purged) => info!("Purged {} audit log entries older than {} days", purged, audit_config.retention_days),
+            Err(e) => warn!("Failed to purge audit log: {}", e),
+        }
+    }
+}
+
+/// Background task to run garbage collection at the configured times
+async fn start_gc_scheduler(runner: GcRunner, gc_config: GcConfig, websocket_state: Arc<WebSocketState>) {
+    let schedule = match gc_config.schedule.as_deref().map(gc::parse_schedule).transpose() {
+        Ok(schedule) => schedule,
+        Err(e) => {
+            warn!("GC scheduler disabled: {}", e);
+            return;
+        }
+    };
+    let options = GcOptions::from(&gc_config);
+
+    loop {
+        tokio::time::sleep(gc::next_run_delay(schedule.as_ref(), gc_config.interval_secs, chrono::Utc::now())).await;
+
+        let report = match runner.run_scheduled(&options).await {
+            Ok(report) => report,
+            Err(e) => {
+                warn!("Scheduled GC failed: {}", e);
+                continue;
+            }
+        };
+
+        let summary = format!(
+            "Reclaimed {} blobs ({} bytes) and {} untagged manifests{}",
+            report.blobs_deleted.len(),
+            report.bytes_freed,
+            report.manifests_deleted.len(),
+            if report.dry_run { " (dry run)" } else { "" }
+        );
+        info!("🧹 Scheduled GC finished: {}", summary);
+
+        let admins = match runner.admin_user_ids().await {
+            Ok(admins) => admins,
+            Err(e) => {
+                warn!("Failed to look up GC notification recipients: {}", e);
+                continue;
+            }
+        };
+
+        for admin in admins {
+            let notification = Notification {
+                id: uuid::Uuid::new_v4().to_string(),
+                title: "Garbage collection completed".to_string(),
+                message: summary.clone(),
+                severity: NotificationSeverity::Info,
+                timestamp: chrono::Utc::now(),
+                read: false,
+            };
+            websocket_state.broadcast_notification(admin, notification).await;
+        }
+    }
+}
+
+/// Collect current system metrics; in dev mode, readings the host can't provide are simulated
+async fn collect_system_metrics(
+    websocket_state: &WebSocketState,
+    registry_metrics: &Metrics,
+    sampler: &mut SystemSampler,
+    simulate: bool,
+) -> ghostdock::websocket::SystemMetrics {
     use ghostdock::websocket::SystemMetrics;
     
-    // In a real implementation, you would collect actual system metrics
-    // For now, we'll simulate some realistic values
-    let cpu_usage = simulate_cpu_usage();
-    let memory_usage = simulate_memory_usage();
-    let disk_usage = simulate_disk_usage();
+    let host = sampler.sample().await;
+    let fill = |value: Option<f64>, simulated: fn() -> f64| value.or_else(|| simulate.then(simulated)).unwrap_or(0.0);
+    let fill_bytes = |value: Option<u64>, simulated: fn() -> u64| value.or_else(|| simulate.then(simulated)).unwrap_or(0);
     
     SystemMetrics {
         timestamp: chrono::Utc::now(),
-        cpu_usage,
-        memory_usage,
-        disk_usage,
-        network_rx: simulate_network_rx(),
-        network_tx: simulate_network_tx(),
+        cpu_usage: fill(host.cpu_usage, simulate_cpu_usage),
+        memory_usage: fill(host.memory_usage, simulate_memory_usage),
+        disk_usage: fill(host.disk_usage, simulate_disk_usage),
+        network_rx: fill_bytes(host.network_rx, simulate_network_rx),
+        network_tx: fill_bytes(host.network_tx, simulate_network_tx),
         active_connections: websocket_state.connection_count().await,
-        registry_operations_per_minute: simulate_registry_ops(),
-        storage_size: simulate_storage_size(),
+        registry_operations_per_minute: registry_metrics.registry_operations_per_minute(),
+        storage_size: host.storage_size,
     }
 }
 
-/// Simulate CPU usage (in production, use system metrics)
+/// Simulate CPU usage for `--dev` runs where the host can't be read
 fn simulate_cpu_usage() -> f64 {
     use rand::Rng;
     let mut rng = rand::thread_rng();
@@ -110,7 +320,7 @@ fn simulate_cpu_usage() -> f64 {
     rng.gen_range(10.0..80.0)
 }
 
-/// Simulate memory usage (in production, use system metrics)
+/// Simulate memory usage for `--dev` runs where the host can't be read
 fn simulate_memory_usage() -> f64 {
     use rand::Rng;
     let mut rng = rand::thread_rng();
@@ -118,7 +328,7 @@ fn simulate_memory_usage() -> f64 {
     rng.gen_range(30.0..90.0)
 }
 
-/// Simulate disk usage (in production, use system metrics)
+/// Simulate disk usage for `--dev` runs where the host can't be read
 fn simulate_disk_usage() -> f64 {
     use rand::Rng;
     let mut rng = rand::thread_rng();
@@ -139,17 +349,3 @@ fn simulate_network_tx() -> u64 {
     let mut rng = rand::thread_rng();
     rng.gen_range(512 * 1024..5 * 1024 * 1024) // 512KB to 5MB
 }
-
-/// Simulate registry operations per minute
-fn simulate_registry_ops() -> u64 {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    rng.gen_range(50..300)
-}
-
-/// Simulate storage size
-fn simulate_storage_size() -> u64 {
-    use rand::Rng;
-    let mut rng = rand::thread_rng();
-    rng.gen_range(1024 * 1024 * 1024..50 * 1024 * 1024 * 1024) // 1GB to 50GB
-}
@@ -0,0 +1,303 @@
+use axum::{
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    RwLock,
+};
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    backup::{self, BackupReport},
+    config::MaintenanceConfig,
+    database::{queries::record_audit, OptimizeReport},
+    error::{Error, Result},
+    performance::OperationClass,
+    handlers::registry::registry_error_body,
+    server::AppState,
+    websocket::{Notification, NotificationSeverity},
+};
+
+/// Registry-wide read-only mode for backups and migrations
+/// Pulls keep working while every mutating request is rejected with 503
+
+const DEFAULT_MESSAGE: &str = "Registry is in read-only mode for maintenance";
+
+const OPTIMIZE_MESSAGE: &str = "Registry is read-only while the database is optimized";
+
+const BACKUP_MESSAGE: &str = "Registry is read-only while a backup is taken";
+
+/// Writes that stay allowed in read-only mode: sessions, turning the mode off, and database upkeep
+const EXEMPT_PREFIXES: &[&str] = &["/auth/", "/token", "/api/admin/maintenance", "/api/admin/database/"];
+
+/// Runtime-toggleable read-only switch, seeded from config
+pub struct MaintenanceMode {
+    read_only: AtomicBool,
+    message: RwLock<Option<String>>,
+    retry_after_secs: u64,
+    /// Set while a database optimize or backup runs; they never overlap
+    database_busy: AtomicBool,
+}
+
+/// Current maintenance state as reported to clients
+#[derive(Debug, Clone, Serialize)]
+pub struct MaintenanceStatus {
+    pub read_only: bool,
+    pub message: Option<String>,
+    pub retry_after_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetMaintenanceRequest {
+    pub read_only: bool,
+    pub message: Option<String>,
+}
+
+impl MaintenanceMode {
+    pub fn new(config: &MaintenanceConfig) -> Self {
+        Self {
+            read_only: AtomicBool::new(config.read_only),
+            message: RwLock::new(config.message.clone()),
+            retry_after_secs: config.retry_after_secs,
+            database_busy: AtomicBool::new(false),
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Relaxed)
+    }
+
+    pub fn status(&self) -> MaintenanceStatus {
+        MaintenanceStatus {
+            read_only: self.is_read_only(),
+            message: self.message.read().unwrap().clone(),
+            retry_after_secs: self.retry_after_secs,
+        }
+    }
+
+    /// Switch the mode; returns whether it changed
+    pub fn set(&self, read_only: bool, message: Option<String>) -> bool {
+        *self.message.write().unwrap() = message;
+        self.read_only.swap(read_only, Ordering::Relaxed) != read_only
+    }
+
+    fn message(&self) -> String {
+        self.message
+            .read()
+            .unwrap()
+            .clone()
+            .unwrap_or_else(|| DEFAULT_MESSAGE.to_string())
+    }
+}
+
+/// Whether a request would modify registry state
+pub fn is_write_request(method: &Method, path: &str) -> bool {
+    if matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS) {
+        return false;
+    }
+    !EXEMPT_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+}
+
+/// Reject writes with 503 and Retry-After while the registry is read-only
+pub async fn read_only_guard(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let maintenance = &state.maintenance;
+    if !maintenance.is_read_only() || !is_write_request(request.method(), request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let path = request.uri().path();
+    if path == "/v2" || path.starts_with("/v2/") {
+        let mut response = (
+            StatusCode::SERVICE_UNAVAILABLE,
+            registry_error_body("UNAVAILABLE", &maintenance.message(), serde_json::json!({ "read_only": true })),
+        )
+            .into_response();
+        if let Ok(value) = HeaderValue::from_str(&maintenance.retry_after_secs.to_string()) {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+        return response;
+    }
+
+    Error::read_only(maintenance.message(), maintenance.retry_after_secs).into_response()
+}
+
+/// Maintenance routes
+pub fn maintenance_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/maintenance", get(get_maintenance).put(set_maintenance))
+        .route("/api/admin/database/optimize", post(optimize_database))
+        .route("/api/admin/database/backup", post(backup_database))
+}
+
+/// Current maintenance state
+async fn get_maintenance(State(state): State<AppState>) -> Json<MaintenanceStatus> {
+    Json(state.maintenance.status())
+}
+
+/// Enter or leave read-only mode (admin only); every active user is notified of the change
+async fn set_maintenance(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<SetMaintenanceRequest>,
+) -> Result<Json<MaintenanceStatus>> {
+    if user.is_robot() || !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Changing maintenance mode requires admin access"));
+    }
+
+    let changed = state.maintenance.set(request.read_only, request.message);
+    let status = state.maintenance.status();
+    if !changed {
+        return Ok(Json(status));
+    }
+
+    let action = if status.read_only { "maintenance.enable" } else { "maintenance.disable" };
+    record_audit(&state, &user.id, action, "registry", Some(serde_json::to_value(&status)?)).await?;
+    tracing::warn!("Read-only mode {} by {}", if status.read_only { "enabled" } else { "disabled" }, user.name);
+
+    let (title, message, severity) = if status.read_only {
+        ("Registry is read-only", state.maintenance.message(), NotificationSeverity::Warning)
+    } else {
+        ("Registry is writable again", "Pushes and deletes are accepted again".to_string(), NotificationSeverity::Success)
+    };
+
+    let recipients: Vec<String> = sqlx::query_scalar("SELECT id FROM users WHERE is_active = TRUE")
+        .fetch_all(&state.database.pool)
+        .await?;
+    for user_id in recipients {
+        let notification = Notification {
+            id: Uuid::new_v4().to_string(),
+            title: title.to_string(),
+            message: message.clone(),
+            severity: severity.clone(),
+            timestamp: chrono::Utc::now(),
+            read: false,
+        };
+        state.websocket.broadcast_notification(user_id, notification).await;
+    }
+
+    Ok(Json(status))
+}
+
+/// Checkpoint, vacuum, and analyze the SQLite database (admin only).
+/// Writes are paused with read-only mode for the duration unless it is already on.
+async fn optimize_database(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<OptimizeReport>> {
+    if user.is_robot() || !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Optimizing the database requires admin access"));
+    }
+    if state.maintenance.database_busy.swap(true, Ordering::AcqRel) {
+        return Err(Error::conflict("Another database optimization or backup is running"));
+    }
+
+    let previous = state.maintenance.status();
+    let paused = !previous.read_only && state.maintenance.set(true, Some(OPTIMIZE_MESSAGE.to_string()));
+
+    let result = async {
+        let _permit = state.performance.acquire_operation(OperationClass::Maintenance).await?;
+        state.database.optimize().await
+    }
+    .await;
+
+    if paused {
+        state.maintenance.set(false, previous.message);
+    }
+    state.maintenance.database_busy.store(false, Ordering::Release);
+
+    let report = result?;
+    tracing::info!(
+        "Database optimized by {} in {} ms, reclaimed {} bytes",
+        user.name,
+        report.duration_ms,
+        report.reclaimed_bytes()
+    );
+    record_audit(&state, &user.id, "database.optimize", "registry", Some(serde_json::to_value(&report)?)).await?;
+
+    Ok(Json(report))
+}
+
+/// Write a backup archive to `maintenance.backup_dir` (admin only).
+/// Writes are paused like an optimize, so the database and blob store are copied at the same point.
+async fn backup_database(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<BackupReport>> {
+    if user.is_robot() || !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Backing up the registry requires admin access"));
+    }
+    let Some(dir) = state.config.maintenance.backup_dir.clone() else {
+        return Err(Error::bad_request("maintenance.backup_dir is not configured"));
+    };
+    if state.maintenance.database_busy.swap(true, Ordering::AcqRel) {
+        return Err(Error::conflict("Another database optimization or backup is running"));
+    }
+
+    let previous = state.maintenance.status();
+    let paused = !previous.read_only && state.maintenance.set(true, Some(BACKUP_MESSAGE.to_string()));
+
+    let output = dir.join(format!("ghostdock-{}.tar.gz", chrono::Utc::now().format("%Y%m%dT%H%M%SZ")));
+    let result = async {
+        let _permit = state.performance.acquire_operation(OperationClass::Maintenance).await?;
+        backup::create(&state.database, &state.config.storage, &output).await
+    }
+    .await;
+
+    if paused {
+        state.maintenance.set(false, previous.message);
+    }
+    state.maintenance.database_busy.store(false, Ordering::Release);
+
+    let report = result?;
+    tracing::info!(
+        "Backup {} written by {} in {} ms ({} blob files)",
+        report.output.display(),
+        user.name,
+        report.duration_ms,
+        report.manifest.storage_files
+    );
+    record_audit(&state, &user.id, "database.backup", "registry", Some(serde_json::to_value(&report)?)).await?;
+
+    Ok(Json(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_write_request() {
+        assert!(!is_write_request(&Method::GET, "/v2/app/manifests/latest"));
+        assert!(!is_write_request(&Method::HEAD, "/v2/app/blobs/sha256:abc"));
+        assert!(is_write_request(&Method::PUT, "/v2/app/manifests/latest"));
+        assert!(is_write_request(&Method::POST, "/v2/app/blobs/uploads/"));
+        assert!(is_write_request(&Method::DELETE, "/api/users/123"));
+
+        // Logging in and leaving maintenance must keep working
+        assert!(!is_write_request(&Method::POST, "/auth/login"));
+        assert!(!is_write_request(&Method::PUT, "/api/admin/maintenance"));
+        assert!(!is_write_request(&Method::POST, "/api/admin/database/optimize"));
+        assert!(!is_write_request(&Method::POST, "/api/admin/database/backup"));
+    }
+
+    #[test]
+    fn test_toggle() {
+        let mode = MaintenanceMode::new(&MaintenanceConfig::default());
+        assert!(!mode.is_read_only());
+
+        assert!(mode.set(true, Some("Backup in progress".to_string())));
+        assert!(!mode.set(true, None));
+        assert!(mode.is_read_only());
+        assert_eq!(mode.message(), DEFAULT_MESSAGE);
+
+        assert!(mode.set(false, None));
+        assert!(!mode.is_read_only());
+    }
+}
@@ -160,7 +160,10 @@ pub struct WebhookModel {
     pub repository_id: Option<Uuid>,
     pub url: String,
     pub secret: Option<String>,
+    pub previous_secret: Option<String>,
+    pub secret_rotated_at: Option<DateTime<Utc>>,
     pub events: String, // JSON array of event types
+    pub tag_patterns: Option<String>, // JSON array of tag globs; NULL fires for any tag
     pub is_active: bool,
     pub created_by: Uuid,
     pub created_at: DateTime<Utc>,
@@ -172,10 +175,15 @@ pub struct WebhookDeliveryModel {
     pub id: Uuid,
     pub webhook_id: Uuid,
     pub event_type: String,
-    pub payload: serde_json::Value,
-    pub response_status: Option<i32>,
+    pub url: String,
+    pub attempt: i64,
+    pub payload: String, // JSON payload as sent
+    pub success: bool,
+    pub response_status: Option<i64>,
     pub response_body: Option<String>,
-    pub delivered_at: Option<DateTime<Utc>>,
+    pub latency_ms: i64,
+    pub error_kind: Option<String>,
+    pub error_message: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
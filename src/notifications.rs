@@ -0,0 +1,422 @@
+use async_trait::async_trait;
+use axum::{
+    extract::State,
+    routing::get,
+    Json, Router,
+};
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::{Pool, Row, Sqlite};
+use std::{
+    collections::HashMap,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+};
+
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    config::{NotificationConfig, SmtpConfig},
+    error::{Error, Result},
+    server::AppState,
+    websocket::{Notification, NotificationSeverity},
+};
+
+/// Out-of-band notification delivery (email, Slack, Discord)
+/// Complements the WebSocket broadcast so offline users still get alerts
+
+/// Delivery channels a user can opt into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationChannel {
+    Email,
+    Slack,
+    Discord,
+}
+
+impl NotificationChannel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationChannel::Email => "email",
+            NotificationChannel::Slack => "slack",
+            NotificationChannel::Discord => "discord",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "email" => Some(NotificationChannel::Email),
+            "slack" => Some(NotificationChannel::Slack),
+            "discord" => Some(NotificationChannel::Discord),
+            _ => None,
+        }
+    }
+}
+
+/// A user's preference for one delivery channel
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NotificationPreference {
+    pub channel: NotificationChannel,
+    /// Email address or webhook URL, depending on the channel
+    pub destination: String,
+    pub min_severity: NotificationSeverity,
+}
+
+/// A delivery backend for notifications
+#[async_trait]
+pub trait NotificationSink: Send + Sync {
+    async fn deliver(&self, destination: &str, notification: &Notification) -> Result<()>;
+}
+
+/// SMTP email sink
+pub struct EmailSink {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl EmailSink {
+    pub fn new(config: &SmtpConfig) -> Result<Self> {
+        let mut builder = AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)
+            .map_err(|e| Error::internal(format!("Invalid SMTP relay: {}", e)))?
+            .port(config.port);
+
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+
+        let from = config.from.parse()
+            .map_err(|e| Error::internal(format!("Invalid SMTP from address: {}", e)))?;
+
+        Ok(Self {
+            transport: builder.build(),
+            from,
+        })
+    }
+}
+
+#[async_trait]
+impl NotificationSink for EmailSink {
+    async fn deliver(&self, destination: &str, notification: &Notification) -> Result<()> {
+        let to: Mailbox = destination.parse()
+            .map_err(|e| Error::validation(format!("Invalid email address: {}", e)))?;
+
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(format!("[GhostDock] {}", notification.title))
+            .body(notification.message.clone())
+            .map_err(|e| Error::internal(format!("Failed to build email: {}", e)))?;
+
+        self.transport.send(email).await
+            .map_err(|e| Error::internal(format!("Failed to send email: {}", e)))?;
+
+        Ok(())
+    }
+}
+
+/// Slack/Discord incoming-webhook sink
+pub struct WebhookSink {
+    channel: NotificationChannel,
+    allowed_hosts: Vec<String>,
+}
+
+impl WebhookSink {
+    pub fn new(channel: NotificationChannel, allowed_hosts: Vec<String>) -> Self {
+        Self { channel, allowed_hosts }
+    }
+
+    fn payload(&self, notification: &Notification) -> serde_json::Value {
+        let text = format!("*{}*\n{}", notification.title, notification.message);
+        match self.channel {
+            NotificationChannel::Discord => serde_json::json!({ "content": text }),
+            _ => serde_json::json!({ "text": text }),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationSink for WebhookSink {
+    async fn deliver(&self, destination: &str, notification: &Notification) -> Result<()> {
+        // Pin the checked address so the request can't be rebound to an internal one
+        let mut client = reqwest::Client::builder().redirect(reqwest::redirect::Policy::none());
+        if let Some((host, addr)) = check_webhook_destination(destination, &self.allowed_hosts).await? {
+            client = client.resolve(&host, addr);
+        }
+        let response = client
+            .build()?
+            .post(destination)
+            .json(&self.payload(notification))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(Error::internal(format!(
+                "{} webhook returned {}",
+                self.channel.as_str(),
+                response.status()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Refuse webhook URLs that reach this host or its internal network: loopback, private,
+/// link-local (including cloud metadata) and similar ranges. Hosts in `allowed_hosts` are
+/// trusted as-is. For other domains, every resolved address must be public; the first is
+/// returned for the caller to connect to.
+pub async fn check_webhook_destination(destination: &str, allowed_hosts: &[String]) -> Result<Option<(String, SocketAddr)>> {
+    let url = url::Url::parse(destination)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or_else(|| Error::validation("Webhook destination must be an http(s) URL"))?;
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    if allowed_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(&host)) {
+        return Ok(None);
+    }
+    let refused = || Error::validation(format!("Webhook host '{}' is on an internal network", host));
+
+    let port = url.port_or_known_default().unwrap_or(443);
+    let addresses: Vec<SocketAddr> = match url.host() {
+        Some(url::Host::Ipv4(ip)) => vec![SocketAddr::new(IpAddr::V4(ip), port)],
+        Some(url::Host::Ipv6(ip)) => vec![SocketAddr::new(IpAddr::V6(ip), port)],
+        Some(url::Host::Domain(domain)) => tokio::net::lookup_host((domain, port))
+            .await
+            .map_err(|e| Error::validation(format!("Cannot resolve webhook host '{}': {}", host, e)))?
+            .collect(),
+        None => return Err(refused()),
+    };
+
+    if addresses.is_empty() || addresses.iter().any(|addr| is_internal_address(addr.ip())) {
+        return Err(refused());
+    }
+    match url.host() {
+        Some(url::Host::Domain(_)) => Ok(Some((host, addresses[0]))),
+        _ => Ok(None),
+    }
+}
+
+fn is_internal_address(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                // Carrier-grade NAT, 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+        }
+        IpAddr::V6(ip) => {
+            let first = ip.segments()[0];
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local fc00::/7 and link-local fe80::/10
+                || (first & 0xfe00) == 0xfc00
+                || (first & 0xffc0) == 0xfe80
+                || ip.to_ipv4_mapped().is_some_and(|v4| is_internal_address(IpAddr::V4(v4)))
+        }
+    }
+}
+
+/// Routes notifications to the sinks each user has opted into
+pub struct NotificationDispatcher {
+    pool: Pool<Sqlite>,
+    sinks: HashMap<NotificationChannel, Box<dyn NotificationSink>>,
+    min_severity: NotificationSeverity,
+}
+
+impl NotificationDispatcher {
+    pub fn new(pool: Pool<Sqlite>, config: &NotificationConfig) -> Result<Self> {
+        let mut sinks: HashMap<NotificationChannel, Box<dyn NotificationSink>> = HashMap::new();
+
+        if let Some(smtp) = &config.smtp {
+            sinks.insert(NotificationChannel::Email, Box::new(EmailSink::new(smtp)?));
+        }
+        if config.enable_slack {
+            sinks.insert(
+                NotificationChannel::Slack,
+                Box::new(WebhookSink::new(NotificationChannel::Slack, config.allowed_webhook_hosts.clone())),
+            );
+        }
+        if config.enable_discord {
+            sinks.insert(
+                NotificationChannel::Discord,
+                Box::new(WebhookSink::new(NotificationChannel::Discord, config.allowed_webhook_hosts.clone())),
+            );
+        }
+
+        Ok(Self {
+            pool,
+            sinks,
+            min_severity: config.min_severity.clone(),
+        })
+    }
+
+    /// Deliver a notification to every channel the user opted into.
+    /// Failures are logged per channel and never abort other deliveries.
+    pub async fn dispatch(&self, user_id: &str, notification: &Notification) -> Result<()> {
+        if notification.severity.rank() < self.min_severity.rank() {
+            return Ok(());
+        }
+
+        for preference in get_preferences(&self.pool, user_id).await? {
+            if notification.severity.rank() < preference.min_severity.rank() {
+                continue;
+            }
+
+            let Some(sink) = self.sinks.get(&preference.channel) else {
+                continue;
+            };
+
+            if let Err(e) = sink.deliver(&preference.destination, notification).await {
+                tracing::warn!(
+                    "Failed to deliver notification {} to user {} via {}: {}",
+                    notification.id, user_id, preference.channel.as_str(), e
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Load a user's notification preferences
+pub async fn get_preferences(pool: &Pool<Sqlite>, user_id: &str) -> Result<Vec<NotificationPreference>> {
+    let rows = sqlx::query(
+        "SELECT channel, destination, min_severity FROM notification_preferences WHERE user_id = $1"
+    )
+    .bind(user_id)
+    .fetch_all(pool)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let channel = NotificationChannel::parse(row.get::<String, _>("channel").as_str())?;
+            let min_severity = NotificationSeverity::parse(row.get::<String, _>("min_severity").as_str())?;
+            Some(NotificationPreference {
+                channel,
+                destination: row.get("destination"),
+                min_severity,
+            })
+        })
+        .collect())
+}
+
+/// Notification preference routes
+pub fn notification_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/users/me/notifications", get(list_preferences).put(replace_preferences))
+}
+
+/// List the authenticated user's notification preferences
+async fn list_preferences(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<Json<Vec<NotificationPreference>>> {
+    Ok(Json(get_preferences(&state.database.pool, &user.id).await?))
+}
+
+/// Replace the authenticated user's notification preferences
+async fn replace_preferences(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(preferences): Json<Vec<NotificationPreference>>,
+) -> Result<Json<Vec<NotificationPreference>>> {
+    for preference in &preferences {
+        if matches!(preference.channel, NotificationChannel::Slack | NotificationChannel::Discord) {
+            check_webhook_destination(&preference.destination, &state.config.notifications.allowed_webhook_hosts).await?;
+        }
+    }
+
+    let mut tx = state.database.pool.begin().await?;
+
+    sqlx::query("DELETE FROM notification_preferences WHERE user_id = $1")
+        .bind(&user.id)
+        .execute(&mut *tx)
+        .await?;
+
+    for preference in &preferences {
+        sqlx::query(
+            r#"
+            INSERT INTO notification_preferences (id, user_id, channel, destination, min_severity, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            "#
+        )
+        .bind(uuid::Uuid::new_v4())
+        .bind(&user.id)
+        .bind(preference.channel.as_str())
+        .bind(&preference.destination)
+        .bind(preference.min_severity.as_str())
+        .bind(chrono::Utc::now())
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(Json(preferences))
+}
+
+/// Build a dispatcher if any notification sink is configured
+pub fn build_dispatcher(pool: Pool<Sqlite>, config: &NotificationConfig) -> Result<Option<Arc<NotificationDispatcher>>> {
+    if config.smtp.is_none() && !config.enable_slack && !config.enable_discord {
+        return Ok(None);
+    }
+    Ok(Some(Arc::new(NotificationDispatcher::new(pool, config)?)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity_ranking() {
+        assert!(NotificationSeverity::Error.rank() > NotificationSeverity::Warning.rank());
+        assert!(NotificationSeverity::Warning.rank() > NotificationSeverity::Info.rank());
+        assert_eq!(NotificationSeverity::parse("warning"), Some(NotificationSeverity::Warning));
+    }
+
+    #[test]
+    fn test_webhook_payloads() {
+        let notification = Notification {
+            id: "1".to_string(),
+            title: "Storage full".to_string(),
+            message: "Disk usage at 95%".to_string(),
+            severity: NotificationSeverity::Error,
+            timestamp: chrono::Utc::now(),
+            read: false,
+        };
+
+        let slack = WebhookSink::new(NotificationChannel::Slack, vec![]).payload(&notification);
+        assert!(slack.get("text").is_some());
+
+        let discord = WebhookSink::new(NotificationChannel::Discord, vec![]).payload(&notification);
+        assert!(discord.get("content").is_some());
+    }
+
+    #[tokio::test]
+    async fn test_webhook_destinations() {
+        for internal in [
+            "http://127.0.0.1/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "https://10.1.2.3/hook",
+            "http://[::1]:8080/hook",
+            "http://[::ffff:192.168.0.1]/hook",
+            "http://[fd00::1]/hook",
+        ] {
+            assert!(check_webhook_destination(internal, &[]).await.is_err(), "{}", internal);
+        }
+        assert!(check_webhook_destination("file:///etc/passwd", &[]).await.is_err());
+
+        assert!(check_webhook_destination("https://93.184.216.34/hook", &[]).await.unwrap().is_none());
+        let allowed = vec!["mattermost.internal".to_string()];
+        assert!(check_webhook_destination("http://mattermost.internal/hooks/x", &allowed).await.unwrap().is_none());
+    }
+}
@@ -1,7 +1,31 @@
-use std::sync::Arc;
-use tokio::sync::{Semaphore, RwLock};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex,
+};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard, OwnedSemaphorePermit, Semaphore, RwLock};
+use bytes::Bytes;
 use dashmap::DashMap;
 use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::SocketAddr;
+
+use crate::{
+    auth::middleware::bearer_subject,
+    config::{BandwidthConfig, CacheConfig, CatalogRateLimitConfig, ConcurrencyConfig, RateLimitConfig},
+    database::queries::list_rate_limit_overrides,
+    enhanced_error::enhanced_logging::forwarded_client_ip,
+    error::{Error, Result},
+    handlers::registry::registry_error_body,
+    server::AppState,
+};
 
 /// High-performance connection pool and caching layer
 #[derive(Clone)]
@@ -12,6 +36,607 @@ pub struct PerformanceLayer {
     pub response_cache: Arc<DashMap<String, CachedResponse>>,
     /// Rate limiting
     pub rate_limiter: Arc<RwLock<RateLimiter>>,
+    /// Per-operation-class concurrency limits
+    pub operation_limits: Arc<OperationLimits>,
+    /// Blob/manifest existence cache for HEAD storms
+    pub existence_cache: Arc<ExistenceCache>,
+    /// Rendered repository listings, per caller scope
+    pub catalog_cache: Arc<CatalogCache>,
+    /// Manifest content fetched by digest
+    pub manifest_cache: Arc<ManifestCache>,
+    /// Blob download rate limits
+    pub download_limits: Arc<streaming::DownloadLimits>,
+    /// Per-client request rate limit with bounded queueing
+    pub request_limiter: Arc<RequestLimiter>,
+    /// Tighter per-client limit for repository listings
+    pub catalog_limiter: Arc<RequestLimiter>,
+    /// Admin-set request rates for individual users
+    pub rate_limit_overrides: Arc<RateLimitOverrides>,
+    /// One push at a time per `repository:tag`
+    pub tag_locks: Arc<TagLocks>,
+    /// Held from a deployment's limit checks until its row is recorded, so concurrent
+    /// deploys can't all pass the same check
+    pub deployment_starts: Arc<AsyncMutex<()>>,
+    /// Shared storage reads for concurrent pulls of one blob
+    pub blob_reads: Arc<BlobReads>,
+}
+
+/// Classes of operations with independent concurrency caps
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OperationClass {
+    /// Cheap reads: blob and manifest pulls
+    Pull,
+    /// Blob upload writes
+    BlobUpload,
+    /// Manifest writes
+    ManifestWrite,
+    /// Background maintenance (GC, scans)
+    Maintenance,
+    /// `docker compose` runs for stack deployments
+    Deployment,
+}
+
+impl OperationClass {
+    pub const ALL: [OperationClass; 5] = [
+        OperationClass::Pull,
+        OperationClass::BlobUpload,
+        OperationClass::ManifestWrite,
+        OperationClass::Maintenance,
+        OperationClass::Deployment,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OperationClass::Pull => "pull",
+            OperationClass::BlobUpload => "blob_upload",
+            OperationClass::ManifestWrite => "manifest_write",
+            OperationClass::Maintenance => "maintenance",
+            OperationClass::Deployment => "deployment",
+        }
+    }
+}
+
+/// Semaphores guarding each operation class
+pub struct OperationLimits {
+    pull: (Arc<Semaphore>, usize),
+    blob_upload: (Arc<Semaphore>, usize),
+    manifest_write: (Arc<Semaphore>, usize),
+    maintenance: (Arc<Semaphore>, usize),
+    deployment: (Arc<Semaphore>, usize),
+    pull_timeout: Duration,
+    retry_after_secs: u64,
+}
+
+impl OperationLimits {
+    pub fn new(config: &ConcurrencyConfig) -> Self {
+        let limit = |max: usize| (Arc::new(Semaphore::new(max)), max);
+        Self {
+            pull: limit(config.max_pulls),
+            blob_upload: limit(config.max_blob_uploads),
+            manifest_write: limit(config.max_manifest_writes),
+            maintenance: limit(config.max_maintenance),
+            deployment: limit(config.max_deployments),
+            pull_timeout: Duration::from_millis(config.pull_acquire_timeout_ms),
+            retry_after_secs: config.retry_after_secs,
+        }
+    }
+
+    fn semaphore(&self, class: OperationClass) -> &(Arc<Semaphore>, usize) {
+        match class {
+            OperationClass::Pull => &self.pull,
+            OperationClass::BlobUpload => &self.blob_upload,
+            OperationClass::ManifestWrite => &self.manifest_write,
+            OperationClass::Maintenance => &self.maintenance,
+            OperationClass::Deployment => &self.deployment,
+        }
+    }
+
+    /// Acquire a permit for an operation class.
+    /// Pulls give up after a short timeout; expensive operations queue.
+    pub async fn acquire(&self, class: OperationClass) -> Result<OwnedSemaphorePermit> {
+        let semaphore = Arc::clone(&self.semaphore(class).0);
+
+        let permit = if class == OperationClass::Pull {
+            tokio::time::timeout(self.pull_timeout, semaphore.acquire_owned())
+                .await
+                .map_err(|_| Error::TooBusy {
+                    message: format!("Too many concurrent {} operations", class.as_str()),
+                    retry_after: self.retry_after_secs,
+                })?
+        } else {
+            semaphore.acquire_owned().await
+        };
+
+        permit.map_err(|_| Error::internal("Concurrency limiter closed"))
+    }
+
+    /// Number of operations of a class currently holding a permit
+    pub fn in_flight(&self, class: OperationClass) -> usize {
+        let (semaphore, max) = self.semaphore(class);
+        max.saturating_sub(semaphore.available_permits())
+    }
+
+    /// Configured limit for an operation class
+    pub fn limit(&self, class: OperationClass) -> usize {
+        self.semaphore(class).1
+    }
+}
+
+/// Serializes writes to each `repository:tag`.
+/// Waiters are served in arrival order, so when pushes to one tag race, the last to arrive
+/// is the last applied and deterministically owns the tag.
+pub struct TagLocks {
+    locks: DashMap<String, Arc<AsyncMutex<()>>>,
+    wait: Duration,
+    retry_after_secs: u64,
+    contended: AtomicU64,
+}
+
+/// Held while a tag is written; releasing it lets the next queued push through
+pub struct TagLockGuard<'a> {
+    guard: Option<OwnedMutexGuard<()>>,
+    key: String,
+    locks: &'a TagLocks,
+}
+
+impl TagLocks {
+    pub fn new(config: &ConcurrencyConfig) -> Self {
+        Self {
+            locks: DashMap::new(),
+            wait: Duration::from_millis(config.tag_lock_timeout_ms),
+            retry_after_secs: config.retry_after_secs,
+            contended: AtomicU64::new(0),
+        }
+    }
+
+    /// Wait for exclusive use of `repository:tag`
+    pub async fn lock(&self, repository: &str, tag: &str) -> Result<TagLockGuard<'_>> {
+        let key = format!("{}:{}", repository, tag);
+        let mutex = Arc::clone(&self.locks.entry(key.clone()).or_default());
+
+        let guard = match Arc::clone(&mutex).try_lock_owned() {
+            Ok(guard) => guard,
+            Err(_) => {
+                self.contended.fetch_add(1, Ordering::Relaxed);
+                match tokio::time::timeout(self.wait, mutex.lock_owned()).await {
+                    Ok(guard) => guard,
+                    Err(_) => {
+                        self.prune(&key);
+                        return Err(Error::TooBusy {
+                            message: format!("Another push to {} is still in progress", key),
+                            retry_after: self.retry_after_secs,
+                        });
+                    }
+                }
+            }
+        };
+
+        Ok(TagLockGuard { guard: Some(guard), key, locks: self })
+    }
+
+    /// Pushes that had to wait for (or gave up on) another push to the same tag
+    pub fn contended(&self) -> u64 {
+        self.contended.load(Ordering::Relaxed)
+    }
+
+    /// Tags with a push in flight or queued
+    pub fn tracked(&self) -> usize {
+        self.locks.len()
+    }
+
+    /// Drop the lock for `key` once nobody holds or waits on it
+    fn prune(&self, key: &str) {
+        self.locks.remove_if(key, |_, mutex| Arc::strong_count(mutex) == 1);
+    }
+}
+
+impl Drop for TagLockGuard<'_> {
+    fn drop(&mut self) {
+        // The guard keeps the mutex alive, so release it before checking for other users
+        self.guard.take();
+        self.locks.prune(&self.key);
+    }
+}
+
+/// Metadata returned by a successful existence lookup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExistenceInfo {
+    pub digest: String,
+    pub media_type: String,
+    pub size: i64,
+}
+
+/// Short-TTL cache of blob and manifest existence, including negative results.
+/// Writers invalidate keys after committing; a generation counter stops lookups
+/// that raced with an invalidation from re-inserting a stale entry.
+///
+/// Lookups of never-seen digests add entries too, so the cache holds at most
+/// `max_entries`. When full, expired entries are swept (at most once per TTL) and
+/// new results go uncached until there is room again.
+pub struct ExistenceCache {
+    entries: DashMap<String, (Option<ExistenceInfo>, Instant)>,
+    ttl: Duration,
+    max_entries: usize,
+    last_sweep: Mutex<Instant>,
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ExistenceCache {
+    pub fn new(ttl: Duration, max_entries: usize) -> Self {
+        Self {
+            entries: DashMap::new(),
+            ttl,
+            max_entries,
+            last_sweep: Mutex::new(Instant::now()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn blob_key(repository: &str, digest: &str) -> String {
+        format!("blob:{}@{}", repository, digest)
+    }
+
+    pub fn manifest_key(repository: &str, reference: &str) -> String {
+        format!("manifest:{}@{}", repository, reference)
+    }
+
+    /// Return the cached result for `key`, or run `lookup` and cache its result.
+    /// `Ok(None)` means the object does not exist; errors are not cached.
+    pub async fn get_or_lookup<F, Fut>(&self, key: &str, lookup: F) -> Result<Option<ExistenceInfo>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<Option<ExistenceInfo>>>,
+    {
+        if self.ttl.is_zero() {
+            return lookup().await;
+        }
+
+        let cached = self.entries.get(key).map(|entry| entry.clone());
+        match cached {
+            Some((value, expires_at)) if expires_at > Instant::now() => {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(value);
+            }
+            Some(_) => {
+                self.entries.remove(key);
+            }
+            None => {}
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let value = lookup().await?;
+        if self.generation.load(Ordering::Acquire) == generation && self.has_room() {
+            self.entries.insert(key.to_string(), (value.clone(), Instant::now() + self.ttl));
+        }
+
+        Ok(value)
+    }
+
+    /// Whether another entry fits, sweeping expired ones first when the cache is full
+    fn has_room(&self) -> bool {
+        if self.entries.len() < self.max_entries {
+            return true;
+        }
+
+        let now = Instant::now();
+        {
+            let mut last_sweep = self.last_sweep.lock().unwrap();
+            if now.duration_since(*last_sweep) < self.ttl {
+                return false;
+            }
+            *last_sweep = now;
+        }
+        self.entries.retain(|_, (_, expires_at)| *expires_at > now);
+        self.entries.len() < self.max_entries
+    }
+
+    /// Entries currently held, including expired ones not yet swept
+    pub fn entry_count(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Drop a key after the object it describes was written or deleted
+    pub fn invalidate(&self, key: &str) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.entries.remove(key);
+    }
+
+    /// Drop every manifest entry for a repository; tag and digest keys can
+    /// point at the same manifest so a write or delete affects all of them
+    pub fn invalidate_manifests(&self, repository: &str) {
+        let prefix = Self::manifest_key(repository, "");
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.entries.retain(|key, _| !key.starts_with(&prefix));
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+}
+
+/// Rendered repository listings keyed by the caller's visibility scope.
+/// Entries never expire on their own: every repository creation, deletion, ownership
+/// or visibility change, and manifest push or delete clears the whole cache.
+pub struct CatalogCache {
+    enabled: bool,
+    entries: DashMap<String, Bytes>,
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    invalidations: AtomicU64,
+}
+
+impl CatalogCache {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            entries: DashMap::new(),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            invalidations: AtomicU64::new(0),
+        }
+    }
+
+    /// Return the cached body for `scope`, or render and cache it.
+    /// A render that raced with an invalidation is served but not cached.
+    pub async fn get_or_render<F, Fut>(&self, scope: &str, render: F) -> Result<Bytes>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<Bytes>>,
+    {
+        if !self.enabled {
+            return render().await;
+        }
+
+        if let Some(body) = self.entries.get(scope) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(body.clone());
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let body = render().await?;
+        if self.generation.load(Ordering::Acquire) == generation {
+            self.entries.insert(scope.to_string(), body.clone());
+        }
+
+        Ok(body)
+    }
+
+    /// Drop every listing after a change that can affect any of them
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.entries.clear();
+        self.invalidations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub fn invalidations(&self) -> u64 {
+        self.invalidations.load(Ordering::Relaxed)
+    }
+}
+
+/// A manifest as served for a digest reference
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CachedManifest {
+    pub repository_id: Uuid,
+    pub digest: String,
+    pub media_type: String,
+    pub content: Bytes,
+}
+
+#[derive(Default)]
+struct ManifestLru {
+    entries: HashMap<String, (Arc<CachedManifest>, u64)>,
+    /// Last-use tick to key, oldest first
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: usize,
+}
+
+impl ManifestLru {
+    fn touch(&mut self, key: &str) -> Option<Arc<CachedManifest>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (manifest, used) = self.entries.get_mut(key)?;
+        self.order.remove(used);
+        *used = tick;
+        self.order.insert(tick, key.to_string());
+        Some(Arc::clone(manifest))
+    }
+
+    fn remove(&mut self, key: &str) {
+        if let Some((manifest, used)) = self.entries.remove(key) {
+            self.order.remove(&used);
+            self.bytes -= manifest.content.len();
+        }
+    }
+}
+
+/// Manifests fetched by digest, keyed by `repository@digest` and bounded by total
+/// content size with least-recently-used eviction. Content behind a digest never
+/// changes, so entries only leave when evicted or when the manifest is deleted.
+/// Tag references are mutable and never cached.
+pub struct ManifestCache {
+    capacity_bytes: usize,
+    lru: Mutex<ManifestLru>,
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl ManifestCache {
+    /// A cache holding up to `capacity_bytes` of manifest content; 0 disables it
+    pub fn new(capacity_bytes: usize) -> Self {
+        Self {
+            capacity_bytes,
+            lru: Mutex::new(ManifestLru::default()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn key(repository: &str, digest: &str) -> String {
+        format!("{}@{}", repository, digest)
+    }
+
+    /// Return the cached manifest for `key`, or load and cache it.
+    /// A load that raced with an invalidation is served but not cached.
+    pub async fn get_or_load<F, Fut>(&self, key: &str, load: F) -> Result<Arc<CachedManifest>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<CachedManifest>>,
+    {
+        if let Some(manifest) = self.lru.lock().unwrap().touch(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(manifest);
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let manifest = Arc::new(load().await?);
+        if self.generation.load(Ordering::Acquire) == generation {
+            self.insert_arc(key, Arc::clone(&manifest));
+        }
+
+        Ok(manifest)
+    }
+
+    /// Cache a manifest that was just stored
+    pub fn insert(&self, key: &str, manifest: CachedManifest) {
+        self.insert_arc(key, Arc::new(manifest));
+    }
+
+    fn insert_arc(&self, key: &str, manifest: Arc<CachedManifest>) {
+        let size = manifest.content.len();
+        if size > self.capacity_bytes {
+            return;
+        }
+
+        let mut lru = self.lru.lock().unwrap();
+        lru.remove(key);
+        while lru.bytes + size > self.capacity_bytes {
+            let Some((_, oldest)) = lru.order.pop_first() else { break };
+            if let Some((evicted, _)) = lru.entries.remove(&oldest) {
+                lru.bytes -= evicted.content.len();
+            }
+        }
+
+        lru.tick += 1;
+        let tick = lru.tick;
+        lru.order.insert(tick, key.to_string());
+        lru.entries.insert(key.to_string(), (manifest, tick));
+        lru.bytes += size;
+    }
+
+    /// Forget a deleted manifest
+    pub fn invalidate(&self, key: &str) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.lru.lock().unwrap().remove(key);
+    }
+
+    /// Forget everything, after bulk deletions such as GC or a repository purge
+    pub fn clear(&self) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        *self.lru.lock().unwrap() = ManifestLru::default();
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    /// Manifest bytes currently held
+    pub fn size_bytes(&self) -> usize {
+        self.lru.lock().unwrap().bytes
+    }
+}
+
+/// Outcome of a blob read, shared with every request that joined it
+type SharedBlobRead = std::result::Result<Option<Bytes>, String>;
+
+/// Coalesces concurrent reads of the same blob: the first request reads it from storage
+/// and everyone who arrives before that read finishes is served the same buffer.
+/// Nothing is kept afterwards, so memory holds one copy per blob being read rather than
+/// one per request. If the reading request is dropped, a waiting one takes over the read.
+pub struct BlobReads {
+    enabled: bool,
+    in_flight: DashMap<String, Arc<tokio::sync::OnceCell<SharedBlobRead>>>,
+    reads: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+impl BlobReads {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            in_flight: DashMap::new(),
+            reads: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// Read `digest` with `read`, or join a read of it already in progress
+    pub async fn get_or_read<F, Fut>(&self, digest: &str, read: F) -> Result<Option<Bytes>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<Option<Bytes>>>,
+    {
+        if !self.enabled {
+            self.reads.fetch_add(1, Ordering::Relaxed);
+            return read().await;
+        }
+
+        let cell = Arc::clone(self.in_flight.entry(digest.to_string()).or_default().value());
+        let mut performed = false;
+        let (performed_flag, reads) = (&mut performed, &self.reads);
+        let result = cell
+            .get_or_init(|| async move {
+                *performed_flag = true;
+                reads.fetch_add(1, Ordering::Relaxed);
+                read().await.map_err(|e| e.to_string())
+            })
+            .await
+            .clone();
+        if !performed {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+        }
+        // Later requests start a fresh read; only this read's entry is removed
+        self.in_flight.remove_if(digest, |_, current| Arc::ptr_eq(current, &cell));
+
+        result.map_err(|message| Error::Storage { message })
+    }
+
+    /// Storage reads performed
+    pub fn reads(&self) -> u64 {
+        self.reads.load(Ordering::Relaxed)
+    }
+
+    /// Requests served by joining another request's read
+    pub fn coalesced(&self) -> u64 {
+        self.coalesced.load(Ordering::Relaxed)
+    }
 }
 
 #[derive(Clone)]
@@ -26,15 +651,377 @@ pub struct RateLimiter {
     requests: DashMap<String, Vec<Instant>>,
 }
 
+impl RateLimiter {
+    /// Record an event for `key` and return how many fall within `window`
+    pub fn record(&self, key: &str, window: Duration) -> usize {
+        let now = Instant::now();
+        let mut events = self.requests.entry(key.to_string()).or_default();
+        events.retain(|&time| now.duration_since(time) <= window);
+        events.push(now);
+        events.len()
+    }
+
+    /// Forget all events recorded for `key`
+    pub fn reset(&self, key: &str) {
+        self.requests.remove(key);
+    }
+}
+
+/// Clients tracked before idle buckets are swept
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// Paths never rate limited: probes and scrapes must keep answering under load
+const RATE_LIMIT_EXEMPT: &[&str] = &["/health", "/readyz", "/metrics"];
+
+/// Repository listings, limited separately by `rate_limit.catalog`
+const CATALOG_PATHS: &[&str] = &["/v2/_catalog", "/api/repositories"];
+
+/// Token bucket per client that lets over-limit requests wait briefly for a token
+/// A waiting request borrows its token from future refills, so queued requests are
+/// released at exactly the sustained rate and later arrivals see the longer wait.
+pub struct RequestLimiter {
+    /// Tokens per second; 0 disables the limiter
+    rate: f64,
+    burst: f64,
+    max_queue_wait: Duration,
+    max_queued: u64,
+    buckets: DashMap<String, Bucket>,
+    queued: AtomicU64,
+    queued_total: AtomicU64,
+    wait_micros_total: AtomicU64,
+    rejected_total: AtomicU64,
+}
+
+struct Bucket {
+    /// Negative while requests are waiting on tokens not yet refilled
+    tokens: f64,
+    updated: Instant,
+    /// Time for this bucket to refill completely at its own rate
+    refill: Duration,
+}
+
+/// What to do with one request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Admission {
+    Now,
+    Wait(Duration),
+    /// The wait would exceed the bound (or the queue is full); retry after this long
+    Reject(Duration),
+}
+
+impl RequestLimiter {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        Self::with_rate(
+            config.requests_per_sec as f64,
+            config.burst,
+            Duration::from_millis(config.max_queue_wait_ms),
+            config.max_queued as u64,
+        )
+    }
+
+    /// Limiter for repository listings; these never queue, a listing isn't worth holding a connection for
+    pub fn catalog(config: &CatalogRateLimitConfig) -> Self {
+        Self::with_rate(config.requests_per_min as f64 / 60.0, config.burst, Duration::ZERO, 0)
+    }
+
+    fn with_rate(rate: f64, burst: u32, max_queue_wait: Duration, max_queued: u64) -> Self {
+        Self {
+            rate,
+            burst: burst.max(1) as f64,
+            max_queue_wait,
+            max_queued,
+            buckets: DashMap::new(),
+            queued: AtomicU64::new(0),
+            queued_total: AtomicU64::new(0),
+            wait_micros_total: AtomicU64::new(0),
+            rejected_total: AtomicU64::new(0),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.rate > 0.0
+    }
+
+    /// Take a token for `key`, or reserve the next one if it's due within the queue bound
+    pub fn admit(&self, key: &str, now: Instant) -> Admission {
+        if !self.enabled() {
+            return Admission::Now;
+        }
+        self.admit_at(key, self.rate, self.burst, now)
+    }
+
+    /// Like `admit`, at a per-user rate set by an admin instead of the configured one.
+    /// The burst is never smaller than one second's worth of requests.
+    pub fn admit_override(&self, key: &str, requests_per_sec: u32, now: Instant) -> Admission {
+        if requests_per_sec == 0 {
+            return Admission::Now;
+        }
+        let rate = requests_per_sec as f64;
+        self.admit_at(key, rate, self.burst.max(rate), now)
+    }
+
+    fn admit_at(&self, key: &str, rate: f64, burst: f64, now: Instant) -> Admission {
+        if self.buckets.len() > MAX_TRACKED_CLIENTS {
+            // A bucket that has had time to refill completely carries no state
+            self.buckets.retain(|_, bucket| now.saturating_duration_since(bucket.updated) < bucket.refill);
+        }
+
+        let refill = Duration::from_secs_f64(burst / rate);
+        let mut bucket = self.buckets.entry(key.to_string()).or_insert(Bucket { tokens: burst, updated: now, refill });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(burst);
+        bucket.updated = now;
+        bucket.refill = refill;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Admission::Now;
+        }
+
+        let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / rate);
+        let queue_full = self.queued.load(Ordering::Relaxed) >= self.max_queued;
+        if queue_full || wait > self.max_queue_wait {
+            return Admission::Reject(wait);
+        }
+        bucket.tokens -= 1.0;
+        Admission::Wait(wait)
+    }
+
+    /// Admit a request from `key`, sleeping through any queue wait.
+    /// Returns how long to tell the client to back off when it is rejected.
+    pub async fn acquire(&self, key: &str) -> std::result::Result<(), Duration> {
+        self.wait_for(self.admit(key, Instant::now())).await
+    }
+
+    /// `acquire` at a per-user rate; 0 is unlimited
+    pub async fn acquire_override(&self, key: &str, requests_per_sec: u32) -> std::result::Result<(), Duration> {
+        self.wait_for(self.admit_override(key, requests_per_sec, Instant::now())).await
+    }
+
+    async fn wait_for(&self, admission: Admission) -> std::result::Result<(), Duration> {
+        match admission {
+            Admission::Now => Ok(()),
+            Admission::Wait(wait) => {
+                self.queued.fetch_add(1, Ordering::Relaxed);
+                self.queued_total.fetch_add(1, Ordering::Relaxed);
+                self.wait_micros_total.fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+                // Leaves the queue even if the client disconnects mid-wait
+                let _slot = QueueSlot(&self.queued);
+                tokio::time::sleep(wait).await;
+                Ok(())
+            }
+            Admission::Reject(retry) => {
+                self.rejected_total.fetch_add(1, Ordering::Relaxed);
+                Err(retry)
+            }
+        }
+    }
+
+    /// Requests currently waiting for a token
+    pub fn queue_depth(&self) -> u64 {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Requests that had to wait for a token
+    pub fn queued_total(&self) -> u64 {
+        self.queued_total.load(Ordering::Relaxed)
+    }
+
+    /// Total time requests spent waiting for a token
+    pub fn queue_wait_total(&self) -> Duration {
+        Duration::from_micros(self.wait_micros_total.load(Ordering::Relaxed))
+    }
+
+    /// Requests rejected with 429
+    pub fn rejected_total(&self) -> u64 {
+        self.rejected_total.load(Ordering::Relaxed)
+    }
+}
+
+struct QueueSlot<'a>(&'a AtomicU64);
+
+impl Drop for QueueSlot<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// How long admin-set rate limits are trusted before being reloaded, so changes made
+/// through another replica take effect
+const RATE_LIMIT_OVERRIDES_TTL: Duration = Duration::from_secs(60);
+
+/// Admin-set request rates for individual users, replacing `rate_limit.requests_per_sec`
+/// for requests made with their tokens. Every override is held in memory: there are few,
+/// and the rate limiter can't afford a database lookup per request.
+#[derive(Default)]
+pub struct RateLimitOverrides {
+    limits: DashMap<String, u32>,
+    loaded: Mutex<Option<Instant>>,
+}
+
+impl RateLimitOverrides {
+    /// The user's requests per second (0 is unlimited), or None to use the default
+    pub fn get(&self, user_id: &str) -> Option<u32> {
+        self.limits.get(user_id).map(|limit| *limit)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.limits.is_empty()
+    }
+
+    /// Apply a change made through this server right away
+    pub fn set(&self, user_id: &str, requests_per_sec: Option<u32>) {
+        match requests_per_sec {
+            Some(limit) => {
+                self.limits.insert(user_id.to_string(), limit);
+            }
+            None => {
+                self.limits.remove(user_id);
+            }
+        }
+    }
+
+    /// Whether the overrides are due to be reloaded; claims the reload so only one caller does it
+    pub fn claim_refresh(&self, now: Instant) -> bool {
+        let mut loaded = self.loaded.lock().unwrap();
+        if loaded.is_some_and(|at| now.saturating_duration_since(at) < RATE_LIMIT_OVERRIDES_TTL) {
+            return false;
+        }
+        *loaded = Some(now);
+        true
+    }
+
+    /// Replace every override with a fresh load from the database
+    pub fn replace(&self, overrides: impl IntoIterator<Item = (String, u32)>) {
+        let fresh: HashMap<String, u32> = overrides.into_iter().collect();
+        self.limits.retain(|user_id, _| fresh.contains_key(user_id));
+        for (user_id, limit) in fresh {
+            self.limits.insert(user_id, limit);
+        }
+    }
+}
+
+/// Enforce the per-client request rate, keyed by the forwarded origin or TCP peer.
+/// Users with an admin-set limit get their own bucket at that rate instead, wherever they
+/// connect from. Repository listings must also pass the tighter catalog limit when it is enabled.
+pub async fn rate_limit(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let performance = &state.performance;
+    let path = request.uri().path();
+    if RATE_LIMIT_EXEMPT.contains(&path) {
+        return next.run(request).await;
+    }
+
+    let overrides = &performance.rate_limit_overrides;
+    if overrides.claim_refresh(Instant::now()) {
+        match list_rate_limit_overrides(&state.database.pool).await {
+            Ok(loaded) => overrides.replace(loaded),
+            Err(e) => tracing::warn!("Failed to load rate limit overrides: {}", e),
+        }
+    }
+    let user_limit = if overrides.is_empty() {
+        None
+    } else {
+        bearer_subject(request.headers(), &state.jwt)
+            .await
+            .and_then(|user_id| overrides.get(&user_id).map(|limit| (user_id, limit)))
+    };
+
+    let general = &performance.request_limiter;
+    let catalog = &performance.catalog_limiter;
+    let is_catalog = catalog.enabled() && is_catalog_request(request.method(), path);
+    if !(general.enabled() || is_catalog || user_limit.is_some()) {
+        return next.run(request).await;
+    }
+
+    let client = forwarded_client_ip(request.headers())
+        .or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+
+    if is_catalog {
+        if let Err(retry) = catalog.acquire(&client).await {
+            return rate_limited(path, retry, "Catalog request rate limit exceeded");
+        }
+    }
+    let admitted = match &user_limit {
+        Some((user_id, limit)) => general.acquire_override(&format!("user:{}", user_id), *limit).await,
+        None => general.acquire(&client).await,
+    };
+    if let Err(retry) = admitted {
+        return rate_limited(path, retry, "Request rate limit exceeded");
+    }
+
+    next.run(request).await
+}
+
+fn is_catalog_request(method: &Method, path: &str) -> bool {
+    (method == Method::GET || method == Method::HEAD) && CATALOG_PATHS.contains(&path.trim_end_matches('/'))
+}
+
+/// `429` with `Retry-After`, in the registry error envelope for `/v2` clients
+fn rate_limited(path: &str, retry: Duration, message: &str) -> Response {
+    let retry_after = retry.as_secs_f64().ceil().max(1.0) as u64;
+
+    if path == "/v2" || path.starts_with("/v2/") {
+        let mut response = (
+            StatusCode::TOO_MANY_REQUESTS,
+            registry_error_body("TOOMANYREQUESTS", message, serde_json::json!({ "retry_after": retry_after })),
+        )
+            .into_response();
+        if let Ok(value) = HeaderValue::from_str(&retry_after.to_string()) {
+            response.headers_mut().insert(header::RETRY_AFTER, value);
+        }
+        return response;
+    }
+
+    Error::too_many_requests(message, retry_after).into_response()
+}
+
 impl PerformanceLayer {
     pub fn new() -> Self {
+        Self::with_concurrency(&ConcurrencyConfig::default())
+    }
+
+    pub fn with_concurrency(config: &ConcurrencyConfig) -> Self {
+        Self::with_config(config, &CacheConfig::default(), &BandwidthConfig::default(), &RateLimitConfig::default())
+    }
+
+    pub fn with_config(
+        concurrency: &ConcurrencyConfig,
+        cache: &CacheConfig,
+        bandwidth: &BandwidthConfig,
+        rate_limit: &RateLimitConfig,
+    ) -> Self {
         Self {
             connection_semaphore: Arc::new(Semaphore::new(1000)), // Max 1000 concurrent connections
             response_cache: Arc::new(DashMap::new()),
             rate_limiter: Arc::new(RwLock::new(RateLimiter::default())),
+            operation_limits: Arc::new(OperationLimits::new(concurrency)),
+            existence_cache: Arc::new(ExistenceCache::new(
+                Duration::from_millis(cache.existence_ttl_ms),
+                cache.existence_max_entries,
+            )),
+            catalog_cache: Arc::new(CatalogCache::new(cache.catalog_enabled)),
+            manifest_cache: Arc::new(ManifestCache::new(cache.manifest_cache_bytes as usize)),
+            download_limits: Arc::new(streaming::DownloadLimits::new(bandwidth)),
+            request_limiter: Arc::new(RequestLimiter::new(rate_limit)),
+            catalog_limiter: Arc::new(RequestLimiter::catalog(&rate_limit.catalog)),
+            rate_limit_overrides: Arc::new(RateLimitOverrides::default()),
+            tag_locks: Arc::new(TagLocks::new(concurrency)),
+            deployment_starts: Arc::new(AsyncMutex::new(())),
+            blob_reads: Arc::new(BlobReads::new(cache.coalesce_blob_reads)),
         }
     }
 
+    /// Acquire a permit for the given operation class
+    pub async fn acquire_operation(&self, class: OperationClass) -> Result<OwnedSemaphorePermit> {
+        self.operation_limits.acquire(class).await
+    }
+
     /// Get from cache or compute
     pub async fn get_or_compute<F, Fut, T>(&self, key: &str, compute: F) -> Option<T>
     where
@@ -145,8 +1132,17 @@ pub mod streaming {
         body::Body,
         response::Response,
     };
+    use bytes::Bytes;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tokio::time::Instant;
     use tokio_util::io::ReaderStream;
-    use futures::Stream;
+    use futures::{Stream, StreamExt};
+
+    use crate::config::BandwidthConfig;
+
+    /// Largest slice sent between throttle checks, so pacing stays smooth for big chunks
+    const THROTTLE_CHUNK_BYTES: usize = 64 * 1024;
     
     pub fn create_streaming_response<S>(stream: S) -> Response
     where
@@ -168,6 +1164,92 @@ pub mod streaming {
         
         Ok(create_streaming_response(reader_stream))
     }
+
+    /// Paces bytes to a fixed rate; shared between streams, it splits the rate among them
+    pub struct Throttle {
+        bytes_per_sec: u64,
+        /// When the bytes reserved so far will have been sent at the configured rate
+        next: Mutex<Instant>,
+    }
+
+    impl Throttle {
+        pub fn new(bytes_per_sec: u64) -> Self {
+            Self {
+                bytes_per_sec: bytes_per_sec.max(1),
+                next: Mutex::new(Instant::now()),
+            }
+        }
+
+        /// Reserve `len` bytes and return when they may be sent
+        /// Idle time is not banked, so a quiet period never turns into a burst.
+        pub fn reserve(&self, len: usize) -> Instant {
+            let mut next = self.next.lock().unwrap();
+            let start = (*next).max(Instant::now());
+            *next = start + Duration::from_secs_f64(len as f64 / self.bytes_per_sec as f64);
+            start
+        }
+    }
+
+    /// Per-client-class download limits plus the registry-wide one
+    pub struct DownloadLimits {
+        config: BandwidthConfig,
+        global: Option<Arc<Throttle>>,
+    }
+
+    impl DownloadLimits {
+        pub fn new(config: &BandwidthConfig) -> Self {
+            Self {
+                config: config.clone(),
+                global: (config.global_bytes_per_sec > 0).then(|| Arc::new(Throttle::new(config.global_bytes_per_sec))),
+            }
+        }
+
+        /// Throttles for one new download; empty when nothing applies
+        pub fn throttles(&self, authenticated: bool) -> Vec<Arc<Throttle>> {
+            let per_connection = if authenticated {
+                self.config.authenticated_bytes_per_sec
+            } else {
+                self.config.anonymous_bytes_per_sec
+            };
+
+            (per_connection > 0)
+                .then(|| Arc::new(Throttle::new(per_connection)))
+                .into_iter()
+                .chain(self.global.clone())
+                .collect()
+        }
+    }
+
+    /// Wrap a body stream so every chunk waits for all of `throttles`
+    pub fn throttle<S>(stream: S, throttles: Vec<Arc<Throttle>>) -> impl Stream<Item = Result<Bytes, std::io::Error>> + Send
+    where
+        S: Stream<Item = Result<Bytes, std::io::Error>> + Send,
+    {
+        stream
+            .flat_map(|item| futures::stream::iter(split_chunk(item)))
+            .then(move |item| {
+                let deadline = match &item {
+                    Ok(chunk) => throttles.iter().map(|t| t.reserve(chunk.len())).max(),
+                    Err(_) => None,
+                };
+                async move {
+                    if let Some(deadline) = deadline {
+                        tokio::time::sleep_until(deadline).await;
+                    }
+                    item
+                }
+            })
+    }
+
+    fn split_chunk(item: Result<Bytes, std::io::Error>) -> Vec<Result<Bytes, std::io::Error>> {
+        match item {
+            Ok(chunk) => (0..chunk.len())
+                .step_by(THROTTLE_CHUNK_BYTES)
+                .map(|start| Ok(chunk.slice(start..(start + THROTTLE_CHUNK_BYTES).min(chunk.len()))))
+                .collect(),
+            Err(e) => vec![Err(e)],
+        }
+    }
 }
 
 #[cfg(test)]
@@ -188,4 +1270,367 @@ mod tests {
         }
         assert!(!perf_layer.check_rate_limit("127.0.0.1", 5, Duration::from_secs(60)).await);
     }
+
+    #[test]
+    fn test_request_limiter_queues_within_bound() {
+        let limiter = RequestLimiter::new(&RateLimitConfig {
+            requests_per_sec: 10,
+            burst: 2,
+            max_queue_wait_ms: 250,
+            max_queued: 16,
+            ..RateLimitConfig::default()
+        });
+        let now = Instant::now();
+        let admit = |at: Instant| match limiter.admit("10.0.0.1", at) {
+            Admission::Now => (0, 0),
+            Admission::Wait(wait) => (1, wait.as_millis()),
+            Admission::Reject(retry) => (2, retry.as_millis()),
+        };
+
+        // Burst first, then waits that grow by one token interval each
+        assert_eq!(admit(now), (0, 0));
+        assert_eq!(admit(now), (0, 0));
+        assert_eq!(admit(now), (1, 100));
+        assert_eq!(admit(now), (1, 200));
+        // A third queued request would wait past the bound
+        assert_eq!(admit(now), (2, 300));
+        // Other clients have their own bucket
+        assert_eq!(limiter.admit("10.0.0.2", now), Admission::Now);
+        // The bucket refills, but only up to the burst
+        assert_eq!(admit(now + Duration::from_secs(5)), (0, 0));
+        assert_eq!(admit(now + Duration::from_secs(5)), (0, 0));
+        assert_eq!(admit(now + Duration::from_secs(5)), (1, 100));
+    }
+
+    #[test]
+    fn test_rate_limit_overrides() {
+        // Rate limiting is off by default; an override still applies to its user
+        let limiter = RequestLimiter::new(&RateLimitConfig { burst: 2, max_queue_wait_ms: 0, ..RateLimitConfig::default() });
+        let now = Instant::now();
+        assert_eq!(limiter.admit("10.0.0.1", now), Admission::Now);
+
+        // The burst covers at least one second at the override rate
+        for _ in 0..5 {
+            assert_eq!(limiter.admit_override("user:ci", 5, now), Admission::Now);
+        }
+        assert!(matches!(limiter.admit_override("user:ci", 5, now), Admission::Reject(_)));
+        assert_eq!(limiter.admit_override("user:ci", 5, now + Duration::from_millis(200)), Admission::Now);
+        // 0 is unlimited
+        for _ in 0..100 {
+            assert_eq!(limiter.admit_override("user:trusted", 0, now), Admission::Now);
+        }
+
+        let overrides = RateLimitOverrides::default();
+        assert!(overrides.claim_refresh(now));
+        assert!(!overrides.claim_refresh(now + Duration::from_secs(1)));
+        assert!(overrides.claim_refresh(now + RATE_LIMIT_OVERRIDES_TTL));
+
+        overrides.replace([("ci".to_string(), 500), ("bot".to_string(), 1)]);
+        overrides.set("bot", None);
+        overrides.set("alice", Some(0));
+        assert_eq!((overrides.get("ci"), overrides.get("bot"), overrides.get("alice")), (Some(500), None, Some(0)));
+        // A reload drops overrides cleared elsewhere
+        overrides.replace([("ci".to_string(), 50)]);
+        assert_eq!((overrides.get("ci"), overrides.get("alice")), (Some(50), None));
+    }
+
+    #[tokio::test]
+    async fn test_tag_locks_serialize_pushes_in_arrival_order() {
+        let locks = Arc::new(TagLocks::new(&ConcurrencyConfig::default()));
+        let applied = Arc::new(Mutex::new(Vec::new()));
+        let in_flight = Arc::new(AtomicU64::new(0));
+
+        // Hold the tag so every pusher queues, then release them all at once
+        let first = locks.lock("team/app", "latest").await.unwrap();
+        let mut pushes = Vec::new();
+        for i in 0..32 {
+            let (locks, applied, in_flight) = (Arc::clone(&locks), Arc::clone(&applied), Arc::clone(&in_flight));
+            pushes.push(tokio::spawn(async move {
+                let _guard = locks.lock("team/app", "latest").await.unwrap();
+                assert_eq!(in_flight.fetch_add(1, Ordering::SeqCst), 0, "two pushes held the tag at once");
+                tokio::task::yield_now().await;
+                applied.lock().unwrap().push(i);
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+            }));
+            // Let this pusher join the queue before the next one arrives
+            while locks.contended() < i + 1 {
+                tokio::task::yield_now().await;
+            }
+        }
+        // Other tags aren't blocked
+        drop(locks.lock("team/app", "stable").await.unwrap());
+        drop(first);
+
+        for push in pushes {
+            push.await.unwrap();
+        }
+        assert_eq!(*applied.lock().unwrap(), (0..32).collect::<Vec<_>>());
+        assert_eq!(locks.tracked(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_tag_lock_timeout() {
+        let config = ConcurrencyConfig {
+            tag_lock_timeout_ms: 0,
+            ..ConcurrencyConfig::default()
+        };
+        let locks = TagLocks::new(&config);
+
+        let held = locks.lock("team/app", "latest").await.unwrap();
+        let err = locks.lock("team/app", "latest").await.err().unwrap();
+        assert_eq!(err.status_code(), axum::http::StatusCode::SERVICE_UNAVAILABLE);
+        drop(held);
+
+        assert_eq!(locks.tracked(), 0);
+        assert!(locks.lock("team/app", "latest").await.is_ok());
+    }
+
+    #[test]
+    fn test_catalog_limiter_rejects_without_queueing() {
+        let limiter = RequestLimiter::catalog(&CatalogRateLimitConfig {
+            requests_per_min: 6,
+            burst: 2,
+        });
+        let now = Instant::now();
+
+        assert_eq!(limiter.admit("10.0.0.1", now), Admission::Now);
+        assert_eq!(limiter.admit("10.0.0.1", now), Admission::Now);
+        // One token every 10s, and the over-limit request is told to come back then
+        assert_eq!(limiter.admit("10.0.0.1", now), Admission::Reject(Duration::from_secs(10)));
+        assert_eq!(limiter.admit("10.0.0.1", now + Duration::from_secs(10)), Admission::Now);
+
+        assert!(!RequestLimiter::catalog(&CatalogRateLimitConfig::default()).enabled());
+        assert!(is_catalog_request(&Method::GET, "/v2/_catalog"));
+        assert!(is_catalog_request(&Method::GET, "/api/repositories/"));
+        assert!(!is_catalog_request(&Method::POST, "/api/repositories"));
+        assert!(!is_catalog_request(&Method::GET, "/api/repositories/team/app"));
+    }
+
+    #[tokio::test]
+    async fn test_pull_permit_timeout() {
+        let config = ConcurrencyConfig {
+            max_pulls: 1,
+            pull_acquire_timeout_ms: 10,
+            ..ConcurrencyConfig::default()
+        };
+        let perf_layer = PerformanceLayer::with_concurrency(&config);
+
+        let permit = perf_layer.acquire_operation(OperationClass::Pull).await.unwrap();
+        assert_eq!(perf_layer.operation_limits.in_flight(OperationClass::Pull), 1);
+
+        let err = perf_layer.acquire_operation(OperationClass::Pull).await.unwrap_err();
+        assert_eq!(err.status_code(), axum::http::StatusCode::SERVICE_UNAVAILABLE);
+
+        drop(permit);
+        assert_eq!(perf_layer.operation_limits.in_flight(OperationClass::Pull), 0);
+    }
+
+    #[tokio::test]
+    async fn test_existence_cache_negative_entry_invalidated() {
+        let cache = ExistenceCache::new(Duration::from_secs(60), 1024);
+        let key = ExistenceCache::blob_key("team/app", "sha256:abc");
+
+        let missing = cache.get_or_lookup(&key, || async { Ok(None) }).await.unwrap();
+        assert_eq!(missing, None);
+        // Served from the negative entry without running the lookup
+        let cached = cache
+            .get_or_lookup(&key, || async { panic!("lookup should be cached") })
+            .await
+            .unwrap();
+        assert_eq!(cached, None);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // Storing the blob clears the negative entry immediately
+        cache.invalidate(&key);
+        let info = ExistenceInfo {
+            digest: "sha256:abc".to_string(),
+            media_type: "application/octet-stream".to_string(),
+            size: 3,
+        };
+        let found = cache
+            .get_or_lookup(&key, || async { Ok(Some(info.clone())) })
+            .await
+            .unwrap();
+        assert_eq!(found, Some(info));
+    }
+
+    fn cached_manifest(digest: &str, size: usize) -> CachedManifest {
+        CachedManifest {
+            repository_id: Uuid::nil(),
+            digest: digest.to_string(),
+            media_type: "application/vnd.oci.image.manifest.v1+json".to_string(),
+            content: Bytes::from(vec![b'x'; size]),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_manifest_cache_serves_digest_pulls_without_loading() {
+        let cache = ManifestCache::new(1024);
+        let key = ManifestCache::key("team/app", "sha256:abc");
+
+        let loaded = cache.get_or_load(&key, || async { Ok(cached_manifest("sha256:abc", 100)) }).await.unwrap();
+        // The second pull never reaches the loader (and so the database)
+        let cached = cache
+            .get_or_load(&key, || async { panic!("digest pull should be cached") })
+            .await
+            .unwrap();
+        assert_eq!(cached, loaded);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        cache.invalidate(&key);
+        assert_eq!(cache.size_bytes(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_cache_evicts_least_recently_used() {
+        let cache = ManifestCache::new(250);
+        cache.insert("app@sha256:a", cached_manifest("sha256:a", 100));
+        cache.insert("app@sha256:b", cached_manifest("sha256:b", 100));
+
+        // Touch `a` so `b` becomes the eviction candidate
+        cache.get_or_load("app@sha256:a", || async { panic!("cached") }).await.unwrap();
+        cache.insert("app@sha256:c", cached_manifest("sha256:c", 100));
+        assert_eq!(cache.size_bytes(), 200);
+
+        let reloaded = cache.get_or_load("app@sha256:b", || async { Ok(cached_manifest("sha256:b", 100)) }).await.unwrap();
+        assert_eq!(reloaded.digest, "sha256:b");
+        assert_eq!(cache.misses(), 1);
+
+        // Larger than the whole cache: served but never stored
+        cache.insert("app@sha256:big", cached_manifest("sha256:big", 500));
+        assert!(cache.size_bytes() <= 250);
+    }
+
+    #[tokio::test]
+    async fn test_catalog_cache_invalidated_on_change() {
+        let cache = CatalogCache::new(true);
+
+        let body = cache.get_or_render("anonymous", || async { Ok(Bytes::from_static(b"[]")) }).await.unwrap();
+        assert_eq!(body, Bytes::from_static(b"[]"));
+        let cached = cache
+            .get_or_render("anonymous", || async { panic!("listing should be cached") })
+            .await
+            .unwrap();
+        assert_eq!(cached, body);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // A push creating a repository makes the next listing fresh
+        cache.invalidate();
+        let fresh = cache
+            .get_or_render("anonymous", || async { Ok(Bytes::from_static(br#"[{"name":"app"}]"#)) })
+            .await
+            .unwrap();
+        assert_eq!(fresh, Bytes::from_static(br#"[{"name":"app"}]"#));
+        assert_eq!(cache.invalidations(), 1);
+
+        // A render that overlaps an invalidation is not cached
+        let cache = CatalogCache::new(true);
+        cache
+            .get_or_render("anonymous", || async {
+                cache.invalidate();
+                Ok(Bytes::from_static(b"[]"))
+            })
+            .await
+            .unwrap();
+        assert_eq!(cache.misses(), 1);
+        cache.get_or_render("anonymous", || async { Ok(Bytes::new()) }).await.unwrap();
+        assert_eq!(cache.misses(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_existence_cache_is_bounded() {
+        let cache = ExistenceCache::new(Duration::from_millis(20), 2);
+        for digest in ["sha256:a", "sha256:b", "sha256:c"] {
+            let key = ExistenceCache::blob_key("team/app", digest);
+            cache.get_or_lookup(&key, || async { Ok(None) }).await.unwrap();
+        }
+        assert_eq!(cache.entry_count(), 2);
+
+        // Once the TTL has passed, a full cache sweeps its expired entries to make room
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let key = ExistenceCache::blob_key("team/app", "sha256:d");
+        cache.get_or_lookup(&key, || async { Ok(None) }).await.unwrap();
+        assert_eq!(cache.entry_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_existence_cache_skips_stale_insert() {
+        let cache = ExistenceCache::new(Duration::from_secs(60), 1024);
+        let key = ExistenceCache::manifest_key("team/app", "latest");
+
+        // A write lands while the lookup is in flight; its stale result is not cached
+        let result = cache
+            .get_or_lookup(&key, || async {
+                cache.invalidate(&key);
+                Ok(None)
+            })
+            .await
+            .unwrap();
+        assert_eq!(result, None);
+        assert!(cache.entries.get(&key).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_download_throttle_paces_chunks() {
+        let limits = streaming::DownloadLimits::new(&BandwidthConfig {
+            anonymous_bytes_per_sec: 1024,
+            authenticated_bytes_per_sec: 0,
+            global_bytes_per_sec: 4096,
+        });
+        assert_eq!(limits.throttles(false).len(), 2);
+        assert_eq!(limits.throttles(true).len(), 1);
+        assert!(streaming::DownloadLimits::new(&BandwidthConfig::default()).throttles(false).is_empty());
+
+        let throttle = streaming::Throttle::new(1024);
+        let first = throttle.reserve(512);
+        let second = throttle.reserve(512);
+        let third = throttle.reserve(1);
+        assert_eq!(second - first, Duration::from_millis(500));
+        assert_eq!(third - second, Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_blob_reads_share_one_read() {
+        let reads = Arc::new(BlobReads::new(true));
+        let (release, released) = tokio::sync::watch::channel(false);
+
+        // Twenty pulls of one blob arrive while the first storage read is still running
+        let pulls: Vec<_> = (0..20)
+            .map(|_| {
+                let reads = Arc::clone(&reads);
+                let mut released = released.clone();
+                tokio::spawn(async move {
+                    reads
+                        .get_or_read("sha256:abc", || async move {
+                            released.wait_for(|done| *done).await.unwrap();
+                            Ok(Some(Bytes::from_static(b"layer")))
+                        })
+                        .await
+                })
+            })
+            .collect();
+        while reads.reads() == 0 {
+            tokio::task::yield_now().await;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        release.send(true).unwrap();
+
+        for pull in pulls {
+            assert_eq!(pull.await.unwrap().unwrap(), Some(Bytes::from_static(b"layer")));
+        }
+        assert_eq!(reads.reads(), 1);
+        assert_eq!(reads.coalesced(), 19);
+        assert!(reads.in_flight.is_empty());
+
+        // Once finished, the next pull reads again instead of reusing the buffer
+        reads.get_or_read("sha256:abc", || async { Ok(None) }).await.unwrap();
+        assert_eq!(reads.reads(), 2);
+
+        let disabled = BlobReads::new(false);
+        let err = disabled
+            .get_or_read("sha256:abc", || async { Err(Error::Storage { message: "gone".to_string() }) })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, Error::Storage { .. }));
+    }
 }
@@ -0,0 +1,709 @@
+//! Promotion requests: copying an image into another repository or tag, e.g. `app-staging:1.4`
+//! to `app:1.4`, only once an approver signs off.
+//!
+//! The source is pinned to a digest when the request is made, so approvers review exactly what
+//! will be copied even if the source tag moves on before they get to it.
+
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use std::collections::HashSet;
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::{may_access, AuthenticatedUser},
+    config::PromotionConfig,
+    database::queries::{
+        get_allowed_artifact_types, get_blob_by_digest, get_manifest_by_digest, get_manifest_by_tag,
+        get_repository_by_name, link_manifest_to_blob, record_audit, record_blob, store_manifest, tag_manifest,
+    },
+    error::{Error, Result},
+    handlers::manifest::check_artifact_type_allowed,
+    performance::OperationClass,
+    server::AppState,
+    types::{ManifestType, Page, PageQuery},
+    utils::{manifest_list_children, validate_digest, validate_repository_name, validate_tag_name},
+    webhooks::{self, RepositoryEvent},
+    websocket::{Notification, NotificationSeverity},
+};
+
+/// Lifecycle of a promotion request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PromotionStatus {
+    Pending,
+    /// Approved and being copied
+    Approved,
+    Rejected,
+    Completed,
+    /// Approved, but the copy failed; see `error`
+    Failed,
+}
+
+impl PromotionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PromotionStatus::Pending => "pending",
+            PromotionStatus::Approved => "approved",
+            PromotionStatus::Rejected => "rejected",
+            PromotionStatus::Completed => "completed",
+            PromotionStatus::Failed => "failed",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "pending" => Some(PromotionStatus::Pending),
+            "approved" => Some(PromotionStatus::Approved),
+            "rejected" => Some(PromotionStatus::Rejected),
+            "completed" => Some(PromotionStatus::Completed),
+            "failed" => Some(PromotionStatus::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// A request to copy a manifest into a destination repository and tag
+#[derive(Debug, Clone, Serialize)]
+pub struct Promotion {
+    pub id: Uuid,
+    pub source_repository: String,
+    /// Tag or digest the requester named
+    pub source_reference: String,
+    /// What the reference resolved to when requested; this is what gets copied
+    pub digest: String,
+    pub destination_repository: String,
+    pub destination_tag: String,
+    pub status: PromotionStatus,
+    pub requested_by: String,
+    pub requested_at: DateTime<Utc>,
+    pub comment: Option<String>,
+    pub reviewed_by: Option<String>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+    pub review_comment: Option<String>,
+    /// Why the copy failed, when status is `failed`
+    pub error: Option<String>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+impl Promotion {
+    fn destination(&self) -> String {
+        format!("{}:{}", self.destination_repository, self.destination_tag)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PromoteRequest {
+    /// Tag or digest in the source repository
+    pub reference: String,
+    pub destination_repository: String,
+    /// Defaults to the source tag; required when promoting by digest
+    pub destination_tag: Option<String>,
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ReviewRequest {
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PromotionQuery {
+    pub status: Option<PromotionStatus>,
+    /// Only promotions into this repository
+    pub repository: Option<String>,
+}
+
+/// Promotion routes
+pub fn promotion_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/repositories/:name/promote", post(request_promotion))
+        .route("/api/promotions", get(list_promotions))
+        .route("/api/promotions/:id", get(get_promotion))
+        .route("/api/promotions/:id/approve", post(approve_promotion))
+        .route("/api/promotions/:id/reject", post(reject_promotion))
+}
+
+/// Ask for an image in `name` to be promoted; approvers are notified
+async fn request_promotion(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    user: AuthenticatedUser,
+    Json(request): Json<PromoteRequest>,
+) -> Result<(StatusCode, Json<Promotion>)> {
+    validate_repository_name(&name)?;
+    validate_repository_name(&request.destination_repository)?;
+    if user.is_share() || !may_access(&state, Some(&user), &name, "pull").await? {
+        return Err(Error::authorization(format!("Not permitted to promote from repository '{}'", name)));
+    }
+
+    let by_digest = request.reference.starts_with("sha256:");
+    let destination_tag = match request.destination_tag {
+        Some(tag) => tag,
+        None if !by_digest => request.reference.clone(),
+        None => return Err(Error::bad_request("destination_tag is required when promoting by digest")),
+    };
+    validate_tag_name(&destination_tag)?;
+    if name == request.destination_repository && request.reference == destination_tag {
+        return Err(Error::bad_request("A tag can't be promoted onto itself"));
+    }
+
+    let source = get_repository_by_name(&state, &name).await?;
+    let destination = get_repository_by_name(&state, &request.destination_repository).await?;
+    let manifest = if by_digest {
+        validate_digest(&request.reference)?;
+        get_manifest_by_digest(&state, &source.id, &request.reference).await?
+    } else {
+        validate_tag_name(&request.reference)?;
+        get_manifest_by_tag(&state, &source.id, &request.reference).await?
+    };
+
+    let pool = &state.database.pool;
+    let duplicate: Option<Uuid> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM promotions
+        WHERE status = 'pending' AND digest = $1 AND destination_repository = $2 AND destination_tag = $3
+        "#
+    )
+    .bind(&manifest.digest)
+    .bind(&request.destination_repository)
+    .bind(&destination_tag)
+    .fetch_optional(pool)
+    .await?;
+    if let Some(existing) = duplicate {
+        return Err(Error::conflict(format!("Promotion {} already requests this", existing)));
+    }
+
+    let promotion = Promotion {
+        id: Uuid::new_v4(),
+        source_repository: name,
+        source_reference: request.reference,
+        digest: manifest.digest,
+        destination_repository: request.destination_repository,
+        destination_tag,
+        status: PromotionStatus::Pending,
+        requested_by: user.id.clone(),
+        requested_at: Utc::now(),
+        comment: request.comment,
+        reviewed_by: None,
+        reviewed_at: None,
+        review_comment: None,
+        error: None,
+        completed_at: None,
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO promotions (
+            id, source_repository, source_reference, digest, destination_repository, destination_tag,
+            status, requested_by, requested_at, comment
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#
+    )
+    .bind(promotion.id)
+    .bind(&promotion.source_repository)
+    .bind(&promotion.source_reference)
+    .bind(&promotion.digest)
+    .bind(&promotion.destination_repository)
+    .bind(&promotion.destination_tag)
+    .bind(promotion.status.as_str())
+    .bind(&promotion.requested_by)
+    .bind(promotion.requested_at)
+    .bind(&promotion.comment)
+    .execute(pool)
+    .await?;
+
+    record_audit(&state, &user.id, "promotion.request", &promotion.destination(), Some(json!({
+        "promotion_id": promotion.id,
+        "source": format!("{}@{}", promotion.source_repository, promotion.digest),
+    })))
+    .await?;
+
+    let recipients = approver_ids(pool, &state.config.promotions, &destination.id).await?;
+    let message = format!(
+        "{} asks to promote {}:{} to {}",
+        user.name,
+        promotion.source_repository,
+        promotion.source_reference,
+        promotion.destination()
+    );
+    for user_id in recipients.into_iter().filter(|id| *id != user.id) {
+        notify(&state, user_id, "Promotion awaiting approval", message.clone(), NotificationSeverity::Info).await;
+    }
+
+    Ok((StatusCode::CREATED, Json(promotion)))
+}
+
+/// List promotions, newest first. Admins and approver team members see every promotion;
+/// others see the ones they requested and the ones into repositories they administer.
+async fn list_promotions(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Query(query): Query<PromotionQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<Page<Promotion>>> {
+    let pool = &state.database.pool;
+    let sees_all = is_registry_approver(pool, &state.config.promotions, &user).await?;
+    let administered = serde_json::to_string(&administered_repositories(&user))?;
+    let (limit, offset) = page.bounds()?;
+
+    let filter = r#"
+        WHERE ($1 OR requested_by = $2 OR destination_repository IN (SELECT value FROM json_each($3)))
+          AND ($4 IS NULL OR status = $4)
+          AND ($5 IS NULL OR destination_repository = $5)
+    "#;
+
+    let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM promotions {}", filter))
+        .bind(sees_all)
+        .bind(&user.id)
+        .bind(&administered)
+        .bind(query.status.map(|s| s.as_str()))
+        .bind(&query.repository)
+        .fetch_one(pool)
+        .await?;
+
+    let promotions = sqlx::query(&format!(
+        "SELECT * FROM promotions {} ORDER BY requested_at DESC LIMIT $6 OFFSET $7",
+        filter
+    ))
+    .bind(sees_all)
+    .bind(&user.id)
+    .bind(&administered)
+    .bind(query.status.map(|s| s.as_str()))
+    .bind(&query.repository)
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(pool)
+    .await?
+    .iter()
+    .map(promotion_from_row)
+    .collect();
+
+    Ok(Json(Page::new(promotions, total as u64, limit, offset)))
+}
+
+/// One promotion, visible to its requester and anyone who may review it
+async fn get_promotion(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<Json<Promotion>> {
+    let promotion = load(&state.database.pool, id).await?;
+    if promotion.requested_by != user.id
+        && !is_approver(&state.database.pool, &state.config.promotions, &user, &promotion.destination_repository).await?
+    {
+        return Err(Error::not_found(format!("Promotion {} not found", id)));
+    }
+    Ok(Json(promotion))
+}
+
+/// Approve a pending promotion and copy the image into its destination
+async fn approve_promotion(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+    review: Option<Json<ReviewRequest>>,
+) -> Result<Json<Promotion>> {
+    let review = review.map(|Json(r)| r).unwrap_or_default();
+    let promotion = decide(&state, id, &user, PromotionStatus::Approved, review.comment).await?;
+
+    let (status, error) = {
+        let _permit = state.performance.acquire_operation(OperationClass::ManifestWrite).await?;
+        match copy_image(
+            &state,
+            &promotion.source_repository,
+            &promotion.digest,
+            &promotion.destination_repository,
+            &promotion.destination_tag,
+            &user.name,
+        )
+        .await
+        {
+            Ok(()) => (PromotionStatus::Completed, None),
+            Err(e) => {
+                tracing::warn!("Promotion {} to {} failed: {}", id, promotion.destination(), e);
+                (PromotionStatus::Failed, Some(e.to_string()))
+            }
+        }
+    };
+
+    sqlx::query("UPDATE promotions SET status = $1, error = $2, completed_at = $3 WHERE id = $4")
+        .bind(status.as_str())
+        .bind(&error)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&state.database.pool)
+        .await?;
+
+    let (title, message, severity) = match &error {
+        None => (
+            "Promotion completed",
+            format!("{} approved {}; it now points at {}", user.name, promotion.destination(), promotion.digest),
+            NotificationSeverity::Success,
+        ),
+        Some(error) => (
+            "Promotion failed",
+            format!("{} approved {}, but the copy failed: {}", user.name, promotion.destination(), error),
+            NotificationSeverity::Error,
+        ),
+    };
+    notify(&state, promotion.requested_by.clone(), title, message, severity).await;
+
+    Ok(Json(load(&state.database.pool, id).await?))
+}
+
+/// Reject a pending promotion
+async fn reject_promotion(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+    review: Option<Json<ReviewRequest>>,
+) -> Result<Json<Promotion>> {
+    let review = review.map(|Json(r)| r).unwrap_or_default();
+    let promotion = decide(&state, id, &user, PromotionStatus::Rejected, review.comment).await?;
+
+    let mut message = format!("{} rejected the promotion to {}", user.name, promotion.destination());
+    if let Some(comment) = &promotion.review_comment {
+        message.push_str(&format!(": {}", comment));
+    }
+    notify(&state, promotion.requested_by.clone(), "Promotion rejected", message, NotificationSeverity::Warning).await;
+
+    Ok(Json(promotion))
+}
+
+/// Move a pending promotion to `status` on behalf of an approver. The status check is part of
+/// the update, so two reviewers racing on one request can't both decide it.
+async fn decide(
+    state: &AppState,
+    id: Uuid,
+    user: &AuthenticatedUser,
+    status: PromotionStatus,
+    comment: Option<String>,
+) -> Result<Promotion> {
+    let pool = &state.database.pool;
+    let config = &state.config.promotions;
+    let promotion = load(pool, id).await?;
+
+    if !is_approver(pool, config, user, &promotion.destination_repository).await? {
+        return Err(Error::authorization(format!(
+            "Not permitted to review promotions into '{}'",
+            promotion.destination_repository
+        )));
+    }
+    if promotion.requested_by == user.id && !config.allow_self_approval {
+        return Err(Error::authorization("Promotions must be reviewed by someone other than the requester"));
+    }
+
+    let updated = sqlx::query(
+        r#"
+        UPDATE promotions SET status = $1, reviewed_by = $2, reviewed_at = $3, review_comment = $4
+        WHERE id = $5 AND status = 'pending'
+        "#
+    )
+    .bind(status.as_str())
+    .bind(&user.id)
+    .bind(Utc::now())
+    .bind(&comment)
+    .bind(id)
+    .execute(pool)
+    .await?;
+    if updated.rows_affected() == 0 {
+        return Err(Error::conflict(format!("Promotion {} is no longer pending", id)));
+    }
+
+    let action = if status == PromotionStatus::Approved { "promotion.approve" } else { "promotion.reject" };
+    record_audit(state, &user.id, action, &promotion.destination(), Some(json!({ "promotion_id": id }))).await?;
+
+    load(pool, id).await
+}
+
+/// Copy a manifest, every manifest it lists and their blobs into `destination`, then point
+/// `tag` at it. Blobs are stored once per digest, so nothing is re-uploaded: the destination
+/// is only linked to the existing objects.
+pub async fn copy_image(
+    state: &AppState,
+    source: &str,
+    digest: &str,
+    destination: &str,
+    tag: &str,
+    actor: &str,
+) -> Result<()> {
+    let pool = &state.database.pool;
+    let source_repo = get_repository_by_name(state, source).await?;
+    let destination_repo = get_repository_by_name(state, destination).await?;
+    let allowed_types = get_allowed_artifact_types(state, &destination_repo.id).await?;
+
+    // Parents come before the manifests they list, so storing in reverse keeps lists valid
+    let mut manifests = Vec::new();
+    let mut seen = HashSet::new();
+    let mut queue = vec![digest.to_string()];
+    while let Some(next) = queue.pop() {
+        if !seen.insert(next.clone()) {
+            continue;
+        }
+        let manifest = get_manifest_by_digest(state, &source_repo.id, &next).await?;
+        queue.extend(manifest_list_children(&manifest.media_type, &manifest.content));
+        manifests.push(manifest);
+    }
+
+    let mut top = None;
+    for manifest in manifests.iter().rev() {
+        let parsed = ManifestType::parse(&manifest.content, Some(&manifest.media_type))?;
+        if manifest.digest == digest {
+            check_artifact_type_allowed(destination, allowed_types.as_deref(), parsed.artifact_type())?;
+        }
+
+        for blob_digest in parsed.blob_digests() {
+            let blob = get_blob_by_digest(state, &source_repo.id, blob_digest).await?;
+            record_blob(state, &destination_repo, blob_digest, blob.size).await?;
+        }
+        let manifest_id = store_manifest(
+            pool,
+            &destination_repo.id,
+            &manifest.digest,
+            &manifest.media_type,
+            &manifest.content,
+            parsed.artifact_type(),
+        )
+        .await?;
+        for blob_digest in parsed.blob_digests() {
+            link_manifest_to_blob(state, manifest_id, blob_digest).await?;
+        }
+
+        if manifest.digest == digest {
+            top = Some((manifest_id, manifest.media_type.clone()));
+        }
+    }
+    let (manifest_id, media_type) = top.ok_or_else(|| Error::not_found(format!("Manifest '{}' not found", digest)))?;
+
+    {
+        let _lock = state.performance.tag_locks.lock(destination, tag).await?;
+        tag_manifest(pool, &destination_repo.id, tag, manifest_id).await?;
+    }
+    state.performance.existence_cache.invalidate_manifests(destination);
+    state.performance.catalog_cache.invalidate();
+
+    let event = RepositoryEvent {
+        event: "push",
+        repository: destination.to_string(),
+        tag: Some(tag.to_string()),
+        digest: digest.to_string(),
+        media_type: Some(media_type),
+        actor: Some(actor.to_string()),
+        timestamp: Utc::now(),
+    };
+    if let Err(e) = webhooks::dispatch(state, &destination_repo.id, event).await {
+        tracing::warn!("Failed to dispatch push webhooks for {}: {}", destination, e);
+    }
+
+    Ok(())
+}
+
+/// Whether `user` may review promotions into `destination`: registry admins, members of
+/// `[promotions] approver_teams`, and admins of the destination repository
+pub async fn is_approver(
+    pool: &SqlitePool,
+    config: &PromotionConfig,
+    user: &AuthenticatedUser,
+    destination: &str,
+) -> Result<bool> {
+    if user.is_robot() || user.is_share() {
+        return Ok(false);
+    }
+    if administered_repositories(user).contains(&destination) {
+        return Ok(true);
+    }
+    is_registry_approver(pool, config, user).await
+}
+
+/// Registry admins and approver team members review promotions into any repository
+async fn is_registry_approver(pool: &SqlitePool, config: &PromotionConfig, user: &AuthenticatedUser) -> Result<bool> {
+    if user.is_robot() || user.is_share() {
+        return Ok(false);
+    }
+    if user.scopes.iter().any(|s| s == "admin") {
+        return Ok(true);
+    }
+    if config.approver_teams.is_empty() {
+        return Ok(false);
+    }
+
+    let teams: Vec<String> = sqlx::query_scalar(
+        "SELECT t.name FROM teams t JOIN team_members m ON m.team_id = t.id WHERE m.user_id = $1"
+    )
+    .bind(&user.id)
+    .fetch_all(pool)
+    .await?;
+    Ok(teams.iter().any(|team| config.approver_teams.contains(team)))
+}
+
+/// Repositories the principal holds admin permission on
+fn administered_repositories(user: &AuthenticatedUser) -> Vec<&str> {
+    user.scopes
+        .iter()
+        .filter_map(|s| s.strip_prefix("repository:")?.strip_suffix(":*"))
+        .collect()
+}
+
+/// Active users who may approve promotions into a repository
+async fn approver_ids(pool: &SqlitePool, config: &PromotionConfig, destination_id: &Uuid) -> Result<Vec<String>> {
+    let mut ids: HashSet<String> = sqlx::query_scalar(
+        r#"
+        SELECT id FROM users
+        WHERE is_active = TRUE AND (
+            is_admin = TRUE
+            OR id IN (SELECT user_id FROM repository_permissions WHERE repository_id = $1 AND permission = 'admin')
+            OR id IN (
+                SELECT m.user_id FROM team_members m
+                JOIN repository_permissions p ON p.team_id = m.team_id
+                WHERE p.repository_id = $1 AND p.permission = 'admin'
+            )
+        )
+        "#
+    )
+    .bind(destination_id)
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .collect();
+
+    for team in &config.approver_teams {
+        let members: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT m.user_id FROM team_members m
+            JOIN teams t ON t.id = m.team_id
+            JOIN users u ON u.id = m.user_id
+            WHERE t.name = $1 AND u.is_active = TRUE
+            "#
+        )
+        .bind(team)
+        .fetch_all(pool)
+        .await?;
+        ids.extend(members);
+    }
+
+    Ok(ids.into_iter().collect())
+}
+
+async fn notify(state: &AppState, user_id: String, title: &str, message: String, severity: NotificationSeverity) {
+    let notification = Notification {
+        id: Uuid::new_v4().to_string(),
+        title: title.to_string(),
+        message,
+        severity,
+        timestamp: Utc::now(),
+        read: false,
+    };
+    state.websocket.broadcast_notification(user_id, notification).await;
+}
+
+async fn load(pool: &SqlitePool, id: Uuid) -> Result<Promotion> {
+    sqlx::query("SELECT * FROM promotions WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .as_ref()
+        .map(promotion_from_row)
+        .ok_or_else(|| Error::not_found(format!("Promotion {} not found", id)))
+}
+
+fn promotion_from_row(row: &SqliteRow) -> Promotion {
+    Promotion {
+        id: row.get("id"),
+        source_repository: row.get("source_repository"),
+        source_reference: row.get("source_reference"),
+        digest: row.get("digest"),
+        destination_repository: row.get("destination_repository"),
+        destination_tag: row.get("destination_tag"),
+        status: PromotionStatus::parse(row.get::<String, _>("status").as_str()).unwrap_or(PromotionStatus::Failed),
+        requested_by: row.get("requested_by"),
+        requested_at: row.get("requested_at"),
+        comment: row.get("comment"),
+        reviewed_by: row.get("reviewed_by"),
+        reviewed_at: row.get("reviewed_at"),
+        review_comment: row.get("review_comment"),
+        error: row.get("error"),
+        completed_at: row.get("completed_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn user(id: &str, scopes: &[&str]) -> AuthenticatedUser {
+        AuthenticatedUser {
+            id: id.to_string(),
+            name: id.to_string(),
+            email: format!("{}@example.com", id),
+            scopes: scopes.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_promotion_approvers() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::database::migrations::create_tables(&pool).await.unwrap();
+
+        let team_id = Uuid::new_v4();
+        sqlx::query("INSERT INTO users (id, username, email) VALUES ('carol', 'carol', 'carol@example.com')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO teams (id, name, created_by, created_at) VALUES ($1, 'release', 'admin', $2)")
+            .bind(team_id)
+            .bind(Utc::now())
+            .execute(&pool)
+            .await
+            .unwrap();
+        sqlx::query("INSERT INTO team_members (team_id, user_id, added_at) VALUES ($1, 'carol', $2)")
+            .bind(team_id)
+            .bind(Utc::now())
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let mut config = PromotionConfig::default();
+        let developer = user("bob", &["registry:read", "registry:write", "repository:app-staging:*"]);
+        let repo_admin = user("dave", &["registry:read", "repository:app:*"]);
+        let release = user("carol", &["registry:read"]);
+
+        assert!(is_approver(&pool, &config, &user("root", &["admin"]), "app").await.unwrap());
+        assert!(is_approver(&pool, &config, &repo_admin, "app").await.unwrap());
+        // Write access or admin on the source doesn't make someone a reviewer for the destination
+        assert!(!is_approver(&pool, &config, &developer, "app").await.unwrap());
+        assert!(!is_approver(&pool, &config, &user("robot:ci", &["repository:app:*"]), "app").await.unwrap());
+
+        assert!(!is_approver(&pool, &config, &release, "app").await.unwrap());
+        config.approver_teams = vec!["release".to_string()];
+        assert!(is_approver(&pool, &config, &release, "app").await.unwrap());
+        assert!(!is_registry_approver(&pool, &config, &repo_admin).await.unwrap());
+        assert_eq!(administered_repositories(&developer), vec!["app-staging"]);
+    }
+
+    #[test]
+    fn test_promotion_status_round_trip() {
+        for status in [
+            PromotionStatus::Pending,
+            PromotionStatus::Approved,
+            PromotionStatus::Rejected,
+            PromotionStatus::Completed,
+            PromotionStatus::Failed,
+        ] {
+            assert_eq!(PromotionStatus::parse(status.as_str()), Some(status));
+        }
+        assert_eq!(PromotionStatus::parse("merged"), None);
+    }
+}
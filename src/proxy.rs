@@ -0,0 +1,320 @@
+use axum::{
+    extract::{Query, State},
+    routing::{get, post},
+    Json, Router,
+};
+use dashmap::DashMap;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    config::{Config, IssueLevel, ProxyConfig, ProxyRule},
+    database::queries::{self, record_audit},
+    error::{Error, Result},
+    import::RemoteRegistry,
+    performance::{CachedManifest, ExistenceCache},
+    server::AppState,
+    types::{Page, PageQuery, Repository},
+    utils::{glob_matches, manifest_blob_digests, verify_digest},
+};
+
+/// Pull-through cache for repositories that are mirrored from another registry
+/// Rules map repository patterns to an upstream (or to "never proxy"); a local miss on a
+/// matching repository is fetched from the upstream, stored, and served as if it had been pushed.
+/// Tags are fetched on first use only; later moves upstream are not followed.
+
+pub struct PullThroughProxy {
+    rules: RwLock<Arc<Vec<ProxyRule>>>,
+    /// File the rules are re-read from on reload, when the server was started with one
+    config_path: Option<PathBuf>,
+    /// One client per upstream so bearer tokens are reused across pulls
+    upstreams: DashMap<String, Arc<RemoteRegistry>>,
+}
+
+/// A rule as reported to admins, without credentials
+#[derive(Debug, Clone, Serialize)]
+pub struct ProxyRuleView {
+    pub pattern: String,
+    pub upstream: Option<String>,
+    pub authenticated: bool,
+}
+
+impl From<&ProxyRule> for ProxyRuleView {
+    fn from(rule: &ProxyRule) -> Self {
+        Self {
+            pattern: rule.pattern.clone(),
+            upstream: rule.upstream.clone(),
+            authenticated: rule.username.is_some(),
+        }
+    }
+}
+
+impl PullThroughProxy {
+    pub fn new(config: &ProxyConfig, config_path: Option<PathBuf>) -> Self {
+        Self {
+            rules: RwLock::new(Arc::new(config.rules.clone())),
+            config_path,
+            upstreams: DashMap::new(),
+        }
+    }
+
+    pub fn rules(&self) -> Arc<Vec<ProxyRule>> {
+        Arc::clone(&self.rules.read().unwrap())
+    }
+
+    /// Re-read the rules from the config file; the old rules stay in place if the new ones are invalid
+    pub fn reload(&self) -> Result<usize> {
+        let path = self
+            .config_path
+            .as_ref()
+            .ok_or_else(|| Error::bad_request("Server was started without a config file; nothing to reload"))?;
+        let config = Config::load(path).map_err(|e| Error::validation(format!("Failed to load {}: {}", path.display(), e)))?;
+
+        let errors: Vec<String> = config
+            .proxy
+            .issues()
+            .into_iter()
+            .filter(|issue| issue.level == IssueLevel::Error)
+            .map(|issue| format!("{}: {}", issue.field, issue.message))
+            .collect();
+        if !errors.is_empty() {
+            return Err(Error::validation(format!("Invalid proxy rules: {}", errors.join("; "))));
+        }
+
+        let count = config.proxy.rules.len();
+        *self.rules.write().unwrap() = Arc::new(config.proxy.rules);
+        // Credentials may have changed with the rules
+        self.upstreams.clear();
+        Ok(count)
+    }
+
+    /// Upstream client for a repository, if its first matching rule proxies it
+    fn upstream_for(&self, repository: &str) -> Result<Option<Arc<RemoteRegistry>>> {
+        let rules = self.rules();
+        let Some(rule) = matching_rule(&rules, repository) else {
+            return Ok(None);
+        };
+        let Some(upstream) = &rule.upstream else {
+            return Ok(None);
+        };
+
+        let key = format!("{}\n{}", upstream, rule.username.as_deref().unwrap_or(""));
+        if let Some(client) = self.upstreams.get(&key) {
+            return Ok(Some(Arc::clone(&client)));
+        }
+
+        let credentials = rule.username.clone().zip(rule.password.clone());
+        let client = Arc::new(RemoteRegistry::new(upstream, credentials)?);
+        self.upstreams.insert(key, Arc::clone(&client));
+        Ok(Some(client))
+    }
+
+    /// Fetch and store a manifest missing locally; `None` when the repository is not proxied
+    /// or the upstream does not have it either
+    pub async fn fetch_manifest(&self, state: &AppState, name: &str, reference: &str) -> Result<Option<CachedManifest>> {
+        let Some(upstream) = self.upstream_for(name)? else {
+            return Ok(None);
+        };
+
+        let manifest = match upstream.get_manifest(name, reference).await {
+            Ok(manifest) => manifest,
+            Err(Error::NotFound { .. }) => return Ok(None),
+            Err(e) => {
+                tracing::warn!("Pull-through fetch of {}:{} failed: {}", name, reference, e);
+                return Err(e);
+            }
+        };
+        let repo = cached_repository(state, name).await?;
+        let manifest_id = queries::store_manifest(
+            &state.database.pool,
+            &repo.id,
+            &manifest.digest,
+            &manifest.media_type,
+            &manifest.content,
+            manifest.artifact_type(),
+        )
+        .await?;
+        // Blobs already cached are linked now; the rest are linked as they are fetched
+        for digest in manifest_blob_digests(&manifest.content) {
+            queries::link_manifest_to_blob(state, manifest_id, &digest).await?;
+        }
+        if !reference.starts_with("sha256:") {
+            let _tag_lock = state.performance.tag_locks.lock(name, reference).await?;
+            queries::tag_manifest(&state.database.pool, &repo.id, reference, manifest_id).await?;
+        }
+        state.performance.existence_cache.invalidate_manifests(name);
+
+        tracing::info!("Cached {}:{} ({}) from upstream", name, reference, manifest.digest);
+        Ok(Some(CachedManifest {
+            repository_id: repo.id,
+            digest: manifest.digest,
+            media_type: manifest.media_type,
+            content: manifest.content.into(),
+        }))
+    }
+
+    /// Fetch and store a blob missing locally; `None` when the repository is not proxied
+    /// or the upstream does not have it either
+    pub async fn fetch_blob(&self, state: &AppState, name: &str, digest: &str) -> Result<Option<Vec<u8>>> {
+        let Some(upstream) = self.upstream_for(name)? else {
+            return Ok(None);
+        };
+
+        let response = upstream.open_blob(name, digest, 0).await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(Error::bad_request(format!(
+                "Upstream returned {} for blob {}",
+                response.status(),
+                digest
+            )));
+        }
+
+        let data = response.bytes().await?.to_vec();
+        if let Err(e) = verify_digest(digest, &data) {
+            tracing::warn!("Rejected blob {} from upstream for {}: {}", digest, name, e);
+            return Err(e);
+        }
+
+        let repo = cached_repository(state, name).await?;
+        state.storage.put_blob(digest, &data).await?;
+        let blob_id = queries::record_blob(state, &repo, digest, data.len() as i64).await?;
+        link_cached_blob(state, &repo, blob_id, digest).await?;
+        state.performance.existence_cache.invalidate(&ExistenceCache::blob_key(name, digest));
+
+        Ok(Some(data))
+    }
+}
+
+/// First rule whose pattern matches the repository
+pub fn matching_rule<'a>(rules: &'a [ProxyRule], repository: &str) -> Option<&'a ProxyRule> {
+    rules.iter().find(|rule| glob_matches(&rule.pattern, repository))
+}
+
+/// Local repository that proxied content lands in; it has no owner and the configured default visibility
+async fn cached_repository(state: &AppState, name: &str) -> Result<Repository> {
+    match queries::get_repository_by_name(state, name).await {
+        Ok(repo) => Ok(repo),
+        Err(Error::NotFound { .. }) | Err(Error::NameUnknown { .. }) => {
+            let defaults = queries::repository_defaults(&state.config.registry, name, None);
+            let repo = queries::create_repository(
+                state,
+                name,
+                "Pull-through cache",
+                defaults.is_public,
+                defaults.namespace.as_deref(),
+                None,
+            )
+            .await?;
+            state.performance.catalog_cache.invalidate();
+            Ok(repo)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Link a newly cached blob to the manifests in its repository that reference it
+async fn link_cached_blob(state: &AppState, repo: &Repository, blob_id: uuid::Uuid, digest: &str) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO manifest_blobs (id, manifest_id, blob_id, created_at)
+        SELECT randomblob(16), m.id, $1, $2
+        FROM manifests m
+        WHERE m.repository_id = $3
+          AND instr(CAST(m.content AS TEXT), $4) > 0
+          AND NOT EXISTS (SELECT 1 FROM manifest_blobs mb WHERE mb.manifest_id = m.id AND mb.blob_id = $1)
+        "#
+    )
+    .bind(blob_id)
+    .bind(chrono::Utc::now())
+    .bind(repo.id)
+    .bind(digest)
+    .execute(&state.database.pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Proxy rule administration
+pub fn proxy_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/admin/proxy/rules", get(list_rules))
+        .route("/api/admin/proxy/reload", post(reload_rules))
+}
+
+/// Active proxy rules in match order (admin only)
+async fn list_rules(
+    State(state): State<AppState>,
+    Query(page): Query<PageQuery>,
+    user: AuthenticatedUser,
+) -> Result<Json<Page<ProxyRuleView>>> {
+    require_admin(&user)?;
+    let (limit, offset) = page.bounds()?;
+    let rules = state.proxy.rules().iter().map(ProxyRuleView::from).collect();
+    Ok(Json(Page::from_vec(rules, limit, offset)))
+}
+
+/// Re-read proxy rules from the config file (admin only)
+async fn reload_rules(State(state): State<AppState>, user: AuthenticatedUser) -> Result<Json<Vec<ProxyRuleView>>> {
+    require_admin(&user)?;
+
+    let count = state.proxy.reload()?;
+    tracing::info!("Proxy rules reloaded by {}: {} rules", user.name, count);
+    record_audit(&state, &user.id, "proxy.reload", "registry", Some(serde_json::json!({ "rules": count }))).await?;
+
+    Ok(Json(state.proxy.rules().iter().map(ProxyRuleView::from).collect()))
+}
+
+fn require_admin(user: &AuthenticatedUser) -> Result<()> {
+    if user.is_robot() || !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Managing proxy rules requires admin access"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, upstream: Option<&str>) -> ProxyRule {
+        ProxyRule {
+            pattern: pattern.to_string(),
+            upstream: upstream.map(str::to_string),
+            username: None,
+            password: None,
+        }
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let rules = vec![
+            rule("internal/*", None),
+            rule("library/*", Some("https://registry-1.docker.io")),
+            rule("*", Some("https://mirror.example.com")),
+        ];
+
+        assert!(matching_rule(&rules, "internal/app").unwrap().upstream.is_none());
+        assert_eq!(
+            matching_rule(&rules, "library/nginx").unwrap().upstream.as_deref(),
+            Some("https://registry-1.docker.io")
+        );
+        assert_eq!(matching_rule(&rules, "team/app").unwrap().pattern, "*");
+        assert!(matching_rule(&rules[..2], "team/app").is_none());
+    }
+
+    #[test]
+    fn test_rule_issues() {
+        let mut config = ProxyConfig {
+            rules: vec![rule("library/*", Some("ftp://example.com")), rule(" ", None)],
+        };
+        let fields: Vec<String> = config.issues().into_iter().map(|i| i.field).collect();
+        assert_eq!(fields, vec!["proxy.rules[0].upstream", "proxy.rules[1].pattern"]);
+
+        config.rules = vec![rule("library/*", Some("https://registry-1.docker.io"))];
+        assert!(config.issues().is_empty());
+    }
+}
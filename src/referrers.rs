@@ -0,0 +1,145 @@
+//! OCI referrers: manifests attached to another through their `subject`, such as signatures,
+//! SBOMs and attestations.
+//!
+//! Referrers are served natively from `/v2/<name>/referrers/<digest>` and mirrored into the
+//! `sha256-<hex>` fallback tag, so clients using either discovery method see the same list.
+
+use crate::{
+    database::queries::{delete_tag, list_referrers, store_manifest, tag_manifest},
+    error::{Error, Result},
+    server::AppState,
+    types::{media_types, ArtifactType, Manifest},
+    utils::sha256_digest,
+};
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
+
+/// Tag holding a subject's referrers index for clients without the referrers API:
+/// `sha256:<hex>` becomes `sha256-<hex>`
+pub fn fallback_tag(subject_digest: &str) -> String {
+    subject_digest.replacen(':', "-", 1)
+}
+
+/// Descriptor a referrer contributes to its subject's index. `artifactType` falls back to the
+/// config media type, and the referrer's annotations are copied so clients can filter without
+/// fetching every manifest.
+pub fn referrer_descriptor(manifest: &Manifest) -> Value {
+    let parsed: Value = serde_json::from_slice(&manifest.content).unwrap_or(Value::Null);
+
+    let mut descriptor = Map::new();
+    descriptor.insert("mediaType".to_string(), json!(manifest.media_type));
+    descriptor.insert("digest".to_string(), json!(manifest.digest));
+    descriptor.insert("size".to_string(), json!(manifest.size));
+    let artifact_type = parsed
+        .get("artifactType")
+        .or_else(|| parsed.pointer("/config/mediaType"))
+        .and_then(Value::as_str);
+    if let Some(artifact_type) = artifact_type {
+        descriptor.insert("artifactType".to_string(), json!(artifact_type));
+    }
+    if let Some(annotations) = parsed.get("annotations").filter(|a| a.is_object()) {
+        descriptor.insert("annotations".to_string(), annotations.clone());
+    }
+
+    Value::Object(descriptor)
+}
+
+/// Whether a referrer descriptor passes an `artifactType` filter
+pub fn matches_artifact_type(descriptor: &Value, artifact_type: Option<&str>) -> bool {
+    artifact_type.map_or(true, |wanted| {
+        descriptor.get("artifactType").and_then(Value::as_str) == Some(wanted)
+    })
+}
+
+/// OCI image index listing referrer descriptors, as served by the API and stored in the fallback tag
+pub fn referrers_index(descriptors: Vec<Value>) -> Value {
+    json!({
+        "schemaVersion": 2,
+        "mediaType": media_types::OCI_INDEX,
+        "manifests": descriptors,
+    })
+}
+
+/// Rewrite a subject's fallback tag to list its current referrers, removing the tag once none remain.
+/// Runs under the tag's lock so concurrent referrer pushes can't drop each other's entries.
+pub async fn refresh_fallback_tag(state: &AppState, repository_id: &Uuid, name: &str, subject_digest: &str) -> Result<()> {
+    let tag = fallback_tag(subject_digest);
+    let _lock = state.performance.tag_locks.lock(name, &tag).await?;
+    let pool = &state.database.pool;
+
+    let referrers = list_referrers(pool, repository_id, subject_digest).await?;
+    if referrers.is_empty() {
+        match delete_tag(state, repository_id, &tag).await {
+            Ok(()) | Err(Error::NotFound { .. }) => {}
+            Err(e) => return Err(e),
+        }
+    } else {
+        let index = referrers_index(referrers.iter().map(referrer_descriptor).collect());
+        let content = serde_json::to_vec(&index)?;
+        let digest = sha256_digest(&content);
+        let manifest_id = store_manifest(pool, repository_id, &digest, media_types::OCI_INDEX, &content, ArtifactType::Index).await?;
+        tag_manifest(pool, repository_id, &tag, manifest_id).await?;
+    }
+
+    state.performance.existence_cache.invalidate_manifests(name);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(digest: &str, content: Value) -> Manifest {
+        let content = serde_json::to_vec(&content).unwrap();
+        Manifest {
+            id: Uuid::new_v4(),
+            repository_id: Uuid::nil(),
+            digest: digest.to_string(),
+            media_type: media_types::OCI_MANIFEST.to_string(),
+            size: content.len() as i64,
+            content,
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_referrer_descriptors() {
+        let subject = json!({ "mediaType": media_types::OCI_MANIFEST, "digest": "sha256:aaa", "size": 10 });
+        let sbom = manifest("sha256:bbb", json!({
+            "schemaVersion": 2,
+            "mediaType": media_types::OCI_MANIFEST,
+            "artifactType": "application/spdx+json",
+            "config": { "mediaType": media_types::OCI_EMPTY, "digest": "sha256:44136fa", "size": 2 },
+            "layers": [],
+            "subject": subject,
+            "annotations": { "org.opencontainers.image.created": "2024-01-01T00:00:00Z" },
+        }));
+        let signature = manifest("sha256:ccc", json!({
+            "schemaVersion": 2,
+            "mediaType": media_types::OCI_MANIFEST,
+            "config": { "mediaType": "application/vnd.dev.cosign.artifact.sig.v1+json", "digest": "sha256:ddd", "size": 2 },
+            "layers": [],
+            "subject": subject,
+        }));
+
+        let sbom = referrer_descriptor(&sbom);
+        assert_eq!(sbom["artifactType"], "application/spdx+json");
+        assert_eq!(sbom["annotations"]["org.opencontainers.image.created"], "2024-01-01T00:00:00Z");
+        // Without an artifactType the config media type stands in
+        let signature = referrer_descriptor(&signature);
+        assert_eq!(signature["artifactType"], "application/vnd.dev.cosign.artifact.sig.v1+json");
+        assert!(signature.get("annotations").is_none());
+
+        assert!(matches_artifact_type(&sbom, None));
+        assert!(matches_artifact_type(&sbom, Some("application/spdx+json")));
+        assert!(!matches_artifact_type(&signature, Some("application/spdx+json")));
+
+        let index = referrers_index(vec![sbom, signature]);
+        assert_eq!(index["mediaType"], media_types::OCI_INDEX);
+        assert_eq!(index["manifests"].as_array().unwrap().len(), 2);
+
+        let tag = fallback_tag(&format!("sha256:{}", "a".repeat(64)));
+        assert_eq!(tag, format!("sha256-{}", "a".repeat(64)));
+        assert!(crate::utils::validate_tag_name(&tag).is_ok());
+    }
+}
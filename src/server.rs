@@ -1,21 +1,45 @@
 use crate::{
-    config::Config,
+    access_log::{self, AccessLog},
+    auth::{jwt::JwtConfig, lockout::LoginLockout},
+    backup::{self, BackupReport},
+    config::{Config, IssueLevel, WebConfig},
     database::Database,
-    error::Result,
-    handlers::{auth, health, registry, manifest},
+    deployments,
+    enhanced_error::{enhanced_logging, metrics::{self, Metrics}},
+    error::{Error, Result},
+    fsck::{self, FsckReport},
+    gc::{self, GcOptions, GcReport, GcRunner},
+    https,
+    handlers::{audit, auth, health, imports, info, registry, manifest, openapi, repositories, robots, shares, stats, teams, users},
+    import::ImportJobs,
+    maintenance::{self, MaintenanceMode},
+    notifications::{self, NotificationDispatcher},
+    performance::{self, PerformanceLayer},
+    promotions,
+    proxy::{self, PullThroughProxy},
+    stack_management,
     storage::Storage,
+    storage_cache::BlobReadCache,
+    storage_fallback::StorageFallback,
+    storage_pressure::StoragePressure,
+    uploads::PendingBlobs,
     web,
+    webhooks,
+    websocket::{self, WebSocketState},
 };
 use axum::{
+    http::{header, HeaderName, HeaderValue, Method},
     routing::{get, post, put, delete, head, patch},
     Router,
 };
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::signal;
 use tower_http::{
-    cors::CorsLayer,
+    cors::{AllowOrigin, Any, CorsLayer},
+    services::ServeDir,
     trace::TraceLayer,
 };
 use tracing::{info, warn};
@@ -24,17 +48,49 @@ pub struct Server {
     config: Config,
     database: Arc<Database>,
     storage: Arc<Storage>,
+    storage_fallback: Option<Arc<StorageFallback>>,
+    storage_pressure: Arc<StoragePressure>,
+    blob_cache: Option<Arc<BlobReadCache>>,
+    performance: PerformanceLayer,
+    notification_dispatcher: Option<Arc<NotificationDispatcher>>,
+    websocket: Arc<WebSocketState>,
+    imports: Arc<ImportJobs>,
+    pending_blobs: Arc<PendingBlobs>,
+    login_lockout: Arc<LoginLockout>,
+    maintenance: Arc<MaintenanceMode>,
+    proxy: Arc<PullThroughProxy>,
+    jwt: Arc<JwtConfig>,
+    access_log: Option<Arc<AccessLog>>,
+    metrics: Arc<Metrics>,
 }
 
 impl Server {
     pub async fn new(config_path: PathBuf) -> Result<Self> {
         // Load configuration
-        let config = if config_path.exists() {
+        let config_file = config_path.exists().then(|| config_path.clone());
+        let config = if config_file.is_some() {
             Config::load(&config_path)?
         } else {
             warn!("Config file not found, using default configuration");
             Config::default()
         };
+        Self::with_config(config, config_file).await
+    }
+
+    /// Build the server around an already loaded configuration; `config_file` is what
+    /// proxy rule reloads read from
+    async fn with_config(config: Config, config_file: Option<PathBuf>) -> Result<Self> {
+        // Refuse settings that would be silently ignored, such as storage encryption. The
+        // default jwt_secret stays a warning here; main reports it on startup.
+        let errors: Vec<String> = config
+            .validate(true)
+            .into_iter()
+            .filter(|issue| issue.level == IssueLevel::Error)
+            .map(|issue| format!("{}: {}", issue.field, issue.message))
+            .collect();
+        if !errors.is_empty() {
+            return Err(Error::validation(format!("Invalid configuration: {}", errors.join("; "))));
+        }
 
         // Initialize database
         let database = Arc::new(Database::new(&config.database).await?);
@@ -42,14 +98,110 @@ impl Server {
 
         // Initialize storage
         let storage = Arc::new(Storage::new(&config.storage).await?);
+        let storage_fallback = StorageFallback::from_config(&config.storage).await?.map(Arc::new);
+        let blob_cache = BlobReadCache::from_config(&config.storage).await?.map(Arc::new);
+
+        let performance = PerformanceLayer::with_config(
+            &config.concurrency,
+            &config.cache,
+            &config.bandwidth,
+            &config.rate_limit,
+        );
+
+        // Initialize out-of-band notification delivery
+        let notification_dispatcher = notifications::build_dispatcher(
+            database.pool.clone(),
+            &config.notifications,
+        )?;
+
+        let jwt = Arc::new(JwtConfig::from_config(&config.auth)?);
+        let websocket = Arc::new(WebSocketState::new());
+        websocket.set_jwt_config(Arc::clone(&jwt));
+        let login_lockout = Arc::new(LoginLockout::new(config.auth.lockout.clone()));
+        let maintenance = Arc::new(MaintenanceMode::new(&config.maintenance));
+        let storage_pressure = Arc::new(StoragePressure::new(&config.storage));
+        let proxy = Arc::new(PullThroughProxy::new(&config.proxy, config_file));
+        let access_log = AccessLog::from_config(&config.logging.access_log).await?;
 
         Ok(Self {
             config,
             database,
             storage,
+            storage_fallback,
+            storage_pressure,
+            blob_cache,
+            performance,
+            notification_dispatcher,
+            websocket,
+            imports: Arc::new(ImportJobs::new()),
+            pending_blobs: Arc::new(PendingBlobs::new()),
+            login_lockout,
+            maintenance,
+            proxy,
+            jwt,
+            access_log,
+            metrics: Metrics::new(),
         })
     }
 
+    /// Loaded configuration
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    /// Run a single garbage collection pass
+    pub async fn run_gc(&self, options: &GcOptions) -> Result<GcReport> {
+        gc::run(&self.database, &self.storage, self.blob_cache.as_deref(), options).await
+    }
+
+    /// Check (and optionally repair) drift between the database and storage
+    pub async fn run_fsck(&self, repair: bool) -> Result<FsckReport> {
+        fsck::run(
+            &self.database,
+            &self.storage,
+            self.storage_fallback.as_deref(),
+            self.blob_cache.as_deref(),
+            &self.config.storage,
+            repair,
+        )
+        .await
+    }
+
+    /// Write a backup bundle of the database and, on the filesystem backend, the blob store
+    pub async fn run_backup(&self, output: &Path) -> Result<BackupReport> {
+        backup::create(&self.database, &self.config.storage, output).await
+    }
+
+    /// Handle for running garbage collection from background tasks
+    pub fn gc_runner(&self) -> GcRunner {
+        GcRunner::new(
+            Arc::clone(&self.database),
+            Arc::clone(&self.storage),
+            self.blob_cache.clone(),
+            self.performance.clone(),
+        )
+    }
+
+    /// Shared database handle for background tasks
+    pub fn database(&self) -> Arc<Database> {
+        Arc::clone(&self.database)
+    }
+
+    /// Shared WebSocket broadcaster for real-time updates
+    pub fn websocket_state(&self) -> Arc<WebSocketState> {
+        Arc::clone(&self.websocket)
+    }
+
+    /// Request counters for the registry API
+    pub fn metrics(&self) -> Arc<Metrics> {
+        Arc::clone(&self.metrics)
+    }
+
+    /// Notification dispatcher, if any sink is configured
+    pub fn notification_dispatcher(&self) -> Option<Arc<NotificationDispatcher>> {
+        self.notification_dispatcher.clone()
+    }
+
     pub async fn run(self) -> Result<()> {
         let registry_app = self.registry_router().await?;
         let web_app = self.web_router().await?;
@@ -71,7 +223,11 @@ impl Server {
         let registry_listener = tokio::net::TcpListener::bind(&registry_addr).await?;
         let web_listener = tokio::net::TcpListener::bind(&web_addr).await?;
 
-        let registry_server = axum::serve(registry_listener, registry_app);
+        // Peer addresses back the login lockout when no proxy sets X-Forwarded-For
+        let registry_server = axum::serve(
+            registry_listener,
+            registry_app.into_make_service_with_connect_info::<SocketAddr>(),
+        );
         let web_server = axum::serve(web_listener, web_app);
 
         tokio::select! {
@@ -99,11 +255,23 @@ impl Server {
             config: self.config.clone(),
             database: Arc::clone(&self.database),
             storage: Arc::clone(&self.storage),
+            storage_fallback: self.storage_fallback.clone(),
+            storage_pressure: Arc::clone(&self.storage_pressure),
+            blob_cache: self.blob_cache.clone(),
+            performance: self.performance.clone(),
+            websocket: Arc::clone(&self.websocket),
+            imports: Arc::clone(&self.imports),
+            pending_blobs: Arc::clone(&self.pending_blobs),
+            login_lockout: Arc::clone(&self.login_lockout),
+            maintenance: Arc::clone(&self.maintenance),
+            proxy: Arc::clone(&self.proxy),
+            jwt: Arc::clone(&self.jwt),
         };
 
         let app = Router::new()
             // Docker Registry v2 API
-            .route("/v2/", get(registry::root))
+            .route("/v2/", get(registry::root).head(registry::root))
+            .route("/v2/_catalog", get(repositories::catalog))
             .route("/v2/:name/blobs/:digest", get(registry::get_blob))
             .route("/v2/:name/blobs/:digest", head(registry::head_blob))
             .route("/v2/:name/blobs/:digest", delete(registry::delete_blob))
@@ -117,23 +285,116 @@ impl Server {
             .route("/v2/:name/manifests/:reference", head(manifest::head_manifest))
             .route("/v2/:name/manifests/:reference", delete(manifest::delete_manifest))
             .route("/v2/:name/tags/list", get(manifest::get_tags))
+            .route("/v2/:name/referrers/:digest", get(manifest::get_referrers))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), crate::auth::middleware::reject_invalid_credentials))
+            .layer(axum::middleware::map_response(registry::method_not_allowed_envelope))
+            .layer(axum::middleware::map_response_with_state(state.clone(), registry::auth_challenge))
+            .layer(axum::middleware::map_response(registry::api_version_header))
+            .layer(axum::middleware::from_fn_with_state(Arc::clone(&self.metrics), metrics::track_registry_operations))
+            
+            // Usage statistics
+            .route("/api/repositories/:name/stats", get(stats::repository_stats))
+            .route("/api/stats/usage", get(stats::global_stats))
+            
+            // Registry capabilities
+            .route("/api/info", get(info::get_info))
+            
+            // Repository management
+            .route("/api/repositories", get(repositories::list_repositories).post(repositories::create_repository))
+            .route("/api/repositories/:name/artifact-types", put(repositories::set_artifact_types))
+            .route("/api/repositories/:name/default-platform", put(repositories::set_default_platform))
+            .route("/api/repositories/:name/deletion-protection", put(repositories::set_deletion_protection))
+            .route("/api/repositories/:name/favorite", post(repositories::favorite_repository).delete(repositories::unfavorite_repository))
+            
+            // Remote registry import
+            .route("/api/admin/import", get(imports::list_imports).post(imports::start_import))
+            .route("/api/admin/import/:job_id", get(imports::get_import).delete(imports::cancel_import))
+            .route("/api/admin/import/:job_id/resume", post(imports::resume_import))
+            
+            // Audit log archival
+            .route("/api/audit/export", get(audit::export_audit_log))
+            
+            // User lifecycle
+            .route("/api/users/:id", delete(users::delete_user))
+            .route("/api/users/:id/repository-limit", put(users::set_repository_limit))
+            .route("/api/users/:id/rate-limit", put(users::set_rate_limit))
+            .route("/api/me", get(users::get_profile))
+            .route("/api/me/password", post(users::change_password))
+            .route("/api/me/favorites", get(repositories::list_favorites))
+            
+            // Detailed tag listing
+            .route("/api/repositories/:name/tags", get(manifest::get_tag_details))
+            .route("/api/repositories/:name/tags/:tag/config", get(manifest::get_image_config))
+            .route("/api/tags/drift", post(manifest::check_tag_drift))
+            // `:check` is captured as a parameter; the handler rejects any other suffix
+            .route("/api/repositories/:name/blobs:check", post(registry::check_blobs))
+            
+            // Robot accounts
+            .route("/api/repositories/:name/robots", get(robots::list_robots).post(robots::create_robot))
+            .route("/api/repositories/:name/robots/:robot_id", delete(robots::delete_robot))
+            
+            // Time-limited share credentials
+            .route("/api/repositories/:name/share", post(shares::create_share))
+            .route("/api/repositories/:name/shares", get(shares::list_shares))
+            .route("/api/repositories/:name/shares/:share_id", delete(shares::revoke_share))
+            
+            // Teams and repository permission grants
+            .route("/api/teams", get(teams::list_teams).post(teams::create_team))
+            .route("/api/teams/:id", delete(teams::delete_team))
+            .route("/api/teams/:id/members", get(teams::list_members))
+            .route("/api/teams/:id/members/:user_id", put(teams::add_member).delete(teams::remove_member))
+            .route("/api/repositories/:name/permissions", get(teams::list_permissions).post(teams::grant_permission))
+            .route("/api/repositories/:name/permissions/:grant_id", delete(teams::revoke_permission))
+            
+            // Notification preferences
+            .merge(notifications::notification_routes())
+            
+            // Webhook secret management
+            .merge(webhooks::webhook_routes())
+            
+            // Read-only maintenance mode
+            .merge(maintenance::maintenance_routes())
+            
+            // Pull-through cache rules
+            .merge(proxy::proxy_routes())
+            
+            // Stack deployments
+            .merge(deployments::deployment_routes())
+            
+            // Saved compose stacks and the public stack registry
+            .merge(stack_management::stack_routes())
+            
+            // Reviewed image promotions
+            .merge(promotions::promotion_routes())
+            
+            // Management API documentation
+            .route("/api/openapi.json", get(openapi::openapi_json))
+            .route("/api/docs", get(openapi::swagger_ui))
+            .nest_service("/api/docs/assets", ServeDir::new(&self.config.web.docs_assets_path))
             
             // Health check
             .route("/health", get(health::health_check))
+            .route("/readyz", get(health::readiness))
             .route("/metrics", get(health::metrics))
             
             // Authentication
+            .route("/token", get(auth::registry_token).post(auth::registry_oauth_token))
             .route("/auth/login", post(auth::login))
             .route("/auth/logout", post(auth::logout))
             .route("/auth/oauth/:provider", get(auth::oauth_redirect))
             .route("/auth/oauth/:provider/callback", get(auth::oauth_callback))
             
+            .fallback(registry::fallback)
+            
             // Middleware
+            .layer(axum::middleware::from_fn_with_state(state.clone(), maintenance::read_only_guard))
+            .layer(axum::middleware::from_fn_with_state(state.clone(), performance::rate_limit))
             .layer(TraceLayer::new_for_http())
-            .layer(CorsLayer::permissive())
+            .layer(cors_layer(&self.config.web))
+            .layer(axum::middleware::from_fn(enhanced_logging::request_id))
             .with_state(state);
 
-        Ok(app)
+        Ok(self.with_access_log(app))
     }
 
     async fn web_router(&self) -> Result<Router> {
@@ -145,16 +406,836 @@ impl Server {
 
         let app = Router::new()
             .merge(web::routes())
+            .merge(websocket::websocket_routes().with_state((*self.websocket).clone()))
             .layer(TraceLayer::new_for_http())
-            .layer(CorsLayer::permissive());
+            .layer(cors_layer(&self.config.web))
+            .layer(axum::middleware::from_fn(enhanced_logging::request_id));
+        let app = match https::HttpsPolicy::from_config(&self.config.web.https) {
+            Some(policy) => app.layer(axum::middleware::from_fn_with_state(policy, https::middleware)),
+            None => app,
+        };
+
+        Ok(self.with_access_log(app))
+    }
 
-        Ok(app)
+    /// Wrap a router in the access log, outermost so it records the final status
+    fn with_access_log(&self, app: Router) -> Router {
+        match &self.access_log {
+            Some(log) => app.layer(axum::middleware::from_fn_with_state(Arc::clone(log), access_log::middleware)),
+            None => app,
+        }
     }
 }
 
+/// CORS for the UI and API clients: explicit methods and headers, and credentials
+/// only when every allowed origin is listed exactly
+fn cors_layer(web: &WebConfig) -> CorsLayer {
+    if !web.cors_enabled {
+        return CorsLayer::new();
+    }
+
+    let layer = CorsLayer::new()
+        .allow_methods([
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            header::CONTENT_TYPE,
+            header::CONTENT_RANGE,
+            header::RANGE,
+            HeaderName::from_static("x-request-id"),
+        ])
+        .expose_headers([
+            header::LOCATION,
+            header::RANGE,
+            header::RETRY_AFTER,
+            header::WWW_AUTHENTICATE,
+            HeaderName::from_static("docker-content-digest"),
+            HeaderName::from_static("docker-upload-uuid"),
+            HeaderName::from_static("docker-distribution-api-version"),
+            HeaderName::from_static("x-request-id"),
+        ])
+        .max_age(Duration::from_secs(web.cors_max_age_secs));
+
+    if web.cors_allows_any_origin() {
+        return layer.allow_origin(Any);
+    }
+
+    let origins: Vec<HeaderValue> = web
+        .cors_origins
+        .iter()
+        .filter_map(|origin| match origin.parse() {
+            Ok(value) => Some(value),
+            Err(_) => {
+                warn!("Ignoring invalid CORS origin '{}'", origin);
+                None
+            }
+        })
+        .collect();
+
+    layer
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_credentials(web.cors_allow_credentials)
+}
+
 #[derive(Clone)]
 pub struct AppState {
     pub config: Config,
     pub database: Arc<Database>,
     pub storage: Arc<Storage>,
+    /// Read-only backend behind `storage` during a storage migration
+    pub storage_fallback: Option<Arc<StorageFallback>>,
+    /// Free-space check that pauses pushes before the volume fills
+    pub storage_pressure: Arc<StoragePressure>,
+    /// Local disk copy of blobs read from object storage
+    pub blob_cache: Option<Arc<BlobReadCache>>,
+    pub performance: PerformanceLayer,
+    pub websocket: Arc<WebSocketState>,
+    pub imports: Arc<ImportJobs>,
+    /// Deferred-verification uploads not yet checked, which pushed manifests may reference
+    pub pending_blobs: Arc<PendingBlobs>,
+    pub login_lockout: Arc<LoginLockout>,
+    pub maintenance: Arc<MaintenanceMode>,
+    /// Pull-through cache consulted on manifest and blob misses
+    pub proxy: Arc<PullThroughProxy>,
+    /// Token signing key and trusted external issuers
+    pub jwt: Arc<JwtConfig>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        types::{media_types, registry_headers},
+        utils::sha256_digest,
+    };
+    use axum::{body::Body, http::{Request, StatusCode}, response::Response};
+    use tower::ServiceExt;
+
+    async fn send(app: &Router, request: Request<Body>) -> Response {
+        app.clone().oneshot(request).await.unwrap()
+    }
+
+    fn request(method: Method, uri: &str, body: Vec<u8>) -> Request<Body> {
+        Request::builder().method(method).uri(uri).body(Body::from(body)).unwrap()
+    }
+
+    /// Registry router over a fresh database and storage under `dir`
+    async fn router(dir: &Path) -> (Server, Router) {
+        router_with(dir, |_| {}).await
+    }
+
+    /// Registry router as `router`, with `configure` applied to the default configuration
+    async fn router_with(dir: &Path, configure: impl FnOnce(&mut Config)) -> (Server, Router) {
+        let mut config = Config::default();
+        configure(&mut config);
+        config.database.path = dir.join("ghostdock.db");
+        config.storage.path = dir.join("storage");
+        std::fs::File::create(&config.database.path).unwrap();
+        let server = Server::with_config(config, None).await.unwrap();
+        let app = server.registry_router().await.unwrap();
+        (server, app)
+    }
+
+    /// Create an account with password `s3cret` and return its id
+    async fn create_user(server: &Server, username: &str) -> String {
+        let id = uuid::Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO users (id, username, email, password_hash) VALUES ($1, $2, $3, $4)")
+            .bind(&id)
+            .bind(username)
+            .bind(format!("{}@example.com", username))
+            .bind(crate::utils::hash_password("s3cret").await.unwrap())
+            .execute(&server.database().pool)
+            .await
+            .unwrap();
+        id
+    }
+
+    /// Bearer header for `username` from the token endpoint, covering `scope`
+    async fn bearer(app: &Router, username: &str, scope: &str) -> HeaderValue {
+        use base64::Engine;
+
+        let credentials = base64::engine::general_purpose::STANDARD.encode(format!("{}:s3cret", username));
+        let uri = format!("/token?service=ghostdock&scope={}", scope);
+        let mut fetch = request(Method::GET, &uri, vec![]);
+        fetch.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Basic {}", credentials)).unwrap());
+        let issued = send(app, fetch).await;
+        assert_eq!(issued.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(issued.into_body(), usize::MAX).await.unwrap();
+        let token = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["token"].as_str().unwrap().to_string();
+        HeaderValue::from_str(&format!("Bearer {}", token)).unwrap()
+    }
+
+    /// Access the token endpoint grants `username` for `scope`
+    async fn granted_access(server: &Server, app: &Router, username: &str, scope: &str) -> Vec<crate::auth::jwt::AccessEntry> {
+        let auth = bearer(app, username, scope).await;
+        let token = auth.to_str().unwrap().strip_prefix("Bearer ").unwrap().to_string();
+        crate::auth::jwt::validate_token(&token, &server.jwt).await.unwrap().access
+    }
+
+    #[tokio::test]
+    async fn test_token_grants_only_permitted_repositories() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        let alice = create_user(&server, "alice").await;
+        let bob = create_user(&server, "bob").await;
+        let pool = &server.database().pool;
+        for (name, is_public) in [("alice/private", false), ("alice/open", true), ("alice/shared", false)] {
+            sqlx::query("INSERT INTO repositories (id, name, namespace, description, is_public, owner_id) VALUES ($1, $2, 'alice', '', $3, $4)")
+                .bind(uuid::Uuid::new_v4())
+                .bind(name)
+                .bind(is_public)
+                .bind(&alice)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+        sqlx::query(
+            "INSERT INTO repository_permissions (id, repository_id, user_id, permission, created_by, created_at)
+             SELECT $1, id, $2, 'read', $3, CURRENT_TIMESTAMP FROM repositories WHERE name = 'alice/shared'",
+        )
+        .bind(uuid::Uuid::new_v4())
+        .bind(&bob)
+        .bind(&alice)
+        .execute(pool)
+        .await
+        .unwrap();
+
+        // Someone else's private repository: nothing
+        assert!(granted_access(&server, &app, "bob", "repository:alice/private:pull,push").await.is_empty());
+        let mut pull = request(Method::GET, "/v2/alice/private/tags/list", vec![]);
+        pull.headers_mut().insert(header::AUTHORIZATION, bearer(&app, "bob", "").await);
+        assert_eq!(send(&app, pull).await.status(), StatusCode::FORBIDDEN);
+
+        // Public repositories and read grants allow pulls only; each scope is granted separately
+        let granted = granted_access(&server, &app, "bob", "repository:alice/open:pull,push&scope=repository:alice/shared:pull,push").await;
+        assert_eq!(granted.len(), 2);
+        assert!(granted.iter().all(|entry| entry.actions == vec!["pull"]));
+
+        // Owners have full access, and new repositories can be pushed into one's own namespace
+        assert_eq!(granted_access(&server, &app, "alice", "repository:alice/private:pull,push,delete").await[0].actions, vec!["pull", "push", "delete"]);
+        assert_eq!(granted_access(&server, &app, "bob", "repository:bob/new:pull,push").await[0].actions, vec!["pull", "push"]);
+        assert!(granted_access(&server, &app, "bob", "repository:alice/new:pull,push").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_login_token_reaches_admin_routes() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        let root = create_user(&server, "root").await;
+        create_user(&server, "dev").await;
+        sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1")
+            .bind(&root)
+            .execute(&server.database().pool)
+            .await
+            .unwrap();
+
+        let login = |username: &str| {
+            let body = serde_json::to_vec(&serde_json::json!({ "username": username, "password": "s3cret" })).unwrap();
+            let mut login = request(Method::POST, "/auth/login", body);
+            login.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            login
+        };
+        let session = |response: Response| async move {
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()["token"].as_str().unwrap().to_string()
+        };
+        let admin_token = session(send(&app, login("root")).await).await;
+        let dev_token = session(send(&app, login("dev")).await).await;
+
+        let claims = crate::auth::jwt::validate_token(&admin_token, &server.jwt).await.unwrap();
+        assert_eq!(claims.sub, root);
+        assert!(claims.scope.iter().any(|s| s == "admin"));
+
+        let get = |uri: &str, token: &str| {
+            let mut get = request(Method::GET, uri, vec![]);
+            get.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).unwrap());
+            get
+        };
+        assert_eq!(send(&app, get("/api/teams", &admin_token)).await.status(), StatusCode::OK);
+        assert_eq!(send(&app, get("/api/teams", &dev_token)).await.status(), StatusCode::FORBIDDEN);
+        assert_eq!(send(&app, get("/api/me", &dev_token)).await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_deleted_user_tokens_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        let root = create_user(&server, "root").await;
+        let dev = create_user(&server, "dev").await;
+        let pool = &server.database().pool;
+        sqlx::query("UPDATE users SET is_admin = TRUE WHERE id = $1").bind(&root).execute(pool).await.unwrap();
+
+        let dev_auth = bearer(&app, "dev", "repository:app:pull,push").await;
+        push_blob(&app, &dev_auth, "app", b"layer").await;
+        sqlx::query("INSERT INTO user_favorites (user_id, repository_id, created_at) SELECT $1, id, CURRENT_TIMESTAMP FROM repositories WHERE name = 'app'")
+            .bind(&root)
+            .execute(pool)
+            .await
+            .unwrap();
+
+        let authorized = |method: Method, uri: &str, auth: &HeaderValue| {
+            let mut req = request(method, uri, vec![]);
+            req.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+            req
+        };
+        assert_eq!(send(&app, authorized(Method::GET, "/api/me", &dev_auth)).await.status(), StatusCode::OK);
+
+        let body = serde_json::to_vec(&serde_json::json!({ "username": "root", "password": "s3cret" })).unwrap();
+        let mut login = request(Method::POST, "/auth/login", body);
+        login.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let session = axum::body::to_bytes(send(&app, login).await.into_body(), usize::MAX).await.unwrap();
+        let token = serde_json::from_slice::<serde_json::Value>(&session).unwrap()["token"].as_str().unwrap().to_string();
+        let root_auth = HeaderValue::from_str(&format!("Bearer {}", token)).unwrap();
+        let purge = format!("/api/users/{}?delete_resources=true", dev);
+        assert_eq!(send(&app, authorized(Method::DELETE, &purge, &root_auth)).await.status(), StatusCode::OK);
+
+        // The token is still validly signed, but its account is gone
+        assert_eq!(send(&app, authorized(Method::GET, "/api/me", &dev_auth)).await.status(), StatusCode::UNAUTHORIZED);
+        let favorites: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_favorites").fetch_one(pool).await.unwrap();
+        assert_eq!(favorites, 0);
+    }
+
+    #[tokio::test]
+    async fn test_stack_routes_are_served() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        create_user(&server, "dev").await;
+        let auth = bearer(&app, "dev", "").await;
+
+        let stack = serde_json::to_vec(&serde_json::json!({
+            "name": "web",
+            "description": null,
+            "compose_content": "version: \"3.8\"\nservices:\n  web:\n    image: nginx:1.25\n",
+            "tags": ["web"],
+            "is_public": false,
+        }))
+        .unwrap();
+        let mut create = request(Method::POST, "/api/stacks", stack);
+        create.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        create.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+        let created = send(&app, create).await;
+        assert_eq!(created.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(created.into_body(), usize::MAX).await.unwrap();
+        let id = serde_json::from_slice::<serde_json::Value>(&body).unwrap()["id"].as_str().unwrap().to_string();
+
+        let mut get = request(Method::GET, &format!("/api/stacks/{}", id), vec![]);
+        get.headers_mut().insert(header::AUTHORIZATION, auth);
+        assert_eq!(send(&app, get).await.status(), StatusCode::OK);
+
+        let anonymous = send(&app, request(Method::GET, "/api/stacks", vec![])).await;
+        assert_eq!(anonymous.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_anonymous_access_is_limited_to_public_pulls() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        let owner = create_user(&server, "dev").await;
+        for (name, is_public) in [("open", true), ("closed", false)] {
+            sqlx::query("INSERT INTO repositories (id, name, description, is_public, owner_id) VALUES ($1, $2, '', $3, $4)")
+                .bind(uuid::Uuid::new_v4())
+                .bind(name)
+                .bind(is_public)
+                .bind(&owner)
+                .execute(&server.database().pool)
+                .await
+                .unwrap();
+        }
+
+        // Past the access check, a missing tag is a 404
+        let public = send(&app, request(Method::GET, "/v2/open/manifests/latest", vec![])).await;
+        assert_eq!(public.status(), StatusCode::NOT_FOUND);
+
+        let private = send(&app, request(Method::GET, "/v2/closed/manifests/latest", vec![])).await;
+        assert_eq!(private.status(), StatusCode::UNAUTHORIZED);
+        assert!(private.headers().contains_key(header::WWW_AUTHENTICATE));
+        let push = send(&app, request(Method::POST, "/v2/open/blobs/uploads/", vec![])).await;
+        assert_eq!(push.status(), StatusCode::UNAUTHORIZED);
+        let delete = send(&app, request(Method::DELETE, "/v2/open/manifests/latest", vec![])).await;
+        assert_eq!(delete.status(), StatusCode::UNAUTHORIZED);
+
+        // Bad credentials are refused rather than treated as anonymous
+        let mut forged = request(Method::GET, "/v2/open/manifests/latest", vec![]);
+        forged.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_static("Bearer not-a-token"));
+        let forged = send(&app, forged).await;
+        assert_eq!(forged.status(), StatusCode::UNAUTHORIZED);
+        assert!(forged.headers().contains_key(header::WWW_AUTHENTICATE));
+
+        let mut pull = request(Method::GET, "/v2/closed/manifests/latest", vec![]);
+        pull.headers_mut().insert(header::AUTHORIZATION, bearer(&app, "dev", "repository:closed:pull").await);
+        assert_eq!(send(&app, pull).await.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_robot_push() {
+        use base64::Engine;
+
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        create_user(&server, "dev").await;
+        let owner = bearer(&app, "dev", "repository:app:pull,push").await;
+        let json_request = |method: Method, uri: &str, body: serde_json::Value| {
+            let mut built = request(method, uri, serde_json::to_vec(&body).unwrap());
+            built.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            built.headers_mut().insert(header::AUTHORIZATION, owner.clone());
+            built
+        };
+
+        // The owner's first push creates the repository
+        let mut started = request(Method::POST, "/v2/app/blobs/uploads/", vec![]);
+        started.headers_mut().insert(header::AUTHORIZATION, owner.clone());
+        let location = send(&app, started).await.headers()[header::LOCATION].to_str().unwrap().to_string();
+        let readme = b"hello".to_vec();
+        let mut uploaded = request(Method::PUT, &format!("{}?digest={}", location, sha256_digest(&readme)), readme);
+        uploaded.headers_mut().insert(header::AUTHORIZATION, owner.clone());
+        assert_eq!(send(&app, uploaded).await.status(), StatusCode::CREATED);
+
+        let robot = send(
+            &app,
+            json_request(Method::POST, "/api/repositories/app/robots", serde_json::json!({ "name": "ci", "permission": "read_write" })),
+        )
+        .await;
+        assert_eq!(robot.status(), StatusCode::CREATED);
+        let body = axum::body::to_bytes(robot.into_body(), usize::MAX).await.unwrap();
+        let robot = serde_json::from_slice::<serde_json::Value>(&body).unwrap();
+        let credentials = base64::engine::general_purpose::STANDARD.encode(format!(
+            "{}:{}",
+            robot["username"].as_str().unwrap(),
+            robot["token"].as_str().unwrap()
+        ));
+        let auth = HeaderValue::from_str(&format!("Basic {}", credentials)).unwrap();
+        let authorized = |mut request: Request<Body>| {
+            request.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+            request
+        };
+
+        let config_blob = br#"{"architecture":"amd64","os":"linux"}"#.to_vec();
+        let config_digest = sha256_digest(&config_blob);
+        let started = send(&app, authorized(request(Method::POST, "/v2/app/blobs/uploads/", vec![]))).await;
+        assert_eq!(started.status(), StatusCode::ACCEPTED);
+        let location = started.headers()[header::LOCATION].to_str().unwrap().to_string();
+        let uri = format!("{}?digest={}", location, config_digest);
+        let uploaded = send(&app, authorized(request(Method::PUT, &uri, config_blob.clone()))).await;
+        assert_eq!(uploaded.status(), StatusCode::CREATED);
+
+        let manifest = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": media_types::OCI_MANIFEST,
+            "config": { "mediaType": media_types::OCI_CONFIG, "digest": config_digest, "size": config_blob.len() },
+            "layers": [],
+        }))
+        .unwrap();
+        let mut push = authorized(request(Method::PUT, "/v2/app/manifests/v1", manifest));
+        push.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(media_types::OCI_MANIFEST));
+        assert_eq!(send(&app, push).await.status(), StatusCode::CREATED);
+
+        // The robot is scoped to its own repository
+        let elsewhere = send(&app, authorized(request(Method::POST, "/v2/other/blobs/uploads/", vec![]))).await;
+        assert_eq!(elsewhere.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_push_and_pull_through_router() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        create_user(&server, "dev").await;
+        let auth = bearer(&app, "dev", "repository:app:pull,push").await;
+        let authorized = |mut request: Request<Body>| {
+            request.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+            request
+        };
+
+        // The config blob goes up in a single PUT
+        let config_blob = br#"{"architecture":"amd64","os":"linux"}"#.to_vec();
+        let config_digest = sha256_digest(&config_blob);
+        let started = send(&app, authorized(request(Method::POST, "/v2/app/blobs/uploads/", vec![]))).await;
+        assert_eq!(started.status(), StatusCode::ACCEPTED);
+        let location = started.headers()[header::LOCATION].to_str().unwrap().to_string();
+        let uri = format!("{}?digest={}", location, config_digest);
+        let uploaded = send(&app, authorized(request(Method::PUT, &uri, config_blob.clone()))).await;
+        assert_eq!(uploaded.status(), StatusCode::CREATED);
+
+        let manifest = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": media_types::OCI_MANIFEST,
+            "config": { "mediaType": media_types::OCI_CONFIG, "digest": config_digest, "size": config_blob.len() },
+            "layers": [],
+        }))
+        .unwrap();
+        let digest = sha256_digest(&manifest);
+        let mut push = authorized(request(Method::PUT, "/v2/app/manifests/v1", manifest.clone()));
+        push.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(media_types::OCI_MANIFEST));
+        let pushed = send(&app, push).await;
+        assert_eq!(pushed.status(), StatusCode::CREATED);
+        assert_eq!(pushed.headers()[registry_headers::CONTENT_DIGEST], digest.as_str());
+
+        for reference in ["v1", digest.as_str()] {
+            let uri = format!("/v2/app/manifests/{}", reference);
+            let mut pull = authorized(request(Method::GET, &uri, vec![]));
+            pull.headers_mut().insert(header::ACCEPT, HeaderValue::from_static(media_types::OCI_MANIFEST));
+            let pulled = send(&app, pull).await;
+            assert_eq!(pulled.status(), StatusCode::OK);
+            assert_eq!(pulled.headers()[header::CONTENT_TYPE], media_types::OCI_MANIFEST);
+            assert_eq!(pulled.headers()[registry_headers::CONTENT_DIGEST], digest.as_str());
+            let body = axum::body::to_bytes(pulled.into_body(), usize::MAX).await.unwrap();
+            assert_eq!(body.as_ref(), manifest.as_slice());
+
+            let head = send(&app, authorized(request(Method::HEAD, &uri, vec![]))).await;
+            assert_eq!(head.status(), StatusCode::OK);
+            assert_eq!(head.headers()[registry_headers::CONTENT_DIGEST], digest.as_str());
+        }
+
+        let missing = send(&app, authorized(request(Method::GET, "/v2/app/manifests/v2", vec![]))).await;
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+    }
+
+    /// Monolithic blob upload, returning the blob's digest
+    async fn push_blob(app: &Router, auth: &HeaderValue, name: &str, blob: &[u8]) -> String {
+        let digest = sha256_digest(blob);
+        let mut started = request(Method::POST, &format!("/v2/{}/blobs/uploads/", name), vec![]);
+        started.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+        let started = send(app, started).await;
+        let location = started.headers()[header::LOCATION].to_str().unwrap().to_string();
+        let mut uploaded = request(Method::PUT, &format!("{}?digest={}", location, digest), blob.to_vec());
+        uploaded.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+        let uploaded = send(app, uploaded).await;
+        assert_eq!(uploaded.status(), StatusCode::CREATED);
+        digest
+    }
+
+    async fn push_manifest(
+        app: &Router,
+        auth: &HeaderValue,
+        name: &str,
+        reference: &str,
+        media_type: &'static str,
+        manifest: &[u8],
+    ) -> Response {
+        let mut push = request(Method::PUT, &format!("/v2/{}/manifests/{}", name, reference), manifest.to_vec());
+        push.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static(media_type));
+        push.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+        send(app, push).await
+    }
+
+    async fn pull_manifest(app: &Router, auth: &HeaderValue, uri: &str, accept: &'static str) -> (String, Vec<u8>) {
+        let mut pull = request(Method::GET, uri, vec![]);
+        pull.headers_mut().insert(header::ACCEPT, HeaderValue::from_static(accept));
+        pull.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+        let pulled = send(app, pull).await;
+        assert_eq!(pulled.status(), StatusCode::OK);
+        let digest = pulled.headers()[registry_headers::CONTENT_DIGEST].to_str().unwrap().to_string();
+        let body = axum::body::to_bytes(pulled.into_body(), usize::MAX).await.unwrap();
+        (digest, body.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_manifest_list_push_and_platform_pull() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        create_user(&server, "dev").await;
+        let auth = bearer(&app, "dev", "repository:app:pull,push").await;
+
+        // One image manifest per platform, pushed by digest
+        let mut children = Vec::new();
+        for architecture in ["amd64", "arm64"] {
+            let config_blob = serde_json::to_vec(&serde_json::json!({ "architecture": architecture, "os": "linux" })).unwrap();
+            let config_digest = push_blob(&app, &auth, "app", &config_blob).await;
+            let manifest = serde_json::to_vec(&serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": media_types::OCI_MANIFEST,
+                "config": { "mediaType": media_types::OCI_CONFIG, "digest": config_digest, "size": config_blob.len() },
+                "layers": [],
+            }))
+            .unwrap();
+            let digest = sha256_digest(&manifest);
+            let pushed = push_manifest(&app, &auth, "app", &digest, media_types::OCI_MANIFEST, &manifest).await;
+            assert_eq!(pushed.status(), StatusCode::CREATED);
+            children.push((architecture, digest, manifest.len()));
+        }
+
+        let index = |entries: &[(&str, String, usize)]| {
+            serde_json::to_vec(&serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": media_types::OCI_INDEX,
+                "manifests": entries.iter().map(|(architecture, digest, size)| serde_json::json!({
+                    "mediaType": media_types::OCI_MANIFEST,
+                    "digest": digest,
+                    "size": size,
+                    "platform": { "os": "linux", "architecture": architecture },
+                })).collect::<Vec<_>>(),
+            }))
+            .unwrap()
+        };
+
+        // A list naming a manifest the repository doesn't hold is refused
+        let dangling = index(&[children[0].clone(), ("arm64", format!("sha256:{}", "0".repeat(64)), 2)]);
+        let refused = push_manifest(&app, &auth, "app", "multi", media_types::OCI_INDEX, &dangling).await;
+        assert_eq!(refused.status(), StatusCode::BAD_REQUEST);
+
+        let list = index(&children);
+        let list_digest = sha256_digest(&list);
+        let pushed = push_manifest(&app, &auth, "app", "multi", media_types::OCI_INDEX, &list).await;
+        assert_eq!(pushed.status(), StatusCode::CREATED);
+
+        // Clients that take lists get the list itself
+        let (digest, body) = pull_manifest(&app, &auth, "/v2/app/manifests/multi", media_types::OCI_INDEX).await;
+        assert_eq!(digest, list_digest);
+        assert_eq!(body, list);
+
+        // Single-manifest clients get linux/amd64, or the platform they ask for
+        let (digest, _) = pull_manifest(&app, &auth, "/v2/app/manifests/multi", media_types::OCI_MANIFEST).await;
+        assert_eq!(digest, children[0].1);
+        let (digest, _) =
+            pull_manifest(&app, &auth, "/v2/app/manifests/multi?platform=linux/arm64", media_types::OCI_MANIFEST).await;
+        assert_eq!(digest, children[1].1);
+
+        // A digest pull always returns exactly those bytes
+        let (digest, _) =
+            pull_manifest(&app, &auth, &format!("/v2/app/manifests/{}", list_digest), media_types::OCI_MANIFEST).await;
+        assert_eq!(digest, list_digest);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_pushed_during_deferred_verification_links_blobs() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router_with(dir.path(), |config| {
+            config.storage.blob_verification = crate::config::BlobVerification::Deferred;
+        })
+        .await;
+        create_user(&server, "dev").await;
+        let auth = bearer(&app, "dev", "repository:app:pull,push").await;
+
+        let config_blob = br#"{"architecture":"amd64","os":"linux"}"#.to_vec();
+        let layer = vec![7u8; 8 * 1024 * 1024];
+        let image = |layer_digest: &str, config_digest: &str| {
+            serde_json::to_vec(&serde_json::json!({
+                "schemaVersion": 2,
+                "mediaType": media_types::OCI_MANIFEST,
+                "config": { "mediaType": media_types::OCI_CONFIG, "digest": config_digest, "size": config_blob.len() },
+                "layers": [{ "mediaType": media_types::OCI_LAYERS[1], "digest": layer_digest, "size": layer.len() }],
+            }))
+            .unwrap()
+        };
+
+        // A layer that was never uploaded is refused rather than left for GC to miss
+        let config_digest = push_blob(&app, &auth, "app", &config_blob).await;
+        let refused = push_manifest(&app, &auth, "app", "v1", media_types::OCI_MANIFEST, &image(&sha256_digest(&layer), &config_digest)).await;
+        assert_eq!(refused.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(refused.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("MANIFEST_BLOB_UNKNOWN"));
+
+        // Pushed straight after its layers are acknowledged, likely before they are verified
+        let layer_digest = push_blob(&app, &auth, "app", &layer).await;
+        let manifest = image(&layer_digest, &config_digest);
+        let pushed = push_manifest(&app, &auth, "app", "v1", media_types::OCI_MANIFEST, &manifest).await;
+        assert_eq!(pushed.status(), StatusCode::CREATED);
+
+        // Either way, both blobs end up linked once verification finishes
+        let mut linked = 0;
+        for _ in 0..100 {
+            linked = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM manifest_blobs mb JOIN manifests m ON m.id = mb.manifest_id WHERE m.digest = $1",
+            )
+            .bind(sha256_digest(&manifest))
+            .fetch_one(&server.database().pool)
+            .await
+            .unwrap();
+            if linked == 2 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(linked, 2);
+    }
+
+    #[tokio::test]
+    async fn test_server_refuses_storage_encryption() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.database.path = dir.path().join("ghostdock.db");
+        config.storage.path = dir.path().join("storage");
+        config.storage.encryption.enabled = true;
+
+        let err = Server::with_config(config, None).await.err().unwrap();
+        assert!(err.to_string().contains("storage.encryption"));
+    }
+
+    #[tokio::test]
+    async fn test_rejected_manifest_creates_no_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        create_user(&server, "dev").await;
+        let auth = bearer(&app, "dev", "repository:app:pull,push").await;
+
+        let missing_blob = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": media_types::OCI_MANIFEST,
+            "config": { "mediaType": media_types::OCI_CONFIG, "digest": sha256_digest(b"{}"), "size": 2 },
+            "layers": [],
+        }))
+        .unwrap();
+        for manifest in [b"not a manifest".to_vec(), missing_blob] {
+            let pushed = push_manifest(&app, &auth, "app", "v1", media_types::OCI_MANIFEST, &manifest).await;
+            assert_eq!(pushed.status(), StatusCode::BAD_REQUEST);
+        }
+
+        let repositories: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM repositories WHERE name = 'app'")
+            .fetch_one(&server.database().pool)
+            .await
+            .unwrap();
+        assert_eq!(repositories, 0);
+    }
+
+    #[tokio::test]
+    async fn test_oversized_manifest_is_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router_with(dir.path(), |config| config.registry.max_manifest_size = 256).await;
+        create_user(&server, "dev").await;
+        let auth = bearer(&app, "dev", "repository:app:pull,push").await;
+
+        let manifest = serde_json::to_vec(&serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": media_types::OCI_INDEX,
+            "manifests": [],
+            "annotations": { "padding": "x".repeat(512) },
+        }))
+        .unwrap();
+        let pushed = push_manifest(&app, &auth, "app", "v1", media_types::OCI_INDEX, &manifest).await;
+        assert_eq!(pushed.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(pushed.into_body(), usize::MAX).await.unwrap();
+        assert!(String::from_utf8_lossy(&body).contains("MANIFEST_INVALID"));
+    }
+
+    #[tokio::test]
+    async fn test_uploads_are_bounded_and_bound_to_their_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router_with(dir.path(), |config| config.storage.max_upload_size = 16).await;
+        create_user(&server, "dev").await;
+        let auth = bearer(&app, "dev", "repository:app:pull,push").await;
+        let other = bearer(&app, "dev", "repository:other:pull,push").await;
+        push_blob(&app, &other, "other", b"elsewhere").await;
+
+        let authorized = |method: Method, uri: &str, body: &[u8], auth: &HeaderValue| {
+            let mut req = request(method, uri, body.to_vec());
+            req.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+            req
+        };
+        let started = send(&app, authorized(Method::POST, "/v2/app/blobs/uploads/", b"", &auth)).await;
+        let location = started.headers()[header::LOCATION].to_str().unwrap().to_string();
+
+        // Chunks that fit are accepted; one that takes the blob past the limit is not
+        let patched = send(&app, authorized(Method::PATCH, &location, &[1u8; 10], &auth)).await;
+        assert_eq!(patched.status(), StatusCode::ACCEPTED);
+        let patched = send(&app, authorized(Method::PATCH, &location, &[1u8; 10], &auth)).await;
+        assert_eq!(patched.status(), StatusCode::BAD_REQUEST);
+
+        // The session can't be driven through another repository's path
+        let foreign = location.replacen("/v2/app/", "/v2/other/", 1);
+        let digest = sha256_digest(&[1u8; 10]);
+        for method in [Method::GET, Method::PATCH, Method::PUT] {
+            let uri = if method == Method::PUT { format!("{}?digest={}", foreign, digest) } else { foreign.clone() };
+            let response = send(&app, authorized(method.clone(), &uri, b"", &other)).await;
+            assert_eq!(response.status(), StatusCode::NOT_FOUND, "{} through another repository", method);
+        }
+        // Cancelling it there is a no-op, so it still completes where it was started
+        send(&app, authorized(Method::DELETE, &foreign, b"", &other)).await;
+
+        let completed = send(&app, authorized(Method::PUT, &format!("{}?digest={}", location, digest), b"", &auth)).await;
+        assert_eq!(completed.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_blob_delete_only_unlinks_repository() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        create_user(&server, "dev").await;
+
+        let blob = b"shared layer".to_vec();
+        let digest = push_blob(&app, &bearer(&app, "dev", "repository:app:pull,push").await, "app", &blob).await;
+        let other = bearer(&app, "dev", "repository:other:pull,push").await;
+        push_blob(&app, &other, "other", &blob).await;
+
+        let auth = bearer(&app, "dev", "repository:app:pull,push,delete").await;
+        let blob_request = |method: Method, name: &str, auth: &HeaderValue| {
+            let mut req = request(method, &format!("/v2/{}/blobs/{}", name, digest), vec![]);
+            req.headers_mut().insert(header::AUTHORIZATION, auth.clone());
+            req
+        };
+        // Prime the existence cache so the delete has to invalidate it
+        assert_eq!(send(&app, blob_request(Method::HEAD, "app", &auth)).await.status(), StatusCode::OK);
+        assert_eq!(send(&app, blob_request(Method::DELETE, "app", &auth)).await.status(), StatusCode::ACCEPTED);
+        assert_eq!(send(&app, blob_request(Method::HEAD, "app", &auth)).await.status(), StatusCode::NOT_FOUND);
+
+        // The other repository still serves the content, which stays stored for GC to judge
+        let pulled = send(&app, blob_request(Method::GET, "other", &other)).await;
+        assert_eq!(pulled.status(), StatusCode::OK);
+        assert_eq!(axum::body::to_bytes(pulled.into_body(), usize::MAX).await.unwrap(), blob);
+        let rows: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM blobs WHERE digest = $1")
+            .bind(&digest)
+            .fetch_one(&server.database().pool)
+            .await
+            .unwrap();
+        assert_eq!(rows, 1);
+    }
+
+    #[tokio::test]
+    async fn test_docker_login_token_flow() {
+        let dir = tempfile::tempdir().unwrap();
+        let (server, app) = router(dir.path()).await;
+        create_user(&server, "dev").await;
+
+        // The client is pointed at the token endpoint on the host it called
+        let mut ping = request(Method::GET, "/v2/", vec![]);
+        ping.headers_mut().insert(header::HOST, HeaderValue::from_static("registry.example.com"));
+        let challenged = send(&app, ping).await;
+        assert_eq!(challenged.status(), StatusCode::UNAUTHORIZED);
+        let challenge = challenged.headers()[header::WWW_AUTHENTICATE].to_str().unwrap();
+        assert!(challenge.starts_with(r#"Bearer realm="http://registry.example.com/token",service="ghostdock""#));
+
+        let json = |response: Response| async move {
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            serde_json::from_slice::<serde_json::Value>(&body).unwrap()
+        };
+
+        let mut fetch = request(Method::GET, "/token?account=dev&service=ghostdock", vec![]);
+        fetch.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_static("Basic ZGV2OnMzY3JldA=="));
+        let issued = send(&app, fetch).await;
+        assert_eq!(issued.status(), StatusCode::OK);
+        let issued = json(issued).await;
+        assert!(issued["expires_in"].as_u64().unwrap() > 0);
+        let token = issued["token"].as_str().unwrap().to_string();
+
+        let mut ping = request(Method::GET, "/v2/", vec![]);
+        ping.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_str(&format!("Bearer {}", token)).unwrap());
+        assert_eq!(send(&app, ping).await.status(), StatusCode::OK);
+
+        // Wrong password
+        let mut fetch = request(Method::GET, "/token?service=ghostdock", vec![]);
+        fetch.headers_mut().insert(header::AUTHORIZATION, HeaderValue::from_static("Basic ZGV2Ondyb25n"));
+        assert_eq!(send(&app, fetch).await.status(), StatusCode::UNAUTHORIZED);
+
+        // OAuth2 password grant, as containerd sends it
+        let oauth = |body: &str| {
+            let mut post = request(Method::POST, "/token", body.as_bytes().to_vec());
+            post.headers_mut().insert(header::CONTENT_TYPE, HeaderValue::from_static("application/x-www-form-urlencoded"));
+            post
+        };
+        let granted = send(&app, oauth("grant_type=password&username=dev&password=s3cret&service=ghostdock&scope=repository%3Ateam%2Fapp%3Apull")).await;
+        assert_eq!(granted.status(), StatusCode::OK);
+        let granted = json(granted).await;
+        assert_eq!(granted["scope"], "repository:team/app:pull");
+        assert!(granted["access_token"].as_str().is_some_and(|t| !t.is_empty()));
+
+        let refresh = send(&app, oauth("grant_type=refresh_token&refresh_token=abc&service=ghostdock")).await;
+        assert_eq!(refresh.status(), StatusCode::BAD_REQUEST);
+    }
 }
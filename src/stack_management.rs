@@ -1,20 +1,25 @@
 use axum::{
     extract::{Path, Query, State},
-    http::StatusCode,
+    http::{header, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
     routing::{get, post, put, delete},
     Router, Json,
 };
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
 use std::collections::HashMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
 use crate::{
-    auth::middleware::AuthenticatedUser,
-    database::Database,
-    error::Result,
+    auth::middleware::{authorize_repository, AuthenticatedUser},
+    database::{queries::record_audit, Database},
+    deployments::{self, DeployRequest, DeploymentStatus},
+    error::{Error, Result},
+    import::RemoteRegistry,
     server::AppState,
+    types::{Page, PageQuery},
 };
 
 /// Docker Compose Stack Management
@@ -61,8 +66,6 @@ pub struct UpdateStackRequest {
 /// Stack query parameters
 #[derive(Debug, Deserialize)]
 pub struct StackQuery {
-    pub limit: Option<usize>,
-    pub offset: Option<usize>,
     pub search: Option<String>,
     pub tags: Option<String>,
     pub author: Option<String>,
@@ -79,6 +82,106 @@ pub struct ImportStackRequest {
     pub is_public: Option<bool>,
 }
 
+/// Stack validation (preflight) request
+#[derive(Debug, Deserialize)]
+pub struct ValidateStackRequest {
+    pub compose_content: String,
+}
+
+/// Availability report for a single image referenced by a stack
+#[derive(Debug, Serialize)]
+pub struct ImageCheck {
+    pub service: String,
+    pub image: String,
+    pub registry: String,
+    pub repository: String,
+    pub reference: String,
+    pub resolvable: bool,
+    pub source: Option<String>,
+    pub reason: Option<String>,
+}
+
+/// Security lint finding for a compose service
+#[derive(Debug, Serialize)]
+pub struct LintFinding {
+    pub service: String,
+    pub severity: String,
+    pub message: String,
+}
+
+/// Result of a stack preflight validation
+#[derive(Debug, Serialize)]
+pub struct StackValidationReport {
+    pub valid: bool,
+    pub errors: Vec<String>,
+    pub resolvable: Vec<ImageCheck>,
+    pub unresolvable: Vec<ImageCheck>,
+    pub build_only_services: Vec<String>,
+    pub lint_findings: Vec<LintFinding>,
+}
+
+/// Stack with its discovery ranking
+#[derive(Debug, Serialize)]
+pub struct RankedStack {
+    #[serde(flatten)]
+    pub stack: Stack,
+    pub featured: bool,
+    pub popularity_score: f64,
+}
+
+/// Admin request to (un)feature a stack
+#[derive(Debug, Deserialize)]
+pub struct FeatureStackRequest {
+    pub featured: bool,
+}
+
+/// Popularity halves every week without new activity
+pub const POPULARITY_HALF_LIFE_DAYS: i64 = 7;
+
+/// Activity older than this many half-lives contributes under 0.1% and is pruned
+const POPULARITY_HORIZON_HALF_LIVES: i64 = 10;
+
+/// How often popularity scores are recomputed
+pub const POPULARITY_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(15 * 60);
+
+/// Events that feed the popularity score
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StackActivity {
+    Download,
+    Star,
+}
+
+impl StackActivity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StackActivity::Download => "download",
+            StackActivity::Star => "star",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "download" => Some(StackActivity::Download),
+            "star" => Some(StackActivity::Star),
+            _ => None,
+        }
+    }
+
+    /// A star signals more intent than a download
+    fn weight(&self) -> f64 {
+        match self {
+            StackActivity::Download => 1.0,
+            StackActivity::Star => 3.0,
+        }
+    }
+}
+
+/// Contribution of one activity event after exponential decay
+pub fn decayed_weight(activity: StackActivity, age: chrono::Duration) -> f64 {
+    let half_life = chrono::Duration::days(POPULARITY_HALF_LIFE_DAYS).num_seconds() as f64;
+    activity.weight() * 0.5f64.powf(age.num_seconds().max(0) as f64 / half_life)
+}
+
 /// Stack routes
 pub fn stack_routes() -> Router<AppState> {
     Router::new()
@@ -94,74 +197,34 @@ pub fn stack_routes() -> Router<AppState> {
         
         // Stack import/export
         .route("/api/stacks/import", post(import_stack_from_url))
+        .route("/api/stacks/validate", post(validate_stack))
         .route("/api/stacks/:id/export", get(export_stack))
         
         // Stack deployment
         .route("/api/stacks/:id/deploy", post(deploy_stack))
         .route("/api/stacks/:id/undeploy", post(undeploy_stack))
         .route("/api/stacks/:id/status", get(get_deployment_status))
+        .route("/api/stacks/:id/rollback", post(rollback_stack))
         
         // Public stack registry
         .route("/api/registry/stacks", get(list_public_stacks))
         .route("/api/registry/stacks/featured", get(list_featured_stacks))
         .route("/api/registry/stacks/popular", get(list_popular_stacks))
+        .route("/api/registry/stacks/:id/featured", put(set_stack_featured))
 }
 
 /// List stacks for the authenticated user
 async fn list_stacks(
     Query(query): Query<StackQuery>,
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<impl IntoResponse> {
-    let limit = query.limit.unwrap_or(20).min(100);
-    let offset = query.offset.unwrap_or(0);
-    
-    // Build filter conditions
-    let mut conditions = vec![];
-    let mut params = vec![];
-    
-    if !query.public_only.unwrap_or(false) {
-        conditions.push("(author = ? OR is_public = true)");
-        params.push(user.id.clone());
-    } else {
-        conditions.push("is_public = true");
-    }
-    
-    if let Some(search) = &query.search {
-        conditions.push("(name LIKE ? OR description LIKE ?)");
-        let search_pattern = format!("%{}%", search);
-        params.push(search_pattern.clone());
-        params.push(search_pattern);
-    }
+    let (limit, offset) = page.bounds()?;
+    let viewer = (!query.public_only.unwrap_or(false)).then_some(user.id.as_str());
+    let (stacks, total) = query_stacks(&state.database.pool, &query, viewer, limit, offset).await?;
     
-    if let Some(tags) = &query.tags {
-        let tag_list: Vec<&str> = tags.split(',').collect();
-        for tag in tag_list {
-            conditions.push("tags LIKE ?");
-            params.push(format!("%{}%", tag));
-        }
-    }
-    
-    if let Some(author) = &query.author {
-        conditions.push("author = ?");
-        params.push(author.clone());
-    }
-    
-    let where_clause = if conditions.is_empty() {
-        String::new()
-    } else {
-        format!("WHERE {}", conditions.join(" AND "))
-    };
-    
-    // TODO: Execute database query
-    let stacks: Vec<Stack> = vec![]; // Placeholder
-    
-    Ok(Json(serde_json::json!({
-        "stacks": stacks,
-        "total": 0,
-        "limit": limit,
-        "offset": offset
-    })))
+    Ok(Json(Page::new(stacks, total, limit, offset)))
 }
 
 /// Create a new stack
@@ -198,7 +261,8 @@ async fn create_stack(
         star_count: 0,
     };
     
-    // TODO: Save stack to database
+    insert_stack(&state.database.pool, &stack).await?;
+    record_audit(&state, &user.id, "stack.create", &stack.id, Some(serde_json::json!({ "name": stack.name }))).await?;
     
     Ok((StatusCode::CREATED, Json(&stack)).into_response())
 }
@@ -209,25 +273,7 @@ async fn get_stack(
     State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<impl IntoResponse> {
-    // TODO: Get stack from database
-    // Check if user has access (owner or public)
-    
-    let stack = Stack {
-        id: id.clone(),
-        name: "Example Stack".to_string(),
-        description: Some("An example Docker Compose stack".to_string()),
-        compose_content: "version: '3.8'\nservices:\n  web:\n    image: nginx:latest".to_string(),
-        version: "1.0.0".to_string(),
-        author: user.id.clone(),
-        author_email: user.email.clone(),
-        tags: vec!["web".to_string(), "nginx".to_string()],
-        is_public: true,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-        download_count: 42,
-        star_count: 5,
-    };
-    
+    let stack = visible_stack(&state.database.pool, &id, Some(&user.id)).await?;
     Ok(Json(stack))
 }
 
@@ -238,8 +284,7 @@ async fn update_stack(
     user: AuthenticatedUser,
     Json(request): Json<UpdateStackRequest>,
 ) -> Result<impl IntoResponse> {
-    // TODO: Update stack in database
-    // Check if user is the owner
+    let mut stack = owned_stack(&state.database.pool, &id, &user, "update").await?;
     
     if let Some(compose_content) = &request.compose_content {
         if let Err(validation_error) = validate_compose_content(compose_content) {
@@ -253,7 +298,45 @@ async fn update_stack(
         }
     }
     
-    Ok((StatusCode::OK, Json(serde_json::json!({"message": "Stack updated successfully"}))).into_response())
+    if let Some(name) = request.name {
+        stack.name = name;
+    }
+    if let Some(description) = request.description {
+        stack.description = Some(description);
+    }
+    if let Some(compose_content) = request.compose_content {
+        stack.compose_content = compose_content;
+    }
+    if let Some(tags) = request.tags {
+        stack.tags = tags;
+    }
+    if let Some(is_public) = request.is_public {
+        stack.is_public = is_public;
+    }
+    stack.updated_at = Utc::now();
+    if stack.name.trim().is_empty() {
+        return Err(Error::validation("Stack name must not be empty"));
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE stacks
+        SET name = $1, description = $2, compose_content = $3, tags = $4, is_public = $5, updated_at = $6
+        WHERE id = $7
+        "#
+    )
+    .bind(&stack.name)
+    .bind(&stack.description)
+    .bind(&stack.compose_content)
+    .bind(serde_json::to_string(&stack.tags)?)
+    .bind(stack.is_public)
+    .bind(stack.updated_at)
+    .bind(&stack.id)
+    .execute(&state.database.pool)
+    .await?;
+    record_audit(&state, &user.id, "stack.update", &stack.id, None).await?;
+
+    Ok((StatusCode::OK, Json(stack)).into_response())
 }
 
 /// Delete a stack
@@ -262,8 +345,27 @@ async fn delete_stack(
     State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<impl IntoResponse> {
-    // TODO: Delete stack from database
-    // Check if user is the owner
+    let stack = owned_stack(&state.database.pool, &id, &user, "delete").await?;
+
+    // Deployment history goes with the stack, but running containers have to be torn down first
+    if let Some(deployment) = deployments::latest_for_stack(&state.database.pool, &id).await? {
+        if deployment.status != DeploymentStatus::Stopped {
+            return Err(Error::conflict(format!("Stack {} is deployed; undeploy it before deleting", id)));
+        }
+    }
+
+    let mut tx = state.database.pool.begin().await?;
+    sqlx::query("DELETE FROM deployments WHERE stack_id = $1")
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+    // Stars and activity cascade
+    sqlx::query("DELETE FROM stacks WHERE id = $1")
+        .bind(&id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    record_audit(&state, &user.id, "stack.delete", &id, Some(serde_json::json!({ "name": stack.name }))).await?;
     
     Ok(StatusCode::NO_CONTENT)
 }
@@ -274,7 +376,18 @@ async fn star_stack(
     State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<impl IntoResponse> {
-    // TODO: Add star to database
+    let starred = sqlx::query("INSERT OR IGNORE INTO stack_stars (stack_id, user_id, created_at) VALUES ($1, $2, $3)")
+        .bind(&id)
+        .bind(&user.id)
+        .bind(Utc::now())
+        .execute(&state.database.pool)
+        .await?
+        .rows_affected() > 0;
+
+    // Only a new star counts towards popularity
+    if starred {
+        record_activity(&state.database.pool, &id, StackActivity::Star).await?;
+    }
     
     Ok(Json(serde_json::json!({"message": "Stack starred successfully"})))
 }
@@ -285,7 +398,19 @@ async fn unstar_stack(
     State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<impl IntoResponse> {
-    // TODO: Remove star from database
+    let unstarred = sqlx::query("DELETE FROM stack_stars WHERE stack_id = $1 AND user_id = $2")
+        .bind(&id)
+        .bind(&user.id)
+        .execute(&state.database.pool)
+        .await?
+        .rows_affected() > 0;
+
+    if unstarred {
+        sqlx::query("UPDATE stacks SET star_count = MAX(star_count - 1, 0) WHERE id = $1")
+            .bind(&id)
+            .execute(&state.database.pool)
+            .await?;
+    }
     
     Ok(Json(serde_json::json!({"message": "Stack unstarred successfully"})))
 }
@@ -295,24 +420,11 @@ async fn download_stack(
     Path(id): Path<String>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse> {
-    // TODO: Increment download counter in database
-    // Return the stack content
-    
-    let stack = Stack {
-        id: id.clone(),
-        name: "Example Stack".to_string(),
-        description: Some("An example Docker Compose stack".to_string()),
-        compose_content: "version: '3.8'\nservices:\n  web:\n    image: nginx:latest".to_string(),
-        version: "1.0.0".to_string(),
-        author: "user123".to_string(),
-        author_email: "user@example.com".to_string(),
-        tags: vec!["web".to_string(), "nginx".to_string()],
-        is_public: true,
-        created_at: chrono::Utc::now(),
-        updated_at: chrono::Utc::now(),
-        download_count: 43,
-        star_count: 5,
-    };
+    let mut stack = visible_stack(&state.database.pool, &id, None).await?;
+    match record_activity(&state.database.pool, &id, StackActivity::Download).await {
+        Ok(()) => stack.download_count += 1,
+        Err(e) => tracing::warn!("Failed to record download of stack {}: {}", id, e),
+    }
     
     Ok(Json(stack))
 }
@@ -322,15 +434,13 @@ async fn get_stack_raw(
     Path(id): Path<String>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse> {
-    // TODO: Get stack from database
-    
-    let compose_content = "version: '3.8'\nservices:\n  web:\n    image: nginx:latest\n    ports:\n      - \"80:80\"";
+    let stack = visible_stack(&state.database.pool, &id, None).await?;
     
     Ok(Response::builder()
         .status(StatusCode::OK)
         .header("Content-Type", "text/yaml")
         .header("Content-Disposition", format!("attachment; filename=\"{}.yml\"", id))
-        .body(axum::body::Body::from(compose_content))
+        .body(axum::body::Body::from(stack.compose_content))
         .unwrap())
 }
 
@@ -399,27 +509,74 @@ async fn import_stack_from_url(
         star_count: 0,
     };
     
-    // TODO: Save stack to database
+    insert_stack(&state.database.pool, &stack).await?;
+    record_audit(&state, &user.id, "stack.import", &stack.id, Some(serde_json::json!({ "url": request.url }))).await?;
     
     Ok((StatusCode::CREATED, Json(&stack)).into_response())
 }
 
+/// Validate a stack without saving it (non-destructive preflight)
+async fn validate_stack(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    headers: HeaderMap,
+    Json(request): Json<ValidateStackRequest>,
+) -> Result<impl IntoResponse> {
+    let mut report = StackValidationReport {
+        valid: true,
+        errors: vec![],
+        resolvable: vec![],
+        unresolvable: vec![],
+        build_only_services: vec![],
+        lint_findings: vec![],
+    };
+
+    if let Err(validation_error) = validate_compose_content(&request.compose_content) {
+        report.valid = false;
+        report.errors.push(validation_error);
+        return Ok(Json(report));
+    }
+
+    let parsed: serde_yaml::Value = serde_yaml::from_str(&request.compose_content)
+        .map_err(|e| crate::error::Error::validation(format!("YAML parsing error: {}", e)))?;
+
+    let (images, build_only) = extract_service_images(&parsed);
+    report.build_only_services = build_only;
+    report.lint_findings = lint_compose_services(&parsed);
+
+    let local_hosts = local_registry_hosts(&state, &headers);
+    for (service, image) in images {
+        let check = check_image_availability(&state, &user, &local_hosts, service, image).await;
+        if check.resolvable {
+            report.resolvable.push(check);
+        } else {
+            report.unresolvable.push(check);
+        }
+    }
+
+    report.valid = report.unresolvable.is_empty();
+
+    Ok(Json(report))
+}
+
 /// Export stack
 async fn export_stack(
     Path(id): Path<String>,
     State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<impl IntoResponse> {
-    // TODO: Get stack from database and create export package
+    let stack = visible_stack(&state.database.pool, &id, Some(&user.id)).await?;
     
     let export_data = serde_json::json!({
         "format": "ghostdock-stack-v1",
         "exported_at": chrono::Utc::now(),
         "exported_by": user.email,
         "stack": {
-            "name": "example-stack",
-            "description": "Example stack",
-            "compose_content": "version: '3.8'\nservices:\n  web:\n    image: nginx:latest"
+            "name": stack.name,
+            "description": stack.description,
+            "version": stack.version,
+            "tags": stack.tags,
+            "compose_content": stack.compose_content
         }
     });
     
@@ -431,91 +588,488 @@ async fn export_stack(
         .unwrap())
 }
 
-/// Deploy stack
+/// Deploy stack with `docker compose`; the deployment is tracked until it is undeployed.
+/// Only admins and `[deployments]` allowed users may deploy, and stacks asking for host
+/// access are refused. Callers at `concurrency.max_deployments_per_user` get 429 until one
+/// of theirs stops.
 async fn deploy_stack(
     Path(id): Path<String>,
     State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<impl IntoResponse> {
-    // TODO: Implement stack deployment using Docker Compose
-    // This would require integration with Docker daemon
+    deployments::ensure_may_deploy(&state, &user).await?;
+
+    let row = sqlx::query("SELECT compose_content, author, is_public FROM stacks WHERE id = $1")
+        .bind(&id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Stack {} not found", id)))?;
+
+    let author: String = row.get("author");
+    if author != user.id && !row.get::<bool, _>("is_public") {
+        return Err(Error::not_found(format!("Stack {} not found", id)));
+    }
+
+    let deployment = deployments::start(
+        &state,
+        DeployRequest {
+            stack_id: &id,
+            compose_content: row.get("compose_content"),
+            started_by: &user.id,
+            rolled_back_from: None,
+        },
+    )
+    .await?;
+    record_audit(
+        &state,
+        &user.id,
+        "stack.deploy",
+        &id,
+        Some(serde_json::json!({ "deployment_id": deployment.deployment_id })),
+    )
+    .await?;
     
-    Ok(Json(serde_json::json!({
-        "message": "Stack deployment initiated",
-        "deployment_id": Uuid::new_v4().to_string(),
-        "status": "deploying"
-    })))
+    Ok((StatusCode::ACCEPTED, Json(deployment)))
 }
 
-/// Undeploy stack
+/// Undeploy stack: tears down the containers, networks and volumes of its current deployment
 async fn undeploy_stack(
     Path(id): Path<String>,
     State(state): State<AppState>,
     user: AuthenticatedUser,
 ) -> Result<impl IntoResponse> {
-    // TODO: Implement stack undeployment
+    let deployment = deployments::latest_for_stack(&state.database.pool, &id)
+        .await?
+        .filter(|d| d.status != DeploymentStatus::Stopped)
+        .ok_or_else(|| Error::not_found(format!("Stack {} is not deployed", id)))?;
+
+    if deployment.started_by != user.id && (user.is_robot() || !user.scopes.iter().any(|s| s == "admin")) {
+        return Err(Error::authorization("Only the user who deployed a stack or an admin can undeploy it"));
+    }
+
+    deployments::stop(&state, &deployment).await?;
+    record_audit(
+        &state,
+        &user.id,
+        "stack.undeploy",
+        &id,
+        Some(serde_json::json!({ "deployment_id": deployment.deployment_id })),
+    )
+    .await?;
     
-    Ok(Json(serde_json::json!({
-        "message": "Stack undeployment initiated",
-        "status": "undeploying"
-    })))
+    Ok((StatusCode::ACCEPTED, Json(serde_json::json!({
+        "deployment_id": deployment.deployment_id,
+        "status": DeploymentStatus::Undeploying
+    }))))
 }
 
-/// Get deployment status
+#[derive(Debug, Deserialize)]
+struct RollbackQuery {
+    /// Earlier deployment whose compose snapshot is redeployed
+    to: Uuid,
+}
+
+/// Roll a stack back to the compose file of an earlier deployment.
+/// The current deployment is torn down and a new one records where it rolled back from.
+async fn rollback_stack(
+    Path(id): Path<String>,
+    Query(query): Query<RollbackQuery>,
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+) -> Result<impl IntoResponse> {
+    deployments::ensure_may_deploy(&state, &user).await?;
+
+    let row = sqlx::query("SELECT author, is_public FROM stacks WHERE id = $1")
+        .bind(&id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Stack {} not found", id)))?;
+    if row.get::<String, _>("author") != user.id && !row.get::<bool, _>("is_public") {
+        return Err(Error::not_found(format!("Stack {} not found", id)));
+    }
+
+    let (source, compose_content) = deployments::snapshot(&state.database.pool, query.to)
+        .await?
+        .filter(|(source, _)| source.stack_id == id)
+        .ok_or_else(|| Error::not_found(format!("Deployment {} of stack {} not found", query.to, id)))?;
+
+    if compose_content.is_empty() {
+        return Err(Error::bad_request(format!("Deployment {} has no compose snapshot to roll back to", query.to)));
+    }
+    if source.status == DeploymentStatus::Failed {
+        return Err(Error::bad_request(format!("Deployment {} failed; roll back to one that ran", query.to)));
+    }
+
+    let current = deployments::latest_for_stack(&state.database.pool, &id).await?;
+    if let Some(current) = current.as_ref().filter(|d| d.status.is_active()) {
+        if current.started_by != user.id && !user.scopes.iter().any(|s| s == "admin") {
+            return Err(Error::authorization("Only the user who deployed a stack or an admin can roll it back"));
+        }
+    }
+
+    let deployment = deployments::replace(
+        &state,
+        DeployRequest {
+            stack_id: &id,
+            compose_content,
+            started_by: &user.id,
+            rolled_back_from: Some(source.deployment_id),
+        },
+        current,
+    )
+    .await?;
+    record_audit(
+        &state,
+        &user.id,
+        "stack.rollback",
+        &id,
+        Some(serde_json::json!({
+            "deployment_id": deployment.deployment_id,
+            "rolled_back_from": source.deployment_id,
+        })),
+    )
+    .await?;
+
+    Ok((StatusCode::ACCEPTED, Json(deployment)))
+}
+
+/// Get deployment status: the recorded lifecycle state plus live container state from Docker
 async fn get_deployment_status(
     Path(id): Path<String>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse> {
-    // TODO: Get actual deployment status
+    let deployment = deployments::latest_for_stack(&state.database.pool, &id)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Stack {} has never been deployed", id)))?;
+
+    let services = if deployment.status.is_active() {
+        deployments::services(&deployment).await.unwrap_or_else(|e| {
+            tracing::warn!("Failed to inspect deployment {}: {}", deployment.deployment_id, e);
+            Vec::new()
+        })
+    } else {
+        Vec::new()
+    };
     
     Ok(Json(serde_json::json!({
         "stack_id": id,
-        "status": "running",
-        "services": [
-            {
-                "name": "web",
-                "status": "running",
-                "replicas": "1/1"
-            }
-        ],
-        "last_updated": chrono::Utc::now()
+        "deployment_id": deployment.deployment_id,
+        "status": deployment.status,
+        "error": deployment.error,
+        "services": services,
+        "started_by": deployment.started_by,
+        "started_at": deployment.started_at,
+        "last_updated": deployment.updated_at
     })))
 }
 
 /// List public stacks
 async fn list_public_stacks(
     Query(query): Query<StackQuery>,
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse> {
-    // TODO: Implement public stack listing
+    let (limit, offset) = page.bounds()?;
+    let (stacks, total) = query_stacks(&state.database.pool, &query, None, limit, offset).await?;
     
-    Ok(Json(serde_json::json!({
-        "stacks": [],
-        "total": 0
-    })))
+    Ok(Json(Page::new(stacks, total, limit, offset)))
 }
 
-/// List featured stacks
+/// List public stacks an admin has featured, most popular first
 async fn list_featured_stacks(
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse> {
-    // TODO: Implement featured stack listing
+    let (limit, offset) = page.bounds()?;
+    let stacks: Vec<RankedStack> = sqlx::query(
+        "SELECT * FROM stacks WHERE is_public = TRUE AND featured = TRUE ORDER BY popularity_score DESC"
+    )
+    .fetch_all(&state.database.pool)
+    .await?
+    .iter()
+    .map(ranked_stack_from_row)
+    .collect();
     
-    Ok(Json(serde_json::json!({
-        "stacks": []
-    })))
+    Ok(Json(Page::from_vec(stacks, limit, offset)))
 }
 
-/// List popular stacks
+/// List public stacks by time-decayed popularity, so recent downloads and stars outrank old totals
 async fn list_popular_stacks(
-    Query(query): Query<StackQuery>,
+    Query(page): Query<PageQuery>,
     State(state): State<AppState>,
 ) -> Result<impl IntoResponse> {
-    // TODO: Implement popular stack listing (sorted by stars/downloads)
+    let (limit, offset) = page.bounds()?;
+    let total: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM stacks WHERE is_public = TRUE")
+        .fetch_one(&state.database.pool)
+        .await?;
+
+    let stacks: Vec<RankedStack> = sqlx::query(
+        r#"
+        SELECT * FROM stacks WHERE is_public = TRUE
+        ORDER BY popularity_score DESC, star_count DESC, download_count DESC
+        LIMIT $1 OFFSET $2
+        "#
+    )
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(&state.database.pool)
+    .await?
+    .iter()
+    .map(ranked_stack_from_row)
+    .collect();
     
-    Ok(Json(serde_json::json!({
-        "stacks": []
-    })))
+    Ok(Json(Page::new(stacks, total as u64, limit, offset)))
+}
+
+/// Feature or unfeature a stack (admin only)
+async fn set_stack_featured(
+    Path(id): Path<String>,
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<FeatureStackRequest>,
+) -> Result<impl IntoResponse> {
+    if user.is_robot() || !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization("Featuring stacks requires admin access"));
+    }
+
+    let updated = sqlx::query("UPDATE stacks SET featured = $1, updated_at = $2 WHERE id = $3")
+        .bind(request.featured)
+        .bind(Utc::now())
+        .bind(&id)
+        .execute(&state.database.pool)
+        .await?
+        .rows_affected();
+    if updated == 0 {
+        return Err(Error::not_found(format!("Stack {} not found", id)));
+    }
+
+    record_audit(
+        &state,
+        &user.id,
+        "stack.feature",
+        &id,
+        Some(serde_json::json!({ "featured": request.featured })),
+    )
+    .await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Record a download or new star: bumps the raw counter and keeps the event for decay scoring
+pub async fn record_activity(pool: &SqlitePool, stack_id: &str, activity: StackActivity) -> Result<()> {
+    let counter = match activity {
+        StackActivity::Download => "UPDATE stacks SET download_count = download_count + 1 WHERE id = $1",
+        StackActivity::Star => "UPDATE stacks SET star_count = star_count + 1 WHERE id = $1",
+    };
+
+    let mut tx = pool.begin().await?;
+    sqlx::query(counter)
+        .bind(stack_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query("INSERT INTO stack_activity (stack_id, kind, created_at) VALUES ($1, $2, $3)")
+        .bind(stack_id)
+        .bind(activity.as_str())
+        .bind(Utc::now())
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Recompute every stack's popularity score from recent activity.
+/// Activity past the decay horizon is pruned; raw counters keep the all-time totals.
+pub async fn refresh_popularity(pool: &SqlitePool) -> Result<usize> {
+    let now = Utc::now();
+    let horizon = now - chrono::Duration::days(POPULARITY_HALF_LIFE_DAYS * POPULARITY_HORIZON_HALF_LIVES);
+
+    sqlx::query("DELETE FROM stack_activity WHERE created_at < $1")
+        .bind(horizon)
+        .execute(pool)
+        .await?;
+
+    let rows = sqlx::query("SELECT stack_id, kind, created_at FROM stack_activity")
+        .fetch_all(pool)
+        .await?;
+
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    for row in rows {
+        let Some(activity) = StackActivity::parse(row.get::<String, _>("kind").as_str()) else {
+            continue;
+        };
+        let created_at: DateTime<Utc> = row.get("created_at");
+        *scores.entry(row.get("stack_id")).or_default() += decayed_weight(activity, now - created_at);
+    }
+
+    let mut tx = pool.begin().await?;
+    sqlx::query("UPDATE stacks SET popularity_score = 0")
+        .execute(&mut *tx)
+        .await?;
+    for (stack_id, score) in &scores {
+        sqlx::query("UPDATE stacks SET popularity_score = $1 WHERE id = $2")
+            .bind(score)
+            .bind(stack_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(scores.len())
+}
+
+fn stack_from_row(row: &SqliteRow) -> Stack {
+    let tags: String = row.get("tags");
+    Stack {
+        id: row.get("id"),
+        name: row.get("name"),
+        description: row.get("description"),
+        compose_content: row.get("compose_content"),
+        version: row.get("version"),
+        author: row.get("author"),
+        author_email: row.get("author_email"),
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        is_public: row.get("is_public"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        download_count: row.get::<i64, _>("download_count") as u64,
+        star_count: row.get::<i64, _>("star_count") as u64,
+    }
+}
+
+fn ranked_stack_from_row(row: &SqliteRow) -> RankedStack {
+    RankedStack {
+        stack: stack_from_row(row),
+        featured: row.get("featured"),
+        popularity_score: row.get("popularity_score"),
+    }
+}
+
+/// Filter stacks by `query`, newest first; `viewer` also sees their own private stacks.
+/// Returns one page and the number of matches across all pages.
+async fn query_stacks(
+    pool: &SqlitePool,
+    query: &StackQuery,
+    viewer: Option<&str>,
+    limit: u32,
+    offset: u32,
+) -> Result<(Vec<Stack>, u64)> {
+    let mut conditions = vec![];
+    let mut params = vec![];
+    
+    match viewer {
+        Some(user_id) => {
+            conditions.push("(author = ? OR is_public = TRUE)");
+            params.push(user_id.to_string());
+        }
+        None => conditions.push("is_public = TRUE"),
+    }
+    
+    if let Some(search) = query.search.as_deref().map(str::trim).filter(|s| !s.is_empty()) {
+        conditions.push(r"(name LIKE ? ESCAPE '\' OR description LIKE ? ESCAPE '\')");
+        let pattern = format!("%{}%", escape_like(search));
+        params.push(pattern.clone());
+        params.push(pattern);
+    }
+    
+    // Tags are a JSON array; every requested tag has to be present exactly
+    if let Some(tags) = &query.tags {
+        for tag in tags.split(',').map(str::trim).filter(|t| !t.is_empty()) {
+            conditions.push("EXISTS (SELECT 1 FROM json_each(stacks.tags) WHERE json_each.value = ?)");
+            params.push(tag.to_string());
+        }
+    }
+    
+    if let Some(author) = &query.author {
+        conditions.push("author = ?");
+        params.push(author.clone());
+    }
+    
+    let where_clause = format!("WHERE {}", conditions.join(" AND "));
+    
+    let count_sql = format!("SELECT COUNT(*) FROM stacks {}", where_clause);
+    let mut count = sqlx::query_scalar::<_, i64>(&count_sql);
+    for param in &params {
+        count = count.bind(param);
+    }
+    let total = count.fetch_one(pool).await?;
+
+    let select_sql = format!("SELECT * FROM stacks {} ORDER BY updated_at DESC LIMIT ? OFFSET ?", where_clause);
+    let mut select = sqlx::query(&select_sql);
+    for param in &params {
+        select = select.bind(param);
+    }
+    let stacks = select
+        .bind(limit as i64)
+        .bind(offset as i64)
+        .fetch_all(pool)
+        .await?
+        .iter()
+        .map(stack_from_row)
+        .collect();
+
+    Ok((stacks, total as u64))
+}
+
+/// Escape LIKE wildcards so user input matches literally (with `ESCAPE '\'`)
+fn escape_like(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        if matches!(c, '%' | '_' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+async fn insert_stack(pool: &SqlitePool, stack: &Stack) -> Result<()> {
+    if stack.name.trim().is_empty() {
+        return Err(Error::validation("Stack name must not be empty"));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO stacks (id, name, description, compose_content, version, author, author_email, tags, is_public, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        "#
+    )
+    .bind(&stack.id)
+    .bind(&stack.name)
+    .bind(&stack.description)
+    .bind(&stack.compose_content)
+    .bind(&stack.version)
+    .bind(&stack.author)
+    .bind(&stack.author_email)
+    .bind(serde_json::to_string(&stack.tags)?)
+    .bind(stack.is_public)
+    .bind(stack.created_at)
+    .bind(stack.updated_at)
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// A stack the caller may read: public, or authored by `user_id`.
+/// Private stacks of other users answer 404 so their ids aren't confirmed.
+async fn visible_stack(pool: &SqlitePool, id: &str, user_id: Option<&str>) -> Result<Stack> {
+    sqlx::query("SELECT * FROM stacks WHERE id = $1")
+        .bind(id)
+        .fetch_optional(pool)
+        .await?
+        .map(|row| stack_from_row(&row))
+        .filter(|stack| stack.is_public || Some(stack.author.as_str()) == user_id)
+        .ok_or_else(|| Error::not_found(format!("Stack {} not found", id)))
+}
+
+/// A stack the caller may change; only its author can
+async fn owned_stack(pool: &SqlitePool, id: &str, user: &AuthenticatedUser, action: &str) -> Result<Stack> {
+    let stack = visible_stack(pool, id, Some(&user.id)).await?;
+    if stack.author != user.id {
+        return Err(Error::authorization(format!("Only the author of a stack can {} it", action)));
+    }
+    Ok(stack)
 }
 
 /// Helper functions
@@ -541,6 +1095,206 @@ fn validate_compose_content(content: &str) -> std::result::Result<(), String> {
     }
 }
 
+/// Split an image reference into (registry, repository, reference).
+/// Images without a registry host resolve to Docker Hub.
+fn parse_image_reference(image: &str) -> (String, String, String) {
+    let (name, reference) = if let Some((name, digest)) = image.split_once('@') {
+        (name, digest.to_string())
+    } else {
+        match image.rsplit_once(':') {
+            Some((name, tag)) if !tag.contains('/') => (name, tag.to_string()),
+            _ => (image, "latest".to_string()),
+        }
+    };
+
+    let (registry, repository) = match name.split_once('/') {
+        Some((host, rest)) if host.contains('.') || host.contains(':') || host == "localhost" => {
+            (host.to_string(), rest.to_string())
+        }
+        _ => ("registry-1.docker.io".to_string(), name.to_string()),
+    };
+
+    let repository = if registry == "registry-1.docker.io" && !repository.contains('/') {
+        format!("library/{}", repository)
+    } else {
+        repository
+    };
+
+    (registry, repository, reference)
+}
+
+/// Collect `image:` references per service, plus services that only declare `build:`
+fn extract_service_images(compose: &serde_yaml::Value) -> (Vec<(String, String)>, Vec<String>) {
+    let mut images = vec![];
+    let mut build_only = vec![];
+
+    if let Some(services) = compose.get("services").and_then(|s| s.as_mapping()) {
+        for (name, service) in services {
+            let name = name.as_str().unwrap_or_default().to_string();
+            match service.get("image").and_then(|i| i.as_str()) {
+                Some(image) => images.push((name, image.to_string())),
+                None if service.get("build").is_some() => build_only.push(name),
+                None => {}
+            }
+        }
+    }
+
+    (images, build_only)
+}
+
+/// Flag risky service settings in a compose file
+fn lint_compose_services(compose: &serde_yaml::Value) -> Vec<LintFinding> {
+    let mut findings = vec![];
+
+    let Some(services) = compose.get("services").and_then(|s| s.as_mapping()) else {
+        return findings;
+    };
+
+    for (name, service) in services {
+        let service_name = name.as_str().unwrap_or_default().to_string();
+        let mut finding = |severity: &str, message: &str| {
+            findings.push(LintFinding {
+                service: service_name.clone(),
+                severity: severity.to_string(),
+                message: message.to_string(),
+            });
+        };
+
+        if service.get("privileged").and_then(|p| p.as_bool()).unwrap_or(false) {
+            finding("high", "Service runs in privileged mode");
+        }
+        if service.get("network_mode").and_then(|n| n.as_str()) == Some("host") {
+            finding("medium", "Service uses host networking");
+        }
+        if service.get("pid").and_then(|p| p.as_str()) == Some("host") {
+            finding("medium", "Service shares the host PID namespace");
+        }
+        if let Some(volumes) = service.get("volumes").and_then(|v| v.as_sequence()) {
+            if volumes.iter().filter_map(|v| v.as_str()).any(|v| v.contains("/var/run/docker.sock")) {
+                finding("high", "Service mounts the Docker socket");
+            }
+        }
+        if let Some(image) = service.get("image").and_then(|i| i.as_str()) {
+            let (_, _, reference) = parse_image_reference(image);
+            if reference == "latest" {
+                finding("low", "Image uses the mutable 'latest' tag");
+            }
+        }
+    }
+
+    findings
+}
+
+/// Host names that refer to this registry in image references: the host the caller used,
+/// the configured token realm's host, and loopback on the registry port
+fn local_registry_hosts(state: &AppState, headers: &HeaderMap) -> Vec<String> {
+    let mut hosts = vec![
+        format!("localhost:{}", state.config.server.port),
+        format!("127.0.0.1:{}", state.config.server.port),
+    ];
+    if let Some(host) = headers.get(header::HOST).and_then(|h| h.to_str().ok()) {
+        hosts.push(host.to_ascii_lowercase());
+    }
+    if let Some(realm) = state.config.auth.challenge.realm.as_deref().and_then(|r| url::Url::parse(r).ok()) {
+        if let Some(host) = realm.host_str() {
+            hosts.push(match realm.port() {
+                Some(port) => format!("{}:{}", host, port),
+                None => host.to_string(),
+            });
+        }
+    }
+    hosts
+}
+
+/// `host[:port]` of a proxy rule's upstream
+fn upstream_host(upstream: &str) -> Option<String> {
+    let url = url::Url::parse(upstream).ok()?;
+    let host = url.host_str()?.to_ascii_lowercase();
+    Some(match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host,
+    })
+}
+
+/// Check whether an image can be pulled. Images on this registry are looked up locally
+/// (subject to the caller's pull access); other hosts are only contacted when a proxy rule
+/// names them as its upstream, so a compose file cannot point the server at arbitrary URLs.
+async fn check_image_availability(
+    state: &AppState,
+    user: &AuthenticatedUser,
+    local_hosts: &[String],
+    service: String,
+    image: String,
+) -> ImageCheck {
+    let (registry, repository, reference) = parse_image_reference(&image);
+    let mut check = ImageCheck {
+        service,
+        image,
+        registry: registry.clone(),
+        repository: repository.clone(),
+        reference: reference.clone(),
+        resolvable: false,
+        source: None,
+        reason: None,
+    };
+
+    if local_hosts.iter().any(|host| host.eq_ignore_ascii_case(&registry)) {
+        // Same queries as the manifest HEAD handler
+        let caller = Some(user.clone());
+        let found = authorize_repository(state, &caller, &repository, "pull").await.is_ok()
+            && match crate::database::queries::get_repository_by_name(state, &repository).await {
+                Ok(repo) if reference.starts_with("sha256:") => {
+                    crate::database::queries::get_manifest_by_digest(state, &repo.id, &reference).await.is_ok()
+                }
+                Ok(repo) => crate::database::queries::get_manifest_by_tag(state, &repo.id, &reference).await.is_ok(),
+                Err(_) => false,
+            };
+        if found {
+            check.resolvable = true;
+            check.source = Some("local".to_string());
+        } else {
+            check.reason = Some("Not found in this registry".to_string());
+        }
+        return check;
+    }
+
+    let rule = state
+        .proxy
+        .rules()
+        .iter()
+        .find(|rule| rule.upstream.as_deref().and_then(upstream_host).as_deref() == Some(registry.as_str()))
+        .cloned();
+    let Some((upstream, credentials)) =
+        rule.and_then(|rule| Some((rule.upstream?, rule.username.zip(rule.password))))
+    else {
+        check.reason = Some(format!("{} is not a configured upstream registry", registry));
+        return check;
+    };
+
+    // RemoteRegistry answers the upstream's Bearer challenge, anonymously or with the rule's credentials
+    let result = match RemoteRegistry::new(&upstream, credentials) {
+        Ok(remote) => remote.get_manifest(&repository, &reference).await.map(|_| ()),
+        Err(e) => Err(e),
+    };
+    match result {
+        Ok(()) => {
+            check.resolvable = true;
+            check.source = Some(registry);
+        }
+        Err(Error::NotFound { .. }) => {
+            check.reason = Some("Not found on the upstream registry".to_string());
+        }
+        Err(Error::Authentication { .. }) => {
+            check.reason = Some("Upstream registry requires authentication".to_string());
+        }
+        Err(e) => {
+            check.reason = Some(format!("Failed to reach upstream registry: {}", e));
+        }
+    }
+
+    check
+}
+
 fn is_valid_compose_url(url: &str) -> bool {
     // Check if URL is valid and from allowed sources
     if let Ok(parsed_url) = url::Url::parse(url) {
@@ -611,6 +1365,46 @@ services:
         assert!(!is_valid_compose_url("ftp://example.com/compose.yml"));
     }
 
+    #[test]
+    fn test_parse_image_reference() {
+        assert_eq!(
+            parse_image_reference("nginx"),
+            ("registry-1.docker.io".to_string(), "library/nginx".to_string(), "latest".to_string())
+        );
+        assert_eq!(
+            parse_image_reference("ghcr.io/org/app:1.2"),
+            ("ghcr.io".to_string(), "org/app".to_string(), "1.2".to_string())
+        );
+        assert_eq!(
+            parse_image_reference("localhost:5000/team/api@sha256:abc"),
+            ("localhost:5000".to_string(), "team/api".to_string(), "sha256:abc".to_string())
+        );
+    }
+
+    #[test]
+    fn test_upstream_host() {
+        // Matched against the registry part of parse_image_reference
+        assert_eq!(upstream_host("https://Registry-1.docker.io/").as_deref(), Some("registry-1.docker.io"));
+        assert_eq!(upstream_host("http://mirror.internal:5000").as_deref(), Some("mirror.internal:5000"));
+        assert_eq!(upstream_host("not a url"), None);
+    }
+
+    #[test]
+    fn test_extract_service_images() {
+        let compose: serde_yaml::Value = serde_yaml::from_str(r#"
+version: '3.8'
+services:
+  web:
+    image: nginx:latest
+  app:
+    build: .
+"#).unwrap();
+
+        let (images, build_only) = extract_service_images(&compose);
+        assert_eq!(images, vec![("web".to_string(), "nginx:latest".to_string())]);
+        assert_eq!(build_only, vec!["app".to_string()]);
+    }
+
     #[test]
     fn test_extract_name_from_url() {
         assert_eq!(
@@ -622,4 +1416,129 @@ services:
             Some("my-stack".to_string())
         );
     }
+
+    #[test]
+    fn test_popularity_decay() {
+        let fresh = decayed_weight(StackActivity::Download, chrono::Duration::zero());
+        let week_old = decayed_weight(StackActivity::Download, chrono::Duration::days(POPULARITY_HALF_LIFE_DAYS));
+        assert_eq!(fresh, 1.0);
+        assert!((week_old - 0.5).abs() < 1e-9);
+
+        // A handful of recent downloads outranks a larger burst from months ago
+        let recent: f64 = (0..5).map(|_| decayed_weight(StackActivity::Download, chrono::Duration::days(1))).sum();
+        let old: f64 = (0..100).map(|_| decayed_weight(StackActivity::Download, chrono::Duration::days(60))).sum();
+        assert!(recent > old);
+
+        assert!(decayed_weight(StackActivity::Star, chrono::Duration::zero()) > fresh);
+    }
+
+    #[tokio::test]
+    async fn test_stack_persistence_and_visibility() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::database::migrations::create_tables(&pool).await.unwrap();
+
+        let stack = Stack {
+            id: Uuid::new_v4().to_string(),
+            name: "web".to_string(),
+            description: None,
+            compose_content: "version: '3.8'\nservices:\n  web:\n    image: nginx:latest".to_string(),
+            version: "1.0.0".to_string(),
+            author: "alice".to_string(),
+            author_email: "alice@example.com".to_string(),
+            tags: vec!["web".to_string(), "nginx".to_string()],
+            is_public: false,
+            created_at: Utc::now(),
+            updated_at: Utc::now(),
+            download_count: 0,
+            star_count: 0,
+        };
+        insert_stack(&pool, &stack).await.unwrap();
+
+        let loaded = visible_stack(&pool, &stack.id, Some("alice")).await.unwrap();
+        assert_eq!(loaded.tags, stack.tags);
+        assert_eq!(loaded.compose_content, stack.compose_content);
+
+        // Private stacks are hidden from everyone but their author
+        assert!(matches!(visible_stack(&pool, &stack.id, Some("bob")).await, Err(Error::NotFound { .. })));
+        assert!(matches!(visible_stack(&pool, &stack.id, None).await, Err(Error::NotFound { .. })));
+        assert!(matches!(visible_stack(&pool, "missing", Some("alice")).await, Err(Error::NotFound { .. })));
+
+        let unnamed = Stack { id: Uuid::new_v4().to_string(), name: " ".to_string(), ..stack };
+        assert!(insert_stack(&pool, &unnamed).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stack_filters() {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        crate::database::migrations::create_tables(&pool).await.unwrap();
+
+        for (name, author, tags, is_public) in [
+            ("nginx proxy", "alice", vec!["web", "proxy"], true),
+            ("postgres", "alice", vec!["database"], false),
+            ("web_app", "bob", vec!["webapp"], true),
+        ] {
+            let stack = Stack {
+                id: Uuid::new_v4().to_string(),
+                name: name.to_string(),
+                description: None,
+                compose_content: "version: '3.8'".to_string(),
+                version: "1.0.0".to_string(),
+                author: author.to_string(),
+                author_email: format!("{}@example.com", author),
+                tags: tags.into_iter().map(String::from).collect(),
+                is_public,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                download_count: 0,
+                star_count: 0,
+            };
+            insert_stack(&pool, &stack).await.unwrap();
+        }
+
+        let names = |found: (Vec<Stack>, u64)| {
+            let mut names: Vec<String> = found.0.into_iter().map(|s| s.name).collect();
+            names.sort();
+            (names, found.1)
+        };
+        let filter = |search: Option<&str>, tags: Option<&str>| StackQuery {
+            search: search.map(String::from),
+            tags: tags.map(String::from),
+            author: None,
+            public_only: None,
+        };
+
+        let (all, total) = names(query_stacks(&pool, &filter(None, None), Some("alice"), 50, 0).await.unwrap());
+        assert_eq!((all.len(), total), (3, 3));
+        // Someone else's private stack stays hidden, and anonymous callers only see public ones
+        assert_eq!(query_stacks(&pool, &filter(None, None), Some("bob"), 50, 0).await.unwrap().1, 2);
+        assert_eq!(query_stacks(&pool, &filter(None, None), None, 50, 0).await.unwrap().1, 2);
+
+        // Wildcards in the search term are literal
+        assert_eq!(query_stacks(&pool, &filter(Some("%"), None), Some("alice"), 50, 0).await.unwrap().1, 0);
+        let (found, _) = names(query_stacks(&pool, &filter(Some("_"), None), Some("alice"), 50, 0).await.unwrap());
+        assert_eq!(found, vec!["web_app"]);
+
+        // Tags match whole entries, and every requested tag is required
+        let (found, _) = names(query_stacks(&pool, &filter(None, Some("web")), Some("alice"), 50, 0).await.unwrap());
+        assert_eq!(found, vec!["nginx proxy"]);
+        assert_eq!(query_stacks(&pool, &filter(None, Some("web,database")), Some("alice"), 50, 0).await.unwrap().1, 0);
+
+        // The total counts every match, not just the page
+        let (page, total) = query_stacks(&pool, &filter(None, None), Some("alice"), 1, 1).await.unwrap();
+        assert_eq!((page.len(), total), (1, 3));
+    }
+
+    #[test]
+    fn test_escape_like() {
+        assert_eq!(escape_like("100%_off\\"), "100\\%\\_off\\\\");
+        assert_eq!(escape_like("nginx"), "nginx");
+    }
 }
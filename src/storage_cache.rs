@@ -0,0 +1,374 @@
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use bytes::Bytes;
+
+use crate::{
+    config::{StorageBackend, StorageConfig},
+    error::Result,
+};
+
+/// Local disk cache for blobs read from object storage
+/// Misses are served from the backend and written here in the background, so repeat
+/// pulls of hot layers come off local disk. Bounded by total size with least-recently-read
+/// eviction.
+
+#[derive(Default)]
+struct CacheIndex {
+    /// Digest to (bytes on disk, last-use tick)
+    entries: HashMap<String, (u64, u64)>,
+    /// Last-use tick to digest, oldest first
+    order: BTreeMap<u64, String>,
+    tick: u64,
+    bytes: u64,
+}
+
+impl CacheIndex {
+    fn touch(&mut self, digest: &str) -> bool {
+        self.tick += 1;
+        let tick = self.tick;
+        let Some((_, used)) = self.entries.get_mut(digest) else {
+            return false;
+        };
+        self.order.remove(used);
+        *used = tick;
+        self.order.insert(tick, digest.to_string());
+        true
+    }
+
+    fn insert(&mut self, digest: &str, size: u64) {
+        self.remove(digest);
+        self.tick += 1;
+        self.order.insert(self.tick, digest.to_string());
+        self.entries.insert(digest.to_string(), (size, self.tick));
+        self.bytes += size;
+    }
+
+    fn remove(&mut self, digest: &str) -> bool {
+        match self.entries.remove(digest) {
+            Some((size, used)) => {
+                self.order.remove(&used);
+                self.bytes -= size;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Drop least recently read entries until at most `max_bytes` remain
+    fn evict_to(&mut self, max_bytes: u64) -> Vec<String> {
+        let mut evicted = Vec::new();
+        while self.bytes > max_bytes {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+            if let Some((size, _)) = self.entries.remove(&oldest) {
+                self.bytes -= size;
+            }
+            evicted.push(oldest);
+        }
+        evicted
+    }
+}
+
+pub struct BlobReadCache {
+    dir: PathBuf,
+    max_bytes: u64,
+    max_blob_bytes: u64,
+    index: Mutex<CacheIndex>,
+    /// Bumped by every invalidation so a fill that raced with a delete is discarded
+    generation: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    evictions: AtomicU64,
+}
+
+impl BlobReadCache {
+    /// Open the configured cache; filesystem storage is already local and never cached
+    pub async fn from_config(config: &StorageConfig) -> Result<Option<Self>> {
+        let cache = &config.read_cache;
+        if !cache.enabled || matches!(config.backend, StorageBackend::Filesystem) {
+            return Ok(None);
+        }
+
+        let opened = Self::open(&cache.path, cache.max_bytes, cache.max_blob_bytes).await?;
+        tracing::info!(
+            "Caching {:?} blob reads in {} ({} of {} bytes in use)",
+            config.backend,
+            cache.path.display(),
+            opened.size_bytes(),
+            cache.max_bytes
+        );
+
+        Ok(Some(opened))
+    }
+
+    /// Open a cache directory, indexing what a previous run left behind by modification time
+    pub async fn open(dir: &Path, max_bytes: u64, max_blob_bytes: u64) -> Result<Self> {
+        tokio::fs::create_dir_all(dir).await?;
+
+        let mut found = Vec::new();
+        let mut entries = tokio::fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Leftovers from fills interrupted by a restart
+            if name.ends_with(".tmp") {
+                let _ = tokio::fs::remove_file(&path).await;
+                continue;
+            }
+            let Some(digest) = digest_from_file_name(&name) else { continue };
+            let metadata = entry.metadata().await?;
+            if metadata.is_file() {
+                found.push((metadata.modified().ok(), digest, metadata.len()));
+            }
+        }
+        found.sort();
+
+        let cache = Self {
+            dir: dir.to_path_buf(),
+            max_bytes,
+            max_blob_bytes,
+            index: Mutex::new(CacheIndex::default()),
+            generation: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            evictions: AtomicU64::new(0),
+        };
+
+        let evicted = {
+            let mut index = cache.index.lock().unwrap();
+            for (_, digest, size) in &found {
+                index.insert(digest, *size);
+            }
+            index.evict_to(max_bytes)
+        };
+        cache.remove_files(evicted).await;
+
+        Ok(cache)
+    }
+
+    /// Serve `digest` from disk, or fetch it and fill the cache once the caller has the bytes
+    pub async fn get_or_fetch<F, Fut>(self: &Arc<Self>, digest: &str, fetch: F) -> Result<Option<Bytes>>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Option<Bytes>>>,
+    {
+        if let Some(data) = self.get(digest).await {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(Some(data));
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+
+        let generation = self.generation.load(Ordering::Acquire);
+        let data = fetch().await?;
+        if let Some(data) = &data {
+            let cache = Arc::clone(self);
+            let digest = digest.to_string();
+            let data = data.clone();
+            tokio::spawn(async move {
+                if let Err(e) = cache.fill(&digest, &data, generation).await {
+                    tracing::warn!("Failed to cache blob {}: {}", digest, e);
+                }
+            });
+        }
+
+        Ok(data)
+    }
+
+    async fn get(&self, digest: &str) -> Option<Bytes> {
+        let path = self.path(digest)?;
+        if !self.index.lock().unwrap().touch(digest) {
+            return None;
+        }
+
+        match tokio::fs::read(&path).await {
+            Ok(data) => Some(Bytes::from(data)),
+            Err(e) => {
+                // Evicted underneath us: drop it and read from the backend
+                tracing::debug!("Discarding cached blob {}: {}", digest, e);
+                self.index.lock().unwrap().remove(digest);
+                let _ = tokio::fs::remove_file(&path).await;
+                None
+            }
+        }
+    }
+
+    async fn fill(&self, digest: &str, data: &[u8], generation: u64) -> Result<()> {
+        let Some(path) = self.path(digest) else { return Ok(()) };
+        if data.len() as u64 > self.max_blob_bytes || self.index.lock().unwrap().entries.contains_key(digest) {
+            return Ok(());
+        }
+
+        let size = data.len() as u64;
+        if size > self.max_bytes {
+            return Ok(());
+        }
+
+        // Write beside the final name and rename so readers never see a partial file
+        let staging = self.dir.join(format!("{}.{}.tmp", file_name(digest), uuid::Uuid::new_v4()));
+        tokio::fs::write(&staging, data).await?;
+        if let Err(e) = tokio::fs::rename(&staging, &path).await {
+            let _ = tokio::fs::remove_file(&staging).await;
+            return Err(e.into());
+        }
+
+        let evicted = {
+            let mut index = self.index.lock().unwrap();
+            if self.generation.load(Ordering::Acquire) != generation {
+                None
+            } else {
+                index.insert(digest, size);
+                Some(index.evict_to(self.max_bytes))
+            }
+        };
+
+        match evicted {
+            Some(evicted) => self.remove_files(evicted).await,
+            // The blob was deleted while we were reading it
+            None => {
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget a deleted blob
+    pub async fn invalidate(&self, digest: &str) {
+        self.generation.fetch_add(1, Ordering::AcqRel);
+        self.index.lock().unwrap().remove(digest);
+        if let Some(path) = self.path(digest) {
+            if let Err(e) = tokio::fs::remove_file(&path).await {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!("Failed to remove cached blob {}: {}", digest, e);
+                }
+            }
+        }
+    }
+
+    async fn remove_files(&self, evicted: Vec<String>) {
+        for digest in evicted {
+            self.evictions.fetch_add(1, Ordering::Relaxed);
+            if let Some(path) = self.path(&digest) {
+                let _ = tokio::fs::remove_file(&path).await;
+            }
+        }
+    }
+
+    fn path(&self, digest: &str) -> Option<PathBuf> {
+        digest_from_file_name(&file_name(digest))?;
+        Some(self.dir.join(file_name(digest)))
+    }
+
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    pub fn evictions(&self) -> u64 {
+        self.evictions.load(Ordering::Relaxed)
+    }
+
+    /// Bytes currently cached on disk
+    pub fn size_bytes(&self) -> u64 {
+        self.index.lock().unwrap().bytes
+    }
+}
+
+/// `sha256:<hex>` is stored as `sha256-<hex>`
+fn file_name(digest: &str) -> String {
+    digest.replacen(':', "-", 1)
+}
+
+/// The digest a cache file holds, or None for anything that isn't a cache entry
+fn digest_from_file_name(name: &str) -> Option<String> {
+    let (algorithm, hex) = name.split_once('-')?;
+    let valid = !algorithm.is_empty()
+        && !hex.is_empty()
+        && algorithm.chars().all(|c| c.is_ascii_alphanumeric())
+        && hex.chars().all(|c| c.is_ascii_hexdigit());
+    valid.then(|| format!("{}:{}", algorithm, hex))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(n: u8) -> String {
+        format!("sha256:{}", format!("{:02x}", n).repeat(32))
+    }
+
+    async fn read(cache: &Arc<BlobReadCache>, digest: &str, data: &'static [u8]) -> Option<Bytes> {
+        cache
+            .get_or_fetch(digest, || async move { Ok(Some(Bytes::from_static(data))) })
+            .await
+            .unwrap()
+    }
+
+    /// Wait for background fills to land
+    async fn settle(cache: &BlobReadCache, bytes: u64) {
+        for _ in 0..100 {
+            if cache.size_bytes() == bytes {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("cache holds {} bytes, expected {}", cache.size_bytes(), bytes);
+    }
+
+    #[tokio::test]
+    async fn test_read_cache_fills_and_evicts() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = Arc::new(BlobReadCache::open(dir.path(), 8, 8).await.unwrap());
+
+        assert_eq!(read(&cache, &digest(1), b"aaaa").await.unwrap(), &b"aaaa"[..]);
+        settle(&cache, 4).await;
+        // Served from disk without calling the backend
+        let cached = cache
+            .get_or_fetch(&digest(1), || async { Err(crate::error::Error::internal("should be cached")) })
+            .await
+            .unwrap();
+        assert_eq!(cached.unwrap(), &b"aaaa"[..]);
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // Too large for a single entry
+        read(&cache, &digest(2), b"bbbbbbbbbb").await;
+        read(&cache, &digest(3), b"cccc").await;
+        settle(&cache, 8).await;
+
+        // Reading blob 1 again makes blob 3 the least recently read, so blob 4 evicts it
+        read(&cache, &digest(1), b"aaaa").await;
+        read(&cache, &digest(4), b"dddd").await;
+        for _ in 0..100 {
+            if cache.evictions() == 1 {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert_eq!(cache.evictions(), 1);
+        assert_eq!(cache.size_bytes(), 8);
+        assert!(!dir.path().join(file_name(&digest(3))).exists());
+
+        cache.invalidate(&digest(1)).await;
+        assert_eq!(cache.size_bytes(), 4);
+        assert!(!dir.path().join(file_name(&digest(1))).exists());
+
+        // A restart picks up what is on disk
+        drop(cache);
+        let reopened = BlobReadCache::open(dir.path(), 8, 8).await.unwrap();
+        assert_eq!(reopened.size_bytes(), 4);
+        assert_eq!(reopened.get(&digest(4)).await.unwrap(), &b"dddd"[..]);
+    }
+
+    #[test]
+    fn test_cache_file_names() {
+        assert_eq!(digest_from_file_name(&file_name(&digest(7))), Some(digest(7)));
+        assert_eq!(digest_from_file_name("sha256-abc.1234.tmp"), None);
+        assert_eq!(digest_from_file_name("../sha256-abc"), None);
+        assert_eq!(digest_from_file_name("lost+found"), None);
+    }
+}
@@ -0,0 +1,112 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::{
+    config::StorageConfig,
+    error::Result,
+    storage::Storage,
+    utils::sha256_digest,
+};
+
+/// Read fallback to a second storage backend during migrations
+/// The primary `Storage` keeps taking every write and delete; this only answers reads it misses
+
+pub struct StorageFallback {
+    secondary: Storage,
+    migrate_on_read: bool,
+    hits: AtomicU64,
+    migrated: AtomicU64,
+}
+
+impl StorageFallback {
+    /// Open the configured fallback backend, if any
+    pub async fn from_config(config: &StorageConfig) -> Result<Option<Self>> {
+        let Some(fallback) = &config.fallback else {
+            return Ok(None);
+        };
+
+        // Same layout as the primary, just a different location
+        let secondary = Storage::new(&StorageConfig {
+            backend: fallback.backend.clone(),
+            path: fallback.path.clone(),
+            fallback: None,
+            ..config.clone()
+        })
+        .await?;
+
+        tracing::info!(
+            "Reading blobs missing from primary storage from {:?} fallback at {}",
+            fallback.backend,
+            fallback.path.display()
+        );
+
+        Ok(Some(Self {
+            secondary,
+            migrate_on_read: fallback.migrate_on_read,
+            hits: AtomicU64::new(0),
+            migrated: AtomicU64::new(0),
+        }))
+    }
+
+    /// Reads the primary missed and the fallback served
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Blobs copied into the primary by `migrate_on_read`
+    pub fn migrated(&self) -> u64 {
+        self.migrated.load(Ordering::Relaxed)
+    }
+
+    /// Copy a blob read from the fallback into the primary
+    ///
+    /// The bytes are checked against the digest first so a damaged fallback
+    /// object is never made permanent. Failures are logged, not returned: the
+    /// caller already has the data and the next read will try again.
+    async fn migrate(&self, primary: &Storage, digest: &str, data: &[u8]) {
+        if sha256_digest(data) != digest {
+            tracing::warn!("Not migrating blob {}: fallback copy does not match its digest", digest);
+            return;
+        }
+
+        match primary.put_blob(digest, data).await {
+            Ok(_) => {
+                self.migrated.fetch_add(1, Ordering::Relaxed);
+                tracing::debug!("Migrated blob {} into primary storage", digest);
+            }
+            Err(e) => tracing::warn!("Failed to migrate blob {} into primary storage: {}", digest, e),
+        }
+    }
+}
+
+/// Read a blob from the primary, falling back to the secondary backend when configured
+pub async fn get_blob(primary: &Storage, fallback: Option<&StorageFallback>, digest: &str) -> Result<Option<Vec<u8>>> {
+    if let Some(data) = primary.get_blob(digest).await? {
+        return Ok(Some(data));
+    }
+
+    let Some(fallback) = fallback else {
+        return Ok(None);
+    };
+
+    let data = fallback.secondary.get_blob(digest).await?;
+    if let Some(data) = &data {
+        fallback.hits.fetch_add(1, Ordering::Relaxed);
+        if fallback.migrate_on_read {
+            fallback.migrate(primary, digest, data).await;
+        }
+    }
+
+    Ok(data)
+}
+
+/// Whether a blob exists in the primary or, failing that, the secondary backend
+pub async fn blob_exists(primary: &Storage, fallback: Option<&StorageFallback>, digest: &str) -> Result<bool> {
+    if primary.blob_exists(digest).await? {
+        return Ok(true);
+    }
+
+    match fallback {
+        Some(fallback) => fallback.secondary.blob_exists(digest).await,
+        None => Ok(false),
+    }
+}
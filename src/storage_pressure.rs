@@ -0,0 +1,201 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use uuid::Uuid;
+
+use crate::{
+    config::{StorageBackend, StorageConfig, StoragePressureConfig},
+    error::{Error, Result},
+    server::AppState,
+    websocket::{Notification, NotificationSeverity},
+};
+
+/// Push backpressure when the storage volume runs low
+/// Uploads are refused with 507 before a write can fail halfway; pulls and deletes are never checked
+
+pub struct StoragePressure {
+    config: StoragePressureConfig,
+    /// Volume to measure, or `None` when the backend can't report free space
+    path: Option<PathBuf>,
+    /// Set while under pressure so admins get one alert per episode, not one per chunk
+    alerted: AtomicBool,
+}
+
+/// Space on the storage volume, in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DiskUsage {
+    pub available: u64,
+    pub total: u64,
+}
+
+impl StoragePressure {
+    pub fn new(config: &StorageConfig) -> Self {
+        let path = (config.pressure.enabled && matches!(config.backend, StorageBackend::Filesystem))
+            .then(|| config.path.clone());
+
+        Self {
+            config: config.pressure.clone(),
+            path,
+            alerted: AtomicBool::new(false),
+        }
+    }
+
+    /// Why writing `incoming` more bytes would breach the threshold, if it would
+    fn shortfall(&self, usage: DiskUsage, incoming: u64) -> Option<String> {
+        let remaining = usage.available.saturating_sub(incoming);
+
+        if remaining < self.config.min_free_bytes {
+            return Some(format!(
+                "{} bytes would remain free, below the {} byte minimum",
+                remaining, self.config.min_free_bytes
+            ));
+        }
+
+        if self.config.min_free_percent > 0.0 && usage.total > 0 {
+            let percent = remaining as f64 / usage.total as f64 * 100.0;
+            if percent < self.config.min_free_percent {
+                return Some(format!(
+                    "{:.1}% of the volume would remain free, below the {}% minimum",
+                    percent, self.config.min_free_percent
+                ));
+            }
+        }
+
+        None
+    }
+}
+
+/// Refuse an upload of `incoming` bytes when it would leave the storage volume below the threshold
+///
+/// A volume whose free space can't be read is treated as healthy: failing every
+/// push over a transient stat error would be worse than the problem this guards against.
+pub async fn ensure_capacity(state: &AppState, incoming: u64) -> Result<()> {
+    let pressure = &state.storage_pressure;
+    let Some(path) = pressure.path.clone() else {
+        return Ok(());
+    };
+
+    let usage = match tokio::task::spawn_blocking(move || disk_usage(&path)).await {
+        Ok(Ok(usage)) => usage,
+        Ok(Err(e)) => {
+            tracing::warn!("Could not read free space on the storage volume: {}", e);
+            return Ok(());
+        }
+        Err(e) => {
+            tracing::warn!("Free space check did not run: {}", e);
+            return Ok(());
+        }
+    };
+
+    let Some(reason) = pressure.shortfall(usage, incoming) else {
+        if pressure.alerted.swap(false, Ordering::Relaxed) {
+            tracing::info!("Storage pressure relieved; {} bytes free, accepting pushes again", usage.available);
+        }
+        return Ok(());
+    };
+
+    tracing::warn!("Rejecting upload under storage pressure: {}", reason);
+    if !pressure.alerted.swap(true, Ordering::Relaxed) {
+        notify_admins(state, &reason).await;
+    }
+
+    Err(Error::insufficient_storage(format!(
+        "Registry storage is nearly full; pushes are paused ({})",
+        reason
+    )))
+}
+
+/// Critical alert to every active admin the first time pushes are refused
+async fn notify_admins(state: &AppState, reason: &str) {
+    let admins: Vec<String> = match sqlx::query_scalar("SELECT id FROM users WHERE is_admin = TRUE AND is_active = TRUE")
+        .fetch_all(&state.database.pool)
+        .await
+    {
+        Ok(admins) => admins,
+        Err(e) => {
+            tracing::warn!("Failed to look up storage pressure alert recipients: {}", e);
+            return;
+        }
+    };
+
+    for admin in admins {
+        let notification = Notification {
+            id: Uuid::new_v4().to_string(),
+            title: "Storage nearly full: pushes paused".to_string(),
+            message: format!("New blob uploads are being rejected because {}. Pulls and deletes still work.", reason),
+            severity: NotificationSeverity::Error,
+            timestamp: chrono::Utc::now(),
+            read: false,
+        };
+        state.websocket.broadcast_notification(admin, notification).await;
+    }
+}
+
+/// Free and total space on the filesystem holding `path`
+#[cfg(unix)]
+pub fn disk_usage(path: &Path) -> std::io::Result<DiskUsage> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `c_path` is NUL-terminated and `stat` is a valid, writable statvfs
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    let fragment = stat.f_frsize as u64;
+    Ok(DiskUsage {
+        // f_bavail, not f_bfree: blocks reserved for root aren't ours to fill
+        available: stat.f_bavail as u64 * fragment,
+        total: stat.f_blocks as u64 * fragment,
+    })
+}
+
+#[cfg(not(unix))]
+pub fn disk_usage(_path: &Path) -> std::io::Result<DiskUsage> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "free space checks are only supported on unix",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GB: u64 = 1024 * 1024 * 1024;
+
+    fn pressure(min_free_bytes: u64, min_free_percent: f64) -> StoragePressure {
+        let mut config = crate::config::Config::default().storage;
+        config.pressure = StoragePressureConfig { enabled: true, min_free_bytes, min_free_percent };
+        StoragePressure::new(&config)
+    }
+
+    #[test]
+    fn test_shortfall_thresholds() {
+        let usage = DiskUsage { available: 3 * GB, total: 100 * GB };
+
+        let by_bytes = pressure(GB, 0.0);
+        assert!(by_bytes.shortfall(usage, 0).is_none());
+        assert!(by_bytes.shortfall(usage, GB).is_none());
+        // The incoming upload counts against what would remain
+        assert!(by_bytes.shortfall(usage, 2 * GB + 1).is_some());
+
+        let by_percent = pressure(0, 5.0);
+        assert!(by_percent.shortfall(usage, 0).is_some());
+        assert!(by_percent.shortfall(DiskUsage { available: 10 * GB, total: 100 * GB }, 0).is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_disk_usage_reads_volume() {
+        let dir = tempfile::tempdir().unwrap();
+        let usage = disk_usage(dir.path()).unwrap();
+        assert!(usage.total > 0);
+        assert!(usage.available <= usage.total);
+
+        assert!(disk_usage(&dir.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::{
+    error::{Error, Result},
+    storage_pressure,
+};
+
+/// Host measurements for the dashboard's live metrics
+///
+/// CPU, memory and network come from `/proc`, so they are only available on Linux; disk
+/// usage is for the volume holding the storage path. Anything that can't be read is `None`
+/// and left to the caller to fill.
+
+/// Walking a large storage tree is expensive, so its size is refreshed less often than the rest
+const STORAGE_SIZE_REFRESH: Duration = Duration::from_secs(60);
+
+/// One reading; rates and network bytes cover the time since the previous sample
+#[derive(Debug, Clone, Default)]
+pub struct HostSample {
+    pub cpu_usage: Option<f64>,
+    pub memory_usage: Option<f64>,
+    pub disk_usage: Option<f64>,
+    pub network_rx: Option<u64>,
+    pub network_tx: Option<u64>,
+    pub storage_size: u64,
+}
+
+/// Busy and total jiffies across all CPUs
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CpuTimes {
+    idle: u64,
+    total: u64,
+}
+
+pub struct SystemSampler {
+    storage_path: PathBuf,
+    cpu: Option<CpuTimes>,
+    network: Option<(u64, u64)>,
+    storage_size: u64,
+    storage_sized_at: Option<Instant>,
+}
+
+impl SystemSampler {
+    /// Take baseline counters so the first sample already has something to compare against
+    pub async fn new(storage_path: &Path) -> Self {
+        Self {
+            storage_path: storage_path.to_path_buf(),
+            cpu: read_proc("/proc/stat").await.as_deref().and_then(parse_cpu_times),
+            network: read_proc("/proc/net/dev").await.as_deref().and_then(parse_network_bytes),
+            storage_size: 0,
+            storage_sized_at: None,
+        }
+    }
+
+    pub async fn sample(&mut self) -> HostSample {
+        let cpu = read_proc("/proc/stat").await.as_deref().and_then(parse_cpu_times);
+        let cpu_usage = match (self.cpu, cpu) {
+            (Some(previous), Some(current)) => cpu_usage(previous, current),
+            _ => None,
+        };
+        if cpu.is_some() {
+            self.cpu = cpu;
+        }
+
+        let network = read_proc("/proc/net/dev").await.as_deref().and_then(parse_network_bytes);
+        // Counters restart when an interface goes away, so a drop reads as no traffic
+        let (network_rx, network_tx) = match (self.network, network) {
+            (Some((rx, tx)), Some((current_rx, current_tx))) => {
+                (Some(current_rx.saturating_sub(rx)), Some(current_tx.saturating_sub(tx)))
+            }
+            _ => (None, None),
+        };
+        if network.is_some() {
+            self.network = network;
+        }
+
+        let size_stale = match self.storage_sized_at {
+            Some(at) => at.elapsed() >= STORAGE_SIZE_REFRESH,
+            None => true,
+        };
+        if size_stale {
+            match directory_size(&self.storage_path).await {
+                Ok(size) => self.storage_size = size,
+                Err(e) => tracing::warn!("Failed to measure {}: {}", self.storage_path.display(), e),
+            }
+            self.storage_sized_at = Some(Instant::now());
+        }
+
+        HostSample {
+            cpu_usage,
+            memory_usage: read_proc("/proc/meminfo").await.as_deref().and_then(parse_memory_usage),
+            disk_usage: storage_pressure::disk_usage(&self.storage_path)
+                .ok()
+                .filter(|usage| usage.total > 0)
+                .map(|usage| percent(usage.total - usage.available, usage.total)),
+            network_rx,
+            network_tx,
+            storage_size: self.storage_size,
+        }
+    }
+}
+
+/// Total size of the files under `path`; a missing path is empty
+pub async fn directory_size(path: &Path) -> Result<u64> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let mut total = 0;
+        for entry in walkdir::WalkDir::new(&path) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.io_error().is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound) => continue,
+                Err(e) => return Err(Error::from(std::io::Error::from(e))),
+            };
+            if entry.file_type().is_file() {
+                total += entry.metadata().map_err(std::io::Error::from)?.len();
+            }
+        }
+        Ok(total)
+    })
+    .await
+    .map_err(|e| Error::internal(format!("Storage size task failed: {}", e)))?
+}
+
+async fn read_proc(path: &str) -> Option<String> {
+    tokio::fs::read_to_string(path).await.ok()
+}
+
+fn percent(part: u64, whole: u64) -> f64 {
+    part as f64 / whole as f64 * 100.0
+}
+
+/// The aggregate `cpu` line of `/proc/stat`
+fn parse_cpu_times(stat: &str) -> Option<CpuTimes> {
+    let fields: Vec<u64> = stat
+        .lines()
+        .find(|line| line.starts_with("cpu "))?
+        .split_whitespace()
+        .skip(1)
+        .map(|field| field.parse().ok())
+        .collect::<Option<_>>()?;
+    if fields.len() < 4 {
+        return None;
+    }
+
+    // user nice system idle iowait irq softirq steal; guest time is already counted in user
+    let idle = fields[3] + fields.get(4).copied().unwrap_or(0);
+    let total = fields.iter().take(8).sum();
+    Some(CpuTimes { idle, total })
+}
+
+fn cpu_usage(previous: CpuTimes, current: CpuTimes) -> Option<f64> {
+    let total = current.total.checked_sub(previous.total).filter(|&t| t > 0)?;
+    let idle = current.idle.saturating_sub(previous.idle).min(total);
+    Some(percent(total - idle, total))
+}
+
+/// Share of memory in use, counting reclaimable cache as free
+fn parse_memory_usage(meminfo: &str) -> Option<f64> {
+    let field = |name: &str| {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse::<u64>()
+            .ok()
+    };
+    let total = field("MemTotal").filter(|&t| t > 0)?;
+    let available = field("MemAvailable")?.min(total);
+    Some(percent(total - available, total))
+}
+
+/// Received and transmitted bytes summed over every interface except loopback
+fn parse_network_bytes(net_dev: &str) -> Option<(u64, u64)> {
+    let mut totals = None;
+    // Two header lines, then `iface: rx_bytes rx_packets ... (8 receive fields) tx_bytes ...`
+    for line in net_dev.lines().skip(2) {
+        let Some((interface, counters)) = line.split_once(':') else { continue };
+        if interface.trim() == "lo" {
+            continue;
+        }
+        let counters: Vec<u64> = counters.split_whitespace().filter_map(|c| c.parse().ok()).collect();
+        if let (Some(rx), Some(tx)) = (counters.first(), counters.get(8)) {
+            let (total_rx, total_tx) = totals.get_or_insert((0u64, 0u64));
+            *total_rx += rx;
+            *total_tx += tx;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_proc_parsing() {
+        let before = parse_cpu_times("cpu  100 0 100 700 100 0 0 0 50 0\ncpu0 50 0 50 350 50 0 0 0 25 0\n").unwrap();
+        assert_eq!(before, CpuTimes { idle: 800, total: 1000 });
+        let after = parse_cpu_times("cpu  200 0 100 800 100 0 0 0 50 0\n").unwrap();
+        assert_eq!(cpu_usage(before, after), Some(50.0));
+        assert_eq!(cpu_usage(after, after), None);
+
+        let meminfo = "MemTotal:       16000 kB\nMemFree:         1000 kB\nMemAvailable:    4000 kB\n";
+        assert_eq!(parse_memory_usage(meminfo), Some(75.0));
+        assert_eq!(parse_memory_usage("MemTotal: 16000 kB\n"), None);
+
+        let net_dev = "Inter-|   Receive                                                |  Transmit\n \
+            face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n    \
+            lo: 5000 50 0 0 0 0 0 0 5000 50 0 0 0 0 0 0\n  \
+            eth0: 1200 10 0 0 0 0 0 0 300 5 0 0 0 0 0 0\n  \
+            eth1: 800 4 0 0 0 0 0 0 200 2 0 0 0 0 0 0\n";
+        assert_eq!(parse_network_bytes(net_dev), Some((2000, 500)));
+        assert_eq!(parse_network_bytes("header\nheader\n"), None);
+    }
+
+    #[tokio::test]
+    async fn test_directory_size() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("blobs/sha256")).unwrap();
+        std::fs::write(dir.path().join("blobs/sha256/ab"), b"layer").unwrap();
+        std::fs::write(dir.path().join("config"), b"{}").unwrap();
+
+        assert_eq!(directory_size(dir.path()).await.unwrap(), 7);
+        assert_eq!(directory_size(&dir.path().join("missing")).await.unwrap(), 0);
+    }
+}
@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use uuid::Uuid;
 use chrono::{DateTime, Utc};
 
@@ -8,6 +8,7 @@ use chrono::{DateTime, Utc};
 pub struct Repository {
     pub id: Uuid,
     pub name: String,
+    pub namespace: Option<String>,
     pub description: String,
     pub is_public: bool,
     pub created_at: DateTime<Utc>,
@@ -31,7 +32,8 @@ pub struct Manifest {
     pub repository_id: Uuid,
     pub digest: String,
     pub media_type: String,
-    pub content: String,
+    /// Raw manifest bytes exactly as pushed; the digest is computed over these
+    pub content: Vec<u8>,
     pub size: i64,
     pub created_at: DateTime<Utc>,
 }
@@ -52,6 +54,8 @@ pub struct UploadSession {
     pub uuid: Uuid,
     pub repository_id: Uuid,
     pub uploaded_size: i64,
+    /// Length declared by the client, if any
+    pub total_size: Option<i64>,
     pub storage_path: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
@@ -71,7 +75,101 @@ pub struct RegistryErrorResponse {
     pub errors: Vec<RegistryError>,
 }
 
-/// Manifest types
+/// Docker Registry v2 response header names
+pub mod registry_headers {
+    pub const API_VERSION: &str = "Docker-Distribution-Api-Version";
+    pub const API_VERSION_VALUE: &str = "registry/2.0";
+    pub const CONTENT_DIGEST: &str = "Docker-Content-Digest";
+    pub const UPLOAD_UUID: &str = "Docker-Upload-UUID";
+    /// Total blob length a client declares for a chunked upload, enabling progress events
+    pub const UPLOAD_LENGTH: &str = "Upload-Length";
+    /// Echoes a pushed manifest's subject, telling clients the referrers API is supported
+    pub const OCI_SUBJECT: &str = "OCI-Subject";
+    /// Filters a referrers response honoured, e.g. `artifactType`
+    pub const OCI_FILTERS_APPLIED: &str = "OCI-Filters-Applied";
+}
+
+/// Well-known manifest, config and layer media types
+pub mod media_types {
+    pub const DOCKER_MANIFEST_V1: &str = "application/vnd.docker.distribution.manifest.v1+json";
+    pub const DOCKER_MANIFEST_V2: &str = "application/vnd.docker.distribution.manifest.v2+json";
+    pub const DOCKER_MANIFEST_LIST: &str = "application/vnd.docker.distribution.manifest.list.v2+json";
+    pub const OCI_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+    pub const OCI_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+
+    /// Manifest media types the registry stores
+    pub const MANIFEST_TYPES: &[&str] = &[DOCKER_MANIFEST_V1, DOCKER_MANIFEST_V2, DOCKER_MANIFEST_LIST, OCI_MANIFEST, OCI_INDEX];
+
+    pub const DOCKER_CONFIG: &str = "application/vnd.docker.container.image.v1+json";
+    pub const OCI_CONFIG: &str = "application/vnd.oci.image.config.v1+json";
+    pub const OCI_EMPTY: &str = "application/vnd.oci.empty.v1+json";
+    pub const HELM_CONFIG: &str = "application/vnd.cncf.helm.config.v1+json";
+
+    /// Layer media types accepted in Docker v2 image manifests
+    pub const DOCKER_LAYERS: &[&str] = &[
+        "application/vnd.docker.image.rootfs.diff.tar.gzip",
+        "application/vnd.docker.image.rootfs.foreign.diff.tar.gzip",
+    ];
+
+    /// Layer media types accepted in OCI image manifests
+    pub const OCI_LAYERS: &[&str] = &[
+        "application/vnd.oci.image.layer.v1.tar",
+        "application/vnd.oci.image.layer.v1.tar+gzip",
+        "application/vnd.oci.image.layer.v1.tar+zstd",
+        "application/vnd.oci.image.layer.nondistributable.v1.tar",
+        "application/vnd.oci.image.layer.nondistributable.v1.tar+gzip",
+        "application/vnd.oci.image.layer.nondistributable.v1.tar+zstd",
+    ];
+
+    /// Layer media types pushed by `helm push`
+    pub const HELM_LAYERS: &[&str] = &[
+        "application/vnd.cncf.helm.chart.content.v1.tar+gzip",
+        "application/vnd.cncf.helm.chart.provenance.v1.prov",
+    ];
+}
+
+/// Kind of artifact a manifest describes, derived from its media types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ArtifactType {
+    Image,
+    HelmChart,
+    Index,
+    Artifact,
+}
+
+impl ArtifactType {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ArtifactType::Image => "image",
+            ArtifactType::HelmChart => "helm_chart",
+            ArtifactType::Index => "index",
+            ArtifactType::Artifact => "artifact",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "image" => Some(ArtifactType::Image),
+            "helm_chart" => Some(ArtifactType::HelmChart),
+            "index" => Some(ArtifactType::Index),
+            "artifact" => Some(ArtifactType::Artifact),
+            _ => None,
+        }
+    }
+
+    /// Classify a manifest by its own media type and its config's media type
+    pub fn detect(manifest_media_type: &str, config_media_type: Option<&str>) -> Self {
+        match (manifest_media_type, config_media_type) {
+            (media_types::DOCKER_MANIFEST_LIST | media_types::OCI_INDEX, _) => ArtifactType::Index,
+            (_, Some(media_types::HELM_CONFIG)) => ArtifactType::HelmChart,
+            (_, Some(media_types::DOCKER_CONFIG | media_types::OCI_CONFIG)) | (_, None) => ArtifactType::Image,
+            _ => ArtifactType::Artifact,
+        }
+    }
+}
+
+/// Manifest types, keyed by the manifest's `mediaType`
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "mediaType")]
 pub enum ManifestType {
@@ -87,6 +185,103 @@ pub enum ManifestType {
     OciIndex(OciIndex),
 }
 
+impl ManifestType {
+    /// Parse manifest bytes. The type comes from the body's `mediaType`, falling back to
+    /// `content_type` (the push's Content-Type or the stored media type) and then Docker v2.
+    /// A `content_type` naming a different manifest type than the body is rejected; one that
+    /// isn't a manifest type at all (e.g. `application/json`) is ignored.
+    ///
+    /// Only the typed view comes from here: the original bytes are what get stored and digested.
+    pub fn parse(content: &[u8], content_type: Option<&str>) -> crate::error::Result<Self> {
+        #[derive(Deserialize)]
+        struct MediaTypeProbe {
+            #[serde(rename = "mediaType")]
+            media_type: Option<String>,
+        }
+
+        let probe: MediaTypeProbe = serde_json::from_slice(content)
+            .map_err(|e| crate::error::Error::manifest_invalid(format!("Manifest is not valid JSON: {}", e)))?;
+        let declared = content_type
+            .map(|value| value.split(';').next().unwrap_or_default().trim())
+            .filter(|value| media_types::MANIFEST_TYPES.contains(value));
+        let media_type = match (probe.media_type.as_deref(), declared) {
+            (Some(body), Some(declared)) if body != declared => {
+                return Err(crate::error::Error::manifest_invalid(format!(
+                    "Manifest mediaType '{}' does not match Content-Type '{}'",
+                    body, declared
+                )))
+            }
+            (Some(body), _) => body,
+            (None, declared) => declared.unwrap_or(media_types::DOCKER_MANIFEST_V2),
+        };
+
+        fn typed<T: serde::de::DeserializeOwned>(content: &[u8], media_type: &str) -> crate::error::Result<T> {
+            serde_json::from_slice(content).map_err(|e| {
+                crate::error::Error::manifest_invalid(format!("Malformed {} manifest: {}", media_type, e))
+            })
+        }
+
+        let manifest = match media_type {
+            media_types::DOCKER_MANIFEST_V1 => ManifestType::V1(typed(content, media_type)?),
+            media_types::DOCKER_MANIFEST_V2 => ManifestType::V2(typed(content, media_type)?),
+            media_types::DOCKER_MANIFEST_LIST => ManifestType::List(typed(content, media_type)?),
+            media_types::OCI_MANIFEST => ManifestType::Oci(typed(content, media_type)?),
+            media_types::OCI_INDEX => ManifestType::OciIndex(typed(content, media_type)?),
+            other => {
+                return Err(crate::error::Error::manifest_invalid(format!(
+                    "Unsupported manifest media type '{}'",
+                    other
+                )))
+            }
+        };
+
+        Ok(manifest)
+    }
+
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            ManifestType::V1(_) => media_types::DOCKER_MANIFEST_V1,
+            ManifestType::V2(_) => media_types::DOCKER_MANIFEST_V2,
+            ManifestType::List(_) => media_types::DOCKER_MANIFEST_LIST,
+            ManifestType::Oci(_) => media_types::OCI_MANIFEST,
+            ManifestType::OciIndex(_) => media_types::OCI_INDEX,
+        }
+    }
+
+    /// Config descriptor of an image manifest
+    pub fn config(&self) -> Option<&Descriptor> {
+        match self {
+            ManifestType::V2(manifest) => Some(&manifest.config),
+            ManifestType::Oci(manifest) => Some(&manifest.config),
+            _ => None,
+        }
+    }
+
+    /// Blobs an image manifest references: its config, then its layers
+    pub fn blob_digests(&self) -> Vec<&str> {
+        match self {
+            ManifestType::V1(manifest) => manifest.fs_layers.iter().map(|l| l.blob_sum.as_str()).collect(),
+            ManifestType::V2(ManifestV2 { config, layers, .. }) | ManifestType::Oci(OciManifest { config, layers, .. }) => {
+                std::iter::once(config).chain(layers).map(|d| d.digest.as_str()).collect()
+            }
+            ManifestType::List(_) | ManifestType::OciIndex(_) => vec![],
+        }
+    }
+
+    pub fn artifact_type(&self) -> ArtifactType {
+        ArtifactType::detect(self.media_type(), self.config().map(|c| c.media_type.as_str()))
+    }
+
+    /// Manifest an OCI manifest or index is attached to via `subject`
+    pub fn subject(&self) -> Option<&Descriptor> {
+        match self {
+            ManifestType::Oci(manifest) => manifest.subject.as_ref(),
+            ManifestType::OciIndex(index) => index.subject.as_ref(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ManifestV1 {
     pub name: String,
@@ -97,11 +292,14 @@ pub struct ManifestV1 {
     pub fs_layers: Vec<V1Layer>,
     #[serde(rename = "schemaVersion")]
     pub schema_version: u32,
+    /// Absent from unsigned schema 1 manifests
+    #[serde(default)]
     pub signatures: Vec<V1Signature>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct V1History {
+    #[serde(rename = "v1Compatibility")]
     pub v1_compatibility: String,
 }
 
@@ -128,8 +326,6 @@ pub struct V1SignatureHeader {
 pub struct ManifestV2 {
     #[serde(rename = "schemaVersion")]
     pub schema_version: u32,
-    #[serde(rename = "mediaType")]
-    pub media_type: String,
     pub config: Descriptor,
     pub layers: Vec<Descriptor>,
 }
@@ -138,8 +334,6 @@ pub struct ManifestV2 {
 pub struct ManifestList {
     #[serde(rename = "schemaVersion")]
     pub schema_version: u32,
-    #[serde(rename = "mediaType")]
-    pub media_type: String,
     pub manifests: Vec<ManifestListEntry>,
 }
 
@@ -168,10 +362,14 @@ pub struct Platform {
 pub struct OciManifest {
     #[serde(rename = "schemaVersion")]
     pub schema_version: u32,
-    #[serde(rename = "mediaType")]
-    pub media_type: String,
+    /// Set on OCI 1.1 artifacts (signatures, SBOMs), whose layers may be of any media type
+    #[serde(rename = "artifactType", skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
     pub config: Descriptor,
     pub layers: Vec<Descriptor>,
+    /// Manifest this one is attached to, making it one of the subject's referrers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Descriptor>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub annotations: Option<HashMap<String, String>>,
 }
@@ -180,10 +378,12 @@ pub struct OciManifest {
 pub struct OciIndex {
     #[serde(rename = "schemaVersion")]
     pub schema_version: u32,
-    #[serde(rename = "mediaType")]
-    pub media_type: String,
+    #[serde(rename = "artifactType", skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
     pub manifests: Vec<Descriptor>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Descriptor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub annotations: Option<HashMap<String, String>>,
 }
 
@@ -214,16 +414,6 @@ pub struct User {
     pub last_login: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct Claims {
-    pub sub: String,
-    pub username: String,
-    pub email: String,
-    pub is_admin: bool,
-    pub exp: usize,
-    pub iat: usize,
-}
-
 /// API response types
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TagsResponse {
@@ -231,12 +421,192 @@ pub struct TagsResponse {
     pub tags: Vec<String>,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateRepositoryRequest {
+    pub name: String,
+    pub description: Option<String>,
+    /// Defaults to the configured visibility for the caller's namespace
+    pub is_public: Option<bool>,
+    /// Artifact kinds the repository accepts; unset accepts any
+    #[serde(default)]
+    pub allowed_artifact_types: Option<Vec<ArtifactType>>,
+}
+
+/// Replace a repository's artifact type restriction; `null` lifts it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArtifactTypeRestriction {
+    pub allowed_artifact_types: Option<Vec<ArtifactType>>,
+}
+
+/// Replace a repository's default platform; `null` falls back to `registry.default_platform`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DefaultPlatform {
+    pub default_platform: Option<String>,
+}
+
+/// Turn a repository's deletion protection on or off
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeletionProtection {
+    pub deletion_protected: bool,
+}
+
+/// Digests to look up in one round trip instead of a HEAD each
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobCheckRequest {
+    pub digests: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlobCheckResponse {
+    pub present: Vec<PresentBlob>,
+    pub missing: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PresentBlob {
+    pub digest: String,
+    pub size: i64,
+}
+
+/// Tag with the manifest it points at and the total image size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagDetail {
+    pub name: String,
+    pub digest: String,
+    pub media_type: String,
+    pub artifact_type: ArtifactType,
+    pub image_size: i64,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Tag references to compare against the digests a deployment was pinned to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagDriftRequest {
+    pub references: Vec<TagDriftReference>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagDriftReference {
+    /// `repository:tag`; a bare repository name means `latest`
+    pub reference: String,
+    /// Digest the deployment resolved the tag to when it was rolled out
+    pub baseline_digest: Option<String>,
+}
+
+/// Where one tag points now, and whether it moved away from its baseline
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct RepositoryListResponse {
-    pub repositories: Vec<Repository>,
+pub struct TagDriftResult {
+    pub reference: String,
+    pub baseline_digest: Option<String>,
+    /// `None` when the reference couldn't be resolved
+    pub current_digest: Option<String>,
+    /// The tag no longer points at the baseline digest; false without a baseline
+    pub drifted: bool,
+    /// When the tag was last pushed or moved
+    pub tag_updated_at: Option<DateTime<Utc>>,
+    /// Why the reference couldn't be resolved
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagDriftReport {
+    pub drifted: usize,
+    pub results: Vec<TagDriftResult>,
+}
+
+/// Runtime settings from an image's config blob, for browsing without a pull
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageConfigDetails {
+    /// Platform-specific manifest the config was read from
+    pub manifest_digest: String,
+    pub config_digest: String,
+    /// `os/architecture[/variant]`
+    pub platform: Option<String>,
+    pub created: Option<String>,
+    pub author: Option<String>,
+    pub user: Option<String>,
+    pub entrypoint: Vec<String>,
+    pub cmd: Vec<String>,
+    pub working_dir: Option<String>,
+    pub env: Vec<String>,
+    /// `port/protocol`, e.g. `8080/tcp`
+    pub exposed_ports: Vec<String>,
+    pub volumes: Vec<String>,
+    pub labels: BTreeMap<String, String>,
+    pub stop_signal: Option<String>,
+}
+
+/// Page size when the caller doesn't give a `limit`
+pub const DEFAULT_PAGE_LIMIT: u32 = 50;
+
+/// Largest `limit` a caller may ask for
+pub const MAX_PAGE_LIMIT: u32 = 500;
+
+/// Envelope shared by every `/api/*` list endpoint
+/// (the Docker v2 `_catalog` and `tags/list` keep their spec shapes)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    /// Items matching the query across all pages
     pub total: u64,
-    pub page: u32,
-    pub per_page: u32,
+    pub limit: u32,
+    pub offset: u32,
+    /// Pass as `cursor` to fetch the next page; absent on the last page
+    pub next_cursor: Option<String>,
+}
+
+/// `limit` / `offset` / `cursor` query parameters; a cursor takes precedence over `offset`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct PageQuery {
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+    pub cursor: Option<String>,
+}
+
+impl PageQuery {
+    /// Resolved `(limit, offset)`, with the limit clamped to `1..=MAX_PAGE_LIMIT`
+    pub fn bounds(&self) -> crate::error::Result<(u32, u32)> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+        let offset = match &self.cursor {
+            Some(cursor) => decode_cursor(cursor)
+                .ok_or_else(|| crate::error::Error::bad_request("Invalid pagination cursor"))?,
+            None => self.offset.unwrap_or(0),
+        };
+        Ok((limit, offset))
+    }
+}
+
+impl<T> Page<T> {
+    /// Wrap one page of items fetched with `limit` / `offset`
+    pub fn new(items: Vec<T>, total: u64, limit: u32, offset: u32) -> Self {
+        let next = offset as u64 + items.len() as u64;
+        Self {
+            next_cursor: (!items.is_empty() && next < total).then(|| encode_cursor(next as u32)),
+            items,
+            total,
+            limit,
+            offset,
+        }
+    }
+
+    /// Page through a list that is already fully loaded (and filtered) in memory
+    pub fn from_vec(all: Vec<T>, limit: u32, offset: u32) -> Self {
+        let total = all.len() as u64;
+        let items = all.into_iter().skip(offset as usize).take(limit as usize).collect();
+        Self::new(items, total, limit, offset)
+    }
+}
+
+/// Cursors are opaque to clients so the encoding can change without breaking them
+fn encode_cursor(offset: u32) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!("offset:{}", offset))
+}
+
+fn decode_cursor(cursor: &str) -> Option<u32> {
+    use base64::Engine;
+    let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(cursor).ok()?;
+    String::from_utf8(decoded).ok()?.strip_prefix("offset:")?.parse().ok()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -247,3 +617,30 @@ pub struct HealthResponse {
     pub database: String,
     pub storage: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_page_cursor_walks_to_the_end() {
+        let all: Vec<u32> = (0..5).collect();
+        let mut query = PageQuery { limit: Some(2), ..PageQuery::default() };
+        let mut seen = Vec::new();
+        loop {
+            let (limit, offset) = query.bounds().unwrap();
+            let page = Page::from_vec(all.clone(), limit, offset);
+            assert_eq!(page.total, 5);
+            seen.extend(page.items);
+            match page.next_cursor {
+                Some(cursor) => query.cursor = Some(cursor),
+                None => break,
+            }
+        }
+        assert_eq!(seen, all);
+
+        let bad = PageQuery { cursor: Some("not a cursor".to_string()), ..PageQuery::default() };
+        assert!(bad.bounds().is_err());
+        assert_eq!(PageQuery { limit: Some(10_000), ..PageQuery::default() }.bounds().unwrap(), (MAX_PAGE_LIMIT, 0));
+    }
+}
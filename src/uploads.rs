@@ -0,0 +1,382 @@
+use axum::http::{header, HeaderMap, HeaderValue};
+use dashmap::{mapref::entry::Entry, DashMap};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+use tokio::{
+    fs::{self, OpenOptions},
+    io::{AsyncReadExt, AsyncWriteExt},
+};
+
+use crate::{
+    error::{Error, Result},
+    types::registry_headers,
+};
+use uuid::Uuid;
+
+/// Minimum spacing between progress events for one upload
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Uploads tracked before idle throttle entries are pruned
+const MAX_TRACKED_UPLOADS: usize = 4096;
+
+/// Durable staging area for in-progress blob uploads
+///
+/// Chunks are appended to a file per upload session and fsynced before the
+/// request completes, so the file length is always the number of bytes a
+/// client may resume from - including after a server restart.
+#[derive(Debug, Clone)]
+pub struct UploadStore {
+    root: PathBuf,
+}
+
+impl UploadStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        Self {
+            root: root.as_ref().to_path_buf(),
+        }
+    }
+
+    fn path(&self, storage_path: &str) -> PathBuf {
+        self.root.join(storage_path)
+    }
+
+    /// Number of bytes durably persisted for an upload
+    pub async fn persisted_size(&self, storage_path: &str) -> Result<u64> {
+        match fs::metadata(self.path(storage_path)).await {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Append a chunk that must start at `offset`, returning the new persisted size
+    pub async fn append_chunk(&self, storage_path: &str, offset: u64, data: &[u8]) -> Result<u64> {
+        let current = self.persisted_size(storage_path).await?;
+        if offset != current {
+            return Err(Error::range_invalid(format!(
+                "Chunk starts at {} but {} bytes are persisted",
+                offset, current
+            )));
+        }
+
+        let path = self.path(storage_path);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let mut file = OpenOptions::new().create(true).append(true).open(&path).await?;
+        file.write_all(data).await?;
+        file.sync_all().await?;
+
+        Ok(current + data.len() as u64)
+    }
+
+    /// Read the full contents of an upload
+    pub async fn read(&self, storage_path: &str) -> Result<Vec<u8>> {
+        match fs::read(self.path(storage_path)).await {
+            Ok(data) => Ok(data),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// `sha256:` digest of an upload, hashed from disk without loading it whole
+    pub async fn digest(&self, storage_path: &str) -> Result<String> {
+        let mut hasher = Sha256::new();
+        let mut file = match fs::File::open(self.path(storage_path)).await {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(format!("sha256:{:x}", hasher.finalize())),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut buffer = vec![0u8; 1024 * 1024];
+        loop {
+            let read = file.read(&mut buffer).await?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+        }
+        Ok(format!("sha256:{:x}", hasher.finalize()))
+    }
+
+    /// Remove an upload's staged bytes
+    pub async fn remove(&self, storage_path: &str) -> Result<()> {
+        match fs::remove_file(self.path(storage_path)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `Range` header value for an upload holding `size` bytes
+///
+/// The distribution spec mandates `0-<offset>` with an inclusive end and no
+/// `bytes=` unit; an empty upload is reported as `0-0`.
+pub fn upload_range(size: u64) -> String {
+    format!("0-{}", size.saturating_sub(1))
+}
+
+/// Headers returned when an upload is initiated, after each chunk and on status
+/// requests, so clients resume from the same offset whichever step they saw last
+pub fn upload_headers(repository: &str, upload_uuid: &str, size: u64) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        header::LOCATION,
+        HeaderValue::from_str(&format!("/v2/{}/blobs/uploads/{}", repository, upload_uuid)).unwrap(),
+    );
+    headers.insert(header::RANGE, HeaderValue::from_str(&upload_range(size)).unwrap());
+    headers.insert(registry_headers::UPLOAD_UUID, HeaderValue::from_str(upload_uuid).unwrap());
+    headers
+}
+
+/// Total blob length a client declared with `Upload-Length`, if any
+pub fn declared_length(headers: &HeaderMap) -> Result<Option<u64>> {
+    headers
+        .get(registry_headers::UPLOAD_LENGTH)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|v| v.trim().parse::<u64>().ok())
+                .ok_or_else(|| Error::bad_request("Invalid Upload-Length header"))
+        })
+        .transpose()
+}
+
+/// Check a chunk's inclusive `Content-Range` against the bytes it carried.
+/// An open end (`512-`) accepts whatever the body holds.
+pub fn check_chunk_range(start: u64, end: u64, len: usize) -> Result<()> {
+    if end == u64::MAX || (len > 0 && end - start + 1 == len as u64) {
+        return Ok(());
+    }
+    Err(Error::range_invalid(format!(
+        "Content-Range {}-{} does not match the {}-byte chunk",
+        start, end, len
+    )))
+}
+
+/// Progress of a chunked upload with a declared length, broadcast on the `upload_progress` topic
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UploadProgress {
+    pub upload_id: Uuid,
+    pub repository: String,
+    pub uploaded_size: u64,
+    pub total_size: u64,
+}
+
+impl UploadProgress {
+    pub fn is_complete(&self) -> bool {
+        self.uploaded_size >= self.total_size
+    }
+}
+
+/// Spaces out progress events per upload so rapid chunks don't flood the broadcast channel
+#[derive(Debug, Default)]
+pub struct ProgressThrottle {
+    last_emitted: DashMap<Uuid, Instant>,
+}
+
+impl ProgressThrottle {
+    /// Whether an event for `upload_id` may go out at `now`; the final one always does
+    pub fn admit(&self, upload_id: Uuid, complete: bool, now: Instant) -> bool {
+        if complete {
+            self.last_emitted.remove(&upload_id);
+            return true;
+        }
+
+        if self.last_emitted.len() >= MAX_TRACKED_UPLOADS {
+            // Entries older than the interval would admit anyway, so dropping them changes nothing
+            self.last_emitted.retain(|_, last| now.duration_since(*last) < PROGRESS_INTERVAL);
+        }
+
+        match self.last_emitted.entry(upload_id) {
+            Entry::Occupied(mut last) => {
+                if now.duration_since(*last.get()) < PROGRESS_INTERVAL {
+                    return false;
+                }
+                last.insert(now);
+            }
+            Entry::Vacant(slot) => {
+                slot.insert(now);
+            }
+        }
+        true
+    }
+
+    /// Drop state for an upload that finished or was cancelled
+    pub fn forget(&self, upload_id: &Uuid) {
+        self.last_emitted.remove(upload_id);
+    }
+}
+
+/// Blobs acknowledged under deferred verification whose digest hasn't been checked yet,
+/// by repository, with the manifests waiting to be linked to them once it has
+#[derive(Debug, Default)]
+pub struct PendingBlobs {
+    pending: DashMap<(Uuid, String), PendingBlob>,
+}
+
+#[derive(Debug, Default)]
+struct PendingBlob {
+    /// Uploads of this digest still being verified
+    uploads: usize,
+    manifests: Vec<Uuid>,
+}
+
+impl PendingBlobs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// An upload of `digest` to the repository was acknowledged and awaits verification
+    pub fn begin(&self, repository_id: Uuid, digest: &str) {
+        self.pending.entry((repository_id, digest.to_string())).or_default().uploads += 1;
+    }
+
+    pub fn is_pending(&self, repository_id: Uuid, digest: &str) -> bool {
+        self.pending.contains_key(&(repository_id, digest.to_string()))
+    }
+
+    /// Have `manifest_id` linked to the blob once verified. False when nothing is pending
+    /// any more, in which case the blob is either stored already or was discarded.
+    pub fn link_when_verified(&self, repository_id: Uuid, digest: &str, manifest_id: Uuid) -> bool {
+        match self.pending.get_mut(&(repository_id, digest.to_string())) {
+            Some(mut pending) => {
+                pending.manifests.push(manifest_id);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// One upload's verification finished, returning the manifests to link if it stored the
+    /// blob. Failed uploads leave waiting manifests to a concurrent upload of the same digest.
+    pub fn finish(&self, repository_id: Uuid, digest: &str, verified: bool) -> Vec<Uuid> {
+        let key = (repository_id, digest.to_string());
+        let Entry::Occupied(mut entry) = self.pending.entry(key) else {
+            return Vec::new();
+        };
+
+        let pending = entry.get_mut();
+        pending.uploads = pending.uploads.saturating_sub(1);
+        if !verified && pending.uploads > 0 {
+            return Vec::new();
+        }
+        let manifests = std::mem::take(&mut pending.manifests);
+        if pending.uploads == 0 {
+            entry.remove();
+        }
+        manifests
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resume_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage_path = "uploads/session";
+
+        let store = UploadStore::new(dir.path());
+        assert_eq!(store.append_chunk(storage_path, 0, b"hello ").await.unwrap(), 6);
+        drop(store);
+
+        // A fresh store over the same directory sees exactly the persisted bytes
+        let store = UploadStore::new(dir.path());
+        assert_eq!(store.persisted_size(storage_path).await.unwrap(), 6);
+        assert_eq!(upload_range(6), "0-5");
+
+        // Resending from a stale offset is rejected
+        assert!(matches!(
+            store.append_chunk(storage_path, 0, b"hello ").await,
+            Err(Error::RangeInvalid { .. })
+        ));
+
+        assert_eq!(store.append_chunk(storage_path, 6, b"world").await.unwrap(), 11);
+        assert_eq!(store.read(storage_path).await.unwrap(), b"hello world");
+        assert_eq!(store.digest(storage_path).await.unwrap(), crate::utils::sha256_digest(b"hello world"));
+
+        store.remove(storage_path).await.unwrap();
+        assert_eq!(store.persisted_size(storage_path).await.unwrap(), 0);
+    }
+
+    #[test]
+    fn test_pending_blobs_hand_over_waiting_manifests() {
+        let pending = PendingBlobs::new();
+        let repository = Uuid::new_v4();
+        let manifest = Uuid::new_v4();
+        assert!(!pending.link_when_verified(repository, "sha256:a", manifest));
+
+        // Two uploads of the same digest; the first fails verification
+        pending.begin(repository, "sha256:a");
+        pending.begin(repository, "sha256:a");
+        assert!(pending.link_when_verified(repository, "sha256:a", manifest));
+        assert!(pending.finish(repository, "sha256:a", false).is_empty());
+        assert!(pending.is_pending(repository, "sha256:a"));
+
+        assert_eq!(pending.finish(repository, "sha256:a", true), vec![manifest]);
+        assert!(!pending.is_pending(repository, "sha256:a"));
+        assert!(!pending.is_pending(Uuid::new_v4(), "sha256:a"));
+    }
+
+    #[test]
+    fn test_check_chunk_range() {
+        assert!(check_chunk_range(0, 5, 6).is_ok());
+        assert!(check_chunk_range(6, 10, 5).is_ok());
+        assert!(check_chunk_range(6, u64::MAX, 3).is_ok());
+
+        // Docker sends inclusive ends, so an exclusive one is a byte short
+        assert!(matches!(check_chunk_range(0, 6, 6), Err(Error::RangeInvalid { .. })));
+        assert!(matches!(check_chunk_range(0, 0, 0), Err(Error::RangeInvalid { .. })));
+    }
+
+    #[test]
+    fn test_upload_headers_at_each_step() {
+        let uuid = "3f1b6d2a-0c4e-4a8b-9d7e-1f2a3b4c5d6e";
+
+        // Initiation: nothing received yet
+        let headers = upload_headers("team/app", uuid, 0);
+        assert_eq!(headers[header::RANGE], "0-0");
+        assert_eq!(headers[header::LOCATION], format!("/v2/team/app/blobs/uploads/{}", uuid));
+        assert_eq!(headers[registry_headers::UPLOAD_UUID], uuid);
+
+        // After a first 1024-byte chunk and a second 512-byte one
+        assert_eq!(upload_headers("team/app", uuid, 1024)[header::RANGE], "0-1023");
+        assert_eq!(upload_headers("team/app", uuid, 1536)[header::RANGE], "0-1535");
+
+        // A status request reports the same range as the last chunk response
+        let status = upload_headers("team/app", uuid, 1536);
+        assert_eq!(status[header::RANGE], "0-1535");
+        assert!(!status[header::RANGE].to_str().unwrap().starts_with("bytes"));
+    }
+
+    #[test]
+    fn test_progress_throttle() {
+        let throttle = ProgressThrottle::default();
+        let upload = Uuid::new_v4();
+        let other = Uuid::new_v4();
+        let start = Instant::now();
+
+        assert!(throttle.admit(upload, false, start));
+        assert!(!throttle.admit(upload, false, start + Duration::from_millis(40)));
+        // Uploads are throttled independently
+        assert!(throttle.admit(other, false, start + Duration::from_millis(40)));
+        assert!(throttle.admit(upload, false, start + PROGRESS_INTERVAL));
+        // The final event is never dropped
+        assert!(throttle.admit(upload, true, start + Duration::from_millis(110)));
+
+        let mut headers = HeaderMap::new();
+        assert_eq!(declared_length(&headers).unwrap(), None);
+        headers.insert(registry_headers::UPLOAD_LENGTH, HeaderValue::from_static("1048576"));
+        assert_eq!(declared_length(&headers).unwrap(), Some(1048576));
+        headers.insert(registry_headers::UPLOAD_LENGTH, HeaderValue::from_static("-1"));
+        assert!(declared_length(&headers).is_err());
+    }
+}
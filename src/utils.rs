@@ -2,18 +2,50 @@ use crate::error::{Error, Result};
 use regex::Regex;
 use sha2::{Sha256, Digest};
 
+/// Longest repository name, including namespaces
+const MAX_REPOSITORY_NAME_LENGTH: usize = 255;
+
+/// Longest single `/`-separated component of a repository name
+const MAX_NAME_COMPONENT_LENGTH: usize = 128;
+
 /// Validate repository name according to Docker registry specification
+///
+/// Each failure names the specific problem, since a bad name is often the first error
+/// a new user sees (`docker push MyApp/image`).
 pub fn validate_repository_name(name: &str) -> Result<()> {
     if name.is_empty() {
         return Err(Error::bad_request("Repository name cannot be empty"));
     }
     
-    if name.len() > 255 {
-        return Err(Error::bad_request("Repository name too long"));
+    if name.len() > MAX_REPOSITORY_NAME_LENGTH {
+        return Err(Error::bad_request(format!(
+            "Repository name is {} characters long; the limit is {}",
+            name.len(),
+            MAX_REPOSITORY_NAME_LENGTH
+        )));
+    }
+
+    if name.chars().any(|c| c.is_ascii_uppercase()) {
+        return Err(Error::bad_request(format!(
+            "Invalid repository name '{}': uppercase letters are not allowed; use '{}'",
+            name,
+            name.to_ascii_lowercase()
+        )));
+    }
+
+    if let Some(c) = name.chars().find(|c| !matches!(c, 'a'..='z' | '0'..='9' | '.' | '_' | '-' | '/')) {
+        return Err(Error::bad_request(format!(
+            "Invalid repository name '{}': '{}' is not allowed; use lowercase letters, numbers, '.', '_', '-', and '/'",
+            name, c
+        )));
+    }
+
+    for component in name.split('/') {
+        validate_name_component(name, component)?;
     }
     
-    // Repository name regex: lowercase letters, numbers, and separators
-    let repo_regex = Regex::new(r"^[a-z0-9]+(?:[._-][a-z0-9]+)*(?:/[a-z0-9]+(?:[._-][a-z0-9]+)*)*$")
+    // Repository name regex: lowercase alphanumeric components joined by separators
+    let repo_regex = Regex::new(r"^[a-z0-9]+(?:(?:\.|_|__|-+)[a-z0-9]+)*(?:/[a-z0-9]+(?:(?:\.|_|__|-+)[a-z0-9]+)*)*$")
         .map_err(|_| Error::internal("Invalid regex"))?;
     
     if !repo_regex.is_match(name) {
@@ -26,6 +58,46 @@ pub fn validate_repository_name(name: &str) -> Result<()> {
     Ok(())
 }
 
+/// Check one `/`-separated component: non-empty, within the length limit, and with valid separators
+fn validate_name_component(name: &str, component: &str) -> Result<()> {
+    if component.is_empty() {
+        return Err(Error::bad_request(format!(
+            "Invalid repository name '{}': it may not start or end with '/' or contain '//'",
+            name
+        )));
+    }
+
+    if component.len() > MAX_NAME_COMPONENT_LENGTH {
+        return Err(Error::bad_request(format!(
+            "Invalid repository name '{}': component '{}' is {} characters long; the limit is {}",
+            name,
+            component,
+            component.len(),
+            MAX_NAME_COMPONENT_LENGTH
+        )));
+    }
+
+    let is_separator = |c: char| matches!(c, '.' | '_' | '-');
+    if component.starts_with(is_separator) || component.ends_with(is_separator) {
+        return Err(Error::bad_request(format!(
+            "Invalid repository name '{}': component '{}' must start and end with a letter or number",
+            name, component
+        )));
+    }
+
+    // The spec allows '.', '_', '__', or any run of '-' between alphanumerics
+    for run in component.split(|c: char| c.is_ascii_alphanumeric()).filter(|run| !run.is_empty()) {
+        if !matches!(run, "." | "_" | "__") && !run.chars().all(|c| c == '-') {
+            return Err(Error::bad_request(format!(
+                "Invalid repository name '{}': '{}' is not a valid separator; use a single '.', '_', or '-'",
+                name, run
+            )));
+        }
+    }
+
+    Ok(())
+}
+
 /// Validate tag name according to Docker registry specification
 pub fn validate_tag_name(tag: &str) -> Result<()> {
     if tag.is_empty() {
@@ -70,6 +142,22 @@ pub fn validate_digest(digest: &str) -> Result<()> {
     Ok(())
 }
 
+/// Validate an `os/architecture[/variant]` platform, e.g. `linux/arm64/v8`
+pub fn validate_platform(platform: &str) -> Result<()> {
+    let components: Vec<&str> = platform.split('/').collect();
+    let valid_component = |c: &&str| {
+        !c.is_empty() && c.chars().all(|ch| ch.is_ascii_lowercase() || ch.is_ascii_digit() || matches!(ch, '_' | '.' | '-'))
+    };
+
+    if !(2..=3).contains(&components.len()) || !components.iter().all(valid_component) {
+        return Err(Error::bad_request(format!(
+            "Invalid platform '{}': expected os/architecture[/variant] in lowercase, e.g. linux/arm64",
+            platform
+        )));
+    }
+    Ok(())
+}
+
 /// Calculate SHA256 digest of data
 pub fn sha256_digest(data: &[u8]) -> String {
     let mut hasher = Sha256::new();
@@ -78,6 +166,23 @@ pub fn sha256_digest(data: &[u8]) -> String {
     format!("sha256:{:x}", result)
 }
 
+/// Fail unless `data` hashes to `expected`
+pub fn verify_digest(expected: &str, data: &[u8]) -> Result<()> {
+    let calculated = sha256_digest(data);
+    if calculated != expected {
+        return Err(Error::bad_request(format!(
+            "Digest mismatch: expected {}, got {}",
+            expected, calculated
+        )));
+    }
+    Ok(())
+}
+
+/// Compare secrets without returning early at the first differing byte
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
 /// Generate a random UUID string
 pub fn generate_uuid() -> String {
     uuid::Uuid::new_v4().to_string()
@@ -129,11 +234,91 @@ pub fn extract_media_type(manifest_content: &str) -> Result<String> {
         .to_string())
 }
 
+/// Child manifest digests referenced by a manifest list or OCI index
+pub fn manifest_list_children(media_type: &str, content: &[u8]) -> Vec<String> {
+    use crate::types::media_types;
+
+    if media_type != media_types::DOCKER_MANIFEST_LIST && media_type != media_types::OCI_INDEX {
+        return vec![];
+    }
+
+    serde_json::from_slice::<serde_json::Value>(content)
+        .ok()
+        .and_then(|v| v.get("manifests").and_then(|m| m.as_array()).cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| entry.get("digest").and_then(|d| d.as_str()).map(str::to_string))
+        .collect()
+}
+
+/// Config and layer blob digests referenced by an image manifest
+pub fn manifest_blob_digests(content: &[u8]) -> Vec<String> {
+    let Ok(manifest) = serde_json::from_slice::<serde_json::Value>(content) else {
+        return vec![];
+    };
+
+    let config = manifest.get("config").into_iter();
+    let layers = manifest
+        .get("layers")
+        .and_then(|l| l.as_array())
+        .into_iter()
+        .flatten();
+
+    config
+        .chain(layers)
+        .filter_map(|entry| entry.get("digest").and_then(|d| d.as_str()).map(str::to_string))
+        .collect()
+}
+
+/// `*` matches any run of characters, `/` included; everything else is literal
+pub fn glob_matches(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard at all
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+/// Digest of the manifest an OCI manifest or index is attached to through `subject`
+pub fn manifest_subject_digest(content: &[u8]) -> Option<String> {
+    serde_json::from_slice::<serde_json::Value>(content)
+        .ok()?
+        .pointer("/subject/digest")?
+        .as_str()
+        .map(str::to_string)
+}
+
 /// Check if a string is a valid digest format
 pub fn is_digest(reference: &str) -> bool {
     reference.starts_with("sha256:") && reference.len() == 71
 }
 
+/// Hash a password with bcrypt at the default cost
+pub async fn hash_password(password: &str) -> crate::error::Result<String> {
+    let password = password.to_string();
+
+    let hash = tokio::task::spawn_blocking(move || {
+        bcrypt::hash(&password, bcrypt::DEFAULT_COST)
+    }).await
+    .map_err(|_| crate::error::Error::from(anyhow::anyhow!("Failed to spawn blocking task")))?
+    .map_err(|_| crate::error::Error::from(anyhow::anyhow!("Password hashing failed")))?;
+
+    Ok(hash)
+}
+
 /// Verify a password against a hash
 pub async fn verify_password(password: &str, hash: &str) -> crate::error::Result<bool> {
     let password = password.to_string();
@@ -147,3 +332,67 @@ pub async fn verify_password(password: &str, hash: &str) -> crate::error::Result
     
     Ok(result)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejection(name: &str) -> String {
+        validate_repository_name(name).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_valid_repository_names() {
+        for name in ["app", "team/app", "team/sub/app-2", "my.app", "my_app", "my__app", "my---app", &"a".repeat(128)] {
+            assert!(validate_repository_name(name).is_ok(), "{} should be valid", name);
+        }
+    }
+
+    #[test]
+    fn test_repository_name_rejections_explain_the_problem() {
+        assert!(rejection("").contains("cannot be empty"));
+        assert!(rejection("MyApp/Image").contains("uppercase letters are not allowed; use 'myapp/image'"));
+        assert!(rejection("team/app:latest").contains("':' is not allowed"));
+        assert!(rejection("/team/app").contains("may not start or end with '/'"));
+        assert!(rejection("team/app/").contains("may not start or end with '/'"));
+        assert!(rejection("team//app").contains("contain '//'"));
+        assert!(rejection("team/-app").contains("component '-app' must start and end with a letter or number"));
+        assert!(rejection("team/app.").contains("component 'app.' must start and end with a letter or number"));
+        assert!(rejection("my..app").contains("'..' is not a valid separator"));
+        assert!(rejection("my._app").contains("'._' is not a valid separator"));
+        assert!(rejection("my___app").contains("'___' is not a valid separator"));
+        assert!(rejection(&format!("team/{}", "a".repeat(129))).contains("is 129 characters long; the limit is 128"));
+
+        let too_long = vec!["a".repeat(100); 3].join("/");
+        assert!(rejection(&too_long).contains("Repository name is 302 characters long; the limit is 255"));
+    }
+
+    #[test]
+    fn test_validate_platform() {
+        assert!(validate_platform("linux/amd64").is_ok());
+        assert!(validate_platform("linux/arm64/v8").is_ok());
+        assert!(validate_platform("linux").is_err());
+        assert!(validate_platform("linux/arm64/v8/extra").is_err());
+        assert!(validate_platform("Linux/ARM64").is_err());
+        assert!(validate_platform("linux//v8").is_err());
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq(b"sha256:abc", b"sha256:abc"));
+        assert!(!constant_time_eq(b"sha256:abc", b"sha256:abd"));
+        assert!(!constant_time_eq(b"sha256:abc", b"sha256:ab"));
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("library/*", "library/nginx"));
+        assert!(glob_matches("library/*", "library/team/nginx"));
+        assert!(!glob_matches("library/*", "librarian/nginx"));
+        assert!(glob_matches("*", "anything/at/all"));
+        assert!(glob_matches("*/cache-*", "team/cache-redis"));
+        assert!(!glob_matches("*/cache-*", "team/redis"));
+        assert!(glob_matches("exact/name", "exact/name"));
+        assert!(!glob_matches("exact/name", "exact/name2"));
+    }
+}
@@ -0,0 +1,745 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    routing::{get, post, put},
+    Json, Router,
+};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use sqlx::{sqlite::SqliteRow, Row, SqlitePool};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::{
+    auth::middleware::AuthenticatedUser,
+    database::queries::{get_repository_by_name, record_audit},
+    error::{Error, Result},
+    server::AppState,
+    types::{Page, PageQuery},
+    utils::glob_matches,
+};
+
+/// Signed outgoing webhooks
+/// Every delivery carries `X-GhostDock-Signature: sha256=<hex HMAC-SHA256 of the raw body>`
+
+pub const SIGNATURE_HEADER: &str = "X-GhostDock-Signature";
+pub const EVENT_HEADER: &str = "X-GhostDock-Event";
+const SIGNATURE_PREFIX: &str = "sha256=";
+
+/// Response bodies are kept up to this many bytes in the delivery log
+const MAX_RECORDED_BODY: usize = 4096;
+
+/// Events a webhook can subscribe to
+pub const EVENTS: &[&str] = &["push", "delete"];
+
+/// Most tag patterns one webhook may filter on
+const MAX_TAG_PATTERNS: usize = 32;
+
+/// Outgoing deliveries give up after this long
+const DELIVERY_TIMEOUT: Duration = Duration::from_secs(10);
+
+type HmacSha256 = Hmac<Sha256>;
+
+fn mac(secret: &str, body: &[u8]) -> HmacSha256 {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(body);
+    mac
+}
+
+/// Signature header value for `body` signed with `secret`
+pub fn sign_payload(secret: &str, body: &[u8]) -> String {
+    format!("{}{}", SIGNATURE_PREFIX, hex::encode(mac(secret, body).finalize().into_bytes()))
+}
+
+/// Check a signature header against any of `secrets`, typically the current and previous
+/// secret so deliveries signed before a rotation still verify. Comparison is constant-time.
+pub fn verify_signature(body: &[u8], signature: &str, secrets: &[&str]) -> bool {
+    let Some(expected) = signature
+        .trim()
+        .strip_prefix(SIGNATURE_PREFIX)
+        .and_then(|sig| hex::decode(sig).ok())
+    else {
+        return false;
+    };
+
+    secrets.iter().any(|secret| mac(secret, body).verify_slice(&expected).is_ok())
+}
+
+fn generate_secret() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    format!("whsec_{}", hex::encode(bytes))
+}
+
+/// Why a delivery failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryErrorKind {
+    /// No response within the client timeout
+    Timeout,
+    /// DNS, TCP or TLS failure before a response
+    Connect,
+    /// Too many or disallowed redirects
+    Redirect,
+    /// Any other failure sending the request or reading the response
+    Request,
+    /// The endpoint answered with a non-2xx status
+    HttpStatus,
+}
+
+impl DeliveryErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeliveryErrorKind::Timeout => "timeout",
+            DeliveryErrorKind::Connect => "connect",
+            DeliveryErrorKind::Redirect => "redirect",
+            DeliveryErrorKind::Request => "request",
+            DeliveryErrorKind::HttpStatus => "http_status",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "timeout" => Some(DeliveryErrorKind::Timeout),
+            "connect" => Some(DeliveryErrorKind::Connect),
+            "redirect" => Some(DeliveryErrorKind::Redirect),
+            "request" => Some(DeliveryErrorKind::Request),
+            "http_status" => Some(DeliveryErrorKind::HttpStatus),
+            _ => None,
+        }
+    }
+
+    fn classify(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            DeliveryErrorKind::Timeout
+        } else if error.is_connect() {
+            DeliveryErrorKind::Connect
+        } else if error.is_redirect() {
+            DeliveryErrorKind::Redirect
+        } else {
+            DeliveryErrorKind::Request
+        }
+    }
+}
+
+/// One recorded delivery attempt: what was sent and what came back
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event_type: String,
+    pub url: String,
+    pub attempt: u32,
+    pub success: bool,
+    pub response_status: Option<u16>,
+    pub response_body: Option<String>,
+    pub latency_ms: u64,
+    pub error_kind: Option<DeliveryErrorKind>,
+    pub error_message: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Where a delivery goes and how it is signed
+pub struct WebhookTarget<'a> {
+    pub id: Uuid,
+    pub url: &'a str,
+    pub secret: Option<&'a str>,
+}
+
+/// POST an event to a webhook, signed with its current secret when it has one.
+/// Every attempt is recorded in the delivery log, including ones that never got a response.
+pub async fn deliver(
+    pool: &SqlitePool,
+    client: &reqwest::Client,
+    webhook: &WebhookTarget<'_>,
+    event: &str,
+    payload: &serde_json::Value,
+    attempt: u32,
+) -> Result<WebhookDelivery> {
+    let body = serde_json::to_vec(payload)?;
+
+    let mut request = client
+        .post(webhook.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .header(EVENT_HEADER, event);
+    if let Some(secret) = webhook.secret {
+        request = request.header(SIGNATURE_HEADER, sign_payload(secret, &body));
+    }
+
+    let started = Instant::now();
+    let outcome = match request.body(body).send().await {
+        Ok(response) => {
+            let status = response.status();
+            // A body that can't be read doesn't change the outcome the status reported
+            let text = response.text().await.unwrap_or_default();
+            Ok((status, truncate_body(text)))
+        }
+        Err(e) => Err(e),
+    };
+
+    let mut delivery = WebhookDelivery {
+        id: Uuid::new_v4(),
+        webhook_id: webhook.id,
+        event_type: event.to_string(),
+        url: webhook.url.to_string(),
+        attempt,
+        success: false,
+        response_status: None,
+        response_body: None,
+        latency_ms: started.elapsed().as_millis() as u64,
+        error_kind: None,
+        error_message: None,
+        created_at: Utc::now(),
+    };
+    match outcome {
+        Ok((status, text)) => {
+            delivery.success = status.is_success();
+            delivery.response_status = Some(status.as_u16());
+            delivery.response_body = Some(text);
+            if !status.is_success() {
+                delivery.error_kind = Some(DeliveryErrorKind::HttpStatus);
+                delivery.error_message = Some(format!("Endpoint responded with {}", status));
+            }
+        }
+        Err(e) => {
+            delivery.error_kind = Some(DeliveryErrorKind::classify(&e));
+            delivery.error_message = Some(e.to_string());
+        }
+    }
+
+    record_delivery(pool, &delivery, payload).await?;
+    Ok(delivery)
+}
+
+async fn record_delivery(pool: &SqlitePool, delivery: &WebhookDelivery, payload: &serde_json::Value) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO webhook_deliveries (
+            id, webhook_id, event_type, url, attempt, payload, success, response_status,
+            response_body, latency_ms, error_kind, error_message, created_at
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        "#
+    )
+    .bind(delivery.id)
+    .bind(delivery.webhook_id)
+    .bind(&delivery.event_type)
+    .bind(&delivery.url)
+    .bind(delivery.attempt as i64)
+    .bind(payload.to_string())
+    .bind(delivery.success)
+    .bind(delivery.response_status.map(i64::from))
+    .bind(&delivery.response_body)
+    .bind(delivery.latency_ms as i64)
+    .bind(delivery.error_kind.map(|k| k.as_str()))
+    .bind(&delivery.error_message)
+    .bind(delivery.created_at)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Cut a response body to `MAX_RECORDED_BODY` bytes on a character boundary
+fn truncate_body(mut body: String) -> String {
+    if body.len() > MAX_RECORDED_BODY {
+        let mut end = MAX_RECORDED_BODY;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+    body
+}
+
+fn delivery_from_row(row: &SqliteRow) -> WebhookDelivery {
+    let error_kind: Option<String> = row.get("error_kind");
+    WebhookDelivery {
+        id: row.get("id"),
+        webhook_id: row.get("webhook_id"),
+        event_type: row.get("event_type"),
+        url: row.get("url"),
+        attempt: row.get::<i64, _>("attempt") as u32,
+        success: row.get("success"),
+        response_status: row.get::<Option<i64>, _>("response_status").map(|s| s as u16),
+        response_body: row.get("response_body"),
+        latency_ms: row.get::<i64, _>("latency_ms") as u64,
+        error_kind: error_kind.as_deref().and_then(DeliveryErrorKind::parse),
+        error_message: row.get("error_message"),
+        created_at: row.get("created_at"),
+    }
+}
+
+/// Which events a webhook fires for. Tag patterns narrow tag events to matching tags
+/// (`prod-*`, `v*`); events without a tag, such as a delete by digest, only reach webhooks without patterns.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WebhookFilter {
+    pub events: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tag_patterns: Option<Vec<String>>,
+}
+
+impl WebhookFilter {
+    fn from_row(row: &SqliteRow) -> Self {
+        let events: String = row.get("events");
+        let tag_patterns: Option<String> = row.get("tag_patterns");
+        Self {
+            events: serde_json::from_str(&events).unwrap_or_default(),
+            tag_patterns: tag_patterns.and_then(|p| serde_json::from_str(&p).ok()),
+        }
+    }
+
+    pub fn matches(&self, event: &str, tag: Option<&str>) -> bool {
+        if !self.events.iter().any(|e| e == event) {
+            return false;
+        }
+        match (&self.tag_patterns, tag) {
+            (None, _) => true,
+            (Some(patterns), Some(tag)) => patterns.iter().any(|pattern| glob_matches(pattern, tag)),
+            (Some(_), None) => false,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.events.is_empty() {
+            return Err(Error::validation("A webhook needs at least one event"));
+        }
+        if let Some(unknown) = self.events.iter().find(|e| !EVENTS.contains(&e.as_str())) {
+            return Err(Error::validation(format!(
+                "Unknown event '{}' (expected one of: {})",
+                unknown,
+                EVENTS.join(", ")
+            )));
+        }
+        if let Some(patterns) = &self.tag_patterns {
+            if patterns.is_empty() || patterns.len() > MAX_TAG_PATTERNS {
+                return Err(Error::validation(format!(
+                    "tag_patterns must list between 1 and {} patterns; omit it to match every tag",
+                    MAX_TAG_PATTERNS
+                )));
+            }
+            for pattern in patterns {
+                let valid = !pattern.is_empty()
+                    && pattern.len() <= 128
+                    && pattern.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-' | '*'));
+                if !valid {
+                    return Err(Error::validation(format!(
+                        "Invalid tag pattern '{}': use tag characters and '*' wildcards",
+                        pattern
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A tag or manifest change in a repository, as delivered to webhooks
+#[derive(Debug, Clone, Serialize)]
+pub struct RepositoryEvent {
+    pub event: &'static str,
+    pub repository: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag: Option<String>,
+    pub digest: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub actor: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+fn delivery_client() -> &'static reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT.get_or_init(|| {
+        reqwest::Client::builder()
+            .timeout(DELIVERY_TIMEOUT)
+            .build()
+            .expect("default TLS backend is available")
+    })
+}
+
+/// Deliver an event to every active webhook on the repository (or registry-wide) whose
+/// filter matches. Deliveries run in the background so a slow endpoint never holds up a push.
+pub async fn dispatch(state: &AppState, repository_id: &Uuid, event: RepositoryEvent) -> Result<()> {
+    let rows = sqlx::query(
+        r#"
+        SELECT id, url, secret, events, tag_patterns FROM webhooks
+        WHERE is_active = TRUE AND (repository_id IS NULL OR repository_id = $1)
+        "#
+    )
+    .bind(repository_id)
+    .fetch_all(&state.database.pool)
+    .await?;
+
+    let targets: Vec<(Uuid, String, Option<String>)> = rows
+        .iter()
+        .filter(|row| WebhookFilter::from_row(row).matches(event.event, event.tag.as_deref()))
+        .map(|row| (row.get("id"), row.get("url"), row.get("secret")))
+        .collect();
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    let payload = serde_json::to_value(&event)?;
+    let pool = state.database.pool.clone();
+    tokio::spawn(async move {
+        for (id, url, secret) in targets {
+            let target = WebhookTarget { id, url: &url, secret: secret.as_deref() };
+            if let Err(e) = deliver(&pool, delivery_client(), &target, event.event, &payload, 1).await {
+                tracing::warn!("Failed to record {} delivery for webhook {}: {}", event.event, id, e);
+            }
+        }
+    });
+
+    Ok(())
+}
+
+/// A webhook as returned by the API; the secret only appears in the create response
+#[derive(Debug, Serialize)]
+pub struct WebhookResponse {
+    pub id: Uuid,
+    pub url: String,
+    pub repository: Option<String>,
+    #[serde(flatten)]
+    pub filter: WebhookFilter,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateWebhookRequest {
+    pub url: String,
+    /// Repository the webhook watches; omitted for a registry-wide webhook (admins only)
+    pub repository: Option<String>,
+    #[serde(flatten)]
+    pub filter: WebhookFilter,
+}
+
+/// Filters for the delivery log
+#[derive(Debug, Deserialize)]
+pub struct DeliveryQuery {
+    pub success: Option<bool>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+/// A page of the delivery log with the webhook's failure rate over the same time range
+#[derive(Debug, Serialize)]
+pub struct DeliveryLog {
+    pub webhook_id: Uuid,
+    /// Attempts in the time range, whatever their outcome
+    pub attempts: i64,
+    pub failed: i64,
+    /// Share of attempts in the time range that failed, 0.0 to 1.0
+    pub failure_rate: f64,
+    #[serde(flatten)]
+    pub page: Page<WebhookDelivery>,
+}
+
+fn failure_rate(total: i64, failed: i64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        failed as f64 / total as f64
+    }
+}
+
+/// Response to a secret rotation; the new secret is only shown once
+#[derive(Debug, Serialize)]
+pub struct RotateSecretResponse {
+    pub id: Uuid,
+    pub secret: String,
+    /// Whether the replaced secret is still accepted as the previous one
+    pub previous_secret_valid: bool,
+    pub rotated_at: DateTime<Utc>,
+}
+
+/// Webhook routes
+pub fn webhook_routes() -> Router<AppState> {
+    Router::new()
+        .route("/api/webhooks", post(create_webhook))
+        .route("/api/webhooks/:id/filter", put(update_filter))
+        .route("/api/webhooks/:id/rotate-secret", post(rotate_secret))
+        .route("/api/webhooks/:id/deliveries", get(list_deliveries))
+}
+
+/// Ensure the caller is an admin or the webhook's creator; returns the webhook row
+async fn authorize_webhook(state: &AppState, id: Uuid, user: &AuthenticatedUser, action: &str) -> Result<SqliteRow> {
+    if user.is_robot() {
+        return Err(Error::authorization("Robot accounts cannot manage webhooks"));
+    }
+
+    let row = sqlx::query("SELECT created_by, secret FROM webhooks WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&state.database.pool)
+        .await?
+        .ok_or_else(|| Error::not_found(format!("Webhook {} not found", id)))?;
+
+    let created_by: String = row.get("created_by");
+    if created_by != user.id && !user.scopes.iter().any(|s| s == "admin") {
+        return Err(Error::authorization(format!("Only the webhook's creator or an admin can {}", action)));
+    }
+    Ok(row)
+}
+
+/// Create a signed webhook. Repository webhooks need the repository's owner or an admin;
+/// registry-wide ones need an admin. The generated secret is returned only here.
+async fn create_webhook(
+    State(state): State<AppState>,
+    user: AuthenticatedUser,
+    Json(request): Json<CreateWebhookRequest>,
+) -> Result<(StatusCode, Json<WebhookResponse>)> {
+    if user.is_robot() || user.is_share() {
+        return Err(Error::authorization("Only user accounts can manage webhooks"));
+    }
+    match url::Url::parse(&request.url) {
+        Ok(url) if url.scheme() == "https" || url.scheme() == "http" => {}
+        _ => return Err(Error::validation(format!("'{}' is not an http(s) URL", request.url))),
+    }
+    request.filter.validate()?;
+
+    let is_admin = user.scopes.iter().any(|s| s == "admin");
+    let repository_id = match &request.repository {
+        Some(name) => {
+            let repo = get_repository_by_name(&state, name).await?;
+            let owner_id: Option<String> = sqlx::query_scalar("SELECT owner_id FROM repositories WHERE id = $1")
+                .bind(repo.id)
+                .fetch_optional(&state.database.pool)
+                .await?
+                .flatten();
+            if !is_admin && owner_id.as_deref() != Some(user.id.as_str()) {
+                return Err(Error::authorization("Only the repository owner or an admin can add webhooks to it"));
+            }
+            Some(repo.id)
+        }
+        None if is_admin => None,
+        None => return Err(Error::authorization("Only admins can create registry-wide webhooks")),
+    };
+
+    let id = Uuid::new_v4();
+    let secret = generate_secret();
+    let now = Utc::now();
+    sqlx::query(
+        r#"
+        INSERT INTO webhooks (id, repository_id, url, secret, events, tag_patterns, created_by, created_at, updated_at)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $8)
+        "#
+    )
+    .bind(id)
+    .bind(repository_id)
+    .bind(&request.url)
+    .bind(&secret)
+    .bind(serde_json::to_string(&request.filter.events)?)
+    .bind(request.filter.tag_patterns.as_ref().map(serde_json::to_string).transpose()?)
+    .bind(&user.id)
+    .bind(now)
+    .execute(&state.database.pool)
+    .await?;
+
+    record_audit(&state, &user.id, "webhook.create", &id.to_string(), Some(serde_json::to_value(&request.filter)?)).await?;
+
+    Ok((
+        StatusCode::CREATED,
+        Json(WebhookResponse {
+            id,
+            url: request.url,
+            repository: request.repository,
+            filter: request.filter,
+            secret: Some(secret),
+            created_at: now,
+        }),
+    ))
+}
+
+/// Replace the events and tag patterns a webhook fires for (admin or the webhook's creator)
+async fn update_filter(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+    Json(filter): Json<WebhookFilter>,
+) -> Result<Json<WebhookFilter>> {
+    authorize_webhook(&state, id, &user, "change its filter").await?;
+    filter.validate()?;
+
+    sqlx::query("UPDATE webhooks SET events = $1, tag_patterns = $2, updated_at = $3 WHERE id = $4")
+        .bind(serde_json::to_string(&filter.events)?)
+        .bind(filter.tag_patterns.as_ref().map(serde_json::to_string).transpose()?)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&state.database.pool)
+        .await?;
+
+    record_audit(&state, &user.id, "webhook.update_filter", &id.to_string(), Some(serde_json::to_value(&filter)?)).await?;
+
+    Ok(Json(filter))
+}
+
+/// Delivery log for a webhook, newest first, filterable by outcome and time range
+async fn list_deliveries(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+    Query(query): Query<DeliveryQuery>,
+    Query(page): Query<PageQuery>,
+) -> Result<Json<DeliveryLog>> {
+    authorize_webhook(&state, id, &user, "view its deliveries").await?;
+    let (limit, offset) = page.bounds()?;
+
+    let deliveries = sqlx::query(
+        r#"
+        SELECT * FROM webhook_deliveries
+        WHERE webhook_id = $1
+          AND ($2 IS NULL OR success = $2)
+          AND ($3 IS NULL OR created_at >= $3)
+          AND ($4 IS NULL OR created_at < $4)
+        ORDER BY created_at DESC
+        LIMIT $5 OFFSET $6
+        "#
+    )
+    .bind(id)
+    .bind(query.success)
+    .bind(query.since)
+    .bind(query.until)
+    .bind(limit as i64)
+    .bind(offset as i64)
+    .fetch_all(&state.database.pool)
+    .await?
+    .iter()
+    .map(delivery_from_row)
+    .collect();
+
+    let counts = sqlx::query(
+        r#"
+        SELECT COUNT(*) AS attempts,
+               COALESCE(SUM(CASE WHEN success THEN 0 ELSE 1 END), 0) AS failed,
+               COALESCE(SUM(CASE WHEN $4 IS NULL OR success = $4 THEN 1 ELSE 0 END), 0) AS matching
+        FROM webhook_deliveries
+        WHERE webhook_id = $1
+          AND ($2 IS NULL OR created_at >= $2)
+          AND ($3 IS NULL OR created_at < $3)
+        "#
+    )
+    .bind(id)
+    .bind(query.since)
+    .bind(query.until)
+    .bind(query.success)
+    .fetch_one(&state.database.pool)
+    .await?;
+    let attempts: i64 = counts.get("attempts");
+    let failed: i64 = counts.get("failed");
+    let matching: i64 = counts.get("matching");
+
+    Ok(Json(DeliveryLog {
+        webhook_id: id,
+        attempts,
+        failed,
+        failure_rate: failure_rate(attempts, failed),
+        page: Page::new(deliveries, matching as u64, limit, offset),
+    }))
+}
+
+/// Replace a webhook's signing secret (admin or the webhook's creator).
+/// The old secret is kept as the previous one until the next rotation.
+async fn rotate_secret(
+    State(state): State<AppState>,
+    Path(id): Path<Uuid>,
+    user: AuthenticatedUser,
+) -> Result<Json<RotateSecretResponse>> {
+    let row = authorize_webhook(&state, id, &user, "rotate its secret").await?;
+    let previous: Option<String> = row.get("secret");
+
+    let secret = generate_secret();
+    let now = Utc::now();
+    sqlx::query(
+        "UPDATE webhooks SET previous_secret = secret, secret = $1, secret_rotated_at = $2, updated_at = $2 WHERE id = $3"
+    )
+    .bind(&secret)
+    .bind(now)
+    .bind(id)
+    .execute(&state.database.pool)
+    .await?;
+
+    record_audit(&state, &user.id, "webhook.rotate_secret", &id.to_string(), None).await?;
+
+    Ok(Json(RotateSecretResponse {
+        id,
+        secret,
+        previous_secret_valid: previous.is_some(),
+        rotated_at: now,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign_payload() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign_payload("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_verify_across_rotation() {
+        let body = br#"{"event":"push"}"#;
+        let old = generate_secret();
+        let new = generate_secret();
+        let signature = sign_payload(&old, body);
+
+        assert!(verify_signature(body, &signature, &[&new, &old]));
+        assert!(!verify_signature(body, &signature, &[&new]));
+        assert!(!verify_signature(b"tampered", &signature, &[&new, &old]));
+        assert!(!verify_signature(body, "md5=abcd", &[&old]));
+    }
+
+    #[test]
+    fn test_truncate_body() {
+        assert_eq!(truncate_body("ok".to_string()), "ok");
+
+        // Never split a multi-byte character
+        let body = format!("{}é", "a".repeat(MAX_RECORDED_BODY - 1));
+        let truncated = truncate_body(body);
+        assert_eq!(truncated.len(), MAX_RECORDED_BODY - 1);
+        assert!(truncated.chars().all(|c| c == 'a'));
+    }
+
+    #[test]
+    fn test_tag_pattern_filter() {
+        let filter = |events: &[&str], patterns: Option<&[&str]>| WebhookFilter {
+            events: events.iter().map(|e| e.to_string()).collect(),
+            tag_patterns: patterns.map(|p| p.iter().map(|t| t.to_string()).collect()),
+        };
+
+        let prod = filter(&["push"], Some(&["prod-*", "v*.*"]));
+        assert!(prod.validate().is_ok());
+        assert!(prod.matches("push", Some("prod-2024-05")));
+        assert!(prod.matches("push", Some("v1.2")));
+        assert!(!prod.matches("push", Some("staging-1")));
+        assert!(!prod.matches("delete", Some("prod-1")));
+        // A push by digest has no tag to match
+        assert!(!prod.matches("push", None));
+
+        let everything = filter(&["push", "delete"], None);
+        assert!(everything.matches("push", Some("latest")));
+        assert!(everything.matches("delete", None));
+
+        assert!(filter(&[], None).validate().is_err());
+        assert!(filter(&["pull"], None).validate().is_err());
+        assert!(filter(&["push"], Some(&[])).validate().is_err());
+        assert!(filter(&["push"], Some(&["prod/*"])).validate().is_err());
+    }
+
+    #[test]
+    fn test_failure_rate() {
+        assert_eq!(failure_rate(0, 0), 0.0);
+        assert_eq!(failure_rate(4, 1), 0.25);
+        assert_eq!(DeliveryErrorKind::parse(DeliveryErrorKind::HttpStatus.as_str()), Some(DeliveryErrorKind::HttpStatus));
+    }
+}
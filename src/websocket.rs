@@ -1,7 +1,7 @@
 use axum::{
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Path, State,
+        Path, Query, State,
     },
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -11,24 +11,75 @@ use axum::{
 use futures::{sink::SinkExt, stream::StreamExt};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    sync::Arc,
+    collections::{HashMap, VecDeque},
+    sync::{Arc, OnceLock},
+    time::Instant,
 };
 use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
 use crate::{
-    auth::{jwt::validate_token, middleware::AuthenticatedUser},
-    error::Result,
+    auth::{
+        jwt::{validate_token, JwtConfig},
+        middleware::AuthenticatedUser,
+    },
+    error::{Error, Result},
+    import::ImportProgress,
+    notifications::NotificationDispatcher,
+    uploads::{ProgressThrottle, UploadProgress},
 };
 
+/// Number of recent events kept per topic for replay after reconnects
+pub const REPLAY_BUFFER_SIZE: usize = 256;
+
+/// A broadcast message tagged with its global sequence number
+#[derive(Debug, Clone)]
+pub struct SequencedMessage {
+    pub seq: u64,
+    pub message: BroadcastMessage,
+}
+
+/// Bounded ring buffer of recent events for one topic
+#[derive(Debug, Default)]
+pub struct TopicBuffer {
+    events: VecDeque<SequencedMessage>,
+    /// Highest sequence number dropped from this buffer
+    evicted_through: u64,
+}
+
+/// Replay buffers and the sequence counter, updated together under one lock
+#[derive(Debug, Default)]
+pub struct ReplayLog {
+    next_seq: u64,
+    topics: HashMap<&'static str, TopicBuffer>,
+}
+
+/// Result of a resume request
+#[derive(Debug)]
+pub enum ReplayResult {
+    /// Missed events, in sequence order
+    Events(Vec<SequencedMessage>),
+    /// The gap exceeds the buffered history for these topics
+    ResyncRequired(Vec<String>),
+}
+
 /// WebSocket connection manager for real-time updates
 #[derive(Clone)]
 pub struct WebSocketState {
     /// Broadcast channel for sending updates to all connected clients
-    pub broadcaster: broadcast::Sender<BroadcastMessage>,
+    pub broadcaster: broadcast::Sender<SequencedMessage>,
+    /// Recent events per topic for replay on resume
+    pub replay_log: Arc<RwLock<ReplayLog>>,
     /// Active WebSocket connections
     pub connections: Arc<RwLock<HashMap<String, ConnectionInfo>>>,
+    /// Out-of-band notification delivery (email, Slack, Discord)
+    pub dispatcher: Arc<OnceLock<Arc<NotificationDispatcher>>>,
+    /// Signing keys used to validate client tokens
+    pub jwt: Arc<OnceLock<Arc<JwtConfig>>>,
+    /// Last progress event sent per upload
+    pub upload_throttle: Arc<ProgressThrottle>,
+    /// Most recent system metrics, sent to metrics subscribers as they connect
+    pub latest_metrics: Arc<RwLock<Option<SystemMetrics>>>,
 }
 
 /// Information about an active WebSocket connection
@@ -69,6 +120,29 @@ pub enum BroadcastMessage {
         deployment_id: String,
         logs: String,
     },
+    /// Remote import job progress (admins only)
+    ImportProgress {
+        progress: ImportProgress,
+    },
+    /// Chunked blob upload progress, for uploads with a declared length
+    UploadProgress {
+        progress: UploadProgress,
+    },
+}
+
+impl BroadcastMessage {
+    /// Subscription topic this message is delivered on
+    pub fn topic(&self) -> &'static str {
+        match self {
+            BroadcastMessage::RegistryActivity { .. } => "registry_activity",
+            BroadcastMessage::StackDeployment { .. } => "stack_deployments",
+            BroadcastMessage::SystemMetrics { .. } => "system_metrics",
+            BroadcastMessage::Notification { .. } => "notifications",
+            BroadcastMessage::DeploymentLogs { .. } => "deployment_logs",
+            BroadcastMessage::ImportProgress { .. } => "import_progress",
+            BroadcastMessage::UploadProgress { .. } => "upload_progress",
+        }
+    }
 }
 
 /// Registry activity events
@@ -114,7 +188,9 @@ pub struct SystemMetrics {
     pub cpu_usage: f64,
     pub memory_usage: f64,
     pub disk_usage: f64,
+    /// Bytes received since the previous sample
     pub network_rx: u64,
+    /// Bytes sent since the previous sample
     pub network_tx: u64,
     pub active_connections: usize,
     pub registry_operations_per_minute: u64,
@@ -133,7 +209,7 @@ pub struct Notification {
 }
 
 /// Notification severity levels
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum NotificationSeverity {
     Info,
@@ -142,6 +218,36 @@ pub enum NotificationSeverity {
     Success,
 }
 
+impl NotificationSeverity {
+    /// Ordering used for severity thresholds (success and info rank lowest)
+    pub fn rank(&self) -> u8 {
+        match self {
+            NotificationSeverity::Success | NotificationSeverity::Info => 0,
+            NotificationSeverity::Warning => 1,
+            NotificationSeverity::Error => 2,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationSeverity::Info => "info",
+            NotificationSeverity::Warning => "warning",
+            NotificationSeverity::Error => "error",
+            NotificationSeverity::Success => "success",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "info" => Some(NotificationSeverity::Info),
+            "warning" => Some(NotificationSeverity::Warning),
+            "error" => Some(NotificationSeverity::Error),
+            "success" => Some(NotificationSeverity::Success),
+            _ => None,
+        }
+    }
+}
+
 /// WebSocket message from client
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
@@ -160,6 +266,10 @@ pub enum ClientMessage {
     Auth {
         token: String,
     },
+    /// Replay events missed since the given sequence number
+    Resume {
+        last_seq: u64,
+    },
 }
 
 /// WebSocket message to client
@@ -188,8 +298,13 @@ pub enum ServerMessage {
     },
     /// Broadcast message
     Broadcast {
+        seq: u64,
         message: BroadcastMessage,
     },
+    /// Missed events can't be replayed; the client must refetch full state
+    ResyncRequired {
+        topics: Vec<String>,
+    },
 }
 
 impl WebSocketState {
@@ -198,17 +313,107 @@ impl WebSocketState {
         let (tx, _rx) = broadcast::channel(1000);
         Self {
             broadcaster: tx,
+            replay_log: Arc::new(RwLock::new(ReplayLog::default())),
             connections: Arc::new(RwLock::new(HashMap::new())),
+            dispatcher: Arc::new(OnceLock::new()),
+            jwt: Arc::new(OnceLock::new()),
+            upload_throttle: Arc::new(ProgressThrottle::default()),
+            latest_metrics: Arc::new(RwLock::new(None)),
         }
     }
 
+    /// Attach the server's JWT configuration for authenticating clients
+    pub fn set_jwt_config(&self, jwt: Arc<JwtConfig>) {
+        let _ = self.jwt.set(jwt);
+    }
+
+    /// Validate a client token, from the upgrade query or an `Auth` message
+    async fn authenticate(&self, token: &str) -> Result<AuthenticatedUser> {
+        let claims = match self.jwt.get() {
+            Some(jwt) => validate_token(token, jwt).await?,
+            // Only reached when no server is attached (e.g. standalone tests)
+            None => {
+                let jwt = JwtConfig::new(std::env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string()));
+                validate_token(token, &jwt).await?
+            }
+        };
+
+        Ok(AuthenticatedUser {
+            id: claims.sub,
+            name: claims.name,
+            email: claims.email,
+            scopes: claims.scope,
+        })
+    }
+
+    /// Record an authenticated connection so it shows up in connection listings
+    async fn register_connection(&self, connection_id: &str, user: &AuthenticatedUser, subscriptions: &[String]) {
+        let connection_info = ConnectionInfo {
+            user_id: user.id.clone(),
+            user_email: user.email.clone(),
+            connected_at: chrono::Utc::now(),
+            subscriptions: subscriptions.to_vec(),
+        };
+
+        self.connections.write().await.insert(connection_id.to_string(), connection_info);
+    }
+
+    /// Attach a notification dispatcher for offline delivery
+    pub fn set_dispatcher(&self, dispatcher: Arc<NotificationDispatcher>) {
+        let _ = self.dispatcher.set(dispatcher);
+    }
+
     /// Broadcast a message to all connected clients
     pub async fn broadcast(&self, message: BroadcastMessage) {
-        if let Err(e) = self.broadcaster.send(message) {
+        // Hold the log lock while sending so sequence order matches delivery order
+        let mut log = self.replay_log.write().await;
+        log.next_seq += 1;
+        let sequenced = SequencedMessage {
+            seq: log.next_seq,
+            message,
+        };
+
+        let buffer = log.topics.entry(sequenced.message.topic()).or_default();
+        if buffer.events.len() >= REPLAY_BUFFER_SIZE {
+            if let Some(evicted) = buffer.events.pop_front() {
+                buffer.evicted_through = evicted.seq;
+            }
+        }
+        buffer.events.push_back(sequenced.clone());
+
+        if let Err(e) = self.broadcaster.send(sequenced) {
             eprintln!("Failed to broadcast message: {}", e);
         }
     }
 
+    /// Collect buffered events after `last_seq` for the given topics
+    pub async fn replay_since(&self, last_seq: u64, topics: &[String]) -> ReplayResult {
+        let log = self.replay_log.read().await;
+        let mut events = Vec::new();
+        let mut overflowed = Vec::new();
+
+        for topic in topics {
+            // Keyed subscriptions such as `upload_progress:<uuid>` replay from their topic's buffer
+            let name = topic.split_once(':').map_or(topic.as_str(), |(name, _)| name);
+            let Some(buffer) = log.topics.get(name) else {
+                continue;
+            };
+            if last_seq < buffer.evicted_through {
+                overflowed.push(topic.clone());
+                continue;
+            }
+            events.extend(buffer.events.iter().filter(|e| e.seq > last_seq).cloned());
+        }
+
+        if !overflowed.is_empty() {
+            return ReplayResult::ResyncRequired(overflowed);
+        }
+
+        events.sort_by_key(|e| e.seq);
+        events.dedup_by_key(|e| e.seq);
+        ReplayResult::Events(events)
+    }
+
     /// Get the number of active connections
     pub async fn connection_count(&self) -> usize {
         self.connections.read().await.len()
@@ -227,12 +432,33 @@ pub fn websocket_routes() -> Router<WebSocketState> {
         .route("/ws/metrics", get(metrics_websocket_handler))
 }
 
+/// Upgrade query parameters
+#[derive(Debug, Default, Deserialize)]
+pub struct WebSocketQuery {
+    /// Browsers can't set `Authorization` on an upgrade, so they may pass the JWT here instead
+    pub token: Option<String>,
+}
+
 /// Main WebSocket handler
+///
+/// A `?token=` is validated before upgrading, so an invalid token gets a 401
+/// instead of an open, unauthenticated socket.
 async fn websocket_handler(
     ws: WebSocketUpgrade,
     State(state): State<WebSocketState>,
-) -> Response {
-    ws.on_upgrade(move |socket| handle_websocket(socket, state))
+    Query(query): Query<WebSocketQuery>,
+) -> Result<Response> {
+    let user = match query.token.as_deref() {
+        Some(token) => Some(
+            state
+                .authenticate(token)
+                .await
+                .map_err(|_| Error::authentication("Invalid authentication token"))?,
+        ),
+        None => None,
+    };
+
+    Ok(ws.on_upgrade(move |socket| handle_websocket(socket, state, user)))
 }
 
 /// Metrics-specific WebSocket handler
@@ -244,24 +470,32 @@ async fn metrics_websocket_handler(
 }
 
 /// Handle a WebSocket connection
-async fn handle_websocket(socket: WebSocket, state: WebSocketState) {
+async fn handle_websocket(socket: WebSocket, state: WebSocketState, user: Option<AuthenticatedUser>) {
     let connection_id = Uuid::new_v4().to_string();
-    let mut authenticated_user: Option<AuthenticatedUser> = None;
+    let mut authenticated_user = user;
     let mut subscriptions: Vec<String> = Vec::new();
+    // Highest sequence number already sent to this client
+    let mut delivered_seq: u64 = 0;
     
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.broadcaster.subscribe();
     
+    if let Some(user) = &authenticated_user {
+        state.register_connection(&connection_id, user, &subscriptions).await;
+    }
+    
     // Send welcome message
     let welcome_msg = ServerMessage::Welcome {
         connection_id: connection_id.clone(),
-        user_id: "anonymous".to_string(),
+        user_id: authenticated_user.as_ref().map_or_else(|| "anonymous".to_string(), |user| user.id.clone()),
         available_topics: vec![
             "registry_activity".to_string(),
             "stack_deployments".to_string(),
             "system_metrics".to_string(),
             "notifications".to_string(),
             "deployment_logs".to_string(),
+            "import_progress".to_string(),
+            "upload_progress".to_string(),
         ],
     };
     
@@ -283,6 +517,7 @@ async fn handle_websocket(socket: WebSocket, state: WebSocketState) {
                                 client_msg,
                                 &mut authenticated_user,
                                 &mut subscriptions,
+                                &mut delivered_seq,
                                 &mut sender,
                                 &connection_id,
                                 &state,
@@ -309,10 +544,15 @@ async fn handle_websocket(socket: WebSocket, state: WebSocketState) {
             // Handle broadcast messages
             broadcast_msg = rx.recv() => {
                 match broadcast_msg {
-                    Ok(msg) => {
+                    Ok(SequencedMessage { seq, message }) => {
+                        // Skip events already delivered through a replay
+                        if seq <= delivered_seq {
+                            continue;
+                        }
                         // Check if user should receive this message based on subscriptions
-                        if should_receive_message(&msg, &subscriptions, &authenticated_user) {
-                            let server_msg = ServerMessage::Broadcast { message: msg };
+                        if should_receive_message(&message, &subscriptions, &authenticated_user) {
+                            delivered_seq = seq;
+                            let server_msg = ServerMessage::Broadcast { seq, message };
                             if let Ok(msg_text) = serde_json::to_string(&server_msg) {
                                 if sender.send(Message::Text(msg_text)).await.is_err() {
                                     break;
@@ -320,6 +560,15 @@ async fn handle_websocket(socket: WebSocket, state: WebSocketState) {
                             }
                         }
                     }
+                    Err(broadcast::error::RecvError::Lagged(_)) => {
+                        // This client fell behind the live channel; ask it to resync
+                        let resync = ServerMessage::ResyncRequired { topics: subscriptions.clone() };
+                        if let Ok(msg_text) = serde_json::to_string(&resync) {
+                            if sender.send(Message::Text(msg_text)).await.is_err() {
+                                break;
+                            }
+                        }
+                    }
                     Err(_) => {
                         // Broadcast channel closed
                         break;
@@ -338,28 +587,17 @@ async fn handle_metrics_websocket(socket: WebSocket, state: WebSocketState) {
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.broadcaster.subscribe();
     
-    // Send initial metrics
-    let initial_metrics = SystemMetrics {
-        timestamp: chrono::Utc::now(),
-        cpu_usage: 25.5,
-        memory_usage: 60.2,
-        disk_usage: 45.0,
-        network_rx: 1024 * 1024,
-        network_tx: 512 * 1024,
-        active_connections: state.connection_count().await,
-        registry_operations_per_minute: 150,
-        storage_size: 1024 * 1024 * 1024 * 5, // 5GB
-    };
-    
-    let welcome_msg = ServerMessage::Broadcast {
-        message: BroadcastMessage::SystemMetrics {
-            metrics: initial_metrics,
-        },
-    };
-    
-    if let Ok(msg_text) = serde_json::to_string(&welcome_msg) {
-        if sender.send(Message::Text(msg_text)).await.is_err() {
-            return;
+    // Send the last sample right away rather than waiting for the next broadcast
+    let latest = state.latest_metrics.read().await.clone();
+    if let Some(metrics) = latest {
+        let welcome_msg = ServerMessage::Broadcast {
+            seq: 0,
+            message: BroadcastMessage::SystemMetrics { metrics },
+        };
+        if let Ok(msg_text) = serde_json::to_string(&welcome_msg) {
+            if sender.send(Message::Text(msg_text)).await.is_err() {
+                return;
+            }
         }
     }
     
@@ -376,8 +614,9 @@ async fn handle_metrics_websocket(socket: WebSocket, state: WebSocketState) {
             
             broadcast_msg = rx.recv() => {
                 match broadcast_msg {
-                    Ok(BroadcastMessage::SystemMetrics { metrics }) => {
+                    Ok(SequencedMessage { seq, message: BroadcastMessage::SystemMetrics { metrics } }) => {
                         let server_msg = ServerMessage::Broadcast {
+                            seq,
                             message: BroadcastMessage::SystemMetrics { metrics },
                         };
                         if let Ok(msg_text) = serde_json::to_string(&server_msg) {
@@ -401,40 +640,17 @@ async fn handle_client_message(
     message: ClientMessage,
     authenticated_user: &mut Option<AuthenticatedUser>,
     subscriptions: &mut Vec<String>,
+    delivered_seq: &mut u64,
     sender: &mut futures::stream::SplitSink<WebSocket, Message>,
     connection_id: &str,
     state: &WebSocketState,
 ) -> Result<bool> {
     match message {
         ClientMessage::Auth { token } => {
-            // We need a JwtConfig instance - for now create a default one
-            let jwt_config = crate::auth::jwt::JwtConfig::new(
-                std::env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string())
-            );
-            
-            match validate_token(&token, &jwt_config) {
-                Ok(claims) => {
-                    *authenticated_user = Some(AuthenticatedUser {
-                        id: claims.sub,
-                        name: claims.name,
-                        email: claims.email,
-                        scopes: claims.scope,
-                    });
-                    
-                    let user = authenticated_user.as_ref().unwrap();
-                    
-                    // Store connection info
-                    let connection_info = ConnectionInfo {
-                        user_id: user.id.clone(),
-                        user_email: user.email.clone(),
-                        connected_at: chrono::Utc::now(),
-                        subscriptions: subscriptions.clone(),
-                    };
-                    
-                    state.connections.write().await.insert(
-                        connection_id.to_string(),
-                        connection_info,
-                    );
+            match state.authenticate(&token).await {
+                Ok(user) => {
+                    let user = authenticated_user.insert(user);
+                    state.register_connection(connection_id, user, subscriptions).await;
                     
                     let welcome_msg = ServerMessage::Welcome {
                         connection_id: connection_id.to_string(),
@@ -445,6 +661,8 @@ async fn handle_client_message(
                             "system_metrics".to_string(),
                             "notifications".to_string(),
                             "deployment_logs".to_string(),
+                            "import_progress".to_string(),
+                            "upload_progress".to_string(),
                         ],
                     };
                     
@@ -494,6 +712,29 @@ async fn handle_client_message(
             }
         }
         
+        ClientMessage::Resume { last_seq } => {
+            match state.replay_since(last_seq, subscriptions).await {
+                ReplayResult::Events(events) => {
+                    for SequencedMessage { seq, message } in events {
+                        if !should_receive_message(&message, subscriptions, authenticated_user) {
+                            continue;
+                        }
+                        *delivered_seq = (*delivered_seq).max(seq);
+                        let server_msg = ServerMessage::Broadcast { seq, message };
+                        if let Ok(msg_text) = serde_json::to_string(&server_msg) {
+                            sender.send(Message::Text(msg_text)).await?;
+                        }
+                    }
+                }
+                ReplayResult::ResyncRequired(topics) => {
+                    let response = ServerMessage::ResyncRequired { topics };
+                    if let Ok(msg_text) = serde_json::to_string(&response) {
+                        sender.send(Message::Text(msg_text)).await?;
+                    }
+                }
+            }
+        }
+        
         ClientMessage::Ping => {
             let pong_msg = ServerMessage::Pong;
             if let Ok(msg_text) = serde_json::to_string(&pong_msg) {
@@ -531,6 +772,20 @@ fn should_receive_message(
         BroadcastMessage::DeploymentLogs { .. } => {
             subscriptions.contains(&"deployment_logs".to_string())
         }
+        BroadcastMessage::ImportProgress { .. } => {
+            subscriptions.contains(&"import_progress".to_string())
+                && authenticated_user
+                    .as_ref()
+                    .is_some_and(|user| user.scopes.iter().any(|s| s == "admin"))
+        }
+        BroadcastMessage::UploadProgress { progress } => {
+            // `upload_progress` follows every upload; `upload_progress:<uuid>` just one
+            let keyed = format!("upload_progress:{}", progress.upload_id);
+            subscriptions.iter().any(|s| s == "upload_progress" || *s == keyed)
+                && authenticated_user
+                    .as_ref()
+                    .is_some_and(|user| user.can_access(&progress.repository, "pull"))
+        }
     }
 }
 
@@ -577,11 +832,23 @@ impl WebSocketState {
     
     /// Broadcast system metrics
     pub async fn broadcast_system_metrics(&self, metrics: SystemMetrics) {
+        *self.latest_metrics.write().await = Some(metrics.clone());
         self.broadcast(BroadcastMessage::SystemMetrics { metrics }).await;
     }
     
-    /// Broadcast user notification
+    /// Broadcast user notification and hand it to the configured sinks
     pub async fn broadcast_notification(&self, user_id: String, notification: Notification) {
+        if let Some(dispatcher) = self.dispatcher.get() {
+            let dispatcher = Arc::clone(dispatcher);
+            let user_id = user_id.clone();
+            let notification = notification.clone();
+            tokio::spawn(async move {
+                if let Err(e) = dispatcher.dispatch(&user_id, &notification).await {
+                    tracing::warn!("Notification dispatch failed for user {}: {}", user_id, e);
+                }
+            });
+        }
+
         self.broadcast(BroadcastMessage::Notification {
             user_id,
             notification,
@@ -601,6 +868,23 @@ impl WebSocketState {
             logs,
         }).await;
     }
+
+    /// Broadcast remote import progress
+    pub async fn broadcast_import_progress(&self, progress: ImportProgress) {
+        self.broadcast(BroadcastMessage::ImportProgress { progress }).await;
+    }
+
+    /// Broadcast chunked upload progress, at most once per `PROGRESS_INTERVAL` per upload
+    pub async fn broadcast_upload_progress(&self, progress: UploadProgress) {
+        if self.upload_throttle.admit(progress.upload_id, progress.is_complete(), Instant::now()) {
+            self.broadcast(BroadcastMessage::UploadProgress { progress }).await;
+        }
+    }
+
+    /// Forget throttle state for an upload that completed or was cancelled
+    pub fn upload_finished(&self, upload_id: &Uuid) {
+        self.upload_throttle.forget(upload_id);
+    }
 }
 
 #[cfg(test)]
@@ -676,4 +960,80 @@ mod tests {
         };
         assert!(!should_receive_message(&metrics_msg, &subscriptions, &user));
     }
+
+    #[test]
+    fn test_upload_progress_subscriptions() {
+        let user = Some(AuthenticatedUser {
+            id: "user123".to_string(),
+            name: "user".to_string(),
+            email: "user@example.com".to_string(),
+            scopes: vec!["repository:team/app:pull".to_string()],
+        });
+        let upload_id = Uuid::new_v4();
+        let progress = |repository: &str| BroadcastMessage::UploadProgress {
+            progress: UploadProgress {
+                upload_id,
+                repository: repository.to_string(),
+                uploaded_size: 512,
+                total_size: 1024,
+            },
+        };
+
+        let keyed = vec![format!("upload_progress:{}", upload_id)];
+        assert!(should_receive_message(&progress("team/app"), &keyed, &user));
+        assert!(should_receive_message(&progress("team/app"), &["upload_progress".to_string()], &user));
+        assert!(!should_receive_message(&progress("team/app"), &[format!("upload_progress:{}", Uuid::new_v4())], &user));
+        // Progress is only visible to callers who can pull the repository
+        assert!(!should_receive_message(&progress("team/other"), &keyed, &user));
+        assert!(!should_receive_message(&progress("team/app"), &keyed, &None));
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_uses_attached_jwt_config() {
+        let state = WebSocketState::new();
+        let jwt = Arc::new(JwtConfig::new("ws-test-secret".to_string()));
+        state.set_jwt_config(Arc::clone(&jwt));
+
+        let token = crate::auth::jwt::generate_token("user123", "alice", "alice@example.com", vec![], &jwt).unwrap();
+        let user = state.authenticate(&token).await.unwrap();
+        assert_eq!(user.id, "user123");
+        assert_eq!(user.name, "alice");
+
+        let foreign = JwtConfig::new("some-other-secret".to_string());
+        let forged = crate::auth::jwt::generate_token("user123", "alice", "alice@example.com", vec![], &foreign).unwrap();
+        assert!(state.authenticate(&forged).await.is_err());
+        assert!(state.authenticate("not-a-jwt").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_resume_replays_missed_events() {
+        let state = WebSocketState::new();
+        let topics = vec!["stack_deployments".to_string()];
+
+        for i in 0..3 {
+            state.broadcast_stack_deployment(format!("stack-{}", i), DeploymentStatus::Running, "ok".to_string()).await;
+        }
+
+        match state.replay_since(1, &topics).await {
+            ReplayResult::Events(events) => {
+                assert_eq!(events.iter().map(|e| e.seq).collect::<Vec<_>>(), vec![2, 3]);
+            }
+            ReplayResult::ResyncRequired(_) => panic!("expected replay"),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resume_after_overflow_requires_resync() {
+        let state = WebSocketState::new();
+        let topics = vec!["stack_deployments".to_string()];
+
+        for i in 0..(REPLAY_BUFFER_SIZE + 5) {
+            state.broadcast_stack_deployment(format!("stack-{}", i), DeploymentStatus::Running, "ok".to_string()).await;
+        }
+
+        assert!(matches!(
+            state.replay_since(1, &topics).await,
+            ReplayResult::ResyncRequired(_)
+        ));
+    }
 }